@@ -49,16 +49,7 @@ impl Reputation {
     /// Update reputation based on interaction outcome
     /// Uses exponential moving average: R(T) = α·R(T-1) + β·C(T)
     pub fn update(&mut self, success: bool, alpha: f64, beta: f64) {
-        // Save snapshot before update
-        self.history.push(ReputationSnapshot {
-            score: self.score,
-            timestamp: self.last_updated,
-        });
-
-        // Trim history to last 100 entries
-        if self.history.len() > 100 {
-            self.history.remove(0);
-        }
+        self.record_snapshot();
 
         let contribution = if success {
             self.successful_interactions += 1;
@@ -72,6 +63,26 @@ impl Reputation {
         self.last_updated = Utc::now();
     }
 
+    /// Shift the score by `delta`, keeping it within 0.0 to 1.0
+    pub fn adjust(&mut self, delta: f64) {
+        self.record_snapshot();
+        self.score = (self.score + delta).clamp(0.0, 1.0);
+        self.last_updated = Utc::now();
+    }
+
+    /// Save the current score to history before it changes
+    fn record_snapshot(&mut self) {
+        self.history.push(ReputationSnapshot {
+            score: self.score,
+            timestamp: self.last_updated,
+        });
+
+        // Trim history to last 100 entries
+        if self.history.len() > 100 {
+            self.history.remove(0);
+        }
+    }
+
     /// Check if peer is trusted (above threshold)
     pub fn is_trusted(&self, threshold: f64) -> bool {
         self.score >= threshold
@@ -112,4 +123,18 @@ mod tests {
         rep.update(false, 0.4, 0.6);
         assert!(rep.score < rep.history.last().unwrap().score);
     }
+
+    #[test]
+    fn test_reputation_adjust_clamps() {
+        let mut rep = Reputation::default();
+        rep.adjust(0.2);
+        assert!((rep.score - 0.7).abs() < 1e-9);
+        assert_eq!(rep.history.last().unwrap().score, 0.5);
+
+        rep.adjust(1.0);
+        assert_eq!(rep.score, 1.0);
+        rep.adjust(-3.0);
+        assert_eq!(rep.score, 0.0);
+        assert_eq!(rep.history.len(), 3);
+    }
 }
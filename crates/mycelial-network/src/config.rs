@@ -24,6 +24,14 @@ pub struct NetworkConfig {
     pub enable_tcp: bool,
     /// Enable QUIC transport
     pub enable_quic: bool,
+    /// Namespace prefixed to the default topics, isolating this logical
+    /// network from others on the same transport (empty for none)
+    #[serde(default)]
+    pub topic_namespace: String,
+    /// Presence topic subscribed alongside the defaults, if presence notices
+    /// are exchanged
+    #[serde(default)]
+    pub presence_topic: Option<String>,
 }
 
 impl Default for NetworkConfig {
@@ -41,6 +49,8 @@ impl Default for NetworkConfig {
             idle_timeout_secs: 30,
             enable_tcp: true,
             enable_quic: true,
+            topic_namespace: String::new(),
+            presence_topic: None,
         }
     }
 }
@@ -58,6 +68,8 @@ impl NetworkConfig {
             idle_timeout_secs: 30,
             enable_tcp: true,
             enable_quic: false, // Simpler for testing
+            topic_namespace: String::new(),
+            presence_topic: None,
         }
     }
 
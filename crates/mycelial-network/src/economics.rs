@@ -7,7 +7,7 @@
 //! - Resource: Resource sharing metrics
 
 use mycelial_protocol::{
-    topics,
+    topics, ActionSignature,
     VouchMessage, CreditMessage, GovernanceMessage, ResourceMessage,
 };
 use tokio::sync::broadcast;
@@ -30,6 +30,18 @@ pub enum EconomicsEvent {
     Resource(ResourceMessage),
 }
 
+impl EconomicsEvent {
+    /// Signature of the client action behind the message, if signed
+    pub fn signature(&self) -> Option<&ActionSignature> {
+        match self {
+            EconomicsEvent::Vouch(msg) => msg.signature(),
+            EconomicsEvent::Credit(msg) => msg.signature(),
+            EconomicsEvent::Governance(msg) => msg.signature(),
+            EconomicsEvent::Resource(msg) => msg.signature(),
+        }
+    }
+}
+
 /// Handler for economics protocol messages
 pub struct EconomicsHandler {
     /// Network handle for publishing
@@ -71,6 +71,16 @@ pub enum NetworkEvent {
         timestamp: DateTime<Utc>,
     },
 
+    /// A local message was published to the gossip network
+    MessagePublished {
+        /// Message ID (a hash of the data)
+        message_id: MessageId,
+        /// Topic the message was published on
+        topic: String,
+        /// Number of mesh peers the message was sent to
+        peer_count: usize,
+    },
+
     /// Successfully subscribed to a topic
     Subscribed {
         /// The topic subscribed to
@@ -83,6 +93,16 @@ pub enum NetworkEvent {
         topic: String,
     },
 
+    /// A publish, subscribe or unsubscribe command was refused by gossipsub
+    OperationFailed {
+        /// `"publish"`, `"subscribe"` or `"unsubscribe"`
+        operation: &'static str,
+        /// Topic the command was for
+        topic: String,
+        /// Error message
+        error: String,
+    },
+
     /// A peer subscribed to a topic we're subscribed to
     PeerSubscribed {
         /// The peer's ID
@@ -70,8 +70,10 @@ pub use transport::{TransportConfig, create_transport, parse_multiaddr, extract_
 
 // Re-export libp2p types commonly used
 pub use libp2p::identity::Keypair;
+pub use libp2p::identity::PublicKey as Libp2pPublicKey;
 pub use libp2p::PeerId as Libp2pPeerId;
 pub use libp2p::Multiaddr;
+pub use libp2p::gossipsub::MessageId;
 
 #[cfg(test)]
 mod tests {
@@ -56,6 +56,14 @@ pub struct NetworkHandle {
 }
 
 impl NetworkHandle {
+    /// Create a handle from an existing command channel
+    ///
+    /// Handles are normally obtained from [`NetworkService::new`]; this allows
+    /// the command stream to be consumed directly, e.g. by tests.
+    pub fn new(command_tx: mpsc::Sender<NetworkCommand>, local_peer_id: PeerId) -> Self {
+        Self { command_tx, local_peer_id }
+    }
+
     /// Get the local peer ID
     pub fn local_peer_id(&self) -> PeerId {
         self.local_peer_id
@@ -253,19 +261,24 @@ impl NetworkService {
         // Note: mesh_n=2, mesh_n_low=1 configured for small test networks
         info!("Gossipsub config: mesh_outbound_min=0, mesh_n=2, mesh_n_low=1, mesh_n_high=4 (optimized for small networks)");
 
+        // Direct messages to this node arrive on its own inbox topic
+        let direct_inbox = mycelial_protocol::topics::direct(&self.swarm.local_peer_id().to_base58());
         let topics = [
             // Core messaging topics
             "/mycelial/1.0.0/chat",
             "/mycelial/1.0.0/announce",
             "/mycelial/1.0.0/reputation",
-            "/mycelial/1.0.0/direct",
+            direct_inbox.as_str(),
             // Economics protocol topics (Phase 7)
             "/mycelial/1.0.0/vouch",      // Vouch/reputation delegation
             "/mycelial/1.0.0/credit",     // Mutual credit transactions
             "/mycelial/1.0.0/governance", // Proposals and voting
             "/mycelial/1.0.0/resource",   // Resource sharing metrics
         ];
-        for topic_str in topics {
+        let presence = self.config.presence_topic.clone();
+        for topic_str in topics.into_iter().chain(presence.as_deref()) {
+            let topic_str = mycelial_protocol::topics::namespaced(&self.config.topic_namespace, topic_str);
+            let topic_str = topic_str.as_str();
             let topic = libp2p::gossipsub::IdentTopic::new(topic_str);
             match self.swarm.behaviour_mut().gossipsub.subscribe(&topic) {
                 Ok(true) => {
@@ -559,6 +572,16 @@ impl NetworkService {
         }
     }
 
+    /// Tell listeners that gossipsub refused a command, since the handle
+    /// only learns whether the command was queued
+    fn report_failure(&self, operation: &'static str, topic: String, error: &NetworkError) {
+        let _ = self.event_tx.send(NetworkEvent::OperationFailed {
+            operation,
+            topic,
+            error: error.to_string(),
+        });
+    }
+
     /// Handle a command, returns false if should shutdown
     async fn handle_command(&mut self, cmd: NetworkCommand) -> bool {
         match cmd {
@@ -577,6 +600,7 @@ impl NetworkService {
             NetworkCommand::Subscribe { topic } => {
                 if let Err(e) = self.swarm.behaviour_mut().subscribe(&topic) {
                     warn!("Failed to subscribe to {}: {:?}", topic, e);
+                    self.report_failure("subscribe", topic, &e);
                 } else {
                     self.subscribed_topics.insert(topic.clone());
                     let _ = self.event_tx.send(NetworkEvent::Subscribed { topic });
@@ -586,6 +610,7 @@ impl NetworkService {
             NetworkCommand::Unsubscribe { topic } => {
                 if let Err(e) = self.swarm.behaviour_mut().unsubscribe(&topic) {
                     warn!("Failed to unsubscribe from {}: {:?}", topic, e);
+                    self.report_failure("unsubscribe", topic, &e);
                 } else {
                     self.subscribed_topics.remove(&topic);
                     let _ = self.event_tx.send(NetworkEvent::Unsubscribed { topic });
@@ -617,15 +642,23 @@ impl NetworkService {
                 match self.swarm.behaviour_mut().publish(&topic, data.clone()) {
                     Ok(msg_id) => {
                         info!("Published message {} to '{}' via {} mesh peers", msg_id, topic, mesh_peers.len());
-                        let mut stats = self.stats.write();
-                        stats.messages_sent += 1;
-                        stats.bytes_sent += data.len() as u64;
+                        {
+                            let mut stats = self.stats.write();
+                            stats.messages_sent += 1;
+                            stats.bytes_sent += data.len() as u64;
+                        }
+                        let _ = self.event_tx.send(NetworkEvent::MessagePublished {
+                            message_id: msg_id,
+                            topic,
+                            peer_count: mesh_peers.len(),
+                        });
                     }
                     Err(e) => {
                         warn!(
                             "Failed to publish to '{}': {:?} | Mesh peers: {} | Consider waiting for mesh formation",
                             topic, e, mesh_peers.len()
                         );
+                        self.report_failure("publish", topic, &e);
                     }
                 }
             }
@@ -659,3 +692,33 @@ impl NetworkService {
         true
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_refused_publish_reported_as_event() {
+        let mut config = NetworkConfig::local_test(0);
+        config.enable_mdns = false;
+        let keypair = libp2p::identity::Keypair::generate_ed25519();
+        let (mut service, _handle, mut events) = NetworkService::new(keypair, config).unwrap();
+
+        // With no peers at all, gossipsub has nobody to publish to
+        let publish = NetworkCommand::Publish {
+            topic: "/mycelial/1.0.0/chat".to_string(),
+            data: b"hello".to_vec(),
+        };
+        assert!(service.handle_command(publish).await);
+
+        match events.try_recv() {
+            Ok(NetworkEvent::OperationFailed { operation, topic, error }) => {
+                assert_eq!(operation, "publish");
+                assert_eq!(topic, "/mycelial/1.0.0/chat");
+                assert!(error.contains("InsufficientPeers"), "unexpected error: {}", error);
+            }
+            other => panic!("expected operation failure, got {:?}", other),
+        }
+        assert_eq!(service.stats.read().messages_sent, 0);
+    }
+}
@@ -0,0 +1,441 @@
+//! Governance anti-entropy sync
+//!
+//! A node that was offline can miss proposals and votes that were only ever
+//! gossiped once. An operator can ask peers to share what they know: the
+//! node publishes a [`SyncRequest`] on the governance topic, peers answer with
+//! a [`SyncResponse`], and anything missing locally is reconciled into the
+//! store. Progress is reported to clients as [`WsMessage::SyncProgress`].
+//!
+//! Each peer's requests are answered at most once per `sync_serve_interval`,
+//! and answers are split into pages that each fit in one gossip message.
+//!
+//! A response is only trusted for what its responder can vouch for: its own
+//! proposals and votes, and those carrying a valid signature by their
+//! author. Proposal statuses are never taken from the responder; synced
+//! proposals start active and are closed here once their deadline passes.
+
+use chrono::{TimeZone, Utc};
+use parking_lot::Mutex;
+use std::collections::{HashMap, HashSet};
+use std::time::Instant;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use mycelial_protocol::{
+    topics, ActionSignature, CastVote, CreateProposal, GovernanceMessage, ProposalStatus, Signed, SyncRequest,
+    SyncResponse, Vote,
+};
+use mycelial_state::governance::received_quorum;
+use mycelial_state::{ProposalRecord, VoteRecord};
+
+use crate::execution;
+use crate::governance_params;
+use crate::network_errors;
+use crate::proposal_types;
+use crate::server::messages::WsMessage;
+use crate::server::governance::{eligible_voters, vote_label, voter_weight};
+use crate::AppState;
+
+/// Serialized size each sync response page is kept under, leaving headroom
+/// below the 1 MB gossip message limit
+const SYNC_PAGE_BYTES: usize = 768 * 1024;
+
+/// Sync requests this node is waiting on, by request ID
+#[derive(Default)]
+pub struct PendingSyncs {
+    requested: Mutex<HashMap<String, Instant>>,
+}
+
+impl PendingSyncs {
+    fn insert(&self, request_id: String) {
+        self.requested.lock().insert(request_id, Instant::now());
+    }
+
+    /// Whether responses to `request_id` are still accepted
+    fn accepts(&self, request_id: &str, window: std::time::Duration) -> bool {
+        let mut requested = self.requested.lock();
+        requested.retain(|_, sent| sent.elapsed() < window);
+        requested.contains_key(request_id)
+    }
+}
+
+/// When each peer's sync request was last answered, by peer ID
+#[derive(Default)]
+pub struct ServedSyncs {
+    served: Mutex<HashMap<String, Instant>>,
+}
+
+impl ServedSyncs {
+    /// Whether `peer` may be answered now, recording the answer if so
+    fn allow(&self, peer: &str, interval: std::time::Duration) -> bool {
+        let mut served = self.served.lock();
+        served.retain(|_, at| at.elapsed() < interval);
+        if served.contains_key(peer) {
+            return false;
+        }
+        served.insert(peer.to_string(), Instant::now());
+        true
+    }
+}
+
+/// Pages of a sync response being assembled
+struct Pages {
+    request_id: Uuid,
+    responder: String,
+    pages: Vec<SyncResponse>,
+    /// Serialized bytes on the last page so far
+    size: usize,
+}
+
+impl Pages {
+    fn new(request_id: Uuid, responder: String) -> Self {
+        let mut pages = Self { request_id, responder, pages: Vec::new(), size: 0 };
+        pages.start();
+        pages
+    }
+
+    fn start(&mut self) {
+        self.pages.push(SyncResponse {
+            request_id: self.request_id,
+            responder: self.responder.clone(),
+            page: self.pages.len() as u32,
+            proposals: Vec::new(),
+            statuses: HashMap::new(),
+            votes: Vec::new(),
+            timestamp: Utc::now(),
+        });
+        self.size = 0;
+    }
+
+    /// The page an item of `bytes` serialized bytes goes on, starting a new
+    /// one if it would overflow the last
+    fn page_for(&mut self, bytes: usize) -> &mut SyncResponse {
+        if self.size > 0 && self.size + bytes > SYNC_PAGE_BYTES {
+            self.start();
+        }
+        self.size += bytes;
+        self.pages.last_mut().expect("there is always a page")
+    }
+}
+
+/// Serialized size of an item within a page, with room for its separator
+fn encoded_len<T: serde::Serialize>(item: &T) -> usize {
+    serde_json::to_vec(item).map_or(0, |data| data.len() + 1)
+}
+
+/// Publish a sync request for governance state from the configured lookback
+///
+/// Returns the request ID, or why it couldn't be sent.
+pub async fn request_sync(state: &AppState) -> Result<String, String> {
+    let lookback = chrono::Duration::from_std(state.config.sync_lookback).unwrap_or_default();
+    let request = SyncRequest::new(state.local_peer_id.to_string(), Utc::now() - lookback);
+    let request_id = request.id.to_string();
+    let data = serde_json::to_vec(&GovernanceMessage::SyncRequest(request))
+        .map_err(|e| format!("Failed to serialize sync request: {}", e))?;
+
+    // Register first so a fast response isn't dropped
+    state.pending_syncs.insert(request_id.clone());
+    state
+        .network
+        .publish(state.wire_topic(topics::GOVERNANCE), data)
+        .await
+        .map_err(|e| {
+            network_errors::report(state, "publish", &e);
+            format!("Failed to publish sync request: {}", e)
+        })?;
+
+    info!("Requested governance sync {}", request_id);
+    let _ = state.event_tx.send(WsMessage::SyncProgress {
+        request_id: request_id.clone(),
+        responder: None,
+        proposals_added: 0,
+        votes_added: 0,
+    });
+    Ok(request_id)
+}
+
+/// Answer a peer's sync request with the proposals and votes known here
+///
+/// The requester is the authenticated gossip source, so it is what's rate
+/// limited.
+pub async fn serve_sync_request(state: &AppState, request: SyncRequest) {
+    if request.requester == state.local_peer_id.to_string() {
+        return;
+    }
+    if !state.served_syncs.allow(&request.requester, state.config.sync_serve_interval) {
+        info!("Not answering sync request from {} again so soon", request.requester);
+        return;
+    }
+    let since = request.since.timestamp_millis();
+    let records = match state.store.list_proposals().await {
+        Ok(records) => records,
+        Err(e) => {
+            warn!("Failed to load proposals for sync: {}", e);
+            return;
+        }
+    };
+
+    // A proposal goes on a page before its votes, so it is known by the
+    // time they are applied
+    let mut pages = Pages::new(request.id, state.local_peer_id.to_string());
+    for record in records
+        .into_iter()
+        .filter(|record| record.created_at >= since)
+        .take(state.config.sync_max_proposals)
+    {
+        let Some(proposal) = to_protocol_proposal(&record, &governance_params::current(state)) else {
+            continue;
+        };
+        let status = to_protocol_status(&record.status);
+        let votes = match state.store.list_votes(&record.id).await {
+            Ok(votes) => votes,
+            Err(e) => {
+                warn!("Failed to load votes for {}: {}", record.id, e);
+                Vec::new()
+            }
+        };
+        let page = pages.page_for(encoded_len(&proposal) + encoded_len(&(proposal.id, &status)));
+        page.statuses.insert(proposal.id, status);
+        let proposal_id = proposal.id;
+        page.proposals.push(proposal);
+        for vote in votes.iter().filter_map(|vote| to_protocol_vote(vote, proposal_id)) {
+            pages.page_for(encoded_len(&vote)).votes.push(vote);
+        }
+    }
+
+    for response in pages.pages {
+        let page = response.page;
+        let data = match serde_json::to_vec(&GovernanceMessage::SyncResponse(response)) {
+            Ok(data) => data,
+            Err(e) => {
+                warn!("Failed to serialize sync response: {}", e);
+                return;
+            }
+        };
+        if let Err(e) = state.network.publish(state.wire_topic(topics::GOVERNANCE), data).await {
+            warn!("Failed to publish sync response page {}: {}", page, e);
+            network_errors::report(state, "publish", &e);
+            return;
+        }
+    }
+}
+
+/// Whether a synced item claimed by `author` can be trusted from `responder`
+///
+/// The responder may speak for itself; anyone else's item must carry their
+/// valid signature.
+fn vouched_for(author: &str, signature: Option<&ActionSignature>, responder: &str) -> bool {
+    author == responder || signature.is_some_and(|signature| signature.signer == author && signature.verify())
+}
+
+/// Reconcile a peer's response to one of this node's sync requests
+///
+/// Only state missing locally is added: unknown proposals, as active, and
+/// votes cast before the deadline by voters with no vote recorded here on
+/// proposals known here. Existing records are left alone since they may be
+/// newer than the responder's copy. Items the responder can't vouch for (see
+/// [`vouched_for`]) are dropped, and added proposals whose deadline has
+/// passed are then closed on the votes known here.
+pub async fn apply_sync_response(state: &AppState, response: SyncResponse) {
+    let request_id = response.request_id.to_string();
+    if !state.pending_syncs.accepts(&request_id, state.config.sync_response_window) {
+        return;
+    }
+
+    let eligible = eligible_voters(state).await;
+    let mut added = Vec::new();
+    for proposal in &response.proposals {
+        if proposal.passing_rule.validate().is_err() {
+            continue;
+        }
+        if !vouched_for(&proposal.proposer, proposal.signature(), &response.responder) {
+            warn!("Ignoring synced proposal {} by {} from {}", proposal.id, proposal.proposer, response.responder);
+            continue;
+        }
+        match state.store.get_proposal(&proposal.id.to_string()).await {
+            Ok(None) => {}
+            Ok(Some(_)) => continue,
+            Err(e) => {
+                warn!("Failed to look up proposal {}: {}", proposal.id, e);
+                continue;
+            }
+        }
+        let (label, parameters) = proposal_types::from_protocol(&proposal.proposal_type);
+        let record = ProposalRecord {
+            id: proposal.id.to_string(),
+            proposer: proposal.proposer.clone(),
+            title: proposal.title.clone(),
+            description: proposal.description.clone(),
+            proposal_type: label,
+            parameters,
+            status: "active".to_string(),
+            quorum: received_quorum(proposal.quorum_voters, proposal.quorum, eligible),
+            quorum_fraction: proposal.quorum,
+            quorum_mode: proposal.quorum_mode,
+            passing_rule: proposal.passing_rule,
+            deadline: proposal.deadline.timestamp_millis(),
+            created_at: proposal.timestamp.timestamp_millis(),
+            version: 1,
+        };
+        match state.store.insert_proposal(&record).await {
+            Ok(true) => added.push((record.id, record.deadline)),
+            Ok(false) => {}
+            Err(e) => warn!("Failed to store synced proposal: {}", e),
+        }
+    }
+
+    // Deadline and voters per proposal, or `None` for proposals unknown here
+    let mut known: HashMap<String, Option<(i64, HashSet<String>)>> = HashMap::new();
+    let mut votes_added = 0;
+    for vote in &response.votes {
+        if !vouched_for(&vote.voter, vote.signature(), &response.responder) {
+            continue;
+        }
+        let proposal_id = vote.proposal_id.to_string();
+        if !known.contains_key(&proposal_id) {
+            let voters = match state.store.get_proposal(&proposal_id).await {
+                Ok(Some(proposal)) => match state.store.list_votes(&proposal_id).await {
+                    Ok(votes) => Some((proposal.deadline, votes.into_iter().map(|v| v.voter).collect())),
+                    Err(e) => {
+                        warn!("Failed to load votes for {}: {}", proposal_id, e);
+                        continue;
+                    }
+                },
+                Ok(None) => None,
+                Err(e) => {
+                    warn!("Failed to look up proposal {}: {}", proposal_id, e);
+                    continue;
+                }
+            };
+            known.insert(proposal_id.clone(), voters);
+        }
+        let Some(Some((deadline, voters))) = known.get_mut(&proposal_id) else {
+            continue;
+        };
+        if vote.timestamp.timestamp_millis() >= *deadline || !voters.insert(vote.voter.clone()) {
+            continue;
+        }
+        // Weighed here, as gossiped votes are
+        let record = VoteRecord {
+            proposal_id,
+            voter: vote.voter.clone(),
+            vote: vote_label(&vote.vote).to_string(),
+            weight: voter_weight(state, &vote.voter).await,
+            timestamp: vote.timestamp.timestamp_millis(),
+        };
+        match state.store.record_vote(&record).await {
+            Ok(()) => votes_added += 1,
+            Err(e) => warn!("Failed to store synced vote: {}", e),
+        }
+    }
+
+    // Statuses are worked out here rather than taken from the responder
+    let now = Utc::now().timestamp_millis();
+    for (proposal_id, deadline) in &added {
+        if *deadline <= now {
+            if let Err(e) = execution::finalize_proposal(state, proposal_id, now).await {
+                warn!("Failed to close synced proposal {}: {}", proposal_id, e);
+            }
+        }
+    }
+
+    let proposals_added = added.len();
+    info!(
+        "Sync {} from {}: {} proposals and {} votes added",
+        request_id, response.responder, proposals_added, votes_added
+    );
+    let _ = state.event_tx.send(WsMessage::SyncProgress {
+        request_id,
+        responder: Some(response.responder),
+        proposals_added,
+        votes_added,
+    });
+}
+
+/// Rebuild the protocol form of a stored proposal
+///
+/// `current` holds the governance parameters in effect, see [`proposal_types::to_protocol`].
+fn to_protocol_proposal(record: &ProposalRecord, current: &HashMap<String, String>) -> Option<CreateProposal> {
+    let timestamp = Utc.timestamp_millis_opt(record.created_at).single()?;
+    let deadline = Utc.timestamp_millis_opt(record.deadline).single()?;
+    Some(CreateProposal {
+        id: Uuid::parse_str(&record.id).ok()?,
+        proposer: record.proposer.clone(),
+        title: record.title.clone(),
+        description: record.description.clone(),
+        proposal_type: proposal_types::to_protocol(&record.proposal_type, &record.parameters, current),
+        quorum: record.quorum_fraction,
+        // Whether the count was fixed isn't stored, so peers derive it from the fraction
+        quorum_voters: None,
+        quorum_mode: record.quorum_mode,
+        threshold: 0.5,
+        passing_rule: record.passing_rule,
+        deadline,
+        timestamp,
+        correlation_id: None,
+        signature: None,
+    })
+}
+
+/// Protocol form of a stored proposal status
+fn to_protocol_status(status: &str) -> ProposalStatus {
+    match status {
+        "passed" => ProposalStatus::Passed,
+        "rejected" => ProposalStatus::Rejected,
+        "cancelled" => ProposalStatus::Cancelled,
+        _ => ProposalStatus::Active,
+    }
+}
+
+/// Rebuild the protocol form of a stored vote
+fn to_protocol_vote(record: &VoteRecord, proposal_id: Uuid) -> Option<CastVote> {
+    let vote = match record.vote.as_str() {
+        "yes" => Vote::For,
+        "no" => Vote::Against,
+        _ => Vote::Abstain,
+    };
+    Some(CastVote {
+        proposal_id,
+        voter: record.voter.clone(),
+        vote,
+        weight: record.weight,
+        reason: None,
+        timestamp: Utc.timestamp_millis_opt(record.timestamp).single()?,
+        correlation_id: None,
+        signature: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pages_stay_under_gossip_limit() {
+        let mut pages = Pages::new(Uuid::new_v4(), "responder".to_string());
+        for _ in 0..3 {
+            let proposal = CreateProposal::new("proposer".to_string(), "Big".to_string(), "x".repeat(300 * 1024));
+            pages.page_for(encoded_len(&proposal)).proposals.push(proposal);
+        }
+
+        // Two fit on the first page and the third starts another
+        assert_eq!(pages.pages.len(), 2);
+        assert_eq!(pages.pages.iter().map(|page| page.page).collect::<Vec<_>>(), vec![0, 1]);
+        assert_eq!(pages.pages[0].proposals.len(), 2);
+        for page in &pages.pages {
+            let data = serde_json::to_vec(&GovernanceMessage::SyncResponse(page.clone())).unwrap();
+            assert!(data.len() < 1024 * 1024);
+        }
+    }
+
+    #[test]
+    fn test_served_syncs_limit_each_peer() {
+        let served = ServedSyncs::default();
+        let interval = std::time::Duration::from_secs(60);
+        assert!(served.allow("a", interval));
+        assert!(!served.allow("a", interval));
+        assert!(served.allow("b", interval));
+        // Once the interval has passed the peer is answered again
+        assert!(served.allow("a", std::time::Duration::ZERO));
+    }
+}
@@ -0,0 +1,244 @@
+//! Chat message edits, deletions, reactions and read receipts
+//!
+//! A peer can correct or retract chat it sent, react to any chat it can see,
+//! and mark direct messages to it read. The change is published as a core
+//! `System` message on the original message's topic, carrying a
+//! [`ChatAmendment`]. Every node applies it to its logged copy so chat history
+//! reflects it. Edits and deletions are only applied if they come from the
+//! message's author, read receipts only if they come from its recipient.
+//! A deleted message can no longer be edited.
+
+use serde::{Deserialize, Serialize};
+use tracing::{debug, error};
+use unicode_segmentation::UnicodeSegmentation;
+
+use mycelial_core::message::Message;
+use mycelial_protocol::topics;
+
+use crate::replay::MessageCategory;
+use crate::server::messages::WsMessage;
+use crate::AppState;
+
+/// Change to a previously sent chat message
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum ChatAmendment {
+    Edit { message_id: String, content: String },
+    Delete { message_id: String },
+    /// Add or take back the sender's reaction
+    React { message_id: String, emoji: String, added: bool },
+    /// The sender, as the recipient of a direct message, has read it
+    Read { message_id: String },
+}
+
+impl ChatAmendment {
+    /// ID of the message being changed
+    pub fn message_id(&self) -> &str {
+        match self {
+            ChatAmendment::Edit { message_id, .. }
+            | ChatAmendment::Delete { message_id }
+            | ChatAmendment::React { message_id, .. }
+            | ChatAmendment::Read { message_id } => message_id,
+        }
+    }
+}
+
+/// Why an amendment was not applied
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AmendError {
+    /// No logged chat message has the ID
+    NotFound,
+    /// The message was sent by someone else
+    NotAuthor,
+    /// The message was not sent directly to the reader
+    NotRecipient,
+    /// The reaction is not a single emoji
+    InvalidEmoji,
+    /// The message was deleted, so can't be edited
+    Deleted,
+    /// The store could not be read or written
+    Store,
+}
+
+/// The logged message an amendment targets, once the amendment is authorized
+#[derive(Debug, Clone)]
+pub struct AmendTarget {
+    /// Topic the message was published on, without the namespace
+    pub topic: String,
+    /// The other party to a direct message, `None` for room and broadcast chat
+    pub to: Option<String>,
+}
+
+/// Longest reaction accepted, in characters
+///
+/// Enough for an emoji with skin tone and joiner sequences, while bounding
+/// clusters stacked with combining marks.
+const MAX_EMOJI_CHARS: usize = 16;
+
+/// Whether `emoji` is a single emoji rather than arbitrary text
+///
+/// It must be exactly one grapheme cluster, so joiner and skin tone sequences
+/// count as one but two emoji side by side don't.
+pub fn valid_emoji(emoji: &str) -> bool {
+    let mut graphemes = emoji.graphemes(true);
+    graphemes.next().is_some()
+        && graphemes.next().is_none()
+        && emoji.chars().count() <= MAX_EMOJI_CHARS
+        && !emoji.is_ascii()
+        && !emoji.chars().any(|c| c.is_whitespace() || c.is_control())
+}
+
+/// Check that `actor` may apply `amendment`
+///
+/// Anyone who can see a message may react, only the recipient of a direct
+/// message may mark it read, and only the author may edit or delete.
+pub async fn authorize(state: &AppState, amendment: &ChatAmendment, actor: &str) -> Result<AmendTarget, AmendError> {
+    if let ChatAmendment::React { emoji, .. } = amendment {
+        if !valid_emoji(emoji) {
+            return Err(AmendError::InvalidEmoji);
+        }
+    }
+    let message_id = amendment.message_id();
+    // The message may have been broadcast but not yet logged
+    state.event_log_fence.wait().await;
+    let logged = match state.store.find_message(message_id).await {
+        Ok(Some(logged)) => logged,
+        Ok(None) => return Err(AmendError::NotFound),
+        Err(e) => {
+            error!("Failed to look up chat message {}: {}", message_id, e);
+            return Err(AmendError::Store);
+        }
+    };
+    let payload: serde_json::Value = serde_json::from_str(&logged.payload_json).unwrap_or_default();
+    let from = payload["from"].as_str().unwrap_or_default();
+    // The other party, if the message went directly to one peer
+    let to = match (payload["room_id"].is_null(), payload["to"].as_str()) {
+        (true, Some(to)) if from == actor => Some(to.to_string()),
+        (true, Some(to)) if to == actor => Some(from.to_string()),
+        // Someone else's direct message can't be seen, so isn't there to amend
+        (true, Some(_)) => return Err(AmendError::NotFound),
+        _ => None,
+    };
+    match amendment {
+        ChatAmendment::React { .. } => {}
+        ChatAmendment::Read { .. } => {
+            if !payload["room_id"].is_null() || payload["to"].as_str() != Some(actor) {
+                return Err(AmendError::NotRecipient);
+            }
+        }
+        ChatAmendment::Edit { .. } | ChatAmendment::Delete { .. } => {
+            if payload["from"].as_str() != Some(actor) {
+                return Err(AmendError::NotAuthor);
+            }
+            if matches!(amendment, ChatAmendment::Edit { .. }) && payload["deleted"] == true {
+                return Err(AmendError::Deleted);
+            }
+        }
+    }
+    // Room IDs are validated before messages are sent or logged, so a
+    // message with an unusable one can't have come through this node
+    let topic = if let Some(room_id) = payload["room_id"].as_str() {
+        topics::room(room_id).ok_or(AmendError::NotFound)?
+    } else if let Some(other) = &to {
+        // Amendments of a direct message go to the other party's inbox
+        topics::direct(other)
+    } else {
+        "/mycelial/1.0.0/chat".to_string()
+    };
+    Ok(AmendTarget { topic, to })
+}
+
+/// Apply an amendment from `actor` to the logged message
+///
+/// Returns the event to broadcast to clients.
+pub async fn apply(state: &AppState, actor: &str, amendment: ChatAmendment, at: i64) -> Result<WsMessage, AmendError> {
+    let target = authorize(state, &amendment, actor).await?;
+    let (updated, event) = match amendment {
+        ChatAmendment::Edit { message_id, content } => (
+            state.store.edit_message(&message_id, &content, at).await,
+            WsMessage::ChatEdited {
+                message_id,
+                from: actor.to_string(),
+                to: target.to,
+                content,
+                edited_at: at,
+            },
+        ),
+        ChatAmendment::Delete { message_id } => (
+            state.store.delete_message(&message_id, at).await,
+            WsMessage::ChatDeleted {
+                message_id,
+                from: actor.to_string(),
+                to: target.to,
+                deleted_at: at,
+            },
+        ),
+        ChatAmendment::React { message_id, emoji, added } => {
+            return match state.store.set_reaction(&message_id, actor, &emoji, added, at).await {
+                Ok(_) => Ok(WsMessage::ChatReaction {
+                    message_id,
+                    reactor: actor.to_string(),
+                    to: target.to,
+                    emoji,
+                    added,
+                }),
+                Err(e) => {
+                    error!("Failed to update chat reaction: {}", e);
+                    Err(AmendError::Store)
+                }
+            };
+        }
+        ChatAmendment::Read { message_id } => {
+            return match state.store.mark_read(&message_id, actor, at).await {
+                Ok(timestamp) => Ok(WsMessage::ReadReceipt {
+                    message_id,
+                    reader: actor.to_string(),
+                    // Only direct messages are authorized to be read
+                    to: target.to.unwrap_or_default(),
+                    timestamp,
+                }),
+                Err(e) => {
+                    error!("Failed to record read receipt: {}", e);
+                    Err(AmendError::Store)
+                }
+            };
+        }
+    };
+    match updated {
+        Ok(true) => Ok(event),
+        Ok(false) => Err(AmendError::NotFound),
+        Err(e) => {
+            error!("Failed to amend chat message: {}", e);
+            Err(AmendError::Store)
+        }
+    }
+}
+
+/// Apply an amendment received from the network and broadcast it
+///
+/// `source` is the authenticated gossip source, preferred over the claimed sender.
+pub async fn handle_remote(state: &AppState, msg: Message, source: Option<String>, at: i64) {
+    let local = state.local_peer_id.to_string();
+    let actor = source.unwrap_or_else(|| msg.sender.to_string());
+    if actor == local {
+        debug!("Ignoring own chat amendment {}", msg.id);
+        return;
+    }
+    if !state.replay_guard.check_and_record(MessageCategory::Chat, &msg.id.to_string()) {
+        debug!("Ignoring replayed chat amendment {}", msg.id);
+        return;
+    }
+    let amendment: ChatAmendment = match serde_json::from_slice(&msg.payload) {
+        Ok(amendment) => amendment,
+        Err(e) => {
+            debug!("Ignoring system message {} on a chat topic: {}", msg.id, e);
+            return;
+        }
+    };
+    match apply(state, &actor, amendment, at).await {
+        Ok(event) => {
+            let _ = state.event_tx.send(event);
+        }
+        Err(e) => debug!("Not applying chat amendment {} from {}: {:?}", msg.id, actor, e),
+    }
+}
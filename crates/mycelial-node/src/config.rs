@@ -0,0 +1,249 @@
+//! Runtime configuration for the node's dashboard server
+//!
+//! These settings tune limits and windows used by the WebSocket handlers and
+//! the network event loop. Defaults are suitable for local development.
+
+use serde_json::json;
+use std::time::Duration;
+
+use mycelial_protocol::topics;
+
+use crate::naming::FallbackName;
+
+/// Tunable settings shared by the server and network event handlers
+#[derive(Debug, Clone)]
+pub struct ServerConfig {
+    /// How long inbound message IDs are remembered for replay protection
+    pub replay_window: Duration,
+    /// Maximum number of remembered message IDs per message category
+    pub replay_capacity: usize,
+    /// Maximum active subscriptions a single connection may register
+    pub max_subscriptions_per_connection: usize,
+    /// Fractions of a proposal's voting period at which reminders are sent
+    pub reminder_points: Vec<f64>,
+    /// How often open proposals are checked for due reminders
+    pub reminder_interval: Duration,
+    /// How often open proposals are checked for passed deadlines
+    pub deadline_check_interval: Duration,
+    /// Delay clients are asked to wait before reconnecting after a shutdown
+    pub shutdown_reconnect_after: Duration,
+    /// How long shutdown waits for connections to flush and close
+    pub shutdown_grace: Duration,
+    /// Whether connections may enable debug capabilities such as raw protocol forwarding
+    pub allow_debug_capabilities: bool,
+    /// How often stats snapshots are persisted for history charts
+    pub stats_snapshot_interval: Duration,
+    /// How long stats snapshots are kept
+    pub stats_retention: Duration,
+    /// Reject economics actions while no peers are connected instead of
+    /// reporting them as sent
+    pub reject_when_isolated: bool,
+    /// Half-life applied to resource contributions when ranking contributors,
+    /// or `None` to weigh all contributions equally
+    pub contribution_half_life: Option<Duration>,
+    /// How often each connection is sent a delivery checkpoint
+    pub checkpoint_interval: Duration,
+    /// Idle time after the last delivery before a checkpoint is sent early
+    pub checkpoint_quiet_period: Duration,
+    /// Token granting a connection admin commands, or `None` to disable them
+    pub admin_token: Option<String>,
+    /// Minimum time between proposals from the same peer (zero disables it)
+    pub proposal_cooldown: Duration,
+    /// Peers the proposal cooldown never applies to
+    pub proposal_cooldown_exempt_peers: Vec<String>,
+    /// Whether admin connections bypass the proposal cooldown
+    pub proposal_cooldown_exempt_admins: bool,
+    /// Reputation below which voters are treated as possible sybils when
+    /// tallying, or `None` to count every vote in full
+    pub sybil_reputation_floor: Option<f64>,
+    /// Maximum combined weight of votes from voters below the floor
+    pub sybil_weight_cap: f64,
+    /// Number of below-floor voters casting the same vote that triggers a warning
+    pub sybil_warning_bloc: usize,
+    /// How far back a governance sync asks peers for proposals
+    pub sync_lookback: Duration,
+    /// How long responses to a governance sync request are accepted
+    pub sync_response_window: Duration,
+    /// Maximum proposals shared in answer to one sync request
+    pub sync_max_proposals: usize,
+    /// Minimum time between answering sync requests from the same peer
+    pub sync_serve_interval: Duration,
+    /// `did:key` whose signature economics actions must carry, or `None` to
+    /// accept unsigned actions
+    pub action_signer: Option<String>,
+    /// How peers without a known name are labelled
+    pub fallback_name: FallbackName,
+    /// How long a reconnection token can be used to resume
+    pub resume_token_ttl: Duration,
+    /// Namespace prefixed to every gossip topic (empty for none)
+    pub topic_namespace: String,
+    /// Minimum time between `NetworkError` reports for one operation
+    pub network_error_interval: Duration,
+    /// How often each WebSocket connection is pinged
+    pub ping_interval: Duration,
+    /// How long a WebSocket connection may go without receiving a frame before it is dropped
+    pub idle_timeout: Duration,
+    /// Messages per second a connection may publish once its burst is spent
+    pub publish_rate: f64,
+    /// Messages a connection may publish in a burst
+    pub publish_burst: u32,
+    /// Bearer token a WebSocket upgrade must present
+    pub ws_token: Option<String>,
+    /// Accept WebSocket upgrades without a token (local development only)
+    pub open_websocket: bool,
+    /// How often current stats are pushed to every client, or `None` to
+    /// send them only on request
+    pub stats_push_interval: Option<Duration>,
+    /// How long a connection remembers delivered event IDs to skip duplicates
+    pub dedup_window: Duration,
+    /// Events the broadcast channel holds before lagging connections miss some
+    pub broadcast_capacity: usize,
+    /// Frames queued for a slow connection before snapshots are dropped, or
+    /// the connection closed if nothing can be
+    pub outbound_queue_capacity: usize,
+    /// Chat messages in the snapshot sent to a new connection
+    pub snapshot_chat_messages: usize,
+    /// Largest WebSocket message accepted from a client, in bytes
+    pub max_frame_size: usize,
+    /// Largest chat message content accepted from a client, in bytes
+    pub max_chat_content: usize,
+    /// How long peer membership changes are collected into one `PeerDelta`
+    pub peer_delta_window: Duration,
+    /// Publish a leave notice when the last session for a peer closes
+    pub announce_presence: bool,
+    /// Topic presence notices are published on
+    pub presence_topic: String,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            replay_window: Duration::from_secs(600),
+            replay_capacity: 10_000,
+            max_subscriptions_per_connection: 64,
+            reminder_points: vec![0.5, 0.9],
+            reminder_interval: Duration::from_secs(30),
+            deadline_check_interval: Duration::from_secs(15),
+            shutdown_reconnect_after: Duration::from_secs(5),
+            shutdown_grace: Duration::from_secs(2),
+            allow_debug_capabilities: false,
+            stats_snapshot_interval: Duration::from_secs(60),
+            stats_retention: Duration::from_secs(7 * 24 * 60 * 60),
+            reject_when_isolated: true,
+            contribution_half_life: None,
+            checkpoint_interval: Duration::from_secs(30),
+            checkpoint_quiet_period: Duration::from_secs(2),
+            admin_token: None,
+            proposal_cooldown: Duration::ZERO,
+            proposal_cooldown_exempt_peers: Vec::new(),
+            proposal_cooldown_exempt_admins: true,
+            sybil_reputation_floor: None,
+            sybil_weight_cap: 1.0,
+            sybil_warning_bloc: 5,
+            sync_lookback: Duration::from_secs(7 * 24 * 60 * 60),
+            sync_response_window: Duration::from_secs(60),
+            sync_max_proposals: 200,
+            sync_serve_interval: Duration::from_secs(30),
+            action_signer: None,
+            fallback_name: FallbackName::ShortId,
+            resume_token_ttl: Duration::from_secs(10 * 60),
+            topic_namespace: String::new(),
+            network_error_interval: Duration::from_secs(10),
+            ping_interval: Duration::from_secs(30),
+            idle_timeout: Duration::from_secs(90),
+            publish_rate: 5.0,
+            publish_burst: 20,
+            ws_token: None,
+            open_websocket: false,
+            stats_push_interval: None,
+            dedup_window: Duration::from_secs(60),
+            broadcast_capacity: 256,
+            outbound_queue_capacity: 256,
+            snapshot_chat_messages: 50,
+            max_frame_size: 64 * 1024,
+            max_chat_content: 4 * 1024,
+            peer_delta_window: Duration::from_millis(200),
+            announce_presence: false,
+            presence_topic: topics::PRESENCE.to_string(),
+        }
+    }
+}
+
+impl ServerConfig {
+    /// The configuration as JSON for operators, with secrets redacted
+    ///
+    /// Durations are reported in milliseconds. Secret values are replaced by
+    /// whether they are set.
+    pub fn snapshot(&self) -> serde_json::Value {
+        json!({
+            "replay_window_ms": self.replay_window.as_millis() as u64,
+            "replay_capacity": self.replay_capacity,
+            "max_subscriptions_per_connection": self.max_subscriptions_per_connection,
+            "reminder_points": self.reminder_points,
+            "reminder_interval_ms": self.reminder_interval.as_millis() as u64,
+            "deadline_check_interval_ms": self.deadline_check_interval.as_millis() as u64,
+            "shutdown_reconnect_after_ms": self.shutdown_reconnect_after.as_millis() as u64,
+            "shutdown_grace_ms": self.shutdown_grace.as_millis() as u64,
+            "allow_debug_capabilities": self.allow_debug_capabilities,
+            "stats_snapshot_interval_ms": self.stats_snapshot_interval.as_millis() as u64,
+            "stats_retention_ms": self.stats_retention.as_millis() as u64,
+            "reject_when_isolated": self.reject_when_isolated,
+            "contribution_half_life_ms": self.contribution_half_life.map(|d| d.as_millis() as u64),
+            "checkpoint_interval_ms": self.checkpoint_interval.as_millis() as u64,
+            "checkpoint_quiet_period_ms": self.checkpoint_quiet_period.as_millis() as u64,
+            "admin_token_set": self.admin_token.is_some(),
+            "proposal_cooldown_ms": self.proposal_cooldown.as_millis() as u64,
+            "proposal_cooldown_exempt_peers": self.proposal_cooldown_exempt_peers,
+            "proposal_cooldown_exempt_admins": self.proposal_cooldown_exempt_admins,
+            "sybil_reputation_floor": self.sybil_reputation_floor,
+            "sybil_weight_cap": self.sybil_weight_cap,
+            "sybil_warning_bloc": self.sybil_warning_bloc,
+            "sync_lookback_ms": self.sync_lookback.as_millis() as u64,
+            "sync_response_window_ms": self.sync_response_window.as_millis() as u64,
+            "sync_max_proposals": self.sync_max_proposals,
+            "sync_serve_interval_ms": self.sync_serve_interval.as_millis() as u64,
+            "action_signer": self.action_signer,
+            "fallback_name": self.fallback_name.label(),
+            "resume_token_ttl_ms": self.resume_token_ttl.as_millis() as u64,
+            "topic_namespace": self.topic_namespace,
+            "network_error_interval_ms": self.network_error_interval.as_millis() as u64,
+            "ping_interval_ms": self.ping_interval.as_millis() as u64,
+            "idle_timeout_ms": self.idle_timeout.as_millis() as u64,
+            "publish_rate": self.publish_rate,
+            "publish_burst": self.publish_burst,
+            "ws_token_set": self.ws_token.is_some(),
+            "open_websocket": self.open_websocket,
+            "stats_push_interval_ms": self.stats_push_interval.map(|d| d.as_millis() as u64),
+            "dedup_window_ms": self.dedup_window.as_millis() as u64,
+            "broadcast_capacity": self.broadcast_capacity,
+            "outbound_queue_capacity": self.outbound_queue_capacity,
+            "snapshot_chat_messages": self.snapshot_chat_messages,
+            "max_frame_size": self.max_frame_size,
+            "max_chat_content": self.max_chat_content,
+            "peer_delta_window_ms": self.peer_delta_window.as_millis() as u64,
+            "announce_presence": self.announce_presence,
+            "presence_topic": self.presence_topic,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snapshot_redacts_secrets() {
+        let config = ServerConfig {
+            max_subscriptions_per_connection: 7,
+            admin_token: Some("hunter2".to_string()),
+            ..ServerConfig::default()
+        };
+        let snapshot = config.snapshot();
+
+        assert_eq!(snapshot["max_subscriptions_per_connection"], 7);
+        assert_eq!(snapshot["proposal_cooldown_ms"], 0);
+        assert_eq!(snapshot["admin_token_set"], true);
+        assert!(snapshot.get("admin_token").is_none());
+        assert!(!snapshot.to_string().contains("hunter2"));
+    }
+}
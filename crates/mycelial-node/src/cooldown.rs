@@ -0,0 +1,87 @@
+//! Per-peer proposal cooldown
+//!
+//! To keep governance from being flooded, a peer that creates a proposal
+//! must wait for the configured cooldown before creating another one.
+//! Exempt peers are never limited. The cooldown can be changed while the
+//! node runs by a passed `parameter_change` proposal.
+
+use parking_lot::{Mutex, RwLock};
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+
+/// Tracks when each peer last created a proposal
+pub struct ProposalCooldowns {
+    /// Minimum time between proposals from one peer (zero disables the cooldown)
+    cooldown: RwLock<Duration>,
+    /// Peers the cooldown never applies to
+    exempt: HashSet<String>,
+    /// Peer ID -> when it last created a proposal (epoch millis)
+    last_proposal: Mutex<HashMap<String, i64>>,
+}
+
+impl ProposalCooldowns {
+    /// Create a tracker enforcing `cooldown` on every peer not in `exempt`
+    pub fn new(cooldown: Duration, exempt: HashSet<String>) -> Self {
+        Self {
+            cooldown: RwLock::new(cooldown),
+            exempt,
+            last_proposal: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// The cooldown currently enforced
+    pub fn cooldown(&self) -> Duration {
+        *self.cooldown.read()
+    }
+
+    /// Enforce `cooldown` from now on
+    pub fn set_cooldown(&self, cooldown: Duration) {
+        *self.cooldown.write() = cooldown;
+    }
+
+    /// Time left before `peer` may create another proposal, if any
+    pub fn remaining(&self, peer: &str, now: i64) -> Option<Duration> {
+        let cooldown = self.cooldown();
+        if cooldown.is_zero() || self.exempt.contains(peer) {
+            return None;
+        }
+        let last = *self.last_proposal.lock().get(peer)?;
+        let elapsed = Duration::from_millis(now.saturating_sub(last).max(0) as u64);
+        cooldown.checked_sub(elapsed).filter(|left| !left.is_zero())
+    }
+
+    /// Record that `peer` created a proposal at `now`
+    pub fn record(&self, peer: &str, now: i64) {
+        let cooldown = self.cooldown();
+        if cooldown.is_zero() {
+            return;
+        }
+        let mut last_proposal = self.last_proposal.lock();
+        let cutoff = now - cooldown.as_millis() as i64;
+        // Entries past their cooldown no longer limit anything
+        last_proposal.retain(|_, last| *last > cutoff);
+        last_proposal.insert(peer.to_string(), now);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cooldown_per_peer() {
+        let exempt = HashSet::from(["trusted".to_string()]);
+        let cooldowns = ProposalCooldowns::new(Duration::from_secs(60), exempt);
+
+        assert_eq!(cooldowns.remaining("alice", 0), None);
+        cooldowns.record("alice", 0);
+        cooldowns.record("trusted", 0);
+
+        assert_eq!(cooldowns.remaining("alice", 15_000), Some(Duration::from_secs(45)));
+        // Other peers and exempt peers are unaffected
+        assert_eq!(cooldowns.remaining("bob", 15_000), None);
+        assert_eq!(cooldowns.remaining("trusted", 15_000), None);
+
+        assert_eq!(cooldowns.remaining("alice", 60_000), None);
+    }
+}
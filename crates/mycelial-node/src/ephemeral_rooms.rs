@@ -0,0 +1,122 @@
+//! Ephemeral chat rooms
+//!
+//! Chat in an ephemeral room is relayed to live subscribers but never written
+//! to the event log. Only the peer that created a room can make it ephemeral,
+//! so joining someone else's room can't switch off its history. A room stops
+//! being ephemeral once it has been idle for the configured time, which keeps
+//! the set of tracked rooms from growing without bound.
+
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// How long an ephemeral room may go without chat before it is forgotten
+pub const EPHEMERAL_ROOM_IDLE: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// An ephemeral room's owner and most recent activity
+struct EphemeralRoom {
+    /// Peer that created the room
+    creator: String,
+    /// When the room was created or last carried chat (epoch millis)
+    last_active: i64,
+}
+
+/// Tracks which rooms are ephemeral, and who made them so
+pub struct EphemeralRooms {
+    /// Idle time after which a room is no longer ephemeral
+    idle: Duration,
+    /// Room ID -> owner and last activity
+    rooms: Mutex<HashMap<String, EphemeralRoom>>,
+}
+
+impl EphemeralRooms {
+    /// Create a tracker forgetting rooms idle for longer than `idle`
+    pub fn new(idle: Duration) -> Self {
+        Self {
+            idle,
+            rooms: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Make `room_id` ephemeral on behalf of `creator`
+    ///
+    /// Fails if another peer already made the room ephemeral.
+    pub fn mark(&self, room_id: &str, creator: &str, now: i64) -> Result<(), String> {
+        let mut rooms = self.rooms.lock();
+        self.prune(&mut rooms, now);
+        match rooms.get_mut(room_id) {
+            Some(room) if room.creator != creator => Err(format!("Room {} belongs to another peer", room_id)),
+            Some(room) => {
+                room.last_active = now;
+                Ok(())
+            }
+            None => {
+                rooms.insert(
+                    room_id.to_string(),
+                    EphemeralRoom {
+                        creator: creator.to_string(),
+                        last_active: now,
+                    },
+                );
+                Ok(())
+            }
+        }
+    }
+
+    /// Whether `room_id` is ephemeral at `now`, without counting as activity
+    pub fn contains(&self, room_id: &str, now: i64) -> bool {
+        let cutoff = now - self.idle.as_millis() as i64;
+        self.rooms.lock().get(room_id).is_some_and(|room| room.last_active > cutoff)
+    }
+
+    /// Whether `room_id` is ephemeral at `now`, keeping it so for another
+    /// idle period if it is
+    pub fn touch(&self, room_id: &str, now: i64) -> bool {
+        let mut rooms = self.rooms.lock();
+        self.prune(&mut rooms, now);
+        match rooms.get_mut(room_id) {
+            Some(room) => {
+                room.last_active = now;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Forget rooms idle since before the cutoff
+    fn prune(&self, rooms: &mut HashMap<String, EphemeralRoom>, now: i64) {
+        let cutoff = now - self.idle.as_millis() as i64;
+        rooms.retain(|_, room| room.last_active > cutoff);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_only_creator_marks_room() {
+        let rooms = EphemeralRooms::new(Duration::from_secs(60));
+
+        assert!(rooms.mark("live", "alice", 0).is_ok());
+        assert!(rooms.mark("live", "bob", 1_000).is_err());
+        assert!(rooms.mark("live", "alice", 2_000).is_ok());
+        assert!(rooms.contains("live", 2_000));
+        assert!(!rooms.contains("other", 2_000));
+    }
+
+    #[test]
+    fn test_idle_rooms_expire() {
+        let rooms = EphemeralRooms::new(Duration::from_secs(60));
+        rooms.mark("live", "alice", 0).unwrap();
+
+        // Chat keeps the room alive
+        assert!(rooms.touch("live", 50_000));
+        assert!(rooms.contains("live", 100_000));
+
+        assert!(!rooms.contains("live", 110_000));
+        assert!(!rooms.touch("live", 110_000));
+        // Once forgotten, anyone may claim the room again
+        assert!(rooms.mark("live", "bob", 110_000).is_ok());
+    }
+}
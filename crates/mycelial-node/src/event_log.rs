@@ -0,0 +1,130 @@
+//! Dashboard event log
+//!
+//! Every event broadcast to WebSocket clients that involves at least one peer
+//! is appended to the store's event log, giving replay requests a single
+//! persisted history to read from. Chat in ephemeral rooms is relayed live
+//! but never logged, so it leaves no history.
+
+use parking_lot::Mutex;
+use std::sync::Arc;
+use tokio::sync::broadcast::error::{RecvError, TryRecvError};
+use tokio::sync::{mpsc, oneshot};
+use tracing::{error, warn};
+
+use crate::server::messages::WsMessage;
+use crate::AppState;
+
+type FenceRequest = oneshot::Sender<()>;
+
+/// Lets readers wait until the recorder has caught up with the broadcast channel
+///
+/// Events are logged in the background, so a client that triggers an event
+/// and immediately reads the log could miss it. Waiting on the fence first
+/// guarantees everything broadcast before the wait is in the log.
+pub struct EventLogFence {
+    tx: mpsc::UnboundedSender<FenceRequest>,
+    /// Handed to the recorder when it starts
+    rx: Mutex<Option<mpsc::UnboundedReceiver<FenceRequest>>>,
+}
+
+impl Default for EventLogFence {
+    fn default() -> Self {
+        let (tx, rx) = mpsc::unbounded_channel();
+        Self {
+            tx,
+            rx: Mutex::new(Some(rx)),
+        }
+    }
+}
+
+impl EventLogFence {
+    /// Wait until every event broadcast before this call has been logged
+    ///
+    /// Returns immediately if no recorder is running.
+    pub async fn wait(&self) {
+        if self.rx.lock().is_some() {
+            return;
+        }
+        let (done_tx, done_rx) = oneshot::channel();
+        if self.tx.send(done_tx).is_ok() {
+            let _ = done_rx.await;
+        }
+    }
+}
+
+/// Persist broadcast events until the channel closes
+pub async fn run_event_recorder(state: Arc<AppState>) {
+    let mut event_rx = state.event_tx.subscribe();
+    let Some(mut fence_rx) = state.event_log_fence.rx.lock().take() else {
+        error!("Event recorder is already running");
+        return;
+    };
+    loop {
+        tokio::select! {
+            event = event_rx.recv() => match event {
+                Ok(event) => {
+                    // Sees every event, so it keeps the queue's high-water mark current
+                    state.sample_broadcast_depth();
+                    record_event(&state, &event).await
+                }
+                Err(RecvError::Lagged(skipped)) => {
+                    warn!("Event recorder lagged, {} events were not logged", skipped);
+                }
+                Err(RecvError::Closed) => break,
+            },
+            Some(done) = fence_rx.recv() => {
+                // Anything broadcast before the request is already queued
+                loop {
+                    match event_rx.try_recv() {
+                        Ok(event) => record_event(&state, &event).await,
+                        Err(TryRecvError::Lagged(skipped)) => {
+                            warn!("Event recorder lagged, {} events were not logged", skipped);
+                        }
+                        Err(TryRecvError::Empty) | Err(TryRecvError::Closed) => break,
+                    }
+                }
+                let _ = done.send(());
+            }
+        }
+    }
+}
+
+/// Whether an event belongs to an ephemeral room and must not be persisted
+fn is_ephemeral(state: &AppState, event: &WsMessage) -> bool {
+    match event {
+        WsMessage::ChatMessage { room_id: Some(room_id), .. } => {
+            state.ephemeral_rooms.touch(room_id, chrono::Utc::now().timestamp_millis())
+        }
+        _ => false,
+    }
+}
+
+/// Append a single event to the log if it involves any peer
+pub async fn record_event(state: &AppState, event: &WsMessage) {
+    let peers = event.involved_peers();
+    if peers.is_empty() || is_ephemeral(state, event) {
+        return;
+    }
+
+    let payload = match serde_json::to_value(event) {
+        Ok(value) => value,
+        Err(e) => {
+            error!("Failed to serialize event for logging: {}", e);
+            return;
+        }
+    };
+    let event_type = payload
+        .get("type")
+        .and_then(|t| t.as_str())
+        .unwrap_or("unknown");
+    let timestamp = chrono::Utc::now().timestamp_millis();
+
+    if let Err(e) = state
+        .store
+        .append_event(event_type, timestamp, &payload.to_string(), &peers)
+        .await
+    {
+        error!("Failed to log {} event: {}", event_type, e);
+    }
+}
+
@@ -0,0 +1,521 @@
+//! Proposal finalization and execution
+//!
+//! When voting on a proposal closes, its final status is stored and
+//! broadcast. Voting closes once the deadline has passed, when a background
+//! task finds it so or an admin finalizes the proposal first. If it passed
+//! and a handler is registered for its proposal type, the handler carries
+//! out the decision and the result is reported as
+//! [`WsMessage::ProposalExecuted`].
+//!
+//! Proposals carry their parameters with them (see
+//! [`ProposalKind`](crate::proposal_types::ProposalKind)); a description is
+//! only ever text for people to read.
+//!
+//! Only proposals created through this node's own clients are executed here;
+//! each proposer's node carries out its own. A proposal received over gossip
+//! or sync is never executed, whoever it names as proposer.
+
+use futures::future::BoxFuture;
+use parking_lot::RwLock;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use mycelial_protocol::{topics, Correlated, CreditMessage, CreditTransfer};
+use mycelial_state::{CreditTransferRecord, ProposalRecord};
+
+use crate::governance_params::Parameter;
+use crate::naming;
+use crate::network_errors;
+use crate::server::messages::WsMessage;
+use crate::server::governance::{eligible_voters, tally_proposal};
+use crate::AppState;
+
+/// Carries out a passed proposal of one type
+pub trait ExecutionHandler: Send + Sync {
+    /// Execute the proposal, returning a description of what was done
+    fn execute<'a>(
+        &'a self,
+        state: &'a AppState,
+        proposal: &'a ProposalRecord,
+    ) -> BoxFuture<'a, Result<String, String>>;
+}
+
+/// Execution handlers keyed by proposal type
+pub struct ExecutionRegistry {
+    handlers: RwLock<HashMap<String, Arc<dyn ExecutionHandler>>>,
+}
+
+impl Default for ExecutionRegistry {
+    /// A registry with the built-in `parameter_change` and `treasury_spend` handlers
+    fn default() -> Self {
+        let registry = Self {
+            handlers: RwLock::new(HashMap::new()),
+        };
+        registry.register("parameter_change", Arc::new(ParameterChange));
+        registry.register("treasury_spend", Arc::new(TreasurySpend));
+        registry
+    }
+}
+
+impl ExecutionRegistry {
+    /// Register the handler for a proposal type, replacing any existing one
+    pub fn register(&self, proposal_type: &str, handler: Arc<dyn ExecutionHandler>) {
+        self.handlers.write().insert(proposal_type.to_string(), handler);
+    }
+
+    fn handler(&self, proposal_type: &str) -> Option<Arc<dyn ExecutionHandler>> {
+        self.handlers.read().get(proposal_type).cloned()
+    }
+}
+
+/// Close voting on a proposal as of `now`, executing it if it passed
+///
+/// Returns the final status, or `None` if the proposal was already closed.
+/// Voting can't be closed before the deadline, so a decision is never taken
+/// on partial votes. Closing is a single conditional update in the store, so
+/// a proposal is never executed twice.
+pub async fn finalize_proposal(
+    state: &AppState,
+    proposal_id: &str,
+    now: i64,
+) -> Result<Option<&'static str>, String> {
+    let mut record = match state.store.get_proposal(proposal_id).await {
+        Ok(Some(record)) => record,
+        Ok(None) => return Err(format!("Unknown proposal: {}", proposal_id)),
+        Err(e) => return Err(format!("Failed to load proposal: {}", e)),
+    };
+    if record.status != "active" {
+        return Ok(None);
+    }
+    if now < record.deadline {
+        return Err(format!("Voting on {} is open until its deadline", proposal_id));
+    }
+    let tally = tally_proposal(state, proposal_id)
+        .await
+        .map_err(|e| format!("Failed to tally votes: {}", e))?;
+    let status = record.outcome(&tally, eligible_voters(state).await);
+
+    match state.store.close_proposal(proposal_id, status).await {
+        Ok(true) => {}
+        Ok(false) => return Ok(None),
+        Err(e) => return Err(format!("Failed to close proposal: {}", e)),
+    }
+    info!("Proposal {} {}", proposal_id, status);
+    record.status = status.to_string();
+    let _ = state.event_tx.send(WsMessage::Proposal {
+        id: record.id.clone(),
+        proposer: record.proposer.clone(),
+        proposer_name: naming::resolve_display_name(state, &record.proposer).await,
+        title: record.title.clone(),
+        description: record.description.clone(),
+        proposal_type: record.proposal_type.clone(),
+        parameters: record.parameters.clone(),
+        status: record.status.clone(),
+        yes_votes: tally.yes.round() as u32,
+        no_votes: tally.no.round() as u32,
+        quorum: record.quorum,
+        deadline: record.deadline,
+        version: record.version,
+        correlation_id: None,
+        timestamp: chrono::Utc::now().timestamp_millis(),
+    });
+
+    let local = match state.store.is_local_proposal(proposal_id).await {
+        Ok(local) => local && record.proposer == state.local_peer_id.to_string(),
+        Err(e) => {
+            warn!("Failed to check origin of proposal {}: {}", proposal_id, e);
+            false
+        }
+    };
+    if status == "passed" && local {
+        if let Some(handler) = state.executors.handler(&record.proposal_type) {
+            let (success, outcome) = match handler.execute(state, &record).await {
+                Ok(outcome) => (true, outcome),
+                Err(e) => {
+                    warn!("Failed to execute proposal {}: {}", proposal_id, e);
+                    (false, e)
+                }
+            };
+            let _ = state.event_tx.send(WsMessage::ProposalExecuted {
+                proposal_id: record.id,
+                success,
+                outcome,
+            });
+        }
+    }
+    Ok(Some(status))
+}
+
+/// Close open proposals on a fixed interval until the process exits
+pub async fn run_deadline_closer(state: Arc<AppState>) {
+    let mut interval = tokio::time::interval(state.config.deadline_check_interval);
+    loop {
+        interval.tick().await;
+        close_expired_proposals(&state, chrono::Utc::now().timestamp_millis()).await;
+    }
+}
+
+/// Finalize every open proposal whose deadline is at or before `now`
+pub async fn close_expired_proposals(state: &AppState, now: i64) {
+    let proposals = match state.store.list_proposals().await {
+        Ok(proposals) => proposals,
+        Err(e) => {
+            warn!("Failed to load proposals for deadline check: {}", e);
+            return;
+        }
+    };
+    for proposal in proposals {
+        if proposal.status != "active" || proposal.deadline > now {
+            continue;
+        }
+        if let Err(e) = finalize_proposal(state, &proposal.id, now).await {
+            warn!("Failed to close expired proposal {}: {}", proposal.id, e);
+        }
+    }
+}
+
+/// Puts every parameter in the proposal into effect on the running node
+///
+/// Only [whitelisted](Parameter::parse) parameters are applied; a proposal
+/// naming any other changes nothing.
+struct ParameterChange;
+
+impl ExecutionHandler for ParameterChange {
+    fn execute<'a>(
+        &'a self,
+        state: &'a AppState,
+        proposal: &'a ProposalRecord,
+    ) -> BoxFuture<'a, Result<String, String>> {
+        Box::pin(async move {
+            if proposal.parameters.is_empty() {
+                return Err("No parameters to change".to_string());
+            }
+            // Checked in full before any is applied
+            let changes = proposal
+                .parameters
+                .iter()
+                .map(|(key, value)| Parameter::parse(key, value))
+                .collect::<Result<Vec<_>, _>>()?;
+            for change in &changes {
+                change.apply(state);
+            }
+            let applied: Vec<_> = proposal
+                .parameters
+                .iter()
+                .map(|(key, value)| format!("{} = {}", key, value))
+                .collect();
+            Ok(format!("Set {}", applied.join(", ")))
+        })
+    }
+}
+
+/// Transfers `amount` credit from the local node to `recipient`, drawn on
+/// the credit line `recipient` extended to it
+struct TreasurySpend;
+
+impl ExecutionHandler for TreasurySpend {
+    fn execute<'a>(
+        &'a self,
+        state: &'a AppState,
+        proposal: &'a ProposalRecord,
+    ) -> BoxFuture<'a, Result<String, String>> {
+        Box::pin(async move {
+            let params = &proposal.parameters;
+            let recipient = params.get("recipient").ok_or("Missing recipient")?;
+            let amount = params
+                .get("amount")
+                .and_then(|amount| amount.parse::<f64>().ok())
+                .filter(|amount| amount.is_finite() && *amount > 0.0)
+                .ok_or("Missing or invalid amount")?;
+            let local = state.local_peer_id.to_string();
+            let line = state
+                .store
+                .find_credit_line(recipient, &local)
+                .await
+                .map_err(|e| format!("Failed to look up credit line: {}", e))?
+                .ok_or_else(|| format!("No credit line from {}", recipient))?;
+            let line_id = Uuid::parse_str(&line.id).map_err(|_| format!("Invalid credit line {}", line.id))?;
+
+            // Tag the transfer with the proposal ID so clients can link them
+            let transfer = CreditTransfer::new(
+                line_id,
+                local.clone(),
+                recipient.clone(),
+                amount,
+            )
+            .with_memo(format!("Treasury spend: {}", proposal.title))
+            .with_correlation_id(Some(proposal.id.clone()));
+            let transfer_id = transfer.id.to_string();
+            let memo = transfer.memo.clone();
+            let data = serde_json::to_vec(&CreditMessage::Transfer(transfer))
+                .map_err(|e| format!("Failed to serialize transfer: {}", e))?;
+            let timestamp = chrono::Utc::now().timestamp_millis();
+
+            // Drawn before publishing, and returned if the publish fails
+            let record = CreditTransferRecord {
+                id: transfer_id.clone(),
+                line_id: line.id.clone(),
+                from: local.clone(),
+                to: recipient.clone(),
+                amount,
+                memo: memo.clone(),
+                created_at: timestamp,
+            };
+            let drawn = state
+                .store
+                .record_credit_transfer(&record)
+                .await
+                .map_err(|e| format!("Failed to draw on credit line: {}", e))?;
+            if !drawn {
+                return Err(format!(
+                    "Transfer of {} exceeds available credit of {}",
+                    amount,
+                    (line.limit - line.balance).max(0.0)
+                ));
+            }
+            if let Err(e) = state.network.publish(state.wire_topic(topics::CREDIT), data).await {
+                network_errors::report(state, "publish", &e);
+                if let Err(e) = state.store.revert_credit_transfer(&transfer_id).await {
+                    warn!("Failed to return unsent transfer {}: {}", transfer_id, e);
+                }
+                return Err(format!("Failed to publish transfer: {}", e));
+            }
+
+            let _ = state.event_tx.send(WsMessage::CreditTransfer {
+                id: transfer_id,
+                from: local,
+                from_name: state.node_name(),
+                to: recipient.clone(),
+                to_name: naming::resolve_display_name(state, &recipient).await,
+                amount,
+                memo,
+                request_ref: None,
+                correlation_id: Some(proposal.id.clone()),
+                timestamp,
+            });
+            Ok(format!("Transferred {} to {}", amount, recipient))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mycelial_network::NetworkCommand;
+    use mycelial_protocol::{PassingRule, QuorumMode};
+    use mycelial_state::{CreditLineRecord, VoteRecord};
+
+    use crate::test_support::test_state;
+
+    #[tokio::test]
+    async fn test_passed_treasury_spend_executes_once() {
+        let (state, mut commands) = test_state().await;
+        let mut events = state.event_tx.subscribe();
+        let id = Uuid::new_v4().to_string();
+        let proposal = ProposalRecord {
+            id: id.clone(),
+            proposer: state.local_peer_id.to_string(),
+            title: "Fund relay".to_string(),
+            description: "Pay for relay hosting".to_string(),
+            proposal_type: "treasury_spend".to_string(),
+            parameters: [("recipient", "relay-operator"), ("amount", "25")]
+                .into_iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect(),
+            status: "active".to_string(),
+            quorum: 1,
+            quorum_fraction: 0.5,
+            quorum_mode: QuorumMode::Snapshot,
+            passing_rule: PassingRule::SimpleMajority,
+            deadline: 0,
+            created_at: 0,
+            version: 1,
+        };
+        state.store.upsert_proposal(&proposal).await.unwrap();
+        state.store.mark_proposal_local(&id).await.unwrap();
+        let line_id = Uuid::new_v4().to_string();
+        state
+            .store
+            .insert_credit_line(&CreditLineRecord {
+                id: line_id.clone(),
+                creditor: "relay-operator".to_string(),
+                debtor: state.local_peer_id.to_string(),
+                limit: 100.0,
+                balance: 0.0,
+                created_at: 0,
+            })
+            .await
+            .unwrap();
+        state
+            .store
+            .record_vote(&VoteRecord {
+                proposal_id: id.clone(),
+                voter: state.local_peer_id.to_string(),
+                vote: "yes".to_string(),
+                weight: 1.0,
+                timestamp: 0,
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(finalize_proposal(&state, &id, 0).await, Ok(Some("passed")));
+        // Already closed, so nothing runs again
+        assert_eq!(finalize_proposal(&state, &id, 0).await, Ok(None));
+
+        let mut executed = 0;
+        while let Ok(event) = events.try_recv() {
+            if let WsMessage::ProposalExecuted { proposal_id, success, .. } = event {
+                assert_eq!(proposal_id, id);
+                assert!(success);
+                executed += 1;
+            }
+        }
+        assert_eq!(executed, 1);
+
+        match commands.try_recv() {
+            Ok(NetworkCommand::Publish { topic, data }) => {
+                assert_eq!(topic, topics::CREDIT);
+                let json: serde_json::Value = serde_json::from_slice(&data).unwrap();
+                assert_eq!(json["to"], "relay-operator");
+                assert_eq!(json["line_id"], line_id.as_str());
+                assert_eq!(json["correlation_id"], id.as_str());
+            }
+            other => panic!("expected publish, got {:?}", other),
+        }
+        assert!(commands.try_recv().is_err());
+        let line = state.store.get_credit_line(&line_id).await.unwrap().unwrap();
+        assert!((line.balance - 25.0).abs() < 1e-9);
+        assert_eq!(state.store.get_proposal(&id).await.unwrap().unwrap().status, "passed");
+    }
+
+    #[tokio::test]
+    async fn test_not_finalized_before_deadline() {
+        let (state, mut commands) = test_state().await;
+        let id = Uuid::new_v4().to_string();
+        state
+            .store
+            .upsert_proposal(&ProposalRecord {
+                id: id.clone(),
+                proposer: state.local_peer_id.to_string(),
+                title: "Fund relay".to_string(),
+                description: String::new(),
+                proposal_type: "treasury_spend".to_string(),
+                parameters: [("recipient", "relay-operator"), ("amount", "25")]
+                    .into_iter()
+                    .map(|(key, value)| (key.to_string(), value.to_string()))
+                    .collect(),
+                status: "active".to_string(),
+                quorum: 1,
+                quorum_fraction: 0.5,
+                quorum_mode: QuorumMode::Snapshot,
+                passing_rule: PassingRule::SimpleMajority,
+                deadline: 10_000,
+                created_at: 0,
+                version: 1,
+            })
+            .await
+            .unwrap();
+        state.store.mark_proposal_local(&id).await.unwrap();
+
+        assert!(finalize_proposal(&state, &id, 9_999).await.is_err());
+        assert_eq!(state.store.get_proposal(&id).await.unwrap().unwrap().status, "active");
+        assert!(commands.try_recv().is_err());
+
+        // No votes by the deadline, so it is rejected
+        assert_eq!(finalize_proposal(&state, &id, 10_000).await, Ok(Some("rejected")));
+    }
+
+    #[tokio::test]
+    async fn test_passed_parameter_change_updates_live_settings() {
+        let (state, _commands) = test_state().await;
+        let id = Uuid::new_v4().to_string();
+        state
+            .store
+            .upsert_proposal(&ProposalRecord {
+                id: id.clone(),
+                proposer: state.local_peer_id.to_string(),
+                title: "Tighten sybil warnings".to_string(),
+                description: "sybil_weight_cap = 100".to_string(),
+                proposal_type: "parameter_change".to_string(),
+                parameters: [("sybil_warning_bloc".to_string(), "2".to_string())].into_iter().collect(),
+                status: "active".to_string(),
+                quorum: 1,
+                quorum_fraction: 0.5,
+                quorum_mode: QuorumMode::Snapshot,
+                passing_rule: PassingRule::SimpleMajority,
+                deadline: 0,
+                created_at: 0,
+                version: 1,
+            })
+            .await
+            .unwrap();
+        state.store.mark_proposal_local(&id).await.unwrap();
+        state
+            .store
+            .record_vote(&VoteRecord {
+                proposal_id: id.clone(),
+                voter: state.local_peer_id.to_string(),
+                vote: "yes".to_string(),
+                weight: 1.0,
+                timestamp: 0,
+            })
+            .await
+            .unwrap();
+        let cap = state.governance_params.sybil_weight_cap();
+
+        assert_eq!(finalize_proposal(&state, &id, 0).await, Ok(Some("passed")));
+        assert_eq!(state.governance_params.sybil_warning_bloc(), 2);
+        // The description is never read as parameters
+        assert_eq!(state.governance_params.sybil_weight_cap(), cap);
+    }
+
+    #[tokio::test]
+    async fn test_received_proposal_naming_local_node_not_executed() {
+        let (state, mut commands) = test_state().await;
+        let mut events = state.event_tx.subscribe();
+        let id = Uuid::new_v4().to_string();
+        // Stored from gossip in the local node's name, never marked local
+        state
+            .store
+            .upsert_proposal(&ProposalRecord {
+                id: id.clone(),
+                proposer: state.local_peer_id.to_string(),
+                title: "Pay me".to_string(),
+                description: String::new(),
+                proposal_type: "treasury_spend".to_string(),
+                parameters: [("recipient", "mallory"), ("amount", "25")]
+                    .into_iter()
+                    .map(|(key, value)| (key.to_string(), value.to_string()))
+                    .collect(),
+                status: "active".to_string(),
+                quorum: 1,
+                quorum_fraction: 0.5,
+                quorum_mode: QuorumMode::Snapshot,
+                passing_rule: PassingRule::SimpleMajority,
+                deadline: 0,
+                created_at: 0,
+                version: 1,
+            })
+            .await
+            .unwrap();
+        state
+            .store
+            .record_vote(&VoteRecord {
+                proposal_id: id.clone(),
+                voter: "mallory".to_string(),
+                vote: "yes".to_string(),
+                weight: 1.0,
+                timestamp: 0,
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(finalize_proposal(&state, &id, 0).await, Ok(Some("passed")));
+        while let Ok(event) = events.try_recv() {
+            assert!(!matches!(event, WsMessage::ProposalExecuted { .. }));
+        }
+        assert!(commands.try_recv().is_err());
+    }
+}
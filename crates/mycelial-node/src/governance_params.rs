@@ -0,0 +1,131 @@
+//! Governance parameters
+//!
+//! A passed `parameter_change` proposal tunes one of a fixed set of runtime
+//! settings, each starting from the node's configuration. [`Parameter::parse`]
+//! is the whitelist: proposals naming any other key are refused when they are
+//! created, and nothing outside it is ever applied.
+
+use parking_lot::RwLock;
+use std::collections::HashMap;
+use std::time::Duration;
+
+use crate::config::ServerConfig;
+use crate::AppState;
+
+/// Keys of the parameters a proposal may change
+pub const PARAMETER_KEYS: [&str; 3] = ["proposal_cooldown_secs", "sybil_weight_cap", "sybil_warning_bloc"];
+
+/// A governance parameter with the value a proposal sets it to
+#[derive(Debug, Clone, PartialEq)]
+pub enum Parameter {
+    /// `proposal_cooldown_secs`: minimum time between proposals from one peer
+    ProposalCooldown(Duration),
+    /// `sybil_weight_cap`: maximum combined weight of low-reputation votes
+    SybilWeightCap(f64),
+    /// `sybil_warning_bloc`: low-reputation voters casting the same vote that trigger a warning
+    SybilWarningBloc(usize),
+}
+
+impl Parameter {
+    /// The parameter `key` set to `value`, if `key` is one proposals may change
+    pub fn parse(key: &str, value: &str) -> Result<Self, String> {
+        let invalid = || format!("Invalid value for {}: {}", key, value);
+        match key {
+            "proposal_cooldown_secs" => value
+                .parse::<u64>()
+                .map(|secs| Parameter::ProposalCooldown(Duration::from_secs(secs)))
+                .map_err(|_| invalid()),
+            "sybil_weight_cap" => value
+                .parse::<f64>()
+                .ok()
+                .filter(|cap| cap.is_finite() && *cap >= 0.0)
+                .map(Parameter::SybilWeightCap)
+                .ok_or_else(invalid),
+            "sybil_warning_bloc" => value
+                .parse::<usize>()
+                .ok()
+                .filter(|bloc| *bloc > 0)
+                .map(Parameter::SybilWarningBloc)
+                .ok_or_else(invalid),
+            _ => Err(format!(
+                "Unknown parameter {}, expected one of {}",
+                key,
+                PARAMETER_KEYS.join(", ")
+            )),
+        }
+    }
+
+    /// Put the new value into effect
+    pub fn apply(&self, state: &AppState) {
+        match *self {
+            Parameter::ProposalCooldown(cooldown) => state.proposal_cooldowns.set_cooldown(cooldown),
+            Parameter::SybilWeightCap(cap) => *state.governance_params.sybil_weight_cap.write() = cap,
+            Parameter::SybilWarningBloc(bloc) => *state.governance_params.sybil_warning_bloc.write() = bloc,
+        }
+    }
+}
+
+/// Live values of the tally settings proposals may change
+pub struct GovernanceParams {
+    sybil_weight_cap: RwLock<f64>,
+    sybil_warning_bloc: RwLock<usize>,
+}
+
+impl GovernanceParams {
+    /// Start from the configured values
+    pub fn new(config: &ServerConfig) -> Self {
+        Self {
+            sybil_weight_cap: RwLock::new(config.sybil_weight_cap),
+            sybil_warning_bloc: RwLock::new(config.sybil_warning_bloc),
+        }
+    }
+
+    /// Maximum combined weight of votes from voters below the reputation floor
+    pub fn sybil_weight_cap(&self) -> f64 {
+        *self.sybil_weight_cap.read()
+    }
+
+    /// Number of below-floor voters casting the same vote that triggers a warning
+    pub fn sybil_warning_bloc(&self) -> usize {
+        *self.sybil_warning_bloc.read()
+    }
+}
+
+/// Current value of every parameter, keyed as proposals name them
+pub fn current(state: &AppState) -> HashMap<String, String> {
+    HashMap::from([
+        (
+            "proposal_cooldown_secs".to_string(),
+            state.proposal_cooldowns.cooldown().as_secs().to_string(),
+        ),
+        (
+            "sybil_weight_cap".to_string(),
+            state.governance_params.sybil_weight_cap().to_string(),
+        ),
+        (
+            "sybil_warning_bloc".to_string(),
+            state.governance_params.sybil_warning_bloc().to_string(),
+        ),
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_only_whitelisted_parameters_parse() {
+        assert_eq!(
+            Parameter::parse("proposal_cooldown_secs", "300"),
+            Ok(Parameter::ProposalCooldown(Duration::from_secs(300)))
+        );
+        assert_eq!(Parameter::parse("sybil_weight_cap", "2.5"), Ok(Parameter::SybilWeightCap(2.5)));
+        assert_eq!(Parameter::parse("sybil_warning_bloc", "4"), Ok(Parameter::SybilWarningBloc(4)));
+
+        assert!(Parameter::parse("recipient", "12D3KooW").is_err());
+        assert!(Parameter::parse("proposal_cooldown_secs", "-1").is_err());
+        assert!(Parameter::parse("sybil_weight_cap", "NaN").is_err());
+        assert!(Parameter::parse("sybil_weight_cap", "-1").is_err());
+        assert!(Parameter::parse("sybil_warning_bloc", "0").is_err());
+    }
+}
@@ -5,23 +5,65 @@
 //! - WebSocket server for real-time dashboard updates
 //! - REST API for peer and network information
 
+mod anti_entropy;
+mod chat_edits;
+mod config;
+mod cooldown;
+mod ephemeral_rooms;
+mod event_log;
+mod execution;
+mod governance_params;
+mod naming;
+mod presence;
+mod proposal_types;
+mod network_errors;
+mod peer_ids;
+mod reminders;
+mod replay;
+mod resource_units;
 mod server;
+mod stats_history;
+mod vouch_reputation;
+#[cfg(test)]
+mod test_support;
 
 use clap::Parser;
-use parking_lot::RwLock;
+use parking_lot::{Mutex, RwLock};
+use std::collections::HashSet;
 use std::sync::Arc;
-use std::sync::atomic::AtomicU64;
-use std::time::Instant;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
 use tokio::sync::broadcast;
-use tracing::{info, warn, error, Level};
+use tracing::{debug, info, warn, error, Level};
 use tracing_subscriber::FmtSubscriber;
 
+use mycelial_core::message::{Message, MessageType};
 use mycelial_core::peer::{PeerId, PeerInfo};
 use mycelial_core::reputation::Reputation;
 use mycelial_network::{NetworkService, NetworkHandle, NetworkConfig, NetworkEvent, Keypair, Libp2pPeerId};
 use mycelial_network::{is_economics_topic, parse_economics_message, EconomicsEvent};
-use mycelial_state::SqliteStore;
-use server::messages::{WsMessage, ContributorEntry};
+use mycelial_protocol::topics;
+use mycelial_state::{SqliteStore, ContributionRecord, CreditLineRecord, CreditTransferRecord, PaymentRequestRecord, ProposalRecord, VoteRecord, VouchRecord};
+use anti_entropy::{PendingSyncs, ServedSyncs};
+use config::ServerConfig;
+use cooldown::ProposalCooldowns;
+use ephemeral_rooms::{EphemeralRooms, EPHEMERAL_ROOM_IDLE};
+use event_log::EventLogFence;
+use execution::ExecutionRegistry;
+use governance_params::GovernanceParams;
+use naming::{FallbackName, NameCache};
+use network_errors::NetworkErrorLimiter;
+use reminders::ReminderTracker;
+use replay::{replay_key, MessageCategory, ReplayGuard};
+use server::connections::ConnectionRegistry;
+use server::peer_delta::PeerDeltaBatcher;
+use server::resume::ResumeTokens;
+use server::subscriptions::TopicSubscribers;
+use server::messages::{WsMessage, ContributorEntry, PeerListEntry};
+use mycelial_state::governance::received_quorum;
+use stats_history::TopicCounts;
+use server::governance::{eligible_voters, proposal_update, quorum_progress, vote_label, voter_weight};
+use server::resources::{pool_update, resource_type_label};
 
 #[derive(Parser)]
 #[command(name = "mycelial-node")]
@@ -54,6 +96,70 @@ struct Args {
     /// Enable verbose logging
     #[arg(long, short)]
     verbose: bool,
+
+    /// Seconds an inbound message ID is remembered for replay protection
+    #[arg(long, default_value_t = 600)]
+    replay_window_secs: u64,
+
+    /// Maximum active subscriptions per WebSocket connection
+    #[arg(long, default_value_t = 64)]
+    max_subscriptions: usize,
+
+    /// Allow dashboard clients to enable debug capabilities (raw protocol messages)
+    #[arg(long)]
+    debug_protocol: bool,
+
+    /// Attempt economics actions even when no peers are connected
+    #[arg(long)]
+    allow_isolated_publish: bool,
+
+    /// Token dashboard clients present to use admin commands (disabled if unset)
+    #[arg(long)]
+    admin_token: Option<String>,
+
+    /// Seconds a peer must wait between proposals (0 disables the cooldown)
+    #[arg(long, default_value_t = 0)]
+    proposal_cooldown_secs: u64,
+
+    /// did:key of the signer whose signature economics actions require (unsigned if unset)
+    #[arg(long)]
+    action_signer: Option<String>,
+
+    /// How peers without a known name are labelled
+    #[arg(long, value_enum, default_value_t = FallbackName::ShortId)]
+    fallback_name: FallbackName,
+
+    /// Namespace for gossip topics, isolating this network from others (none if unset)
+    #[arg(long, default_value = "")]
+    topic_namespace: String,
+
+    /// Token dashboard clients must present to open a WebSocket connection
+    #[arg(long)]
+    ws_token: Option<String>,
+
+    /// Accept WebSocket connections without a token (local development only)
+    #[arg(long)]
+    open_websocket: bool,
+
+    /// Events buffered for WebSocket clients before slow ones start missing some
+    #[arg(long, default_value_t = 256)]
+    broadcast_capacity: usize,
+
+    /// Largest WebSocket message accepted from a dashboard client, in bytes
+    #[arg(long, default_value_t = 64 * 1024)]
+    max_frame_bytes: usize,
+
+    /// Seconds between stats pushed to every client (only sent on request if unset)
+    #[arg(long)]
+    stats_push_secs: Option<u64>,
+
+    /// Publish a leave notice when the last dashboard session for a peer closes
+    #[arg(long)]
+    announce_presence: bool,
+
+    /// Topic presence notices are published on
+    #[arg(long, default_value = topics::PRESENCE)]
+    presence_topic: String,
 }
 
 /// Application state shared across handlers
@@ -68,12 +174,122 @@ pub struct AppState {
     pub event_tx: broadcast::Sender<WsMessage>,
     /// Message counter
     pub message_count: AtomicU64,
+    /// Message counters by topic
+    pub topic_counts: TopicCounts,
+    /// Deepest the broadcast channel's queue has been seen
+    pub broadcast_high_water: AtomicUsize,
     /// Node start time
     pub start_time: Instant,
-    /// Node name
-    pub node_name: String,
+    /// Node name, changeable from the dashboard
+    pub node_name: RwLock<String>,
+    /// Addresses the P2P network is listening on, as they are reported
+    pub listen_addresses: RwLock<Vec<String>>,
     /// Subscribed topics
     pub subscribed_topics: RwLock<Vec<String>>,
+    /// Peers with at least one open connection
+    pub connected_peers: RwLock<HashSet<String>>,
+    /// Server configuration
+    pub config: ServerConfig,
+    /// Seen-ID tracking for inbound network messages
+    pub replay_guard: ReplayGuard,
+    /// Proposal reminders already emitted
+    pub reminders: ReminderTracker,
+    /// Open WebSocket connections
+    pub connections: ConnectionRegistry,
+    /// When each peer last created a proposal
+    pub proposal_cooldowns: ProposalCooldowns,
+    /// Rooms whose chat is relayed but never persisted
+    pub ephemeral_rooms: EphemeralRooms,
+    /// Proposals already flagged for possible sybil voting
+    pub sybil_warnings: Mutex<HashSet<String>>,
+    /// Lets event log reads wait for pending writes
+    pub event_log_fence: EventLogFence,
+    /// Governance sync requests awaiting responses
+    pub pending_syncs: PendingSyncs,
+    /// When each peer's sync request was last answered
+    pub served_syncs: ServedSyncs,
+    /// Handlers that carry out passed proposals, by proposal type
+    pub executors: ExecutionRegistry,
+    /// Tally settings changeable by passed `parameter_change` proposals
+    pub governance_params: GovernanceParams,
+    /// Signs and checks reconnection tokens
+    pub resume_tokens: ResumeTokens,
+    /// When each network operation's failure was last reported
+    pub network_errors: NetworkErrorLimiter,
+    /// Connections subscribed to each client-requested topic
+    pub topic_subscribers: TopicSubscribers,
+    /// Batches peer membership changes into `PeerDelta` broadcasts
+    pub peer_deltas: PeerDeltaBatcher,
+}
+
+impl AppState {
+    /// Create application state with a fresh broadcast channel
+    pub fn new(
+        local_peer_id: PeerId,
+        network: NetworkHandle,
+        store: SqliteStore,
+        node_name: String,
+        config: ServerConfig,
+    ) -> Self {
+        let (event_tx, _) = broadcast::channel(config.broadcast_capacity.max(1));
+        let peer_deltas = PeerDeltaBatcher::new(event_tx.clone(), config.peer_delta_window);
+        Self {
+            local_peer_id,
+            network,
+            store,
+            event_tx,
+            message_count: AtomicU64::new(0),
+            topic_counts: TopicCounts::default(),
+            broadcast_high_water: AtomicUsize::new(0),
+            start_time: Instant::now(),
+            node_name: RwLock::new(node_name),
+            listen_addresses: RwLock::new(Vec::new()),
+            subscribed_topics: RwLock::new(Vec::new()),
+            connected_peers: RwLock::new(HashSet::new()),
+            replay_guard: ReplayGuard::new(config.replay_capacity, config.replay_window),
+            reminders: ReminderTracker::default(),
+            connections: ConnectionRegistry::default(),
+            proposal_cooldowns: ProposalCooldowns::new(
+                config.proposal_cooldown,
+                config.proposal_cooldown_exempt_peers.iter().cloned().collect(),
+            ),
+            ephemeral_rooms: EphemeralRooms::new(EPHEMERAL_ROOM_IDLE),
+            sybil_warnings: Mutex::new(HashSet::new()),
+            event_log_fence: EventLogFence::default(),
+            pending_syncs: PendingSyncs::default(),
+            served_syncs: ServedSyncs::default(),
+            executors: ExecutionRegistry::default(),
+            governance_params: GovernanceParams::new(&config),
+            resume_tokens: ResumeTokens::new(config.resume_token_ttl),
+            network_errors: NetworkErrorLimiter::new(config.network_error_interval),
+            topic_subscribers: TopicSubscribers::default(),
+            peer_deltas,
+            config,
+        }
+    }
+
+    /// A topic as published and subscribed under the configured namespace
+    pub fn wire_topic(&self, topic: &str) -> String {
+        topics::namespaced(&self.config.topic_namespace, topic)
+    }
+
+    /// The node's current name
+    pub fn node_name(&self) -> String {
+        self.node_name.read().clone()
+    }
+
+    /// Events queued in the broadcast channel that the slowest receiver has
+    /// yet to take, recording a new high-water mark if it is one
+    pub fn sample_broadcast_depth(&self) -> usize {
+        let depth = self.event_tx.len();
+        self.broadcast_high_water.fetch_max(depth, Ordering::Relaxed);
+        depth
+    }
+
+    /// Whether the node has no connected peers to gossip with
+    pub fn is_isolated(&self) -> bool {
+        self.connected_peers.read().is_empty()
+    }
 }
 
 #[tokio::main]
@@ -111,6 +327,9 @@ async fn main() -> anyhow::Result<()> {
     let db_url = format!("sqlite:{}?mode=rwc", args.db);
     let store = SqliteStore::new(&db_url).await?;
     info!("Database initialized: {}", args.db);
+    if let Err(e) = resource_units::migrate_stored(&store).await {
+        warn!("Failed to convert stored contribution units: {}", e);
+    }
 
     // Configure network
     // Port 0 tells the OS to assign an available port automatically
@@ -119,6 +338,8 @@ async fn main() -> anyhow::Result<()> {
         format!("/ip4/0.0.0.0/tcp/{}", p2p_port),
         format!("/ip4/0.0.0.0/udp/{}/quic-v1", if p2p_port == 0 { 0 } else { p2p_port + 1 }),
     ];
+    config.topic_namespace = args.topic_namespace.clone();
+    config.presence_topic = args.announce_presence.then(|| args.presence_topic.clone());
 
     if p2p_port == 0 {
         info!("P2P port: auto-assign (OS will select available port)");
@@ -136,20 +357,39 @@ async fn main() -> anyhow::Result<()> {
 
     info!("Network service created");
 
-    // Create broadcast channel for WebSocket events
-    let (event_tx, _) = broadcast::channel(256);
+    let server_config = ServerConfig {
+        replay_window: Duration::from_secs(args.replay_window_secs),
+        max_subscriptions_per_connection: args.max_subscriptions,
+        allow_debug_capabilities: args.debug_protocol,
+        reject_when_isolated: !args.allow_isolated_publish,
+        admin_token: args.admin_token.clone(),
+        proposal_cooldown: Duration::from_secs(args.proposal_cooldown_secs),
+        action_signer: args.action_signer.clone(),
+        fallback_name: args.fallback_name,
+        topic_namespace: args.topic_namespace.clone(),
+        ws_token: args.ws_token.clone(),
+        open_websocket: args.open_websocket,
+        broadcast_capacity: args.broadcast_capacity,
+        max_frame_size: args.max_frame_bytes,
+        stats_push_interval: args.stats_push_secs.filter(|&secs| secs > 0).map(Duration::from_secs),
+        announce_presence: args.announce_presence,
+        presence_topic: args.presence_topic.clone(),
+        ..ServerConfig::default()
+    };
+    if server_config.open_websocket {
+        warn!("WebSocket connections are accepted without a token (--open-websocket)");
+    } else if server_config.ws_token.is_none() {
+        warn!("No --ws-token set; all WebSocket connections will be rejected");
+    }
 
     // Create shared state
-    let state = Arc::new(AppState {
-        local_peer_id: local_peer_id.clone(),
-        network: network_handle.clone(),
+    let state = Arc::new(AppState::new(
+        local_peer_id.clone(),
+        network_handle.clone(),
         store,
-        event_tx: event_tx.clone(),
-        message_count: AtomicU64::new(0),
-        start_time: Instant::now(),
-        node_name: args.name.clone(),
-        subscribed_topics: RwLock::new(Vec::new()),
-    });
+        args.name.clone(),
+        server_config,
+    ));
 
     // Spawn network service
     tokio::spawn(async move {
@@ -158,6 +398,23 @@ async fn main() -> anyhow::Result<()> {
         }
     });
 
+    // Persist broadcast events for replay
+    tokio::spawn(event_log::run_event_recorder(state.clone()));
+
+    // Remind clients of proposals approaching their deadline
+    tokio::spawn(reminders::run_reminder_scheduler(state.clone()));
+
+    // Close voting on proposals whose deadline has passed
+    tokio::spawn(execution::run_deadline_closer(state.clone()));
+
+    // Sample stats for history charts
+    tokio::spawn(stats_history::run_stats_recorder(state.clone()));
+
+    // Push live stats to dashboards, if configured
+    if let Some(interval) = state.config.stats_push_interval {
+        tokio::spawn(stats_history::run_stats_push(state.clone(), interval));
+    }
+
     // Spawn network event handler
     let event_state = state.clone();
     let peer_id_for_events = libp2p_peer_id;
@@ -181,12 +438,62 @@ async fn main() -> anyhow::Result<()> {
     info!("  REST API: http://127.0.0.1:{}/api/", actual_http_port);
     info!("═══════════════════════════════════════════════════════════");
 
-    let app = server::create_router(state);
-    axum::serve(listener, app).await?;
+    let app = server::create_router(state.clone());
+    axum::serve(listener, app)
+        .with_graceful_shutdown(shutdown_signal(state))
+        .await?;
 
     Ok(())
 }
 
+/// Wait for Ctrl-C or SIGTERM, then close dashboard connections cleanly
+///
+/// Each connection is sent a `Shutdown` message and a Close frame; this
+/// returns once they have all closed or the grace period runs out.
+async fn shutdown_signal(state: Arc<AppState>) {
+    let ctrl_c = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            error!("Failed to listen for Ctrl-C: {}", e);
+            std::future::pending::<()>().await;
+        }
+    };
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(e) => {
+                error!("Failed to listen for SIGTERM: {}", e);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {}
+        _ = terminate => {}
+    }
+
+    let reconnect_after_ms = state.config.shutdown_reconnect_after.as_millis() as u64;
+    let signalled = state.connections.shutdown("Node is shutting down", reconnect_after_ms);
+    info!("Shutting down, closing {} WebSocket connections", signalled);
+    let closed = async {
+        while !state.connections.is_empty() {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+    };
+    if tokio::time::timeout(state.config.shutdown_grace, closed).await.is_err() {
+        warn!("Some WebSocket connections did not close within {:?}", state.config.shutdown_grace);
+        // Their sessions won't get to announce leaving before the node exits
+        for peer_id in state.connections.peers() {
+            presence::announce_leave(&state, &peer_id).await;
+        }
+    }
+}
+
 /// Handle events from the P2P network
 async fn handle_network_event(event: NetworkEvent, state: &AppState, local_peer_id: Libp2pPeerId) {
     match event {
@@ -194,17 +501,17 @@ async fn handle_network_event(event: NetworkEvent, state: &AppState, local_peer_
             info!("Peer connected: {} (total: {})", peer_id, num_connections);
 
             let core_peer_id = PeerId(peer_id.to_base58());
-            let short_id = &peer_id.to_base58()[..8.min(peer_id.to_base58().len())];
 
             // Create peer info
-            // Use peer_id's base58 as public_key (PeerId is derived from public key)
+            // Use peer_id's base58 as public_key (PeerId is derived from public key).
+            // No name is stored, so a name the peer set earlier is kept.
             let peer_info = PeerInfo {
                 id: core_peer_id.clone(),
                 public_key: peer_id.to_base58(),
                 addresses: vec![],
                 first_seen: chrono::Utc::now(),
                 last_seen: chrono::Utc::now(),
-                name: Some(format!("Peer-{}", short_id)),
+                name: None,
             };
 
             // Store peer with default reputation
@@ -212,23 +519,73 @@ async fn handle_network_event(event: NetworkEvent, state: &AppState, local_peer_
                 warn!("Failed to store peer: {}", e);
             }
 
-            // Broadcast to dashboard
-            let _ = state.event_tx.send(WsMessage::PeerJoined {
-                peer_id: peer_id.to_base58(),
-                name: peer_info.name.clone(),
-            });
+            let was_isolated = state.is_isolated();
+            let (joined, connected) = {
+                let mut peers = state.connected_peers.write();
+                let joined = peers.insert(peer_id.to_base58());
+                (joined, peers.len())
+            };
+
+            // Broadcast to dashboard only for the peer's first connection
+            if joined {
+                let name = naming::resolve_display_name(state, &peer_id.to_base58()).await;
+                let entry = match state.store.get_peer(&peer_id.to_base58()).await {
+                    Ok(Some(found)) => PeerListEntry::from(found),
+                    _ => PeerListEntry {
+                        id: peer_id.to_base58(),
+                        name: None,
+                        reputation: Reputation::default().score,
+                        addresses: vec![],
+                    },
+                };
+                state.peer_deltas.joined(PeerListEntry { name: Some(name.clone()), ..entry });
+                let _ = state.event_tx.send(WsMessage::PeerJoined {
+                    peer_id: peer_id.to_base58(),
+                    name: Some(name),
+                });
+            }
+
+            if was_isolated {
+                info!("Network connectivity restored");
+                let _ = state.event_tx.send(WsMessage::NetworkStatus {
+                    isolated: false,
+                    connected_peers: connected,
+                });
+            }
         }
 
         NetworkEvent::PeerDisconnected { peer_id, num_connections } => {
             info!("Peer disconnected: {} (remaining: {})", peer_id, num_connections);
-            let _ = state.event_tx.send(WsMessage::PeerLeft {
-                peer_id: peer_id.to_base58(),
-            });
+
+            // The peer only leaves once its last connection closes
+            if num_connections == 0 {
+                let removed = state.connected_peers.write().remove(&peer_id.to_base58());
+                if removed {
+                    state.peer_deltas.left(&peer_id.to_base58());
+                    let _ = state.event_tx.send(WsMessage::PeerLeft {
+                        peer_id: peer_id.to_base58(),
+                    });
+                }
+                if removed && state.is_isolated() {
+                    warn!("Network isolated: no connected peers");
+                    let _ = state.event_tx.send(WsMessage::NetworkStatus {
+                        isolated: true,
+                        connected_peers: 0,
+                    });
+                }
+            }
         }
 
         NetworkEvent::MessageReceived { message_id, topic, source, data, timestamp } => {
+            // Topics are handled without the namespace; other namespaces are ignored
+            let Some(topic) = topics::strip_namespace(&state.config.topic_namespace, &topic).map(str::to_string) else {
+                debug!("Ignoring message on {} outside namespace", topic);
+                return;
+            };
+
             // Update message count
             state.message_count.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            state.topic_counts.record(&topic);
 
             let from_id = source.map(|p| p.to_base58()).unwrap_or_else(|| "unknown".to_string());
             let ts = timestamp.timestamp_millis();
@@ -236,28 +593,110 @@ async fn handle_network_event(event: NetworkEvent, state: &AppState, local_peer_
             // Check if this is an economics protocol message
             if is_economics_topic(&topic) {
                 if let Some(econ_event) = parse_economics_message(&topic, &data) {
+                    // Only its author may publish a message acting for a peer
+                    if let Some(author) = replay::author(&econ_event) {
+                        if source.is_some() && from_id != author {
+                            warn!("Ignoring message by {} published by {}", author, from_id);
+                            return;
+                        }
+                    }
+                    if econ_event.signature().is_some_and(|signature| !signature.verify()) {
+                        warn!("Ignoring message from {} with an invalid action signature", from_id);
+                        return;
+                    }
+                    let (category, key) = replay_key(&econ_event);
+                    if !state.replay_guard.check_and_record(category, &key) {
+                        debug!("Ignoring replayed {:?} message {}", category, key);
+                        return;
+                    }
+
+                    if state.config.allow_debug_capabilities {
+                        let json = serde_json::from_slice(&data).unwrap_or(serde_json::Value::Null);
+                        let _ = state.event_tx.send(WsMessage::RawProtocol {
+                            topic: topic.clone(),
+                            json,
+                        });
+                    }
+
                     match econ_event {
                         EconomicsEvent::Vouch(vouch_msg) => {
                             use mycelial_protocol::VouchMessage;
                             match vouch_msg {
                                 VouchMessage::VouchRequest(req) => {
+                                    if req.voucher == req.vouchee {
+                                        warn!("Ignoring self-vouch by {}", req.voucher);
+                                        return;
+                                    }
+                                    let record = VouchRecord {
+                                        id: req.id.to_string(),
+                                        voucher: req.voucher.clone(),
+                                        vouchee: req.vouchee.clone(),
+                                        weight: req.stake,
+                                        message: req.message.clone(),
+                                        status: "pending".to_string(),
+                                        created_at: req.timestamp.timestamp_millis(),
+                                    };
+                                    if let Err(e) = state.store.insert_vouch(&record).await {
+                                        warn!("Failed to store vouch: {}", e);
+                                    }
+                                    let mut names = NameCache::default();
                                     let _ = state.event_tx.send(WsMessage::VouchRequest {
                                         id: req.id.to_string(),
+                                        voucher_name: names.display_name(state, &req.voucher).await,
+                                        vouchee_name: names.display_name(state, &req.vouchee).await,
                                         voucher: req.voucher,
                                         vouchee: req.vouchee,
                                         weight: req.stake,
+                                        correlation_id: req.correlation_id,
                                         timestamp: ts,
                                     });
                                 }
                                 VouchMessage::VouchAck(ack) => {
+                                    // Only the vouchee may answer a vouch
+                                    let vouchee = match state.store.get_vouch(&ack.vouch_id.to_string()).await {
+                                        Ok(vouch) => vouch.map(|vouch| vouch.vouchee),
+                                        Err(e) => {
+                                            warn!("Failed to load vouch {}: {}", ack.vouch_id, e);
+                                            return;
+                                        }
+                                    };
+                                    if vouchee.as_deref() != Some(ack.from.as_str()) {
+                                        warn!("Ignoring response to vouch {} by {}", ack.vouch_id, ack.from);
+                                        return;
+                                    }
+                                    let new_reputation = vouch_reputation::record_vouch_response(
+                                        &state,
+                                        &ack.vouch_id.to_string(),
+                                        ack.accepted,
+                                        ts,
+                                    )
+                                    .await;
                                     let _ = state.event_tx.send(WsMessage::VouchAck {
                                         id: message_id.to_string(),
                                         request_id: ack.vouch_id.to_string(),
                                         accepted: ack.accepted,
-                                        new_reputation: None,
+                                        new_reputation,
+                                        correlation_id: ack.correlation_id,
                                         timestamp: ts,
                                     });
                                 }
+                                VouchMessage::VouchRevoke(revoke) => {
+                                    let timestamp = revoke.timestamp.timestamp_millis();
+                                    if vouch_reputation::record_vouch_revocation(
+                                        &state,
+                                        &revoke.voucher,
+                                        &revoke.vouchee,
+                                        timestamp,
+                                    )
+                                    .await
+                                    {
+                                        let _ = state.event_tx.send(WsMessage::VouchRevoked {
+                                            voucher: revoke.voucher,
+                                            vouchee: revoke.vouchee,
+                                            timestamp,
+                                        });
+                                    }
+                                }
                                 VouchMessage::ReputationUpdate(update) => {
                                     let _ = state.event_tx.send(WsMessage::ReputationUpdate {
                                         peer_id: update.peer_id,
@@ -270,22 +709,94 @@ async fn handle_network_event(event: NetworkEvent, state: &AppState, local_peer_
                             use mycelial_protocol::CreditMessage;
                             match credit_msg {
                                 CreditMessage::CreateLine(line) => {
+                                    let record = CreditLineRecord {
+                                        id: line.id.to_string(),
+                                        creditor: line.creditor.clone(),
+                                        debtor: line.debtor.clone(),
+                                        limit: line.limit,
+                                        balance: 0.0,
+                                        created_at: line.timestamp.timestamp_millis(),
+                                    };
+                                    if let Err(e) = state.store.insert_credit_line(&record).await {
+                                        warn!("Failed to store credit line: {}", e);
+                                    }
                                     let _ = state.event_tx.send(WsMessage::CreditLine {
                                         id: line.id.to_string(),
                                         creditor: line.creditor,
                                         debtor: line.debtor,
                                         limit: line.limit,
                                         balance: 0.0,
+                                        correlation_id: line.correlation_id,
                                         timestamp: ts,
                                     });
                                 }
                                 CreditMessage::Transfer(transfer) => {
+                                    let transfer_id = transfer.id.to_string();
+                                    let line_id = transfer.line_id.to_string();
+                                    let record = CreditTransferRecord {
+                                        id: transfer_id.clone(),
+                                        line_id: line_id.clone(),
+                                        from: transfer.from.clone(),
+                                        to: transfer.to.clone(),
+                                        amount: transfer.amount,
+                                        memo: transfer.memo.clone(),
+                                        created_at: ts,
+                                    };
+                                    // Drawn within the line's limit, or dropped
+                                    match state.store.record_credit_transfer(&record).await {
+                                        Ok(true) => {}
+                                        Ok(false) => {
+                                            warn!(
+                                                "Ignoring transfer {} of {} on credit line {}: not a line between them, \
+                                                 already seen, or over its limit",
+                                                transfer_id, transfer.amount, line_id
+                                            );
+                                            return;
+                                        }
+                                        Err(e) => {
+                                            warn!("Failed to update credit line {}: {}", line_id, e);
+                                            return;
+                                        }
+                                    }
+                                    if let Some(ref r) = transfer.request_ref {
+                                        if let Err(e) = state.store.settle_payment_request(r, &record).await {
+                                            warn!("Failed to settle payment request {}: {}", r, e);
+                                        }
+                                    }
+                                    let mut names = NameCache::default();
                                     let _ = state.event_tx.send(WsMessage::CreditTransfer {
-                                        id: transfer.id.to_string(),
+                                        id: transfer_id,
+                                        from_name: names.display_name(state, &transfer.from).await,
+                                        to_name: names.display_name(state, &transfer.to).await,
                                         from: transfer.from,
                                         to: transfer.to,
                                         amount: transfer.amount,
                                         memo: transfer.memo,
+                                        request_ref: transfer.request_ref,
+                                        correlation_id: transfer.correlation_id,
+                                        timestamp: ts,
+                                    });
+                                }
+                                CreditMessage::PaymentRequest(request) => {
+                                    let record = PaymentRequestRecord {
+                                        id: request.id.to_string(),
+                                        requester: request.requester,
+                                        payer: request.payer,
+                                        amount: request.amount,
+                                        memo: request.memo,
+                                        created_at: request.timestamp.timestamp_millis(),
+                                        settled_by: None,
+                                    };
+                                    if let Err(e) = state.store.insert_payment_request(&record).await {
+                                        warn!("Failed to store payment request: {}", e);
+                                    }
+                                    let _ = state.event_tx.send(WsMessage::PaymentRequest {
+                                        id: record.id,
+                                        requester: record.requester,
+                                        payer: record.payer,
+                                        amount: record.amount,
+                                        memo: record.memo,
+                                        correlation_id: request.correlation_id,
                                         timestamp: ts,
                                     });
                                 }
@@ -303,65 +814,245 @@ async fn handle_network_event(event: NetworkEvent, state: &AppState, local_peer_
                             use mycelial_protocol::GovernanceMessage;
                             match gov_msg {
                                 GovernanceMessage::CreateProposal(proposal) => {
-                                    // quorum is f64 (0.0-1.0), convert to percentage as u32
-                                    let quorum_pct = (proposal.quorum * 100.0) as u32;
+                                    // Measured by receipt time so a proposer can't backdate around it
+                                    if let Some(left) = state.proposal_cooldowns.remaining(&proposal.proposer, ts) {
+                                        warn!(
+                                            "Ignoring proposal {} from {}: cooldown has {}s left",
+                                            proposal.id, proposal.proposer, left.as_secs()
+                                        );
+                                        return;
+                                    }
+                                    if let Err(e) = proposal.passing_rule.validate() {
+                                        warn!("Ignoring proposal {} with invalid passing rule: {}", proposal.id, e);
+                                        return;
+                                    }
+                                    // Unless the proposer fixed a count reachable here, quorum is
+                                    // a fraction (0.0-1.0) of the voters eligible here
+                                    let eligible = eligible_voters(state).await;
+                                    let required = received_quorum(proposal.quorum_voters, proposal.quorum, eligible);
+                                    let (proposal_type, parameters) = proposal_types::from_protocol(&proposal.proposal_type);
+                                    let record = ProposalRecord {
+                                        id: proposal.id.to_string(),
+                                        proposer: proposal.proposer.clone(),
+                                        title: proposal.title.clone(),
+                                        description: proposal.description.clone(),
+                                        proposal_type,
+                                        parameters,
+                                        status: "active".to_string(),
+                                        quorum: required,
+                                        quorum_fraction: proposal.quorum,
+                                        quorum_mode: proposal.quorum_mode,
+                                        passing_rule: proposal.passing_rule,
+                                        deadline: proposal.deadline.timestamp_millis(),
+                                        created_at: proposal.timestamp.timestamp_millis(),
+                                        version: 1,
+                                    };
+                                    // A known proposal is never replaced; amendments have their own path
+                                    match state.store.insert_proposal(&record).await {
+                                        Ok(true) => state.proposal_cooldowns.record(&proposal.proposer, ts),
+                                        Ok(false) => {
+                                            debug!("Ignoring already known proposal {}", proposal.id);
+                                            return;
+                                        }
+                                        Err(e) => {
+                                            warn!("Failed to store proposal: {}", e);
+                                            return;
+                                        }
+                                    }
                                     let _ = state.event_tx.send(WsMessage::Proposal {
                                         id: proposal.id.to_string(),
+                                        proposer_name: naming::resolve_display_name(state, &proposal.proposer).await,
                                         proposer: proposal.proposer,
                                         title: proposal.title,
                                         description: proposal.description,
-                                        proposal_type: format!("{:?}", proposal.proposal_type),
+                                        proposal_type: record.proposal_type,
+                                        parameters: record.parameters,
                                         status: "active".to_string(),
                                         yes_votes: 0,
                                         no_votes: 0,
-                                        quorum: quorum_pct,
+                                        quorum: required,
                                         deadline: proposal.deadline.timestamp_millis(),
+                                        version: 1,
+                                        correlation_id: proposal.correlation_id,
                                         timestamp: ts,
                                     });
                                 }
                                 GovernanceMessage::CastVote(vote) => {
+                                    // Only open proposals take votes
+                                    match state.store.get_proposal(&vote.proposal_id.to_string()).await {
+                                        Ok(Some(proposal)) if proposal.accepts_votes(ts) => {}
+                                        Ok(_) => {
+                                            debug!("Ignoring vote on unknown or closed proposal {}", vote.proposal_id);
+                                            return;
+                                        }
+                                        Err(e) => {
+                                            warn!("Failed to load proposal {}: {}", vote.proposal_id, e);
+                                            return;
+                                        }
+                                    }
+                                    // Weighed by the reputation known here, not the claimed weight
+                                    let weight = voter_weight(state, &vote.voter).await;
+                                    let record = VoteRecord {
+                                        proposal_id: vote.proposal_id.to_string(),
+                                        voter: vote.voter.clone(),
+                                        vote: vote_label(&vote.vote).to_string(),
+                                        weight,
+                                        timestamp: vote.timestamp.timestamp_millis(),
+                                    };
+                                    if let Err(e) = state.store.record_vote(&record).await {
+                                        warn!("Failed to store vote: {}", e);
+                                    }
                                     let _ = state.event_tx.send(WsMessage::VoteCast {
                                         id: message_id.to_string(),
                                         proposal_id: vote.proposal_id.to_string(),
+                                        voter_name: naming::resolve_display_name(state, &vote.voter).await,
                                         voter: vote.voter,
                                         vote: format!("{:?}", vote.vote),
-                                        weight: vote.weight,
+                                        weight,
+                                        correlation_id: vote.correlation_id,
                                         timestamp: ts,
                                     });
+                                    if let Some(progress) = quorum_progress(state, &record.proposal_id).await {
+                                        let _ = state.event_tx.send(progress);
+                                    }
+                                    if let Some(update) = proposal_update(state, &record.proposal_id).await {
+                                        let _ = state.event_tx.send(update);
+                                    }
                                 }
                                 GovernanceMessage::ProposalUpdate(update) => {
                                     // votes_for/against are f64 (weighted), convert to u32 counts
                                     let _ = state.event_tx.send(WsMessage::Proposal {
                                         id: update.proposal_id.to_string(),
                                         proposer: "".to_string(),
+                                        proposer_name: "".to_string(),
                                         title: "".to_string(),
                                         description: "".to_string(),
                                         proposal_type: "".to_string(),
+                                        parameters: Default::default(),
                                         status: format!("{:?}", update.status),
                                         yes_votes: update.votes_for as u32,
                                         no_votes: update.votes_against as u32,
                                         quorum: 0,
                                         deadline: 0,
+                                        version: 0,
+                                        correlation_id: None,
                                         timestamp: ts,
                                     });
                                 }
                                 GovernanceMessage::ProposalExecuted(_) => {
                                     // Handle proposal execution if needed
                                 }
+                                GovernanceMessage::AmendProposal(amendment) => {
+                                    let proposal_id = amendment.proposal_id.to_string();
+                                    let record = match state.store.get_proposal(&proposal_id).await {
+                                        Ok(Some(record)) if record.proposer == amendment.proposer => record,
+                                        Ok(_) => {
+                                            debug!("Ignoring amendment to unknown or foreign proposal {}", proposal_id);
+                                            return;
+                                        }
+                                        Err(e) => {
+                                            warn!("Failed to load proposal {}: {}", proposal_id, e);
+                                            return;
+                                        }
+                                    };
+                                    match state
+                                        .store
+                                        .amend_proposal(&proposal_id, &amendment.title, &amendment.description, amendment.version)
+                                        .await
+                                    {
+                                        Ok(true) => {
+                                            let _ = state.event_tx.send(WsMessage::Proposal {
+                                                id: proposal_id,
+                                                proposer_name: naming::resolve_display_name(state, &record.proposer).await,
+                                                proposer: record.proposer,
+                                                title: amendment.title,
+                                                description: amendment.description,
+                                                proposal_type: record.proposal_type,
+                                                parameters: record.parameters,
+                                                status: record.status,
+                                                yes_votes: 0,
+                                                no_votes: 0,
+                                                quorum: record.quorum,
+                                                deadline: record.deadline,
+                                                version: amendment.version,
+                                                correlation_id: None,
+                                                timestamp: ts,
+                                            });
+                                        }
+                                        Ok(false) => {
+                                            debug!("Ignoring stale or late amendment to {}", proposal_id);
+                                        }
+                                        Err(e) => warn!("Failed to store amendment: {}", e),
+                                    }
+                                }
+                                GovernanceMessage::CancelProposal(cancellation) => {
+                                    let proposal_id = cancellation.proposal_id.to_string();
+                                    match state.store.get_proposal(&proposal_id).await {
+                                        Ok(Some(record)) if record.proposer == cancellation.proposer => {}
+                                        Ok(_) => {
+                                            debug!("Ignoring cancellation of unknown or foreign proposal {}", proposal_id);
+                                            return;
+                                        }
+                                        Err(e) => {
+                                            warn!("Failed to load proposal {}: {}", proposal_id, e);
+                                            return;
+                                        }
+                                    }
+                                    match state.store.close_proposal(&proposal_id, "cancelled").await {
+                                        Ok(true) => {
+                                            if let Some(update) = proposal_update(state, &proposal_id).await {
+                                                let _ = state.event_tx.send(update);
+                                            }
+                                        }
+                                        Ok(false) => debug!("Ignoring cancellation of closed proposal {}", proposal_id),
+                                        Err(e) => warn!("Failed to cancel proposal {}: {}", proposal_id, e),
+                                    }
+                                }
+                                GovernanceMessage::SyncRequest(request) => {
+                                    anti_entropy::serve_sync_request(state, request).await;
+                                }
+                                GovernanceMessage::SyncResponse(response) => {
+                                    anti_entropy::apply_sync_response(state, response).await;
+                                }
                             }
                         }
                         EconomicsEvent::Resource(res_msg) => {
                             use mycelial_protocol::ResourceMessage;
                             match res_msg {
-                                ResourceMessage::Contribution(contrib) => {
+                                ResourceMessage::Contribution(mut contrib) => {
+                                    match resource_units::normalize(&contrib.resource_type, contrib.amount, &contrib.unit) {
+                                        Ok((amount, unit)) => {
+                                            contrib.amount = amount;
+                                            contrib.unit = unit;
+                                        }
+                                        Err(e) => {
+                                            warn!("Ignoring resource contribution from {}: {}", contrib.peer_id, e);
+                                            return;
+                                        }
+                                    }
+                                    let record = ContributionRecord {
+                                        id: contrib.id.to_string(),
+                                        peer_id: contrib.peer_id.clone(),
+                                        resource_type: resource_type_label(&contrib.resource_type),
+                                        amount: contrib.amount,
+                                        unit: contrib.unit.clone(),
+                                        timestamp: contrib.timestamp.timestamp_millis(),
+                                    };
+                                    if let Err(e) = state.store.insert_resource_contribution(&record).await {
+                                        warn!("Failed to store resource contribution: {}", e);
+                                    }
                                     let _ = state.event_tx.send(WsMessage::ResourceContribution {
                                         id: contrib.id.to_string(),
                                         peer_id: contrib.peer_id,
                                         resource_type: format!("{:?}", contrib.resource_type),
                                         amount: contrib.amount,
                                         unit: contrib.unit,
+                                        correlation_id: contrib.correlation_id,
                                         timestamp: ts,
                                     });
+                                    if let Some(pool) = pool_update(state, &record.resource_type).await {
+                                        let _ = state.event_tx.send(pool);
+                                    }
                                 }
                                 ResourceMessage::PoolUpdate(pool) => {
                                     let contributors: Vec<ContributorEntry> = pool.top_contributors
@@ -388,29 +1079,74 @@ async fn handle_network_event(event: NetworkEvent, state: &AppState, local_peer_
                     }
                 }
             }
+            else if topic == state.config.presence_topic {
+                presence::handle_remote(state, &data, source.map(|_| from_id));
+            }
             // Try to parse as chat message (handles chat, content, direct, and room topics)
             else if topic.contains("chat") || topic.contains("content") || topic.contains("direct") || topic.contains("room") {
-                if let Ok(content) = String::from_utf8(data.clone()) {
-                    let short_from = &from_id[..8.min(from_id.len())];
-
-                    // Extract room_id from topic if it's a room message
-                    // Topic format: /mycelial/1.0.0/room/{room_id}
-                    let room_id = if topic.contains("/room/") {
-                        topic.split("/room/").nth(1).map(|s| s.to_string())
-                    } else {
-                        None
-                    };
-
-                    let _ = state.event_tx.send(WsMessage::ChatMessage {
-                        id: message_id.to_string(),
-                        from: from_id.clone(),
-                        from_name: format!("Peer-{}", short_from),
-                        to: None,
-                        room_id,
-                        content,
-                        timestamp: ts,
-                    });
+                // Chat is published as a core Message; plain UTF-8 text is accepted too
+                let (id, sender, recipient, content) = match serde_json::from_slice::<Message>(&data) {
+                    // Edits and deletions of earlier chat
+                    Ok(msg) if msg.message_type == MessageType::System => {
+                        chat_edits::handle_remote(state, msg, source.map(|_| from_id), ts).await;
+                        return;
+                    }
+                    Ok(msg) => match String::from_utf8(msg.payload) {
+                        Ok(content) => (
+                            msg.id.to_string(),
+                            Some(msg.sender.to_string()),
+                            msg.recipient.map(|r| r.to_string()),
+                            content,
+                        ),
+                        Err(_) => {
+                            debug!("Ignoring chat message {} with a non-text payload", msg.id);
+                            return;
+                        }
+                    },
+                    Err(_) => match String::from_utf8(data) {
+                        Ok(content) => (message_id.to_string(), None, None, content),
+                        Err(_) => return,
+                    },
+                };
+
+                // This node's own chat was already echoed when it was sent
+                let local = state.local_peer_id.to_string();
+                if from_id == local || sender.as_deref() == Some(local.as_str()) {
+                    debug!("Ignoring own chat message {}", id);
+                    return;
+                }
+
+                if !state.replay_guard.check_and_record(MessageCategory::Chat, &id) {
+                    debug!("Ignoring replayed chat message {}", id);
+                    return;
+                }
+
+                // Prefer the gossip source, which is authenticated, over the claimed sender
+                let from = match source {
+                    Some(_) => from_id,
+                    None => sender.unwrap_or(from_id),
+                };
+                let from_name = naming::resolve_display_name(state, &from).await;
+
+                // Direct messages must name this node as their recipient
+                if topics::direct_recipient(&topic).is_some() && recipient.as_deref() != Some(local.as_str()) {
+                    debug!("Dropping direct message {} not addressed to this node", id);
+                    return;
                 }
+
+                // Extract room_id from topic if it's a room message
+                let room_id = topics::room_id(&topic).map(str::to_string);
+                let to = recipient;
+
+                let _ = state.event_tx.send(WsMessage::ChatMessage {
+                    id,
+                    from,
+                    from_name,
+                    to,
+                    room_id,
+                    content,
+                    timestamp: ts,
+                });
             }
         }
 
@@ -422,6 +1158,20 @@ async fn handle_network_event(event: NetworkEvent, state: &AppState, local_peer_
             info!("  Full multiaddr (use this to connect):");
             info!("    {}", full_multiaddr);
             info!("═══════════════════════════════════════════════════════════");
+
+            let address = address.to_string();
+            let mut addresses = state.listen_addresses.write();
+            if !addresses.contains(&address) {
+                addresses.push(address);
+            }
+        }
+
+        NetworkEvent::MessagePublished { message_id, topic, peer_count } => {
+            let _ = state.event_tx.send(WsMessage::Propagation {
+                message_id: message_id.to_string(),
+                topic,
+                peer_count,
+            });
         }
 
         NetworkEvent::Subscribed { topic } => {
@@ -434,6 +1184,10 @@ async fn handle_network_event(event: NetworkEvent, state: &AppState, local_peer_
             state.subscribed_topics.write().retain(|t| t != &topic);
         }
 
+        NetworkEvent::OperationFailed { operation, topic, error } => {
+            network_errors::report_refused(state, operation, &topic, &error);
+        }
+
         NetworkEvent::Started { peer_id, listen_addresses: _ } => {
             info!("Network started for peer: {}", peer_id);
             info!("Listen addresses will be reported as they become available");
@@ -458,3 +1212,771 @@ async fn handle_network_event(event: NetworkEvent, state: &AppState, local_peer_
         _ => {}
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mycelial_network::MessageId;
+    use mycelial_protocol::{CastVote, GovernanceMessage, Vote};
+
+    #[tokio::test]
+    async fn test_replayed_vote_counted_once() {
+        let (state, _commands) = test_support::test_state().await;
+        let mut events = state.event_tx.subscribe();
+        let local = Keypair::generate_ed25519().public().to_peer_id();
+
+        let proposal_id = store_proposal(&state, &local).await;
+        let vote = GovernanceMessage::CastVote(CastVote::new(
+            proposal_id,
+            "remote_voter".to_string(),
+            Vote::For,
+            1.0,
+        ));
+        let data = serde_json::to_vec(&vote).unwrap();
+
+        // Deliver the same vote twice, as a replaying peer would
+        for _ in 0..2 {
+            let event = NetworkEvent::MessageReceived {
+                message_id: MessageId::from(data.clone()),
+                topic: topics::GOVERNANCE.to_string(),
+                source: None,
+                data: data.clone(),
+                timestamp: chrono::Utc::now(),
+            };
+            handle_network_event(event, &state, local).await;
+        }
+
+        let votes = state.store.list_votes(&proposal_id.to_string()).await.unwrap();
+        assert_eq!(votes.len(), 1);
+
+        assert!(matches!(events.try_recv(), Ok(WsMessage::VoteCast { .. })));
+        assert!(events.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_inbound_vote_weighed_by_stored_reputation() {
+        let (state, _commands) = test_support::test_state().await;
+        let local = Keypair::generate_ed25519().public().to_peer_id();
+        let voter = Keypair::generate_ed25519().public().to_peer_id();
+        let forger = Keypair::generate_ed25519().public().to_peer_id();
+        let info = PeerInfo {
+            id: PeerId(voter.to_base58()),
+            public_key: voter.to_base58(),
+            addresses: vec![],
+            first_seen: chrono::Utc::now(),
+            last_seen: chrono::Utc::now(),
+            name: None,
+        };
+        let reputation = Reputation { score: 0.6, ..Default::default() };
+        state.store.upsert_peer(&info, Some(&reputation)).await.unwrap();
+        let proposal_id = store_proposal(&state, &voter).await;
+        let vote = |secs: i64| {
+            let mut vote = CastVote::new(proposal_id, voter.to_base58(), Vote::For, 2.0);
+            vote.timestamp = chrono::DateTime::from_timestamp(secs, 0).unwrap();
+            serde_json::to_vec(&GovernanceMessage::CastVote(vote)).unwrap()
+        };
+
+        // Cast in the voter's name by another peer
+        handle_network_event(economics_event(topics::GOVERNANCE, vote(1), forger), &state, local).await;
+        assert!(state.store.list_votes(&proposal_id.to_string()).await.unwrap().is_empty());
+
+        // The claimed 2.0 is replaced by the weight of a 0.6 reputation
+        handle_network_event(economics_event(topics::GOVERNANCE, vote(2), voter), &state, local).await;
+        let votes = state.store.list_votes(&proposal_id.to_string()).await.unwrap();
+        assert_eq!(votes.len(), 1);
+        assert!((votes[0].weight - 1.2).abs() < 1e-9);
+    }
+
+    #[tokio::test]
+    async fn test_inbound_vote_only_on_open_proposals() {
+        let (state, _commands) = test_support::test_state().await;
+        let local = Keypair::generate_ed25519().public().to_peer_id();
+        let voter = Keypair::generate_ed25519().public().to_peer_id();
+        let vote = |proposal_id: uuid::Uuid| {
+            let vote = CastVote::new(proposal_id, voter.to_base58(), Vote::For, 1.0);
+            serde_json::to_vec(&GovernanceMessage::CastVote(vote)).unwrap()
+        };
+
+        let unknown = uuid::Uuid::new_v4();
+        handle_network_event(economics_event(topics::GOVERNANCE, vote(unknown), voter), &state, local).await;
+        assert!(state.store.list_votes(&unknown.to_string()).await.unwrap().is_empty());
+
+        let expired = store_proposal(&state, &local).await;
+        let mut record = state.store.get_proposal(&expired.to_string()).await.unwrap().unwrap();
+        record.deadline = 1;
+        state.store.upsert_proposal(&record).await.unwrap();
+        handle_network_event(economics_event(topics::GOVERNANCE, vote(expired), voter), &state, local).await;
+        assert!(state.store.list_votes(&expired.to_string()).await.unwrap().is_empty());
+
+        let closed = store_proposal(&state, &local).await;
+        assert!(state.store.close_proposal(&closed.to_string(), "rejected").await.unwrap());
+        handle_network_event(economics_event(topics::GOVERNANCE, vote(closed), voter), &state, local).await;
+        assert!(state.store.list_votes(&closed.to_string()).await.unwrap().is_empty());
+
+        let open = store_proposal(&state, &local).await;
+        handle_network_event(economics_event(topics::GOVERNANCE, vote(open), voter), &state, local).await;
+        assert_eq!(state.store.list_votes(&open.to_string()).await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_inbound_invalid_action_signature_dropped() {
+        use mycelial_core::identity::{Keypair as ActionKeypair, KeypairExt};
+        use mycelial_protocol::{ActionSignature, Signed};
+
+        let (state, _commands) = test_support::test_state().await;
+        let local = Keypair::generate_ed25519().public().to_peer_id();
+        let keypair = ActionKeypair::generate();
+        let signature = |payload: &str| ActionSignature {
+            signer: keypair.did().to_string(),
+            payload: payload.to_string(),
+            nonce: "n1".to_string(),
+            timestamp: 1_000,
+            signature: keypair.sign_bytes(&ActionSignature::signed_bytes("n1", 1_000, "vote yes")).to_hex(),
+        };
+        let vote = |proposal_id: uuid::Uuid, signature: ActionSignature| {
+            let vote = CastVote::new(proposal_id, "voter".to_string(), Vote::For, 1.0).with_signature(Some(signature));
+            (proposal_id.to_string(), serde_json::to_vec(&GovernanceMessage::CastVote(vote)).unwrap())
+        };
+
+        let (forged, data) = vote(store_proposal(&state, &local).await, signature("vote no"));
+        handle_network_event(governance_event(data), &state, local).await;
+        assert!(state.store.list_votes(&forged).await.unwrap().is_empty());
+
+        let (genuine, data) = vote(store_proposal(&state, &local).await, signature("vote yes"));
+        handle_network_event(governance_event(data), &state, local).await;
+        assert_eq!(state.store.list_votes(&genuine).await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_raw_protocol_accompanies_digested_event() {
+        let config = ServerConfig {
+            allow_debug_capabilities: true,
+            ..ServerConfig::default()
+        };
+        let (state, _commands) = test_support::test_state_with_config(config).await;
+        let mut events = state.event_tx.subscribe();
+        let local = Keypair::generate_ed25519().public().to_peer_id();
+
+        let vote = GovernanceMessage::CastVote(CastVote::new(
+            store_proposal(&state, &local).await,
+            "remote_voter".to_string(),
+            Vote::For,
+            1.0,
+        ));
+        let data = serde_json::to_vec(&vote).unwrap();
+        let event = NetworkEvent::MessageReceived {
+            message_id: MessageId::from(data.clone()),
+            topic: topics::GOVERNANCE.to_string(),
+            source: None,
+            data: data.clone(),
+            timestamp: chrono::Utc::now(),
+        };
+        handle_network_event(event, &state, local).await;
+
+        match events.try_recv().unwrap() {
+            WsMessage::RawProtocol { topic, json } => {
+                assert_eq!(topic, topics::GOVERNANCE);
+                assert_eq!(json, serde_json::to_value(&vote).unwrap());
+            }
+            other => panic!("unexpected message: {:?}", other),
+        }
+        assert!(matches!(events.try_recv(), Ok(WsMessage::VoteCast { .. })));
+    }
+
+    /// Take the payload of the next gossip publish from a test network
+    fn next_publish(commands: &mut tokio::sync::mpsc::Receiver<mycelial_network::NetworkCommand>) -> Vec<u8> {
+        match commands.try_recv() {
+            Ok(mycelial_network::NetworkCommand::Publish { data, .. }) => data,
+            other => panic!("expected publish, got {:?}", other),
+        }
+    }
+
+    /// An economics message on `topic` as gossiped by `source`
+    fn economics_event(topic: &str, data: Vec<u8>, source: Libp2pPeerId) -> NetworkEvent {
+        NetworkEvent::MessageReceived {
+            message_id: MessageId::from(data.clone()),
+            topic: topic.to_string(),
+            source: Some(source),
+            data,
+            timestamp: chrono::Utc::now(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_inbound_credit_line_only_from_creditor() {
+        use mycelial_protocol::{CreateCreditLine, CreditMessage};
+
+        let (state, _commands) = test_support::test_state().await;
+        let mut events = state.event_tx.subscribe();
+        let local = Keypair::generate_ed25519().public().to_peer_id();
+        let creditor = Keypair::generate_ed25519().public().to_peer_id();
+        let forger = Keypair::generate_ed25519().public().to_peer_id();
+        let line = |limit: f64| {
+            let line = CreateCreditLine::new(creditor.to_base58(), state.local_peer_id.to_string(), limit);
+            serde_json::to_vec(&CreditMessage::CreateLine(line)).unwrap()
+        };
+
+        handle_network_event(economics_event(topics::CREDIT, line(500.0), forger), &state, local).await;
+        assert!(events.try_recv().is_err());
+        assert!(state.store.find_credit_line(&creditor.to_base58(), state.local_peer_id.as_str()).await.unwrap().is_none());
+
+        handle_network_event(economics_event(topics::CREDIT, line(50.0), creditor), &state, local).await;
+        assert!(matches!(events.try_recv(), Ok(WsMessage::CreditLine { .. })));
+        let stored = state.store.find_credit_line(&creditor.to_base58(), state.local_peer_id.as_str()).await.unwrap();
+        assert_eq!(stored.map(|line| line.limit), Some(50.0));
+    }
+
+    #[tokio::test]
+    async fn test_inbound_vouches_only_from_their_parties() {
+        use mycelial_protocol::{VouchAck, VouchMessage, VouchRequest};
+        use uuid::Uuid;
+
+        let (state, _commands) = test_support::test_state().await;
+        let mut events = state.event_tx.subscribe();
+        let local = Keypair::generate_ed25519().public().to_peer_id();
+        let voucher = Keypair::generate_ed25519().public().to_peer_id();
+        let vouchee = Keypair::generate_ed25519().public().to_peer_id();
+        let forger = Keypair::generate_ed25519().public().to_peer_id();
+        let request = |voucher: Libp2pPeerId, vouchee: Libp2pPeerId| {
+            VouchRequest::new(voucher.to_base58(), vouchee.to_base58(), 0.5)
+        };
+        let vouch = |request: &VouchRequest| serde_json::to_vec(&VouchMessage::VouchRequest(request.clone())).unwrap();
+        let ack = |vouch_id: Uuid, from: Libp2pPeerId| {
+            let ack = VouchAck {
+                vouch_id,
+                from: from.to_base58(),
+                accepted: false,
+                reason: None,
+                timestamp: chrono::Utc::now(),
+                correlation_id: None,
+                signature: None,
+            };
+            serde_json::to_vec(&VouchMessage::VouchAck(ack)).unwrap()
+        };
+
+        // Staked on someone else's behalf, or for the voucher itself
+        let forged = request(voucher, vouchee);
+        handle_network_event(economics_event(topics::VOUCH, vouch(&forged), forger), &state, local).await;
+        let own = request(voucher, voucher);
+        handle_network_event(economics_event(topics::VOUCH, vouch(&own), voucher), &state, local).await;
+        assert!(events.try_recv().is_err());
+        assert!(state.store.get_vouch(&forged.id.to_string()).await.unwrap().is_none());
+        assert!(state.store.get_vouch(&own.id.to_string()).await.unwrap().is_none());
+
+        let genuine = request(voucher, vouchee);
+        handle_network_event(economics_event(topics::VOUCH, vouch(&genuine), voucher), &state, local).await;
+        assert!(matches!(events.try_recv(), Ok(WsMessage::VouchRequest { .. })));
+
+        // Only the vouchee's answer counts
+        for responder in [forger, voucher] {
+            handle_network_event(economics_event(topics::VOUCH, ack(genuine.id, responder), responder), &state, local)
+                .await;
+        }
+        assert!(events.try_recv().is_err());
+        assert_eq!(state.store.get_vouch(&genuine.id.to_string()).await.unwrap().unwrap().status, "pending");
+
+        handle_network_event(economics_event(topics::VOUCH, ack(genuine.id, vouchee), vouchee), &state, local).await;
+        assert!(matches!(events.try_recv(), Ok(WsMessage::VouchAck { accepted: false, .. })));
+        assert_eq!(state.store.get_vouch(&genuine.id.to_string()).await.unwrap().unwrap().status, "rejected");
+    }
+
+    #[tokio::test]
+    async fn test_inbound_transfer_drawn_within_limit_by_debtor() {
+        use mycelial_protocol::{CreditMessage, CreditTransfer};
+        use uuid::Uuid;
+
+        let (state, _commands) = test_support::test_state().await;
+        let mut events = state.event_tx.subscribe();
+        let local = Keypair::generate_ed25519().public().to_peer_id();
+        let debtor = Keypair::generate_ed25519().public().to_peer_id();
+        let forger = Keypair::generate_ed25519().public().to_peer_id();
+        let line_id = Uuid::new_v4();
+        state
+            .store
+            .insert_credit_line(&CreditLineRecord {
+                id: line_id.to_string(),
+                creditor: state.local_peer_id.to_string(),
+                debtor: debtor.to_base58(),
+                limit: 50.0,
+                balance: 0.0,
+                created_at: 1_000,
+            })
+            .await
+            .unwrap();
+        let transfer = |line_id: Uuid, amount: f64| {
+            let transfer = CreditTransfer::new(line_id, debtor.to_base58(), state.local_peer_id.to_string(), amount);
+            serde_json::to_vec(&CreditMessage::Transfer(transfer)).unwrap()
+        };
+        let balance = || async { state.store.get_credit_line(&line_id.to_string()).await.unwrap().unwrap().balance };
+
+        // Published by someone other than the debtor, over the limit, or on
+        // an unknown line: dropped without drawing
+        handle_network_event(economics_event(topics::CREDIT, transfer(line_id, 10.0), forger), &state, local).await;
+        handle_network_event(economics_event(topics::CREDIT, transfer(line_id, 60.0), debtor), &state, local).await;
+        handle_network_event(economics_event(topics::CREDIT, transfer(Uuid::new_v4(), 10.0), debtor), &state, local).await;
+        assert!(events.try_recv().is_err());
+        assert_eq!(balance().await, 0.0);
+
+        handle_network_event(economics_event(topics::CREDIT, transfer(line_id, 50.0), debtor), &state, local).await;
+        assert!(matches!(events.try_recv(), Ok(WsMessage::CreditTransfer { amount, .. }) if amount == 50.0));
+        assert_eq!(balance().await, 50.0);
+    }
+
+    /// Store an active proposal by `proposer`, returning its ID
+    async fn store_proposal(state: &AppState, proposer: &Libp2pPeerId) -> uuid::Uuid {
+        use mycelial_protocol::{PassingRule, QuorumMode};
+
+        let id = uuid::Uuid::new_v4();
+        let record = ProposalRecord {
+            id: id.to_string(),
+            proposer: proposer.to_base58(),
+            title: "Original".to_string(),
+            description: "Original text".to_string(),
+            proposal_type: "text".to_string(),
+            parameters: Default::default(),
+            status: "active".to_string(),
+            quorum: 1,
+            quorum_fraction: 0.5,
+            quorum_mode: QuorumMode::Snapshot,
+            passing_rule: PassingRule::SimpleMajority,
+            deadline: i64::MAX,
+            created_at: 0,
+            version: 1,
+        };
+        state.store.upsert_proposal(&record).await.unwrap();
+        id
+    }
+
+    #[tokio::test]
+    async fn test_inbound_cancellation_only_from_proposer() {
+        use mycelial_protocol::ProposalCancellation;
+
+        let (state, _commands) = test_support::test_state().await;
+        let local = Keypair::generate_ed25519().public().to_peer_id();
+        let proposer = Keypair::generate_ed25519().public().to_peer_id();
+        let forger = Keypair::generate_ed25519().public().to_peer_id();
+        let id = store_proposal(&state, &proposer).await;
+        let cancel = || {
+            let cancellation = ProposalCancellation {
+                proposal_id: id,
+                proposer: proposer.to_base58(),
+                timestamp: chrono::Utc::now(),
+            };
+            serde_json::to_vec(&GovernanceMessage::CancelProposal(cancellation)).unwrap()
+        };
+        let status = || async { state.store.get_proposal(&id.to_string()).await.unwrap().unwrap().status };
+
+        handle_network_event(economics_event(topics::GOVERNANCE, cancel(), forger), &state, local).await;
+        assert_eq!(status().await, "active");
+
+        // The forgery didn't use up the genuine cancellation
+        handle_network_event(economics_event(topics::GOVERNANCE, cancel(), proposer), &state, local).await;
+        assert_eq!(status().await, "cancelled");
+
+        // Nor does the cancelled proposal take votes
+        let vote = CastVote::new(id, forger.to_base58(), Vote::For, 1.0);
+        let data = serde_json::to_vec(&GovernanceMessage::CastVote(vote)).unwrap();
+        handle_network_event(economics_event(topics::GOVERNANCE, data, forger), &state, local).await;
+        assert!(state.store.list_votes(&id.to_string()).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_inbound_amendment_only_from_proposer() {
+        use mycelial_protocol::ProposalAmendment;
+
+        let (state, _commands) = test_support::test_state().await;
+        let local = Keypair::generate_ed25519().public().to_peer_id();
+        let proposer = Keypair::generate_ed25519().public().to_peer_id();
+        let forger = Keypair::generate_ed25519().public().to_peer_id();
+        let id = store_proposal(&state, &proposer).await;
+        let amend = |title: &str| {
+            let amendment = ProposalAmendment {
+                proposal_id: id,
+                proposer: proposer.to_base58(),
+                title: title.to_string(),
+                description: "Amended text".to_string(),
+                version: 2,
+                timestamp: chrono::Utc::now(),
+            };
+            serde_json::to_vec(&GovernanceMessage::AmendProposal(amendment)).unwrap()
+        };
+        let title = || async { state.store.get_proposal(&id.to_string()).await.unwrap().unwrap().title };
+
+        handle_network_event(economics_event(topics::GOVERNANCE, amend("Forged"), forger), &state, local).await;
+        assert_eq!(title().await, "Original");
+
+        handle_network_event(economics_event(topics::GOVERNANCE, amend("Amended"), proposer), &state, local).await;
+        assert_eq!(title().await, "Amended");
+    }
+
+    #[tokio::test]
+    async fn test_inbound_proposal_only_from_proposer() {
+        use mycelial_protocol::CreateProposal;
+
+        let (state, _commands) = test_support::test_state().await;
+        let local = Keypair::generate_ed25519().public().to_peer_id();
+        let forger = Keypair::generate_ed25519().public().to_peer_id();
+        // Made out in this node's name, but published by another peer
+        let proposal = CreateProposal::new(
+            state.local_peer_id.to_string(),
+            "Pay me".to_string(),
+            "Treasury spend".to_string(),
+        );
+        let id = proposal.id.to_string();
+        let data = serde_json::to_vec(&GovernanceMessage::CreateProposal(proposal)).unwrap();
+
+        handle_network_event(economics_event(topics::GOVERNANCE, data, forger), &state, local).await;
+        assert!(state.store.get_proposal(&id).await.unwrap().is_none());
+        // Nor did it use up this node's proposal cooldown
+        let now = chrono::Utc::now().timestamp_millis();
+        assert!(state.proposal_cooldowns.remaining(state.local_peer_id.as_str(), now).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_resent_proposal_keeps_stored_terms() {
+        use mycelial_protocol::CreateProposal;
+
+        let (state, _commands) = test_support::test_state().await;
+        let local = Keypair::generate_ed25519().public().to_peer_id();
+        let proposer = Keypair::generate_ed25519().public().to_peer_id();
+        let id = store_proposal(&state, &proposer).await;
+        // The same ID sent again by its proposer, with new terms
+        let mut proposal = CreateProposal::new(proposer.to_base58(), "Rewritten".to_string(), "New terms".to_string());
+        proposal.id = id;
+        let data = serde_json::to_vec(&GovernanceMessage::CreateProposal(proposal)).unwrap();
+
+        handle_network_event(economics_event(topics::GOVERNANCE, data, proposer), &state, local).await;
+        let stored = state.store.get_proposal(&id.to_string()).await.unwrap().unwrap();
+        assert_eq!(stored.title, "Original");
+        assert_eq!(stored.deadline, i64::MAX);
+    }
+
+    fn governance_event(data: Vec<u8>) -> NetworkEvent {
+        NetworkEvent::MessageReceived {
+            message_id: MessageId::from(data.clone()),
+            topic: topics::GOVERNANCE.to_string(),
+            source: None,
+            data,
+            timestamp: chrono::Utc::now(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_sync_fills_in_missed_proposals() {
+        let (lagging, mut lagging_commands) = test_support::test_state().await;
+        let (peer, mut peer_commands) = test_support::test_state().await;
+        let mut events = lagging.event_tx.subscribe();
+        let local = Keypair::generate_ed25519().public().to_peer_id();
+
+        // The peer made a proposal the lagging node missed, and saw its own
+        // vote and another peer's, which it can't vouch for
+        let responder = peer.local_peer_id.to_string();
+        let proposal = mycelial_protocol::CreateProposal::new(
+            responder.clone(),
+            "Missed".to_string(),
+            "Gossiped while offline".to_string(),
+        );
+        let own = CastVote::new(proposal.id, responder.clone(), Vote::For, 1.0);
+        let other = CastVote::new(proposal.id, "voter".to_string(), Vote::Against, 1.0);
+        for message in [
+            GovernanceMessage::CreateProposal(proposal.clone()),
+            GovernanceMessage::CastVote(own),
+            GovernanceMessage::CastVote(other),
+        ] {
+            handle_network_event(governance_event(serde_json::to_vec(&message).unwrap()), &peer, local).await;
+        }
+        assert_eq!(peer.store.list_votes(&proposal.id.to_string()).await.unwrap().len(), 2);
+        // It was since cancelled there
+        assert!(peer.store.close_proposal(&proposal.id.to_string(), "cancelled").await.unwrap());
+
+        anti_entropy::request_sync(&lagging).await.unwrap();
+        assert!(matches!(
+            events.try_recv(),
+            Ok(WsMessage::SyncProgress { responder: None, .. })
+        ));
+
+        // Relay the request to the peer and its response back
+        let request = next_publish(&mut lagging_commands);
+        handle_network_event(governance_event(request), &peer, local).await;
+        let response = next_publish(&mut peer_commands);
+        handle_network_event(governance_event(response.clone()), &lagging, local).await;
+
+        // The responder's status isn't taken, and only its own vote is
+        let stored = lagging.store.get_proposal(&proposal.id.to_string()).await.unwrap().unwrap();
+        assert_eq!(stored.title, "Missed");
+        assert_eq!(stored.status, "active");
+        let votes = lagging.store.list_votes(&proposal.id.to_string()).await.unwrap();
+        assert_eq!(votes.len(), 1);
+        assert_eq!((votes[0].voter.as_str(), votes[0].vote.as_str()), (responder.as_str(), "yes"));
+        match events.try_recv() {
+            Ok(WsMessage::SyncProgress { responder, proposals_added, votes_added, .. }) => {
+                assert_eq!(responder, Some(peer.local_peer_id.to_string()));
+                assert_eq!((proposals_added, votes_added), (1, 1));
+            }
+            other => panic!("expected sync progress, got {:?}", other),
+        }
+
+        // Unsolicited responses are ignored
+        let mut unsolicited: GovernanceMessage = serde_json::from_slice(&response).unwrap();
+        if let GovernanceMessage::SyncResponse(ref mut response) = unsolicited {
+            response.request_id = uuid::Uuid::new_v4();
+        }
+        handle_network_event(governance_event(serde_json::to_vec(&unsolicited).unwrap()), &lagging, local).await;
+        assert!(events.try_recv().is_err());
+
+        // Votes on proposals unknown here are dropped
+        let mut stray: GovernanceMessage = serde_json::from_slice(&response).unwrap();
+        let unknown = uuid::Uuid::new_v4();
+        if let GovernanceMessage::SyncResponse(ref mut response) = stray {
+            response.page = 1;
+            response.proposals.clear();
+            response.votes = vec![CastVote::new(unknown, response.responder.clone(), Vote::For, 1.0)];
+        }
+        handle_network_event(governance_event(serde_json::to_vec(&stray).unwrap()), &lagging, local).await;
+        assert!(matches!(
+            events.try_recv(),
+            Ok(WsMessage::SyncProgress { proposals_added: 0, votes_added: 0, .. })
+        ));
+        assert!(lagging.store.list_votes(&unknown.to_string()).await.unwrap().is_empty());
+
+        // The peer won't answer the same node again so soon
+        anti_entropy::request_sync(&lagging).await.unwrap();
+        let request = next_publish(&mut lagging_commands);
+        handle_network_event(governance_event(request), &peer, local).await;
+        assert!(peer_commands.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_topic_namespace_isolates_networks() {
+        let namespaced = |ns: &str| ServerConfig {
+            topic_namespace: ns.to_string(),
+            ..ServerConfig::default()
+        };
+        let (requester, mut requester_commands) = test_support::test_state_with_config(namespaced("a")).await;
+        let (same, mut same_commands) = test_support::test_state_with_config(namespaced("a")).await;
+        let (other, mut other_commands) = test_support::test_state_with_config(namespaced("b")).await;
+        let local = Keypair::generate_ed25519().public().to_peer_id();
+
+        anti_entropy::request_sync(&requester).await.unwrap();
+        let (topic, data) = match requester_commands.try_recv() {
+            Ok(mycelial_network::NetworkCommand::Publish { topic, data }) => (topic, data),
+            other => panic!("expected publish, got {:?}", other),
+        };
+        assert_eq!(topic, format!("/a{}", topics::GOVERNANCE));
+
+        let received = |topic: &str| NetworkEvent::MessageReceived {
+            message_id: MessageId::from(data.clone()),
+            topic: topic.to_string(),
+            source: None,
+            data: data.clone(),
+            timestamp: chrono::Utc::now(),
+        };
+
+        // A node in another namespace never sees the request
+        handle_network_event(received(&topic), &other, local).await;
+        assert!(other_commands.try_recv().is_err());
+
+        // One in the same namespace answers it on the namespaced topic
+        handle_network_event(received(&topic), &same, local).await;
+        match same_commands.try_recv() {
+            Ok(mycelial_network::NetworkCommand::Publish { topic, .. }) => assert_eq!(topic, "/a/mycelial/1.0.0/governance"),
+            other => panic!("expected publish, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_membership_changes_emit_one_event_each() {
+        let (state, _commands) = test_support::test_state().await;
+        let mut events = state.event_tx.subscribe();
+        let local = Keypair::generate_ed25519().public().to_peer_id();
+        let peer = Keypair::generate_ed25519().public().to_peer_id();
+
+        // Two connections open and close; membership changes once each way
+        for num_connections in [1, 2] {
+            handle_network_event(NetworkEvent::PeerConnected { peer_id: peer, num_connections }, &state, local).await;
+        }
+        for num_connections in [1, 0] {
+            handle_network_event(NetworkEvent::PeerDisconnected { peer_id: peer, num_connections }, &state, local).await;
+        }
+
+        let mut membership = Vec::new();
+        while let Ok(event) = events.try_recv() {
+            match event {
+                WsMessage::PeerJoined { peer_id, .. } => membership.push(("joined", peer_id)),
+                WsMessage::PeerLeft { peer_id } => membership.push(("left", peer_id)),
+                _ => {}
+            }
+        }
+        assert_eq!(
+            membership,
+            vec![("joined", peer.to_base58()), ("left", peer.to_base58())]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_rapid_joins_broadcast_one_peer_delta() {
+        let config = ServerConfig {
+            peer_delta_window: Duration::from_secs(60),
+            ..ServerConfig::default()
+        };
+        let (state, _commands) = test_support::test_state_with_config(config).await;
+        let mut events = state.event_tx.subscribe();
+        let local = Keypair::generate_ed25519().public().to_peer_id();
+        let peers: Vec<_> = (0..3).map(|_| Keypair::generate_ed25519().public().to_peer_id()).collect();
+
+        for peer_id in &peers {
+            handle_network_event(NetworkEvent::PeerConnected { peer_id: *peer_id, num_connections: 1 }, &state, local).await;
+        }
+        // Pausing only now keeps the store's pool timeouts on the real clock;
+        // the window then elapses without waiting on it
+        tokio::time::pause();
+        tokio::time::sleep(Duration::from_secs(61)).await;
+
+        let mut deltas = Vec::new();
+        while let Ok(event) = events.try_recv() {
+            if let WsMessage::PeerDelta { added, removed } = event {
+                deltas.push((added, removed));
+            }
+        }
+        assert_eq!(deltas.len(), 1);
+        let (added, removed) = &deltas[0];
+        let ids: Vec<_> = added.iter().map(|entry| entry.id.clone()).collect();
+        assert_eq!(ids, peers.iter().map(|peer| peer.to_base58()).collect::<Vec<_>>());
+        assert!(removed.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_inbound_chat_broadcast_once() {
+        let (state, _commands) = test_support::test_state().await;
+        let mut events = state.event_tx.subscribe();
+        let local = Keypair::generate_ed25519().public().to_peer_id();
+        let remote = Keypair::generate_ed25519().public().to_peer_id();
+        let chat = |sender: PeerId, source: Option<Libp2pPeerId>| {
+            let msg = Message::new(MessageType::Content, sender, b"hello mesh".to_vec());
+            let data = serde_json::to_vec(&msg).unwrap();
+            let event = NetworkEvent::MessageReceived {
+                message_id: MessageId::from(data.clone()),
+                topic: "/mycelial/1.0.0/chat".to_string(),
+                source,
+                data,
+                timestamp: chrono::Utc::now(),
+            };
+            (msg.id.to_string(), event)
+        };
+
+        // A peer's message arrives twice, e.g. through two mesh neighbours
+        let (id, event) = chat(PeerId(remote.to_base58()), Some(remote));
+        handle_network_event(event.clone(), &state, local).await;
+        handle_network_event(event, &state, local).await;
+        // The node's own message is never delivered back
+        let (_, own) = chat(state.local_peer_id.clone(), None);
+        handle_network_event(own, &state, local).await;
+
+        match events.try_recv().unwrap() {
+            WsMessage::ChatMessage { id: received, from, content, to, .. } => {
+                assert_eq!(received, id);
+                assert_eq!(from, remote.to_base58());
+                assert_eq!(content, "hello mesh");
+                assert_eq!(to, None);
+            }
+            other => panic!("expected chat, got {:?}", other),
+        }
+        assert!(events.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_inbound_direct_chat_only_for_local_peer() {
+        let (state, _commands) = test_support::test_state().await;
+        let mut events = state.event_tx.subscribe();
+        let local = Keypair::generate_ed25519().public().to_peer_id();
+        let remote = Keypair::generate_ed25519().public().to_peer_id();
+        let sender = PeerId(remote.to_base58());
+        let dm = |recipient: Option<PeerId>| {
+            let msg = match recipient.clone() {
+                Some(recipient) => Message::direct(sender.clone(), recipient, b"psst".to_vec()),
+                None => Message::new(MessageType::Content, sender.clone(), b"psst".to_vec()),
+            };
+            let data = serde_json::to_vec(&msg).unwrap();
+            NetworkEvent::MessageReceived {
+                message_id: MessageId::from(data.clone()),
+                topic: topics::direct(state.local_peer_id.as_str()),
+                source: Some(remote),
+                data,
+                timestamp: chrono::Utc::now(),
+            }
+        };
+
+        // Direct messages naming another recipient, or none, are dropped
+        let elsewhere = PeerId(Keypair::generate_ed25519().public().to_peer_id().to_base58());
+        handle_network_event(dm(Some(elsewhere)), &state, local).await;
+        handle_network_event(dm(None), &state, local).await;
+        assert!(events.try_recv().is_err());
+
+        handle_network_event(dm(Some(state.local_peer_id.clone())), &state, local).await;
+        match events.try_recv().unwrap() {
+            WsMessage::ChatMessage { from, to, .. } => {
+                assert_eq!(from, remote.to_base58());
+                assert_eq!(to, Some(state.local_peer_id.to_string()));
+            }
+            other => panic!("expected chat, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_publish_reports_propagation() {
+        use crate::server::messages::Capability;
+        use crate::server::session::Session;
+
+        let (state, _commands) = test_support::test_state().await;
+        let mut events = state.event_tx.subscribe();
+        let local = Keypair::generate_ed25519().public().to_peer_id();
+
+        // The network reports a message sent to three mesh peers
+        let event = NetworkEvent::MessagePublished {
+            message_id: MessageId::from(b"published".to_vec()),
+            topic: topics::CREDIT.to_string(),
+            peer_count: 3,
+        };
+        handle_network_event(event, &state, local).await;
+
+        let propagation = events.try_recv().unwrap();
+        match &propagation {
+            WsMessage::Propagation { topic, peer_count, .. } => {
+                assert_eq!(topic, topics::CREDIT);
+                assert_eq!(*peer_count, 3);
+            }
+            other => panic!("expected propagation, got {:?}", other),
+        }
+
+        // Only connections that opted in receive it
+        let (reply_tx, _reply_rx) = tokio::sync::mpsc::unbounded_channel();
+        let session = Session::new(reply_tx, 8);
+        assert!(!session.delivery().read().wants(&propagation));
+        session.set_capability(Capability::Propagation, true);
+        assert!(session.delivery().read().wants(&propagation));
+    }
+
+    #[tokio::test]
+    async fn test_refused_publish_reaches_clients() {
+        let (state, _commands) = test_support::test_state().await;
+        let mut events = state.event_tx.subscribe();
+        let local = Keypair::generate_ed25519().public().to_peer_id();
+
+        let event = NetworkEvent::OperationFailed {
+            operation: "publish",
+            topic: topics::CREDIT.to_string(),
+            error: "Gossipsub error: Failed to publish: InsufficientPeers".to_string(),
+        };
+        handle_network_event(event, &state, local).await;
+
+        match events.try_recv().unwrap() {
+            WsMessage::NetworkError { operation, detail, recoverable } => {
+                assert_eq!(operation, "publish");
+                assert!(detail.contains("InsufficientPeers"));
+                assert!(detail.contains(topics::CREDIT));
+                assert!(recoverable);
+            }
+            other => panic!("expected network error, got {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,171 @@
+//! Display names for peers
+//!
+//! Most peers never announce a name, which leaves dashboards showing long
+//! base58 IDs. Names are resolved here in one place: a peer's real name is
+//! used when known, otherwise a fallback derived from its ID, so the same
+//! peer always gets the same label.
+
+use std::collections::HashMap;
+use tracing::warn;
+
+use crate::AppState;
+
+/// Words for friendly fallback names
+const ADJECTIVES: [&str; 16] = [
+    "amber", "brisk", "calm", "dusky", "eager", "fuzzy", "gentle", "hardy",
+    "ivory", "jolly", "keen", "lucid", "mossy", "nimble", "quiet", "rusty",
+];
+const NOUNS: [&str; 16] = [
+    "badger", "cedar", "fern", "heron", "lichen", "marten", "morel", "otter",
+    "puffball", "raven", "sorrel", "spore", "thistle", "truffle", "willow", "wren",
+];
+
+/// Characters of the peer ID kept in a short-ID fallback name
+const SHORT_ID_LEN: usize = 10;
+
+/// How peers without a known name are labelled
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum FallbackName {
+    /// The end of the peer ID, e.g. `Peer-5NBi8N7f3a`
+    ///
+    /// Peer IDs of one key type share their leading characters (`12D3KooW`
+    /// for Ed25519), so the trailing ones are what tell peers apart.
+    #[default]
+    ShortId,
+    /// A deterministic word pair with a short suffix, e.g. `mossy-heron-7f3a`
+    Friendly,
+}
+
+impl FallbackName {
+    /// Label for configuration snapshots
+    pub fn label(self) -> &'static str {
+        match self {
+            FallbackName::ShortId => "short_id",
+            FallbackName::Friendly => "friendly",
+        }
+    }
+
+    /// The fallback name for `peer_id`
+    pub fn for_peer(self, peer_id: &str) -> String {
+        match self {
+            FallbackName::ShortId => {
+                let skip = peer_id.chars().count().saturating_sub(SHORT_ID_LEN);
+                format!("Peer-{}", peer_id.chars().skip(skip).collect::<String>())
+            }
+            FallbackName::Friendly => {
+                // FNV-1a, so names are stable across builds and restarts
+                let hash = peer_id
+                    .bytes()
+                    .fold(0xcbf2_9ce4_8422_2325u64, |h, b| (h ^ b as u64).wrapping_mul(0x0100_0000_01b3));
+                format!(
+                    "{}-{}-{:04x}",
+                    ADJECTIVES[(hash & 0xf) as usize],
+                    NOUNS[((hash >> 4) & 0xf) as usize],
+                    (hash >> 48) as u16
+                )
+            }
+        }
+    }
+}
+
+/// A peer's real name if one is set, otherwise its fallback name
+pub fn display_name(name: Option<&str>, peer_id: &str, fallback: FallbackName) -> String {
+    match name.map(str::trim) {
+        Some(name) if !name.is_empty() => name.to_string(),
+        _ => fallback.for_peer(peer_id),
+    }
+}
+
+/// A peer's name from the store, if it has one
+///
+/// The local node is named by its configured node name.
+pub async fn resolve_name(state: &AppState, peer_id: &str) -> Option<String> {
+    if peer_id == state.local_peer_id.as_str() {
+        return Some(state.node_name());
+    }
+    match state.store.get_peer(peer_id).await {
+        Ok(peer) => peer.and_then(|(info, _)| info.name),
+        Err(e) => {
+            warn!("Failed to look up peer {}: {}", peer_id, e);
+            None
+        }
+    }
+}
+
+/// Resolve a peer's display name from the store
+pub async fn resolve_display_name(state: &AppState, peer_id: &str) -> String {
+    let name = resolve_name(state, peer_id).await;
+    display_name(name.as_deref(), peer_id, state.config.fallback_name)
+}
+
+/// Display names resolved while handling one message, so each peer is
+/// looked up at most once
+#[derive(Debug, Default)]
+pub struct NameCache {
+    names: HashMap<String, String>,
+}
+
+impl NameCache {
+    /// The display name for `peer_id`, from the cache or the store
+    pub async fn display_name(&mut self, state: &AppState, peer_id: &str) -> String {
+        if let Some(name) = self.names.get(peer_id) {
+            return name.clone();
+        }
+        let name = resolve_display_name(state, peer_id).await;
+        self.names.insert(peer_id.to_string(), name.clone());
+        name
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::test_state;
+    use mycelial_core::peer::{PeerId, PeerInfo};
+
+    #[test]
+    fn test_fallback_names() {
+        let peer = "12D3KooWGzBxe7fEz1mrYDj8bMKn3aYJFVkmeqYm5X5NBi8N7f3a";
+
+        assert_eq!(display_name(None, peer, FallbackName::ShortId), "Peer-5NBi8N7f3a");
+        assert_eq!(display_name(Some("  "), peer, FallbackName::ShortId), "Peer-5NBi8N7f3a");
+        // Peers sharing the common prefix still get distinct names
+        assert_ne!(
+            FallbackName::ShortId.for_peer("12D3KooWAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA"),
+            FallbackName::ShortId.for_peer("12D3KooWBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBB")
+        );
+        assert_eq!(FallbackName::ShortId.for_peer("abc"), "Peer-abc");
+        assert_eq!(display_name(Some("alice"), peer, FallbackName::Friendly), "alice");
+
+        // Friendly names are deterministic and differ between peers
+        let friendly = display_name(None, peer, FallbackName::Friendly);
+        assert_eq!(friendly, FallbackName::Friendly.for_peer(peer));
+        assert_eq!(friendly.split('-').count(), 3);
+        assert_ne!(friendly, FallbackName::Friendly.for_peer("12D3KooWOtherPeer"));
+    }
+
+    #[tokio::test]
+    async fn test_resolve_name_from_store() {
+        let (state, _commands) = test_state().await;
+        let peer = PeerInfo {
+            id: PeerId("12D3KooWKnown".to_string()),
+            public_key: "known".to_string(),
+            addresses: vec![],
+            first_seen: chrono::Utc::now(),
+            last_seen: chrono::Utc::now(),
+            name: Some("alice".to_string()),
+        };
+        state.store.upsert_peer(&peer, None).await.unwrap();
+
+        assert_eq!(resolve_name(&state, "12D3KooWKnown").await.as_deref(), Some("alice"));
+        assert_eq!(resolve_name(&state, "12D3KooWUnknown").await, None);
+        assert_eq!(resolve_display_name(&state, "12D3KooWUnknown").await, "Peer-ooWUnknown");
+        assert_eq!(resolve_name(&state, state.local_peer_id.as_str()).await, Some(state.node_name()));
+
+        // Cached names are reused without going back to the store
+        let mut names = NameCache::default();
+        assert_eq!(names.display_name(&state, "12D3KooWKnown").await, "alice");
+        state.store.delete_peer("12D3KooWKnown").await.unwrap();
+        assert_eq!(names.display_name(&state, "12D3KooWKnown").await, "alice");
+    }
+}
@@ -0,0 +1,98 @@
+//! Network failure reporting
+//!
+//! Failed network operations are surfaced to clients as
+//! [`WsMessage::NetworkError`] so dashboards can show a connectivity banner.
+//! That covers both commands the network service couldn't accept and ones
+//! gossipsub later refused, which arrive as `NetworkEvent::OperationFailed`.
+//! While the network is down the same operation fails over and over, so each
+//! operation is reported at most once per configured interval.
+
+use mycelial_network::NetworkError;
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::time::Duration;
+
+use crate::server::messages::WsMessage;
+use crate::AppState;
+
+/// Tracks when each operation's failure was last reported
+pub struct NetworkErrorLimiter {
+    /// Minimum time between reports for one operation
+    interval: Duration,
+    /// Operation -> when its failure was last reported (epoch millis)
+    last_reported: Mutex<HashMap<&'static str, i64>>,
+}
+
+impl NetworkErrorLimiter {
+    /// Create a limiter allowing one report per operation every `interval`
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            last_reported: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Whether a failure of `operation` at `now` should be reported, recording it if so
+    pub fn should_report(&self, operation: &'static str, now: i64) -> bool {
+        let mut last_reported = self.last_reported.lock();
+        if let Some(last) = last_reported.get(operation) {
+            if now.saturating_sub(*last) < self.interval.as_millis() as i64 {
+                return false;
+            }
+        }
+        last_reported.insert(operation, now);
+        true
+    }
+}
+
+/// Whether an operation that failed with `error` may succeed if retried
+///
+/// A closed command channel means the network service has stopped, which
+/// needs a restart rather than a retry.
+pub fn is_recoverable(error: &NetworkError) -> bool {
+    !matches!(
+        error,
+        NetworkError::Channel(_) | NetworkError::NotStarted | NetworkError::Config(_)
+    )
+}
+
+/// Tell clients that a network operation failed, unless one was just reported
+pub fn report(state: &AppState, operation: &'static str, error: &NetworkError) {
+    notify(state, operation, error.to_string(), is_recoverable(error));
+}
+
+/// Tell clients that gossipsub refused an operation on `topic`, unless one
+/// was just reported
+///
+/// The service was running to refuse it, so a retry may succeed.
+pub fn report_refused(state: &AppState, operation: &'static str, topic: &str, error: &str) {
+    notify(state, operation, format!("{} ({})", error, topic), true);
+}
+
+fn notify(state: &AppState, operation: &'static str, detail: String, recoverable: bool) {
+    let now = chrono::Utc::now().timestamp_millis();
+    if !state.network_errors.should_report(operation, now) {
+        return;
+    }
+    let _ = state.event_tx.send(WsMessage::NetworkError {
+        operation: operation.to_string(),
+        detail,
+        recoverable,
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reports_rate_limited_per_operation() {
+        let limiter = NetworkErrorLimiter::new(Duration::from_secs(10));
+
+        assert!(limiter.should_report("publish", 0));
+        assert!(!limiter.should_report("publish", 5_000));
+        // Other operations are limited separately
+        assert!(limiter.should_report("subscribe", 5_000));
+        assert!(limiter.should_report("publish", 10_000));
+    }
+}
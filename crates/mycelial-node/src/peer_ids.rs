@@ -0,0 +1,37 @@
+//! Client-supplied peer IDs
+//!
+//! Peer IDs arrive as strings in client requests and proposal parameters.
+//! They are parsed here, in one place, so every handler accepts the same
+//! forms and stores the same canonical base58 encoding.
+
+use mycelial_core::peer::PeerId;
+use mycelial_network::Libp2pPeerId;
+
+/// Parse a client-supplied peer ID into its canonical form
+///
+/// Peers are identified by their libp2p peer ID. Surrounding whitespace is
+/// ignored; anything else that doesn't parse is rejected before it can be
+/// published.
+pub fn parse_peer_id(field: &str, raw: &str) -> Result<PeerId, String> {
+    let raw = raw.trim();
+    if raw.is_empty() {
+        return Err(format!("{} must be a peer ID", field));
+    }
+    raw.parse::<Libp2pPeerId>()
+        .map(|peer_id| PeerId(peer_id.to_base58()))
+        .map_err(|_| format!("{} is not a valid peer ID: {}", field, raw))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mycelial_network::Keypair;
+
+    #[test]
+    fn test_parse_peer_id() {
+        let peer = Keypair::generate_ed25519().public().to_peer_id().to_base58();
+        assert_eq!(parse_peer_id("to", &format!(" {} ", peer)), Ok(PeerId(peer)));
+        assert_eq!(parse_peer_id("to", "  "), Err("to must be a peer ID".to_string()));
+        assert!(parse_peer_id("to", "not-a-peer").unwrap_err().contains("not a valid peer ID"));
+    }
+}
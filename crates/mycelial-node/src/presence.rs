@@ -0,0 +1,110 @@
+//! Presence announcements
+//!
+//! When the last dashboard session acting for a peer closes, or the node
+//! shuts down with sessions still open, the departure is published on the
+//! presence topic so other nodes needn't wait for the connection to time out,
+//! and `PeerLeft` is broadcast to local clients. Announcing is opt-in
+//! (`announce_presence`), and the topic is configurable (`presence_topic`).
+//! Nodes announcing presence also listen on the topic, and treat a peer that
+//! announces its own departure as gone.
+
+use mycelial_protocol::{PresenceLeave, PresenceMessage};
+use tracing::{debug, error, info};
+
+use crate::network_errors;
+use crate::server::messages::WsMessage;
+use crate::AppState;
+
+/// Announce that `peer_id` has left, if presence announcements are enabled
+///
+/// Returns whether the leave notice was published.
+pub async fn announce_leave(state: &AppState, peer_id: &str) -> bool {
+    if !state.config.announce_presence {
+        return false;
+    }
+    let notice = PresenceMessage::Leave(PresenceLeave::new(peer_id.to_string()));
+    let data = match serde_json::to_vec(&notice) {
+        Ok(data) => data,
+        Err(e) => {
+            error!("Failed to serialize presence notice: {}", e);
+            return false;
+        }
+    };
+    let topic = &state.config.presence_topic;
+    let published = match state.network.publish(state.wire_topic(topic), data).await {
+        Ok(_) => {
+            state.topic_counts.record(topic);
+            info!("Announced departure of {}", peer_id);
+            true
+        }
+        Err(e) => {
+            error!("Failed to publish presence notice: {}", e);
+            network_errors::report(state, "publish", &e);
+            false
+        }
+    };
+
+    // Local clients learn of the departure even if the publish failed
+    state.peer_deltas.left(peer_id);
+    let _ = state.event_tx.send(WsMessage::PeerLeft {
+        peer_id: peer_id.to_string(),
+    });
+    published
+}
+
+/// Apply a presence notice received from the network
+///
+/// `source` is the authenticated gossip source; a peer may only announce its
+/// own departure.
+pub fn handle_remote(state: &AppState, data: &[u8], source: Option<String>) {
+    let notice = match serde_json::from_slice::<PresenceMessage>(data) {
+        Ok(notice) => notice,
+        Err(e) => {
+            debug!("Ignoring malformed presence notice: {}", e);
+            return;
+        }
+    };
+    match notice {
+        PresenceMessage::Leave(leave) => {
+            if source.as_deref() != Some(leave.peer_id.as_str()) {
+                debug!("Ignoring departure of {} announced by {:?}", leave.peer_id, source);
+                return;
+            }
+            if state.connected_peers.write().remove(&leave.peer_id) {
+                info!("Peer {} announced its departure", leave.peer_id);
+                state.peer_deltas.left(&leave.peer_id);
+                let _ = state.event_tx.send(WsMessage::PeerLeft { peer_id: leave.peer_id });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::test_state;
+
+    #[tokio::test]
+    async fn test_remote_leave_only_from_departing_peer() {
+        let (state, _commands) = test_state().await;
+        let mut events = state.event_tx.subscribe();
+        let notice = serde_json::to_vec(&PresenceMessage::Leave(PresenceLeave::new("test-peer".to_string()))).unwrap();
+
+        // Another peer can't announce the departure
+        handle_remote(&state, &notice, Some("other-peer".to_string()));
+        handle_remote(&state, &notice, None);
+        assert!(state.connected_peers.read().contains("test-peer"));
+        assert!(events.try_recv().is_err());
+
+        handle_remote(&state, &notice, Some("test-peer".to_string()));
+        assert!(!state.connected_peers.read().contains("test-peer"));
+        assert!(matches!(
+            events.try_recv(),
+            Ok(WsMessage::PeerLeft { peer_id }) if peer_id == "test-peer"
+        ));
+
+        // Announcing again changes nothing
+        handle_remote(&state, &notice, Some("test-peer".to_string()));
+        assert!(events.try_recv().is_err());
+    }
+}
@@ -0,0 +1,227 @@
+//! Proposal types and their parameters
+//!
+//! Clients create proposals with a [`ProposalKind`], which carries what the
+//! proposal would do if it passed. The node stores the kind's label as the
+//! proposal type and its payload as string parameters, which execution
+//! handlers read back. Peers exchange the protocol's [`ProposalType`];
+//! [`from_protocol`] and [`to_protocol`] convert between the two.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+use mycelial_protocol::ProposalType;
+
+use crate::governance_params::Parameter;
+use crate::peer_ids::parse_peer_id;
+
+/// What a proposal does if it passes, with its parameters
+///
+/// A plain text proposal may be given as the string `"text"`; the others are
+/// objects keyed by type, e.g. `{"treasury_spend": {"recipient": "12D3KooW...", "amount": 25}}`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProposalKind {
+    /// A decision with no automatic effect
+    Text,
+    /// Set a governance parameter (see [`Parameter`])
+    ParameterChange { key: String, value: String },
+    /// Transfer credit from this node's treasury
+    TreasurySpend { recipient: String, amount: f64 },
+}
+
+impl ProposalKind {
+    /// Proposal type label, as stored and used to find its execution handler
+    pub fn label(&self) -> &'static str {
+        match self {
+            ProposalKind::Text => "text",
+            ProposalKind::ParameterChange { .. } => "parameter_change",
+            ProposalKind::TreasurySpend { .. } => "treasury_spend",
+        }
+    }
+
+    /// Check the payload, returning it with whitespace trimmed and peer IDs canonical
+    pub fn validate(self) -> Result<Self, String> {
+        match self {
+            ProposalKind::Text => Ok(ProposalKind::Text),
+            ProposalKind::ParameterChange { key, value } => {
+                let key = key.trim();
+                let value = value.trim();
+                // Only parameters the node can put into effect may be proposed
+                Parameter::parse(key, value)?;
+                Ok(ProposalKind::ParameterChange {
+                    key: key.to_string(),
+                    value: value.to_string(),
+                })
+            }
+            ProposalKind::TreasurySpend { recipient, amount } => {
+                let recipient = parse_peer_id("recipient", &recipient)?;
+                if !amount.is_finite() || amount <= 0.0 {
+                    return Err("Treasury spend amount must be positive".to_string());
+                }
+                Ok(ProposalKind::TreasurySpend {
+                    recipient: recipient.to_string(),
+                    amount,
+                })
+            }
+        }
+    }
+
+    /// The payload as stored proposal parameters
+    pub fn parameters(&self) -> BTreeMap<String, String> {
+        match self {
+            ProposalKind::Text => BTreeMap::new(),
+            ProposalKind::ParameterChange { key, value } => BTreeMap::from([(key.clone(), value.clone())]),
+            ProposalKind::TreasurySpend { recipient, amount } => BTreeMap::from([
+                ("recipient".to_string(), recipient.clone()),
+                ("amount".to_string(), amount.to_string()),
+            ]),
+        }
+    }
+}
+
+/// Stored type label and parameters for a proposal received from a peer
+pub fn from_protocol(proposal_type: &ProposalType) -> (String, BTreeMap<String, String>) {
+    let (label, parameters): (&str, Vec<(&str, String)>) = match proposal_type {
+        ProposalType::General => ("text", Vec::new()),
+        ProposalType::ParameterChange { parameter, new_value, .. } => {
+            return (
+                "parameter_change".to_string(),
+                BTreeMap::from([(parameter.clone(), new_value.clone())]),
+            );
+        }
+        ProposalType::FundingRequest { amount, recipient } => (
+            "treasury_spend",
+            vec![("recipient", recipient.clone()), ("amount", amount.to_string())],
+        ),
+        ProposalType::ProtocolUpgrade { version } => ("protocol_upgrade", vec![("version", version.clone())]),
+        ProposalType::ModuleChange { action, module } => (
+            "module_change",
+            vec![("action", action.clone()), ("module", module.clone())],
+        ),
+        ProposalType::Emergency { action } => ("emergency", vec![("action", action.clone())]),
+    };
+    let parameters = parameters.into_iter().map(|(key, value)| (key.to_string(), value)).collect();
+    (label.to_string(), parameters)
+}
+
+/// Protocol form of a stored proposal type, for announcing it to peers
+///
+/// `current` holds the governance parameters in effect, reported as the old
+/// value of a parameter change. Types the protocol can't express, and
+/// parameters that don't fit their type, are announced as general proposals.
+pub fn to_protocol(
+    label: &str,
+    parameters: &BTreeMap<String, String>,
+    current: &HashMap<String, String>,
+) -> ProposalType {
+    let get = |key: &str| parameters.get(key).cloned();
+    let typed = match label {
+        "parameter_change" => parameters.iter().next().map(|(parameter, value)| ProposalType::ParameterChange {
+            parameter: parameter.clone(),
+            old_value: current.get(parameter).cloned().unwrap_or_default(),
+            new_value: value.clone(),
+        }),
+        "treasury_spend" => get("recipient").zip(get("amount").and_then(|a| a.parse().ok())).map(
+            |(recipient, amount)| ProposalType::FundingRequest { amount, recipient },
+        ),
+        "protocol_upgrade" => get("version").map(|version| ProposalType::ProtocolUpgrade { version }),
+        "module_change" => get("action")
+            .zip(get("module"))
+            .map(|(action, module)| ProposalType::ModuleChange { action, module }),
+        "emergency" => get("action").map(|action| ProposalType::Emergency { action }),
+        _ => None,
+    };
+    typed.unwrap_or(ProposalType::General)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mycelial_network::Keypair;
+
+    fn peer() -> String {
+        Keypair::generate_ed25519().public().to_peer_id().to_base58()
+    }
+
+    #[test]
+    fn test_kinds_parse_from_client_json() {
+        let text: ProposalKind = serde_json::from_str(r#""text""#).unwrap();
+        assert_eq!(text, ProposalKind::Text);
+        let change: ProposalKind =
+            serde_json::from_str(r#"{"parameter_change":{"key":"sybil_warning_bloc","value":"3"}}"#).unwrap();
+        assert_eq!(change.label(), "parameter_change");
+        // A typed proposal without its payload doesn't parse
+        assert!(serde_json::from_str::<ProposalKind>(r#""treasury_spend""#).is_err());
+        assert!(serde_json::from_str::<ProposalKind>(r#"{"treasury_spend":{"amount":5}}"#).is_err());
+    }
+
+    #[test]
+    fn test_parameter_change_validation() {
+        let change = |key: &str, value: &str| ProposalKind::ParameterChange {
+            key: key.to_string(),
+            value: value.to_string(),
+        };
+        assert_eq!(
+            change(" sybil_warning_bloc ", " 3 ").validate(),
+            Ok(change("sybil_warning_bloc", "3"))
+        );
+        assert!(change("", "3").validate().is_err());
+        // Keys the node has no setting for are refused
+        assert!(change("min_quorum", "3").validate().is_err());
+        assert!(change("recipient", "3").validate().is_err());
+        assert!(change("sybil_warning_bloc", "  ").validate().is_err());
+        assert!(change("sybil_warning_bloc", "3\namount = 100").validate().is_err());
+        assert_eq!(
+            change("sybil_warning_bloc", "3").parameters(),
+            BTreeMap::from([("sybil_warning_bloc".to_string(), "3".to_string())])
+        );
+    }
+
+    #[test]
+    fn test_treasury_spend_validation() {
+        let recipient = peer();
+        let spend = |recipient: &str, amount: f64| ProposalKind::TreasurySpend {
+            recipient: recipient.to_string(),
+            amount,
+        };
+        assert_eq!(
+            spend(&format!(" {} ", recipient), 25.0).validate(),
+            Ok(spend(&recipient, 25.0))
+        );
+        assert!(spend("not-a-peer", 25.0).validate().is_err());
+        assert!(spend(&recipient, 0.0).validate().is_err());
+        assert!(spend(&recipient, -5.0).validate().is_err());
+        assert!(spend(&recipient, f64::INFINITY).validate().is_err());
+        assert!(spend(&recipient, f64::NAN).validate().is_err());
+        assert_eq!(spend(&recipient, 25.0).parameters()["amount"], "25");
+    }
+
+    #[test]
+    fn test_text_validation_and_protocol_round_trip() {
+        assert_eq!(ProposalKind::Text.validate(), Ok(ProposalKind::Text));
+        assert!(ProposalKind::Text.parameters().is_empty());
+
+        let recipient = peer();
+        let spend = ProposalKind::TreasurySpend { recipient: recipient.clone(), amount: 12.5 };
+        let protocol = to_protocol(spend.label(), &spend.parameters(), &HashMap::new());
+        assert!(matches!(
+            &protocol,
+            ProposalType::FundingRequest { amount, recipient: to } if *amount == 12.5 && *to == recipient
+        ));
+        assert_eq!(from_protocol(&protocol), (spend.label().to_string(), spend.parameters()));
+
+        let current = HashMap::from([("sybil_warning_bloc".to_string(), "2".to_string())]);
+        let change = ProposalKind::ParameterChange { key: "sybil_warning_bloc".to_string(), value: "3".to_string() };
+        match to_protocol(change.label(), &change.parameters(), &current) {
+            ProposalType::ParameterChange { parameter, old_value, new_value } => {
+                assert_eq!((parameter.as_str(), old_value.as_str(), new_value.as_str()), ("sybil_warning_bloc", "2", "3"));
+            }
+            other => panic!("expected parameter change, got {:?}", other),
+        }
+
+        assert!(matches!(to_protocol("text", &BTreeMap::new(), &current), ProposalType::General));
+        // Parameters that don't fit their type fall back to a general proposal
+        assert!(matches!(to_protocol("treasury_spend", &BTreeMap::new(), &current), ProposalType::General));
+        assert_eq!(from_protocol(&ProposalType::General), ("text".to_string(), BTreeMap::new()));
+    }
+}
@@ -0,0 +1,158 @@
+//! Proposal deadline reminders
+//!
+//! A background task periodically checks open proposals and emits a
+//! [`WsMessage::ProposalReminder`] each time a configured fraction of the
+//! voting period elapses, nudging participation before the deadline.
+
+use parking_lot::Mutex;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use tracing::warn;
+
+use crate::server::messages::WsMessage;
+use crate::server::governance::tally_proposal;
+use crate::AppState;
+
+/// Tracks which reminder points have fired for each open proposal
+#[derive(Default)]
+pub struct ReminderTracker {
+    /// Proposal ID -> number of reminder points already crossed
+    fired: Mutex<HashMap<String, usize>>,
+}
+
+impl ReminderTracker {
+    /// Record progress through a proposal's voting period
+    ///
+    /// Returns `true` if `elapsed` crosses a reminder point that had not fired
+    /// yet. Crossing several points at once (e.g. after a restart) yields a
+    /// single reminder.
+    pub fn advance(&self, proposal_id: &str, points: &[f64], elapsed: f64) -> bool {
+        let crossed = points.iter().filter(|point| elapsed >= **point).count();
+        let mut fired = self.fired.lock();
+        let entry = fired.entry(proposal_id.to_string()).or_insert(0);
+        if crossed > *entry {
+            *entry = crossed;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Forget proposals that are no longer open
+    pub fn retain(&self, open: &HashSet<String>) {
+        self.fired.lock().retain(|id, _| open.contains(id));
+    }
+}
+
+/// Check open proposals on a fixed interval until the process exits
+pub async fn run_reminder_scheduler(state: Arc<AppState>) {
+    let mut interval = tokio::time::interval(state.config.reminder_interval);
+    loop {
+        interval.tick().await;
+        check_reminders(&state, chrono::Utc::now().timestamp_millis()).await;
+    }
+}
+
+/// Emit reminders for open proposals that reached a new reminder point
+pub async fn check_reminders(state: &AppState, now: i64) {
+    let proposals = match state.store.list_proposals().await {
+        Ok(proposals) => proposals,
+        Err(e) => {
+            warn!("Failed to load proposals for reminders: {}", e);
+            return;
+        }
+    };
+
+    let mut open = HashSet::new();
+    for proposal in proposals {
+        if proposal.status != "active" || proposal.deadline <= now {
+            continue;
+        }
+        open.insert(proposal.id.clone());
+
+        let period = proposal.deadline - proposal.created_at;
+        if period <= 0 {
+            continue;
+        }
+        let elapsed = (now - proposal.created_at) as f64 / period as f64;
+        if !state
+            .reminders
+            .advance(&proposal.id, &state.config.reminder_points, elapsed)
+        {
+            continue;
+        }
+
+        let tally = match tally_proposal(state, &proposal.id).await {
+            Ok(tally) => tally,
+            Err(e) => {
+                warn!("Failed to tally votes for {}: {}", proposal.id, e);
+                continue;
+            }
+        };
+        let _ = state.event_tx.send(WsMessage::ProposalReminder {
+            proposal_id: proposal.id,
+            closes_in_secs: ((proposal.deadline - now) / 1000) as u64,
+            current_tally: tally.into(),
+        });
+    }
+
+    state.reminders.retain(&open);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::test_state;
+    use mycelial_protocol::{PassingRule, QuorumMode};
+    use mycelial_state::ProposalRecord;
+
+    #[tokio::test]
+    async fn test_reminder_fires_once() {
+        let (state, _commands) = test_state().await;
+        let mut events = state.event_tx.subscribe();
+
+        state
+            .store
+            .upsert_proposal(&ProposalRecord {
+                id: "p1".to_string(),
+                proposer: "alice".to_string(),
+                title: "Title".to_string(),
+                description: "Description".to_string(),
+                proposal_type: "text".to_string(),
+                parameters: Default::default(),
+                status: "active".to_string(),
+                quorum: 3,
+                quorum_fraction: 0.5,
+                quorum_mode: QuorumMode::Snapshot,
+                passing_rule: PassingRule::SimpleMajority,
+                deadline: 100_000,
+                created_at: 0,
+                version: 1,
+            })
+            .await
+            .unwrap();
+
+        // Before the first point nothing fires
+        check_reminders(&state, 40_000).await;
+        assert!(events.try_recv().is_err());
+
+        // Crossing 50% fires exactly once
+        check_reminders(&state, 50_000).await;
+        match events.try_recv().unwrap() {
+            WsMessage::ProposalReminder { proposal_id, closes_in_secs, current_tally } => {
+                assert_eq!(proposal_id, "p1");
+                assert_eq!(closes_in_secs, 50);
+                assert_eq!(current_tally.voters, 0);
+            }
+            other => panic!("unexpected message: {:?}", other),
+        }
+        check_reminders(&state, 60_000).await;
+        assert!(events.try_recv().is_err());
+
+        // The 90% point fires its own reminder
+        check_reminders(&state, 91_000).await;
+        assert!(matches!(events.try_recv(), Ok(WsMessage::ProposalReminder { .. })));
+        check_reminders(&state, 95_000).await;
+        assert!(events.try_recv().is_err());
+    }
+}
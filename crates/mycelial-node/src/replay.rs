@@ -0,0 +1,254 @@
+//! Replay protection for inbound network messages
+//!
+//! Gossipsub deduplicates by content hash only for a short cache window, and a
+//! peer can re-publish an old message with different framing. The
+//! [`ReplayGuard`] remembers the logical IDs of recently processed protocol
+//! messages per category so duplicates are dropped at the ingestion boundary
+//! before they can double-count votes or transfers.
+
+use lru::LruCache;
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::num::NonZeroUsize;
+use std::time::{Duration, Instant};
+
+use mycelial_network::EconomicsEvent;
+use mycelial_protocol::{CreditMessage, GovernanceMessage, ResourceMessage, VouchMessage};
+
+/// Category of inbound message, each tracked independently
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MessageCategory {
+    Chat,
+    Vouch,
+    Credit,
+    Governance,
+    Resource,
+    /// Nonces of signed client actions
+    Action,
+}
+
+/// Remembers recently seen message IDs per category
+pub struct ReplayGuard {
+    window: Duration,
+    capacity: NonZeroUsize,
+    seen: Mutex<HashMap<MessageCategory, LruCache<String, Instant>>>,
+}
+
+impl ReplayGuard {
+    /// Create a guard remembering up to `capacity` IDs per category for `window`
+    pub fn new(capacity: usize, window: Duration) -> Self {
+        Self {
+            window,
+            capacity: NonZeroUsize::new(capacity).unwrap_or(NonZeroUsize::MIN),
+            seen: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Record an ID, returning `false` if it was already seen within the window
+    pub fn check_and_record(&self, category: MessageCategory, id: &str) -> bool {
+        let now = Instant::now();
+        let mut seen = self.seen.lock();
+        let cache = seen
+            .entry(category)
+            .or_insert_with(|| LruCache::new(self.capacity));
+
+        if let Some(first_seen) = cache.get(id) {
+            if now.duration_since(*first_seen) < self.window {
+                return false;
+            }
+        }
+
+        cache.put(id.to_string(), now);
+        true
+    }
+}
+
+/// Derive the category and logical ID of an economics message
+///
+/// Messages that carry their own UUID use it directly; the rest are keyed by
+/// the fields that identify a single logical action. Timestamps are left out
+/// of those keys, so re-sending an action later doesn't make it new. Votes
+/// are the exception: a voter may change their vote back and forth, so each
+/// vote is keyed by its signature nonce, or failing that its timestamp,
+/// rather than by its content.
+pub fn replay_key(event: &EconomicsEvent) -> (MessageCategory, String) {
+    match event {
+        EconomicsEvent::Vouch(msg) => {
+            let key = match msg {
+                VouchMessage::VouchRequest(req) => req.id.to_string(),
+                VouchMessage::VouchAck(ack) => format!("ack:{}:{}", ack.vouch_id, ack.from),
+                VouchMessage::VouchRevoke(revoke) => format!(
+                    "revoke:{}:{}:{}",
+                    revoke.voucher,
+                    revoke.vouchee,
+                    revoke.timestamp.timestamp_millis()
+                ),
+                VouchMessage::ReputationUpdate(update) => format!(
+                    "reputation:{}:{}",
+                    update.peer_id,
+                    update.timestamp.timestamp_millis()
+                ),
+            };
+            (MessageCategory::Vouch, key)
+        }
+        EconomicsEvent::Credit(msg) => {
+            let key = match msg {
+                CreditMessage::CreateLine(line) => line.id.to_string(),
+                CreditMessage::LineAck(ack) => format!("line_ack:{}:{}", ack.line_id, ack.from),
+                CreditMessage::Transfer(transfer) => transfer.id.to_string(),
+                CreditMessage::TransferAck(ack) => format!("transfer_ack:{}:{}", ack.transfer_id, ack.from),
+                CreditMessage::LineUpdate(update) => format!(
+                    "line_update:{}:{}",
+                    update.line_id,
+                    update.last_transaction.timestamp_millis()
+                ),
+                CreditMessage::PaymentRequest(request) => request.id.to_string(),
+            };
+            (MessageCategory::Credit, key)
+        }
+        EconomicsEvent::Governance(msg) => {
+            let key = match msg {
+                GovernanceMessage::CreateProposal(proposal) => proposal.id.to_string(),
+                GovernanceMessage::CastVote(vote) => match &vote.signature {
+                    Some(signature) => format!("vote:{}:{}", signature.signer, signature.nonce),
+                    None => format!(
+                        "vote:{}:{}:{}",
+                        vote.proposal_id,
+                        vote.voter,
+                        vote.timestamp.timestamp_millis()
+                    ),
+                },
+                GovernanceMessage::ProposalUpdate(update) => format!(
+                    "update:{}:{}",
+                    update.proposal_id,
+                    update.timestamp.timestamp_millis()
+                ),
+                GovernanceMessage::ProposalExecuted(executed) => {
+                    format!("executed:{}", executed.proposal_id)
+                }
+                GovernanceMessage::AmendProposal(amendment) => {
+                    format!("amend:{}:{}", amendment.proposal_id, amendment.version)
+                }
+                GovernanceMessage::CancelProposal(cancellation) => {
+                    format!("cancel:{}", cancellation.proposal_id)
+                }
+                GovernanceMessage::SyncRequest(request) => format!("sync:{}", request.id),
+                GovernanceMessage::SyncResponse(response) => {
+                    format!("sync_response:{}:{}:{}", response.request_id, response.responder, response.page)
+                }
+            };
+            (MessageCategory::Governance, key)
+        }
+        EconomicsEvent::Resource(msg) => {
+            let key = match msg {
+                ResourceMessage::Contribution(contrib) => contrib.id.to_string(),
+                ResourceMessage::Metrics(metrics) => format!(
+                    "metrics:{}:{}",
+                    metrics.peer_id,
+                    metrics.timestamp.timestamp_millis()
+                ),
+                ResourceMessage::PoolUpdate(pool) => {
+                    format!("pool:{}", pool.timestamp.timestamp_millis())
+                }
+            };
+            (MessageCategory::Resource, key)
+        }
+    }
+}
+
+/// Peer that alone may publish an economics message, if it names one
+///
+/// Checked against the gossip source before the message's replay key is
+/// recorded, so a forgery can't claim the key of the genuine message.
+pub fn author(event: &EconomicsEvent) -> Option<&str> {
+    match event {
+        EconomicsEvent::Vouch(VouchMessage::VouchRequest(req)) => Some(&req.voucher),
+        EconomicsEvent::Vouch(VouchMessage::VouchAck(ack)) => Some(&ack.from),
+        EconomicsEvent::Vouch(VouchMessage::VouchRevoke(revoke)) => Some(&revoke.voucher),
+        EconomicsEvent::Credit(CreditMessage::CreateLine(line)) => Some(&line.creditor),
+        EconomicsEvent::Credit(CreditMessage::Transfer(transfer)) => Some(&transfer.from),
+        EconomicsEvent::Credit(CreditMessage::TransferAck(ack)) => Some(&ack.from),
+        EconomicsEvent::Governance(GovernanceMessage::CreateProposal(proposal)) => Some(&proposal.proposer),
+        EconomicsEvent::Governance(GovernanceMessage::CastVote(vote)) => Some(&vote.voter),
+        EconomicsEvent::Governance(GovernanceMessage::AmendProposal(amendment)) => Some(&amendment.proposer),
+        EconomicsEvent::Governance(GovernanceMessage::CancelProposal(cancellation)) => Some(&cancellation.proposer),
+        EconomicsEvent::Governance(GovernanceMessage::SyncRequest(request)) => Some(&request.requester),
+        EconomicsEvent::Governance(GovernanceMessage::SyncResponse(response)) => Some(&response.responder),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_duplicate_rejected_within_window() {
+        let guard = ReplayGuard::new(16, Duration::from_secs(60));
+        assert!(guard.check_and_record(MessageCategory::Governance, "a"));
+        assert!(!guard.check_and_record(MessageCategory::Governance, "a"));
+        // Categories are tracked independently
+        assert!(guard.check_and_record(MessageCategory::Credit, "a"));
+    }
+
+    #[test]
+    fn test_vote_key_unique_per_message() {
+        use mycelial_protocol::{ActionSignature, CastVote, Vote};
+
+        let proposal_id = uuid::Uuid::new_v4();
+        let key = |vote: &CastVote| replay_key(&EconomicsEvent::Governance(GovernanceMessage::CastVote(vote.clone()))).1;
+
+        // Yes, then no, then yes again: the last vote is not a replay of the first
+        let yes = CastVote::new(proposal_id, "alice".to_string(), Vote::For, 1.0);
+        let mut no = yes.clone();
+        no.vote = Vote::Against;
+        no.timestamp = yes.timestamp + chrono::Duration::seconds(10);
+        let mut yes_again = yes.clone();
+        yes_again.timestamp = yes.timestamp + chrono::Duration::seconds(20);
+        assert_ne!(key(&yes), key(&no));
+        assert_ne!(key(&yes), key(&yes_again));
+        assert_eq!(key(&yes), key(&yes.clone()));
+
+        // Signed votes are keyed by their nonce
+        let signed = |nonce: &str, timestamp| {
+            let mut vote = yes.clone();
+            vote.timestamp = timestamp;
+            vote.signature = Some(ActionSignature {
+                signer: "did:key:alice".to_string(),
+                payload: String::new(),
+                nonce: nonce.to_string(),
+                timestamp: 0,
+                signature: String::new(),
+            });
+            vote
+        };
+        assert_eq!(key(&signed("n1", yes.timestamp)), key(&signed("n1", yes_again.timestamp)));
+        assert_ne!(key(&signed("n1", yes.timestamp)), key(&signed("n2", yes.timestamp)));
+    }
+
+    #[test]
+    fn test_transfer_ack_keyed_by_sender() {
+        use mycelial_protocol::CreditTransferAck;
+
+        let ack = |from: &str| {
+            EconomicsEvent::Credit(CreditMessage::TransferAck(CreditTransferAck {
+                transfer_id: uuid::Uuid::nil(),
+                from: from.to_string(),
+                success: true,
+                new_balance: None,
+                error: None,
+                timestamp: chrono::Utc::now(),
+            }))
+        };
+        assert_ne!(replay_key(&ack("alice")).1, replay_key(&ack("bob")).1);
+        assert_eq!(author(&ack("alice")), Some("alice"));
+    }
+
+    #[test]
+    fn test_duplicate_accepted_after_window() {
+        let guard = ReplayGuard::new(16, Duration::from_millis(10));
+        assert!(guard.check_and_record(MessageCategory::Chat, "a"));
+        std::thread::sleep(Duration::from_millis(20));
+        assert!(guard.check_and_record(MessageCategory::Chat, "a"));
+    }
+}
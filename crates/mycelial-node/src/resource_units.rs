@@ -0,0 +1,218 @@
+//! Resource contribution units
+//!
+//! Peers report contributions with free-form units ("GB", "megabytes",
+//! "Mbps", ...). Summing those as reported mixes scales, so contributions are
+//! converted to one canonical unit per resource type before they are stored
+//! or published: bytes for storage, bits per second for bandwidth and relay,
+//! and cores for compute. Storage units are matched case-insensitively, so
+//! an abbreviated `b` always means bytes there. Rate abbreviations follow the
+//! usual convention instead: `b` is bits and `B` is bytes, so "Mb/s" and
+//! "Mbps" are megabits while "MB/s" and "MBps" are megabytes. Units stored
+//! before conversion are rewritten at startup by [`migrate_stored`].
+
+use mycelial_protocol::ResourceType;
+use mycelial_state::SqliteStore;
+use tracing::{info, warn};
+
+use crate::server::resources::{parse_resource_type, resource_type_label};
+
+/// Canonical storage unit
+pub const BYTES: &str = "bytes";
+
+/// Canonical bandwidth and relay unit
+pub const BITS_PER_SECOND: &str = "bits/s";
+
+/// Canonical compute unit
+pub const CORES: &str = "cores";
+
+/// Storage units and their size in bytes
+const STORAGE_UNITS: &[(&[&str], f64)] = &[
+    (&["b", "byte", "bytes"], 1.0),
+    (&["kb", "kilobyte", "kilobytes"], 1e3),
+    (&["mb", "megabyte", "megabytes"], 1e6),
+    (&["gb", "gigabyte", "gigabytes"], 1e9),
+    (&["tb", "terabyte", "terabytes"], 1e12),
+    (&["kib", "kibibyte", "kibibytes"], 1024.0),
+    (&["mib", "mebibyte", "mebibytes"], 1_048_576.0),
+    (&["gib", "gibibyte", "gibibytes"], 1_073_741_824.0),
+    (&["tib", "tebibyte", "tebibytes"], 1_099_511_627_776.0),
+];
+
+/// Spelled-out rate units and their size in bits per second
+///
+/// These are matched case-insensitively; abbreviations are handled by
+/// [`rate_abbreviation_scale`].
+const RATE_UNITS: &[(&[&str], f64)] = &[
+    (&["bit/s", "bits/s"], 1.0),
+    (&["kbit/s", "kilobits/s"], 1e3),
+    (&["mbit/s", "megabits/s"], 1e6),
+    (&["gbit/s", "gigabits/s"], 1e9),
+    (&["bytes/s"], 8.0),
+    (&["kilobytes/s"], 8e3),
+    (&["megabytes/s"], 8e6),
+    (&["gigabytes/s"], 8e9),
+];
+
+/// Rate prefixes and their multiplier
+const RATE_PREFIXES: &[(&str, f64)] = &[("", 1.0), ("k", 1e3), ("m", 1e6), ("g", 1e9)];
+
+/// Size in bits per second of an abbreviated rate such as "kbps" or "MB/s"
+///
+/// The prefix is matched case-insensitively, but `b` means bits and `B`
+/// means bytes.
+fn rate_abbreviation_scale(unit: &str) -> Option<f64> {
+    let stem = unit.strip_suffix("ps").or_else(|| unit.strip_suffix("/s"))?;
+    let (prefix, bits) = match stem.strip_suffix('b') {
+        Some(prefix) => (prefix, 1.0),
+        None => (stem.strip_suffix('B')?, 8.0),
+    };
+    RATE_PREFIXES
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case(prefix))
+        .map(|(_, scale)| scale * bits)
+}
+
+/// Compute units and their size in cores
+const COMPUTE_UNITS: &[(&[&str], f64)] = &[
+    (&["core", "cores", "cpu", "cpus", "vcpu", "vcpus"], 1.0),
+    (&["millicore", "millicores", "m"], 1e-3),
+];
+
+/// Check a contribution's amount and convert it to the canonical unit for its type
+///
+/// Returns the converted amount and canonical unit. The amount must be
+/// finite and non-negative. Types without a canonical unit keep their unit,
+/// trimmed and lowercased.
+pub fn normalize(resource_type: &ResourceType, amount: f64, unit: &str) -> Result<(f64, String), String> {
+    if !amount.is_finite() || amount < 0.0 {
+        return Err("Resource amount must be a finite, non-negative number".to_string());
+    }
+    let trimmed = unit.trim();
+    let unit = trimmed.to_lowercase();
+    let (units, canonical) = match resource_type {
+        ResourceType::Storage => (STORAGE_UNITS, BYTES),
+        ResourceType::Bandwidth | ResourceType::Relay => (RATE_UNITS, BITS_PER_SECOND),
+        ResourceType::Compute => (COMPUTE_UNITS, CORES),
+        ResourceType::Other(_) => {
+            if unit.is_empty() {
+                return Err("Resource unit must not be empty".to_string());
+            }
+            return Ok((amount, unit));
+        }
+    };
+    let scale = units
+        .iter()
+        .find(|(names, _)| names.contains(&unit.as_str()))
+        .map(|(_, scale)| *scale);
+    let scale = match resource_type {
+        ResourceType::Bandwidth | ResourceType::Relay => scale.or_else(|| rate_abbreviation_scale(trimmed)),
+        _ => scale,
+    };
+    scale
+        .map(|scale| (amount * scale, canonical.to_string()))
+        .ok_or_else(|| format!("Unrecognized unit '{}' for {}", unit, resource_type_label(resource_type)))
+}
+
+/// Convert stored contributions to their canonical units
+///
+/// Contributions stored before units were normalized keep whatever unit
+/// their reporter used, which skews rankings and pool capacity. Rewriting is
+/// idempotent, so this runs on every startup; contributions with a unit that
+/// can't be converted are left as they are. Returns how many were rewritten.
+pub async fn migrate_stored(store: &SqliteStore) -> mycelial_state::Result<usize> {
+    let mut migrated = 0;
+    for contribution in store.list_all_resource_contributions().await? {
+        let resource_type = parse_resource_type(&contribution.resource_type);
+        match normalize(&resource_type, contribution.amount, &contribution.unit) {
+            Ok((amount, unit)) if amount != contribution.amount || unit != contribution.unit => {
+                store.update_resource_contribution_unit(&contribution.id, amount, &unit).await?;
+                migrated += 1;
+            }
+            Ok(_) => {}
+            Err(e) => warn!("Leaving contribution {} unconverted: {}", contribution.id, e),
+        }
+    }
+    if migrated > 0 {
+        info!("Converted {} stored contributions to canonical units", migrated);
+    }
+    Ok(migrated)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_units_normalized_per_type() {
+        for unit in ["MB", "mb", "megabytes", " Megabyte "] {
+            assert_eq!(normalize(&ResourceType::Storage, 5.0, unit), Ok((5e6, BYTES.to_string())));
+        }
+        assert_eq!(normalize(&ResourceType::Storage, 2.0, "GiB"), Ok((2_147_483_648.0, BYTES.to_string())));
+        assert_eq!(normalize(&ResourceType::Bandwidth, 100.0, "Mbps"), Ok((1e8, BITS_PER_SECOND.to_string())));
+        assert_eq!(normalize(&ResourceType::Bandwidth, 1.0, "MB/s"), Ok((8e6, BITS_PER_SECOND.to_string())));
+        assert_eq!(normalize(&ResourceType::Relay, 3.0, "kbit/s"), Ok((3e3, BITS_PER_SECOND.to_string())));
+        assert_eq!(normalize(&ResourceType::Compute, 500.0, "millicores"), Ok((0.5, CORES.to_string())));
+        assert_eq!(
+            normalize(&ResourceType::Other("gpu".to_string()), 2.0, " Cards "),
+            Ok((2.0, "cards".to_string()))
+        );
+        assert_eq!(normalize(&ResourceType::Storage, 0.0, "bytes"), Ok((0.0, BYTES.to_string())));
+    }
+
+    #[test]
+    fn test_rate_abbreviations_distinguish_bits_and_bytes() {
+        for (unit, bits) in [("Mbps", 1e6), ("Mb/s", 1e6), ("mbps", 1e6), ("MBps", 8e6), ("MB/s", 8e6), ("mB/s", 8e6)] {
+            assert_eq!(normalize(&ResourceType::Bandwidth, 1.0, unit), Ok((bits, BITS_PER_SECOND.to_string())), "{unit}");
+        }
+        assert_eq!(normalize(&ResourceType::Relay, 1.0, "bps"), Ok((1.0, BITS_PER_SECOND.to_string())));
+        assert_eq!(normalize(&ResourceType::Relay, 1.0, "Bps"), Ok((8.0, BITS_PER_SECOND.to_string())));
+        assert_eq!(normalize(&ResourceType::Relay, 1.0, "Gigabytes/s"), Ok((8e9, BITS_PER_SECOND.to_string())));
+        assert!(normalize(&ResourceType::Bandwidth, 1.0, "Xbps").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_stored_units_migrated() {
+        use mycelial_state::ContributionRecord;
+
+        let store = SqliteStore::new(":memory:").await.unwrap();
+        let record = |id: &str, resource_type: &str, amount: f64, unit: &str| ContributionRecord {
+            id: id.to_string(),
+            peer_id: "peer".to_string(),
+            resource_type: resource_type.to_string(),
+            amount,
+            unit: unit.to_string(),
+            timestamp: 1,
+        };
+        for contribution in [
+            record("a", "storage", 2.0, "GB"),
+            record("b", "bandwidth", 1.0, "MB/s"),
+            record("c", "compute", 4.0, CORES),
+            record("d", "storage", 1.0, "furlongs"),
+        ] {
+            store.insert_resource_contribution(&contribution).await.unwrap();
+        }
+
+        assert_eq!(migrate_stored(&store).await.unwrap(), 2);
+        let storage = store.list_resource_contributions("storage").await.unwrap();
+        let converted = storage.iter().find(|c| c.id == "a").unwrap();
+        assert_eq!((converted.amount, converted.unit.as_str()), (2e9, BYTES));
+        let unknown = storage.iter().find(|c| c.id == "d").unwrap();
+        assert_eq!(unknown.unit, "furlongs");
+        let bandwidth = &store.list_resource_contributions("bandwidth").await.unwrap()[0];
+        assert_eq!((bandwidth.amount, bandwidth.unit.as_str()), (8e6, BITS_PER_SECOND));
+
+        // Already canonical, so a second run changes nothing
+        assert_eq!(migrate_stored(&store).await.unwrap(), 0);
+    }
+
+    #[test]
+    fn test_invalid_contributions_rejected() {
+        assert!(normalize(&ResourceType::Storage, -5.0, "bytes").is_err());
+        assert!(normalize(&ResourceType::Storage, f64::NAN, "bytes").is_err());
+        assert!(normalize(&ResourceType::Bandwidth, f64::INFINITY, "bps").is_err());
+        // Units of another type, or none at all
+        assert!(normalize(&ResourceType::Storage, 1.0, "Mbps").is_err());
+        assert!(normalize(&ResourceType::Compute, 1.0, "furlongs").is_err());
+        assert!(normalize(&ResourceType::Other("gpu".to_string()), 1.0, "  ").is_err());
+    }
+}
@@ -0,0 +1,138 @@
+//! WebSocket upgrade authentication
+//!
+//! A dashboard connection can publish to the gossip network as this node, so
+//! upgrades must present the configured bearer token. Browsers can't set
+//! headers on a WebSocket, so besides `Authorization: Bearer <token>` the
+//! token is accepted as a `?token=` query parameter or as a
+//! `bearer.<token>` entry in `Sec-WebSocket-Protocol`. With
+//! `open_websocket` set, every upgrade is accepted (local development only).
+
+use axum::http::{header, HeaderMap, StatusCode};
+use serde::Deserialize;
+
+use crate::config::ServerConfig;
+
+/// Subprotocol prefix carrying the token in `Sec-WebSocket-Protocol`
+const PROTOCOL_PREFIX: &str = "bearer.";
+
+/// Query parameters carrying the upgrade token
+#[derive(Debug, Default, Deserialize)]
+pub struct TokenParams {
+    pub token: Option<String>,
+}
+
+/// How an upgrade request was accepted
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Accepted {
+    /// Without a subprotocol
+    Plain,
+    /// Via a `Sec-WebSocket-Protocol` entry, which must be echoed back
+    Protocol(String),
+}
+
+/// Whether a presented token equals the expected one, compared in constant time
+///
+/// Timing reveals at most whether the lengths differ.
+pub fn tokens_match(expected: &str, presented: &str) -> bool {
+    let (expected, presented) = (expected.as_bytes(), presented.as_bytes());
+    if expected.len() != presented.len() {
+        return false;
+    }
+    let diff = expected.iter().zip(presented).fold(0u8, |diff, (a, b)| diff | (a ^ b));
+    std::hint::black_box(diff) == 0
+}
+
+/// Check an upgrade request's token against the configuration
+///
+/// Fails with 401 when no token matches, including when none is configured.
+pub fn authorize_upgrade(
+    config: &ServerConfig,
+    query_token: Option<&str>,
+    headers: &HeaderMap,
+) -> Result<Accepted, StatusCode> {
+    if config.open_websocket {
+        return Ok(Accepted::Plain);
+    }
+    let Some(expected) = config.ws_token.as_deref() else {
+        return Err(StatusCode::UNAUTHORIZED);
+    };
+
+    let bearer = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    let matches = |token: Option<&str>| token.is_some_and(|token| tokens_match(expected, token));
+    if matches(query_token) || matches(bearer) {
+        return Ok(Accepted::Plain);
+    }
+
+    let protocol = headers
+        .get_all(header::SEC_WEBSOCKET_PROTOCOL)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(str::trim)
+        .find(|protocol| matches(protocol.strip_prefix(PROTOCOL_PREFIX)));
+    match protocol {
+        Some(protocol) => Ok(Accepted::Protocol(protocol.to_string())),
+        None => Err(StatusCode::UNAUTHORIZED),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    fn secured() -> ServerConfig {
+        ServerConfig {
+            ws_token: Some("s3cret".to_string()),
+            ..ServerConfig::default()
+        }
+    }
+
+    #[test]
+    fn test_upgrade_accepted_with_token() {
+        let config = secured();
+        let none = HeaderMap::new();
+        assert_eq!(authorize_upgrade(&config, Some("s3cret"), &none), Ok(Accepted::Plain));
+
+        let mut headers = HeaderMap::new();
+        headers.insert(header::AUTHORIZATION, HeaderValue::from_static("Bearer s3cret"));
+        assert_eq!(authorize_upgrade(&config, None, &headers), Ok(Accepted::Plain));
+
+        let mut headers = HeaderMap::new();
+        headers.insert(header::SEC_WEBSOCKET_PROTOCOL, HeaderValue::from_static("json, bearer.s3cret"));
+        assert_eq!(
+            authorize_upgrade(&config, None, &headers),
+            Ok(Accepted::Protocol("bearer.s3cret".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_upgrade_rejected_without_valid_token() {
+        let config = secured();
+        let mut headers = HeaderMap::new();
+        headers.insert(header::AUTHORIZATION, HeaderValue::from_static("Bearer wrong"));
+        headers.insert(header::SEC_WEBSOCKET_PROTOCOL, HeaderValue::from_static("bearer.wrong"));
+
+        assert_eq!(authorize_upgrade(&config, None, &HeaderMap::new()), Err(StatusCode::UNAUTHORIZED));
+        assert_eq!(authorize_upgrade(&config, Some("wrong"), &headers), Err(StatusCode::UNAUTHORIZED));
+        // With no token configured, only open mode lets connections in
+        let unconfigured = ServerConfig::default();
+        assert_eq!(authorize_upgrade(&unconfigured, Some(""), &headers), Err(StatusCode::UNAUTHORIZED));
+        let open = ServerConfig {
+            open_websocket: true,
+            ..ServerConfig::default()
+        };
+        assert_eq!(authorize_upgrade(&open, None, &HeaderMap::new()), Ok(Accepted::Plain));
+    }
+
+    #[test]
+    fn test_tokens_match() {
+        assert!(tokens_match("s3cret", "s3cret"));
+        assert!(!tokens_match("s3cret", "s3creT"));
+        assert!(!tokens_match("s3cret", "s3cre"));
+        assert!(!tokens_match("s3cret", ""));
+    }
+}
@@ -0,0 +1,71 @@
+//! Delivery checkpoints
+//!
+//! Each connection numbers the messages it delivers. Periodically, and once
+//! the stream goes quiet, the server sends a [`WsMessage::Checkpoint`]
+//! stating that every message up to `seq` has been delivered, so clients
+//! persisting events locally know where it is safe to compact.
+
+use super::messages::WsMessage;
+
+/// Counts delivered messages and decides when a checkpoint is due
+#[derive(Debug, Default)]
+pub struct CheckpointTracker {
+    /// Messages delivered on the connection, excluding checkpoints
+    delivered: u64,
+    /// Sequence covered by the last checkpoint sent
+    checkpointed: u64,
+}
+
+impl CheckpointTracker {
+    /// Record that a message was delivered, returning its sequence number
+    pub fn record_delivery(&mut self) -> u64 {
+        self.delivered += 1;
+        self.delivered
+    }
+
+    /// Whether messages were delivered since the last checkpoint
+    pub fn pending(&self) -> bool {
+        self.delivered > self.checkpointed
+    }
+
+    /// Produce a checkpoint covering everything delivered so far, if anything new was
+    pub fn take_checkpoint(&mut self, timestamp: i64) -> Option<WsMessage> {
+        if !self.pending() {
+            return None;
+        }
+        self.checkpointed = self.delivered;
+        Some(WsMessage::Checkpoint {
+            seq: self.delivered,
+            timestamp,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_checkpoint_never_exceeds_delivered() {
+        let mut tracker = CheckpointTracker::default();
+        assert!(tracker.take_checkpoint(0).is_none());
+
+        let mut last_delivered = 0;
+        for _ in 0..3 {
+            last_delivered = tracker.record_delivery();
+        }
+        match tracker.take_checkpoint(1) {
+            Some(WsMessage::Checkpoint { seq, .. }) => assert_eq!(seq, last_delivered),
+            other => panic!("unexpected checkpoint: {:?}", other),
+        }
+
+        // Nothing new delivered, so no new checkpoint
+        assert!(tracker.take_checkpoint(2).is_none());
+
+        last_delivered = tracker.record_delivery();
+        match tracker.take_checkpoint(3) {
+            Some(WsMessage::Checkpoint { seq, .. }) => assert!(seq <= last_delivered),
+            other => panic!("unexpected checkpoint: {:?}", other),
+        }
+    }
+}
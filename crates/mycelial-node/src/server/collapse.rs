@@ -0,0 +1,137 @@
+//! Collapsing of repetitive updates for lagging connections
+//!
+//! Some broadcast events only report the latest state of an entity, such as
+//! a proposal's quorum progress. When a connection with
+//! [`Capability::CollapseUpdates`](super::messages::Capability::CollapseUpdates)
+//! falls behind, the backlog of such events is reduced to the newest update
+//! per entity before delivery, instead of sending every intermediate value.
+//! If the connection falls so far behind that events are dropped while the
+//! backlog is drained, a `Resync` takes their place.
+
+use std::collections::{HashMap, VecDeque};
+use tokio::sync::broadcast::{self, error::TryRecvError};
+
+use super::messages::WsMessage;
+use super::websocket::lag_resync;
+
+/// Entity an event reports the latest state of, if later events supersede it
+fn collapse_key(event: &WsMessage) -> Option<String> {
+    match event {
+        WsMessage::QuorumProgress { proposal_id, .. } => Some(format!("quorum:{}", proposal_id)),
+        WsMessage::ResourcePoolUpdate { resource_type, .. } => Some(format!("pool:{}", resource_type)),
+        _ => None,
+    }
+}
+
+/// Events waiting to be delivered, with superseded updates replaced in place
+#[derive(Debug, Default)]
+pub struct CollapseBuffer {
+    queue: VecDeque<WsMessage>,
+    /// Collapse key -> absolute position of its pending event
+    positions: HashMap<String, usize>,
+    /// Events popped so far, to turn positions into queue indexes
+    popped: usize,
+}
+
+impl CollapseBuffer {
+    /// Queue an event, replacing a pending update for the same entity
+    ///
+    /// The newer value takes the older one's place, so it is delivered no
+    /// later than the update it replaces would have been.
+    pub fn push(&mut self, event: WsMessage) {
+        let Some(key) = collapse_key(&event) else {
+            self.queue.push_back(event);
+            return;
+        };
+        match self.positions.get(&key) {
+            Some(&position) => self.queue[position - self.popped] = event,
+            None => {
+                self.positions.insert(key, self.popped + self.queue.len());
+                self.queue.push_back(event);
+            }
+        }
+    }
+
+    /// Queue every wanted event already waiting on `rx`, and a `Resync` where
+    /// events were skipped
+    pub fn fill(&mut self, rx: &mut broadcast::Receiver<WsMessage>, wants: impl Fn(&WsMessage) -> bool) {
+        loop {
+            match rx.try_recv() {
+                Ok(event) if wants(&event) => self.push(event),
+                Ok(_) => {}
+                Err(TryRecvError::Lagged(skipped)) => self.push(lag_resync(skipped)),
+                Err(TryRecvError::Empty) | Err(TryRecvError::Closed) => break,
+            }
+        }
+    }
+
+    /// Take the next event to deliver
+    pub fn pop(&mut self) -> Option<WsMessage> {
+        let event = self.queue.pop_front()?;
+        if let Some(key) = collapse_key(&event) {
+            self.positions.remove(&key);
+        }
+        self.popped += 1;
+        Some(event)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mycelial_protocol::QuorumMode;
+
+    fn progress(proposal_id: &str, voters: usize) -> WsMessage {
+        WsMessage::QuorumProgress {
+            proposal_id: proposal_id.to_string(),
+            voters,
+            required: 10,
+            eligible: 20,
+            quorum_mode: QuorumMode::Snapshot,
+        }
+    }
+
+    #[test]
+    fn test_lagging_quorum_updates_collapse_to_latest() {
+        let (tx, mut rx) = broadcast::channel(64);
+        // The client falls behind while a proposal's quorum moves quickly
+        for voters in 1..=5 {
+            tx.send(progress("p1", voters)).unwrap();
+        }
+        tx.send(WsMessage::PeerLeft { peer_id: "alice".to_string() }).unwrap();
+        tx.send(progress("p2", 1)).unwrap();
+        tx.send(progress("p1", 6)).unwrap();
+
+        let mut buffer = CollapseBuffer::default();
+        buffer.fill(&mut rx, |_| true);
+        let delivered: Vec<_> = std::iter::from_fn(|| buffer.pop()).collect();
+
+        assert_eq!(delivered.len(), 3);
+        assert!(matches!(&delivered[0], WsMessage::QuorumProgress { proposal_id, voters: 6, .. } if proposal_id == "p1"));
+        assert!(matches!(&delivered[1], WsMessage::PeerLeft { .. }));
+        assert!(matches!(&delivered[2], WsMessage::QuorumProgress { proposal_id, voters: 1, .. } if proposal_id == "p2"));
+
+        // Once delivered, a new update is queued rather than replacing anything
+        buffer.push(progress("p1", 7));
+        assert!(matches!(buffer.pop(), Some(WsMessage::QuorumProgress { voters: 7, .. })));
+        assert!(buffer.pop().is_none());
+    }
+
+    #[test]
+    fn test_events_dropped_while_filling_become_resync() {
+        let (tx, mut rx) = broadcast::channel(2);
+        for voters in 1..=5 {
+            tx.send(progress("p1", voters)).unwrap();
+        }
+
+        let mut buffer = CollapseBuffer::default();
+        buffer.fill(&mut rx, |_| true);
+
+        match buffer.pop() {
+            Some(WsMessage::Resync { reason }) => assert_eq!(reason, "Missed 3 events while lagging"),
+            other => panic!("expected resync, got {:?}", other),
+        }
+        assert!(matches!(buffer.pop(), Some(WsMessage::QuorumProgress { voters: 5, .. })));
+        assert!(buffer.pop().is_none());
+    }
+}
@@ -0,0 +1,308 @@
+//! Registry of open WebSocket connections
+//!
+//! Every connection registers its reply channel and a close signal under a
+//! generated connection ID, so operators can address a specific client (for
+//! example, to disconnect it during an abuse incident). The registry also
+//! records who each connection is, when it was last heard from and how many
+//! frames are waiting for it, for listing open connections. A connection acts
+//! for a peer (the local node, until it identifies as another) whether or not
+//! it has identified, and presence follows the peers connections act for;
+//! listings only name peers a connection identified as.
+
+use parking_lot::RwLock;
+use std::collections::{BTreeSet, HashMap};
+use std::sync::Arc;
+use tokio::sync::{mpsc, Notify};
+
+use super::heartbeat::Activity;
+use super::messages::{ConnectionEntry, ErrorCode, WsMessage};
+use super::outbound::OutboundQueue;
+use super::session::EncodingStats;
+
+/// Handles needed to reach a registered connection
+struct ConnectionHandle {
+    /// Channel for messages delivered only to the connection
+    reply_tx: mpsc::UnboundedSender<WsMessage>,
+    /// Signalled when the connection should close
+    close: Arc<Notify>,
+    /// Bytes sent to the connection
+    stats: Arc<EncodingStats>,
+    /// Frames waiting to be written to the connection
+    outbound: OutboundQueue,
+    /// When frames were last received from the connection
+    activity: Activity,
+    /// When the connection registered, in milliseconds since the epoch
+    connected_at: i64,
+    /// Peer the connection identified as
+    peer_id: Option<String>,
+    /// Peer the connection acts for, identified or not
+    acts_for: Option<String>,
+}
+
+/// Open connections keyed by connection ID
+#[derive(Default)]
+pub struct ConnectionRegistry {
+    connections: RwLock<HashMap<String, ConnectionHandle>>,
+}
+
+impl ConnectionRegistry {
+    /// Register a connection, returning the signal its send task waits on to close
+    pub fn register(
+        &self,
+        id: &str,
+        reply_tx: mpsc::UnboundedSender<WsMessage>,
+        stats: Arc<EncodingStats>,
+        outbound: OutboundQueue,
+        activity: Activity,
+    ) -> Arc<Notify> {
+        let close = Arc::new(Notify::new());
+        self.connections.write().insert(
+            id.to_string(),
+            ConnectionHandle {
+                reply_tx,
+                close: close.clone(),
+                stats,
+                outbound,
+                activity,
+                connected_at: chrono::Utc::now().timestamp_millis(),
+                peer_id: None,
+                acts_for: None,
+            },
+        );
+        close
+    }
+
+    /// Record the peer a connection acts for before it identifies
+    pub fn act_for(&self, id: &str, peer_id: &str) {
+        if let Some(handle) = self.connections.write().get_mut(id) {
+            handle.acts_for = Some(peer_id.to_string());
+        }
+    }
+
+    /// Record the peer a connection identified as, which it then acts for
+    pub fn identify(&self, id: &str, peer_id: &str) {
+        if let Some(handle) = self.connections.write().get_mut(id) {
+            handle.peer_id = Some(peer_id.to_string());
+            handle.acts_for = Some(peer_id.to_string());
+        }
+    }
+
+    /// Open connections, oldest first
+    pub fn list(&self) -> Vec<ConnectionEntry> {
+        let now = chrono::Utc::now().timestamp_millis();
+        let mut entries: Vec<ConnectionEntry> = self
+            .connections
+            .read()
+            .iter()
+            .map(|(id, handle)| ConnectionEntry {
+                connection_id: id.clone(),
+                peer_id: handle.peer_id.clone(),
+                connected_at: handle.connected_at,
+                last_activity: now - handle.activity.idle_for().as_millis() as i64,
+                queue_depth: handle.outbound.depth(),
+            })
+            .collect();
+        entries.sort_by(|a, b| (a.connected_at, &a.connection_id).cmp(&(b.connected_at, &b.connection_id)));
+        entries
+    }
+
+    /// Whether any open connection acts for `peer_id`
+    pub fn is_connected(&self, peer_id: &str) -> bool {
+        self.connections
+            .read()
+            .values()
+            .any(|handle| handle.acts_for.as_deref() == Some(peer_id))
+    }
+
+    /// Peers that open connections act for, without duplicates
+    pub fn peers(&self) -> Vec<String> {
+        let peers: BTreeSet<String> = self
+            .connections
+            .read()
+            .values()
+            .filter_map(|handle| handle.acts_for.clone())
+            .collect();
+        peers.into_iter().collect()
+    }
+
+    /// Number of open connections
+    pub fn len(&self) -> usize {
+        self.connections.read().len()
+    }
+
+    /// Byte counts for a connection, if it is registered
+    pub fn stats(&self, id: &str) -> Option<Arc<EncodingStats>> {
+        self.connections.read().get(id).map(|handle| handle.stats.clone())
+    }
+
+    /// Outbound queue of a connection, if it is registered
+    pub fn outbound(&self, id: &str) -> Option<OutboundQueue> {
+        self.connections.read().get(id).map(|handle| handle.outbound.clone())
+    }
+
+    /// Remove a connection once it has closed
+    ///
+    /// Returns the peer the connection acted for if no other open connection
+    /// acts for it, so exactly one closing connection sees each peer leave.
+    pub fn unregister(&self, id: &str) -> Option<String> {
+        let mut connections = self.connections.write();
+        let peer_id = connections.remove(id)?.acts_for?;
+        let still_connected = connections.values().any(|handle| handle.acts_for.as_ref() == Some(&peer_id));
+        (!still_connected).then_some(peer_id)
+    }
+
+    /// Disconnect a connection, telling it why
+    ///
+    /// The connection is sent an `Unauthorized` error before its close signal
+    /// fires, so the error is flushed ahead of the Close frame. Returns whether
+    /// the connection existed.
+    pub fn kick(&self, id: &str, reason: &str) -> bool {
+        let Some(handle) = self.connections.write().remove(id) else {
+            return false;
+        };
+        let _ = handle.reply_tx.send(WsMessage::Error {
+            code: ErrorCode::Unauthorized,
+            message: reason.to_string(),
+        });
+        handle.close.notify_one();
+        true
+    }
+
+    /// Tell every connection the node is shutting down and signal it to close
+    ///
+    /// Connections stay registered until their socket has closed, so callers
+    /// can wait for [`is_empty`](Self::is_empty). Returns how many were signalled.
+    pub fn shutdown(&self, reason: &str, reconnect_after_ms: u64) -> usize {
+        let connections = self.connections.read();
+        for handle in connections.values() {
+            let _ = handle.reply_tx.send(WsMessage::Shutdown {
+                reason: reason.to_string(),
+                reconnect_after_ms,
+            });
+            handle.close.notify_one();
+        }
+        connections.len()
+    }
+
+    /// Whether no connections are open
+    pub fn is_empty(&self) -> bool {
+        self.connections.read().is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_kick_closes_only_target() {
+        let registry = ConnectionRegistry::default();
+        let (kicked_tx, mut kicked_rx) = mpsc::unbounded_channel();
+        let (other_tx, mut other_rx) = mpsc::unbounded_channel();
+        let kicked_close = registry.register("kicked", kicked_tx, Arc::default(), OutboundQueue::new(8), Activity::default());
+        let other_close = registry.register("other", other_tx, Arc::default(), OutboundQueue::new(8), Activity::default());
+
+        assert!(registry.kick("kicked", "spamming"));
+        assert!(!registry.kick("missing", "spamming"));
+
+        match kicked_rx.try_recv() {
+            Ok(WsMessage::Error { code, message }) => {
+                assert_eq!(code, ErrorCode::Unauthorized);
+                assert_eq!(message, "spamming");
+            }
+            other => panic!("expected error, got {:?}", other),
+        }
+        // The close permit is stored, so the send task sees it even if it
+        // wasn't waiting yet
+        tokio::time::timeout(std::time::Duration::from_millis(100), kicked_close.notified())
+            .await
+            .expect("kicked connection should be signalled");
+
+        assert!(other_rx.try_recv().is_err());
+        assert!(
+            tokio::time::timeout(std::time::Duration::from_millis(20), other_close.notified())
+                .await
+                .is_err()
+        );
+        // A kicked connection is no longer registered
+        assert!(!registry.kick("kicked", "again"));
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_signals_every_connection() {
+        let registry = ConnectionRegistry::default();
+        let (a_tx, mut a_rx) = mpsc::unbounded_channel();
+        let (b_tx, mut b_rx) = mpsc::unbounded_channel();
+        let a_close = registry.register("a", a_tx, Arc::default(), OutboundQueue::new(8), Activity::default());
+        let b_close = registry.register("b", b_tx, Arc::default(), OutboundQueue::new(8), Activity::default());
+
+        assert_eq!(registry.shutdown("restarting", 5_000), 2);
+        for (rx, close) in [(&mut a_rx, &a_close), (&mut b_rx, &b_close)] {
+            assert!(matches!(
+                rx.try_recv(),
+                Ok(WsMessage::Shutdown { reconnect_after_ms: 5_000, .. })
+            ));
+            tokio::time::timeout(std::time::Duration::from_millis(100), close.notified())
+                .await
+                .expect("connection should be signalled");
+        }
+        // Connections unregister themselves once closed
+        assert!(!registry.is_empty());
+        registry.unregister("a");
+        registry.unregister("b");
+        assert!(registry.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_list_reports_identity_and_tracks_unregister() {
+        let registry = ConnectionRegistry::default();
+        let (a_tx, _a_rx) = mpsc::unbounded_channel();
+        let (b_tx, _b_rx) = mpsc::unbounded_channel();
+        let before = chrono::Utc::now().timestamp_millis();
+        registry.register("a", a_tx, Arc::default(), OutboundQueue::new(8), Activity::default());
+        registry.register("b", b_tx, Arc::default(), OutboundQueue::new(8), Activity::default());
+        registry.identify("a", "12D3KooWPeer");
+        registry.identify("missing", "12D3KooWPeer");
+        assert_eq!(registry.len(), 2);
+
+        let entries = registry.list();
+        assert_eq!(entries.len(), 2);
+        let a = entries.iter().find(|entry| entry.connection_id == "a").unwrap();
+        assert_eq!(a.peer_id.as_deref(), Some("12D3KooWPeer"));
+        assert!(a.connected_at >= before);
+        assert!(a.last_activity >= before);
+        let b = entries.iter().find(|entry| entry.connection_id == "b").unwrap();
+        assert_eq!(b.peer_id, None);
+
+        registry.unregister("a");
+        let entries = registry.list();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].connection_id, "b");
+        assert_eq!(registry.len(), 1);
+    }
+
+    #[test]
+    fn test_peer_connected_until_last_connection_closes() {
+        let registry = ConnectionRegistry::default();
+        for id in ["a", "b", "c"] {
+            let (tx, _rx) = mpsc::unbounded_channel();
+            registry.register(id, tx, Arc::default(), OutboundQueue::new(8), Activity::default());
+        }
+        registry.identify("a", "12D3KooWPeer");
+        registry.act_for("b", "12D3KooWPeer");
+        assert_eq!(registry.peers(), vec!["12D3KooWPeer".to_string()]);
+        // Acting for a peer isn't identifying as it
+        let b = registry.list().into_iter().find(|entry| entry.connection_id == "b").unwrap();
+        assert_eq!(b.peer_id, None);
+
+        // Only the last connection for the peer reports it as gone
+        assert_eq!(registry.unregister("a"), None);
+        assert!(registry.is_connected("12D3KooWPeer"));
+        assert_eq!(registry.unregister("b").as_deref(), Some("12D3KooWPeer"));
+        assert!(!registry.is_connected("12D3KooWPeer"));
+        assert!(registry.peers().is_empty());
+        // Nor is a connection acting for no peer, or one already gone
+        assert_eq!(registry.unregister("c"), None);
+        assert_eq!(registry.unregister("b"), None);
+    }
+}
@@ -0,0 +1,621 @@
+//! Credit requests from dashboard clients
+//!
+//! Credit lines, transfers and payment requests, and the credit queries.
+
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+use crate::AppState;
+use crate::naming;
+use crate::peer_ids::parse_peer_id;
+use super::handler_error::HandlerError;
+use super::messages::{ClientMessage, CreditDirection, CreditLineEntry, CreditTransferEntry, WsMessage};
+use super::session::Session;
+use super::websocket::publish_economics;
+use mycelial_state::{CreditLineRecord, CreditTransferRecord, PaymentRequestRecord};
+use mycelial_protocol::{
+    topics, Correlated, Signed,
+    CreditMessage, CreateCreditLine as ProtocolCreateCreditLine, CreditTransfer as ProtocolCreditTransfer,
+    PaymentRequest as ProtocolPaymentRequest,
+};
+
+/// Credit lines the local node extends or receives
+pub(super) async fn credit_line_entries(state: &AppState) -> Result<Vec<CreditLineEntry>, HandlerError> {
+    let local = state.local_peer_id.to_string();
+    match state.store.list_credit_lines_for(&local).await {
+        Ok(lines) => Ok(lines
+            .into_iter()
+            .map(|line| CreditLineEntry {
+                direction: if line.creditor == local {
+                    CreditDirection::Extended
+                } else {
+                    CreditDirection::Received
+                },
+                id: line.id,
+                creditor: line.creditor,
+                debtor: line.debtor,
+                limit: line.limit,
+                balance: line.balance,
+            })
+            .collect()),
+        Err(e) => {
+            error!("Failed to load credit lines: {}", e);
+            Err(HandlerError::internal("Failed to load credit lines"))
+        }
+    }
+}
+
+/// Check that a transfer from the local peer to `to` may settle a payment request
+async fn validate_request_ref(state: &AppState, request_ref: &str, to: &str) -> Result<(), String> {
+    let request = match state.store.get_payment_request(request_ref).await {
+        Ok(Some(request)) => request,
+        Ok(None) => return Err(format!("Unknown payment request {}", request_ref)),
+        Err(e) => return Err(format!("Failed to look up payment request: {}", e)),
+    };
+    if request.payer != state.local_peer_id.to_string() || request.requester != to {
+        return Err(format!(
+            "Payment request {} is not a request from {} to this node",
+            request_ref, to
+        ));
+    }
+    if request.settled_by.is_some() {
+        return Err(format!("Payment request {} is already settled", request_ref));
+    }
+    Ok(())
+}
+
+/// Carry out a credit request routed here by the WebSocket handler
+pub(super) async fn handle_request(msg: ClientMessage, state: &AppState, session: &mut Session) -> Result<(), HandlerError> {
+    match msg {
+        ClientMessage::CreateCreditLine { debtor, limit, correlation_id } => {
+            let debtor = match parse_peer_id("debtor", &debtor) {
+                Ok(peer_id) => peer_id.to_string(),
+                Err(message) => {
+                    return Err(HandlerError::invalid(message));
+                }
+            };
+            info!("CreateCreditLine: debtor='{}', limit={}", debtor, limit);
+
+            let timestamp = chrono::Utc::now().timestamp_millis();
+
+            let line = ProtocolCreateCreditLine::new(
+                state.local_peer_id.to_string(),
+                debtor.clone(),
+                limit,
+            )
+            .with_correlation_id(correlation_id.clone())
+            .with_signature(session.take_action_signature());
+            let line_id = line.id.to_string();
+            let credit_msg = CreditMessage::CreateLine(line);
+
+            match serde_json::to_vec(&credit_msg) {
+                Ok(data) => {
+                    if publish_economics(state, session, topics::CREDIT, data, "credit line").await? {
+                        let record = CreditLineRecord {
+                            id: line_id.clone(),
+                            creditor: state.local_peer_id.to_string(),
+                            debtor: debtor.clone(),
+                            limit,
+                            balance: 0.0,
+                            created_at: timestamp,
+                        };
+                        if let Err(e) = state.store.insert_credit_line(&record).await {
+                            warn!("Failed to store credit line: {}", e);
+                        }
+                        let echo_msg = WsMessage::CreditLine {
+                            id: line_id,
+                            creditor: state.local_peer_id.to_string(),
+                            debtor,
+                            limit,
+                            balance: 0.0,
+                            correlation_id,
+                            timestamp,
+                        };
+                        let _ = state.event_tx.send(echo_msg);
+                    }
+                }
+                Err(e) => {
+                    error!("Failed to serialize credit line: {}", e);
+                    return Err(HandlerError::internal("Failed to serialize credit line"));
+                }
+            }
+        }
+
+        ClientMessage::GetCreditLines => {
+            let lines = credit_line_entries(state).await?;
+            session.reply(WsMessage::CreditLinesList { lines });
+        }
+
+        ClientMessage::GetCreditHistory { line_id } => {
+            let Ok(line_id) = Uuid::parse_str(line_id.trim()) else {
+                return Err(HandlerError::invalid("line_id must be a credit line ID"));
+            };
+            let line_id = line_id.to_string();
+            let line = match state.store.get_credit_line(&line_id).await {
+                Ok(Some(line)) => line,
+                Ok(None) => return Err(HandlerError::invalid(format!("Unknown credit line {}", line_id))),
+                Err(e) => {
+                    error!("Failed to load credit line {}: {}", line_id, e);
+                    return Err(HandlerError::internal("Failed to load credit line"));
+                }
+            };
+            let transfers = match state.store.list_credit_transfers(&line_id).await {
+                Ok(transfers) => transfers,
+                Err(e) => {
+                    error!("Failed to load transfers on credit line {}: {}", line_id, e);
+                    return Err(HandlerError::internal("Failed to load credit history"));
+                }
+            };
+            let transfers = transfers
+                .into_iter()
+                .map(|entry| CreditTransferEntry {
+                    id: entry.transfer.id,
+                    from: entry.transfer.from,
+                    to: entry.transfer.to,
+                    amount: entry.transfer.amount,
+                    memo: entry.transfer.memo,
+                    timestamp: entry.transfer.created_at,
+                    running_balance: entry.balance_after,
+                })
+                .collect();
+            session.reply(WsMessage::CreditHistory {
+                line_id,
+                transfers,
+                current_balance: line.balance,
+            });
+        }
+
+        ClientMessage::TransferCredit { to, amount, memo, request_ref, correlation_id } => {
+            let to = match parse_peer_id("to", &to) {
+                Ok(peer_id) => peer_id.to_string(),
+                Err(message) => {
+                    return Err(HandlerError::invalid(message));
+                }
+            };
+            info!("TransferCredit: to='{}', amount={}", to, amount);
+
+            if !amount.is_finite() || amount <= 0.0 {
+                return Err(HandlerError::invalid("Amount must be positive"));
+            }
+
+            if let Some(ref request_ref) = request_ref {
+                if let Err(message) = validate_request_ref(state, request_ref, &to).await {
+                    return Err(HandlerError::invalid(message));
+                }
+            }
+
+            let local = state.local_peer_id.to_string();
+            // Paying a peer draws on the credit line they extended to this node
+            let line = match state.store.find_credit_line(&to, &local).await {
+                Ok(Some(line)) => line,
+                Ok(None) => return Err(HandlerError::invalid(format!("No credit line from {}", to))),
+                Err(e) => {
+                    error!("Failed to look up credit line to {}: {}", to, e);
+                    return Err(HandlerError::internal("Failed to look up credit line"));
+                }
+            };
+            let Ok(line_id) = Uuid::parse_str(&line.id) else {
+                error!("Stored credit line {} has an invalid ID", line.id);
+                return Err(HandlerError::internal("Invalid credit line"));
+            };
+            let available = (line.limit - line.balance).max(0.0);
+            let exceeds = || {
+                HandlerError::invalid(format!("Transfer of {} exceeds available credit of {}", amount, available))
+            };
+            if amount > available {
+                return Err(exceeds());
+            }
+
+            let timestamp = chrono::Utc::now().timestamp_millis();
+
+            let mut transfer = ProtocolCreditTransfer::new(
+                line_id,
+                state.local_peer_id.to_string(),
+                to.clone(),
+                amount,
+            )
+            .with_correlation_id(correlation_id.clone())
+            .with_signature(session.take_action_signature());
+            if let Some(ref m) = memo {
+                transfer = transfer.with_memo(m);
+            }
+            if let Some(ref r) = request_ref {
+                transfer = transfer.with_request_ref(r);
+            }
+            let transfer_id = transfer.id.to_string();
+            let transfer_msg = CreditMessage::Transfer(transfer);
+
+            match serde_json::to_vec(&transfer_msg) {
+                Ok(data) => {
+                    // Drawn before publishing, so a concurrent transfer can't
+                    // take the same credit; returned if the publish fails
+                    let record = CreditTransferRecord {
+                        id: transfer_id.clone(),
+                        line_id: line.id.clone(),
+                        from: local.clone(),
+                        to: to.clone(),
+                        amount,
+                        memo: memo.clone(),
+                        created_at: timestamp,
+                    };
+                    match state.store.record_credit_transfer(&record).await {
+                        Ok(true) => {}
+                        Ok(false) => return Err(exceeds()),
+                        Err(e) => {
+                            error!("Failed to draw on credit line {}: {}", line.id, e);
+                            return Err(HandlerError::internal("Failed to draw on credit line"));
+                        }
+                    }
+                    let published = publish_economics(state, session, topics::CREDIT, data, "credit transfer").await;
+                    if !matches!(published, Ok(true)) {
+                        if let Err(e) = state.store.revert_credit_transfer(&transfer_id).await {
+                            error!("Failed to return unsent transfer {} to credit line {}: {}", transfer_id, line.id, e);
+                        }
+                        return published.map(|_| ());
+                    }
+                    if let Some(ref r) = request_ref {
+                        if let Err(e) = state.store.settle_payment_request(r, &record).await {
+                            warn!("Failed to settle payment request {}: {}", r, e);
+                        }
+                    }
+                    let echo_msg = WsMessage::CreditTransfer {
+                        id: transfer_id,
+                        from: local,
+                        from_name: state.node_name(),
+                        to_name: naming::resolve_display_name(state, &to).await,
+                        to,
+                        amount,
+                        memo,
+                        request_ref,
+                        correlation_id,
+                        timestamp,
+                    };
+                    let _ = state.event_tx.send(echo_msg);
+                }
+                Err(e) => {
+                    error!("Failed to serialize credit transfer: {}", e);
+                    return Err(HandlerError::internal("Failed to serialize credit transfer"));
+                }
+            }
+        }
+
+        ClientMessage::RequestPayment { from, amount, memo, correlation_id } => {
+            info!("RequestPayment: from='{}', amount={}", from, amount);
+
+            if !amount.is_finite() || amount <= 0.0 {
+                return Err(HandlerError::invalid("Amount must be positive"));
+            }
+
+            let mut request = ProtocolPaymentRequest::new(
+                state.local_peer_id.to_string(),
+                from.clone(),
+                amount,
+            )
+            .with_correlation_id(correlation_id.clone())
+            .with_signature(session.take_action_signature());
+            if let Some(ref m) = memo {
+                request = request.with_memo(m);
+            }
+            let record = PaymentRequestRecord {
+                id: request.id.to_string(),
+                requester: request.requester.clone(),
+                payer: from,
+                amount,
+                memo,
+                created_at: request.timestamp.timestamp_millis(),
+                settled_by: None,
+            };
+            let request_msg = CreditMessage::PaymentRequest(request);
+
+            match serde_json::to_vec(&request_msg) {
+                Ok(data) => {
+                    if !publish_economics(state, session, topics::CREDIT, data, "payment request").await? {
+                        return Ok(());
+                    }
+                    if let Err(e) = state.store.insert_payment_request(&record).await {
+                        warn!("Failed to store payment request: {}", e);
+                    }
+                    let _ = state.event_tx.send(WsMessage::PaymentRequest {
+                        id: record.id,
+                        requester: record.requester,
+                        payer: record.payer,
+                        amount: record.amount,
+                        memo: record.memo,
+                        correlation_id,
+                        timestamp: record.created_at,
+                    });
+                }
+                Err(e) => {
+                    error!("Failed to serialize payment request: {}", e);
+                    return Err(HandlerError::internal("Failed to serialize payment request"));
+                }
+            }
+        }
+
+        other => unreachable!("not a credit request: {:?}", other),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::messages::ErrorCode;
+    use crate::server::websocket::handle_client_message;
+    use crate::test_support::{remote_peer, store_credit_line_from, test_session, test_state};
+    use mycelial_network::NetworkCommand;
+
+    #[tokio::test]
+    async fn test_transfer_settles_referenced_payment_request() {
+        let (state, _commands) = test_state().await;
+        let mut events = state.event_tx.subscribe();
+        let (mut session, mut replies) = test_session(8);
+        let bob = remote_peer();
+        store_credit_line_from(&state, &bob, 100.0).await;
+
+        state
+            .store
+            .insert_payment_request(&PaymentRequestRecord {
+                id: "req-1".to_string(),
+                requester: bob.clone(),
+                payer: state.local_peer_id.to_string(),
+                amount: 10.0,
+                memo: None,
+                created_at: 1_000,
+                settled_by: None,
+            })
+            .await
+            .unwrap();
+
+        let transfer = |request_ref: &str| ClientMessage::TransferCredit {
+            to: bob.clone(),
+            amount: 10.0,
+            memo: None,
+            request_ref: Some(request_ref.to_string()),
+            correlation_id: None,
+        };
+
+        handle_client_message(transfer("req-1"), &state, &mut session).await;
+        let transfer_id = match events.try_recv().unwrap() {
+            WsMessage::CreditTransfer { id, request_ref, .. } => {
+                assert_eq!(request_ref.as_deref(), Some("req-1"));
+                id
+            }
+            other => panic!("unexpected message: {:?}", other),
+        };
+        let request = state.store.get_payment_request("req-1").await.unwrap().unwrap();
+        assert_eq!(request.settled_by, Some(transfer_id));
+
+        // Settled and unknown references are both rejected
+        for request_ref in ["req-1", "req-unknown"] {
+            handle_client_message(transfer(request_ref), &state, &mut session).await;
+            match replies.try_recv().unwrap() {
+                WsMessage::Error { code, .. } => assert_eq!(code, ErrorCode::InvalidRequest),
+                other => panic!("unexpected message: {:?}", other),
+            }
+        }
+        assert!(events.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_transfer_rejected_while_isolated() {
+        let (state, mut commands) = test_state().await;
+        state.connected_peers.write().clear();
+        let mut events = state.event_tx.subscribe();
+        let (mut session, mut replies) = test_session(8);
+        let bob = remote_peer();
+        let line_id = store_credit_line_from(&state, &bob, 100.0).await;
+
+        let msg = ClientMessage::TransferCredit {
+            to: bob,
+            amount: 5.0,
+            memo: None,
+            request_ref: None,
+            correlation_id: None,
+        };
+        handle_client_message(msg, &state, &mut session).await;
+
+        match replies.try_recv().unwrap() {
+            WsMessage::Error { code, message } => {
+                assert_eq!(code, ErrorCode::Internal);
+                assert_eq!(message, "network isolated");
+            }
+            other => panic!("unexpected message: {:?}", other),
+        }
+        // Nothing was published or echoed as sent, and the credit was returned
+        assert!(commands.try_recv().is_err());
+        assert!(events.try_recv().is_err());
+        let line = state.store.get_credit_line(&line_id).await.unwrap().unwrap();
+        assert_eq!(line.balance, 0.0);
+        assert!(state.store.list_credit_transfers(&line_id).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_get_credit_lines_in_both_directions() {
+        let (state, _commands) = test_state().await;
+        let (mut session, mut replies) = test_session(8);
+        let local = state.local_peer_id.to_string();
+        let lines = [
+            ("l1", local.as_str(), "bob", 1_000),
+            ("l2", "alice", local.as_str(), 2_000),
+            ("l3", local.as_str(), "carol", 3_000),
+            ("l4", "bob", "carol", 4_000),
+        ];
+        for (id, creditor, debtor, created_at) in lines {
+            let record = CreditLineRecord {
+                id: id.to_string(),
+                creditor: creditor.to_string(),
+                debtor: debtor.to_string(),
+                limit: 50.0,
+                balance: 10.0,
+                created_at,
+            };
+            state.store.insert_credit_line(&record).await.unwrap();
+        }
+
+        handle_client_message(ClientMessage::GetCreditLines, &state, &mut session).await;
+        match replies.try_recv() {
+            Ok(WsMessage::CreditLinesList { lines }) => {
+                let summary: Vec<_> = lines.iter().map(|l| (l.id.as_str(), l.direction)).collect();
+                assert_eq!(
+                    summary,
+                    vec![
+                        ("l3", CreditDirection::Extended),
+                        ("l2", CreditDirection::Received),
+                        ("l1", CreditDirection::Extended),
+                    ]
+                );
+                assert_eq!(lines[1].creditor, "alice");
+                assert!((lines[1].limit - 50.0).abs() < 1e-9);
+                assert!((lines[1].balance - 10.0).abs() < 1e-9);
+            }
+            other => panic!("expected credit lines, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_transfer_limited_by_credit_line() {
+        let (state, mut commands) = test_state().await;
+        let (mut session, mut replies) = test_session(8);
+        let mut events = state.event_tx.subscribe();
+        let local = state.local_peer_id.to_string();
+        let bob = remote_peer();
+        let line_id = Uuid::new_v4().to_string();
+        let record = CreditLineRecord {
+            id: line_id.clone(),
+            creditor: bob.clone(),
+            debtor: local.clone(),
+            limit: 100.0,
+            balance: 0.0,
+            created_at: 1,
+        };
+        state.store.insert_credit_line(&record).await.unwrap();
+        let transfer = |amount: f64| ClientMessage::TransferCredit {
+            to: bob.clone(),
+            amount,
+            memo: None,
+            request_ref: None,
+            correlation_id: None,
+        };
+        async fn balance(state: &AppState, creditor: &str, debtor: &str) -> f64 {
+            state.store.find_credit_line(creditor, debtor).await.unwrap().unwrap().balance
+        }
+
+        // Within the limit: published against the line and drawn on it
+        handle_client_message(transfer(60.0), &state, &mut session).await;
+        assert!(matches!(events.try_recv(), Ok(WsMessage::CreditTransfer { .. })));
+        match commands.try_recv() {
+            Ok(NetworkCommand::Publish { data, .. }) => {
+                let json: serde_json::Value = serde_json::from_slice(&data).unwrap();
+                assert_eq!(json["line_id"], line_id);
+            }
+            other => panic!("expected publish, got {:?}", other),
+        }
+        assert!((balance(&state, &bob, &local).await - 60.0).abs() < 1e-9);
+
+        // Over the remaining 40: rejected with the available amount
+        handle_client_message(transfer(40.5), &state, &mut session).await;
+        match replies.try_recv() {
+            Ok(WsMessage::Error { code, message }) => {
+                assert_eq!(code, ErrorCode::InvalidRequest);
+                assert!(message.contains("available credit of 40"), "{}", message);
+            }
+            other => panic!("expected error, got {:?}", other),
+        }
+        assert!(commands.try_recv().is_err());
+        assert!((balance(&state, &bob, &local).await - 60.0).abs() < 1e-9);
+
+        // Exactly the remaining amount is allowed
+        handle_client_message(transfer(40.0), &state, &mut session).await;
+        assert!(matches!(events.try_recv(), Ok(WsMessage::CreditTransfer { .. })));
+        assert!((balance(&state, &bob, &local).await - 100.0).abs() < 1e-9);
+        assert!(commands.try_recv().is_ok());
+
+        // Amounts that would give credit back are rejected
+        for amount in [0.0, -10.0, f64::NAN] {
+            handle_client_message(transfer(amount), &state, &mut session).await;
+            assert!(matches!(
+                replies.try_recv(),
+                Ok(WsMessage::Error { code: ErrorCode::InvalidRequest, .. })
+            ));
+        }
+
+        // Without a line from the recipient there is nothing to draw on
+        let carol = remote_peer();
+        let msg = ClientMessage::TransferCredit {
+            to: carol.clone(),
+            amount: 1.0,
+            memo: None,
+            request_ref: None,
+            correlation_id: None,
+        };
+        handle_client_message(msg, &state, &mut session).await;
+        match replies.try_recv() {
+            Ok(WsMessage::Error { code, message }) => {
+                assert_eq!(code, ErrorCode::InvalidRequest);
+                assert_eq!(message, format!("No credit line from {}", carol));
+            }
+            other => panic!("expected error, got {:?}", other),
+        }
+        assert!(commands.try_recv().is_err());
+        assert!(events.try_recv().is_err());
+        assert!((balance(&state, &bob, &local).await - 100.0).abs() < 1e-9);
+    }
+
+    #[tokio::test]
+    async fn test_credit_history_lists_transfers_with_running_balance() {
+        let (state, _commands) = test_state().await;
+        let (mut session, mut replies) = test_session(8);
+        let local = state.local_peer_id.to_string();
+        let bob = remote_peer();
+        let line_id = Uuid::new_v4().to_string();
+        // A line with credit drawn before its transfers were recorded
+        let record = CreditLineRecord {
+            id: line_id.clone(),
+            creditor: bob.clone(),
+            debtor: local.clone(),
+            limit: 100.0,
+            balance: 10.0,
+            created_at: 1_000,
+        };
+        state.store.insert_credit_line(&record).await.unwrap();
+        for (id, amount, memo, created_at) in [("t1", 25.0, "rent", 2_000), ("t2", 15.5, "tools", 3_000)] {
+            let transfer = CreditTransferRecord {
+                id: id.to_string(),
+                line_id: line_id.clone(),
+                from: local.clone(),
+                to: bob.clone(),
+                amount,
+                memo: Some(memo.to_string()),
+                created_at,
+            };
+            assert!(state.store.record_credit_transfer(&transfer).await.unwrap());
+        }
+
+        handle_client_message(ClientMessage::GetCreditHistory { line_id: line_id.clone() }, &state, &mut session).await;
+        match replies.try_recv() {
+            Ok(WsMessage::CreditHistory { line_id: id, transfers, current_balance }) => {
+                assert_eq!(id, line_id);
+                let summary: Vec<_> = transfers
+                    .iter()
+                    .map(|t| (t.memo.as_deref().unwrap(), t.amount, t.running_balance))
+                    .collect();
+                // Running balances include what was drawn before
+                assert_eq!(summary, vec![("rent", 25.0, 35.0), ("tools", 15.5, 50.5)]);
+                assert!(transfers.iter().all(|t| t.from == local && t.to == bob));
+                assert_eq!(transfers[0].timestamp, 2_000);
+                assert!((current_balance - 50.5).abs() < 1e-9);
+            }
+            other => panic!("expected credit history, got {:?}", other),
+        }
+
+        // Unknown and malformed line IDs are rejected
+        for line_id in [Uuid::new_v4().to_string(), "not-a-line".to_string()] {
+            handle_client_message(ClientMessage::GetCreditHistory { line_id }, &state, &mut session).await;
+            assert!(matches!(
+                replies.try_recv(),
+                Ok(WsMessage::Error { code: ErrorCode::InvalidRequest, .. })
+            ));
+        }
+    }
+}
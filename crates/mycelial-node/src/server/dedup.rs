@@ -0,0 +1,106 @@
+//! Per-connection suppression of duplicate events
+//!
+//! The same logical event can be broadcast more than once: a locally sent
+//! message is echoed immediately and may come back through the network, and
+//! gossip can re-deliver what a peer already relayed. Each connection's send
+//! task remembers the IDs it recently delivered and skips repeats.
+
+use lru::LruCache;
+use std::num::NonZeroUsize;
+use std::time::{Duration, Instant};
+
+use super::messages::WsMessage;
+
+/// Most IDs remembered per connection
+const CAPACITY: usize = 1024;
+
+/// ID of an event that reports a single occurrence, such as one chat message
+///
+/// Events that report the latest state of something (proposals, pools) are
+/// expected to repeat their ID and are never deduplicated.
+pub fn occurrence_id(event: &WsMessage) -> Option<&str> {
+    match event {
+        WsMessage::ChatMessage { id, .. }
+        | WsMessage::VouchRequest { id, .. }
+        | WsMessage::VouchAck { id, .. }
+        | WsMessage::CreditTransfer { id, .. }
+        | WsMessage::PaymentRequest { id, .. }
+        | WsMessage::VoteCast { id, .. }
+        | WsMessage::ResourceContribution { id, .. } => Some(id),
+        _ => None,
+    }
+}
+
+/// IDs recently delivered to one connection
+#[derive(Debug)]
+pub struct DeliveredIds {
+    window: Duration,
+    delivered: LruCache<String, Instant>,
+}
+
+impl DeliveredIds {
+    /// Remember deliveries for `window`
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            delivered: LruCache::new(NonZeroUsize::new(CAPACITY).unwrap_or(NonZeroUsize::MIN)),
+        }
+    }
+
+    /// Whether `event` should be delivered at `now`, recording it if so
+    pub fn first_delivery(&mut self, event: &WsMessage, now: Instant) -> bool {
+        let Some(id) = occurrence_id(event) else {
+            return true;
+        };
+        if let Some(delivered_at) = self.delivered.get(id) {
+            if now.saturating_duration_since(*delivered_at) < self.window {
+                return false;
+            }
+        }
+        self.delivered.put(id.to_string(), now);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chat(id: &str) -> WsMessage {
+        WsMessage::ChatMessage {
+            id: id.to_string(),
+            from: "alice".to_string(),
+            from_name: "alice".to_string(),
+            to: None,
+            room_id: None,
+            content: "hi".to_string(),
+            timestamp: 0,
+        }
+    }
+
+    #[test]
+    fn test_same_id_forwarded_once_per_window() {
+        let mut delivered = DeliveredIds::new(Duration::from_secs(60));
+        let start = Instant::now();
+
+        assert!(delivered.first_delivery(&chat("m1"), start));
+        assert!(!delivered.first_delivery(&chat("m1"), start + Duration::from_secs(1)));
+        assert!(delivered.first_delivery(&chat("m2"), start));
+        // Once the window has passed the ID is forgotten
+        assert!(delivered.first_delivery(&chat("m1"), start + Duration::from_secs(60)));
+
+        // Events without an occurrence ID are always delivered
+        let stats = WsMessage::Stats {
+            peer_count: 1,
+            message_count: 0,
+            topic_counts: Default::default(),
+            uptime_seconds: 0,
+            broadcast_queue_depth: 0,
+            broadcast_high_water: 0,
+            broadcast_capacity: 256,
+            connection_count: 0,
+        };
+        assert!(delivered.first_delivery(&stats, start));
+        assert!(delivered.first_delivery(&stats, start));
+    }
+}
@@ -0,0 +1,258 @@
+//! Wire encodings for WebSocket frames
+//!
+//! Messages are JSON text frames by default. A client can connect with
+//! `?encoding=msgpack` to exchange MessagePack binary frames instead, which
+//! are considerably smaller for high-frequency payloads like peer lists and
+//! pool updates. MessagePack frames carry the same fields as the JSON form,
+//! encoded as maps, so messages keep their `type` tag.
+//!
+//! Independently, a client that sees `capability:compress_gzip` in `Hello`
+//! can enable that capability. Large frames are then gzipped and sent as
+//! binary frames, recognisable by the gzip magic bytes (a MessagePack message
+//! always starts with a map marker instead). Small frames, and frames that
+//! wouldn't shrink, are sent as usual.
+
+use axum::extract::ws::Message;
+use flate2::write::GzEncoder;
+use serde::Deserialize;
+use std::io::Write;
+use tracing::debug;
+
+use super::snapshot::{snapshot_sections, SnapshotSections};
+
+/// How messages are encoded on a connection, chosen when it opens
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WireEncoding {
+    /// JSON text frames
+    #[default]
+    Json,
+    /// MessagePack binary frames
+    #[serde(rename = "msgpack")]
+    MessagePack,
+}
+
+/// Compression applied to large frames on a connection
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Compression {
+    /// Frames are sent as encoded
+    #[default]
+    None,
+    /// Large frames are gzipped into binary frames
+    Gzip,
+}
+
+/// Smallest frame, in bytes, worth compressing
+pub const COMPRESSION_THRESHOLD: usize = 1024;
+
+/// Leading bytes of a gzip stream
+#[cfg(test)]
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// Query parameters accepted on the WebSocket upgrade
+#[derive(Debug, Default, Deserialize)]
+pub struct ConnectParams {
+    /// Frame encoding for the connection
+    #[serde(default)]
+    pub encoding: WireEncoding,
+    /// State sent when the connection opens
+    #[serde(default, deserialize_with = "snapshot_sections")]
+    pub snapshot: SnapshotSections,
+}
+
+/// A message encoded for the socket
+#[derive(Debug, Clone, PartialEq)]
+pub enum Frame {
+    Text(String),
+    Binary(Vec<u8>),
+}
+
+impl Frame {
+    /// Bytes written to the socket for this frame
+    pub fn len(&self) -> usize {
+        match self {
+            Frame::Text(text) => text.len(),
+            Frame::Binary(data) => data.len(),
+        }
+    }
+
+    /// Whether the frame has no payload
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl From<Frame> for Message {
+    fn from(frame: Frame) -> Self {
+        match frame {
+            Frame::Text(text) => Message::Text(text),
+            Frame::Binary(data) => Message::Binary(data),
+        }
+    }
+}
+
+impl WireEncoding {
+    /// Encode a message already serialized as JSON
+    pub fn frame(self, json: String) -> Result<Frame, String> {
+        match self {
+            WireEncoding::Json => Ok(Frame::Text(json)),
+            WireEncoding::MessagePack => {
+                let value: serde_json::Value = serde_json::from_str(&json).map_err(|e| e.to_string())?;
+                rmp_serde::to_vec_named(&value)
+                    .map(Frame::Binary)
+                    .map_err(|e| e.to_string())
+            }
+        }
+    }
+}
+
+impl Compression {
+    /// Compress a frame if it is large enough and compression helps
+    pub fn apply(self, frame: Frame) -> Frame {
+        if self == Compression::None || frame.len() < COMPRESSION_THRESHOLD {
+            return frame;
+        }
+        let bytes = match &frame {
+            Frame::Text(text) => text.as_bytes(),
+            Frame::Binary(data) => data.as_slice(),
+        };
+        let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+        let compressed = match encoder.write_all(bytes).and_then(|_| encoder.finish()) {
+            Ok(compressed) => compressed,
+            Err(e) => {
+                debug!("Failed to compress frame, sending uncompressed: {}", e);
+                return frame;
+            }
+        };
+        if compressed.len() >= bytes.len() {
+            return frame;
+        }
+        debug!(
+            "Compressed {} byte frame to {} bytes (ratio {:.2})",
+            bytes.len(),
+            compressed.len(),
+            compressed.len() as f64 / bytes.len() as f64
+        );
+        Frame::Binary(compressed)
+    }
+}
+
+/// Whether a binary frame is gzip-compressed
+#[cfg(test)]
+pub fn is_gzip(data: &[u8]) -> bool {
+    data.starts_with(&GZIP_MAGIC)
+}
+
+/// Decompress a gzip frame
+#[cfg(test)]
+pub fn gunzip(data: &[u8]) -> Result<Vec<u8>, String> {
+    use std::io::Read;
+
+    let mut decoded = Vec::new();
+    flate2::read::GzDecoder::new(data)
+        .read_to_end(&mut decoded)
+        .map_err(|e| e.to_string())?;
+    Ok(decoded)
+}
+
+/// Decode a MessagePack client frame into the equivalent JSON text
+pub fn decode_binary(data: &[u8]) -> Result<String, String> {
+    let value: serde_json::Value = rmp_serde::from_slice(data).map_err(|e| e.to_string())?;
+    Ok(value.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::messages::{ClientMessage, PeerListEntry, WsMessage};
+
+    fn peers_list() -> WsMessage {
+        WsMessage::PeersList {
+            peers: vec![PeerListEntry {
+                id: "12D3KooWPeer".to_string(),
+                name: Some("alice".to_string()),
+                reputation: 0.75,
+                addresses: vec!["/ip4/127.0.0.1/tcp/9000".to_string()],
+            }],
+        }
+    }
+
+    #[test]
+    fn test_peers_list_round_trips_in_both_encodings() {
+        let json = serde_json::to_string(&peers_list()).unwrap();
+        let expected = serde_json::to_value(peers_list()).unwrap();
+
+        match WireEncoding::Json.frame(json.clone()).unwrap() {
+            Frame::Text(text) => assert_eq!(serde_json::from_str::<serde_json::Value>(&text).unwrap(), expected),
+            other => panic!("expected text frame, got {:?}", other),
+        }
+
+        match WireEncoding::MessagePack.frame(json.clone()).unwrap() {
+            Frame::Binary(data) => {
+                assert!(data.len() < json.len());
+                let decoded: serde_json::Value = rmp_serde::from_slice(&data).unwrap();
+                assert_eq!(decoded, expected);
+                assert_eq!(decoded["type"], "peers_list");
+            }
+            other => panic!("expected binary frame, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_client_messages_decode_from_msgpack() {
+        let value = serde_json::json!({ "type": "get_peers" });
+        let data = rmp_serde::to_vec_named(&value).unwrap();
+
+        let text = decode_binary(&data).unwrap();
+        assert!(matches!(serde_json::from_str::<ClientMessage>(&text), Ok(ClientMessage::GetPeers)));
+        assert!(decode_binary(&[0xc1]).is_err());
+    }
+
+    #[test]
+    fn test_encoding_query_defaults_to_json() {
+        let params: ConnectParams = serde_json::from_str("{}").unwrap();
+        assert_eq!(params.encoding, WireEncoding::Json);
+        let params: ConnectParams = serde_json::from_str(r#"{"encoding":"msgpack"}"#).unwrap();
+        assert_eq!(params.encoding, WireEncoding::MessagePack);
+    }
+
+    #[test]
+    fn test_large_frames_compressed_and_decodable() {
+        let peers: Vec<PeerListEntry> = (0..40)
+            .map(|i| PeerListEntry {
+                id: format!("12D3KooWPeer{}", i),
+                name: Some(format!("peer-{}", i)),
+                reputation: 0.5,
+                addresses: vec![format!("/ip4/10.0.0.{}/tcp/9000", i)],
+            })
+            .collect();
+        let json = serde_json::to_string(&WsMessage::PeersList { peers }).unwrap();
+        assert!(json.len() >= COMPRESSION_THRESHOLD);
+
+        // Without compression the frame is unchanged
+        let plain = Compression::None.apply(Frame::Text(json.clone()));
+        assert_eq!(plain, Frame::Text(json.clone()));
+
+        match Compression::Gzip.apply(Frame::Text(json.clone())) {
+            Frame::Binary(data) => {
+                assert!(is_gzip(&data));
+                assert!(data.len() < json.len());
+                assert_eq!(String::from_utf8(gunzip(&data).unwrap()).unwrap(), json);
+            }
+            other => panic!("expected compressed frame, got {:?}", other),
+        }
+
+        // MessagePack frames compress too, and are distinguishable when not
+        let packed = WireEncoding::MessagePack.frame(json.clone()).unwrap();
+        let Frame::Binary(raw) = packed.clone() else { panic!("expected binary frame") };
+        assert!(!is_gzip(&raw));
+        match Compression::Gzip.apply(packed) {
+            Frame::Binary(data) => assert_eq!(gunzip(&data).unwrap(), raw),
+            other => panic!("expected compressed frame, got {:?}", other),
+        }
+
+        // Small frames are left alone
+        let small = Frame::Text(serde_json::to_string(&peers_list()).unwrap());
+        assert_eq!(Compression::Gzip.apply(small.clone()), small);
+    }
+}
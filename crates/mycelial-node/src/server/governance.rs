@@ -0,0 +1,1503 @@
+//! Governance requests from dashboard clients
+//!
+//! Proposals and votes, along with the tallies and statuses reported for
+//! them, which the network event loop and anti-entropy also broadcast.
+
+use std::collections::HashMap;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+use crate::AppState;
+use crate::execution;
+use crate::governance_params;
+use crate::naming::{self, NameCache};
+use crate::proposal_types;
+use crate::vouch_reputation;
+use super::handler_error::HandlerError;
+use super::messages::{ClientMessage, ProposalEntry, WarningKind, WsMessage};
+use super::session::Session;
+use super::websocket::publish_economics;
+use mycelial_state::{CappedTally, ProposalRecord, VoteRecord, VoteTally};
+use mycelial_state::governance::required_voters;
+use mycelial_protocol::{
+    topics, Correlated, Signed,
+    GovernanceMessage, CreateProposal as ProtocolCreateProposal, CastVote as ProtocolCastVote, Vote,
+    ProposalAmendment as ProtocolProposalAmendment, ProposalCancellation,
+};
+
+/// Window used when measuring recent governance participation (30 days)
+const PARTICIPATION_WINDOW_MS: i64 = 30 * 24 * 60 * 60 * 1000;
+
+/// Default and allowed range of a proposal's voting period, in seconds
+const DEFAULT_PROPOSAL_DEADLINE_SECS: u64 = 24 * 60 * 60;
+const MIN_PROPOSAL_DEADLINE_SECS: u64 = 60;
+const MAX_PROPOSAL_DEADLINE_SECS: u64 = 30 * 24 * 60 * 60;
+
+/// Map a protocol vote to the label used by dashboard clients and the store
+pub(crate) fn vote_label(vote: &Vote) -> &'static str {
+    match vote {
+        Vote::For => "yes",
+        Vote::Against => "no",
+        Vote::Abstain => "abstain",
+    }
+}
+
+/// Protocol vote for a client's vote label, if it is one of yes, no or abstain
+pub(crate) fn parse_vote(label: &str) -> Option<Vote> {
+    match label {
+        "yes" => Some(Vote::For),
+        "no" => Some(Vote::Against),
+        "abstain" => Some(Vote::Abstain),
+        _ => None,
+    }
+}
+
+/// Lightest vote weight, given to the least trusted voters
+pub(crate) const MIN_VOTE_WEIGHT: f64 = 0.5;
+
+/// Heaviest vote weight, given to fully trusted voters
+pub(crate) const MAX_VOTE_WEIGHT: f64 = 2.0;
+
+/// Vote weight for a reputation score
+///
+/// A neutral score of 0.5 weighs 1.0; the result is clamped to
+/// [`MIN_VOTE_WEIGHT`]..=[`MAX_VOTE_WEIGHT`] so no voter is silenced or dominant.
+pub(crate) fn vote_weight(reputation: f64) -> f64 {
+    if reputation.is_nan() {
+        return MIN_VOTE_WEIGHT;
+    }
+    (reputation * 2.0).clamp(MIN_VOTE_WEIGHT, MAX_VOTE_WEIGHT)
+}
+
+/// Vote weight of `voter` from its stored reputation
+///
+/// Voters without a stored record count as neutral.
+pub(crate) async fn voter_weight(state: &AppState, voter: &str) -> f64 {
+    let reputation = vouch_reputation::reputation_of(state, voter).await.unwrap_or_default();
+    vote_weight(reputation.score)
+}
+
+/// Number of peers eligible to vote: every known peer plus the local node
+pub(crate) async fn eligible_voters(state: &AppState) -> usize {
+    state.store.count_peers().await.map(|n| n as usize + 1).unwrap_or(1)
+}
+
+/// Tally a proposal, applying the sybil weight cap when configured
+///
+/// Operators are warned (once per proposal) when a large bloc of
+/// low-reputation voters cast the same vote.
+pub(crate) async fn tally_proposal(state: &AppState, proposal_id: &str) -> mycelial_state::Result<VoteTally> {
+    let Some(floor) = state.config.sybil_reputation_floor else {
+        return state.store.tally_votes(proposal_id).await;
+    };
+    let capped = state
+        .store
+        .tally_votes_capped(proposal_id, floor, state.governance_params.sybil_weight_cap())
+        .await?;
+    warn_of_sybil_bloc(state, proposal_id, &capped, floor);
+    Ok(capped.tally)
+}
+
+/// [`tally_proposal`] for every proposal at once, with one grouped query
+///
+/// Proposals nobody has voted on are absent from the result.
+async fn tally_proposals(state: &AppState) -> mycelial_state::Result<HashMap<String, VoteTally>> {
+    let Some(floor) = state.config.sybil_reputation_floor else {
+        return state.store.tally_all_votes().await;
+    };
+    let capped = state
+        .store
+        .tally_all_votes_capped(floor, state.governance_params.sybil_weight_cap())
+        .await?;
+    Ok(capped
+        .into_iter()
+        .map(|(proposal_id, capped)| {
+            warn_of_sybil_bloc(state, &proposal_id, &capped, floor);
+            (proposal_id, capped.tally)
+        })
+        .collect())
+}
+
+/// Warn operators, once per proposal, of a large low-reputation voting bloc
+fn warn_of_sybil_bloc(state: &AppState, proposal_id: &str, capped: &CappedTally, floor: f64) {
+    if capped.largest_low_reputation_bloc >= state.governance_params.sybil_warning_bloc()
+        && state.sybil_warnings.lock().insert(proposal_id.to_string())
+    {
+        warn!(
+            "Possible sybil voting on {}: {} low-reputation voters voted identically",
+            proposal_id, capped.largest_low_reputation_bloc
+        );
+        let _ = state.event_tx.send(WsMessage::Warning {
+            kind: WarningKind::PossibleSybil,
+            message: format!(
+                "Proposal {}: {} voters below reputation {} voted identically; their weight is capped at {}",
+                proposal_id,
+                capped.largest_low_reputation_bloc,
+                floor,
+                state.governance_params.sybil_weight_cap()
+            ),
+        });
+    }
+}
+
+/// A proposal with its tallies and current status
+async fn proposal_entry(
+    state: &AppState,
+    names: &mut NameCache,
+    record: ProposalRecord,
+    tally: &VoteTally,
+    eligible: usize,
+    now: i64,
+) -> ProposalEntry {
+    let status = record.current_status(tally, eligible, now).to_string();
+    let quorum = record.required_voters(eligible);
+    ProposalEntry {
+        id: record.id,
+        proposer_name: names.display_name(state, &record.proposer).await,
+        proposer: record.proposer,
+        title: record.title,
+        description: record.description,
+        proposal_type: record.proposal_type,
+        parameters: record.parameters,
+        status,
+        yes_votes: tally.yes.round() as u32,
+        no_votes: tally.no.round() as u32,
+        quorum,
+        deadline: record.deadline,
+        version: record.version,
+    }
+}
+
+/// A proposal's current tallies and status, to broadcast after it changes
+pub(crate) async fn proposal_update(state: &AppState, proposal_id: &str) -> Option<WsMessage> {
+    let record = match state.store.get_proposal(proposal_id).await {
+        Ok(Some(record)) => record,
+        Ok(None) => return None,
+        Err(e) => {
+            warn!("Failed to load proposal {}: {}", proposal_id, e);
+            return None;
+        }
+    };
+    let tally = match tally_proposal(state, proposal_id).await {
+        Ok(tally) => tally,
+        Err(e) => {
+            warn!("Failed to tally votes for {}: {}", proposal_id, e);
+            return None;
+        }
+    };
+    let now = chrono::Utc::now().timestamp_millis();
+    let eligible = eligible_voters(state).await;
+    let entry = proposal_entry(state, &mut NameCache::default(), record, &tally, eligible, now).await;
+    Some(WsMessage::Proposal {
+        id: entry.id,
+        proposer: entry.proposer,
+        proposer_name: entry.proposer_name,
+        title: entry.title,
+        description: entry.description,
+        proposal_type: entry.proposal_type,
+        parameters: entry.parameters,
+        status: entry.status,
+        yes_votes: entry.yes_votes,
+        no_votes: entry.no_votes,
+        quorum: entry.quorum,
+        deadline: entry.deadline,
+        version: entry.version,
+        correlation_id: None,
+        timestamp: now,
+    })
+}
+
+/// Quorum progress for a proposal, measured against the current eligible set
+pub(crate) async fn quorum_progress(state: &AppState, proposal_id: &str) -> Option<WsMessage> {
+    let record = match state.store.get_proposal(proposal_id).await {
+        Ok(Some(record)) => record,
+        Ok(None) => return None,
+        Err(e) => {
+            warn!("Failed to load proposal {}: {}", proposal_id, e);
+            return None;
+        }
+    };
+    let tally = match tally_proposal(state, proposal_id).await {
+        Ok(tally) => tally,
+        Err(e) => {
+            warn!("Failed to tally votes for {}: {}", proposal_id, e);
+            return None;
+        }
+    };
+    let eligible = eligible_voters(state).await;
+    Some(WsMessage::QuorumProgress {
+        proposal_id: record.id.clone(),
+        voters: tally.voters,
+        required: record.required_voters(eligible),
+        eligible,
+        quorum_mode: record.quorum_mode,
+    })
+}
+
+/// Every stored proposal with its current tallies and status
+pub(super) async fn proposal_entries(state: &AppState) -> Result<Vec<ProposalEntry>, HandlerError> {
+    let records = match state.store.list_proposals().await {
+        Ok(records) => records,
+        Err(e) => {
+            error!("Failed to list proposals: {}", e);
+            return Err(HandlerError::internal("Failed to list proposals"));
+        }
+    };
+    let tallies = match tally_proposals(state).await {
+        Ok(tallies) => tallies,
+        Err(e) => {
+            error!("Failed to tally proposals: {}", e);
+            return Err(HandlerError::internal("Failed to list proposals"));
+        }
+    };
+    let eligible = eligible_voters(state).await;
+    let now = chrono::Utc::now().timestamp_millis();
+    let mut names = NameCache::default();
+    let mut proposals = Vec::with_capacity(records.len());
+    for record in records {
+        let tally = tallies.get(&record.id).cloned().unwrap_or_default();
+        proposals.push(proposal_entry(state, &mut names, record, &tally, eligible, now).await);
+    }
+    Ok(proposals)
+}
+
+/// Carry out a governance request routed here by the WebSocket handler
+pub(super) async fn handle_request(msg: ClientMessage, state: &AppState, session: &mut Session) -> Result<(), HandlerError> {
+    match msg {
+        ClientMessage::CreateProposal {
+            title,
+            description,
+            proposal_type,
+            quorum,
+            quorum_mode,
+            passing_rule,
+            deadline_secs,
+            correlation_id,
+        } => {
+            info!("CreateProposal: title='{}'", title);
+
+            // A quorum larger than the eligible set could never be met
+            let eligible = eligible_voters(state).await;
+            if let Some(quorum) = quorum {
+                if quorum == 0 || quorum as usize > eligible {
+                    return Err(HandlerError::invalid(format!(
+                        "Quorum must be between 1 and the {} eligible voters",
+                        eligible
+                    )));
+                }
+            }
+
+            if let Err(e) = passing_rule.validate() {
+                return Err(HandlerError::invalid(e));
+            }
+            let proposal_type = match proposal_type.validate() {
+                Ok(proposal_type) => proposal_type,
+                Err(e) => {
+                    return Err(HandlerError::invalid(e));
+                }
+            };
+            let parameters = proposal_type.parameters();
+            let deadline_secs = deadline_secs.unwrap_or(DEFAULT_PROPOSAL_DEADLINE_SECS);
+            if !(MIN_PROPOSAL_DEADLINE_SECS..=MAX_PROPOSAL_DEADLINE_SECS).contains(&deadline_secs) {
+                return Err(HandlerError::invalid(
+                    format!(
+                        "Proposal deadline must be between {} and {} seconds",
+                        MIN_PROPOSAL_DEADLINE_SECS, MAX_PROPOSAL_DEADLINE_SECS
+                    ),
+                ));
+            }
+
+            let now = chrono::Utc::now();
+            let timestamp = now.timestamp_millis();
+            let deadline = now + chrono::Duration::seconds(deadline_secs as i64);
+            let proposer = state.local_peer_id.to_string();
+            let exempt = session.is_admin() && state.config.proposal_cooldown_exempt_admins;
+            if !exempt {
+                if let Some(left) = state.proposal_cooldowns.remaining(&proposer, timestamp) {
+                    return Err(HandlerError::rate_limited(
+                        format!("Proposal cooldown active, {}s remaining", left.as_secs_f64().ceil()),
+                    ));
+                }
+            }
+
+            let protocol_proposal = ProtocolCreateProposal::new(
+                proposer.clone(),
+                title.clone(),
+                description.clone(),
+            )
+            .with_type(proposal_types::to_protocol(
+                proposal_type.label(),
+                &parameters,
+                &governance_params::current(state),
+            ))
+            .with_quorum_mode(quorum_mode)
+            .with_passing_rule(passing_rule)
+            .with_deadline(deadline)
+            .with_correlation_id(correlation_id.clone())
+            .with_signature(session.take_action_signature());
+            // An explicit count also sets the fraction, which dynamic proposals scale by
+            let protocol_proposal = match quorum {
+                Some(voters) => protocol_proposal
+                    .with_quorum(voters as f64 / eligible as f64)
+                    .with_quorum_voters(voters),
+                None => protocol_proposal,
+            };
+            let proposal_id = protocol_proposal.id.to_string();
+            let quorum_fraction = protocol_proposal.quorum;
+            let quorum = quorum.unwrap_or_else(|| required_voters(quorum_fraction, eligible));
+            let proposal_msg = GovernanceMessage::CreateProposal(protocol_proposal);
+
+            match serde_json::to_vec(&proposal_msg) {
+                Ok(data) => {
+                    if publish_economics(state, session, topics::GOVERNANCE, data, "proposal").await? {
+                        state.proposal_cooldowns.record(&proposer, timestamp);
+                        let record = ProposalRecord {
+                            id: proposal_id,
+                            proposer,
+                            title,
+                            description,
+                            proposal_type: proposal_type.label().to_string(),
+                            parameters,
+                            status: "active".to_string(),
+                            quorum,
+                            quorum_fraction,
+                            quorum_mode,
+                            passing_rule,
+                            deadline: deadline.timestamp_millis(),
+                            created_at: timestamp,
+                            version: 1,
+                        };
+                        if let Err(e) = state.store.upsert_proposal(&record).await {
+                            warn!("Failed to store proposal: {}", e);
+                        } else if let Err(e) = state.store.mark_proposal_local(&record.id).await {
+                            warn!("Failed to mark proposal {} as local: {}", record.id, e);
+                        }
+
+                        let echo_msg = WsMessage::Proposal {
+                            id: record.id,
+                            proposer_name: naming::resolve_display_name(state, &record.proposer).await,
+                            proposer: record.proposer,
+                            title: record.title,
+                            description: record.description,
+                            proposal_type: record.proposal_type,
+                            parameters: record.parameters,
+                            status: record.status,
+                            yes_votes: 0,
+                            no_votes: 0,
+                            quorum: record.quorum,
+                            deadline: record.deadline,
+                            version: record.version,
+                            correlation_id,
+                            timestamp,
+                        };
+                        let _ = state.event_tx.send(echo_msg);
+                    }
+                }
+                Err(e) => {
+                    error!("Failed to serialize proposal: {}", e);
+                    return Err(HandlerError::internal("Failed to serialize proposal"));
+                }
+            }
+        }
+
+        ClientMessage::AmendProposal { proposal_id, title, description } => {
+            info!("AmendProposal: proposal_id='{}'", proposal_id);
+
+            let prop_uuid = match Uuid::parse_str(&proposal_id) {
+                Ok(id) => id,
+                Err(e) => {
+                    return Err(HandlerError::invalid(format!("Invalid proposal ID: {}", e)));
+                }
+            };
+            let record = match state.store.get_proposal(&proposal_id).await {
+                Ok(Some(record)) => record,
+                Ok(None) => {
+                    return Err(HandlerError::invalid(format!("Unknown proposal {}", proposal_id)));
+                }
+                Err(e) => {
+                    error!("Failed to load proposal {}: {}", proposal_id, e);
+                    return Err(HandlerError::internal("Failed to load proposal"));
+                }
+            };
+            if record.proposer != state.local_peer_id.to_string() {
+                return Err(HandlerError::forbidden("Only the proposer can amend a proposal"));
+            }
+            if title.is_none() && description.is_none() {
+                return Err(HandlerError::invalid("Nothing to amend"));
+            }
+            match state.store.list_votes(&proposal_id).await {
+                Ok(votes) if votes.is_empty() && record.status == "active" => {}
+                Ok(_) => {
+                    return Err(HandlerError::invalid(
+                        "Proposals can't be amended once voting has started",
+                    ));
+                }
+                Err(e) => {
+                    error!("Failed to load votes for {}: {}", proposal_id, e);
+                    return Err(HandlerError::internal("Failed to load votes"));
+                }
+            }
+
+            let timestamp = chrono::Utc::now();
+            let amendment = ProtocolProposalAmendment {
+                proposal_id: prop_uuid,
+                proposer: record.proposer.clone(),
+                title: title.unwrap_or(record.title),
+                description: description.unwrap_or(record.description),
+                version: record.version + 1,
+                timestamp,
+            };
+
+            match serde_json::to_vec(&GovernanceMessage::AmendProposal(amendment.clone())) {
+                Ok(data) => {
+                    if publish_economics(state, session, topics::GOVERNANCE, data, "amendment").await? {
+                        match state
+                            .store
+                            .amend_proposal(&proposal_id, &amendment.title, &amendment.description, amendment.version)
+                            .await
+                        {
+                            Ok(true) => {}
+                            Ok(false) => warn!("Amendment to {} was superseded before it was stored", proposal_id),
+                            Err(e) => warn!("Failed to store amendment: {}", e),
+                        }
+
+                        let _ = state.event_tx.send(WsMessage::Proposal {
+                            id: proposal_id,
+                            proposer_name: naming::resolve_display_name(state, &record.proposer).await,
+                            proposer: record.proposer,
+                            title: amendment.title,
+                            description: amendment.description,
+                            proposal_type: record.proposal_type,
+                            parameters: record.parameters,
+                            status: record.status,
+                            yes_votes: 0,
+                            no_votes: 0,
+                            quorum: record.quorum,
+                            deadline: record.deadline,
+                            version: amendment.version,
+                            correlation_id: None,
+                            timestamp: timestamp.timestamp_millis(),
+                        });
+                    }
+                }
+                Err(e) => {
+                    error!("Failed to serialize amendment: {}", e);
+                    return Err(HandlerError::internal("Failed to serialize amendment"));
+                }
+            }
+        }
+
+        ClientMessage::CancelProposal { proposal_id } => {
+            info!("CancelProposal: proposal_id='{}'", proposal_id);
+
+            let prop_uuid = match Uuid::parse_str(&proposal_id) {
+                Ok(id) => id,
+                Err(e) => {
+                    return Err(HandlerError::invalid(format!("Invalid proposal ID: {}", e)));
+                }
+            };
+            let record = match state.store.get_proposal(&proposal_id).await {
+                Ok(Some(record)) => record,
+                Ok(None) => {
+                    return Err(HandlerError::invalid(format!("Unknown proposal {}", proposal_id)));
+                }
+                Err(e) => {
+                    error!("Failed to load proposal {}: {}", proposal_id, e);
+                    return Err(HandlerError::internal("Failed to load proposal"));
+                }
+            };
+            if record.proposer != state.local_peer_id.to_string() {
+                return Err(HandlerError::forbidden("Only the proposer can cancel a proposal"));
+            }
+            if record.status != "active" {
+                return Err(HandlerError::invalid(format!("Proposal is already {}", record.status)));
+            }
+
+            let cancellation = ProposalCancellation {
+                proposal_id: prop_uuid,
+                proposer: record.proposer,
+                timestamp: chrono::Utc::now(),
+            };
+            match serde_json::to_vec(&GovernanceMessage::CancelProposal(cancellation)) {
+                Ok(data) => {
+                    if publish_economics(state, session, topics::GOVERNANCE, data, "cancellation").await? {
+                        match state.store.close_proposal(&proposal_id, "cancelled").await {
+                            Ok(true) => {
+                                if let Some(update) = proposal_update(state, &proposal_id).await {
+                                    let _ = state.event_tx.send(update);
+                                }
+                            }
+                            Ok(false) => warn!("Proposal {} closed before it was cancelled", proposal_id),
+                            Err(e) => warn!("Failed to store cancellation: {}", e),
+                        }
+                    }
+                }
+                Err(e) => {
+                    error!("Failed to serialize cancellation: {}", e);
+                    return Err(HandlerError::internal("Failed to serialize cancellation"));
+                }
+            }
+        }
+
+        // Each peer holds one vote per proposal. Voting again changes the
+        // vote: the store replaces the earlier record, so tallies count the
+        // voter once with their latest choice. Repeating the same vote would
+        // change nothing and is rejected rather than republished.
+        ClientMessage::CastVote { proposal_id, vote, correlation_id } => {
+            info!("CastVote: proposal_id='{}', vote='{}'", proposal_id, vote);
+
+            let timestamp = chrono::Utc::now().timestamp_millis();
+
+            // Parse proposal_id as UUID
+            let prop_uuid = match Uuid::parse_str(&proposal_id) {
+                Ok(id) => id,
+                Err(e) => return Err(HandlerError::invalid(format!("Invalid proposal ID: {}", e))),
+            };
+
+            let Some(vote_enum) = parse_vote(&vote) else {
+                return Err(HandlerError::invalid(format!("Invalid vote '{}': expected yes, no or abstain", vote)));
+            };
+
+            // Only open proposals take votes
+            match state.store.get_proposal(&proposal_id).await {
+                Ok(Some(proposal)) if proposal.accepts_votes(timestamp) => {}
+                Ok(Some(proposal)) if proposal.status != "active" => {
+                    return Err(HandlerError::invalid(format!("Proposal is {}", proposal.status)));
+                }
+                Ok(Some(_)) => return Err(HandlerError::invalid("Proposal deadline has passed")),
+                Ok(None) => return Err(HandlerError::invalid(format!("Unknown proposal: {}", proposal_id))),
+                Err(e) => {
+                    error!("Failed to load proposal {}: {}", proposal_id, e);
+                    return Err(HandlerError::internal("Failed to load proposal"));
+                }
+            }
+
+            let voter = state.local_peer_id.to_string();
+            let weight = voter_weight(state, &voter).await;
+
+            let vote_record = VoteRecord {
+                proposal_id: proposal_id.clone(),
+                voter,
+                vote: vote_label(&vote_enum).to_string(),
+                weight,
+                timestamp,
+            };
+
+            match state.store.get_vote(&proposal_id, &vote_record.voter).await {
+                Ok(Some(previous)) if previous.vote == vote_record.vote => {
+                    return Err(HandlerError::invalid(
+                        format!("Already voted {} on this proposal", previous.vote),
+                    ));
+                }
+                Ok(Some(previous)) => {
+                    info!("Changing vote on {} from {} to {}", proposal_id, previous.vote, vote_record.vote);
+                }
+                Ok(None) => {}
+                Err(e) => warn!("Failed to look up previous vote: {}", e),
+            }
+
+            // CastVote::new takes (proposal_id: Uuid, voter, vote, weight)
+            let vote_msg = GovernanceMessage::CastVote(ProtocolCastVote::new(
+                prop_uuid,
+                state.local_peer_id.to_string(),
+                vote_enum,
+                weight,
+            )
+            .with_correlation_id(correlation_id.clone())
+            .with_signature(session.take_action_signature()));
+
+            match serde_json::to_vec(&vote_msg) {
+                Ok(data) => {
+                    if publish_economics(state, session, topics::GOVERNANCE, data, "vote").await? {
+                        if let Err(e) = state.store.record_vote(&vote_record).await {
+                            warn!("Failed to store vote: {}", e);
+                        }
+
+                        let echo_msg = WsMessage::VoteCast {
+                            id: Uuid::new_v4().to_string(),
+                            proposal_id: proposal_id.clone(),
+                            voter: state.local_peer_id.to_string(),
+                            voter_name: state.node_name(),
+                            vote,
+                            weight,
+                            correlation_id,
+                            timestamp,
+                        };
+                        let _ = state.event_tx.send(echo_msg);
+
+                        if let Some(progress) = quorum_progress(state, &proposal_id).await {
+                            let _ = state.event_tx.send(progress);
+                        }
+                        if let Some(update) = proposal_update(state, &proposal_id).await {
+                            let _ = state.event_tx.send(update);
+                        }
+                    }
+                }
+                Err(e) => {
+                    error!("Failed to serialize vote: {}", e);
+                    return Err(HandlerError::internal("Failed to serialize vote"));
+                }
+            }
+        }
+
+        ClientMessage::FinalizeProposal { proposal_id } => {
+            if !session.is_admin() {
+                return Err(HandlerError::forbidden("FinalizeProposal requires admin"));
+            }
+            let now = chrono::Utc::now().timestamp_millis();
+            match execution::finalize_proposal(state, &proposal_id, now).await {
+                Ok(Some(_)) => {}
+                Ok(None) => return Err(HandlerError::invalid("Proposal is already closed")),
+                Err(e) => {
+                    warn!("{}", e);
+                    return Err(HandlerError::invalid(e));
+                }
+            }
+        }
+
+        ClientMessage::GetProposals => {
+            let proposals = proposal_entries(state).await?;
+            session.reply(WsMessage::Proposals { proposals });
+        }
+
+        ClientMessage::GetGovernanceStats => {
+            let now = chrono::Utc::now().timestamp_millis();
+            let eligible = eligible_voters(state).await;
+
+            let stats = match state.store.governance_stats(eligible, now - PARTICIPATION_WINDOW_MS, now).await {
+                Ok(stats) => stats,
+                Err(e) => {
+                    error!("Failed to compute governance stats: {}", e);
+                    return Err(HandlerError::internal("Failed to compute governance stats"));
+                }
+            };
+            session.reply(WsMessage::GovernanceStats {
+                active_proposals: stats.active_proposals,
+                avg_turnout: stats.avg_turnout,
+                participating_peers: stats.participating_peers,
+                total_peers: stats.total_peers,
+            });
+        }
+
+        ClientMessage::GetGovernanceSummary => {
+            let local = state.local_peer_id.to_string();
+            let loaded = match state.store.list_proposals().await {
+                Ok(records) => state.store.vote_counts(&local).await.map(|counts| (records, counts)),
+                Err(e) => Err(e),
+            };
+            let (records, (total_votes, my_votes)) = match loaded {
+                Ok(loaded) => loaded,
+                Err(e) => {
+                    error!("Failed to load governance summary: {}", e);
+                    return Err(HandlerError::internal("Failed to load governance summary"));
+                }
+            };
+            // Statuses as GetProposals reports them, so proposals past their
+            // deadline count by outcome even before they are closed
+            let eligible = eligible_voters(state).await;
+            let now = chrono::Utc::now().timestamp_millis();
+            let tallies = match tally_proposals(state).await {
+                Ok(tallies) => tallies,
+                Err(e) => {
+                    error!("Failed to tally proposals: {}", e);
+                    return Err(HandlerError::internal("Failed to load governance summary"));
+                }
+            };
+            let (mut active, mut passed, mut rejected) = (0, 0, 0);
+            for record in records {
+                let tally = tallies.get(&record.id).cloned().unwrap_or_default();
+                match record.current_status(&tally, eligible, now) {
+                    "active" => active += 1,
+                    "passed" => passed += 1,
+                    "rejected" => rejected += 1,
+                    _ => {}
+                }
+            }
+            session.reply(WsMessage::GovernanceSummary {
+                active,
+                passed,
+                rejected,
+                total_votes,
+                my_votes,
+            });
+        }
+
+        other => unreachable!("not a governance request: {:?}", other),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ServerConfig;
+    use crate::proposal_types::ProposalKind;
+    use crate::server::messages::ErrorCode;
+    use crate::server::websocket::handle_client_message;
+    use crate::test_support::{remote_peer, store_local_proposal, test_session, test_state, test_state_with_config};
+    use mycelial_core::peer::PeerInfo;
+    use mycelial_core::reputation::Reputation;
+    use mycelial_network::NetworkCommand;
+    use mycelial_protocol::{PassingRule, QuorumMode};
+
+    #[tokio::test]
+    async fn test_low_reputation_votes_capped_and_flagged() {
+        let config = ServerConfig {
+            sybil_reputation_floor: Some(0.2),
+            sybil_weight_cap: 1.0,
+            sybil_warning_bloc: 3,
+            ..ServerConfig::default()
+        };
+        let (state, _commands) = test_state_with_config(config).await;
+        let mut events = state.event_tx.subscribe();
+        let id = store_local_proposal(&state).await;
+
+        let known = |peer: &str, score: f64| {
+            let info = PeerInfo {
+                id: mycelial_core::peer::PeerId(peer.to_string()),
+                public_key: peer.to_string(),
+                addresses: vec![],
+                first_seen: chrono::Utc::now(),
+                last_seen: chrono::Utc::now(),
+                name: None,
+            };
+            let reputation = mycelial_core::reputation::Reputation {
+                score,
+                ..Default::default()
+            };
+            (info, reputation)
+        };
+
+        // Ten low-reputation identities all vote yes
+        let mut votes: Vec<(String, &str)> = (0..10).map(|i| (format!("sybil-{}", i), "yes")).collect();
+        votes.push(("trusted".to_string(), "no"));
+        for (voter, vote) in &votes {
+            let (info, reputation) = known(voter, if *vote == "yes" { 0.05 } else { 0.9 });
+            state.store.upsert_peer(&info, Some(&reputation)).await.unwrap();
+        }
+        for (voter, vote) in votes {
+            let record = VoteRecord {
+                proposal_id: id.clone(),
+                voter,
+                vote: vote.to_string(),
+                weight: 1.0,
+                timestamp: 0,
+            };
+            state.store.record_vote(&record).await.unwrap();
+        }
+
+        let tally = tally_proposal(&state, &id).await.unwrap();
+        assert!((tally.yes - 1.0).abs() < 1e-9);
+        assert!((tally.no - 1.0).abs() < 1e-9);
+        assert_eq!(tally.voters, 11);
+
+        match events.try_recv().unwrap() {
+            WsMessage::Warning { kind, .. } => assert_eq!(kind, WarningKind::PossibleSybil),
+            other => panic!("unexpected message: {:?}", other),
+        }
+        // The proposal is only flagged once
+        tally_proposal(&state, &id).await.unwrap();
+        assert!(events.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_amend_proposal_before_voting() {
+        let (state, _commands) = test_state().await;
+        let mut events = state.event_tx.subscribe();
+        let (mut session, mut replies) = test_session(8);
+        let id = store_local_proposal(&state).await;
+
+        let msg = ClientMessage::AmendProposal {
+            proposal_id: id.clone(),
+            title: Some("Amended".to_string()),
+            description: None,
+        };
+        handle_client_message(msg, &state, &mut session).await;
+
+        match events.try_recv().unwrap() {
+            WsMessage::Proposal { title, description, version, .. } => {
+                assert_eq!(title, "Amended");
+                assert_eq!(description, "Original text");
+                assert_eq!(version, 2);
+            }
+            other => panic!("unexpected message: {:?}", other),
+        }
+        assert!(replies.try_recv().is_err());
+
+        let stored = state.store.get_proposal(&id).await.unwrap().unwrap();
+        assert_eq!(stored.title, "Amended");
+        assert_eq!(stored.version, 2);
+    }
+
+    #[tokio::test]
+    async fn test_amend_proposal_rejected_after_vote() {
+        let (state, _commands) = test_state().await;
+        let mut events = state.event_tx.subscribe();
+        let (mut session, mut replies) = test_session(8);
+        let id = store_local_proposal(&state).await;
+
+        state
+            .store
+            .record_vote(&VoteRecord {
+                proposal_id: id.clone(),
+                voter: "bob".to_string(),
+                vote: "yes".to_string(),
+                weight: 1.0,
+                timestamp: 1,
+            })
+            .await
+            .unwrap();
+
+        let msg = ClientMessage::AmendProposal {
+            proposal_id: id.clone(),
+            title: Some("Amended".to_string()),
+            description: None,
+        };
+        handle_client_message(msg, &state, &mut session).await;
+
+        match replies.try_recv().unwrap() {
+            WsMessage::Error { code, .. } => assert_eq!(code, ErrorCode::InvalidRequest),
+            other => panic!("unexpected message: {:?}", other),
+        }
+        assert!(events.try_recv().is_err());
+        assert_eq!(state.store.get_proposal(&id).await.unwrap().unwrap().title, "Original");
+    }
+
+    #[tokio::test]
+    async fn test_proposer_cancels_proposal() {
+        let (state, mut commands) = test_state().await;
+        let mut events = state.event_tx.subscribe();
+        let (mut session, mut replies) = test_session(8);
+        let id = store_local_proposal(&state).await;
+
+        handle_client_message(ClientMessage::CancelProposal { proposal_id: id.clone() }, &state, &mut session).await;
+
+        match commands.try_recv() {
+            Ok(NetworkCommand::Publish { topic, data }) => {
+                assert_eq!(topic, topics::GOVERNANCE);
+                match serde_json::from_slice::<GovernanceMessage>(&data).unwrap() {
+                    GovernanceMessage::CancelProposal(published) => {
+                        assert_eq!(published.proposal_id.to_string(), id);
+                        assert_eq!(published.proposer, state.local_peer_id.to_string());
+                    }
+                    other => panic!("expected cancellation, got {:?}", other),
+                }
+            }
+            other => panic!("expected publish, got {:?}", other),
+        }
+        match events.try_recv() {
+            Ok(WsMessage::Proposal { id: cancelled, status, .. }) => {
+                assert_eq!(cancelled, id);
+                assert_eq!(status, "cancelled");
+            }
+            other => panic!("expected proposal update, got {:?}", other),
+        }
+        assert!(replies.try_recv().is_err());
+        assert_eq!(state.store.get_proposal(&id).await.unwrap().unwrap().status, "cancelled");
+
+        // A cancelled proposal can't be cancelled again
+        handle_client_message(ClientMessage::CancelProposal { proposal_id: id }, &state, &mut session).await;
+        assert!(matches!(
+            replies.try_recv(),
+            Ok(WsMessage::Error { code: ErrorCode::InvalidRequest, .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_vote_on_cancelled_proposal_rejected() {
+        let (state, mut commands) = test_state().await;
+        let (mut session, mut replies) = test_session(8);
+        let id = store_local_proposal(&state).await;
+        handle_client_message(ClientMessage::CancelProposal { proposal_id: id.clone() }, &state, &mut session).await;
+        while commands.try_recv().is_ok() {}
+        let mut events = state.event_tx.subscribe();
+
+        let vote = ClientMessage::CastVote {
+            proposal_id: id.clone(),
+            vote: "yes".to_string(),
+            correlation_id: None,
+        };
+        handle_client_message(vote, &state, &mut session).await;
+        match replies.try_recv() {
+            Ok(WsMessage::Error { code: ErrorCode::InvalidRequest, message }) => {
+                assert_eq!(message, "Proposal is cancelled");
+            }
+            other => panic!("expected invalid request, got {:?}", other),
+        }
+        // Neither stored, published nor rebroadcast with a fresh tally
+        assert!(state.store.list_votes(&id).await.unwrap().is_empty());
+        assert!(commands.try_recv().is_err());
+        assert!(events.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_cancel_rejected_for_non_proposer() {
+        let (state, mut commands) = test_state().await;
+        let mut events = state.event_tx.subscribe();
+        let (mut session, mut replies) = test_session(8);
+        let local_id = store_local_proposal(&state).await;
+        let mut record = state.store.get_proposal(&local_id).await.unwrap().unwrap();
+        record.id = Uuid::new_v4().to_string();
+        record.proposer = "remote-peer".to_string();
+        state.store.upsert_proposal(&record).await.unwrap();
+
+        let msg = ClientMessage::CancelProposal { proposal_id: record.id.clone() };
+        handle_client_message(msg, &state, &mut session).await;
+
+        assert!(matches!(
+            replies.try_recv(),
+            Ok(WsMessage::Error { code: ErrorCode::Forbidden, .. })
+        ));
+        assert!(commands.try_recv().is_err());
+        assert!(events.try_recv().is_err());
+        assert_eq!(state.store.get_proposal(&record.id).await.unwrap().unwrap().status, "active");
+    }
+
+    #[tokio::test]
+    async fn test_quorum_progress_follows_mode() {
+        let (state, _commands) = test_state().await;
+        let mut events = state.event_tx.subscribe();
+        let (mut session, _replies) = test_session(8);
+
+        // Only the local node is eligible at creation
+        for quorum_mode in [QuorumMode::Snapshot, QuorumMode::Dynamic] {
+            let msg = ClientMessage::CreateProposal {
+                title: format!("{:?}", quorum_mode),
+                description: "Quorum test".to_string(),
+                proposal_type: ProposalKind::Text,
+                quorum: None,
+                quorum_mode,
+                passing_rule: PassingRule::SimpleMajority,
+                deadline_secs: None,
+                correlation_id: None,
+            };
+            handle_client_message(msg, &state, &mut session).await;
+        }
+        let mut ids = Vec::new();
+        while let Ok(event) = events.try_recv() {
+            if let WsMessage::Proposal { id, quorum, .. } = event {
+                assert_eq!(quorum, 1);
+                ids.push(id);
+            }
+        }
+        assert_eq!(ids.len(), 2);
+
+        // Three more peers become eligible before voting
+        for i in 0..3 {
+            let peer = PeerInfo {
+                id: mycelial_core::peer::PeerId(format!("peer-{}", i)),
+                public_key: format!("peer-{}", i),
+                addresses: vec![],
+                first_seen: chrono::Utc::now(),
+                last_seen: chrono::Utc::now(),
+                name: None,
+            };
+            state.store.upsert_peer(&peer, None).await.unwrap();
+        }
+
+        let mut required = Vec::new();
+        for id in &ids {
+            let msg = ClientMessage::CastVote {
+                proposal_id: id.clone(),
+                vote: "yes".to_string(),
+                correlation_id: None,
+            };
+            handle_client_message(msg, &state, &mut session).await;
+            while let Ok(event) = events.try_recv() {
+                if let WsMessage::QuorumProgress { voters, required: needed, eligible, .. } = event {
+                    assert_eq!(voters, 1);
+                    assert_eq!(eligible, 4);
+                    required.push(needed);
+                }
+            }
+        }
+        // Snapshot keeps the creation-time requirement, dynamic follows the live set
+        assert_eq!(required, vec![1, 2]);
+    }
+
+    #[tokio::test]
+    async fn test_proposal_quorum_default_and_override() {
+        let (state, mut commands) = test_state().await;
+        let (mut session, mut replies) = test_session(8);
+        let mut events = state.event_tx.subscribe();
+        // Six known peers plus the local node: seven eligible voters
+        for i in 0..6 {
+            let peer = PeerInfo {
+                id: mycelial_core::peer::PeerId(format!("peer-{}", i)),
+                public_key: format!("peer-{}", i),
+                addresses: vec![],
+                first_seen: chrono::Utc::now(),
+                last_seen: chrono::Utc::now(),
+                name: None,
+            };
+            state.store.upsert_peer(&peer, None).await.unwrap();
+        }
+        let propose = |quorum| ClientMessage::CreateProposal {
+            title: format!("Quorum {:?}", quorum),
+            description: "Quorum test".to_string(),
+            proposal_type: ProposalKind::Text,
+            quorum,
+            quorum_mode: QuorumMode::Snapshot,
+            passing_rule: PassingRule::SimpleMajority,
+            deadline_secs: None,
+            correlation_id: None,
+        };
+
+        for (quorum, expected) in [(None, 4), (Some(2), 2)] {
+            handle_client_message(propose(quorum), &state, &mut session).await;
+            let id = match events.try_recv() {
+                Ok(WsMessage::Proposal { id, quorum, .. }) => {
+                    assert_eq!(quorum, expected);
+                    id
+                }
+                other => panic!("expected proposal, got {:?}", other),
+            };
+            assert_eq!(state.store.get_proposal(&id).await.unwrap().unwrap().quorum, expected);
+            match commands.try_recv() {
+                Ok(NetworkCommand::Publish { data, .. }) => {
+                    let json: serde_json::Value = serde_json::from_slice(&data).unwrap();
+                    assert_eq!(json["quorum_voters"], serde_json::json!(quorum));
+                }
+                other => panic!("expected publish, got {:?}", other),
+            }
+        }
+
+        // Zero, or more voters than are eligible, is rejected
+        for quorum in [0, 8] {
+            handle_client_message(propose(Some(quorum)), &state, &mut session).await;
+            assert!(matches!(
+                replies.try_recv(),
+                Ok(WsMessage::Error { code: ErrorCode::InvalidRequest, .. })
+            ));
+        }
+        assert!(commands.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_votes_update_proposal_tallies_and_status() {
+        let (state, _commands) = test_state().await;
+        let (mut session, mut replies) = test_session(8);
+        let mut events = state.event_tx.subscribe();
+
+        let msg = ClientMessage::CreateProposal {
+            title: "Live".to_string(),
+            description: "Still open".to_string(),
+            proposal_type: ProposalKind::Text,
+            quorum: None,
+            quorum_mode: QuorumMode::Snapshot,
+            passing_rule: PassingRule::SimpleMajority,
+            deadline_secs: None,
+            correlation_id: None,
+        };
+        handle_client_message(msg, &state, &mut session).await;
+        let mut live = None;
+        while let Ok(event) = events.try_recv() {
+            if let WsMessage::Proposal { id, .. } = event {
+                live = Some(id);
+            }
+        }
+        let live = live.unwrap();
+
+        let msg = ClientMessage::CastVote {
+            proposal_id: live.clone(),
+            vote: "yes".to_string(),
+            correlation_id: None,
+        };
+        handle_client_message(msg, &state, &mut session).await;
+        let mut updated = None;
+        while let Ok(event) = events.try_recv() {
+            if let WsMessage::Proposal { yes_votes, no_votes, status, .. } = event {
+                updated = Some((yes_votes, no_votes, status));
+            }
+        }
+        // Quorum is met, but voting stays open until the deadline
+        assert_eq!(updated, Some((1, 0, "active".to_string())));
+
+        // A proposal whose deadline has passed reports its outcome
+        let closed = store_local_proposal(&state).await;
+        let mut record = state.store.get_proposal(&closed).await.unwrap().unwrap();
+        record.quorum = 1;
+        record.quorum_fraction = 0.0;
+        record.deadline = 1;
+        record.created_at = 1;
+        state.store.upsert_proposal(&record).await.unwrap();
+        let vote = VoteRecord {
+            proposal_id: closed.clone(),
+            voter: "peer-1".to_string(),
+            vote: "no".to_string(),
+            weight: 1.0,
+            timestamp: 0,
+        };
+        state.store.record_vote(&vote).await.unwrap();
+
+        handle_client_message(ClientMessage::GetProposals, &state, &mut session).await;
+        match replies.try_recv().unwrap() {
+            WsMessage::Proposals { proposals } => {
+                assert_eq!(proposals.len(), 2);
+                assert_eq!(proposals[0].id, live);
+                assert_eq!((proposals[0].yes_votes, proposals[0].status.as_str()), (1, "active"));
+                assert_eq!(proposals[1].id, closed);
+                assert_eq!((proposals[1].no_votes, proposals[1].status.as_str()), (1, "rejected"));
+            }
+            other => panic!("unexpected message: {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_governance_summary_counts_by_status() {
+        let (state, _commands) = test_state().await;
+        let (mut session, mut replies) = test_session(8);
+        let local = state.local_peer_id.to_string();
+        let vote = |proposal_id: &str, voter: &str, vote: &str| VoteRecord {
+            proposal_id: proposal_id.to_string(),
+            voter: voter.to_string(),
+            vote: vote.to_string(),
+            weight: 1.0,
+            timestamp: 0,
+        };
+
+        // Two open proposals, one of them voted on by this node
+        let open = store_local_proposal(&state).await;
+        store_local_proposal(&state).await;
+        state.store.record_vote(&vote(&open, &local, "yes")).await.unwrap();
+
+        // One closed as passed
+        let closed = store_local_proposal(&state).await;
+        state.store.close_proposal(&closed, "passed").await.unwrap();
+
+        // Two past their deadline but not yet closed: one met quorum with a
+        // yes, the other drew a single no
+        for (voter, choice, expected) in [(&local, "yes", "passed"), (&remote_peer(), "no", "rejected")] {
+            let id = store_local_proposal(&state).await;
+            let mut record = state.store.get_proposal(&id).await.unwrap().unwrap();
+            record.quorum = 1;
+            record.deadline = 1;
+            state.store.upsert_proposal(&record).await.unwrap();
+            state.store.record_vote(&vote(&id, voter, choice)).await.unwrap();
+            let tally = state.store.tally_votes(&id).await.unwrap();
+            assert_eq!(record.current_status(&tally, 2, 2), expected);
+        }
+
+        handle_client_message(ClientMessage::GetGovernanceSummary, &state, &mut session).await;
+        match replies.try_recv() {
+            Ok(WsMessage::GovernanceSummary { active, passed, rejected, total_votes, my_votes }) => {
+                assert_eq!((active, passed, rejected), (2, 2, 1));
+                assert_eq!((total_votes, my_votes), (3, 2));
+            }
+            other => panic!("expected governance summary, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_governance_stats_sent_only_to_requester() {
+        let (state, _commands) = test_state().await;
+        let (mut session, mut replies) = test_session(8);
+        let mut events = state.event_tx.subscribe();
+        store_local_proposal(&state).await;
+
+        handle_client_message(ClientMessage::GetGovernanceStats, &state, &mut session).await;
+        match replies.try_recv() {
+            Ok(WsMessage::GovernanceStats { active_proposals, .. }) => assert_eq!(active_proposals, 1),
+            other => panic!("expected governance stats, got {:?}", other),
+        }
+        assert!(events.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_repeat_vote_changes_rather_than_adds() {
+        let (state, _commands) = test_state().await;
+        let (mut session, mut replies) = test_session(8);
+        let mut events = state.event_tx.subscribe();
+        let id = store_local_proposal(&state).await;
+        let cast = |vote: &str| ClientMessage::CastVote {
+            proposal_id: id.clone(),
+            vote: vote.to_string(),
+            correlation_id: None,
+        };
+
+        // First vote is counted
+        handle_client_message(cast("yes"), &state, &mut session).await;
+        let tally = state.store.tally_votes(&id).await.unwrap();
+        assert_eq!((tally.yes, tally.no, tally.voters), (1.0, 0.0, 1));
+
+        // Repeating the same vote is rejected and not republished
+        while events.try_recv().is_ok() {}
+        handle_client_message(cast("yes"), &state, &mut session).await;
+        match replies.try_recv().unwrap() {
+            WsMessage::Error { code: ErrorCode::InvalidRequest, message } => {
+                assert!(message.contains("Already voted yes"));
+            }
+            other => panic!("unexpected message: {:?}", other),
+        }
+        assert!(events.try_recv().is_err());
+
+        // A different vote replaces the first instead of adding to it
+        handle_client_message(cast("no"), &state, &mut session).await;
+        let tally = state.store.tally_votes(&id).await.unwrap();
+        assert_eq!((tally.yes, tally.no, tally.voters), (0.0, 1.0, 1));
+        let mut updated = None;
+        while let Ok(event) = events.try_recv() {
+            if let WsMessage::Proposal { yes_votes, no_votes, .. } = event {
+                updated = Some((yes_votes, no_votes));
+            }
+        }
+        assert_eq!(updated, Some((0, 1)));
+    }
+
+    #[test]
+    fn test_vote_weight_follows_reputation_within_bounds() {
+        assert_eq!(vote_weight(0.5), 1.0);
+        assert_eq!(vote_weight(0.75), 1.5);
+        assert_eq!(vote_weight(0.3), 0.6);
+        // Floor
+        assert_eq!(vote_weight(0.0), MIN_VOTE_WEIGHT);
+        assert_eq!(vote_weight(0.1), MIN_VOTE_WEIGHT);
+        assert_eq!(vote_weight(f64::NAN), MIN_VOTE_WEIGHT);
+        // Ceiling
+        assert_eq!(vote_weight(1.0), MAX_VOTE_WEIGHT);
+        assert_eq!(vote_weight(5.0), MAX_VOTE_WEIGHT);
+    }
+
+    #[tokio::test]
+    async fn test_vote_rejected_unless_valid_and_open() {
+        let (state, mut commands) = test_state().await;
+        let (mut session, mut replies) = test_session(8);
+        let cast = |proposal_id: &str, vote: &str| ClientMessage::CastVote {
+            proposal_id: proposal_id.to_string(),
+            vote: vote.to_string(),
+            correlation_id: None,
+        };
+        let mut rejected = |message: &str| match replies.try_recv() {
+            Ok(WsMessage::Error { code: ErrorCode::InvalidRequest, message: error }) => {
+                assert!(error.contains(message), "{}", error);
+            }
+            other => panic!("expected invalid request, got {:?}", other),
+        };
+
+        // Anything but yes, no or abstain is refused rather than read as abstain
+        let open = store_local_proposal(&state).await;
+        handle_client_message(cast(&open, "maybe"), &state, &mut session).await;
+        rejected("Invalid vote");
+
+        handle_client_message(cast(&Uuid::new_v4().to_string(), "yes"), &state, &mut session).await;
+        rejected("Unknown proposal");
+
+        let expired = store_local_proposal(&state).await;
+        let mut record = state.store.get_proposal(&expired).await.unwrap().unwrap();
+        record.deadline = 1;
+        state.store.upsert_proposal(&record).await.unwrap();
+        handle_client_message(cast(&expired, "yes"), &state, &mut session).await;
+        rejected("deadline has passed");
+
+        let closed = store_local_proposal(&state).await;
+        assert!(state.store.close_proposal(&closed, "passed").await.unwrap());
+        handle_client_message(cast(&closed, "no"), &state, &mut session).await;
+        rejected("Proposal is passed");
+
+        // None of them were stored or published
+        for id in [&open, &expired, &closed] {
+            assert!(state.store.list_votes(id).await.unwrap().is_empty());
+        }
+        assert!(commands.try_recv().is_err());
+
+        handle_client_message(cast(&open, "abstain"), &state, &mut session).await;
+        assert!(replies.try_recv().is_err());
+        assert_eq!(state.store.list_votes(&open).await.unwrap()[0].vote, "abstain");
+    }
+
+    #[tokio::test]
+    async fn test_cast_vote_weighted_by_own_reputation() {
+        let (state, _commands) = test_state().await;
+        let (mut session, _replies) = test_session(8);
+        let mut events = state.event_tx.subscribe();
+        let id = store_local_proposal(&state).await;
+        let local = PeerInfo {
+            id: mycelial_core::peer::PeerId(state.local_peer_id.to_string()),
+            public_key: state.local_peer_id.to_string(),
+            addresses: vec![],
+            first_seen: chrono::Utc::now(),
+            last_seen: chrono::Utc::now(),
+            name: None,
+        };
+        let reputation = Reputation {
+            score: 0.9,
+            ..Default::default()
+        };
+        state.store.upsert_peer(&local, Some(&reputation)).await.unwrap();
+
+        let msg = ClientMessage::CastVote {
+            proposal_id: id.clone(),
+            vote: "yes".to_string(),
+            correlation_id: None,
+        };
+        handle_client_message(msg, &state, &mut session).await;
+
+        let mut echoed = None;
+        while let Ok(event) = events.try_recv() {
+            if let WsMessage::VoteCast { weight, .. } = event {
+                echoed = Some(weight);
+            }
+        }
+        assert_eq!(echoed, Some(1.8));
+        let tally = state.store.tally_votes(&id).await.unwrap();
+        assert!((tally.yes - 1.8).abs() < 1e-9);
+    }
+
+    #[tokio::test]
+    async fn test_structured_proposal_type_validated_and_carried() {
+        let (state, mut commands) = test_state().await;
+        let (mut session, mut replies) = test_session(8);
+        let mut events = state.event_tx.subscribe();
+        let propose = |proposal_type| ClientMessage::CreateProposal {
+            title: "Raise quorum".to_string(),
+            description: "More voters".to_string(),
+            proposal_type,
+            quorum: None,
+            quorum_mode: QuorumMode::Snapshot,
+            passing_rule: PassingRule::SimpleMajority,
+            deadline_secs: None,
+            correlation_id: None,
+        };
+
+        // Malformed payloads are rejected before anything is published
+        let bad = ProposalKind::TreasurySpend { recipient: remote_peer(), amount: -1.0 };
+        handle_client_message(propose(bad), &state, &mut session).await;
+        assert!(matches!(
+            replies.try_recv(),
+            Ok(WsMessage::Error { code: ErrorCode::InvalidRequest, .. })
+        ));
+        assert!(commands.try_recv().is_err());
+
+        // So are parameters the node has no setting for
+        let unknown = ProposalKind::ParameterChange { key: "min_quorum".to_string(), value: "3".to_string() };
+        handle_client_message(propose(unknown), &state, &mut session).await;
+        match replies.try_recv() {
+            Ok(WsMessage::Error { code: ErrorCode::InvalidRequest, message }) => {
+                assert!(message.starts_with("Unknown parameter min_quorum"), "{}", message);
+            }
+            other => panic!("expected invalid request, got {:?}", other),
+        }
+        assert!(commands.try_recv().is_err());
+
+        let change = ProposalKind::ParameterChange { key: "sybil_warning_bloc".to_string(), value: "3".to_string() };
+        handle_client_message(propose(change), &state, &mut session).await;
+        match commands.try_recv() {
+            Ok(NetworkCommand::Publish { data, .. }) => {
+                let json: serde_json::Value = serde_json::from_slice(&data).unwrap();
+                let proposal_type = &json["proposal_type"]["parameter_change"];
+                assert_eq!(proposal_type["parameter"], "sybil_warning_bloc");
+                assert_eq!(proposal_type["old_value"], "5");
+                assert_eq!(proposal_type["new_value"], "3");
+            }
+            other => panic!("expected publish, got {:?}", other),
+        }
+        let id = match events.try_recv() {
+            Ok(WsMessage::Proposal { id, proposal_type, parameters, .. }) => {
+                assert_eq!(proposal_type, "parameter_change");
+                assert_eq!(parameters.get("sybil_warning_bloc").map(String::as_str), Some("3"));
+                id
+            }
+            other => panic!("expected proposal, got {:?}", other),
+        };
+        let stored = state.store.get_proposal(&id).await.unwrap().unwrap();
+        assert_eq!(stored.parameters.get("sybil_warning_bloc").map(String::as_str), Some("3"));
+    }
+
+    #[tokio::test]
+    async fn test_proposal_closes_at_deadline() {
+        let (state, _commands) = test_state().await;
+        let (mut session, mut replies) = test_session(8);
+        let mut events = state.event_tx.subscribe();
+        let propose = |deadline_secs| ClientMessage::CreateProposal {
+            title: "Short".to_string(),
+            description: "Closes in a minute".to_string(),
+            proposal_type: ProposalKind::Text,
+            quorum: None,
+            quorum_mode: QuorumMode::Snapshot,
+            passing_rule: PassingRule::SimpleMajority,
+            deadline_secs,
+            correlation_id: None,
+        };
+
+        for out_of_range in [Some(10), Some(MAX_PROPOSAL_DEADLINE_SECS + 1)] {
+            handle_client_message(propose(out_of_range), &state, &mut session).await;
+            assert!(matches!(
+                replies.try_recv(),
+                Ok(WsMessage::Error { code: ErrorCode::InvalidRequest, .. })
+            ));
+        }
+
+        handle_client_message(propose(Some(60)), &state, &mut session).await;
+        let (id, deadline) = match events.try_recv() {
+            Ok(WsMessage::Proposal { id, deadline, timestamp, .. }) => {
+                assert_eq!(deadline - timestamp, 60_000);
+                (id, deadline)
+            }
+            other => panic!("expected proposal, got {:?}", other),
+        };
+        let vote = ClientMessage::CastVote {
+            proposal_id: id.clone(),
+            vote: "yes".to_string(),
+            correlation_id: None,
+        };
+        handle_client_message(vote, &state, &mut session).await;
+        while events.try_recv().is_ok() {}
+
+        // Still open before the deadline
+        execution::close_expired_proposals(&state, deadline - 1).await;
+        assert!(events.try_recv().is_err());
+
+        execution::close_expired_proposals(&state, deadline).await;
+        match events.try_recv() {
+            Ok(WsMessage::Proposal { id: closed, status, .. }) => {
+                assert_eq!(closed, id);
+                assert_eq!(status, "passed");
+            }
+            other => panic!("expected closed proposal, got {:?}", other),
+        }
+        assert_eq!(state.store.get_proposal(&id).await.unwrap().unwrap().status, "passed");
+
+        // Closed proposals are left alone afterwards
+        execution::close_expired_proposals(&state, deadline + 1).await;
+        assert!(events.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_proposal_cooldown() {
+        let config = ServerConfig {
+            proposal_cooldown: std::time::Duration::from_millis(200),
+            ..ServerConfig::default()
+        };
+        let (state, _commands) = test_state_with_config(config).await;
+        let (mut session, mut reply_rx) = test_session(8);
+        let mut events = state.event_tx.subscribe();
+        let propose = || ClientMessage::CreateProposal {
+            title: "Title".to_string(),
+            description: "Description".to_string(),
+            proposal_type: ProposalKind::Text,
+            quorum: None,
+            quorum_mode: QuorumMode::Snapshot,
+            passing_rule: PassingRule::SimpleMajority,
+            deadline_secs: None,
+            correlation_id: None,
+        };
+
+        handle_client_message(propose(), &state, &mut session).await;
+        assert!(matches!(events.try_recv(), Ok(WsMessage::Proposal { .. })));
+
+        // A second proposal inside the cooldown is rejected
+        handle_client_message(propose(), &state, &mut session).await;
+        match reply_rx.try_recv() {
+            Ok(WsMessage::Error { code, message }) => {
+                assert_eq!(code, ErrorCode::RateLimited);
+                assert!(message.contains("remaining"));
+            }
+            other => panic!("expected cooldown error, got {:?}", other),
+        }
+        assert!(events.try_recv().is_err());
+
+        tokio::time::sleep(std::time::Duration::from_millis(250)).await;
+        handle_client_message(propose(), &state, &mut session).await;
+        assert!(matches!(events.try_recv(), Ok(WsMessage::Proposal { .. })));
+    }
+}
@@ -0,0 +1,102 @@
+//! Errors from handling a client message
+//!
+//! Client message handlers return a [`HandlerError`] when a request can't be
+//! carried out. The dispatcher turns it into a [`WsMessage::Error`] for the
+//! connection that sent the request, so handlers don't each build the reply.
+//!
+//! [`WsMessage::Error`]: super::messages::WsMessage::Error
+
+use std::fmt;
+
+use super::messages::ErrorCode;
+
+/// Why a client request failed, with the message shown to the client
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HandlerError {
+    /// The client exceeded a rate or resource limit
+    RateLimited(String),
+    /// The request was malformed or referenced something invalid
+    InvalidRequest(String),
+    /// The connection is not permitted to perform the request
+    Forbidden(String),
+    /// The request lacked a valid signature
+    Unauthorized(String),
+    /// Server-side failure; the message should not expose internals
+    Internal(String),
+}
+
+impl HandlerError {
+    pub fn rate_limited(message: impl Into<String>) -> Self {
+        HandlerError::RateLimited(message.into())
+    }
+
+    pub fn invalid(message: impl Into<String>) -> Self {
+        HandlerError::InvalidRequest(message.into())
+    }
+
+    pub fn forbidden(message: impl Into<String>) -> Self {
+        HandlerError::Forbidden(message.into())
+    }
+
+    pub fn unauthorized(message: impl Into<String>) -> Self {
+        HandlerError::Unauthorized(message.into())
+    }
+
+    pub fn internal(message: impl Into<String>) -> Self {
+        HandlerError::Internal(message.into())
+    }
+
+    /// Error code reported to the client
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            HandlerError::RateLimited(_) => ErrorCode::RateLimited,
+            HandlerError::InvalidRequest(_) => ErrorCode::InvalidRequest,
+            HandlerError::Forbidden(_) => ErrorCode::Forbidden,
+            HandlerError::Unauthorized(_) => ErrorCode::Unauthorized,
+            HandlerError::Internal(_) => ErrorCode::Internal,
+        }
+    }
+
+    /// Message reported to the client
+    pub fn message(&self) -> &str {
+        match self {
+            HandlerError::RateLimited(message)
+            | HandlerError::InvalidRequest(message)
+            | HandlerError::Forbidden(message)
+            | HandlerError::Unauthorized(message)
+            | HandlerError::Internal(message) => message,
+        }
+    }
+}
+
+impl fmt::Display for HandlerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}: {}", self.code(), self.message())
+    }
+}
+
+impl std::error::Error for HandlerError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_each_variant_maps_to_code_and_message() {
+        let cases = [
+            (HandlerError::rate_limited("Slow down"), ErrorCode::RateLimited),
+            (HandlerError::invalid("Slow down"), ErrorCode::InvalidRequest),
+            (HandlerError::forbidden("Slow down"), ErrorCode::Forbidden),
+            (HandlerError::unauthorized("Slow down"), ErrorCode::Unauthorized),
+            (HandlerError::internal("Slow down"), ErrorCode::Internal),
+        ];
+        for (error, code) in cases {
+            assert_eq!(error.code(), code);
+            assert_eq!(error.message(), "Slow down");
+        }
+        assert_eq!(
+            HandlerError::forbidden("Kick requires admin").to_string(),
+            "Forbidden: Kick requires admin"
+        );
+    }
+}
@@ -0,0 +1,72 @@
+//! Idle detection for WebSocket connections
+//!
+//! A client that disappears without a Close frame would otherwise keep its
+//! connection's tasks running until the next send fails. The server pings
+//! every connection periodically; any frame received, including the Pong,
+//! counts as activity, and a connection with no activity for the idle
+//! timeout is dropped.
+
+use parking_lot::Mutex;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// When a connection last received a frame
+#[derive(Debug, Clone)]
+pub struct Activity {
+    last_seen: Arc<Mutex<Instant>>,
+}
+
+impl Default for Activity {
+    fn default() -> Self {
+        Self {
+            last_seen: Arc::new(Mutex::new(Instant::now())),
+        }
+    }
+}
+
+impl Activity {
+    /// Record that a frame was received
+    pub fn touch(&self) {
+        *self.last_seen.lock() = Instant::now();
+    }
+
+    /// How long since a frame was last received
+    pub fn idle_for(&self) -> Duration {
+        self.last_seen.lock().elapsed()
+    }
+
+    /// Resolve once no frame has been received for `timeout`
+    pub async fn idle(&self, timeout: Duration) {
+        loop {
+            let deadline = *self.last_seen.lock() + timeout;
+            if Instant::now() >= deadline {
+                return;
+            }
+            tokio::time::sleep_until(deadline).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn test_activity_postpones_idle() {
+        let activity = Activity::default();
+        let started = Instant::now();
+
+        let watcher = activity.clone();
+        let idle = tokio::spawn(async move { watcher.idle(Duration::from_secs(90)).await });
+
+        // A frame (e.g. a Pong) at 60s pushes the deadline to 150s
+        tokio::time::sleep(Duration::from_secs(60)).await;
+        activity.touch();
+        tokio::time::sleep(Duration::from_secs(60)).await;
+        assert!(!idle.is_finished());
+
+        idle.await.unwrap();
+        assert_eq!(started.elapsed(), Duration::from_secs(150));
+    }
+}
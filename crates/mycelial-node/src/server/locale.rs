@@ -0,0 +1,107 @@
+//! Locale-aware display formatting
+//!
+//! Thin clients can ask the server to pre-format economics amounts for a
+//! locale. Formatted strings are added next to the raw numbers as
+//! `display_*` fields; the raw numeric fields stay authoritative.
+
+use serde_json::Value;
+
+/// Numeric fields that get a `display_<field>` companion when a locale is set
+const DISPLAY_FIELDS: &[&str] = &["amount", "limit", "balance"];
+
+/// Supported number-formatting conventions
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Locale {
+    /// `1,234.56`
+    En,
+    /// `1.234,56`
+    De,
+    /// `1 234,56`
+    Fr,
+}
+
+impl Locale {
+    /// Parse a BCP 47 tag such as `en-US` or `de`, matching on the language
+    pub fn parse(tag: &str) -> Option<Self> {
+        let language = tag.split(['-', '_']).next()?.to_ascii_lowercase();
+        match language.as_str() {
+            "en" => Some(Locale::En),
+            "de" => Some(Locale::De),
+            "fr" => Some(Locale::Fr),
+            _ => None,
+        }
+    }
+
+    fn separators(self) -> (char, char) {
+        match self {
+            Locale::En => (',', '.'),
+            Locale::De => ('.', ','),
+            Locale::Fr => ('\u{202f}', ','),
+        }
+    }
+
+    /// Format an amount with two decimals and grouping separators
+    pub fn format_amount(self, value: f64) -> String {
+        if !value.is_finite() {
+            return value.to_string();
+        }
+        let (group_sep, decimal_sep) = self.separators();
+        let fixed = format!("{:.2}", value.abs());
+        let (whole, fraction) = fixed.split_once('.').unwrap_or((fixed.as_str(), "00"));
+
+        let mut grouped = String::new();
+        for (i, digit) in whole.chars().enumerate() {
+            if i > 0 && (whole.len() - i) % 3 == 0 {
+                grouped.push(group_sep);
+            }
+            grouped.push(digit);
+        }
+
+        let sign = if value < 0.0 && fixed != "0.00" { "-" } else { "" };
+        format!("{}{}{}{}", sign, grouped, decimal_sep, fraction)
+    }
+
+    /// Add `display_*` strings next to the numeric amount fields of a message
+    pub fn add_display_fields(self, message: &mut Value) {
+        let Some(object) = message.as_object_mut() else {
+            return;
+        };
+        for field in DISPLAY_FIELDS {
+            if let Some(value) = object.get(*field).and_then(Value::as_f64) {
+                object.insert(
+                    format!("display_{}", field),
+                    Value::String(self.format_amount(value)),
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_amount() {
+        assert_eq!(Locale::En.format_amount(1234567.891), "1,234,567.89");
+        assert_eq!(Locale::De.format_amount(1234.5), "1.234,50");
+        assert_eq!(Locale::Fr.format_amount(-1234.5), "-1\u{202f}234,50");
+        assert_eq!(Locale::En.format_amount(12.0), "12.00");
+        assert_eq!(Locale::parse("de-DE"), Some(Locale::De));
+        assert_eq!(Locale::parse("xx"), None);
+    }
+
+    #[test]
+    fn test_display_fields_keep_raw_values() {
+        let mut message = serde_json::json!({
+            "type": "credit_transfer",
+            "amount": 1234.5,
+            "memo": null,
+        });
+        Locale::De.add_display_fields(&mut message);
+
+        assert_eq!(message["display_amount"], "1.234,50");
+        assert_eq!(message["amount"], 1234.5);
+        assert!(message.get("display_limit").is_none());
+    }
+}
@@ -5,12 +5,121 @@
 //! governance, resource).
 
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
 use mycelial_core::peer::PeerInfo;
+use mycelial_protocol::{PassingRule, QuorumMode};
+use mycelial_state::VoteTally;
+
+use crate::proposal_types::ProposalKind;
+use super::schema::MessageCatalog;
+
+/// Version of the client/server protocol, sent in `Hello`
+///
+/// Bumped on incompatible changes; additions are advertised in [`FEATURES`].
+/// Version 2 made `create_proposal`'s `proposal_type` a [`ProposalKind`],
+/// which carries the proposal's parameters.
+pub const PROTOCOL_VERSION: u32 = 2;
+
+/// Client message types and capabilities this server supports, sent in `Hello`
+/// so clients can feature-detect
+pub const FEATURES: &[&str] = &[
+    "send_chat",
+    "edit_chat",
+    "delete_chat",
+    "react_chat",
+    "mark_read",
+    "typing",
+    "identify",
+    "set_nickname",
+    "resume",
+    "ping",
+    "get_unread_counts",
+    "mark_seen",
+    "get_peers",
+    "get_chat_history",
+    "get_peer",
+    "get_peers_bulk",
+    "search_peers",
+    "get_stats",
+    "get_stats_history",
+    "subscribe",
+    "subscribe_many",
+    "unsubscribe",
+    "set_capability",
+    "set_locale",
+    "set_event_filter",
+    "authenticate_admin",
+    "kick",
+    "request_sync",
+    "get_config",
+    "signed",
+    "get_connection_stats",
+    "get_connections",
+    "describe_protocol",
+    "whoami",
+    "send_vouch",
+    "respond_vouch",
+    "revoke_vouch",
+    "create_credit_line",
+    "get_credit_lines",
+    "get_credit_history",
+    "transfer_credit",
+    "request_payment",
+    "create_proposal",
+    "amend_proposal",
+    "cancel_proposal",
+    "cast_vote",
+    "finalize_proposal",
+    "get_proposals",
+    "get_governance_stats",
+    "get_governance_summary",
+    "get_vouch_stats",
+    "get_pending_vouches",
+    "get_my_vouches",
+    "replay_for_peer",
+    "report_resource",
+    "get_resource_contributors",
+    "get_peer_resource_history",
+    "create_room",
+    "join_room",
+    "leave_room",
+    "get_rooms",
+    "capability:raw_protocol",
+    "capability:propagation",
+    "capability:collapse_updates",
+    "capability:peer_deltas",
+    "capability:timestamp_iso",
+    "capability:compress_gzip",
+    "encoding:msgpack",
+];
 
 /// Messages sent from server to client
 #[derive(Debug, Clone, Serialize)]
 #[serde(tag = "type", rename_all = "snake_case")]
 pub enum WsMessage {
+    /// First message on every connection, identifying the server and its protocol
+    Hello {
+        protocol_version: u32,
+        node_name: String,
+        peer_id: String,
+        /// Supported client message types and capabilities
+        features: Vec<String>,
+    },
+
+    /// Outcome of a client message that carried a `client_ref`
+    Ack {
+        client_ref: String,
+        ok: bool,
+        error: Option<String>,
+    },
+
+    /// Reply to a client `Ping`, for measuring latency and clock skew
+    Pong {
+        nonce: String,
+        /// When the server handled the ping (epoch millis)
+        server_time: i64,
+    },
+
     /// A peer joined the network
     PeerJoined {
         peer_id: String,
@@ -22,6 +131,13 @@ pub enum WsMessage {
         peer_id: String,
     },
 
+    /// Peers that joined or left within a short window, for connections
+    /// with the `peer_deltas` capability
+    PeerDelta {
+        added: Vec<PeerListEntry>,
+        removed: Vec<String>,
+    },
+
     /// A chat message was received
     ChatMessage {
         id: String,
@@ -33,37 +149,279 @@ pub enum WsMessage {
         timestamp: i64,
     },
 
+    /// The author of a chat message changed its content; `to` is set when
+    /// the message was sent directly to one peer
+    ChatEdited {
+        message_id: String,
+        from: String,
+        to: Option<String>,
+        content: String,
+        edited_at: i64,
+    },
+
+    /// The author of a chat message retracted it; `to` is set when the
+    /// message was sent directly to one peer
+    ChatDeleted {
+        message_id: String,
+        from: String,
+        to: Option<String>,
+        deleted_at: i64,
+    },
+
+    /// A peer added or took back a reaction to a chat message
+    ChatReaction {
+        message_id: String,
+        reactor: String,
+        /// The other party when the message was sent directly to one peer
+        to: Option<String>,
+        emoji: String,
+        added: bool,
+    },
+
+    /// The recipient of a direct message has read it; only its sender is told
+    ReadReceipt {
+        message_id: String,
+        reader: String,
+        /// Sender of the message
+        to: String,
+        /// When the message was first read (epoch millis)
+        timestamp: i64,
+    },
+
+    /// A peer is typing, to everyone or to `to` only; relayed live, never logged
+    TypingIndicator {
+        from: String,
+        from_name: String,
+        to: Option<String>,
+        timestamp: i64,
+    },
+
     /// A peer's reputation was updated
     ReputationUpdate {
         peer_id: String,
         new_score: f64,
     },
 
+    /// Sent once a connection is set up, with the token for resuming it later
+    ConnectionReady {
+        connection_id: String,
+        /// Last event log sequence at connect
+        last_seq: i64,
+        /// Token to present in `Resume` after reconnecting
+        resume_token: String,
+        /// When the token expires (epoch millis)
+        expires_at: i64,
+    },
+
+    /// Items newer than the local identity's seen markers
+    UnreadCounts {
+        chat: usize,
+        proposals: usize,
+        vouches: usize,
+    },
+
+    /// Logged events missed since the resumed connection's token was issued
+    Resumed {
+        /// Connection the token was issued to
+        resumed_from: String,
+        events: Vec<ReplayedEvent>,
+    },
+
+    /// The connection can't be resumed; the client should refetch all state
+    Resync {
+        reason: String,
+    },
+
+    /// The node is shutting down and is about to close the connection
+    Shutdown {
+        reason: String,
+        /// Suggested delay before reconnecting
+        reconnect_after_ms: u64,
+    },
+
     /// Full list of peers
     PeersList {
         peers: Vec<PeerListEntry>,
     },
 
+    /// A page of past chat messages, newest first
+    ChatHistory {
+        messages: Vec<ChatHistoryEntry>,
+        /// Whether older messages exist beyond this page
+        has_more: bool,
+    },
+
+    /// Metadata for a requested set of peers
+    PeersBulk {
+        /// Known peers, in request order
+        entries: Vec<PeerListEntry>,
+        /// Requested IDs with no stored peer
+        unknown: Vec<String>,
+    },
+
+    /// Full detail for one peer
+    PeerDetail {
+        id: String,
+        name: Option<String>,
+        display_name: String,
+        public_key: String,
+        addresses: Vec<String>,
+        first_seen: i64,
+        last_seen: i64,
+        reputation: f64,
+        successful_interactions: u64,
+        failed_interactions: u64,
+        /// Vouches the peer gave or received, newest first
+        vouches: Vec<PeerVouchEntry>,
+    },
+
+    /// The node lost or regained connectivity to the gossip network
+    NetworkStatus {
+        isolated: bool,
+        connected_peers: usize,
+    },
+
+    /// A network operation failed, reported at most once per interval per operation
+    NetworkError {
+        /// Operation that failed, e.g. `publish` or `subscribe`
+        operation: String,
+        detail: String,
+        /// Whether retrying may succeed without restarting the node
+        recoverable: bool,
+    },
+
     /// Network statistics
     Stats {
         peer_count: usize,
         message_count: u64,
+        /// Messages published or received on each topic
+        topic_counts: HashMap<String, u64>,
         uptime_seconds: u64,
+        /// Events queued for the slowest client
+        broadcast_queue_depth: usize,
+        /// Deepest the queue has been since startup
+        broadcast_high_water: usize,
+        /// Events the queue holds before slow clients miss some
+        broadcast_capacity: usize,
+        /// Open WebSocket connections
+        connection_count: usize,
+    },
+
+    /// Downsampled stats history, oldest first
+    StatsHistory {
+        interval_secs: u64,
+        points: Vec<StatsPoint>,
+    },
+
+    /// All messages up to `seq` on this connection have been delivered
+    Checkpoint {
+        seq: u64,
+        timestamp: i64,
     },
 
     /// Error message
     Error {
+        code: ErrorCode,
+        message: String,
+    },
+
+    /// An integrity concern for operators
+    ///
+    /// Only delivered to connections authenticated as admin.
+    Warning {
+        kind: WarningKind,
         message: String,
     },
 
+    /// Outcome of a client's topic subscription
+    SubscribeResult {
+        topic: String,
+        success: bool,
+        error: Option<String>,
+    },
+
+    /// The local node was renamed
+    NodeRenamed {
+        name: String,
+    },
+
+    /// Result of an unsubscribe request
+    UnsubscribeResult {
+        topic: String,
+        success: bool,
+        error: Option<String>,
+    },
+
+    /// Per-topic outcomes of a bulk subscription, in request order
+    SubscribeManyResult {
+        results: Vec<SubscribeResultEntry>,
+    },
+
+    /// Result of an admin kick: whether the connection existed
+    KickResult {
+        connection_id: String,
+        existed: bool,
+    },
+
+    /// The node's runtime configuration, with secrets redacted
+    ConfigSnapshot {
+        config: serde_json::Value,
+    },
+
+    /// Bytes sent to a connection before and after wire encoding
+    ///
+    /// `compression_ratio` is `bytes_after / bytes_before`; below 1.0 means
+    /// encoding settings are saving bandwidth.
+    ConnectionStats {
+        connection_id: String,
+        messages_sent: u64,
+        bytes_before: u64,
+        bytes_after: u64,
+        compression_ratio: f64,
+        /// Frames waiting to be written to the connection
+        queue_depth: usize,
+        /// Snapshots dropped because the connection fell behind
+        frames_dropped: u64,
+    },
+
+    /// Description of every client and server message
+    ProtocolSchema {
+        messages: MessageCatalog,
+    },
+
+    /// The local node's identity
+    Identity {
+        peer_id: String,
+        node_name: String,
+        /// Addresses the node's P2P network is listening on
+        addresses: Vec<String>,
+    },
+
+    /// Open WebSocket connections, oldest first
+    Connections {
+        clients: Vec<ConnectionEntry>,
+    },
+
+    /// Progress of a governance sync: sent once when the request goes out
+    /// (`responder` unset) and again for each peer response applied
+    SyncProgress {
+        request_id: String,
+        responder: Option<String>,
+        proposals_added: usize,
+        votes_added: usize,
+    },
+
     // ============ Economics Protocol Messages ============
 
     /// Vouch request received
     VouchRequest {
         id: String,
         voucher: String,
+        voucher_name: String,
         vouchee: String,
+        vouchee_name: String,
         weight: f64,
+        correlation_id: Option<String>,
         timestamp: i64,
     },
 
@@ -73,6 +431,14 @@ pub enum WsMessage {
         request_id: String,
         accepted: bool,
         new_reputation: Option<f64>,
+        correlation_id: Option<String>,
+        timestamp: i64,
+    },
+
+    /// A voucher withdrew its vouches for a peer
+    VouchRevoked {
+        voucher: String,
+        vouchee: String,
         timestamp: i64,
     },
 
@@ -83,16 +449,44 @@ pub enum WsMessage {
         debtor: String,
         limit: f64,
         balance: f64,
+        correlation_id: Option<String>,
         timestamp: i64,
     },
 
+    /// Credit lines this node extended or received
+    CreditLinesList {
+        lines: Vec<CreditLineEntry>,
+    },
+
+    /// Transfers drawn on a credit line, oldest first
+    CreditHistory {
+        line_id: String,
+        transfers: Vec<CreditTransferEntry>,
+        current_balance: f64,
+    },
+
     /// Credit transfer completed
     CreditTransfer {
         id: String,
         from: String,
+        from_name: String,
         to: String,
+        to_name: String,
+        amount: f64,
+        memo: Option<String>,
+        request_ref: Option<String>,
+        correlation_id: Option<String>,
+        timestamp: i64,
+    },
+
+    /// A peer asked another peer for payment
+    PaymentRequest {
+        id: String,
+        requester: String,
+        payer: String,
         amount: f64,
         memo: Option<String>,
+        correlation_id: Option<String>,
         timestamp: i64,
     },
 
@@ -100,24 +494,105 @@ pub enum WsMessage {
     Proposal {
         id: String,
         proposer: String,
+        proposer_name: String,
         title: String,
         description: String,
         proposal_type: String,
+        /// Parameters of the proposal type
+        #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+        parameters: BTreeMap<String, String>,
         status: String,
         yes_votes: u32,
         no_votes: u32,
         quorum: u32,
         deadline: i64,
+        version: u32,
+        correlation_id: Option<String>,
         timestamp: i64,
     },
 
+    /// All known proposals, newest first
+    Proposals {
+        proposals: Vec<ProposalEntry>,
+    },
+
+    /// A passed proposal was carried out
+    ProposalExecuted {
+        proposal_id: String,
+        /// Whether execution succeeded
+        success: bool,
+        /// What was done, or why it failed
+        outcome: String,
+    },
+
+    /// Votes so far against the number currently needed for quorum
+    QuorumProgress {
+        proposal_id: String,
+        voters: usize,
+        required: u32,
+        eligible: usize,
+        quorum_mode: QuorumMode,
+    },
+
+    /// A proposal is approaching its voting deadline
+    ProposalReminder {
+        proposal_id: String,
+        closes_in_secs: u64,
+        current_tally: TallyEntry,
+    },
+
+    /// Aggregate governance participation metrics
+    GovernanceStats {
+        active_proposals: usize,
+        avg_turnout: f64,
+        participating_peers: usize,
+        total_peers: usize,
+    },
+
+    /// Proposal counts by current status, with votes cast overall and by this node
+    GovernanceSummary {
+        active: usize,
+        passed: usize,
+        rejected: usize,
+        total_votes: usize,
+        my_votes: usize,
+    },
+
+    /// Trust-network overview aggregated from stored vouches
+    VouchStats {
+        total_vouches: usize,
+        avg_weight: f64,
+        most_vouched_peers: Vec<VouchedPeerEntry>,
+        pending_count: usize,
+    },
+
+    /// Vouch requests awaiting this node's response, newest first
+    PendingVouches {
+        requests: Vec<VouchRequestEntry>,
+    },
+
+    /// Vouches this node gave and received, newest first
+    VouchList {
+        given: Vec<VouchEntry>,
+        received: Vec<VouchEntry>,
+    },
+
+    /// Logged events involving a peer, replayed on request
+    PeerReplay {
+        peer_id: String,
+        events: Vec<ReplayedEvent>,
+        has_more: bool,
+    },
+
     /// Vote cast on a proposal
     VoteCast {
         id: String,
         proposal_id: String,
         voter: String,
+        voter_name: String,
         vote: String,
         weight: f64,
+        correlation_id: Option<String>,
         timestamp: i64,
     },
 
@@ -128,6 +603,7 @@ pub enum WsMessage {
         resource_type: String,
         amount: f64,
         unit: String,
+        correlation_id: Option<String>,
         timestamp: i64,
     },
 
@@ -140,6 +616,38 @@ pub enum WsMessage {
         timestamp: i64,
     },
 
+    /// An inbound economics message as received, for protocol debugging
+    ///
+    /// Only delivered to connections with [`Capability::RawProtocol`] enabled.
+    RawProtocol {
+        topic: String,
+        json: serde_json::Value,
+    },
+
+    /// How many peers a locally published message was sent to
+    ///
+    /// Only delivered to connections with [`Capability::Propagation`] enabled.
+    Propagation {
+        message_id: String,
+        topic: String,
+        peer_count: usize,
+    },
+
+    /// Ranked contributors to a resource type
+    ResourceContributors {
+        resource_type: String,
+        contributors: Vec<ContributorEntry>,
+        total_contributors: usize,
+        has_more: bool,
+    },
+
+    /// A peer's resource contributions over time, oldest first
+    PeerResourceHistory {
+        peer_id: String,
+        contributions: Vec<ResourceHistoryEntry>,
+        has_more: bool,
+    },
+
     // ============ Room/Seance Messages ============
 
     /// Successfully joined a room
@@ -152,6 +660,7 @@ pub enum WsMessage {
         created_by: String,
         created_at: i64,
         is_public: bool,
+        ephemeral: bool,
     },
 
     /// Left a room
@@ -178,6 +687,393 @@ pub enum WsMessage {
     },
 }
 
+impl WsMessage {
+    /// The `type` tag the message is serialized with, e.g. `"chat_message"`
+    pub fn type_tag(&self) -> &'static str {
+        match self {
+            WsMessage::Hello { .. } => "hello",
+            WsMessage::Ack { .. } => "ack",
+            WsMessage::Pong { .. } => "pong",
+            WsMessage::PeerJoined { .. } => "peer_joined",
+            WsMessage::PeerLeft { .. } => "peer_left",
+            WsMessage::PeerDelta { .. } => "peer_delta",
+            WsMessage::ChatMessage { .. } => "chat_message",
+            WsMessage::ChatEdited { .. } => "chat_edited",
+            WsMessage::ChatDeleted { .. } => "chat_deleted",
+            WsMessage::ChatReaction { .. } => "chat_reaction",
+            WsMessage::ReadReceipt { .. } => "read_receipt",
+            WsMessage::TypingIndicator { .. } => "typing_indicator",
+            WsMessage::ReputationUpdate { .. } => "reputation_update",
+            WsMessage::ConnectionReady { .. } => "connection_ready",
+            WsMessage::UnreadCounts { .. } => "unread_counts",
+            WsMessage::Resumed { .. } => "resumed",
+            WsMessage::Resync { .. } => "resync",
+            WsMessage::Shutdown { .. } => "shutdown",
+            WsMessage::PeersList { .. } => "peers_list",
+            WsMessage::ChatHistory { .. } => "chat_history",
+            WsMessage::PeersBulk { .. } => "peers_bulk",
+            WsMessage::PeerDetail { .. } => "peer_detail",
+            WsMessage::NetworkStatus { .. } => "network_status",
+            WsMessage::NetworkError { .. } => "network_error",
+            WsMessage::Stats { .. } => "stats",
+            WsMessage::StatsHistory { .. } => "stats_history",
+            WsMessage::Checkpoint { .. } => "checkpoint",
+            WsMessage::Error { .. } => "error",
+            WsMessage::Warning { .. } => "warning",
+            WsMessage::SubscribeResult { .. } => "subscribe_result",
+            WsMessage::NodeRenamed { .. } => "node_renamed",
+            WsMessage::UnsubscribeResult { .. } => "unsubscribe_result",
+            WsMessage::SubscribeManyResult { .. } => "subscribe_many_result",
+            WsMessage::KickResult { .. } => "kick_result",
+            WsMessage::ConfigSnapshot { .. } => "config_snapshot",
+            WsMessage::ConnectionStats { .. } => "connection_stats",
+            WsMessage::ProtocolSchema { .. } => "protocol_schema",
+            WsMessage::Identity { .. } => "identity",
+            WsMessage::Connections { .. } => "connections",
+            WsMessage::SyncProgress { .. } => "sync_progress",
+            WsMessage::VouchRequest { .. } => "vouch_request",
+            WsMessage::VouchAck { .. } => "vouch_ack",
+            WsMessage::VouchRevoked { .. } => "vouch_revoked",
+            WsMessage::CreditLine { .. } => "credit_line",
+            WsMessage::CreditLinesList { .. } => "credit_lines_list",
+            WsMessage::CreditHistory { .. } => "credit_history",
+            WsMessage::CreditTransfer { .. } => "credit_transfer",
+            WsMessage::PaymentRequest { .. } => "payment_request",
+            WsMessage::Proposal { .. } => "proposal",
+            WsMessage::Proposals { .. } => "proposals",
+            WsMessage::ProposalExecuted { .. } => "proposal_executed",
+            WsMessage::QuorumProgress { .. } => "quorum_progress",
+            WsMessage::ProposalReminder { .. } => "proposal_reminder",
+            WsMessage::GovernanceStats { .. } => "governance_stats",
+            WsMessage::GovernanceSummary { .. } => "governance_summary",
+            WsMessage::VouchStats { .. } => "vouch_stats",
+            WsMessage::PendingVouches { .. } => "pending_vouches",
+            WsMessage::VouchList { .. } => "vouch_list",
+            WsMessage::PeerReplay { .. } => "peer_replay",
+            WsMessage::VoteCast { .. } => "vote_cast",
+            WsMessage::ResourceContribution { .. } => "resource_contribution",
+            WsMessage::ResourcePoolUpdate { .. } => "resource_pool_update",
+            WsMessage::RawProtocol { .. } => "raw_protocol",
+            WsMessage::Propagation { .. } => "propagation",
+            WsMessage::ResourceContributors { .. } => "resource_contributors",
+            WsMessage::PeerResourceHistory { .. } => "peer_resource_history",
+            WsMessage::RoomJoined { .. } => "room_joined",
+            WsMessage::RoomLeft { .. } => "room_left",
+            WsMessage::RoomList { .. } => "room_list",
+            WsMessage::RoomPeerJoined { .. } => "room_peer_joined",
+            WsMessage::RoomPeerLeft { .. } => "room_peer_left",
+        }
+    }
+
+    /// Peers an event involves, used to build per-peer timelines
+    ///
+    /// Snapshots and replies (peer lists, stats, errors) involve no peers.
+    pub fn involved_peers(&self) -> Vec<String> {
+        match self {
+            WsMessage::PeerJoined { peer_id, .. }
+            | WsMessage::PeerLeft { peer_id }
+            | WsMessage::ReputationUpdate { peer_id, .. }
+            | WsMessage::ResourceContribution { peer_id, .. }
+            | WsMessage::PeerResourceHistory { peer_id, .. }
+            | WsMessage::RoomPeerJoined { peer_id, .. }
+            | WsMessage::RoomPeerLeft { peer_id, .. } => vec![peer_id.clone()],
+            WsMessage::ReadReceipt { reader, to, .. } => vec![reader.clone(), to.clone()],
+            WsMessage::ChatMessage { from, to, .. }
+            | WsMessage::ChatReaction { reactor: from, to, .. }
+            | WsMessage::ChatEdited { from, to, .. }
+            | WsMessage::ChatDeleted { from, to, .. } => {
+                let mut peers = vec![from.clone()];
+                peers.extend(to.clone());
+                peers
+            }
+            WsMessage::VouchRequest { voucher, vouchee, .. }
+            | WsMessage::VouchRevoked { voucher, vouchee, .. } => {
+                vec![voucher.clone(), vouchee.clone()]
+            }
+            WsMessage::CreditLine { creditor, debtor, .. } => {
+                vec![creditor.clone(), debtor.clone()]
+            }
+            WsMessage::CreditTransfer { from, to, .. } => vec![from.clone(), to.clone()],
+            WsMessage::PaymentRequest { requester, payer, .. } => {
+                vec![requester.clone(), payer.clone()]
+            }
+            WsMessage::Proposal { proposer, .. } => vec![proposer.clone()],
+            WsMessage::VoteCast { voter, .. } => vec![voter.clone()],
+            WsMessage::ResourcePoolUpdate { contributors, .. }
+            | WsMessage::ResourceContributors { contributors, .. } => {
+                contributors.iter().map(|c| c.peer_id.clone()).collect()
+            }
+            WsMessage::RoomJoined { members, created_by, .. } => {
+                let mut peers = members.clone();
+                peers.push(created_by.clone());
+                peers
+            }
+            WsMessage::VouchAck { .. }
+            | WsMessage::ConnectionReady { .. }
+            | WsMessage::Resumed { .. }
+            | WsMessage::UnreadCounts { .. }
+            | WsMessage::Resync { .. }
+            | WsMessage::Shutdown { .. }
+            | WsMessage::PeersList { .. }
+            | WsMessage::PeerDelta { .. }
+            | WsMessage::ProtocolSchema { .. }
+            | WsMessage::Identity { .. }
+            | WsMessage::PeersBulk { .. }
+            | WsMessage::PeerDetail { .. }
+            | WsMessage::CreditLinesList { .. }
+            | WsMessage::CreditHistory { .. }
+            | WsMessage::PendingVouches { .. }
+            | WsMessage::VouchList { .. }
+            | WsMessage::ChatHistory { .. }
+            | WsMessage::ProposalExecuted { .. }
+            | WsMessage::Proposals { .. }
+            | WsMessage::NetworkStatus { .. }
+            | WsMessage::Stats { .. }
+            | WsMessage::StatsHistory { .. }
+            | WsMessage::Checkpoint { .. }
+            | WsMessage::Error { .. }
+            | WsMessage::ProposalReminder { .. }
+            | WsMessage::QuorumProgress { .. }
+            | WsMessage::GovernanceStats { .. }
+            | WsMessage::GovernanceSummary { .. }
+            | WsMessage::VouchStats { .. }
+            | WsMessage::PeerReplay { .. }
+            | WsMessage::RawProtocol { .. }
+            | WsMessage::Propagation { .. }
+            | WsMessage::NetworkError { .. }
+            | WsMessage::RoomLeft { .. }
+            | WsMessage::RoomList { .. }
+            | WsMessage::Warning { .. }
+            | WsMessage::SubscribeResult { .. }
+            | WsMessage::SubscribeManyResult { .. }
+            | WsMessage::UnsubscribeResult { .. }
+            | WsMessage::NodeRenamed { .. }
+            | WsMessage::Hello { .. }
+            | WsMessage::Ack { .. }
+            | WsMessage::Pong { .. }
+            | WsMessage::TypingIndicator { .. }
+            | WsMessage::KickResult { .. }
+            | WsMessage::ConnectionStats { .. }
+            | WsMessage::Connections { .. }
+            | WsMessage::ConfigSnapshot { .. }
+            | WsMessage::SyncProgress { .. } => Vec::new(),
+        }
+    }
+}
+
+/// Machine-readable category of a [`WsMessage::Error`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    /// The client exceeded a rate or resource limit
+    RateLimited,
+    /// The request was malformed or referenced something invalid
+    InvalidRequest,
+    /// The connection is not permitted to perform the request
+    Forbidden,
+    /// The request lacked a valid signature or credential, or the connection
+    /// was disconnected by an operator
+    Unauthorized,
+    /// Server-side failure
+    Internal,
+}
+
+/// Which side of a credit line this node is on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CreditDirection {
+    /// This node is the creditor
+    Extended,
+    /// This node is the debtor
+    Received,
+}
+
+/// An open connection in a [`WsMessage::Connections`] list
+#[derive(Debug, Clone, Serialize)]
+pub struct ConnectionEntry {
+    pub connection_id: String,
+    /// Peer the connection acts for, once identified
+    pub peer_id: Option<String>,
+    /// When the connection was opened, in milliseconds since the epoch
+    pub connected_at: i64,
+    /// When a frame was last received from the connection, in milliseconds since the epoch
+    pub last_activity: i64,
+    /// Frames waiting to be written to the connection
+    pub queue_depth: usize,
+}
+
+/// Entry in the credit lines list
+#[derive(Debug, Clone, Serialize)]
+pub struct CreditLineEntry {
+    pub id: String,
+    pub creditor: String,
+    pub debtor: String,
+    pub limit: f64,
+    pub balance: f64,
+    pub direction: CreditDirection,
+}
+
+/// Entry in a credit line's transfer history
+#[derive(Debug, Clone, Serialize)]
+pub struct CreditTransferEntry {
+    pub id: String,
+    pub from: String,
+    pub to: String,
+    pub amount: f64,
+    pub memo: Option<String>,
+    pub timestamp: i64,
+    /// Line balance once this transfer was drawn
+    pub running_balance: f64,
+}
+
+/// Category of a [`WsMessage::Warning`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WarningKind {
+    /// Many low-reputation identities voted the same way on a proposal
+    PossibleSybil,
+}
+
+/// Categories tracked for unread counts
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UnreadCategory {
+    Chat,
+    Proposals,
+    Vouches,
+}
+
+impl UnreadCategory {
+    /// Category name used for stored seen markers
+    pub fn label(self) -> &'static str {
+        match self {
+            UnreadCategory::Chat => "chat",
+            UnreadCategory::Proposals => "proposals",
+            UnreadCategory::Vouches => "vouches",
+        }
+    }
+}
+
+/// Opt-in per-connection capabilities
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Capability {
+    /// Forward undigested inbound economics messages as `RawProtocol`
+    RawProtocol,
+    /// Report how many peers each published message reached as `Propagation`
+    Propagation,
+    /// Collapse backlogged updates to the latest per entity while behind
+    CollapseUpdates,
+    /// Receive membership changes batched as `PeerDelta` instead of
+    /// individual `PeerJoined` and `PeerLeft` events
+    PeerDeltas,
+    /// Add an RFC 3339 `<field>_iso` string next to each epoch-millisecond time
+    TimestampIso,
+    /// Gzip large frames into binary frames
+    CompressGzip,
+}
+
+/// A proposal with its current tallies and status
+#[derive(Debug, Clone, Serialize)]
+pub struct ProposalEntry {
+    pub id: String,
+    pub proposer: String,
+    pub proposer_name: String,
+    pub title: String,
+    pub description: String,
+    pub proposal_type: String,
+    /// Parameters of the proposal type
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub parameters: BTreeMap<String, String>,
+    pub status: String,
+    pub yes_votes: u32,
+    pub no_votes: u32,
+    pub quorum: u32,
+    pub deadline: i64,
+    pub version: u32,
+}
+
+/// Weighted vote totals for a proposal
+#[derive(Debug, Clone, Serialize)]
+pub struct TallyEntry {
+    pub yes: f64,
+    pub no: f64,
+    pub abstain: f64,
+    pub voters: usize,
+}
+
+impl From<VoteTally> for TallyEntry {
+    fn from(tally: VoteTally) -> Self {
+        Self {
+            yes: tally.yes,
+            no: tally.no,
+            abstain: tally.abstain,
+            voters: tally.voters,
+        }
+    }
+}
+
+/// A point in a stats history series
+#[derive(Debug, Clone, Serialize)]
+pub struct StatsPoint {
+    /// When the sample was taken (epoch millis)
+    pub timestamp: i64,
+    pub peer_count: usize,
+    /// Total messages received since the node started
+    pub message_count: u64,
+    /// Messages per second since the previous point
+    pub message_rate: f64,
+}
+
+/// A logged event returned by a replay request
+#[derive(Debug, Clone, Serialize)]
+pub struct ReplayedEvent {
+    /// Sequence number in the event log
+    pub seq: i64,
+    /// When the event was logged (epoch millis)
+    pub timestamp: i64,
+    /// The original event message
+    pub event: serde_json::Value,
+}
+
+/// A past chat message, as it was broadcast
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChatHistoryEntry {
+    /// Position in the event log; with `timestamp`, the cursor for older pages
+    #[serde(default)]
+    pub seq: i64,
+    pub id: String,
+    pub from: String,
+    pub from_name: String,
+    pub to: Option<String>,
+    pub room_id: Option<String>,
+    pub content: String,
+    pub timestamp: i64,
+    /// When the author last edited or deleted the message
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub edited_at: Option<i64>,
+    /// Whether the author deleted the message; its content is then empty
+    #[serde(default)]
+    pub deleted: bool,
+    /// Number of reactions per emoji
+    #[serde(default)]
+    pub reactions: BTreeMap<String, u64>,
+    /// When the recipient of a direct message read it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub read_at: Option<i64>,
+}
+
+/// A vouch given or received by a peer
+#[derive(Debug, Clone, Serialize)]
+pub struct PeerVouchEntry {
+    pub id: String,
+    pub voucher: String,
+    pub vouchee: String,
+    pub weight: f64,
+    pub status: String,
+    pub created_at: i64,
+}
+
 /// Entry in the peers list
 #[derive(Debug, Clone, Serialize)]
 pub struct PeerListEntry {
@@ -206,6 +1102,57 @@ pub struct ContributorEntry {
     pub percentage: f64,
 }
 
+/// Outcome of subscribing to one topic of a bulk subscription
+#[derive(Debug, Clone, Serialize)]
+pub struct SubscribeResultEntry {
+    pub topic: String,
+    pub success: bool,
+    pub error: Option<String>,
+}
+
+/// A single contribution in a peer's resource history
+#[derive(Debug, Clone, Serialize)]
+pub struct ResourceHistoryEntry {
+    pub id: String,
+    pub resource_type: String,
+    pub amount: f64,
+    pub unit: String,
+    pub timestamp: i64,
+}
+
+/// A peer ranked by inbound accepted vouch weight
+#[derive(Debug, Clone, Serialize)]
+pub struct VouchedPeerEntry {
+    pub peer_id: String,
+    pub total_weight: f64,
+}
+
+/// A vouch request awaiting a response
+#[derive(Debug, Clone, Serialize)]
+pub struct VouchRequestEntry {
+    pub id: String,
+    pub voucher: String,
+    pub voucher_name: String,
+    pub weight: f64,
+    pub message: Option<String>,
+    pub created_at: i64,
+}
+
+/// A vouch this node gave or received
+#[derive(Debug, Clone, Serialize)]
+pub struct VouchEntry {
+    pub id: String,
+    pub voucher: String,
+    pub vouchee: String,
+    pub weight: f64,
+    /// Whether the vouchee accepted the vouch and it still stands
+    pub accepted: bool,
+    /// pending, accepted, rejected, or revoked
+    pub status: String,
+    /// When the vouch was made (epoch millis)
+    pub timestamp: i64,
+}
+
 /// Entry for room list
 #[derive(Debug, Clone, Serialize)]
 pub struct RoomEntry {
@@ -217,28 +1164,232 @@ pub struct RoomEntry {
     pub created_at: i64,
 }
 
+/// A credential sent by a client, logged as `<redacted>`
+#[derive(Clone, Deserialize)]
+#[serde(transparent)]
+pub struct Secret(String);
+
+impl Secret {
+    pub fn new(value: impl Into<String>) -> Self {
+        Self(value.into())
+    }
+
+    /// The credential itself, for checking it
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+impl std::fmt::Debug for Secret {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("<redacted>")
+    }
+}
+
 /// Messages sent from client to server
 #[derive(Debug, Deserialize)]
 #[serde(tag = "type", rename_all = "snake_case")]
 pub enum ClientMessage {
     /// Send a chat message
+    ///
+    /// A message to a room joins the connection to it. Room IDs are limited
+    /// to letters, digits, `-` and `_` so they can't alter the topic.
     SendChat {
         content: String,
         to: Option<String>,
+        #[serde(alias = "room")]
         room_id: Option<String>,
     },
 
+    /// Replace the content of a chat message this node sent
+    EditChat {
+        message_id: String,
+        content: String,
+    },
+
+    /// Retract a chat message this node sent
+    DeleteChat {
+        message_id: String,
+    },
+
+    /// Toggle this node's reaction to a chat message
+    ReactChat {
+        message_id: String,
+        emoji: String,
+    },
+
+    /// Mark a direct message to this node read, notifying its sender
+    ///
+    /// Messages not sent directly to this node are ignored.
+    MarkRead {
+        message_id: String,
+    },
+
+    /// Tell others this connection is typing, to everyone or to one peer
+    Typing {
+        #[serde(default)]
+        to: Option<String>,
+    },
+
+    /// Rename the local node
+    SetNickname {
+        name: String,
+    },
+
+    /// Act for a peer, receiving only direct messages to or from it
+    ///
+    /// Connections act for the local node until they identify otherwise.
+    /// Identifying as any other peer needs `signature`: the peer's
+    /// hex-encoded signature over `mycelial-identify:<connection_id>`, with
+    /// the ID from `connection_ready`.
+    Identify {
+        peer_id: String,
+        #[serde(default)]
+        signature: Option<String>,
+    },
+
+    /// Resume from where an earlier connection left off
+    Resume {
+        /// Token from that connection's `ConnectionReady`
+        token: String,
+    },
+
+    /// Application-level ping, answered with a `Pong` echoing the nonce
+    Ping {
+        nonce: String,
+    },
+
+    /// Request unread counts per category
+    GetUnreadCounts,
+
+    /// Mark a category read up to a time, clearing its unread count
+    MarkSeen {
+        category: UnreadCategory,
+        /// Items at or before this time (epoch millis) are read
+        up_to: i64,
+    },
+
     /// Request peer list
     GetPeers,
 
+    /// Request past chat messages sent before `before` (epoch millis), newest first
+    GetChatHistory {
+        before: Option<i64>,
+        /// With `before`, continue after the message with this `seq`, so
+        /// messages sharing its timestamp aren't skipped
+        #[serde(default)]
+        before_seq: Option<i64>,
+        limit: usize,
+    },
+
+    /// Request full detail for one peer
+    GetPeer {
+        peer_id: String,
+    },
+
+    /// Request metadata for specific peers in one round-trip
+    GetPeersBulk {
+        ids: Vec<String>,
+    },
+
+    /// Request the peers whose name contains `query` (ignoring case), most
+    /// recently seen first
+    SearchPeers {
+        query: String,
+        /// Only peers with at least this reputation
+        #[serde(default)]
+        min_reputation: Option<f64>,
+        limit: usize,
+    },
+
     /// Request network stats
     GetStats,
 
+    /// Request stats history downsampled to an interval
+    GetStatsHistory {
+        /// Only samples at or after this time (epoch millis)
+        since: i64,
+        /// Width of each point in seconds
+        interval_secs: u64,
+    },
+
     /// Subscribe to a topic
     Subscribe {
         topic: String,
     },
 
+    /// Subscribe to several topics at once
+    SubscribeMany {
+        topics: Vec<String>,
+    },
+
+    /// Unsubscribe from a topic subscribed with `Subscribe` or `SubscribeMany`
+    Unsubscribe {
+        topic: String,
+    },
+
+    /// Enable or disable an opt-in capability for this connection
+    SetCapability {
+        capability: Capability,
+        enabled: bool,
+    },
+
+    /// Set the locale for pre-formatted display fields (`None` to disable)
+    SetLocale {
+        locale: Option<String>,
+    },
+
+    /// Only forward broadcast events with these `type` tags (empty for all)
+    ///
+    /// Replies to this connection's own requests are always delivered.
+    SetEventFilter {
+        include: Vec<String>,
+    },
+
+    /// Authenticate this connection as an operator using the admin token
+    AuthenticateAdmin {
+        token: Secret,
+    },
+
+    /// Forcibly disconnect another connection (admin only)
+    Kick {
+        connection_id: String,
+        reason: String,
+    },
+
+    /// Ask peers for missed governance state and reconcile it (admin only)
+    RequestSync,
+
+    /// Get the node's runtime configuration (admin only)
+    GetConfig,
+
+    /// An economics action signed by the configured action signer
+    ///
+    /// `payload` is the JSON of the action exactly as signed, `nonce` a value
+    /// never used for another signature, `timestamp` the time of signing
+    /// (epoch millis), and `signature` the hex-encoded Ed25519 signature over
+    /// [`ActionSignature::signed_bytes`](mycelial_protocol::ActionSignature::signed_bytes).
+    Signed {
+        payload: String,
+        nonce: String,
+        timestamp: i64,
+        signature: String,
+    },
+
+    /// Get encoding byte counts for a connection (admin only)
+    GetConnectionStats {
+        connection_id: String,
+    },
+
+    /// List open connections (admin only)
+    GetConnections,
+
+    /// Request a description of every client and server message
+    DescribeProtocol,
+
+    /// Request the local node's peer ID, name and listen addresses
+    Whoami,
+
     // ============ Economics Protocol Client Messages ============
 
     /// Request to vouch for another peer
@@ -249,6 +1400,18 @@ pub enum ClientMessage {
         weight: f64,
         /// Optional message
         message: Option<String>,
+        /// Tag echoed on the resulting events to group related activity
+        #[serde(default)]
+        correlation_id: Option<String>,
+    },
+
+    /// Withdraw this node's vouches for a peer
+    RevokeVouch {
+        /// Peer the node vouched for
+        vouchee: String,
+        /// Tag echoed on the resulting events to group related activity
+        #[serde(default)]
+        correlation_id: Option<String>,
     },
 
     /// Respond to a vouch request
@@ -257,6 +1420,9 @@ pub enum ClientMessage {
         request_id: String,
         /// Accept or reject
         accept: bool,
+        /// Tag echoed on the resulting events to group related activity
+        #[serde(default)]
+        correlation_id: Option<String>,
     },
 
     /// Create a credit line with another peer
@@ -265,6 +1431,18 @@ pub enum ClientMessage {
         debtor: String,
         /// Credit limit
         limit: f64,
+        /// Tag echoed on the resulting events to group related activity
+        #[serde(default)]
+        correlation_id: Option<String>,
+    },
+
+    /// Request the credit lines this node extended or received
+    GetCreditLines,
+
+    /// Request the transfers drawn on a credit line
+    GetCreditHistory {
+        /// Credit line to list
+        line_id: String,
     },
 
     /// Transfer credit to another peer
@@ -275,6 +1453,25 @@ pub enum ClientMessage {
         amount: f64,
         /// Optional memo
         memo: Option<String>,
+        /// Payment request this transfer settles
+        #[serde(default)]
+        request_ref: Option<String>,
+        /// Tag echoed on the resulting events to group related activity
+        #[serde(default)]
+        correlation_id: Option<String>,
+    },
+
+    /// Ask another peer to send credit
+    RequestPayment {
+        /// Peer asked to pay
+        from: String,
+        /// Requested amount
+        amount: f64,
+        /// Optional memo
+        memo: Option<String>,
+        /// Tag echoed on the resulting events to group related activity
+        #[serde(default)]
+        correlation_id: Option<String>,
     },
 
     /// Create a governance proposal
@@ -283,8 +1480,38 @@ pub enum ClientMessage {
         title: String,
         /// Proposal description
         description: String,
-        /// Proposal type (text, parameter_change, treasury_spend)
-        proposal_type: String,
+        /// What the proposal does if it passes, with its parameters
+        proposal_type: ProposalKind,
+        /// Voters needed for quorum (default: half the eligible voters, rounded up)
+        #[serde(default)]
+        quorum: Option<u32>,
+        /// Whether the quorum is fixed at creation or follows the eligible set
+        #[serde(default)]
+        quorum_mode: QuorumMode,
+        /// How the votes decide whether the proposal passes
+        #[serde(default)]
+        passing_rule: PassingRule,
+        /// Voting period in seconds (default 24 hours)
+        #[serde(default)]
+        deadline_secs: Option<u64>,
+        /// Tag echoed on the resulting events to group related activity
+        #[serde(default)]
+        correlation_id: Option<String>,
+    },
+
+    /// Amend a proposal's title or description before voting starts
+    AmendProposal {
+        /// Proposal ID
+        proposal_id: String,
+        /// New title, if changing
+        title: Option<String>,
+        /// New description, if changing
+        description: Option<String>,
+    },
+
+    /// Withdraw an active proposal made by this node
+    CancelProposal {
+        proposal_id: String,
     },
 
     /// Cast a vote on a proposal
@@ -293,6 +1520,44 @@ pub enum ClientMessage {
         proposal_id: String,
         /// Vote (yes, no, abstain)
         vote: String,
+        /// Tag echoed on the resulting events to group related activity
+        #[serde(default)]
+        correlation_id: Option<String>,
+    },
+
+    /// Close voting on a proposal now, executing it if it passed (admin only)
+    FinalizeProposal {
+        proposal_id: String,
+    },
+
+    /// Request all known proposals with their current tallies
+    GetProposals,
+
+    /// Request aggregate governance participation metrics
+    GetGovernanceStats,
+
+    /// Request proposal counts by status and vote totals
+    GetGovernanceSummary,
+
+    /// Request aggregate vouch statistics
+    GetVouchStats,
+
+    /// Request vouch requests awaiting this node's response
+    GetPendingVouches,
+
+    /// Request the vouches this node gave and received
+    GetMyVouches,
+
+    /// Replay logged events involving a peer after a timestamp
+    ReplayForPeer {
+        /// Peer whose timeline to replay
+        peer_id: String,
+        /// Only events after this time (epoch millis)
+        since: i64,
+        /// Sequence of the last event already received, for paging within `since`
+        after_seq: Option<i64>,
+        /// Page size (capped server-side)
+        limit: Option<usize>,
     },
 
     /// Report a resource contribution
@@ -303,6 +1568,32 @@ pub enum ClientMessage {
         amount: f64,
         /// Unit of measurement
         unit: String,
+        /// Tag echoed on the resulting events to group related activity
+        #[serde(default)]
+        correlation_id: Option<String>,
+    },
+
+    /// Request the top contributors to a resource type
+    GetResourceContributors {
+        /// Resource type (bandwidth, storage, compute, ...)
+        resource_type: String,
+        /// Page size (capped server-side)
+        limit: Option<usize>,
+        /// Number of ranked contributors to skip
+        offset: Option<usize>,
+    },
+
+    /// Request a peer's resource contributions over time
+    GetPeerResourceHistory {
+        peer_id: String,
+        /// Only this resource type, if given
+        resource_type: Option<String>,
+        /// Only contributions at or after this time (epoch millis)
+        since: i64,
+        /// Page size (capped server-side)
+        limit: Option<usize>,
+        /// Number of contributions to skip
+        offset: Option<usize>,
     },
 
     // ============ Room/Seance Client Messages ============
@@ -317,6 +1608,8 @@ pub enum ClientMessage {
         description: Option<String>,
         /// Whether room is publicly discoverable
         is_public: Option<bool>,
+        /// Relay messages without persisting them (default false)
+        ephemeral: Option<bool>,
     },
 
     /// Join an existing room
@@ -336,3 +1629,54 @@ pub enum ClientMessage {
     /// Get list of available rooms
     GetRooms,
 }
+
+impl ClientMessage {
+    /// Whether handling the message publishes to the network, and so counts
+    /// against the connection's publish rate limit
+    pub fn publishes(&self) -> bool {
+        self.is_economics_action()
+            || matches!(
+                self,
+                ClientMessage::SendChat { .. }
+                    | ClientMessage::EditChat { .. }
+                    | ClientMessage::DeleteChat { .. }
+                    | ClientMessage::ReactChat { .. }
+                    | ClientMessage::JoinRoom { .. }
+                    | ClientMessage::LeaveRoom { .. }
+            )
+    }
+
+    /// Whether the message is an economics action published to peers
+    pub fn is_economics_action(&self) -> bool {
+        matches!(
+            self,
+            ClientMessage::SendVouch { .. }
+                | ClientMessage::RespondVouch { .. }
+                | ClientMessage::RevokeVouch { .. }
+                | ClientMessage::CreateCreditLine { .. }
+                | ClientMessage::TransferCredit { .. }
+                | ClientMessage::RequestPayment { .. }
+                | ClientMessage::CreateProposal { .. }
+                | ClientMessage::AmendProposal { .. }
+                | ClientMessage::CancelProposal { .. }
+                | ClientMessage::CastVote { .. }
+                | ClientMessage::ReportResource { .. }
+        )
+    }
+
+    /// Correlation ID the client tagged the action with, if any
+    pub fn correlation_id(&self) -> Option<&str> {
+        match self {
+            ClientMessage::SendVouch { correlation_id, .. }
+            | ClientMessage::RespondVouch { correlation_id, .. }
+            | ClientMessage::RevokeVouch { correlation_id, .. }
+            | ClientMessage::CreateCreditLine { correlation_id, .. }
+            | ClientMessage::TransferCredit { correlation_id, .. }
+            | ClientMessage::RequestPayment { correlation_id, .. }
+            | ClientMessage::CreateProposal { correlation_id, .. }
+            | ClientMessage::CastVote { correlation_id, .. }
+            | ClientMessage::ReportResource { correlation_id, .. } => correlation_id.as_deref(),
+            _ => None,
+        }
+    }
+}
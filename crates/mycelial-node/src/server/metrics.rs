@@ -0,0 +1,194 @@
+//! Prometheus metrics endpoint
+//!
+//! `GET /metrics` exposes the node's statistics in the Prometheus text
+//! format, for operators who scrape many nodes rather than poll `GetStats`.
+//! The values are read from the same `Stats` message that WebSocket clients
+//! receive, so the two never disagree.
+
+use axum::{
+    extract::State,
+    http::header,
+    response::IntoResponse,
+};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::Arc;
+
+use crate::stats_history::current_stats;
+use crate::AppState;
+use super::messages::WsMessage;
+
+/// Content type of the Prometheus text exposition format
+pub const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+/// How a metric's value behaves over time
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MetricKind {
+    /// Only ever increases
+    Counter,
+    /// May go up or down
+    Gauge,
+}
+
+impl MetricKind {
+    fn as_str(self) -> &'static str {
+        match self {
+            MetricKind::Counter => "counter",
+            MetricKind::Gauge => "gauge",
+        }
+    }
+}
+
+/// Metrics being collected for one scrape, rendered in registration order
+#[derive(Debug, Default)]
+pub struct MetricsRegistry {
+    text: String,
+}
+
+impl MetricsRegistry {
+    /// Add a metric with a single unlabelled value
+    pub fn register(&mut self, name: &str, help: &str, kind: MetricKind, value: f64) {
+        self.header(name, help, kind);
+        let _ = writeln!(self.text, "{} {}", name, value);
+    }
+
+    /// Add a metric with one value per value of `label`
+    pub fn register_labelled<'a>(
+        &mut self,
+        name: &str,
+        help: &str,
+        kind: MetricKind,
+        label: &str,
+        values: impl IntoIterator<Item = (&'a str, f64)>,
+    ) {
+        self.header(name, help, kind);
+        for (label_value, value) in values {
+            let _ = writeln!(self.text, "{}{{{}=\"{}\"}} {}", name, label, escape(label_value), value);
+        }
+    }
+
+    fn header(&mut self, name: &str, help: &str, kind: MetricKind) {
+        let _ = writeln!(self.text, "# HELP {} {}", name, help);
+        let _ = writeln!(self.text, "# TYPE {} {}", name, kind.as_str());
+    }
+
+    /// The metrics in the Prometheus text format
+    pub fn render(self) -> String {
+        self.text
+    }
+}
+
+/// Escape a label value for the text format
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+/// Collect the node's metrics from its current `Stats`
+pub async fn collect(state: &AppState) -> MetricsRegistry {
+    let mut registry = MetricsRegistry::default();
+    let WsMessage::Stats {
+        peer_count,
+        message_count,
+        topic_counts,
+        uptime_seconds,
+        broadcast_queue_depth,
+        broadcast_high_water,
+        broadcast_capacity,
+        connection_count,
+    } = current_stats(state).await
+    else {
+        return registry;
+    };
+
+    registry.register(
+        "mycelial_messages_total",
+        "Messages published or received since startup",
+        MetricKind::Counter,
+        message_count as f64,
+    );
+    // Sorted so scrapes list topics in a stable order
+    let topic_counts: BTreeMap<_, _> = topic_counts.into_iter().collect();
+    registry.register_labelled(
+        "mycelial_topic_messages_total",
+        "Messages published or received on each topic since startup",
+        MetricKind::Counter,
+        "topic",
+        topic_counts.iter().map(|(topic, count)| (topic.as_str(), *count as f64)),
+    );
+    registry.register("mycelial_known_peers", "Peers known to the node", MetricKind::Gauge, peer_count as f64);
+    registry.register(
+        "mycelial_connected_clients",
+        "Open WebSocket connections",
+        MetricKind::Gauge,
+        connection_count as f64,
+    );
+    registry.register(
+        "mycelial_broadcast_queue_depth",
+        "Events queued for the slowest WebSocket connection",
+        MetricKind::Gauge,
+        broadcast_queue_depth as f64,
+    );
+    registry.register(
+        "mycelial_broadcast_queue_high_water",
+        "Deepest the broadcast queue has been since startup",
+        MetricKind::Gauge,
+        broadcast_high_water as f64,
+    );
+    registry.register(
+        "mycelial_broadcast_queue_capacity",
+        "Events the broadcast queue holds before connections miss some",
+        MetricKind::Gauge,
+        broadcast_capacity as f64,
+    );
+    registry.register(
+        "mycelial_uptime_seconds",
+        "Seconds since the node started",
+        MetricKind::Gauge,
+        uptime_seconds as f64,
+    );
+    registry
+}
+
+/// Serve the node's metrics in the Prometheus text format
+pub async fn metrics(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    ([(header::CONTENT_TYPE, CONTENT_TYPE)], collect(&state).await.render())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::test_state;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    #[test]
+    fn test_labelled_values_escaped() {
+        let mut registry = MetricsRegistry::default();
+        registry.register_labelled("m", "Help", MetricKind::Counter, "topic", [("a\"b", 2.0)]);
+        assert_eq!(registry.render(), "# HELP m Help\n# TYPE m counter\nm{topic=\"a\\\"b\"} 2\n");
+    }
+
+    #[tokio::test]
+    async fn test_metrics_endpoint_serves_counters() {
+        let (state, _commands) = test_state().await;
+        state.topic_counts.record("/mycelial/1.0.0/chat");
+        state.topic_counts.record("/mycelial/1.0.0/chat");
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let app = crate::server::create_router(state.clone());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+        stream
+            .write_all(b"GET /metrics HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+            .await
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+
+        assert!(response.starts_with("HTTP/1.1 200"));
+        assert!(response.contains("content-type: text/plain; version=0.0.4"));
+        assert!(response.contains("# TYPE mycelial_topic_messages_total counter"));
+        assert!(response.contains("mycelial_topic_messages_total{topic=\"/mycelial/1.0.0/chat\"} 2\n"));
+        assert!(response.contains("mycelial_connected_clients 0\n"));
+    }
+}
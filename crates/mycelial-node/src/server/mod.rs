@@ -5,7 +5,31 @@
 
 pub mod websocket;
 pub mod rest;
+pub mod auth;
+pub mod checkpoint;
+pub mod collapse;
+pub mod connections;
+pub mod credit;
+pub mod dedup;
+pub mod encoding;
+pub mod governance;
+pub mod handler_error;
+pub mod heartbeat;
+pub mod locale;
 pub mod messages;
+pub mod metrics;
+pub mod outbound;
+pub mod peer_delta;
+pub mod rate_limit;
+pub mod resources;
+pub mod resume;
+pub mod rooms;
+pub mod schema;
+pub mod session;
+pub mod snapshot;
+pub mod subscriptions;
+pub mod timestamps;
+pub mod vouches;
 
 use axum::{
     routing::get,
@@ -29,6 +53,8 @@ pub fn create_router(state: Arc<AppState>) -> Router {
         .route("/api/peers", get(rest::list_peers))
         .route("/api/peer/:id", get(rest::get_peer))
         .route("/api/stats", get(rest::get_stats))
+        // Prometheus scrape endpoint
+        .route("/metrics", get(metrics::metrics))
         // CORS for dashboard
         .layer(
             CorsLayer::new()
@@ -0,0 +1,317 @@
+//! Bounded per-connection outbound queue
+//!
+//! Frames for a connection pass through a bounded queue to a task that
+//! writes them to the socket, so a slow socket doesn't hold up dispatching
+//! and a client can only fall so far behind. Frames are written by priority
+//! tier, in the order they were queued within a tier, so chat isn't stuck
+//! behind a burst of bulk updates. When the queue is full, the oldest
+//! low-priority frame is dropped to make room: periodic snapshots such as
+//! `Stats` and `ResourcePoolUpdate`, which the next one supersedes. If
+//! nothing can be dropped, the queue is cleared and closed with a reason,
+//! since the client can no longer keep up with messages that must arrive.
+
+use axum::extract::ws::Message;
+use parking_lot::Mutex;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use tokio::sync::Notify;
+
+use super::messages::WsMessage;
+
+/// Reason given when a connection is closed for falling behind
+pub const OVERFLOW_REASON: &str = "Outbound queue full";
+
+/// When a frame is written, and whether it may be dropped when the queue is full
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Priority {
+    /// Written ahead of other frames: chat, which someone is waiting on
+    High,
+    /// Must be delivered, or the connection closed
+    Normal,
+    /// Written last, and superseded by a later frame, so safe to drop
+    Low,
+}
+
+impl Priority {
+    /// Priority of the frame carrying `event`
+    pub fn of(event: &WsMessage) -> Self {
+        match event {
+            WsMessage::ChatMessage { .. }
+            | WsMessage::ChatEdited { .. }
+            | WsMessage::ChatDeleted { .. }
+            | WsMessage::ChatReaction { .. }
+            | WsMessage::ReadReceipt { .. }
+            | WsMessage::TypingIndicator { .. } => Priority::High,
+            WsMessage::Stats { .. } | WsMessage::ResourcePoolUpdate { .. } => Priority::Low,
+            _ => Priority::Normal,
+        }
+    }
+
+    /// Index of the tier holding frames of this priority, highest first
+    fn tier(self) -> usize {
+        match self {
+            Priority::High => 0,
+            Priority::Normal => 1,
+            Priority::Low => 2,
+        }
+    }
+}
+
+/// A frame waiting to be written
+#[derive(Debug)]
+pub struct QueuedFrame {
+    pub message: Message,
+    pub priority: Priority,
+    /// Serialized size of the message before wire encoding, for data frames
+    pub serialized_len: Option<usize>,
+    /// Whether the frame counts toward delivery checkpoints
+    pub counted: bool,
+}
+
+/// Outcome of queueing a frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pushed {
+    /// The frame was queued
+    Queued,
+    /// The queue was full, so the oldest low-priority frame (possibly this one) was dropped
+    Dropped,
+    /// The queue was full of frames that can't be dropped, so it was closed
+    Overflow,
+}
+
+/// What the writer should do next
+#[derive(Debug)]
+pub enum Next {
+    /// Write this frame
+    Frame(QueuedFrame),
+    /// Nothing more will be queued: close the socket, with the reason if any
+    Closed(Option<String>),
+}
+
+#[derive(Default)]
+struct QueueState {
+    /// Frames by priority tier, highest first, each in the order queued
+    tiers: [VecDeque<QueuedFrame>; 3],
+    /// Set once closed, with the reason the connection is closing if any
+    closed: Option<Option<String>>,
+}
+
+impl QueueState {
+    fn len(&self) -> usize {
+        self.tiers.iter().map(VecDeque::len).sum()
+    }
+}
+
+struct Shared {
+    state: Mutex<QueueState>,
+    ready: Notify,
+    capacity: usize,
+    dropped: AtomicU64,
+}
+
+/// Frames waiting for a connection's socket, shared by its dispatcher and writer
+#[derive(Clone)]
+pub struct OutboundQueue {
+    shared: Arc<Shared>,
+}
+
+impl OutboundQueue {
+    /// Create a queue holding at most `capacity` frames (at least one)
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            shared: Arc::new(Shared {
+                state: Mutex::default(),
+                ready: Notify::new(),
+                capacity: capacity.max(1),
+                dropped: AtomicU64::new(0),
+            }),
+        }
+    }
+
+    /// Queue a frame, applying the drop policy if the queue is full
+    pub fn push(&self, frame: QueuedFrame) -> Pushed {
+        let mut state = self.shared.state.lock();
+        let mut pushed = Pushed::Queued;
+        if state.len() >= self.shared.capacity {
+            let oldest_low = state.tiers[Priority::Low.tier()].pop_front();
+            match oldest_low {
+                Some(_) => {}
+                None if frame.priority == Priority::Low => {
+                    self.shared.dropped.fetch_add(1, Ordering::Relaxed);
+                    return Pushed::Dropped;
+                }
+                None => {
+                    state.tiers.iter_mut().for_each(VecDeque::clear);
+                    state.closed = Some(Some(OVERFLOW_REASON.to_string()));
+                    drop(state);
+                    self.shared.ready.notify_one();
+                    return Pushed::Overflow;
+                }
+            }
+            self.shared.dropped.fetch_add(1, Ordering::Relaxed);
+            pushed = Pushed::Dropped;
+        }
+        state.tiers[frame.priority.tier()].push_back(frame);
+        drop(state);
+        self.shared.ready.notify_one();
+        pushed
+    }
+
+    /// Close the queue once the frames already queued are written
+    ///
+    /// Has no effect if the queue is already closed.
+    pub fn close(&self) {
+        let mut state = self.shared.state.lock();
+        if state.closed.is_none() {
+            state.closed = Some(None);
+        }
+        drop(state);
+        self.shared.ready.notify_one();
+    }
+
+    /// Wait for the next frame to write: the oldest of the highest priority queued
+    ///
+    /// Only one task should pop from a queue.
+    pub async fn pop(&self) -> Next {
+        loop {
+            {
+                let mut state = self.shared.state.lock();
+                if let Some(frame) = state.tiers.iter_mut().find_map(VecDeque::pop_front) {
+                    return Next::Frame(frame);
+                }
+                if let Some(reason) = &state.closed {
+                    return Next::Closed(reason.clone());
+                }
+            }
+            // A push or close between the check and here leaves a permit
+            self.shared.ready.notified().await;
+        }
+    }
+
+    /// Frames waiting to be written
+    pub fn depth(&self) -> usize {
+        self.shared.state.lock().len()
+    }
+
+    /// Frames dropped to make room so far
+    pub fn dropped(&self) -> u64 {
+        self.shared.dropped.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(text: &str, priority: Priority) -> QueuedFrame {
+        QueuedFrame {
+            message: Message::Text(text.to_string()),
+            priority,
+            serialized_len: Some(text.len()),
+            counted: true,
+        }
+    }
+
+    /// Drain what a writer would see, without waiting
+    async fn drain(queue: &OutboundQueue) -> (Vec<String>, Option<String>) {
+        let mut texts = Vec::new();
+        loop {
+            match queue.pop().await {
+                Next::Frame(QueuedFrame { message: Message::Text(text), .. }) => texts.push(text),
+                Next::Frame(other) => panic!("unexpected frame {:?}", other),
+                Next::Closed(reason) => return (texts, reason),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_stalled_writer_drops_oldest_low_priority() {
+        // Nothing pops, as if the socket had stalled
+        let queue = OutboundQueue::new(3);
+        assert_eq!(queue.push(frame("stats-1", Priority::Low)), Pushed::Queued);
+        assert_eq!(queue.push(frame("chat-1", Priority::Normal)), Pushed::Queued);
+        assert_eq!(queue.push(frame("stats-2", Priority::Low)), Pushed::Queued);
+        assert_eq!(queue.depth(), 3);
+
+        // Room is made by dropping the oldest snapshot, never the chat
+        assert_eq!(queue.push(frame("chat-2", Priority::Normal)), Pushed::Dropped);
+        assert_eq!(queue.push(frame("stats-3", Priority::Low)), Pushed::Dropped);
+        assert_eq!(queue.depth(), 3);
+        assert_eq!(queue.dropped(), 2);
+
+        queue.close();
+        let (texts, reason) = drain(&queue).await;
+        assert_eq!(texts, vec!["chat-1", "chat-2", "stats-3"]);
+        assert_eq!(reason, None);
+    }
+
+    #[tokio::test]
+    async fn test_stalled_writer_full_of_chat_closes_queue() {
+        let queue = OutboundQueue::new(2);
+        queue.push(frame("chat-1", Priority::Normal));
+        queue.push(frame("chat-2", Priority::Normal));
+
+        // A snapshot is simply dropped
+        assert_eq!(queue.push(frame("stats", Priority::Low)), Pushed::Dropped);
+        assert_eq!(queue.depth(), 2);
+
+        // A message that must arrive can't be, so the connection is closed
+        assert_eq!(queue.push(frame("chat-3", Priority::Normal)), Pushed::Overflow);
+        assert_eq!(queue.depth(), 0);
+        let (texts, reason) = drain(&queue).await;
+        assert!(texts.is_empty());
+        assert_eq!(reason.as_deref(), Some(OVERFLOW_REASON));
+    }
+
+    #[tokio::test]
+    async fn test_chat_overtakes_queued_bulk_updates() {
+        let queue = OutboundQueue::new(64);
+        for i in 0..20 {
+            queue.push(frame(&format!("pool-{}", i), Priority::Low));
+        }
+        queue.push(frame("proposal", Priority::Normal));
+        queue.push(frame("chat-1", Priority::High));
+        queue.push(frame("chat-2", Priority::High));
+        assert_eq!(queue.depth(), 23);
+
+        queue.close();
+        let (texts, _) = drain(&queue).await;
+        // Higher tiers first, each in the order queued
+        assert_eq!(&texts[..3], ["chat-1", "chat-2", "proposal"]);
+        let expected: Vec<_> = (0..20).map(|i| format!("pool-{}", i)).collect();
+        assert_eq!(texts[3..], expected[..]);
+    }
+
+    #[tokio::test]
+    async fn test_full_queue_keeps_chat() {
+        // High-priority frames, like normal ones, are never dropped
+        let queue = OutboundQueue::new(2);
+        queue.push(frame("stats", Priority::Low));
+        queue.push(frame("chat-1", Priority::High));
+        assert_eq!(queue.push(frame("chat-2", Priority::High)), Pushed::Dropped);
+        assert_eq!(queue.push(frame("chat-3", Priority::High)), Pushed::Overflow);
+
+        let typing = WsMessage::TypingIndicator {
+            from: "alice".to_string(),
+            from_name: "Alice".to_string(),
+            to: None,
+            timestamp: 0,
+        };
+        assert_eq!(Priority::of(&typing), Priority::High);
+    }
+
+    #[tokio::test]
+    async fn test_pop_waits_for_push() {
+        let queue = OutboundQueue::new(4);
+        let writer = queue.clone();
+        let popped = tokio::spawn(async move { writer.pop().await });
+        tokio::task::yield_now().await;
+        queue.push(frame("chat", Priority::Normal));
+        match popped.await.unwrap() {
+            Next::Frame(QueuedFrame { message: Message::Text(text), .. }) => assert_eq!(text, "chat"),
+            other => panic!("expected frame, got {:?}", other),
+        }
+        assert_eq!(Priority::of(&WsMessage::Resync { reason: String::new() }), Priority::Normal);
+    }
+}
@@ -0,0 +1,154 @@
+//! Batched peer membership changes
+//!
+//! Peers often join or leave in bursts, for example when the node reconnects
+//! to the mesh. Rather than one event per change, membership changes seen
+//! within a short window are merged into a single [`WsMessage::PeerDelta`].
+//! A peer that joins and leaves within the same window cancels out.
+//! Connections opt in with the `peer_deltas` capability, which replaces the
+//! individual `PeerJoined` and `PeerLeft` events.
+
+use parking_lot::Mutex;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast;
+
+use super::messages::{PeerListEntry, WsMessage};
+
+/// Membership changes waiting for the window to close
+#[derive(Default)]
+struct PendingDelta {
+    added: Vec<PeerListEntry>,
+    removed: Vec<String>,
+}
+
+/// Merges membership changes into one `PeerDelta` per window
+pub struct PeerDeltaBatcher {
+    event_tx: broadcast::Sender<WsMessage>,
+    window: Duration,
+    /// Changes in the open window, or `None` if no window is open
+    pending: Arc<Mutex<Option<PendingDelta>>>,
+}
+
+impl PeerDeltaBatcher {
+    /// Create a batcher broadcasting on `event_tx` once `window` after the first change
+    pub fn new(event_tx: broadcast::Sender<WsMessage>, window: Duration) -> Self {
+        Self {
+            event_tx,
+            window,
+            pending: Arc::default(),
+        }
+    }
+
+    /// Record that a peer joined
+    pub fn joined(&self, entry: PeerListEntry) {
+        self.record(|pending| {
+            // Leaving and rejoining within the window reports the peer as
+            // added, since its details may have changed
+            pending.removed.retain(|id| *id != entry.id);
+            pending.added.retain(|added| added.id != entry.id);
+            pending.added.push(entry);
+        });
+    }
+
+    /// Record that a peer left
+    pub fn left(&self, peer_id: &str) {
+        self.record(|pending| {
+            let before = pending.added.len();
+            pending.added.retain(|added| added.id != peer_id);
+            // A peer that joined within the window was never reported
+            if pending.added.len() == before && !pending.removed.iter().any(|id| id == peer_id) {
+                pending.removed.push(peer_id.to_string());
+            }
+        });
+    }
+
+    /// Apply a change, opening a window and scheduling its flush if none is open
+    fn record(&self, change: impl FnOnce(&mut PendingDelta)) {
+        let mut pending = self.pending.lock();
+        let opened = pending.is_none();
+        change(pending.get_or_insert_with(PendingDelta::default));
+        drop(pending);
+        if !opened {
+            return;
+        }
+
+        let shared = self.pending.clone();
+        let event_tx = self.event_tx.clone();
+        let window = self.window;
+        tokio::spawn(async move {
+            tokio::time::sleep(window).await;
+            let Some(delta) = shared.lock().take() else {
+                return;
+            };
+            if delta.added.is_empty() && delta.removed.is_empty() {
+                return;
+            }
+            let _ = event_tx.send(WsMessage::PeerDelta {
+                added: delta.added,
+                removed: delta.removed,
+            });
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(id: &str) -> PeerListEntry {
+        PeerListEntry {
+            id: id.to_string(),
+            name: None,
+            reputation: 0.5,
+            addresses: vec![],
+        }
+    }
+
+    fn delta(event: WsMessage) -> (Vec<String>, Vec<String>) {
+        match event {
+            WsMessage::PeerDelta { added, removed } => {
+                (added.into_iter().map(|entry| entry.id).collect(), removed)
+            }
+            other => panic!("expected peer delta, got {:?}", other),
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_rapid_joins_batched_into_one_delta() {
+        let (event_tx, mut events) = broadcast::channel(16);
+        let batcher = PeerDeltaBatcher::new(event_tx, Duration::from_millis(200));
+
+        batcher.joined(entry("a"));
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        batcher.joined(entry("b"));
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        batcher.joined(entry("c"));
+        assert!(events.try_recv().is_err());
+
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        assert_eq!(delta(events.try_recv().unwrap()), (vec!["a".into(), "b".into(), "c".into()], vec![]));
+        assert!(events.try_recv().is_err());
+
+        // A later change opens a new window
+        batcher.left("a");
+        tokio::time::sleep(Duration::from_millis(250)).await;
+        assert_eq!(delta(events.try_recv().unwrap()), (vec![], vec!["a".to_string()]));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_join_and_leave_within_window_cancel_out() {
+        let (event_tx, mut events) = broadcast::channel(16);
+        let batcher = PeerDeltaBatcher::new(event_tx, Duration::from_millis(200));
+
+        batcher.joined(entry("a"));
+        batcher.left("a");
+        tokio::time::sleep(Duration::from_millis(250)).await;
+        assert!(events.try_recv().is_err());
+
+        // Leaving and rejoining reports the peer as added
+        batcher.left("b");
+        batcher.joined(entry("b"));
+        tokio::time::sleep(Duration::from_millis(250)).await;
+        assert_eq!(delta(events.try_recv().unwrap()), (vec!["b".to_string()], vec![]));
+    }
+}
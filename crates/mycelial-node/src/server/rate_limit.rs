@@ -0,0 +1,64 @@
+//! Per-connection publish rate limiting
+//!
+//! Every message a client asks the node to publish is gossiped to the whole
+//! network, so each connection draws from a token bucket: it may burst up to
+//! the bucket size, then publish at the refill rate.
+
+use std::time::Instant;
+
+/// Token bucket allowing `burst` messages at once and `rate` per second after
+#[derive(Debug, Clone)]
+pub struct TokenBucket {
+    /// Tokens added per second
+    rate: f64,
+    /// Maximum tokens held
+    burst: f64,
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    /// Create a full bucket
+    pub fn new(rate: f64, burst: u32) -> Self {
+        Self {
+            rate: rate.max(0.0),
+            burst: burst as f64,
+            tokens: burst as f64,
+            last_refill: Instant::now(),
+        }
+    }
+
+    /// Take a token if one is available at `now`
+    pub fn try_acquire(&mut self, now: Instant) -> bool {
+        let elapsed = now.saturating_duration_since(self.last_refill).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.burst);
+        self.last_refill = now;
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_token_bucket_refills_at_rate() {
+        let mut bucket = TokenBucket::new(2.0, 3);
+        let start = Instant::now();
+
+        assert_eq!((0..5).filter(|_| bucket.try_acquire(start)).count(), 3);
+        // Half a second at 2/s refills one token
+        let later = start + Duration::from_millis(500);
+        assert!(bucket.try_acquire(later));
+        assert!(!bucket.try_acquire(later));
+        // Refills never exceed the burst size
+        let much_later = later + Duration::from_secs(60);
+        assert_eq!((0..5).filter(|_| bucket.try_acquire(much_later)).count(), 3);
+    }
+}
@@ -0,0 +1,411 @@
+//! Resource requests from dashboard clients
+//!
+//! Contribution reports, the resulting pools, and contributor queries.
+
+use tracing::{error, info, warn};
+
+use crate::AppState;
+use crate::resource_units;
+use super::handler_error::HandlerError;
+use super::messages::{ClientMessage, ContributorEntry, ResourceHistoryEntry, WsMessage};
+use super::session::Session;
+use super::websocket::publish_economics;
+use mycelial_state::ContributionRecord;
+use mycelial_state::resources::{available_capacity, rank_contributors};
+use mycelial_protocol::{
+    topics, Correlated, Signed,
+    ResourceMessage, ResourceContribution as ProtocolResourceContribution, ResourceType,
+};
+
+/// Default and maximum page sizes for contributor rankings
+const DEFAULT_CONTRIBUTORS_PAGE: usize = 20;
+const MAX_CONTRIBUTORS_PAGE: usize = 100;
+
+/// Default and maximum page sizes for a peer's resource history
+const DEFAULT_RESOURCE_HISTORY_PAGE: usize = 100;
+const MAX_RESOURCE_HISTORY_PAGE: usize = 500;
+
+/// The recomputed pool for a resource type, to broadcast after a contribution
+///
+/// The available total counts each peer's latest report; contributor shares
+/// are ranked the same way as [`ClientMessage::GetResourceContributors`].
+pub(crate) async fn pool_update(state: &AppState, resource_type: &str) -> Option<WsMessage> {
+    let contributions = match state.store.list_resource_contributions(resource_type).await {
+        Ok(contributions) => contributions,
+        Err(e) => {
+            warn!("Failed to load contributions for {}: {}", resource_type, e);
+            return None;
+        }
+    };
+    let now = chrono::Utc::now().timestamp_millis();
+    let half_life_ms = state
+        .config
+        .contribution_half_life
+        .map(|half_life| half_life.as_millis() as i64);
+    let contributors = rank_contributors(&contributions, now, half_life_ms)
+        .into_iter()
+        .map(|c| ContributorEntry {
+            peer_id: c.peer_id,
+            contribution: c.contribution,
+            percentage: c.percentage,
+        })
+        .collect();
+    Some(WsMessage::ResourcePoolUpdate {
+        resource_type: resource_type.to_string(),
+        total_available: available_capacity(&contributions),
+        // Usage isn't reported by peers yet
+        total_used: 0.0,
+        contributors,
+        timestamp: now,
+    })
+}
+
+/// Map a protocol resource type to the label used for storage and queries
+pub(crate) fn resource_type_label(resource_type: &ResourceType) -> String {
+    match resource_type {
+        ResourceType::Bandwidth => "bandwidth".to_string(),
+        ResourceType::Storage => "storage".to_string(),
+        ResourceType::Compute => "compute".to_string(),
+        ResourceType::Relay => "relay".to_string(),
+        ResourceType::Other(other) => other.to_lowercase(),
+    }
+}
+
+/// Map a stored or client-supplied resource type label to its protocol type
+pub(crate) fn parse_resource_type(label: &str) -> ResourceType {
+    match label {
+        "bandwidth" => ResourceType::Bandwidth,
+        "storage" => ResourceType::Storage,
+        "compute" => ResourceType::Compute,
+        "relay" => ResourceType::Relay,
+        other => ResourceType::Other(other.to_string()),
+    }
+}
+
+/// Carry out a resource request routed here by the WebSocket handler
+pub(super) async fn handle_request(msg: ClientMessage, state: &AppState, session: &mut Session) -> Result<(), HandlerError> {
+    match msg {
+        ClientMessage::ReportResource { resource_type, amount, unit, correlation_id } => {
+            info!("ReportResource: type='{}', amount={}", resource_type, amount);
+
+            let timestamp = chrono::Utc::now().timestamp_millis();
+
+            let res_type = parse_resource_type(&resource_type);
+
+            let (amount, unit) = resource_units::normalize(&res_type, amount, &unit).map_err(HandlerError::invalid)?;
+
+            let contribution = ProtocolResourceContribution::new(
+                state.local_peer_id.to_string(),
+                res_type,
+                amount,
+                unit.clone(),
+            )
+            .with_correlation_id(correlation_id.clone())
+            .with_signature(session.take_action_signature());
+            let record = ContributionRecord {
+                id: contribution.id.to_string(),
+                peer_id: contribution.peer_id.clone(),
+                resource_type: resource_type_label(&contribution.resource_type),
+                amount,
+                unit: unit.clone(),
+                timestamp,
+            };
+            let resource_msg = ResourceMessage::Contribution(contribution);
+
+            match serde_json::to_vec(&resource_msg) {
+                Ok(data) => {
+                    if publish_economics(state, session, topics::RESOURCE, data, "resource contribution").await? {
+                        if let Err(e) = state.store.insert_resource_contribution(&record).await {
+                            warn!("Failed to store resource contribution: {}", e);
+                        }
+                        let echo_msg = WsMessage::ResourceContribution {
+                            id: record.id,
+                            peer_id: state.local_peer_id.to_string(),
+                            resource_type,
+                            amount,
+                            unit,
+                            correlation_id,
+                            timestamp,
+                        };
+                        let _ = state.event_tx.send(echo_msg);
+                        if let Some(pool) = pool_update(state, &record.resource_type).await {
+                            let _ = state.event_tx.send(pool);
+                        }
+                    }
+                }
+                Err(e) => {
+                    error!("Failed to serialize resource contribution: {}", e);
+                    return Err(HandlerError::internal("Failed to serialize resource contribution"));
+                }
+            }
+        }
+
+        ClientMessage::GetResourceContributors { resource_type, limit, offset } => {
+            let resource_type = resource_type.to_lowercase();
+            let limit = limit.unwrap_or(DEFAULT_CONTRIBUTORS_PAGE).clamp(1, MAX_CONTRIBUTORS_PAGE);
+            let offset = offset.unwrap_or(0);
+
+            let contributions = match state.store.list_resource_contributions(&resource_type).await {
+                Ok(contributions) => contributions,
+                Err(e) => {
+                    error!("Failed to load contributions for {}: {}", resource_type, e);
+                    return Err(HandlerError::internal("Failed to load contributors"));
+                }
+            };
+            let half_life_ms = state
+                .config
+                .contribution_half_life
+                .map(|half_life| half_life.as_millis() as i64);
+            let ranked = rank_contributors(&contributions, chrono::Utc::now().timestamp_millis(), half_life_ms);
+
+            let total_contributors = ranked.len();
+            let contributors: Vec<ContributorEntry> = ranked
+                .into_iter()
+                .skip(offset)
+                .take(limit)
+                .map(|c| ContributorEntry {
+                    peer_id: c.peer_id,
+                    contribution: c.contribution,
+                    percentage: c.percentage,
+                })
+                .collect();
+            let has_more = offset.saturating_add(contributors.len()) < total_contributors;
+
+            session.reply(WsMessage::ResourceContributors {
+                resource_type,
+                contributors,
+                total_contributors,
+                has_more,
+            });
+        }
+
+        ClientMessage::GetPeerResourceHistory { peer_id, resource_type, since, limit, offset } => {
+            let resource_type = resource_type.map(|t| t.to_lowercase());
+            let limit = limit.unwrap_or(DEFAULT_RESOURCE_HISTORY_PAGE).clamp(1, MAX_RESOURCE_HISTORY_PAGE);
+            let offset = offset.unwrap_or(0);
+
+            // Fetch one extra row to learn whether another page exists
+            let mut contributions = match state
+                .store
+                .list_peer_contributions(&peer_id, resource_type.as_deref(), since, limit as i64 + 1, offset as i64)
+                .await
+            {
+                Ok(contributions) => contributions,
+                Err(e) => {
+                    error!("Failed to load resource history for {}: {}", peer_id, e);
+                    return Err(HandlerError::internal("Failed to load resource history"));
+                }
+            };
+            let has_more = contributions.len() > limit;
+            contributions.truncate(limit);
+
+            session.reply(WsMessage::PeerResourceHistory {
+                peer_id,
+                contributions: contributions
+                    .into_iter()
+                    .map(|c| ResourceHistoryEntry {
+                        id: c.id,
+                        resource_type: c.resource_type,
+                        amount: c.amount,
+                        unit: c.unit,
+                        timestamp: c.timestamp,
+                    })
+                    .collect(),
+                has_more,
+            });
+        }
+
+        other => unreachable!("not a resource request: {:?}", other),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::messages::ErrorCode;
+    use crate::server::websocket::handle_client_message;
+    use crate::test_support::{test_session, test_state};
+
+    #[tokio::test]
+    async fn test_reported_contribution_updates_pool() {
+        let (state, _commands) = test_state().await;
+        let (mut session, _replies) = test_session(8);
+        let mut events = state.event_tx.subscribe();
+
+        state
+            .store
+            .insert_resource_contribution(&ContributionRecord {
+                id: "remote".to_string(),
+                peer_id: "remote-peer".to_string(),
+                resource_type: "storage".to_string(),
+                amount: 30e9,
+                unit: "bytes".to_string(),
+                timestamp: 0,
+            })
+            .await
+            .unwrap();
+
+        let msg = ClientMessage::ReportResource {
+            resource_type: "storage".to_string(),
+            amount: 10.0,
+            unit: "GB".to_string(),
+            correlation_id: None,
+        };
+        handle_client_message(msg, &state, &mut session).await;
+
+        let mut pool = None;
+        while let Ok(event) = events.try_recv() {
+            if let WsMessage::ResourcePoolUpdate { resource_type, total_available, contributors, .. } = event {
+                pool = Some((resource_type, total_available, contributors));
+            }
+        }
+        let (resource_type, total_available, contributors) = pool.unwrap();
+        assert_eq!(resource_type, "storage");
+        assert!((total_available - 40e9).abs() < 1e-3);
+        let peers: Vec<_> = contributors.iter().map(|c| c.peer_id.clone()).collect();
+        assert_eq!(peers, vec!["remote-peer".to_string(), state.local_peer_id.to_string()]);
+        assert!((contributors[0].percentage - 75.0).abs() < 1e-9);
+        assert!((contributors[1].percentage - 25.0).abs() < 1e-9);
+        let total: f64 = contributors.iter().map(|c| c.percentage).sum();
+        assert!((total - 100.0).abs() < 1e-9);
+    }
+
+    #[tokio::test]
+    async fn test_reported_contribution_validated_and_normalized() {
+        let (state, mut commands) = test_state().await;
+        let (mut session, mut replies) = test_session(8);
+
+        for (amount, unit) in [(-5.0, "bytes"), (f64::NAN, "MB"), (5.0, "furlongs"), (5.0, "Mbps")] {
+            let msg = ClientMessage::ReportResource {
+                resource_type: "storage".to_string(),
+                amount,
+                unit: unit.to_string(),
+                correlation_id: None,
+            };
+            handle_client_message(msg, &state, &mut session).await;
+            assert!(matches!(
+                replies.try_recv(),
+                Ok(WsMessage::Error { code: ErrorCode::InvalidRequest, .. })
+            ));
+        }
+        assert!(commands.try_recv().is_err());
+        assert!(state.store.list_resource_contributions("storage").await.unwrap().is_empty());
+
+        for unit in ["MB", "mb", "megabytes"] {
+            let msg = ClientMessage::ReportResource {
+                resource_type: "storage".to_string(),
+                amount: 2.0,
+                unit: unit.to_string(),
+                correlation_id: None,
+            };
+            handle_client_message(msg, &state, &mut session).await;
+        }
+        let stored = state.store.list_resource_contributions("storage").await.unwrap();
+        assert_eq!(stored.len(), 3);
+        assert!(stored.iter().all(|c| c.amount == 2e6 && c.unit == "bytes"));
+    }
+
+    #[tokio::test]
+    async fn test_resource_contributors_paged_in_rank_order() {
+        let (state, _commands) = test_state().await;
+        let (mut session, mut replies) = test_session(8);
+
+        for (i, (peer, amount)) in [("alice", 30.0), ("bob", 60.0), ("carol", 10.0)].iter().enumerate() {
+            state
+                .store
+                .insert_resource_contribution(&ContributionRecord {
+                    id: format!("c{}", i),
+                    peer_id: peer.to_string(),
+                    resource_type: "bandwidth".to_string(),
+                    amount: *amount,
+                    unit: "Mbps".to_string(),
+                    timestamp: 0,
+                })
+                .await
+                .unwrap();
+        }
+
+        let msg = ClientMessage::GetResourceContributors {
+            resource_type: "Bandwidth".to_string(),
+            limit: Some(2),
+            offset: None,
+        };
+        handle_client_message(msg, &state, &mut session).await;
+
+        match replies.try_recv().unwrap() {
+            WsMessage::ResourceContributors { contributors, total_contributors, has_more, .. } => {
+                assert_eq!(total_contributors, 3);
+                assert!(has_more);
+                let peers: Vec<_> = contributors.iter().map(|c| c.peer_id.as_str()).collect();
+                assert_eq!(peers, vec!["bob", "alice"]);
+                assert!((contributors[0].percentage - 60.0).abs() < 1e-9);
+                assert!((contributors[1].percentage - 30.0).abs() < 1e-9);
+            }
+            other => panic!("unexpected message: {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_peer_resource_history_filters_by_peer_and_type() {
+        let (state, _commands) = test_state().await;
+        let (mut session, mut replies) = test_session(8);
+
+        let seeded = [
+            ("c1", "alice", "bandwidth", 100),
+            ("c2", "alice", "storage", 200),
+            ("c3", "bob", "bandwidth", 300),
+            ("c4", "alice", "bandwidth", 400),
+            ("c5", "alice", "bandwidth", 500),
+        ];
+        for (id, peer_id, resource_type, timestamp) in seeded {
+            let record = ContributionRecord {
+                id: id.to_string(),
+                peer_id: peer_id.to_string(),
+                resource_type: resource_type.to_string(),
+                amount: 1.0,
+                unit: "MB".to_string(),
+                timestamp,
+            };
+            state.store.insert_resource_contribution(&record).await.unwrap();
+        }
+
+        let msg = ClientMessage::GetPeerResourceHistory {
+            peer_id: "alice".to_string(),
+            resource_type: Some("Bandwidth".to_string()),
+            since: 0,
+            limit: Some(2),
+            offset: None,
+        };
+        handle_client_message(msg, &state, &mut session).await;
+
+        match replies.try_recv().unwrap() {
+            WsMessage::PeerResourceHistory { peer_id, contributions, has_more } => {
+                assert_eq!(peer_id, "alice");
+                assert!(has_more);
+                let ids: Vec<_> = contributions.iter().map(|c| c.id.as_str()).collect();
+                assert_eq!(ids, vec!["c1", "c4"]);
+            }
+            other => panic!("unexpected message: {:?}", other),
+        }
+
+        // Without a type filter, every resource from `since` onward is included
+        let msg = ClientMessage::GetPeerResourceHistory {
+            peer_id: "alice".to_string(),
+            resource_type: None,
+            since: 200,
+            limit: None,
+            offset: None,
+        };
+        handle_client_message(msg, &state, &mut session).await;
+
+        match replies.try_recv().unwrap() {
+            WsMessage::PeerResourceHistory { contributions, has_more, .. } => {
+                assert!(!has_more);
+                let ids: Vec<_> = contributions.iter().map(|c| c.id.as_str()).collect();
+                assert_eq!(ids, vec!["c2", "c4", "c5"]);
+            }
+            other => panic!("unexpected message: {:?}", other),
+        }
+    }
+}
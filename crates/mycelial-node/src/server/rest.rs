@@ -71,7 +71,7 @@ pub async fn node_info(
 ) -> Json<NodeInfo> {
     Json(NodeInfo {
         version: env!("CARGO_PKG_VERSION"),
-        name: state.node_name.clone(),
+        name: state.node_name(),
         peer_id: state.local_peer_id.to_string(),
     })
 }
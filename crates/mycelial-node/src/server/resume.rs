@@ -0,0 +1,123 @@
+//! Reconnection tokens
+//!
+//! Each connection is issued a token in [`WsMessage::ConnectionReady`]
+//! recording where in the event log it started. A reconnecting client
+//! presents the token to resume from that point instead of choosing its own
+//! starting sequence. Tokens are signed with a key generated at startup and
+//! expire, so forged, stale, or pre-restart tokens are refused and the client
+//! is told to resync in full.
+//!
+//! [`WsMessage::ConnectionReady`]: super::messages::WsMessage::ConnectionReady
+
+use mycelial_core::identity::{Keypair, KeypairExt, PublicKeyExt, SignatureBytes};
+use std::time::Duration;
+
+/// Where a resuming connection left off
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResumePoint {
+    /// Connection the token was issued to
+    pub connection_id: String,
+    /// Last event log sequence the connection had seen
+    pub last_seq: i64,
+}
+
+/// Why a reconnection token was refused
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResumeError {
+    /// The token is not in the expected format
+    Malformed,
+    /// The signature does not match the token's contents
+    Forged,
+    /// The token is past its expiry
+    Expired,
+}
+
+impl ResumeError {
+    /// Reason reported to the client with the resync instruction
+    pub fn message(self) -> &'static str {
+        match self {
+            ResumeError::Malformed => "Malformed reconnection token",
+            ResumeError::Forged => "Invalid reconnection token",
+            ResumeError::Expired => "Reconnection token expired",
+        }
+    }
+}
+
+/// Issues and checks reconnection tokens
+pub struct ResumeTokens {
+    keypair: Keypair,
+    ttl: Duration,
+}
+
+impl ResumeTokens {
+    /// Sign tokens valid for `ttl` with a freshly generated key
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            keypair: Keypair::generate(),
+            ttl,
+        }
+    }
+
+    /// Issue a token for `connection_id` at `last_seq`, returning it and its expiry (epoch millis)
+    pub fn issue(&self, connection_id: &str, last_seq: i64, now: i64) -> (String, i64) {
+        let expires_at = now.saturating_add(self.ttl.as_millis() as i64);
+        let payload = format!("{}.{}.{}", connection_id, last_seq, expires_at);
+        let signature = self.keypair.sign_bytes(payload.as_bytes()).to_hex();
+        (format!("{}.{}", payload, signature), expires_at)
+    }
+
+    /// Check a token, returning where its connection left off
+    pub fn verify(&self, token: &str, now: i64) -> Result<ResumePoint, ResumeError> {
+        let (payload, signature) = token.rsplit_once('.').ok_or(ResumeError::Malformed)?;
+        let signature = SignatureBytes::from_hex(signature).map_err(|_| ResumeError::Malformed)?;
+        if self.keypair.public_key().verify_bytes(payload.as_bytes(), &signature).is_err() {
+            return Err(ResumeError::Forged);
+        }
+
+        // Signed by this server, so the fields are ones it wrote
+        let mut fields = payload.split('.');
+        let (Some(connection_id), Some(last_seq), Some(expires_at), None) =
+            (fields.next(), fields.next(), fields.next(), fields.next())
+        else {
+            return Err(ResumeError::Malformed);
+        };
+        let last_seq = last_seq.parse().map_err(|_| ResumeError::Malformed)?;
+        let expires_at: i64 = expires_at.parse().map_err(|_| ResumeError::Malformed)?;
+        if now >= expires_at {
+            return Err(ResumeError::Expired);
+        }
+        Ok(ResumePoint {
+            connection_id: connection_id.to_string(),
+            last_seq,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resume_tokens() {
+        let tokens = ResumeTokens::new(Duration::from_secs(60));
+        let (token, expires_at) = tokens.issue("conn-1", 42, 1_000);
+        assert_eq!(expires_at, 61_000);
+
+        assert_eq!(
+            tokens.verify(&token, 30_000),
+            Ok(ResumePoint {
+                connection_id: "conn-1".to_string(),
+                last_seq: 42,
+            })
+        );
+        assert_eq!(tokens.verify(&token, 61_000), Err(ResumeError::Expired));
+
+        // Rewinding the sequence invalidates the signature
+        let tampered = token.replacen(".42.", ".0.", 1);
+        assert_eq!(tokens.verify(&tampered, 30_000), Err(ResumeError::Forged));
+        // So does a token from another server (or before a restart)
+        let (foreign, _) = ResumeTokens::new(Duration::from_secs(60)).issue("conn-1", 42, 1_000);
+        assert_eq!(tokens.verify(&foreign, 30_000), Err(ResumeError::Forged));
+        assert_eq!(tokens.verify("garbage", 30_000), Err(ResumeError::Malformed));
+    }
+}
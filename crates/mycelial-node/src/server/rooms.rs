@@ -0,0 +1,225 @@
+//! Chat room requests from dashboard clients
+//!
+//! A room is a gossip topic; joining subscribes through the same per-topic
+//! refcount as plain subscriptions, so the node follows a room while any
+//! connection is in it.
+
+use tracing::{debug, info, warn};
+use uuid::Uuid;
+
+use crate::AppState;
+use super::handler_error::HandlerError;
+use super::messages::{ClientMessage, WsMessage};
+use super::session::Session;
+use super::websocket::{subscribe_topic, unsubscribe_topic, SubscribeError};
+use mycelial_protocol::topics;
+
+/// Topic for a client-supplied room ID
+pub(super) fn room_topic(room_id: &str) -> Result<String, HandlerError> {
+    topics::room(room_id).ok_or_else(|| {
+        HandlerError::invalid(format!(
+            "Invalid room ID: use 1 to {} letters, digits, '-' or '_'",
+            topics::MAX_ROOM_ID_LEN
+        ))
+    })
+}
+
+/// Subscribe the connection, and the node if it isn't already, to a room topic
+///
+/// Rooms share the refcount of [`subscribe_topic`], so the node stays in a
+/// room until the last connection leaves it. `action` names the request in
+/// the error reported to the client.
+pub(super) async fn subscribe_room(
+    state: &AppState,
+    session: &mut Session,
+    topic: &str,
+    action: &str,
+) -> Result<(), HandlerError> {
+    match subscribe_topic(state, session, topic).await {
+        Ok(()) => Ok(()),
+        Err(e @ SubscribeError::LimitReached(_)) => Err(HandlerError::rate_limited(e.message())),
+        Err(SubscribeError::Failed(e)) => Err(HandlerError::internal(format!("Failed to {}: {}", action, e))),
+    }
+}
+
+/// Carry out a room request routed here by the WebSocket handler
+pub(super) async fn handle_request(msg: ClientMessage, state: &AppState, session: &mut Session) -> Result<(), HandlerError> {
+    match msg {
+        ClientMessage::CreateRoom { room_id, room_name, description, is_public, ephemeral } => {
+            info!("CreateRoom: name='{}', is_public={:?}", room_name, is_public);
+
+            let timestamp = chrono::Utc::now().timestamp_millis();
+            let id = room_id.unwrap_or_else(|| Uuid::new_v4().to_string());
+            let topic = room_topic(&id)?;
+            let is_public = is_public.unwrap_or(true);
+            let ephemeral = ephemeral.unwrap_or(false);
+            if ephemeral {
+                let creator = session.identity().unwrap_or_else(|| state.local_peer_id.to_string());
+                state.ephemeral_rooms.mark(&id, &creator, timestamp).map_err(HandlerError::forbidden)?;
+            }
+
+            subscribe_room(state, session, &topic, "create room").await?;
+
+            info!("Room created and subscribed to topic: {}", topic);
+
+            // Send room joined confirmation
+            let room_msg = WsMessage::RoomJoined {
+                id: id.clone(),
+                name: room_name,
+                description,
+                topic,
+                members: vec![state.local_peer_id.to_string()],
+                created_by: state.local_peer_id.to_string(),
+                created_at: timestamp,
+                is_public,
+                ephemeral,
+            };
+            let _ = state.event_tx.send(room_msg);
+        }
+
+        ClientMessage::JoinRoom { room_id, room_name: _ } => {
+            info!("JoinRoom: room_id='{}'", room_id);
+
+            let timestamp = chrono::Utc::now().timestamp_millis();
+            let topic = room_topic(&room_id)?;
+
+            subscribe_room(state, session, &topic, "join room").await?;
+
+            info!("Joined room and subscribed to topic: {}", topic);
+            let ephemeral = state.ephemeral_rooms.contains(&room_id, timestamp);
+
+            // Send room joined confirmation
+            // Note: In a full implementation, we'd fetch room details from state/network
+            let room_msg = WsMessage::RoomJoined {
+                id: room_id.clone(),
+                name: format!("Room {}", &room_id[..8.min(room_id.len())]),
+                description: None,
+                topic: topic.clone(),
+                members: vec![state.local_peer_id.to_string()],
+                created_by: "unknown".to_string(),
+                created_at: timestamp,
+                is_public: true,
+                ephemeral,
+            };
+            let _ = state.event_tx.send(room_msg);
+
+            // Notify other room members (broadcast to room topic)
+            let peer_joined_msg = WsMessage::RoomPeerJoined {
+                room_id: room_id.clone(),
+                peer_id: state.local_peer_id.to_string(),
+                peer_name: Some(state.node_name()),
+            };
+            if let Ok(data) = serde_json::to_vec(&peer_joined_msg) {
+                if let Err(e) = state.network.publish(state.wire_topic(&topic), data).await {
+                    warn!("Failed to announce room join: {}", e);
+                }
+            }
+        }
+
+        ClientMessage::LeaveRoom { room_id } => {
+            info!("LeaveRoom: room_id='{}'", room_id);
+
+            let topic = room_topic(&room_id)?;
+
+            // Notify other room members before leaving
+            let peer_left_msg = WsMessage::RoomPeerLeft {
+                room_id: room_id.clone(),
+                peer_id: state.local_peer_id.to_string(),
+            };
+            if let Ok(data) = serde_json::to_vec(&peer_left_msg) {
+                if let Err(e) = state.network.publish(state.wire_topic(&topic), data).await {
+                    warn!("Failed to announce room leave: {}", e);
+                }
+            }
+
+            // The node stays in the room while other connections are in it
+            if let Err(e) = unsubscribe_topic(state, session, &topic).await {
+                debug!("LeaveRoom: {}", e);
+            }
+
+            info!("Left room topic: {}", topic);
+
+            // Send room left confirmation
+            let left_msg = WsMessage::RoomLeft { room_id };
+            let _ = state.event_tx.send(left_msg);
+        }
+
+        ClientMessage::GetRooms => {
+            info!("GetRooms requested");
+
+            // For now, send an empty list
+            // In a full implementation, we'd query a room registry or DHT
+            let rooms_msg = WsMessage::RoomList { rooms: vec![] };
+            let _ = state.event_tx.send(rooms_msg);
+        }
+
+        other => unreachable!("not a room request: {:?}", other),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::messages::ErrorCode;
+    use crate::server::websocket::{handle_client_message, release_topic};
+    use crate::test_support::{remote_peer, test_session, test_state};
+    use mycelial_network::NetworkCommand;
+
+    #[tokio::test]
+    async fn test_only_room_creator_makes_room_ephemeral() {
+        let (state, _commands) = test_state().await;
+        let (mut creator, _creator_rx) = test_session(8);
+        let (mut other, mut other_rx) = test_session(8);
+        other.identify(remote_peer());
+        let create = |ephemeral| ClientMessage::CreateRoom {
+            room_id: Some("live".to_string()),
+            room_name: "live".to_string(),
+            description: None,
+            is_public: None,
+            ephemeral: Some(ephemeral),
+        };
+
+        handle_client_message(create(true), &state, &mut creator).await;
+        handle_client_message(create(true), &state, &mut other).await;
+        assert!(matches!(
+            other_rx.try_recv(),
+            Ok(WsMessage::Error { code: ErrorCode::Forbidden, .. })
+        ));
+        assert!(state.ephemeral_rooms.contains("live", chrono::Utc::now().timestamp_millis()));
+    }
+
+    #[tokio::test]
+    async fn test_room_topic_shared_until_last_connection_leaves() {
+        let (state, mut commands) = test_state().await;
+        let (mut first, _first_rx) = test_session(8);
+        let (mut second, _second_rx) = test_session(8);
+        let join = || ClientMessage::JoinRoom { room_id: "lobby".to_string(), room_name: None };
+        let leave = || ClientMessage::LeaveRoom { room_id: "lobby".to_string() };
+        let topic = "/mycelial/1.0.0/room/lobby";
+        let mut membership_changes = || {
+            std::iter::from_fn(|| commands.try_recv().ok())
+                .filter(|command| !matches!(command, NetworkCommand::Publish { .. }))
+                .collect::<Vec<_>>()
+        };
+
+        handle_client_message(join(), &state, &mut first).await;
+        handle_client_message(join(), &state, &mut second).await;
+        let changes = membership_changes();
+        assert_eq!(changes.len(), 1);
+        assert!(matches!(&changes[0], NetworkCommand::Subscribe { topic: t } if t == topic));
+
+        // The second connection is still in the room
+        handle_client_message(leave(), &state, &mut first).await;
+        assert!(!first.is_subscribed(topic));
+        assert!(membership_changes().is_empty());
+
+        // Closing the last connection in the room releases its topic
+        for topic in state.topic_subscribers.remove_connection(second.id()) {
+            release_topic(&state, &topic).await;
+        }
+        let changes = membership_changes();
+        assert_eq!(changes.len(), 1);
+        assert!(matches!(&changes[0], NetworkCommand::Unsubscribe { topic: t } if t == topic));
+    }
+}
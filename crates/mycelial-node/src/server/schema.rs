@@ -0,0 +1,1334 @@
+//! Self-describing catalog of the WebSocket protocol
+//!
+//! `DescribeProtocol` replies with the `type` tag, fields and field types of
+//! every client and server message, so dashboards can validate payloads and
+//! build forms without reading this crate. The catalog is kept by hand next
+//! to the message enums in [`super::messages`]; tests check it against the
+//! messages the server actually accepts.
+//!
+//! Field types are `string`, `integer`, `number`, `boolean` or `any`;
+//! `array<T>` and `map<T>` (string keys) for collections; `enum<a|b>` for a
+//! fixed set of strings; otherwise the name of a structure sent within
+//! messages, such as `PeerListEntry`.
+
+use serde::Serialize;
+
+/// One field of a message
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct FieldSchema {
+    pub name: &'static str,
+    #[serde(rename = "type")]
+    pub field_type: &'static str,
+    /// Whether the field may be absent or null
+    pub optional: bool,
+}
+
+impl FieldSchema {
+    /// A field that is always present
+    pub const fn required(name: &'static str, field_type: &'static str) -> Self {
+        Self { name, field_type, optional: false }
+    }
+
+    /// A field that may be absent or null
+    pub const fn optional(name: &'static str, field_type: &'static str) -> Self {
+        Self { name, field_type, optional: true }
+    }
+}
+
+/// One message: its `type` tag and fields
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct MessageSchema {
+    /// Value of the message's `type` field
+    pub name: &'static str,
+    pub description: &'static str,
+    pub fields: &'static [FieldSchema],
+}
+
+/// Every message in the protocol, by direction
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct MessageCatalog {
+    /// Messages clients send
+    pub client: &'static [MessageSchema],
+    /// Messages the server sends
+    pub server: &'static [MessageSchema],
+}
+
+/// The catalog of the protocol this server speaks
+pub fn catalog() -> MessageCatalog {
+    MessageCatalog {
+        client: CLIENT_MESSAGES,
+        server: SERVER_MESSAGES,
+    }
+}
+
+/// Messages clients send, in [`ClientMessage`](super::messages::ClientMessage) order
+pub static CLIENT_MESSAGES: &[MessageSchema] = &[
+    MessageSchema {
+        name: "send_chat",
+        description: "Send a chat message",
+        fields: &[
+            FieldSchema::required("content", "string"),
+            FieldSchema::optional("to", "string"),
+            FieldSchema::optional("room_id", "string"),
+        ],
+    },
+    MessageSchema {
+        name: "edit_chat",
+        description: "Replace the content of a chat message this node sent",
+        fields: &[
+            FieldSchema::required("message_id", "string"),
+            FieldSchema::required("content", "string"),
+        ],
+    },
+    MessageSchema {
+        name: "delete_chat",
+        description: "Retract a chat message this node sent",
+        fields: &[
+            FieldSchema::required("message_id", "string"),
+        ],
+    },
+    MessageSchema {
+        name: "react_chat",
+        description: "Toggle this node's reaction to a chat message",
+        fields: &[
+            FieldSchema::required("message_id", "string"),
+            FieldSchema::required("emoji", "string"),
+        ],
+    },
+    MessageSchema {
+        name: "mark_read",
+        description: "Mark a direct message to this node read, notifying its sender",
+        fields: &[
+            FieldSchema::required("message_id", "string"),
+        ],
+    },
+    MessageSchema {
+        name: "typing",
+        description: "Tell others this connection is typing, to everyone or to one peer",
+        fields: &[
+            FieldSchema::optional("to", "string"),
+        ],
+    },
+    MessageSchema {
+        name: "set_nickname",
+        description: "Rename the local node",
+        fields: &[
+            FieldSchema::required("name", "string"),
+        ],
+    },
+    MessageSchema {
+        name: "identify",
+        description: "Act for a peer, receiving only direct messages to or from it",
+        fields: &[
+            FieldSchema::required("peer_id", "string"),
+            FieldSchema::optional("signature", "string"),
+        ],
+    },
+    MessageSchema {
+        name: "resume",
+        description: "Resume from where an earlier connection left off",
+        fields: &[
+            FieldSchema::required("token", "string"),
+        ],
+    },
+    MessageSchema {
+        name: "ping",
+        description: "Application-level ping, answered with a `Pong` echoing the nonce",
+        fields: &[
+            FieldSchema::required("nonce", "string"),
+        ],
+    },
+    MessageSchema {
+        name: "get_unread_counts",
+        description: "Request unread counts per category",
+        fields: &[],
+    },
+    MessageSchema {
+        name: "mark_seen",
+        description: "Mark a category read up to a time, clearing its unread count",
+        fields: &[
+            FieldSchema::required("category", "enum<chat|proposals|vouches>"),
+            FieldSchema::required("up_to", "integer"),
+        ],
+    },
+    MessageSchema {
+        name: "get_peers",
+        description: "Request peer list",
+        fields: &[],
+    },
+    MessageSchema {
+        name: "get_chat_history",
+        description: "Request past chat messages sent before `before` (epoch millis), newest first",
+        fields: &[
+            FieldSchema::optional("before", "integer"),
+            FieldSchema::optional("before_seq", "integer"),
+            FieldSchema::required("limit", "integer"),
+        ],
+    },
+    MessageSchema {
+        name: "get_peer",
+        description: "Request full detail for one peer",
+        fields: &[
+            FieldSchema::required("peer_id", "string"),
+        ],
+    },
+    MessageSchema {
+        name: "get_peers_bulk",
+        description: "Request metadata for specific peers in one round-trip",
+        fields: &[
+            FieldSchema::required("ids", "array<string>"),
+        ],
+    },
+    MessageSchema {
+        name: "search_peers",
+        description: "Request the peers whose name contains a query, ignoring case",
+        fields: &[
+            FieldSchema::required("query", "string"),
+            FieldSchema::optional("min_reputation", "number"),
+            FieldSchema::required("limit", "integer"),
+        ],
+    },
+    MessageSchema {
+        name: "get_stats",
+        description: "Request network stats",
+        fields: &[],
+    },
+    MessageSchema {
+        name: "get_stats_history",
+        description: "Request stats history downsampled to an interval",
+        fields: &[
+            FieldSchema::required("since", "integer"),
+            FieldSchema::required("interval_secs", "integer"),
+        ],
+    },
+    MessageSchema {
+        name: "subscribe",
+        description: "Subscribe to a topic",
+        fields: &[
+            FieldSchema::required("topic", "string"),
+        ],
+    },
+    MessageSchema {
+        name: "subscribe_many",
+        description: "Subscribe to several topics at once",
+        fields: &[
+            FieldSchema::required("topics", "array<string>"),
+        ],
+    },
+    MessageSchema {
+        name: "unsubscribe",
+        description: "Unsubscribe from a topic subscribed with `Subscribe` or `SubscribeMany`",
+        fields: &[
+            FieldSchema::required("topic", "string"),
+        ],
+    },
+    MessageSchema {
+        name: "set_capability",
+        description: "Enable or disable an opt-in capability for this connection",
+        fields: &[
+            FieldSchema::required("capability", "enum<raw_protocol|propagation|collapse_updates|peer_deltas|timestamp_iso|compress_gzip>"),
+            FieldSchema::required("enabled", "boolean"),
+        ],
+    },
+    MessageSchema {
+        name: "set_locale",
+        description: "Set the locale for pre-formatted display fields (`None` to disable)",
+        fields: &[
+            FieldSchema::optional("locale", "string"),
+        ],
+    },
+    MessageSchema {
+        name: "set_event_filter",
+        description: "Only forward broadcast events with these `type` tags (empty for all)",
+        fields: &[
+            FieldSchema::required("include", "array<string>"),
+        ],
+    },
+    MessageSchema {
+        name: "authenticate_admin",
+        description: "Authenticate this connection as an operator using the admin token",
+        fields: &[
+            FieldSchema::required("token", "string"),
+        ],
+    },
+    MessageSchema {
+        name: "kick",
+        description: "Forcibly disconnect another connection (admin only)",
+        fields: &[
+            FieldSchema::required("connection_id", "string"),
+            FieldSchema::required("reason", "string"),
+        ],
+    },
+    MessageSchema {
+        name: "request_sync",
+        description: "Ask peers for missed governance state and reconcile it (admin only)",
+        fields: &[],
+    },
+    MessageSchema {
+        name: "get_config",
+        description: "Get the node's runtime configuration (admin only)",
+        fields: &[],
+    },
+    MessageSchema {
+        name: "signed",
+        description: "An economics action signed by the configured action signer",
+        fields: &[
+            FieldSchema::required("payload", "string"),
+            FieldSchema::required("nonce", "string"),
+            FieldSchema::required("timestamp", "integer"),
+            FieldSchema::required("signature", "string"),
+        ],
+    },
+    MessageSchema {
+        name: "get_connection_stats",
+        description: "Get encoding byte counts for a connection (admin only)",
+        fields: &[
+            FieldSchema::required("connection_id", "string"),
+        ],
+    },
+    MessageSchema {
+        name: "get_connections",
+        description: "List open connections (admin only)",
+        fields: &[],
+    },
+    MessageSchema {
+        name: "describe_protocol",
+        description: "Request a description of every client and server message",
+        fields: &[],
+    },
+    MessageSchema {
+        name: "whoami",
+        description: "Request the local node's peer ID, name and listen addresses",
+        fields: &[],
+    },
+    MessageSchema {
+        name: "send_vouch",
+        description: "Request to vouch for another peer",
+        fields: &[
+            FieldSchema::required("vouchee", "string"),
+            FieldSchema::required("weight", "number"),
+            FieldSchema::optional("message", "string"),
+            FieldSchema::optional("correlation_id", "string"),
+        ],
+    },
+    MessageSchema {
+        name: "revoke_vouch",
+        description: "Withdraw this node's vouches for a peer",
+        fields: &[
+            FieldSchema::required("vouchee", "string"),
+            FieldSchema::optional("correlation_id", "string"),
+        ],
+    },
+    MessageSchema {
+        name: "respond_vouch",
+        description: "Respond to a vouch request",
+        fields: &[
+            FieldSchema::required("request_id", "string"),
+            FieldSchema::required("accept", "boolean"),
+            FieldSchema::optional("correlation_id", "string"),
+        ],
+    },
+    MessageSchema {
+        name: "create_credit_line",
+        description: "Create a credit line with another peer",
+        fields: &[
+            FieldSchema::required("debtor", "string"),
+            FieldSchema::required("limit", "number"),
+            FieldSchema::optional("correlation_id", "string"),
+        ],
+    },
+    MessageSchema {
+        name: "get_credit_lines",
+        description: "Request the credit lines this node extended or received",
+        fields: &[],
+    },
+    MessageSchema {
+        name: "get_credit_history",
+        description: "Request the transfers drawn on a credit line",
+        fields: &[
+            FieldSchema::required("line_id", "string"),
+        ],
+    },
+    MessageSchema {
+        name: "transfer_credit",
+        description: "Transfer credit to another peer",
+        fields: &[
+            FieldSchema::required("to", "string"),
+            FieldSchema::required("amount", "number"),
+            FieldSchema::optional("memo", "string"),
+            FieldSchema::optional("request_ref", "string"),
+            FieldSchema::optional("correlation_id", "string"),
+        ],
+    },
+    MessageSchema {
+        name: "request_payment",
+        description: "Ask another peer to send credit",
+        fields: &[
+            FieldSchema::required("from", "string"),
+            FieldSchema::required("amount", "number"),
+            FieldSchema::optional("memo", "string"),
+            FieldSchema::optional("correlation_id", "string"),
+        ],
+    },
+    MessageSchema {
+        name: "create_proposal",
+        description: "Create a governance proposal",
+        fields: &[
+            FieldSchema::required("title", "string"),
+            FieldSchema::required("description", "string"),
+            FieldSchema::required("proposal_type", "ProposalKind"),
+            FieldSchema::optional("quorum", "integer"),
+            FieldSchema::optional("quorum_mode", "enum<snapshot|dynamic>"),
+            FieldSchema::optional("passing_rule", "PassingRule"),
+            FieldSchema::optional("deadline_secs", "integer"),
+            FieldSchema::optional("correlation_id", "string"),
+        ],
+    },
+    MessageSchema {
+        name: "amend_proposal",
+        description: "Amend a proposal's title or description before voting starts",
+        fields: &[
+            FieldSchema::required("proposal_id", "string"),
+            FieldSchema::optional("title", "string"),
+            FieldSchema::optional("description", "string"),
+        ],
+    },
+    MessageSchema {
+        name: "cancel_proposal",
+        description: "Withdraw an active proposal made by this node",
+        fields: &[
+            FieldSchema::required("proposal_id", "string"),
+        ],
+    },
+    MessageSchema {
+        name: "cast_vote",
+        description: "Cast a vote on a proposal",
+        fields: &[
+            FieldSchema::required("proposal_id", "string"),
+            FieldSchema::required("vote", "string"),
+            FieldSchema::optional("correlation_id", "string"),
+        ],
+    },
+    MessageSchema {
+        name: "finalize_proposal",
+        description: "Close voting on a proposal now, executing it if it passed (admin only)",
+        fields: &[
+            FieldSchema::required("proposal_id", "string"),
+        ],
+    },
+    MessageSchema {
+        name: "get_proposals",
+        description: "Request all known proposals with their current tallies",
+        fields: &[],
+    },
+    MessageSchema {
+        name: "get_governance_stats",
+        description: "Request aggregate governance participation metrics",
+        fields: &[],
+    },
+    MessageSchema {
+        name: "get_governance_summary",
+        description: "Request proposal counts by status and vote totals",
+        fields: &[],
+    },
+    MessageSchema {
+        name: "get_vouch_stats",
+        description: "Request aggregate vouch statistics",
+        fields: &[],
+    },
+    MessageSchema {
+        name: "get_pending_vouches",
+        description: "Request vouch requests awaiting this node's response",
+        fields: &[],
+    },
+    MessageSchema {
+        name: "get_my_vouches",
+        description: "Request the vouches this node gave and received",
+        fields: &[],
+    },
+    MessageSchema {
+        name: "replay_for_peer",
+        description: "Replay logged events involving a peer after a timestamp",
+        fields: &[
+            FieldSchema::required("peer_id", "string"),
+            FieldSchema::required("since", "integer"),
+            FieldSchema::optional("after_seq", "integer"),
+            FieldSchema::optional("limit", "integer"),
+        ],
+    },
+    MessageSchema {
+        name: "report_resource",
+        description: "Report a resource contribution",
+        fields: &[
+            FieldSchema::required("resource_type", "string"),
+            FieldSchema::required("amount", "number"),
+            FieldSchema::required("unit", "string"),
+            FieldSchema::optional("correlation_id", "string"),
+        ],
+    },
+    MessageSchema {
+        name: "get_resource_contributors",
+        description: "Request the top contributors to a resource type",
+        fields: &[
+            FieldSchema::required("resource_type", "string"),
+            FieldSchema::optional("limit", "integer"),
+            FieldSchema::optional("offset", "integer"),
+        ],
+    },
+    MessageSchema {
+        name: "get_peer_resource_history",
+        description: "Request a peer's resource contributions over time",
+        fields: &[
+            FieldSchema::required("peer_id", "string"),
+            FieldSchema::optional("resource_type", "string"),
+            FieldSchema::required("since", "integer"),
+            FieldSchema::optional("limit", "integer"),
+            FieldSchema::optional("offset", "integer"),
+        ],
+    },
+    MessageSchema {
+        name: "create_room",
+        description: "Create a new room",
+        fields: &[
+            FieldSchema::optional("room_id", "string"),
+            FieldSchema::required("room_name", "string"),
+            FieldSchema::optional("description", "string"),
+            FieldSchema::optional("is_public", "boolean"),
+            FieldSchema::optional("ephemeral", "boolean"),
+        ],
+    },
+    MessageSchema {
+        name: "join_room",
+        description: "Join an existing room",
+        fields: &[
+            FieldSchema::required("room_id", "string"),
+            FieldSchema::optional("room_name", "string"),
+        ],
+    },
+    MessageSchema {
+        name: "leave_room",
+        description: "Leave a room",
+        fields: &[
+            FieldSchema::required("room_id", "string"),
+        ],
+    },
+    MessageSchema {
+        name: "get_rooms",
+        description: "Get list of available rooms",
+        fields: &[],
+    },
+];
+
+/// Messages the server sends, in [`WsMessage`](super::messages::WsMessage) order
+pub static SERVER_MESSAGES: &[MessageSchema] = &[
+    MessageSchema {
+        name: "hello",
+        description: "First message on every connection, identifying the server and its protocol",
+        fields: &[
+            FieldSchema::required("protocol_version", "integer"),
+            FieldSchema::required("node_name", "string"),
+            FieldSchema::required("peer_id", "string"),
+            FieldSchema::required("features", "array<string>"),
+        ],
+    },
+    MessageSchema {
+        name: "ack",
+        description: "Outcome of a client message that carried a `client_ref`",
+        fields: &[
+            FieldSchema::required("client_ref", "string"),
+            FieldSchema::required("ok", "boolean"),
+            FieldSchema::optional("error", "string"),
+        ],
+    },
+    MessageSchema {
+        name: "pong",
+        description: "Reply to a client `Ping`, for measuring latency and clock skew",
+        fields: &[
+            FieldSchema::required("nonce", "string"),
+            FieldSchema::required("server_time", "integer"),
+        ],
+    },
+    MessageSchema {
+        name: "peer_joined",
+        description: "A peer joined the network",
+        fields: &[
+            FieldSchema::required("peer_id", "string"),
+            FieldSchema::optional("name", "string"),
+        ],
+    },
+    MessageSchema {
+        name: "peer_left",
+        description: "A peer left the network",
+        fields: &[
+            FieldSchema::required("peer_id", "string"),
+        ],
+    },
+    MessageSchema {
+        name: "peer_delta",
+        description: "Peers that joined or left within a short window, for connections with the `peer_deltas` capability",
+        fields: &[
+            FieldSchema::required("added", "array<PeerListEntry>"),
+            FieldSchema::required("removed", "array<string>"),
+        ],
+    },
+    MessageSchema {
+        name: "chat_message",
+        description: "A chat message was received",
+        fields: &[
+            FieldSchema::required("id", "string"),
+            FieldSchema::required("from", "string"),
+            FieldSchema::required("from_name", "string"),
+            FieldSchema::optional("to", "string"),
+            FieldSchema::optional("room_id", "string"),
+            FieldSchema::required("content", "string"),
+            FieldSchema::required("timestamp", "integer"),
+        ],
+    },
+    MessageSchema {
+        name: "chat_edited",
+        description: "The author of a chat message changed its content; `to` is set when the message was sent directly to one peer",
+        fields: &[
+            FieldSchema::required("message_id", "string"),
+            FieldSchema::required("from", "string"),
+            FieldSchema::optional("to", "string"),
+            FieldSchema::required("content", "string"),
+            FieldSchema::required("edited_at", "integer"),
+        ],
+    },
+    MessageSchema {
+        name: "chat_deleted",
+        description: "The author of a chat message retracted it; `to` is set when the message was sent directly to one peer",
+        fields: &[
+            FieldSchema::required("message_id", "string"),
+            FieldSchema::required("from", "string"),
+            FieldSchema::optional("to", "string"),
+            FieldSchema::required("deleted_at", "integer"),
+        ],
+    },
+    MessageSchema {
+        name: "chat_reaction",
+        description: "A peer added or took back a reaction to a chat message",
+        fields: &[
+            FieldSchema::required("message_id", "string"),
+            FieldSchema::required("reactor", "string"),
+            FieldSchema::optional("to", "string"),
+            FieldSchema::required("emoji", "string"),
+            FieldSchema::required("added", "boolean"),
+        ],
+    },
+    MessageSchema {
+        name: "read_receipt",
+        description: "The recipient of a direct message has read it; only its sender is told",
+        fields: &[
+            FieldSchema::required("message_id", "string"),
+            FieldSchema::required("reader", "string"),
+            FieldSchema::required("to", "string"),
+            FieldSchema::required("timestamp", "integer"),
+        ],
+    },
+    MessageSchema {
+        name: "typing_indicator",
+        description: "A peer is typing, to everyone or to `to` only; relayed live, never logged",
+        fields: &[
+            FieldSchema::required("from", "string"),
+            FieldSchema::required("from_name", "string"),
+            FieldSchema::optional("to", "string"),
+            FieldSchema::required("timestamp", "integer"),
+        ],
+    },
+    MessageSchema {
+        name: "reputation_update",
+        description: "A peer's reputation was updated",
+        fields: &[
+            FieldSchema::required("peer_id", "string"),
+            FieldSchema::required("new_score", "number"),
+        ],
+    },
+    MessageSchema {
+        name: "connection_ready",
+        description: "Sent once a connection is set up, with the token for resuming it later",
+        fields: &[
+            FieldSchema::required("connection_id", "string"),
+            FieldSchema::required("last_seq", "integer"),
+            FieldSchema::required("resume_token", "string"),
+            FieldSchema::required("expires_at", "integer"),
+        ],
+    },
+    MessageSchema {
+        name: "unread_counts",
+        description: "Items newer than the local identity's seen markers",
+        fields: &[
+            FieldSchema::required("chat", "integer"),
+            FieldSchema::required("proposals", "integer"),
+            FieldSchema::required("vouches", "integer"),
+        ],
+    },
+    MessageSchema {
+        name: "resumed",
+        description: "Logged events missed since the resumed connection's token was issued",
+        fields: &[
+            FieldSchema::required("resumed_from", "string"),
+            FieldSchema::required("events", "array<ReplayedEvent>"),
+        ],
+    },
+    MessageSchema {
+        name: "resync",
+        description: "The connection can't be resumed; the client should refetch all state",
+        fields: &[
+            FieldSchema::required("reason", "string"),
+        ],
+    },
+    MessageSchema {
+        name: "shutdown",
+        description: "The node is shutting down and is about to close the connection",
+        fields: &[
+            FieldSchema::required("reason", "string"),
+            FieldSchema::required("reconnect_after_ms", "integer"),
+        ],
+    },
+    MessageSchema {
+        name: "peers_list",
+        description: "Full list of peers",
+        fields: &[
+            FieldSchema::required("peers", "array<PeerListEntry>"),
+        ],
+    },
+    MessageSchema {
+        name: "chat_history",
+        description: "A page of past chat messages, newest first",
+        fields: &[
+            FieldSchema::required("messages", "array<ChatHistoryEntry>"),
+            FieldSchema::required("has_more", "boolean"),
+        ],
+    },
+    MessageSchema {
+        name: "peers_bulk",
+        description: "Metadata for a requested set of peers",
+        fields: &[
+            FieldSchema::required("entries", "array<PeerListEntry>"),
+            FieldSchema::required("unknown", "array<string>"),
+        ],
+    },
+    MessageSchema {
+        name: "peer_detail",
+        description: "Full detail for one peer",
+        fields: &[
+            FieldSchema::required("id", "string"),
+            FieldSchema::optional("name", "string"),
+            FieldSchema::required("display_name", "string"),
+            FieldSchema::required("public_key", "string"),
+            FieldSchema::required("addresses", "array<string>"),
+            FieldSchema::required("first_seen", "integer"),
+            FieldSchema::required("last_seen", "integer"),
+            FieldSchema::required("reputation", "number"),
+            FieldSchema::required("successful_interactions", "integer"),
+            FieldSchema::required("failed_interactions", "integer"),
+            FieldSchema::required("vouches", "array<PeerVouchEntry>"),
+        ],
+    },
+    MessageSchema {
+        name: "network_status",
+        description: "The node lost or regained connectivity to the gossip network",
+        fields: &[
+            FieldSchema::required("isolated", "boolean"),
+            FieldSchema::required("connected_peers", "integer"),
+        ],
+    },
+    MessageSchema {
+        name: "network_error",
+        description: "A network operation failed, reported at most once per interval per operation",
+        fields: &[
+            FieldSchema::required("operation", "string"),
+            FieldSchema::required("detail", "string"),
+            FieldSchema::required("recoverable", "boolean"),
+        ],
+    },
+    MessageSchema {
+        name: "stats",
+        description: "Network statistics",
+        fields: &[
+            FieldSchema::required("peer_count", "integer"),
+            FieldSchema::required("message_count", "integer"),
+            FieldSchema::required("topic_counts", "map<integer>"),
+            FieldSchema::required("uptime_seconds", "integer"),
+            FieldSchema::required("broadcast_queue_depth", "integer"),
+            FieldSchema::required("broadcast_high_water", "integer"),
+            FieldSchema::required("broadcast_capacity", "integer"),
+            FieldSchema::required("connection_count", "integer"),
+        ],
+    },
+    MessageSchema {
+        name: "stats_history",
+        description: "Downsampled stats history, oldest first",
+        fields: &[
+            FieldSchema::required("interval_secs", "integer"),
+            FieldSchema::required("points", "array<StatsPoint>"),
+        ],
+    },
+    MessageSchema {
+        name: "checkpoint",
+        description: "All messages up to `seq` on this connection have been delivered",
+        fields: &[
+            FieldSchema::required("seq", "integer"),
+            FieldSchema::required("timestamp", "integer"),
+        ],
+    },
+    MessageSchema {
+        name: "error",
+        description: "Error message",
+        fields: &[
+            FieldSchema::required("code", "enum<rate_limited|invalid_request|forbidden|unauthorized|internal>"),
+            FieldSchema::required("message", "string"),
+        ],
+    },
+    MessageSchema {
+        name: "warning",
+        description: "An integrity concern for operators",
+        fields: &[
+            FieldSchema::required("kind", "enum<possible_sybil>"),
+            FieldSchema::required("message", "string"),
+        ],
+    },
+    MessageSchema {
+        name: "subscribe_result",
+        description: "Outcome of a client's topic subscription",
+        fields: &[
+            FieldSchema::required("topic", "string"),
+            FieldSchema::required("success", "boolean"),
+            FieldSchema::optional("error", "string"),
+        ],
+    },
+    MessageSchema {
+        name: "node_renamed",
+        description: "The local node was renamed",
+        fields: &[
+            FieldSchema::required("name", "string"),
+        ],
+    },
+    MessageSchema {
+        name: "unsubscribe_result",
+        description: "Result of an unsubscribe request",
+        fields: &[
+            FieldSchema::required("topic", "string"),
+            FieldSchema::required("success", "boolean"),
+            FieldSchema::optional("error", "string"),
+        ],
+    },
+    MessageSchema {
+        name: "subscribe_many_result",
+        description: "Per-topic outcomes of a bulk subscription, in request order",
+        fields: &[
+            FieldSchema::required("results", "array<SubscribeResultEntry>"),
+        ],
+    },
+    MessageSchema {
+        name: "kick_result",
+        description: "Result of an admin kick: whether the connection existed",
+        fields: &[
+            FieldSchema::required("connection_id", "string"),
+            FieldSchema::required("existed", "boolean"),
+        ],
+    },
+    MessageSchema {
+        name: "config_snapshot",
+        description: "The node's runtime configuration, with secrets redacted",
+        fields: &[
+            FieldSchema::required("config", "any"),
+        ],
+    },
+    MessageSchema {
+        name: "connection_stats",
+        description: "Bytes sent to a connection before and after wire encoding",
+        fields: &[
+            FieldSchema::required("connection_id", "string"),
+            FieldSchema::required("messages_sent", "integer"),
+            FieldSchema::required("bytes_before", "integer"),
+            FieldSchema::required("bytes_after", "integer"),
+            FieldSchema::required("compression_ratio", "number"),
+            FieldSchema::required("queue_depth", "integer"),
+            FieldSchema::required("frames_dropped", "integer"),
+        ],
+    },
+    MessageSchema {
+        name: "protocol_schema",
+        description: "Description of every client and server message",
+        fields: &[
+            FieldSchema::required("messages", "MessageCatalog"),
+        ],
+    },
+    MessageSchema {
+        name: "identity",
+        description: "The local node's identity",
+        fields: &[
+            FieldSchema::required("peer_id", "string"),
+            FieldSchema::required("node_name", "string"),
+            FieldSchema::required("addresses", "array<string>"),
+        ],
+    },
+    MessageSchema {
+        name: "connections",
+        description: "Open WebSocket connections, oldest first",
+        fields: &[
+            FieldSchema::required("clients", "array<ConnectionEntry>"),
+        ],
+    },
+    MessageSchema {
+        name: "sync_progress",
+        description: "Progress of a governance sync: sent once when the request goes out (`responder` unset) and again for each peer response applied",
+        fields: &[
+            FieldSchema::required("request_id", "string"),
+            FieldSchema::optional("responder", "string"),
+            FieldSchema::required("proposals_added", "integer"),
+            FieldSchema::required("votes_added", "integer"),
+        ],
+    },
+    MessageSchema {
+        name: "vouch_request",
+        description: "Vouch request received",
+        fields: &[
+            FieldSchema::required("id", "string"),
+            FieldSchema::required("voucher", "string"),
+            FieldSchema::required("voucher_name", "string"),
+            FieldSchema::required("vouchee", "string"),
+            FieldSchema::required("vouchee_name", "string"),
+            FieldSchema::required("weight", "number"),
+            FieldSchema::optional("correlation_id", "string"),
+            FieldSchema::required("timestamp", "integer"),
+        ],
+    },
+    MessageSchema {
+        name: "vouch_ack",
+        description: "Vouch acknowledgement",
+        fields: &[
+            FieldSchema::required("id", "string"),
+            FieldSchema::required("request_id", "string"),
+            FieldSchema::required("accepted", "boolean"),
+            FieldSchema::optional("new_reputation", "number"),
+            FieldSchema::optional("correlation_id", "string"),
+            FieldSchema::required("timestamp", "integer"),
+        ],
+    },
+    MessageSchema {
+        name: "vouch_revoked",
+        description: "A voucher withdrew its vouches for a peer",
+        fields: &[
+            FieldSchema::required("voucher", "string"),
+            FieldSchema::required("vouchee", "string"),
+            FieldSchema::required("timestamp", "integer"),
+        ],
+    },
+    MessageSchema {
+        name: "credit_line",
+        description: "Credit line created or updated",
+        fields: &[
+            FieldSchema::required("id", "string"),
+            FieldSchema::required("creditor", "string"),
+            FieldSchema::required("debtor", "string"),
+            FieldSchema::required("limit", "number"),
+            FieldSchema::required("balance", "number"),
+            FieldSchema::optional("correlation_id", "string"),
+            FieldSchema::required("timestamp", "integer"),
+        ],
+    },
+    MessageSchema {
+        name: "credit_lines_list",
+        description: "Credit lines this node extended or received",
+        fields: &[
+            FieldSchema::required("lines", "array<CreditLineEntry>"),
+        ],
+    },
+    MessageSchema {
+        name: "credit_history",
+        description: "Transfers drawn on a credit line, oldest first",
+        fields: &[
+            FieldSchema::required("line_id", "string"),
+            FieldSchema::required("transfers", "array<CreditTransferEntry>"),
+            FieldSchema::required("current_balance", "number"),
+        ],
+    },
+    MessageSchema {
+        name: "credit_transfer",
+        description: "Credit transfer completed",
+        fields: &[
+            FieldSchema::required("id", "string"),
+            FieldSchema::required("from", "string"),
+            FieldSchema::required("from_name", "string"),
+            FieldSchema::required("to", "string"),
+            FieldSchema::required("to_name", "string"),
+            FieldSchema::required("amount", "number"),
+            FieldSchema::optional("memo", "string"),
+            FieldSchema::optional("request_ref", "string"),
+            FieldSchema::optional("correlation_id", "string"),
+            FieldSchema::required("timestamp", "integer"),
+        ],
+    },
+    MessageSchema {
+        name: "payment_request",
+        description: "A peer asked another peer for payment",
+        fields: &[
+            FieldSchema::required("id", "string"),
+            FieldSchema::required("requester", "string"),
+            FieldSchema::required("payer", "string"),
+            FieldSchema::required("amount", "number"),
+            FieldSchema::optional("memo", "string"),
+            FieldSchema::optional("correlation_id", "string"),
+            FieldSchema::required("timestamp", "integer"),
+        ],
+    },
+    MessageSchema {
+        name: "proposal",
+        description: "Governance proposal created",
+        fields: &[
+            FieldSchema::required("id", "string"),
+            FieldSchema::required("proposer", "string"),
+            FieldSchema::required("proposer_name", "string"),
+            FieldSchema::required("title", "string"),
+            FieldSchema::required("description", "string"),
+            FieldSchema::required("proposal_type", "string"),
+            FieldSchema::optional("parameters", "map<string>"),
+            FieldSchema::required("status", "string"),
+            FieldSchema::required("yes_votes", "integer"),
+            FieldSchema::required("no_votes", "integer"),
+            FieldSchema::required("quorum", "integer"),
+            FieldSchema::required("deadline", "integer"),
+            FieldSchema::required("version", "integer"),
+            FieldSchema::optional("correlation_id", "string"),
+            FieldSchema::required("timestamp", "integer"),
+        ],
+    },
+    MessageSchema {
+        name: "proposals",
+        description: "All known proposals, newest first",
+        fields: &[
+            FieldSchema::required("proposals", "array<ProposalEntry>"),
+        ],
+    },
+    MessageSchema {
+        name: "proposal_executed",
+        description: "A passed proposal was carried out",
+        fields: &[
+            FieldSchema::required("proposal_id", "string"),
+            FieldSchema::required("success", "boolean"),
+            FieldSchema::required("outcome", "string"),
+        ],
+    },
+    MessageSchema {
+        name: "quorum_progress",
+        description: "Votes so far against the number currently needed for quorum",
+        fields: &[
+            FieldSchema::required("proposal_id", "string"),
+            FieldSchema::required("voters", "integer"),
+            FieldSchema::required("required", "integer"),
+            FieldSchema::required("eligible", "integer"),
+            FieldSchema::required("quorum_mode", "enum<snapshot|dynamic>"),
+        ],
+    },
+    MessageSchema {
+        name: "proposal_reminder",
+        description: "A proposal is approaching its voting deadline",
+        fields: &[
+            FieldSchema::required("proposal_id", "string"),
+            FieldSchema::required("closes_in_secs", "integer"),
+            FieldSchema::required("current_tally", "TallyEntry"),
+        ],
+    },
+    MessageSchema {
+        name: "governance_stats",
+        description: "Aggregate governance participation metrics",
+        fields: &[
+            FieldSchema::required("active_proposals", "integer"),
+            FieldSchema::required("avg_turnout", "number"),
+            FieldSchema::required("participating_peers", "integer"),
+            FieldSchema::required("total_peers", "integer"),
+        ],
+    },
+    MessageSchema {
+        name: "governance_summary",
+        description: "Proposal counts by current status, with votes cast overall and by this node",
+        fields: &[
+            FieldSchema::required("active", "integer"),
+            FieldSchema::required("passed", "integer"),
+            FieldSchema::required("rejected", "integer"),
+            FieldSchema::required("total_votes", "integer"),
+            FieldSchema::required("my_votes", "integer"),
+        ],
+    },
+    MessageSchema {
+        name: "vouch_stats",
+        description: "Trust-network overview aggregated from stored vouches",
+        fields: &[
+            FieldSchema::required("total_vouches", "integer"),
+            FieldSchema::required("avg_weight", "number"),
+            FieldSchema::required("most_vouched_peers", "array<VouchedPeerEntry>"),
+            FieldSchema::required("pending_count", "integer"),
+        ],
+    },
+    MessageSchema {
+        name: "pending_vouches",
+        description: "Vouch requests awaiting this node's response, newest first",
+        fields: &[
+            FieldSchema::required("requests", "array<VouchRequestEntry>"),
+        ],
+    },
+    MessageSchema {
+        name: "vouch_list",
+        description: "Vouches this node gave and received, newest first",
+        fields: &[
+            FieldSchema::required("given", "array<VouchEntry>"),
+            FieldSchema::required("received", "array<VouchEntry>"),
+        ],
+    },
+    MessageSchema {
+        name: "peer_replay",
+        description: "Logged events involving a peer, replayed on request",
+        fields: &[
+            FieldSchema::required("peer_id", "string"),
+            FieldSchema::required("events", "array<ReplayedEvent>"),
+            FieldSchema::required("has_more", "boolean"),
+        ],
+    },
+    MessageSchema {
+        name: "vote_cast",
+        description: "Vote cast on a proposal",
+        fields: &[
+            FieldSchema::required("id", "string"),
+            FieldSchema::required("proposal_id", "string"),
+            FieldSchema::required("voter", "string"),
+            FieldSchema::required("voter_name", "string"),
+            FieldSchema::required("vote", "string"),
+            FieldSchema::required("weight", "number"),
+            FieldSchema::optional("correlation_id", "string"),
+            FieldSchema::required("timestamp", "integer"),
+        ],
+    },
+    MessageSchema {
+        name: "resource_contribution",
+        description: "Resource contribution reported",
+        fields: &[
+            FieldSchema::required("id", "string"),
+            FieldSchema::required("peer_id", "string"),
+            FieldSchema::required("resource_type", "string"),
+            FieldSchema::required("amount", "number"),
+            FieldSchema::required("unit", "string"),
+            FieldSchema::optional("correlation_id", "string"),
+            FieldSchema::required("timestamp", "integer"),
+        ],
+    },
+    MessageSchema {
+        name: "resource_pool_update",
+        description: "Resource pool update",
+        fields: &[
+            FieldSchema::required("resource_type", "string"),
+            FieldSchema::required("total_available", "number"),
+            FieldSchema::required("total_used", "number"),
+            FieldSchema::required("contributors", "array<ContributorEntry>"),
+            FieldSchema::required("timestamp", "integer"),
+        ],
+    },
+    MessageSchema {
+        name: "raw_protocol",
+        description: "An inbound economics message as received, for protocol debugging",
+        fields: &[
+            FieldSchema::required("topic", "string"),
+            FieldSchema::required("json", "any"),
+        ],
+    },
+    MessageSchema {
+        name: "propagation",
+        description: "How many peers a locally published message was sent to",
+        fields: &[
+            FieldSchema::required("message_id", "string"),
+            FieldSchema::required("topic", "string"),
+            FieldSchema::required("peer_count", "integer"),
+        ],
+    },
+    MessageSchema {
+        name: "resource_contributors",
+        description: "Ranked contributors to a resource type",
+        fields: &[
+            FieldSchema::required("resource_type", "string"),
+            FieldSchema::required("contributors", "array<ContributorEntry>"),
+            FieldSchema::required("total_contributors", "integer"),
+            FieldSchema::required("has_more", "boolean"),
+        ],
+    },
+    MessageSchema {
+        name: "peer_resource_history",
+        description: "A peer's resource contributions over time, oldest first",
+        fields: &[
+            FieldSchema::required("peer_id", "string"),
+            FieldSchema::required("contributions", "array<ResourceHistoryEntry>"),
+            FieldSchema::required("has_more", "boolean"),
+        ],
+    },
+    MessageSchema {
+        name: "room_joined",
+        description: "Successfully joined a room",
+        fields: &[
+            FieldSchema::required("id", "string"),
+            FieldSchema::required("name", "string"),
+            FieldSchema::optional("description", "string"),
+            FieldSchema::required("topic", "string"),
+            FieldSchema::required("members", "array<string>"),
+            FieldSchema::required("created_by", "string"),
+            FieldSchema::required("created_at", "integer"),
+            FieldSchema::required("is_public", "boolean"),
+            FieldSchema::required("ephemeral", "boolean"),
+        ],
+    },
+    MessageSchema {
+        name: "room_left",
+        description: "Left a room",
+        fields: &[
+            FieldSchema::required("room_id", "string"),
+        ],
+    },
+    MessageSchema {
+        name: "room_list",
+        description: "List of available rooms",
+        fields: &[
+            FieldSchema::required("rooms", "array<RoomEntry>"),
+        ],
+    },
+    MessageSchema {
+        name: "room_peer_joined",
+        description: "A peer joined a room",
+        fields: &[
+            FieldSchema::required("room_id", "string"),
+            FieldSchema::required("peer_id", "string"),
+            FieldSchema::optional("peer_name", "string"),
+        ],
+    },
+    MessageSchema {
+        name: "room_peer_left",
+        description: "A peer left a room",
+        fields: &[
+            FieldSchema::required("room_id", "string"),
+            FieldSchema::required("peer_id", "string"),
+        ],
+    },
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::messages::{ClientMessage, FEATURES};
+    use serde_json::{json, Map, Value};
+    use std::collections::HashSet;
+
+    /// A value of `field_type` that deserializes, for building sample messages
+    fn sample(field_type: &str) -> Value {
+        match field_type {
+            "string" => json!("sample"),
+            "integer" => json!(1),
+            "number" => json!(0.5),
+            "boolean" => json!(true),
+            "ProposalKind" => json!("text"),
+            "PassingRule" => json!("simple_majority"),
+            t if t.starts_with("array<") => json!([]),
+            t if t.starts_with("map<") => json!({}),
+            t if t.starts_with("enum<") => {
+                let first = t["enum<".len()..].split(['|', '>']).next().unwrap();
+                json!(first)
+            }
+            other => panic!("no sample for field type {}", other),
+        }
+    }
+
+    fn sample_message(message: &MessageSchema, include: impl Fn(&FieldSchema) -> bool) -> Value {
+        let mut fields = Map::new();
+        fields.insert("type".to_string(), json!(message.name));
+        for field in message.fields.iter().filter(|field| include(field)) {
+            fields.insert(field.name.to_string(), sample(field.field_type));
+        }
+        Value::Object(fields)
+    }
+
+    #[test]
+    fn test_catalog_lists_every_client_message() {
+        // Every client message type is advertised in FEATURES
+        let advertised: HashSet<_> = FEATURES.iter().copied().filter(|f| !f.contains(':')).collect();
+        let described: HashSet<_> = CLIENT_MESSAGES.iter().map(|m| m.name).collect();
+        assert_eq!(described.len(), CLIENT_MESSAGES.len(), "duplicate client message");
+        assert_eq!(described, advertised);
+
+        let server: HashSet<_> = SERVER_MESSAGES.iter().map(|m| m.name).collect();
+        assert_eq!(server.len(), SERVER_MESSAGES.len(), "duplicate server message");
+        assert!(server.contains("protocol_schema"));
+    }
+
+    #[test]
+    fn test_client_schemas_match_deserialization() {
+        for message in CLIENT_MESSAGES {
+            // With only the required fields, and with every field
+            for value in [
+                sample_message(message, |field| !field.optional),
+                sample_message(message, |_| true),
+            ] {
+                if let Err(e) = serde_json::from_value::<ClientMessage>(value.clone()) {
+                    panic!("{} rejected: {} ({})", message.name, e, value);
+                }
+            }
+            // Each required field really is required
+            for missing in message.fields.iter().filter(|field| !field.optional) {
+                let value = sample_message(message, |field| !field.optional && field.name != missing.name);
+                assert!(
+                    serde_json::from_value::<ClientMessage>(value).is_err(),
+                    "{}.{} is not required",
+                    message.name,
+                    missing.name
+                );
+            }
+        }
+    }
+
+    /// Tags and field names of the `WsMessage` variants, read from the
+    /// source since the enum can only be serialized, not enumerated
+    fn server_variants() -> Vec<(String, HashSet<String>)> {
+        let source = include_str!("messages.rs");
+        let start = source.find("pub enum WsMessage {").unwrap();
+        let body = &source[start..];
+        let body = &body[..body.find("\n}").unwrap()];
+
+        let mut variants: Vec<(String, HashSet<String>)> = Vec::new();
+        for line in body.lines().skip(1) {
+            if let Some(variant) = line.strip_prefix("    ").filter(|l| l.starts_with(|c: char| c.is_ascii_uppercase())) {
+                let name = variant.trim_end_matches([' ', '{', ',']);
+                let mut tag = String::new();
+                for (i, c) in name.chars().enumerate() {
+                    if c.is_ascii_uppercase() && i > 0 {
+                        tag.push('_');
+                    }
+                    tag.push(c.to_ascii_lowercase());
+                }
+                variants.push((tag, HashSet::new()));
+            } else if let Some(field) = line.strip_prefix("        ").and_then(|l| l.split_once(": ")) {
+                if !field.0.starts_with(['/', '#']) {
+                    variants.last_mut().unwrap().1.insert(field.0.to_string());
+                }
+            }
+        }
+        variants
+    }
+
+    #[test]
+    fn test_server_schemas_match_messages() {
+        let variants = server_variants();
+        assert_eq!(variants.len(), SERVER_MESSAGES.len(), "server messages missing from the catalog");
+        for (tag, fields) in variants {
+            let message = SERVER_MESSAGES
+                .iter()
+                .find(|m| m.name == tag)
+                .unwrap_or_else(|| panic!("{} is not in the catalog", tag));
+            let described: HashSet<String> = message.fields.iter().map(|f| f.name.to_string()).collect();
+            assert_eq!(described, fields, "fields of {} differ", tag);
+        }
+    }
+
+    #[test]
+    fn test_catalog_serializes_field_types() {
+        let value = serde_json::to_value(catalog()).unwrap();
+        let send_chat = value["client"].as_array().unwrap().iter().find(|m| m["name"] == "send_chat").unwrap();
+        assert_eq!(send_chat["fields"][0], json!({"name": "content", "type": "string", "optional": false}));
+    }
+}
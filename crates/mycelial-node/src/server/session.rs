@@ -0,0 +1,573 @@
+//! Per-connection session state
+//!
+//! Each WebSocket connection owns a [`Session`] tracking what that client has
+//! registered (topic subscriptions, room memberships) and a reply channel for
+//! messages meant only for that client rather than the broadcast channel.
+//! Delivery preferences are shared with the connection's send task, which
+//! uses them to decide which broadcast events reach the client.
+
+use parking_lot::{Mutex, RwLock};
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+use uuid::Uuid;
+
+use mycelial_protocol::ActionSignature;
+
+use super::encoding::Compression;
+use super::locale::Locale;
+use super::messages::{Capability, ErrorCode, WsMessage};
+use super::rate_limit::TokenBucket;
+use super::timestamps;
+
+/// Minimum time between typing indicators relayed for one connection
+pub const TYPING_DEBOUNCE: Duration = Duration::from_secs(1);
+
+/// Returned when a connection already holds its maximum number of subscriptions
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SubscriptionLimitExceeded {
+    /// The configured per-connection limit
+    pub limit: usize,
+}
+
+/// Which broadcast events a connection wants delivered
+#[derive(Debug, Default)]
+pub struct DeliveryPrefs {
+    /// Opt-in capabilities enabled for the connection
+    capabilities: HashSet<Capability>,
+    /// Locale for pre-formatted display fields, if requested
+    locale: Option<Locale>,
+    /// Whether the connection has authenticated as an operator
+    admin: bool,
+    /// Peer the connection acts for; direct messages are only delivered to it
+    identity: Option<String>,
+    /// Broadcast event types to forward; empty forwards every type
+    event_filter: HashSet<String>,
+}
+
+impl DeliveryPrefs {
+    /// Peer the connection acts for, if any
+    pub fn identity(&self) -> Option<&str> {
+        self.identity.as_deref()
+    }
+
+    /// Whether a broadcast event should be forwarded to the connection
+    pub fn wants(&self, event: &WsMessage) -> bool {
+        if !self.event_filter.is_empty() && !self.event_filter.contains(event.type_tag()) {
+            return false;
+        }
+        match event {
+            WsMessage::RawProtocol { .. } => self.capabilities.contains(&Capability::RawProtocol),
+            WsMessage::Propagation { .. } => self.capabilities.contains(&Capability::Propagation),
+            WsMessage::Warning { .. } => self.admin,
+            WsMessage::PeerJoined { .. } | WsMessage::PeerLeft { .. } => {
+                !self.capabilities.contains(&Capability::PeerDeltas)
+            }
+            WsMessage::PeerDelta { .. } => self.capabilities.contains(&Capability::PeerDeltas),
+            WsMessage::ChatMessage { from, to: Some(to), .. }
+            | WsMessage::ChatEdited { from, to: Some(to), .. }
+            | WsMessage::ChatDeleted { from, to: Some(to), .. }
+            | WsMessage::ChatReaction { reactor: from, to: Some(to), .. } => self.sees_direct(Some(from), to),
+            WsMessage::TypingIndicator { to: Some(to), .. } => self.sees_direct(None, to),
+            WsMessage::ReadReceipt { to, .. } => self.sees_direct(None, to),
+            _ => true,
+        }
+    }
+
+    /// Whether a logged event, replayed from its stored JSON, should be
+    /// forwarded to the connection
+    ///
+    /// Applies the same rules as [`wants`](Self::wants), so a replay never
+    /// shows a connection what it wasn't sent live.
+    pub fn wants_logged(&self, event: &serde_json::Value) -> bool {
+        let tag = event["type"].as_str().unwrap_or_default();
+        if !self.event_filter.is_empty() && !self.event_filter.contains(tag) {
+            return false;
+        }
+        match (tag, event["to"].as_str()) {
+            ("raw_protocol", _) => self.capabilities.contains(&Capability::RawProtocol),
+            ("propagation", _) => self.capabilities.contains(&Capability::Propagation),
+            ("warning", _) => self.admin,
+            ("peer_joined" | "peer_left", _) => !self.capabilities.contains(&Capability::PeerDeltas),
+            ("peer_delta", _) => self.capabilities.contains(&Capability::PeerDeltas),
+            ("chat_message" | "chat_edited" | "chat_deleted", Some(to)) => {
+                self.sees_direct(event["from"].as_str(), to)
+            }
+            ("chat_reaction", Some(to)) => self.sees_direct(event["reactor"].as_str(), to),
+            ("typing_indicator" | "read_receipt", Some(to)) => self.sees_direct(None, to),
+            // Receipts always name the sender they are for
+            ("read_receipt", None) => false,
+            _ => true,
+        }
+    }
+
+    /// Whether the connection's identity is party to a direct event
+    fn sees_direct(&self, from: Option<&str>, to: &str) -> bool {
+        self.identity
+            .as_deref()
+            .is_some_and(|identity| identity == to || Some(identity) == from)
+    }
+
+    /// Whether backlogged updates are collapsed to the latest per entity
+    pub fn collapses_updates(&self) -> bool {
+        self.capabilities.contains(&Capability::CollapseUpdates)
+    }
+
+    /// Compression applied to the connection's large frames
+    pub fn compression(&self) -> Compression {
+        if self.capabilities.contains(&Capability::CompressGzip) {
+            Compression::Gzip
+        } else {
+            Compression::None
+        }
+    }
+
+    /// Serialize a message for the connection, adding display fields for its
+    /// locale and RFC 3339 times if requested
+    pub fn encode(&self, event: &WsMessage) -> serde_json::Result<String> {
+        let iso_times = self.capabilities.contains(&Capability::TimestampIso);
+        if self.locale.is_none() && !iso_times {
+            return serde_json::to_string(event);
+        }
+        let mut value = serde_json::to_value(event)?;
+        if let Some(locale) = self.locale {
+            locale.add_display_fields(&mut value);
+        }
+        if iso_times {
+            timestamps::add_iso_fields(&mut value);
+        }
+        Ok(value.to_string())
+    }
+}
+
+/// Bytes sent to a connection, before and after wire encoding
+///
+/// `bytes_before` counts each message as serialized; `bytes_after` counts
+/// what was written to the socket once any compression or alternative
+/// encoding was applied. Their ratio shows whether those settings pay off.
+#[derive(Debug, Default)]
+pub struct EncodingStats {
+    messages: AtomicU64,
+    bytes_before: AtomicU64,
+    bytes_after: AtomicU64,
+}
+
+impl EncodingStats {
+    /// Record one message sent to the connection
+    pub fn record(&self, before: usize, after: usize) {
+        self.messages.fetch_add(1, Ordering::Relaxed);
+        self.bytes_before.fetch_add(before as u64, Ordering::Relaxed);
+        self.bytes_after.fetch_add(after as u64, Ordering::Relaxed);
+    }
+
+    /// Messages sent so far
+    pub fn messages(&self) -> u64 {
+        self.messages.load(Ordering::Relaxed)
+    }
+
+    /// Serialized bytes before wire encoding
+    pub fn bytes_before(&self) -> u64 {
+        self.bytes_before.load(Ordering::Relaxed)
+    }
+
+    /// Bytes written to the socket
+    pub fn bytes_after(&self) -> u64 {
+        self.bytes_after.load(Ordering::Relaxed)
+    }
+
+    /// Bytes after encoding per byte before, or 1.0 if nothing was sent
+    pub fn ratio(&self) -> f64 {
+        match self.bytes_before() {
+            0 => 1.0,
+            before => self.bytes_after() as f64 / before as f64,
+        }
+    }
+}
+
+/// State owned by a single WebSocket connection
+pub struct Session {
+    /// Identifier used to address this connection in the registry
+    id: String,
+    /// Channel for replies delivered only to this connection
+    reply_tx: mpsc::UnboundedSender<WsMessage>,
+    /// Active subscriptions registered by this connection
+    subscriptions: HashSet<String>,
+    /// Maximum number of active subscriptions
+    max_subscriptions: usize,
+    /// Delivery preferences shared with the send task
+    delivery: Arc<RwLock<DeliveryPrefs>>,
+    /// Byte counts updated by the send task
+    encoding_stats: Arc<EncodingStats>,
+    /// Verified signature for the action currently being handled
+    action_signature: Option<ActionSignature>,
+    /// Limits how fast the client may publish, if set
+    publish_limit: Option<TokenBucket>,
+    /// Why the request being handled failed, if it did, for its acknowledgement
+    failure: Mutex<Option<String>>,
+    /// When a typing indicator was last relayed for this connection
+    last_typing: Option<Instant>,
+}
+
+impl Session {
+    /// Create a session replying through `reply_tx`
+    pub fn new(reply_tx: mpsc::UnboundedSender<WsMessage>, max_subscriptions: usize) -> Self {
+        Self {
+            id: Uuid::new_v4().to_string(),
+            reply_tx,
+            subscriptions: HashSet::new(),
+            max_subscriptions,
+            delivery: Arc::default(),
+            encoding_stats: Arc::default(),
+            action_signature: None,
+            publish_limit: None,
+            failure: Mutex::new(None),
+            last_typing: None,
+        }
+    }
+
+    /// Connection ID for this session
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Whether the connection may use admin commands
+    pub fn is_admin(&self) -> bool {
+        self.delivery.read().admin
+    }
+
+    /// Grant admin commands and admin-only events to this connection
+    pub fn grant_admin(&mut self) {
+        self.delivery.write().admin = true;
+    }
+
+    /// Set the peer this connection acts for
+    pub fn identify(&self, peer_id: impl Into<String>) {
+        self.delivery.write().identity = Some(peer_id.into());
+    }
+
+    /// Peer this connection acts for, if identified
+    pub fn identity(&self) -> Option<String> {
+        self.delivery.read().identity.clone()
+    }
+
+    /// Handle to the delivery preferences, for the connection's send task
+    pub fn delivery(&self) -> Arc<RwLock<DeliveryPrefs>> {
+        self.delivery.clone()
+    }
+
+    /// Handle to the connection's byte counts, for the send task and registry
+    pub fn encoding_stats(&self) -> Arc<EncodingStats> {
+        self.encoding_stats.clone()
+    }
+
+    /// Attach a verified signature to the action about to be handled
+    pub fn set_action_signature(&mut self, signature: ActionSignature) {
+        self.action_signature = Some(signature);
+    }
+
+    /// Whether the action being handled carries a verified signature
+    pub fn has_action_signature(&self) -> bool {
+        self.action_signature.is_some()
+    }
+
+    /// Take the verified signature for the action being handled, if any
+    pub fn take_action_signature(&mut self) -> Option<ActionSignature> {
+        self.action_signature.take()
+    }
+
+    /// Limit how fast the client may publish to the network
+    pub fn set_publish_limit(&mut self, limit: TokenBucket) {
+        self.publish_limit = Some(limit);
+    }
+
+    /// Take a publish token, returning `false` if the client is over its limit
+    pub fn try_publish(&mut self) -> bool {
+        match &mut self.publish_limit {
+            Some(limit) => limit.try_acquire(Instant::now()),
+            None => true,
+        }
+    }
+
+    /// Whether a typing indicator at `now` should be relayed, recording it if so
+    ///
+    /// At most one is relayed per [`TYPING_DEBOUNCE`].
+    pub fn try_typing(&mut self, now: Instant) -> bool {
+        if let Some(last) = self.last_typing {
+            if now.saturating_duration_since(last) < TYPING_DEBOUNCE {
+                return false;
+            }
+        }
+        self.last_typing = Some(now);
+        true
+    }
+
+    /// Enable or disable an opt-in capability
+    pub fn set_capability(&self, capability: Capability, enabled: bool) {
+        let mut delivery = self.delivery.write();
+        if enabled {
+            delivery.capabilities.insert(capability);
+        } else {
+            delivery.capabilities.remove(&capability);
+        }
+    }
+
+    /// Send a message to this connection only
+    pub fn reply(&self, msg: WsMessage) {
+        let _ = self.reply_tx.send(msg);
+    }
+
+    /// Send an error to this connection only
+    ///
+    /// The error also marks the request being handled as failed.
+    pub fn reply_error(&self, code: ErrorCode, message: impl Into<String>) {
+        let message = message.into();
+        self.record_failure(message.clone());
+        self.reply(WsMessage::Error { code, message });
+    }
+
+    /// Mark the request being handled as failed without replying an error
+    pub fn record_failure(&self, message: impl Into<String>) {
+        self.failure.lock().get_or_insert(message.into());
+    }
+
+    /// Take why the request just handled failed, if it did
+    pub fn take_failure(&self) -> Option<String> {
+        self.failure.lock().take()
+    }
+
+    /// Set or clear the locale used for display fields
+    pub fn set_locale(&self, locale: Option<Locale>) {
+        self.delivery.write().locale = locale;
+    }
+
+    /// Restrict broadcast events to these type tags, or forward all if empty
+    pub fn set_event_filter(&self, include: HashSet<String>) {
+        self.delivery.write().event_filter = include;
+    }
+
+    /// Register a subscription, enforcing the per-connection limit
+    ///
+    /// All subscription-like registrations go through here so the limit
+    /// covers them uniformly. Re-adding an existing key is not counted twice.
+    pub fn add_subscription(&mut self, key: &str) -> Result<(), SubscriptionLimitExceeded> {
+        if self.subscriptions.contains(key) {
+            return Ok(());
+        }
+        if self.subscriptions.len() >= self.max_subscriptions {
+            return Err(SubscriptionLimitExceeded {
+                limit: self.max_subscriptions,
+            });
+        }
+        self.subscriptions.insert(key.to_string());
+        Ok(())
+    }
+
+    /// Whether `key` is a registered subscription
+    pub fn is_subscribed(&self, key: &str) -> bool {
+        self.subscriptions.contains(key)
+    }
+
+    /// Remove a subscription, returning whether it was registered
+    pub fn remove_subscription(&mut self, key: &str) -> bool {
+        self.subscriptions.remove(key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_subscription_limit() {
+        let (reply_tx, _reply_rx) = mpsc::unbounded_channel();
+        let mut session = Session::new(reply_tx, 2);
+
+        assert!(session.add_subscription("a").is_ok());
+        assert!(session.add_subscription("b").is_ok());
+        // Re-adding an existing subscription doesn't count against the limit
+        assert!(session.add_subscription("a").is_ok());
+        assert_eq!(
+            session.add_subscription("c"),
+            Err(SubscriptionLimitExceeded { limit: 2 })
+        );
+
+        assert!(session.remove_subscription("a"));
+        assert!(session.add_subscription("c").is_ok());
+    }
+
+    #[test]
+    fn test_typing_debounced_per_interval() {
+        let (reply_tx, _reply_rx) = mpsc::unbounded_channel();
+        let mut session = Session::new(reply_tx, 2);
+        let start = Instant::now();
+
+        assert!(session.try_typing(start));
+        assert!(!session.try_typing(start + Duration::from_millis(300)));
+        assert!(!session.try_typing(start + Duration::from_millis(999)));
+        // Suppressed indicators don't push the window back
+        assert!(session.try_typing(start + TYPING_DEBOUNCE));
+        assert!(!session.try_typing(start + Duration::from_millis(1500)));
+    }
+
+    #[test]
+    fn test_encoding_stats_ratio() {
+        use super::super::encoding::{Frame, WireEncoding};
+        use flate2::read::GzDecoder;
+        use std::io::Read;
+
+        let stats = EncodingStats::default();
+        assert_eq!(stats.ratio(), 1.0);
+
+        // A large, repetitive frame goes out gzipped; a small one as is
+        let large = serde_json::json!({ "type": "peers_list", "peers": vec!["12D3KooWPeer"; 200] }).to_string();
+        let small = serde_json::json!({ "type": "pong" }).to_string();
+        let mut sent = Vec::new();
+        for json in [&large, &small] {
+            let frame = Compression::Gzip.apply(WireEncoding::Json.frame(json.clone()).unwrap());
+            stats.record(json.len(), frame.len());
+            sent.push(frame);
+        }
+
+        let compressed = match &sent[0] {
+            Frame::Binary(data) => data.clone(),
+            other => panic!("expected a gzipped frame, got {:?}", other),
+        };
+        let mut restored = String::new();
+        GzDecoder::new(compressed.as_slice()).read_to_string(&mut restored).unwrap();
+        assert_eq!(restored, large);
+        assert_eq!(sent[1], Frame::Text(small.clone()));
+
+        // The ratio is over all bytes sent, not an average of per-frame ratios
+        assert_eq!(stats.messages(), 2);
+        assert_eq!(stats.bytes_before(), (large.len() + small.len()) as u64);
+        assert_eq!(stats.bytes_after(), (compressed.len() + small.len()) as u64);
+        let expected = (compressed.len() + small.len()) as f64 / (large.len() + small.len()) as f64;
+        assert!((stats.ratio() - expected).abs() < 1e-12);
+        assert!(stats.ratio() < 0.5);
+    }
+
+    #[test]
+    fn test_raw_protocol_requires_capability() {
+        let (reply_tx, _reply_rx) = mpsc::unbounded_channel();
+        let session = Session::new(reply_tx, 2);
+        let delivery = session.delivery();
+        let raw = WsMessage::RawProtocol {
+            topic: "t".to_string(),
+            json: serde_json::Value::Null,
+        };
+
+        assert!(!delivery.read().wants(&raw));
+        session.set_capability(Capability::RawProtocol, true);
+        assert!(delivery.read().wants(&raw));
+        session.set_capability(Capability::RawProtocol, false);
+        assert!(!delivery.read().wants(&raw));
+    }
+
+    #[test]
+    fn test_event_filter_limits_broadcast_types() {
+        let (reply_tx, _reply_rx) = mpsc::unbounded_channel();
+        let session = Session::new(reply_tx, 2);
+        let delivery = session.delivery();
+        let stats = WsMessage::Stats {
+            peer_count: 1,
+            message_count: 0,
+            topic_counts: Default::default(),
+            uptime_seconds: 0,
+            broadcast_queue_depth: 0,
+            broadcast_high_water: 0,
+            broadcast_capacity: 256,
+            connection_count: 1,
+        };
+        let chat = WsMessage::ChatMessage {
+            id: "m1".to_string(),
+            from: "alice".to_string(),
+            from_name: "alice".to_string(),
+            to: None,
+            room_id: None,
+            content: "hi".to_string(),
+            timestamp: 0,
+        };
+
+        assert!(delivery.read().wants(&stats));
+        session.set_event_filter(HashSet::from(["chat_message".to_string()]));
+        assert!(!delivery.read().wants(&stats));
+        assert!(delivery.read().wants(&chat));
+
+        session.set_event_filter(HashSet::new());
+        assert!(delivery.read().wants(&stats));
+    }
+
+    #[test]
+    fn test_peer_deltas_replace_membership_events() {
+        let (reply_tx, _reply_rx) = mpsc::unbounded_channel();
+        let session = Session::new(reply_tx, 2);
+        let delivery = session.delivery();
+        let joined = WsMessage::PeerJoined { peer_id: "a".to_string(), name: None };
+        let delta = WsMessage::PeerDelta { added: vec![], removed: vec!["a".to_string()] };
+
+        assert!(delivery.read().wants(&joined));
+        assert!(!delivery.read().wants(&delta));
+        session.set_capability(Capability::PeerDeltas, true);
+        assert!(!delivery.read().wants(&joined));
+        assert!(delivery.read().wants(&delta));
+    }
+
+    #[test]
+    fn test_compression_negotiated_by_capability() {
+        let (reply_tx, _reply_rx) = mpsc::unbounded_channel();
+        let session = Session::new(reply_tx, 2);
+        let delivery = session.delivery();
+
+        assert_eq!(delivery.read().compression(), Compression::None);
+        session.set_capability(Capability::CompressGzip, true);
+        assert_eq!(delivery.read().compression(), Compression::Gzip);
+        session.set_capability(Capability::CompressGzip, false);
+        assert_eq!(delivery.read().compression(), Compression::None);
+    }
+
+    #[test]
+    fn test_timestamp_iso_capability_adds_iso_times() {
+        let (reply_tx, _reply_rx) = mpsc::unbounded_channel();
+        let session = Session::new(reply_tx, 2);
+        let delivery = session.delivery();
+        let pong = WsMessage::Pong { nonce: "n".to_string(), server_time: 1_709_296_245_250 };
+
+        let plain: serde_json::Value = serde_json::from_str(&delivery.read().encode(&pong).unwrap()).unwrap();
+        assert!(plain.get("server_time_iso").is_none());
+
+        session.set_capability(Capability::TimestampIso, true);
+        let iso: serde_json::Value = serde_json::from_str(&delivery.read().encode(&pong).unwrap()).unwrap();
+        assert_eq!(iso["server_time"], 1_709_296_245_250_i64);
+        assert_eq!(iso["server_time_iso"], "2024-03-01T12:30:45.250Z");
+    }
+
+    #[test]
+    fn test_locale_adds_display_amount() {
+        let (reply_tx, _reply_rx) = mpsc::unbounded_channel();
+        let session = Session::new(reply_tx, 2);
+        let transfer = WsMessage::CreditTransfer {
+            id: "t1".to_string(),
+            from: "alice".to_string(),
+            from_name: "alice".to_string(),
+            to: "bob".to_string(),
+            to_name: "bob".to_string(),
+            amount: 2500.0,
+            memo: None,
+            request_ref: None,
+            correlation_id: None,
+            timestamp: 0,
+        };
+
+        let plain: serde_json::Value =
+            serde_json::from_str(&session.delivery().read().encode(&transfer).unwrap()).unwrap();
+        assert!(plain.get("display_amount").is_none());
+
+        session.set_locale(Some(Locale::En));
+        let localized: serde_json::Value =
+            serde_json::from_str(&session.delivery().read().encode(&transfer).unwrap()).unwrap();
+        assert_eq!(localized["display_amount"], "2,500.00");
+        assert_eq!(localized["amount"], 2500.0);
+    }
+}
@@ -0,0 +1,106 @@
+//! State sent to a connection when it opens
+//!
+//! After `Hello`, a new connection is bootstrapped with the current state of
+//! each subsystem as the messages it would get by asking: `PeersList`,
+//! `Proposals` (active only), `CreditLinesList`, a `ResourcePoolUpdate` per
+//! resource type and the latest `ChatHistory` page. Clients that don't need
+//! all of it name the sections they want with `?snapshot=peers,chat`;
+//! `?snapshot=none` skips the snapshot.
+
+use serde::{Deserialize, Deserializer};
+use std::collections::BTreeSet;
+use tracing::debug;
+
+/// Part of the connect-time snapshot
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SnapshotSection {
+    Peers,
+    Proposals,
+    CreditLines,
+    Resources,
+    Chat,
+}
+
+impl SnapshotSection {
+    /// Every section, in the order they are sent
+    pub const ALL: [SnapshotSection; 5] = [
+        SnapshotSection::Peers,
+        SnapshotSection::Proposals,
+        SnapshotSection::CreditLines,
+        SnapshotSection::Resources,
+        SnapshotSection::Chat,
+    ];
+
+    /// Parse a section name as given in the `snapshot` parameter
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "peers" => Some(SnapshotSection::Peers),
+            "proposals" => Some(SnapshotSection::Proposals),
+            "credit_lines" => Some(SnapshotSection::CreditLines),
+            "resources" => Some(SnapshotSection::Resources),
+            "chat" => Some(SnapshotSection::Chat),
+            _ => None,
+        }
+    }
+}
+
+/// Sections of the snapshot a connection asked for
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SnapshotSections(BTreeSet<SnapshotSection>);
+
+impl Default for SnapshotSections {
+    /// Every section, for clients that don't say
+    fn default() -> Self {
+        Self(SnapshotSection::ALL.into_iter().collect())
+    }
+}
+
+impl SnapshotSections {
+    /// No sections
+    pub fn none() -> Self {
+        Self(BTreeSet::new())
+    }
+
+    /// Parse a comma-separated list of section names, ignoring unknown names
+    pub fn parse(list: &str) -> Self {
+        let mut sections = Self::none();
+        for name in list.split(',').map(str::trim).filter(|name| !name.is_empty()) {
+            match SnapshotSection::parse(&name.to_ascii_lowercase()) {
+                Some(section) => {
+                    sections.0.insert(section);
+                }
+                None if name.eq_ignore_ascii_case("none") => {}
+                None => debug!("Unknown snapshot section {:?} requested", name),
+            }
+        }
+        sections
+    }
+
+    /// Whether `section` should be sent
+    pub fn includes(&self, section: SnapshotSection) -> bool {
+        self.0.contains(&section)
+    }
+}
+
+/// Parse the `snapshot` parameter, sending every section if it is absent
+pub fn snapshot_sections<'de, D: Deserializer<'de>>(deserializer: D) -> Result<SnapshotSections, D::Error> {
+    let value = Option::<String>::deserialize(deserializer)?;
+    Ok(value.map(|list| SnapshotSections::parse(&list)).unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sections_parsed_from_list() {
+        let sections = SnapshotSections::parse("peers, Chat,bogus");
+        assert!(sections.includes(SnapshotSection::Peers));
+        assert!(sections.includes(SnapshotSection::Chat));
+        assert!(!sections.includes(SnapshotSection::Proposals));
+
+        assert_eq!(SnapshotSections::parse("none"), SnapshotSections::none());
+        assert_eq!(SnapshotSections::parse(""), SnapshotSections::none());
+        assert!(SnapshotSection::ALL.iter().all(|s| SnapshotSections::default().includes(*s)));
+    }
+}
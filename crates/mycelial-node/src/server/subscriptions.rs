@@ -0,0 +1,75 @@
+//! Client topic subscriptions shared across connections
+//!
+//! Several connections may subscribe to the same topic, but the node only
+//! subscribes to it on the network once. Each topic tracks the connections
+//! that asked for it; the network subscription is made for the first and
+//! dropped when the last one unsubscribes or disconnects.
+
+use parking_lot::Mutex;
+use std::collections::{HashMap, HashSet};
+
+/// Connections subscribed to each client-requested topic
+#[derive(Debug, Default)]
+pub struct TopicSubscribers {
+    topics: Mutex<HashMap<String, HashSet<String>>>,
+}
+
+impl TopicSubscribers {
+    /// Record that a connection subscribed, returning whether it is the topic's first subscriber
+    pub fn add(&self, connection_id: &str, topic: &str) -> bool {
+        let mut topics = self.topics.lock();
+        let subscribers = topics.entry(topic.to_string()).or_default();
+        subscribers.insert(connection_id.to_string()) && subscribers.len() == 1
+    }
+
+    /// Record that a connection unsubscribed, returning whether the topic has no subscribers left
+    pub fn remove(&self, connection_id: &str, topic: &str) -> bool {
+        let mut topics = self.topics.lock();
+        let Some(subscribers) = topics.get_mut(topic) else {
+            return false;
+        };
+        if !subscribers.remove(connection_id) {
+            return false;
+        }
+        if subscribers.is_empty() {
+            topics.remove(topic);
+            return true;
+        }
+        false
+    }
+
+    /// Drop all of a closed connection's subscriptions, returning the topics left without subscribers
+    pub fn remove_connection(&self, connection_id: &str) -> Vec<String> {
+        let mut topics = self.topics.lock();
+        let mut released = Vec::new();
+        topics.retain(|topic, subscribers| {
+            if subscribers.remove(connection_id) && subscribers.is_empty() {
+                released.push(topic.clone());
+                return false;
+            }
+            true
+        });
+        released
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_topic_released_by_last_subscriber() {
+        let subscribers = TopicSubscribers::default();
+
+        assert!(subscribers.add("a", "news"));
+        assert!(!subscribers.add("b", "news"));
+        assert!(!subscribers.add("a", "news"));
+        assert!(subscribers.add("a", "sports"));
+
+        assert!(!subscribers.remove("a", "news"));
+        assert!(!subscribers.remove("a", "news"));
+        // Closing the last connection releases everything it held alone
+        assert_eq!(subscribers.remove_connection("b"), vec!["news".to_string()]);
+        assert_eq!(subscribers.remove_connection("a"), vec!["sports".to_string()]);
+    }
+}
@@ -0,0 +1,103 @@
+//! Human-readable timestamps
+//!
+//! Times in server messages are epoch milliseconds, which clients easily
+//! mistake for seconds and which are hard to read in logs. Connections with
+//! the `timestamp_iso` capability get an RFC 3339 string next to each time
+//! field as `<field>_iso`; the numeric fields stay authoritative.
+
+use chrono::{DateTime, SecondsFormat};
+use serde_json::Value;
+
+/// Epoch-millisecond fields that get an `<field>_iso` companion
+const TIME_FIELDS: &[&str] = &[
+    "timestamp",
+    "server_time",
+    "created_at",
+    "edited_at",
+    "deleted_at",
+    "expires_at",
+    "deadline",
+    "first_seen",
+    "last_seen",
+    "connected_at",
+    "last_activity",
+    "read_at",
+    "since",
+    "up_to",
+];
+
+/// Format epoch milliseconds as RFC 3339 in UTC, or `None` if out of range
+pub fn to_rfc3339(millis: i64) -> Option<String> {
+    DateTime::from_timestamp_millis(millis).map(|time| time.to_rfc3339_opts(SecondsFormat::Millis, true))
+}
+
+/// Add `<field>_iso` strings next to the time fields of a message, including
+/// those of nested entries
+pub fn add_iso_fields(message: &mut Value) {
+    match message {
+        Value::Object(object) => {
+            for field in TIME_FIELDS {
+                if let Some(iso) = object.get(*field).and_then(Value::as_i64).and_then(to_rfc3339) {
+                    object.insert(format!("{}_iso", field), Value::String(iso));
+                }
+            }
+            for value in object.values_mut() {
+                if value.is_object() || value.is_array() {
+                    add_iso_fields(value);
+                }
+            }
+        }
+        Value::Array(values) => values.iter_mut().for_each(add_iso_fields),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeZone, Utc};
+
+    #[test]
+    fn test_iso_agrees_with_millis() {
+        let instant = Utc.with_ymd_and_hms(2024, 3, 1, 12, 30, 45).unwrap() + chrono::Duration::milliseconds(250);
+        let millis = instant.timestamp_millis();
+        let iso = to_rfc3339(millis).unwrap();
+        assert_eq!(iso, "2024-03-01T12:30:45.250Z");
+        assert_eq!(DateTime::parse_from_rfc3339(&iso).unwrap().timestamp_millis(), millis);
+        assert_eq!(to_rfc3339(i64::MAX), None);
+    }
+
+    #[test]
+    fn test_iso_fields_added_beside_raw_values() {
+        let mut message = serde_json::json!({
+            "type": "proposals_list",
+            "timestamp": 0,
+            "proposals": [{ "id": "p1", "deadline": 1_000, "title": "Fund the hub" }],
+            "memo": null,
+        });
+        add_iso_fields(&mut message);
+
+        assert_eq!(message["timestamp"], 0);
+        assert_eq!(message["timestamp_iso"], "1970-01-01T00:00:00.000Z");
+        assert_eq!(message["proposals"][0]["deadline_iso"], "1970-01-01T00:00:01.000Z");
+        assert!(message["proposals"][0].get("title_iso").is_none());
+    }
+
+    #[test]
+    fn test_connection_and_read_times_annotated() {
+        let mut message = serde_json::json!({
+            "type": "connections_list",
+            "connections": [{ "connected_at": 1_000, "last_activity": 2_000 }],
+            "read_at": 3_000,
+            "since": 4_000,
+            "up_to": 5_000,
+        });
+        add_iso_fields(&mut message);
+
+        assert_eq!(message["connections"][0]["connected_at_iso"], "1970-01-01T00:00:01.000Z");
+        assert_eq!(message["connections"][0]["last_activity_iso"], "1970-01-01T00:00:02.000Z");
+        assert_eq!(message["read_at_iso"], "1970-01-01T00:00:03.000Z");
+        assert_eq!(message["since_iso"], "1970-01-01T00:00:04.000Z");
+        assert_eq!(message["up_to_iso"], "1970-01-01T00:00:05.000Z");
+    }
+}
@@ -0,0 +1,503 @@
+//! Vouch requests from dashboard clients
+//!
+//! Vouching for peers, answering and revoking vouches, and the vouch queries.
+
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+use crate::AppState;
+use crate::naming::{self, NameCache};
+use crate::peer_ids::parse_peer_id;
+use crate::vouch_reputation;
+use super::handler_error::HandlerError;
+use super::messages::{ClientMessage, VouchEntry, VouchRequestEntry, VouchedPeerEntry, WsMessage};
+use super::session::Session;
+use super::websocket::publish_economics;
+use mycelial_state::VouchRecord;
+use mycelial_protocol::{
+    topics, Correlated, Signed,
+    VouchMessage, VouchRequest, VouchAck as ProtocolVouchAck, VouchRevoke as ProtocolVouchRevoke,
+};
+
+/// Number of most-vouched peers included in vouch statistics
+const TOP_VOUCHED_PEERS: usize = 10;
+
+/// Carry out a vouch request routed here by the WebSocket handler
+pub(super) async fn handle_request(msg: ClientMessage, state: &AppState, session: &mut Session) -> Result<(), HandlerError> {
+    match msg {
+        ClientMessage::SendVouch { vouchee, weight, message, correlation_id } => {
+            let vouchee = match parse_peer_id("vouchee", &vouchee) {
+                Ok(peer_id) => peer_id.to_string(),
+                Err(message) => {
+                    return Err(HandlerError::invalid(message));
+                }
+            };
+            info!("SendVouch: vouchee='{}', weight={}", vouchee, weight);
+
+            if vouchee == state.local_peer_id.to_string() {
+                return Err(HandlerError::invalid("Cannot vouch for yourself"));
+            }
+
+            let timestamp = chrono::Utc::now().timestamp_millis();
+
+            // Create vouch request message (uses stake, not weight)
+            let mut vouch_req = VouchRequest::new(
+                state.local_peer_id.to_string(),
+                vouchee.clone(),
+                weight, // VouchRequest calls this 'stake'
+            )
+            .with_correlation_id(correlation_id.clone())
+            .with_signature(session.take_action_signature());
+            if let Some(msg) = message {
+                vouch_req = vouch_req.with_message(msg);
+            }
+            let request_id = vouch_req.id.to_string();
+            let record = VouchRecord {
+                id: request_id.clone(),
+                voucher: vouch_req.voucher.clone(),
+                vouchee: vouchee.clone(),
+                weight,
+                message: vouch_req.message.clone(),
+                status: "pending".to_string(),
+                created_at: timestamp,
+            };
+            let vouch_msg = VouchMessage::VouchRequest(vouch_req);
+
+            // Serialize and publish to network
+            match serde_json::to_vec(&vouch_msg) {
+                Ok(data) => {
+                    if publish_economics(state, session, topics::VOUCH, data, "vouch request").await? {
+                        info!("Vouch request published successfully");
+                        if let Err(e) = state.store.insert_vouch(&record).await {
+                            warn!("Failed to store vouch: {}", e);
+                        }
+
+                        // Local echo for the sender
+                        let echo_msg = WsMessage::VouchRequest {
+                            id: request_id,
+                            voucher: state.local_peer_id.to_string(),
+                            voucher_name: state.node_name(),
+                            vouchee_name: naming::resolve_display_name(state, &vouchee).await,
+                            vouchee,
+                            weight,
+                            correlation_id,
+                            timestamp,
+                        };
+                        let _ = state.event_tx.send(echo_msg);
+                    }
+                }
+                Err(e) => {
+                    error!("Failed to serialize vouch request: {}", e);
+                    return Err(HandlerError::internal("Failed to serialize vouch request"));
+                }
+            }
+        }
+
+        ClientMessage::RespondVouch { request_id, accept, correlation_id } => {
+            info!("RespondVouch: request_id='{}', accept={}", request_id, accept);
+
+            let timestamp = chrono::Utc::now().timestamp_millis();
+
+            // Parse request_id as UUID
+            let vouch_id = match Uuid::parse_str(&request_id) {
+                Ok(id) => id,
+                Err(e) => return Err(HandlerError::invalid(format!("Invalid vouch request ID: {}", e))),
+            };
+
+            // Create vouch ack message with correct fields
+            let ack_msg = VouchMessage::VouchAck(ProtocolVouchAck {
+                vouch_id,
+                from: state.local_peer_id.to_string(),
+                accepted: accept,
+                reason: None,
+                timestamp: chrono::Utc::now(),
+                correlation_id: correlation_id.clone(),
+                signature: session.take_action_signature(),
+            });
+
+            match serde_json::to_vec(&ack_msg) {
+                Ok(data) => {
+                    if publish_economics(state, session, topics::VOUCH, data, "vouch ack").await? {
+                        let new_reputation =
+                            vouch_reputation::record_vouch_response(state, &request_id, accept, timestamp).await;
+                        let echo_msg = WsMessage::VouchAck {
+                            id: Uuid::new_v4().to_string(),
+                            request_id,
+                            accepted: accept,
+                            new_reputation,
+                            correlation_id,
+                            timestamp,
+                        };
+                        let _ = state.event_tx.send(echo_msg);
+                    }
+                }
+                Err(e) => {
+                    error!("Failed to serialize vouch ack: {}", e);
+                    return Err(HandlerError::internal("Failed to serialize vouch ack"));
+                }
+            }
+        }
+
+        ClientMessage::RevokeVouch { vouchee, correlation_id } => {
+            let vouchee = match parse_peer_id("vouchee", &vouchee) {
+                Ok(peer_id) => peer_id.to_string(),
+                Err(message) => {
+                    return Err(HandlerError::invalid(message));
+                }
+            };
+            info!("RevokeVouch: vouchee='{}'", vouchee);
+            let voucher = state.local_peer_id.to_string();
+
+            // Only vouches this node made can be revoked
+            match state.store.active_vouches(&voucher, &vouchee).await {
+                Ok(vouches) if vouches.is_empty() => {
+                    return Err(HandlerError::invalid(format!("No vouch for {} to revoke", vouchee)));
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    error!("Failed to load vouches for {}: {}", vouchee, e);
+                    return Err(HandlerError::internal("Failed to load vouches"));
+                }
+            }
+
+            let revoke = ProtocolVouchRevoke::new(voucher.clone(), vouchee.clone())
+                .with_correlation_id(correlation_id)
+                .with_signature(session.take_action_signature());
+            let timestamp = revoke.timestamp.timestamp_millis();
+
+            match serde_json::to_vec(&VouchMessage::VouchRevoke(revoke)) {
+                Ok(data) => {
+                    if publish_economics(state, session, topics::VOUCH, data, "vouch revocation").await? {
+                        vouch_reputation::record_vouch_revocation(state, &voucher, &vouchee, timestamp).await;
+                        let _ = state.event_tx.send(WsMessage::VouchRevoked { voucher, vouchee, timestamp });
+                    }
+                }
+                Err(e) => {
+                    error!("Failed to serialize vouch revocation: {}", e);
+                    return Err(HandlerError::internal("Failed to serialize vouch revocation"));
+                }
+            }
+        }
+
+        ClientMessage::GetVouchStats => {
+            match state.store.vouch_stats(TOP_VOUCHED_PEERS).await {
+                Ok(stats) => session.reply(WsMessage::VouchStats {
+                    total_vouches: stats.total_vouches,
+                    avg_weight: stats.avg_weight,
+                    most_vouched_peers: stats
+                        .most_vouched_peers
+                        .into_iter()
+                        .map(|(peer_id, total_weight)| VouchedPeerEntry { peer_id, total_weight })
+                        .collect(),
+                    pending_count: stats.pending_count,
+                }),
+                Err(e) => {
+                    error!("Failed to compute vouch stats: {}", e);
+                    return Err(HandlerError::internal("Failed to compute vouch stats"));
+                }
+            }
+        }
+
+        ClientMessage::GetPendingVouches => {
+            match state.store.list_pending_vouches_for(&state.local_peer_id.to_string()).await {
+                Ok(pending) => {
+                    let mut names = NameCache::default();
+                    let mut requests = Vec::with_capacity(pending.len());
+                    for vouch in pending {
+                        requests.push(VouchRequestEntry {
+                            voucher_name: names.display_name(state, &vouch.voucher).await,
+                            id: vouch.id,
+                            voucher: vouch.voucher,
+                            weight: vouch.weight,
+                            message: vouch.message,
+                            created_at: vouch.created_at,
+                        });
+                    }
+                    session.reply(WsMessage::PendingVouches { requests });
+                }
+                Err(e) => {
+                    error!("Failed to load pending vouches: {}", e);
+                    return Err(HandlerError::internal("Failed to load pending vouches"));
+                }
+            }
+        }
+
+        ClientMessage::GetMyVouches => {
+            let local = state.local_peer_id.to_string();
+            let vouches = match state.store.list_peer_vouches(&local).await {
+                Ok(vouches) => vouches,
+                Err(e) => {
+                    error!("Failed to load vouches: {}", e);
+                    return Err(HandlerError::internal("Failed to load vouches"));
+                }
+            };
+            let (given, received): (Vec<_>, Vec<_>) = vouches
+                .into_iter()
+                .map(|v| VouchEntry {
+                    accepted: v.status == "accepted",
+                    id: v.id,
+                    voucher: v.voucher,
+                    vouchee: v.vouchee,
+                    weight: v.weight,
+                    status: v.status,
+                    timestamp: v.created_at,
+                })
+                .partition(|entry| entry.voucher == local);
+            session.reply(WsMessage::VouchList { given, received });
+        }
+
+        other => unreachable!("not a vouch request: {:?}", other),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::messages::ErrorCode;
+    use crate::server::websocket::handle_client_message;
+    use crate::test_support::{remote_peer, test_session, test_state};
+    use mycelial_network::NetworkCommand;
+
+    #[tokio::test]
+    async fn test_vouch_response_broadcasts_reputation_change() {
+        let (state, _commands) = test_state().await;
+        let (mut session, _replies) = test_session(8);
+        let mut events = state.event_tx.subscribe();
+        let local = state.local_peer_id.to_string();
+        let mut requests = Vec::new();
+        for voucher in ["alice", "bob"] {
+            let record = VouchRecord {
+                id: Uuid::new_v4().to_string(),
+                voucher: voucher.to_string(),
+                vouchee: local.clone(),
+                weight: 1.0,
+                message: None,
+                status: "pending".to_string(),
+                created_at: 0,
+            };
+            state.store.insert_vouch(&record).await.unwrap();
+            requests.push(record.id);
+        }
+        let respond = |request_id: &str, accept| ClientMessage::RespondVouch {
+            request_id: request_id.to_string(),
+            accept,
+            correlation_id: None,
+        };
+
+        // Rejecting leaves this node's reputation alone
+        handle_client_message(respond(&requests[0], false), &state, &mut session).await;
+        match events.try_recv() {
+            Ok(WsMessage::VouchAck { accepted, new_reputation, .. }) => {
+                assert!(!accepted);
+                assert_eq!(new_reputation, None);
+            }
+            other => panic!("expected vouch ack, got {:?}", other),
+        }
+        assert!(events.try_recv().is_err());
+        assert_eq!(vouch_reputation::reputation_of(&state, &local).await.unwrap().score, 0.5);
+
+        // Accepting raises it, and every client hears of the change
+        handle_client_message(respond(&requests[1], true), &state, &mut session).await;
+        let new_score = match events.try_recv() {
+            Ok(WsMessage::ReputationUpdate { peer_id, new_score }) => {
+                assert_eq!(peer_id, local);
+                new_score
+            }
+            other => panic!("expected reputation update, got {:?}", other),
+        };
+        assert!(new_score > 0.5);
+        match events.try_recv() {
+            Ok(WsMessage::VouchAck { accepted, new_reputation, .. }) => {
+                assert!(accepted);
+                assert_eq!(new_reputation, Some(new_score));
+            }
+            other => panic!("expected vouch ack, got {:?}", other),
+        }
+        assert_eq!(vouch_reputation::reputation_of(&state, &local).await.unwrap().score, new_score);
+    }
+
+    #[tokio::test]
+    async fn test_self_vouch_rejected() {
+        let (state, mut commands) = test_state().await;
+        let (mut session, mut replies) = test_session(8);
+
+        let vouch = ClientMessage::SendVouch {
+            vouchee: state.local_peer_id.to_string(),
+            weight: 1.0,
+            message: None,
+            correlation_id: None,
+        };
+        handle_client_message(vouch, &state, &mut session).await;
+        match replies.try_recv() {
+            Ok(WsMessage::Error { code, message }) => {
+                assert_eq!(code, ErrorCode::InvalidRequest);
+                assert_eq!(message, "Cannot vouch for yourself");
+            }
+            other => panic!("expected invalid request, got {:?}", other),
+        }
+        assert!(commands.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_revoke_vouch() {
+        let (state, mut commands) = test_state().await;
+        let (mut session, mut replies) = test_session(8);
+        let vouchee = remote_peer();
+        let revoke = |vouchee: &str| ClientMessage::RevokeVouch {
+            vouchee: vouchee.to_string(),
+            correlation_id: None,
+        };
+
+        let vouch = ClientMessage::SendVouch {
+            vouchee: vouchee.clone(),
+            weight: 0.5,
+            message: None,
+            correlation_id: None,
+        };
+        handle_client_message(vouch, &state, &mut session).await;
+        assert!(matches!(commands.try_recv(), Ok(NetworkCommand::Publish { .. })));
+        let mut events = state.event_tx.subscribe();
+
+        handle_client_message(revoke(&vouchee), &state, &mut session).await;
+        match commands.try_recv() {
+            Ok(NetworkCommand::Publish { topic, data }) => {
+                assert_eq!(topic, topics::VOUCH);
+                match serde_json::from_slice::<VouchMessage>(&data).unwrap() {
+                    VouchMessage::VouchRevoke(published) => {
+                        assert_eq!(published.voucher, state.local_peer_id.to_string());
+                        assert_eq!(published.vouchee, vouchee);
+                    }
+                    other => panic!("expected vouch revocation, got {:?}", other),
+                }
+            }
+            other => panic!("expected publish, got {:?}", other),
+        }
+        match events.try_recv() {
+            Ok(WsMessage::VouchRevoked { voucher, vouchee: revoked, .. }) => {
+                assert_eq!(voucher, state.local_peer_id.to_string());
+                assert_eq!(revoked, vouchee);
+            }
+            other => panic!("expected vouch revoked, got {:?}", other),
+        }
+        let local = state.local_peer_id.to_string();
+        assert!(state.store.active_vouches(&local, &vouchee).await.unwrap().is_empty());
+
+        // Nothing is left to revoke
+        handle_client_message(revoke(&vouchee), &state, &mut session).await;
+        assert!(matches!(
+            replies.try_recv(),
+            Ok(WsMessage::Error { code: ErrorCode::InvalidRequest, .. })
+        ));
+
+        // Another node's vouch can't be revoked from here
+        let other = remote_peer();
+        let record = VouchRecord {
+            id: Uuid::new_v4().to_string(),
+            voucher: remote_peer(),
+            vouchee: other.clone(),
+            weight: 0.5,
+            message: None,
+            status: "accepted".to_string(),
+            created_at: 0,
+        };
+        state.store.insert_vouch(&record).await.unwrap();
+        handle_client_message(revoke(&other), &state, &mut session).await;
+        match replies.try_recv() {
+            Ok(WsMessage::Error { code: ErrorCode::InvalidRequest, message }) => {
+                assert!(message.starts_with("No vouch for"), "{}", message);
+            }
+            other => panic!("expected invalid request, got {:?}", other),
+        }
+        assert_eq!(state.store.get_vouch(&record.id).await.unwrap().unwrap().status, "accepted");
+        assert!(commands.try_recv().is_err());
+        assert!(events.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_my_vouches_split_by_direction() {
+        let (state, _commands) = test_state().await;
+        let (mut session, mut replies) = test_session(8);
+        let local = state.local_peer_id.to_string();
+        for (id, voucher, vouchee, created_at) in [
+            ("given-old", local.as_str(), "bob", 1),
+            ("given-new", local.as_str(), "carol", 2),
+            ("received", "alice", local.as_str(), 3),
+            ("unrelated", "alice", "bob", 4),
+        ] {
+            let record = VouchRecord {
+                id: id.to_string(),
+                voucher: voucher.to_string(),
+                vouchee: vouchee.to_string(),
+                weight: 0.5,
+                message: None,
+                status: "pending".to_string(),
+                created_at,
+            };
+            state.store.insert_vouch(&record).await.unwrap();
+        }
+        state.store.respond_vouch("received", true, 5).await.unwrap();
+
+        handle_client_message(ClientMessage::GetMyVouches, &state, &mut session).await;
+        match replies.try_recv() {
+            Ok(WsMessage::VouchList { given, received }) => {
+                let given: Vec<_> = given.iter().map(|v| (v.id.as_str(), v.vouchee.as_str(), v.timestamp)).collect();
+                assert_eq!(given, vec![("given-new", "carol", 2), ("given-old", "bob", 1)]);
+                assert_eq!(received.len(), 1);
+                assert_eq!(received[0].voucher, "alice");
+                assert_eq!(received[0].weight, 0.5);
+                assert!(received[0].accepted);
+                assert_eq!(received[0].status, "accepted");
+            }
+            other => panic!("expected vouch list, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_pending_vouch_inbox() {
+        let (state, _commands) = test_state().await;
+        let (mut session, mut replies) = test_session(8);
+        let local = state.local_peer_id.to_string();
+        let mut ids = Vec::new();
+        for (voucher, vouchee) in [("alice", local.as_str()), ("bob", local.as_str()), ("carol", "dave")] {
+            let request = VouchRequest::new(voucher.to_string(), vouchee.to_string(), 0.5);
+            ids.push(request.id.to_string());
+            let data = serde_json::to_vec(&VouchMessage::VouchRequest(request)).unwrap();
+            let event = mycelial_network::NetworkEvent::MessageReceived {
+                message_id: mycelial_network::MessageId::from(data.clone()),
+                topic: topics::VOUCH.to_string(),
+                source: None,
+                data,
+                timestamp: chrono::Utc::now(),
+            };
+            let libp2p_local = mycelial_network::Keypair::generate_ed25519().public().to_peer_id();
+            crate::handle_network_event(event, &state, libp2p_local).await;
+        }
+
+        handle_client_message(ClientMessage::GetPendingVouches, &state, &mut session).await;
+        match replies.try_recv() {
+            Ok(WsMessage::PendingVouches { requests }) => {
+                let mut vouchers: Vec<_> = requests.iter().map(|r| r.voucher.as_str()).collect();
+                vouchers.sort();
+                assert_eq!(vouchers, vec!["alice", "bob"]);
+            }
+            other => panic!("expected pending vouches, got {:?}", other),
+        }
+
+        let respond = ClientMessage::RespondVouch {
+            request_id: ids[0].clone(),
+            accept: true,
+            correlation_id: None,
+        };
+        handle_client_message(respond, &state, &mut session).await;
+        handle_client_message(ClientMessage::GetPendingVouches, &state, &mut session).await;
+        match replies.try_recv() {
+            Ok(WsMessage::PendingVouches { requests }) => {
+                assert_eq!(requests.len(), 1);
+                assert_eq!(requests[0].voucher, "bob");
+                assert_eq!(requests[0].id, ids[1]);
+            }
+            other => panic!("expected pending vouches, got {:?}", other),
+        }
+    }
+}
@@ -1,139 +1,926 @@
 //! WebSocket connection handling
 //!
-//! This module handles WebSocket connections from dashboard clients,
-//! including support for economics protocol messages.
+//! This module handles WebSocket connections from dashboard clients.
+//! Economics and room requests are routed to their own modules
+//! ([`vouches`](super::vouches), [`credit`](super::credit),
+//! [`governance`](super::governance), [`resources`](super::resources) and
+//! [`rooms`](super::rooms)).
 
 use axum::{
     extract::{
-        ws::{Message, WebSocket, WebSocketUpgrade},
-        State,
+        ws::{close_code, CloseFrame, Message, WebSocket, WebSocketUpgrade},
+        Query, State,
     },
-    response::IntoResponse,
+    http::HeaderMap,
+    response::{IntoResponse, Response},
 };
+use futures::stream::SplitSink;
 use futures::{SinkExt, StreamExt};
+use parking_lot::{Mutex, RwLock};
+use std::collections::HashSet;
 use std::sync::Arc;
-use tracing::{info, warn, error};
-use uuid::Uuid;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio::sync::mpsc;
+use tracing::{debug, info, warn, error};
 
 use crate::AppState;
-use super::messages::{WsMessage, ClientMessage, PeerListEntry};
-use mycelial_protocol::{
-    topics,
-    VouchMessage, VouchRequest, VouchAck as ProtocolVouchAck,
-    CreditMessage, CreateCreditLine as ProtocolCreateCreditLine, CreditTransfer as ProtocolCreditTransfer,
-    GovernanceMessage, CreateProposal as ProtocolCreateProposal, CastVote as ProtocolCastVote, Vote,
-    ResourceMessage, ResourceContribution as ProtocolResourceContribution, ResourceType,
+use crate::anti_entropy;
+use crate::chat_edits::{self, AmendError, ChatAmendment};
+use crate::naming;
+use crate::network_errors;
+use crate::peer_ids::parse_peer_id;
+use crate::presence;
+use crate::replay::MessageCategory;
+use crate::stats_history::{current_stats, to_points};
+use super::messages::{
+    WsMessage, ClientMessage, Capability, ChatHistoryEntry, ErrorCode, PeerListEntry, PeerVouchEntry, ReplayedEvent,
+    SubscribeResultEntry, FEATURES, PROTOCOL_VERSION,
 };
+use super::auth::{authorize_upgrade, tokens_match, Accepted, TokenParams};
+use super::checkpoint::CheckpointTracker;
+use super::{credit, governance, resources, rooms, vouches};
+use super::collapse::CollapseBuffer;
+use super::dedup::DeliveredIds;
+use super::encoding::{decode_binary, ConnectParams, WireEncoding};
+use super::handler_error::HandlerError;
+use super::heartbeat::Activity;
+use super::locale::Locale;
+use super::rate_limit::TokenBucket;
+use super::schema;
+use super::outbound::{Next, OutboundQueue, Priority, Pushed, QueuedFrame};
+use super::session::{DeliveryPrefs, EncodingStats, Session};
+use super::snapshot::{SnapshotSection, SnapshotSections};
+use mycelial_core::identity::SignatureBytes;
+use mycelial_network::{Libp2pPeerId, Libp2pPublicKey};
+use mycelial_state::LoggedEvent;
+use mycelial_state::stats::downsample;
+use mycelial_protocol::{topics, ActionSignature, MAX_CORRELATION_ID_LEN};
+
+/// Most missed events a resume delivers before requiring a full resync
+const MAX_RESUME_EVENTS: usize = 500;
+
+/// Default and maximum page sizes for event replay
+const DEFAULT_REPLAY_PAGE: usize = 100;
+const MAX_REPLAY_PAGE: usize = 500;
+
+/// Maximum topics accepted in a single bulk subscription
+const MAX_SUBSCRIBE_MANY: usize = 32;
+
+/// Maximum page size for chat history
+const MAX_CHAT_HISTORY_PAGE: usize = 200;
+
+/// Resource types whose pools are included in the connect-time snapshot
+const SNAPSHOT_RESOURCE_TYPES: [&str; 4] = ["bandwidth", "storage", "compute", "relay"];
+
+/// Maximum peer IDs accepted in a single bulk peer lookup
+const MAX_PEERS_BULK: usize = 100;
+
+/// Maximum peers returned by a single peer search
+const MAX_PEER_SEARCH_RESULTS: usize = 100;
+
+/// Maximum length of a client-supplied topic name
+const MAX_TOPIC_LEN: usize = 256;
+
+/// Maximum length of the local node's name, in characters
+const MAX_NICKNAME_LEN: usize = 64;
+
+/// A page of chat visible to `identity`, newest first, and whether older messages remain
+async fn chat_history(
+    state: &AppState,
+    identity: Option<&str>,
+    before: Option<(i64, i64)>,
+    limit: usize,
+) -> Result<(Vec<ChatHistoryEntry>, bool), HandlerError> {
+    state.event_log_fence.wait().await;
+    // One extra row tells whether older messages remain
+    let logged = match state.store.list_messages(identity, before, limit as i64 + 1).await {
+        Ok(logged) => logged,
+        Err(e) => {
+            error!("Failed to load chat history: {}", e);
+            return Err(HandlerError::internal("Failed to load chat history"));
+        }
+    };
+    let has_more = logged.len() > limit;
+    let mut messages: Vec<ChatHistoryEntry> = logged
+        .iter()
+        .take(limit)
+        .filter_map(|event| {
+            let mut message: ChatHistoryEntry = serde_json::from_str(&event.payload_json).ok()?;
+            message.seq = event.seq;
+            Some(message)
+        })
+        .collect();
+    let ids: Vec<String> = messages.iter().map(|message| message.id.clone()).collect();
+    let mut reactions = match state.store.reaction_counts_for(&ids).await {
+        Ok(reactions) => reactions,
+        Err(e) => {
+            warn!("Failed to load reactions for chat history: {}", e);
+            Default::default()
+        }
+    };
+    for message in &mut messages {
+        message.reactions = reactions.remove(&message.id).unwrap_or_default();
+        if message.to.is_some() {
+            match state.store.read_at(&message.id).await {
+                Ok(read_at) => message.read_at = read_at,
+                Err(e) => warn!("Failed to load read state for {}: {}", message.id, e),
+            }
+        }
+    }
+    Ok((messages, has_more))
+}
+
+/// Check that a client-supplied topic name is safe to subscribe to
+fn validate_topic(topic: &str) -> Result<(), String> {
+    if topic.is_empty() {
+        return Err("Topic must not be empty".to_string());
+    }
+    if topic.len() > MAX_TOPIC_LEN {
+        return Err(format!("Topic exceeds {} bytes", MAX_TOPIC_LEN));
+    }
+    if topic.chars().any(|c| c.is_whitespace() || c.is_control()) {
+        return Err("Topic must not contain whitespace or control characters".to_string());
+    }
+    Ok(())
+}
+
+/// Why a topic subscription was refused
+pub(super) enum SubscribeError {
+    /// The connection already holds its maximum number of subscriptions
+    LimitReached(usize),
+    /// The topic was invalid or the network rejected it
+    Failed(String),
+}
+
+impl SubscribeError {
+    pub(super) fn message(self) -> String {
+        match self {
+            SubscribeError::LimitReached(limit) => format!("Subscription limit of {} reached", limit),
+            SubscribeError::Failed(message) => message,
+        }
+    }
+}
+
+/// Subscribe the node to a topic on behalf of a connection
+///
+/// The connection's subscription is rolled back if the network rejects it.
+pub(super) async fn subscribe_topic(state: &AppState, session: &mut Session, topic: &str) -> Result<(), SubscribeError> {
+    validate_topic(topic).map_err(SubscribeError::Failed)?;
+    if let Err(e) = session.add_subscription(topic) {
+        return Err(SubscribeError::LimitReached(e.limit));
+    }
+    // Only the topic's first subscriber subscribes the node
+    if !state.topic_subscribers.add(session.id(), topic) {
+        return Ok(());
+    }
+    if let Err(e) = state.network.subscribe(&state.wire_topic(topic)).await {
+        error!("Failed to subscribe to topic {}: {}", topic, e);
+        network_errors::report(state, "subscribe", &e);
+        state.topic_subscribers.remove(session.id(), topic);
+        session.remove_subscription(topic);
+        return Err(SubscribeError::Failed(e.to_string()));
+    }
+    Ok(())
+}
+
+/// Unsubscribe a connection from a topic it subscribed to
+pub(super) async fn unsubscribe_topic(state: &AppState, session: &mut Session, topic: &str) -> Result<(), String> {
+    if !session.remove_subscription(topic) {
+        return Err(format!("Not subscribed to {}", topic));
+    }
+    if state.topic_subscribers.remove(session.id(), topic) {
+        release_topic(state, topic).await;
+    }
+    Ok(())
+}
+
+/// Unsubscribe the node from a topic no connection needs any more
+///
+/// Protocol topics (under `/mycelial/`) are left alone, since the node
+/// relies on them regardless of what clients asked for. Room topics are the
+/// exception: the node only follows a room while a connection is in it.
+pub(super) async fn release_topic(state: &AppState, topic: &str) {
+    if topic.starts_with("/mycelial/") && topics::room_id(topic).is_none() {
+        return;
+    }
+    if let Err(e) = state.network.unsubscribe(&state.wire_topic(topic)).await {
+        error!("Failed to unsubscribe from topic {}: {}", topic, e);
+        network_errors::report(state, "unsubscribe", &e);
+    }
+}
+
+/// Bytes a client signs to identify as a peer other than this node
+///
+/// Naming the connection keeps a signature from being replayed on another one.
+pub(crate) fn identify_challenge(connection_id: &str) -> String {
+    format!("mycelial-identify:{}", connection_id)
+}
+
+/// Whether `signature` (hex) is `peer`'s signature over the connection's challenge
+///
+/// Ed25519 peer IDs embed the public key, so no key exchange is needed.
+fn verify_identify(peer: &Libp2pPeerId, connection_id: &str, signature: &str) -> bool {
+    let Ok(public_key) = Libp2pPublicKey::try_decode_protobuf(peer.as_ref().digest()) else {
+        return false;
+    };
+    let Ok(signature) = SignatureBytes::from_hex(signature) else {
+        return false;
+    };
+    public_key.verify(identify_challenge(connection_id).as_bytes(), &signature.to_bytes())
+}
+
+/// Publish an edit, deletion or reaction to chat, then apply it locally
+async fn amend_chat(state: &AppState, session: &Session, amendment: ChatAmendment) -> Result<(), HandlerError> {
+    let local = state.local_peer_id.to_string();
+    let target = match chat_edits::authorize(state, &amendment, &local).await {
+        Ok(target) => target,
+        Err(e) => return amend_failure(amendment.message_id(), e),
+    };
+
+    let payload = match serde_json::to_vec(&amendment) {
+        Ok(payload) => payload,
+        Err(e) => {
+            error!("Failed to serialize chat amendment: {}", e);
+            return Err(HandlerError::internal("Failed to serialize chat amendment"));
+        }
+    };
+    let msg = mycelial_core::message::Message::new(
+        mycelial_core::message::MessageType::System,
+        state.local_peer_id.clone(),
+        payload,
+    );
+    let data = match serde_json::to_vec(&msg) {
+        Ok(data) => data,
+        Err(e) => {
+            error!("Failed to serialize chat amendment: {}", e);
+            return Err(HandlerError::internal("Failed to serialize chat amendment"));
+        }
+    };
+    if let Err(e) = state.network.publish(state.wire_topic(&target.topic), data).await {
+        error!("Failed to publish chat amendment: {}", e);
+        network_errors::report(state, "publish", &e);
+        session.record_failure(format!("Failed to publish chat amendment: {}", e));
+        return Ok(());
+    }
+    state.topic_counts.record(&target.topic);
+
+    let message_id = amendment.message_id().to_string();
+    match chat_edits::apply(state, &local, amendment, chrono::Utc::now().timestamp_millis()).await {
+        Ok(event) => {
+            let _ = state.event_tx.send(event);
+            Ok(())
+        }
+        Err(e) => amend_failure(&message_id, e),
+    }
+}
+
+/// Check chat content against the configured size limit
+fn check_chat_content(state: &AppState, content: &str) -> Result<(), String> {
+    if content.len() > state.config.max_chat_content {
+        return Err(format!(
+            "Chat content of {} bytes exceeds the limit of {} bytes",
+            content.len(),
+            state.config.max_chat_content
+        ));
+    }
+    Ok(())
+}
+
+/// Outcome of a chat amendment that couldn't be made
+fn amend_failure(message_id: &str, error: AmendError) -> Result<(), HandlerError> {
+    match error {
+        AmendError::NotFound => Err(HandlerError::invalid(format!("Unknown chat message {}", message_id))),
+        AmendError::NotAuthor => Err(HandlerError::forbidden("Only the author can change a chat message")),
+        AmendError::NotRecipient => {
+            // Only direct messages get receipts; reading anything else is not an error
+            debug!("Not sending a read receipt for {}, it was not sent directly to us", message_id);
+            Ok(())
+        }
+        AmendError::InvalidEmoji => Err(HandlerError::invalid("Reaction must be a single emoji")),
+        AmendError::Deleted => Err(HandlerError::invalid(format!("Chat message {} was deleted", message_id))),
+        AmendError::Store => Err(HandlerError::internal("Failed to update chat message")),
+    }
+}
+
+/// Publish an economics action to the network
+///
+/// Returns `Ok(true)` only if the action was handed to the network, so
+/// callers echo it as sent; a failed publish is reported to every client as a
+/// network error instead. While the node has no connected peers, publishing
+/// would silently go nowhere, so the action is rejected with an error (unless
+/// the node is configured to attempt it anyway).
+pub(super) async fn publish_economics(
+    state: &AppState,
+    session: &Session,
+    topic: &str,
+    data: Vec<u8>,
+    action: &str,
+) -> Result<bool, HandlerError> {
+    if state.config.reject_when_isolated && state.is_isolated() {
+        warn!("Rejecting {} while the network is isolated", action);
+        return Err(HandlerError::internal("network isolated"));
+    }
+    if let Err(e) = state.network.publish(state.wire_topic(topic), data).await {
+        error!("Failed to publish {}: {}", action, e);
+        network_errors::report(state, "publish", &e);
+        session.record_failure(format!("Failed to publish {}: {}", action, e));
+        return Ok(false);
+    }
+    state.topic_counts.record(topic);
+    Ok(true)
+}
+
+/// Reply with the unread counts of the identity the connection acts for
+async fn send_unread_counts(state: &AppState, session: &Session) -> Result<(), HandlerError> {
+    let identity = session.identity().unwrap_or_else(|| state.local_peer_id.to_string());
+    // Chat is counted from the event log, so include this connection's writes
+    state.event_log_fence.wait().await;
+    match state.store.unread_counts(&identity).await {
+        Ok(counts) => {
+            session.reply(WsMessage::UnreadCounts {
+                chat: counts.chat,
+                proposals: counts.proposals,
+                vouches: counts.vouches,
+            });
+            Ok(())
+        }
+        Err(e) => {
+            error!("Failed to count unread items: {}", e);
+            Err(HandlerError::internal("Failed to count unread items"))
+        }
+    }
+}
+
+/// Decode logged events for replay, skipping any that can't be read
+fn to_replayed(logged: Vec<LoggedEvent>) -> Vec<ReplayedEvent> {
+    logged
+        .into_iter()
+        .filter_map(|e| {
+            serde_json::from_str(&e.payload_json)
+                .map(|event| ReplayedEvent { seq: e.seq, timestamp: e.timestamp, event })
+                .map_err(|err| warn!("Skipping unreadable logged event {}: {}", e.seq, err))
+                .ok()
+        })
+        .collect()
+}
 
 /// Handle WebSocket upgrade
+///
+/// The upgrade must carry the configured token (see [`super::auth`]);
+/// `?encoding=msgpack` switches the connection to MessagePack binary frames,
+/// and `?snapshot=` picks the state sent once it opens (see [`super::snapshot`]).
 pub async fn ws_handler(
     ws: WebSocketUpgrade,
+    Query(params): Query<ConnectParams>,
+    Query(auth): Query<TokenParams>,
+    headers: HeaderMap,
     State(state): State<Arc<AppState>>,
-) -> impl IntoResponse {
-    ws.on_upgrade(|socket| handle_socket(socket, state))
+) -> Response {
+    // Oversized messages are refused by the transport before they are buffered
+    let ws = ws
+        .max_message_size(state.config.max_frame_size)
+        .max_frame_size(state.config.max_frame_size);
+    let ws = match authorize_upgrade(&state.config, auth.token.as_deref(), &headers) {
+        Ok(Accepted::Plain) => ws,
+        Ok(Accepted::Protocol(protocol)) => ws.protocols([protocol]),
+        Err(status) => {
+            warn!("Rejected unauthenticated WebSocket upgrade");
+            return status.into_response();
+        }
+    };
+    ws.on_upgrade(move |socket| handle_socket(socket, state, params))
+        .into_response()
 }
 
 /// Handle individual WebSocket connection
-async fn handle_socket(socket: WebSocket, state: Arc<AppState>) {
-    info!("New WebSocket connection established");
+async fn handle_socket(socket: WebSocket, state: Arc<AppState>, params: ConnectParams) {
+    let ConnectParams { encoding, snapshot } = params;
+    info!("New WebSocket connection established ({:?} frames)", encoding);
     let (mut sender, mut receiver) = socket.split();
 
     // Subscribe to broadcast events
     let mut event_rx = state.event_tx.subscribe();
-
-    // Send initial peer list
-    match state.store.list_peers().await {
-        Ok(peers) => {
-            let entries: Vec<PeerListEntry> = peers.into_iter().map(Into::into).collect();
-            let init_msg = WsMessage::PeersList { peers: entries };
-            if let Ok(json) = serde_json::to_string(&init_msg) {
-                let _ = sender.send(Message::Text(json.into())).await;
+    let checkpoints = Arc::new(Mutex::new(CheckpointTracker::default()));
+
+    // Send the greeting and the requested snapshot of current state; the
+    // client can't have asked for compression before seeing the greeting
+    for init_msg in greeting(&state, &snapshot).await {
+        let frame = serde_json::to_string(&init_msg)
+            .map_err(|e| e.to_string())
+            .and_then(|json| encoding.frame(json));
+        if let Ok(frame) = frame {
+            if sender.send(frame.into()).await.is_ok() {
+                checkpoints.lock().record_delivery();
             }
         }
-        Err(e) => {
-            warn!("Failed to get initial peer list: {}", e);
+    }
+
+    // Replies addressed only to this connection
+    let (reply_tx, mut reply_rx) = mpsc::unbounded_channel();
+    let mut session = Session::new(reply_tx.clone(), state.config.max_subscriptions_per_connection);
+    session.identify(state.local_peer_id.to_string());
+    session.set_publish_limit(TokenBucket::new(state.config.publish_rate, state.config.publish_burst));
+    let delivery = session.delivery();
+    let encoding_stats = session.encoding_stats();
+    let connection_id = session.id().to_string();
+    let activity = Activity::default();
+    let outbound = OutboundQueue::new(state.config.outbound_queue_capacity);
+    let close = state.connections.register(
+        &connection_id,
+        reply_tx,
+        encoding_stats.clone(),
+        outbound.clone(),
+        activity.clone(),
+    );
+    state.connections.act_for(&connection_id, &state.local_peer_id.to_string());
+    info!("Registered WebSocket connection {}", connection_id);
+
+    // Issue the token for resuming this connection from the current end of the log
+    state.event_log_fence.wait().await;
+    match state.store.latest_event_seq().await {
+        Ok(last_seq) => {
+            let now = chrono::Utc::now().timestamp_millis();
+            let (resume_token, expires_at) = state.resume_tokens.issue(&connection_id, last_seq, now);
+            session.reply(WsMessage::ConnectionReady {
+                connection_id: connection_id.clone(),
+                last_seq,
+                resume_token,
+                expires_at,
+            });
         }
+        Err(e) => warn!("Failed to read event log position: {}", e),
     }
 
-    // Spawn task to forward broadcast events to this client
+    // Spawn a task writing queued frames to the socket, highest priority
+    // first, so a slow socket doesn't hold up dispatching
+    let mut write_task = tokio::spawn(write_frames(sender, outbound.clone(), encoding_stats, checkpoints.clone()));
+
+    // Spawn task to queue broadcast events and direct replies for this client,
+    // interleaving delivery checkpoints
+    let dispatch_id = connection_id.clone();
+    let checkpoint_interval = state.config.checkpoint_interval;
+    let quiet_period = state.config.checkpoint_quiet_period;
+    let ping_interval = state.config.ping_interval;
+    let mut delivered = DeliveredIds::new(state.config.dedup_window);
     let mut send_task = tokio::spawn(async move {
-        while let Ok(event) = event_rx.recv().await {
-            if let Ok(json) = serde_json::to_string(&event) {
-                if sender.send(Message::Text(json.into())).await.is_err() {
-                    break;
+        let mut checkpoint_timer = tokio::time::interval(checkpoint_interval);
+        checkpoint_timer.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        let mut ping_timer = tokio::time::interval_at(tokio::time::Instant::now() + ping_interval, ping_interval);
+        ping_timer.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        let mut last_delivery = tokio::time::Instant::now();
+        let mut backlog = CollapseBuffer::default();
+        loop {
+            let event = if let Some(event) = backlog.pop() {
+                event
+            } else {
+                tokio::select! {
+                    event = recv_broadcast(&mut event_rx, &mut backlog, &delivery) => match event {
+                        Some(event) => event,
+                        None => break,
+                    },
+                    reply = reply_rx.recv() => match reply {
+                        Some(reply) => reply,
+                        None => break,
+                    },
+                    _ = checkpoint_timer.tick() => {
+                        match checkpoints.lock().take_checkpoint(chrono::Utc::now().timestamp_millis()) {
+                            Some(checkpoint) => checkpoint,
+                            None => continue,
+                        }
+                    }
+                    _ = ping_timer.tick() => {
+                        let ping = QueuedFrame {
+                            message: Message::Ping(Vec::new()),
+                            priority: Priority::Normal,
+                            serialized_len: None,
+                            counted: false,
+                        };
+                        if outbound.push(ping) == Pushed::Overflow {
+                            warn!("Connection {} fell too far behind, disconnecting", dispatch_id);
+                            break;
+                        }
+                        continue;
+                    }
+                    _ = tokio::time::sleep_until(last_delivery + quiet_period), if checkpoints.lock().pending() => {
+                        match checkpoints.lock().take_checkpoint(chrono::Utc::now().timestamp_millis()) {
+                            Some(checkpoint) => checkpoint,
+                            None => continue,
+                        }
+                    }
+                    _ = close.notified() => {
+                        // Queue what was addressed to this connection (including the
+                        // reason it is being closed); the Close frame follows it
+                        while let Ok(reply) = reply_rx.try_recv() {
+                            let (encoded, compression) = {
+                                let prefs = delivery.read();
+                                (prefs.encode(&reply), prefs.compression())
+                            };
+                            if let Ok(json) = encoded {
+                                let len = json.len();
+                                if let Ok(frame) = encoding.frame(json).map(|frame| compression.apply(frame)) {
+                                    outbound.push(QueuedFrame {
+                                        message: frame.into(),
+                                        priority: Priority::of(&reply),
+                                        serialized_len: Some(len),
+                                        counted: false,
+                                    });
+                                }
+                            }
+                        }
+                        break;
+                    }
+                }
+            };
+            if !delivered.first_delivery(&event, std::time::Instant::now()) {
+                continue;
+            }
+            let is_checkpoint = matches!(event, WsMessage::Checkpoint { .. });
+            let (encoded, compression) = {
+                let prefs = delivery.read();
+                (prefs.encode(&event), prefs.compression())
+            };
+            let frame = encoded.map_err(|e| e.to_string()).and_then(|json| {
+                let len = json.len();
+                encoding.frame(json).map(|frame| (len, compression.apply(frame)))
+            });
+            if let Ok((len, frame)) = frame {
+                let queued = QueuedFrame {
+                    message: frame.into(),
+                    priority: Priority::of(&event),
+                    serialized_len: Some(len),
+                    counted: !is_checkpoint,
+                };
+                match outbound.push(queued) {
+                    Pushed::Queued => {}
+                    Pushed::Dropped => debug!("Connection {} is behind, dropped a snapshot", dispatch_id),
+                    Pushed::Overflow => {
+                        warn!("Connection {} fell too far behind, disconnecting", dispatch_id);
+                        break;
+                    }
+                }
+                if !is_checkpoint {
+                    last_delivery = tokio::time::Instant::now();
                 }
             }
         }
+        // The writer sends the Close frame once the queue is drained
+        outbound.close();
     });
 
-    // Handle incoming messages from client
+    // Handle incoming messages from client. Every frame counts as activity;
+    // Ping and Pong frames are otherwise ignored (axum answers Pings itself)
+    let recv_activity = activity.clone();
     let state_clone = state.clone();
     let mut recv_task = tokio::spawn(async move {
-        while let Some(Ok(msg)) = receiver.next().await {
+        while let Some(received) = receiver.next().await {
+            let msg = match received {
+                Ok(msg) => msg,
+                Err(e) => {
+                    // Includes messages over the transport's size limit
+                    warn!("WebSocket receive failed, closing: {}", e);
+                    break;
+                }
+            };
+            recv_activity.touch();
+            // Every frame type is handled explicitly so new ones can't be
+            // dropped unnoticed. Fragmented messages are reassembled by the
+            // transport, so each frame here is a complete message.
             match msg {
                 Message::Text(text) => {
-                    info!("Received WebSocket text: {}", text);
-                    match serde_json::from_str::<ClientMessage>(&text) {
-                        Ok(client_msg) => {
-                            handle_client_message(client_msg, &state_clone).await;
-                        }
-                        Err(e) => {
-                            warn!("Failed to parse client message: {} - raw: {}", e, text);
-                        }
+                    if !within_frame_limit(&text, &state_clone, &session) {
+                        continue;
+                    }
+                    // Parsed messages are logged with credentials redacted
+                    debug!("Received WebSocket text ({} bytes)", text.len());
+                    handle_text(&text, &state_clone, &mut session).await;
+                }
+                Message::Binary(data) if encoding == WireEncoding::MessagePack => match decode_binary(&data) {
+                    // The JSON form of a MessagePack frame can be larger than the frame itself
+                    Ok(text) if !within_frame_limit(&text, &state_clone, &session) => {}
+                    Ok(text) => handle_text(&text, &state_clone, &mut session).await,
+                    Err(e) => {
+                        warn!("Failed to decode MessagePack frame: {}", e);
+                        session.reply_error(ErrorCode::InvalidRequest, format!("Invalid MessagePack: {}", e));
                     }
+                },
+                // Binary frames carry messages only under MessagePack
+                Message::Binary(data) => {
+                    warn!(
+                        "Connection {} sent a {}-byte binary frame without negotiating MessagePack",
+                        session.id(),
+                        data.len()
+                    );
+                    session.reply_error(
+                        ErrorCode::InvalidRequest,
+                        "Binary frames require connecting with encoding=msgpack",
+                    );
                 }
+                // The transport answers pings; both already counted as activity
+                Message::Ping(_) | Message::Pong(_) => {}
                 Message::Close(_) => break,
-                _ => {}
             }
         }
     });
 
-    // Wait for either task to finish
+    // Wait for the socket to close in either direction, or for the client to
+    // go quiet. The writer finishes once the dispatcher has closed the queue.
     tokio::select! {
-        _ = &mut send_task => recv_task.abort(),
-        _ = &mut recv_task => send_task.abort(),
+        _ = &mut write_task => {}
+        _ = &mut recv_task => {}
+        _ = activity.idle(state.config.idle_timeout) => {
+            info!(
+                "WebSocket connection {} idle for {:?}, disconnecting",
+                connection_id, state.config.idle_timeout
+            );
+        }
+    }
+    send_task.abort();
+    recv_task.abort();
+    write_task.abort();
+
+    let departed = state.connections.unregister(&connection_id);
+    for topic in state.topic_subscribers.remove_connection(&connection_id) {
+        release_topic(&state, &topic).await;
+    }
+    if let Some(peer_id) = departed {
+        presence::announce_leave(&state, &peer_id).await;
+    }
+    info!("WebSocket connection {} closed", connection_id);
+}
+
+/// Write a connection's queued frames to its socket until the queue closes
+///
+/// A frame counts as delivered once written: its bytes go into `stats` and,
+/// unless it is a checkpoint or control frame, it advances `checkpoints`.
+/// A queue closed for overflow closes the socket with the reason.
+async fn write_frames(
+    mut sender: SplitSink<WebSocket, Message>,
+    queue: OutboundQueue,
+    stats: Arc<EncodingStats>,
+    checkpoints: Arc<Mutex<CheckpointTracker>>,
+) {
+    loop {
+        match queue.pop().await {
+            Next::Frame(frame) => {
+                let sent = match &frame.message {
+                    Message::Text(text) => text.len(),
+                    Message::Binary(data) => data.len(),
+                    _ => 0,
+                };
+                if sender.send(frame.message).await.is_err() {
+                    break;
+                }
+                if let Some(len) = frame.serialized_len {
+                    stats.record(len, sent);
+                }
+                if frame.counted {
+                    checkpoints.lock().record_delivery();
+                }
+            }
+            Next::Closed(reason) => {
+                let close = reason.map(|reason| CloseFrame {
+                    code: close_code::AGAIN,
+                    reason: reason.into(),
+                });
+                let _ = sender.send(Message::Close(close)).await;
+                break;
+            }
+        }
+    }
+}
+
+/// Receive the next broadcast event a connection wants, or `None` once the
+/// channel closes
+///
+/// Connections that collapse updates move everything already waiting into
+/// `backlog` and take its first event. A connection that fell behind the
+/// channel gets a `Resync` in place of what it missed, whichever path it
+/// takes.
+async fn recv_broadcast(
+    event_rx: &mut broadcast::Receiver<WsMessage>,
+    backlog: &mut CollapseBuffer,
+    delivery: &RwLock<DeliveryPrefs>,
+) -> Option<WsMessage> {
+    loop {
+        match event_rx.recv().await {
+            Ok(event) if delivery.read().wants(&event) => {
+                if !delivery.read().collapses_updates() {
+                    return Some(event);
+                }
+                // Anything else already waiting means the client is behind
+                backlog.push(event);
+                backlog.fill(event_rx, |event| delivery.read().wants(event));
+                return backlog.pop();
+            }
+            Ok(_) => continue,
+            Err(RecvError::Lagged(skipped)) => return Some(lag_resync(skipped)),
+            Err(RecvError::Closed) => return None,
+        }
+    }
+}
+
+/// Tell a connection that fell behind the broadcast channel to refetch state
+///
+/// The skipped events are gone, so the client can't catch up by itself, but
+/// it stays connected.
+pub(super) fn lag_resync(skipped: u64) -> WsMessage {
+    warn!("WebSocket connection lagged, {} events were skipped", skipped);
+    WsMessage::Resync {
+        reason: format!("Missed {} events while lagging", skipped),
+    }
+}
+
+/// Messages sent when a connection opens: `Hello`, then the peer list
+async fn greeting(state: &AppState, snapshot: &SnapshotSections) -> Vec<WsMessage> {
+    let mut messages = vec![WsMessage::Hello {
+        protocol_version: PROTOCOL_VERSION,
+        node_name: state.node_name(),
+        peer_id: state.local_peer_id.to_string(),
+        features: FEATURES.iter().map(|feature| feature.to_string()).collect(),
+    }];
+    if snapshot.includes(SnapshotSection::Peers) {
+        match state.store.list_peers().await {
+            Ok(peers) => {
+                let entries: Vec<PeerListEntry> = peers.into_iter().map(Into::into).collect();
+                messages.push(WsMessage::PeersList { peers: entries });
+            }
+            Err(e) => {
+                warn!("Failed to get initial peer list: {}", e);
+            }
+        }
+    }
+    if snapshot.includes(SnapshotSection::Proposals) {
+        if let Ok(proposals) = governance::proposal_entries(state).await {
+            let proposals = proposals.into_iter().filter(|entry| entry.status == "active").collect();
+            messages.push(WsMessage::Proposals { proposals });
+        }
+    }
+    if snapshot.includes(SnapshotSection::CreditLines) {
+        if let Ok(lines) = credit::credit_line_entries(state).await {
+            messages.push(WsMessage::CreditLinesList { lines });
+        }
+    }
+    if snapshot.includes(SnapshotSection::Resources) {
+        for resource_type in SNAPSHOT_RESOURCE_TYPES {
+            messages.extend(resources::pool_update(state, resource_type).await);
+        }
+    }
+    if snapshot.includes(SnapshotSection::Chat) {
+        // The greeting goes out before the connection is authenticated as
+        // anyone, so it carries public chat only; direct messages come
+        // from `GetChatHistory`
+        let limit = state.config.snapshot_chat_messages.min(MAX_CHAT_HISTORY_PAGE);
+        if let Ok((page, has_more)) = chat_history(state, None, None, limit).await {
+            messages.push(WsMessage::ChatHistory { messages: page, has_more });
+        }
+    }
+    messages
+}
+
+/// Client-supplied reference echoed in the `Ack` for a message
+#[derive(serde::Deserialize)]
+struct ClientRef {
+    client_ref: Option<String>,
+}
+
+/// Whether a client message is within the configured size, telling the client if not
+fn within_frame_limit(text: &str, state: &AppState, session: &Session) -> bool {
+    if text.len() <= state.config.max_frame_size {
+        return true;
+    }
+    warn!("Rejected {} byte client message", text.len());
+    session.reply_error(
+        ErrorCode::InvalidRequest,
+        format!(
+            "Message of {} bytes exceeds the limit of {} bytes",
+            text.len(),
+            state.config.max_frame_size
+        ),
+    );
+    false
+}
+
+/// Parse and handle a text frame, telling the client if it can't be parsed
+///
+/// Any message may carry a `client_ref`; if it does, the outcome of handling
+/// it is acknowledged with an `Ack` carrying the same reference.
+async fn handle_text(text: &str, state: &AppState, session: &mut Session) {
+    let client_ref = serde_json::from_str::<ClientRef>(text).ok().and_then(|r| r.client_ref);
+    session.take_failure();
+    match serde_json::from_str::<ClientMessage>(text) {
+        Ok(client_msg) => handle_client_message(client_msg, state, session).await,
+        Err(e) => {
+            // The frame itself may hold a credential or signed payload, so only its size is logged
+            warn!("Failed to parse client message: {} ({} bytes)", e, text.len());
+            session.reply_error(ErrorCode::InvalidRequest, format!("Invalid message: {}", e));
+        }
     }
+    if let Some(client_ref) = client_ref {
+        let error = session.take_failure();
+        session.reply(WsMessage::Ack {
+            client_ref,
+            ok: error.is_none(),
+            error,
+        });
+    }
+}
 
-    info!("WebSocket connection closed");
+/// Handle messages from the client, replying with an `Error` if the request fails
+///
+/// This is the one place a [`HandlerError`] becomes a `WsMessage::Error`
+/// for the connection that sent the request.
+pub(super) async fn handle_client_message(msg: ClientMessage, state: &AppState, session: &mut Session) {
+    if let Err(e) = handle_request(msg, state, session).await {
+        session.reply_error(e.code(), e.message());
+    }
 }
 
-/// Handle messages from the client
-async fn handle_client_message(msg: ClientMessage, state: &AppState) {
+/// Carry out a client request
+///
+/// A connection's messages are handled one at a time, in the order received,
+/// and each handler awaits its own persistence before returning. Reads from
+/// the event log (which is written in the background) first wait on the
+/// [`EventLogFence`](crate::event_log::EventLogFence), so every read observes
+/// the connection's earlier writes.
+async fn handle_request(msg: ClientMessage, state: &AppState, session: &mut Session) -> Result<(), HandlerError> {
     info!("Received client message: {:?}", msg);
 
+    if msg.publishes() && !session.try_publish() {
+        warn!("Connection {} exceeded its publish rate limit", session.id());
+        return Err(HandlerError::rate_limited("Publish rate limit exceeded"));
+    }
+
+    if msg.is_economics_action() && state.config.action_signer.is_some() && !session.has_action_signature() {
+        return Err(HandlerError::unauthorized("Economics actions must be signed"));
+    }
+
+    if msg.correlation_id().is_some_and(|id| id.len() > MAX_CORRELATION_ID_LEN) {
+        return Err(HandlerError::invalid(format!(
+            "Correlation ID must be at most {} bytes",
+            MAX_CORRELATION_ID_LEN
+        )));
+    }
+
     match msg {
         ClientMessage::SendChat { content, to, room_id } => {
             info!("SendChat: content='{}', to={:?}, room_id={:?}", content, to, room_id);
+            if let Err(e) = check_chat_content(state, &content) {
+                return Err(HandlerError::invalid(e));
+            }
+
+            if room_id.is_some() && to.is_some() {
+                return Err(HandlerError::invalid("A chat message goes to a room or a peer, not both"));
+            }
+            let recipient = to
+                .as_deref()
+                .map(|to| parse_peer_id("Recipient", to))
+                .transpose()
+                .map_err(HandlerError::invalid)?;
+
+            // Determine topic based on message target
+            let topic = if let Some(room_id) = &room_id {
+                let topic = rooms::room_topic(room_id)?;
+                // Sending to a room joins it, so replies reach this connection
+                if !session.is_subscribed(&topic) {
+                    rooms::subscribe_room(state, session, &topic, "join room").await?;
+                }
+                topic
+            } else if let Some(recipient) = &recipient {
+                topics::direct(recipient.as_str())
+            } else {
+                "/mycelial/1.0.0/chat".to_string()
+            };
 
-            // Generate message ID and timestamp for local echo
-            let message_id = Uuid::new_v4().to_string();
             let timestamp = chrono::Utc::now().timestamp_millis();
 
-            // Create chat message using core Message type
-            let chat_msg = mycelial_core::message::Message::new(
-                mycelial_core::message::MessageType::Content,
-                state.local_peer_id.clone(),
-                content.as_bytes().to_vec(),
-            );
+            // Create chat message using core Message type; direct messages
+            // name their recipient, who drops any that don't
+            let payload = content.as_bytes().to_vec();
+            let chat_msg = match recipient.clone() {
+                Some(recipient) => {
+                    mycelial_core::message::Message::direct(state.local_peer_id.clone(), recipient, payload)
+                }
+                None => mycelial_core::message::Message::new(
+                    mycelial_core::message::MessageType::Content,
+                    state.local_peer_id.clone(),
+                    payload,
+                ),
+            };
+            // The local echo shares the published ID so later edits match on every node
+            let message_id = chat_msg.id.to_string();
 
             // Serialize and publish to network
             match serde_json::to_vec(&chat_msg) {
                 Ok(data) => {
-                    // Determine topic based on message target
-                    let topic = if room_id.is_some() {
-                        format!("/mycelial/1.0.0/room/{}", room_id.as_ref().unwrap())
-                    } else if to.is_some() {
-                        "/mycelial/1.0.0/direct".to_string()
-                    } else {
-                        "/mycelial/1.0.0/chat".to_string()
-                    };
-
                     info!("Publishing to topic: {}", topic);
 
-                    if let Err(e) = state.network.publish(&topic, data).await {
+                    if let Err(e) = state.network.publish(state.wire_topic(&topic), data).await {
                         error!("Failed to publish chat: {}", e);
+                        network_errors::report(state, "publish", &e);
+                        session.record_failure(format!("Failed to publish chat: {}", e));
                     } else {
                         info!("Chat message published successfully");
+                        state.topic_counts.record(&topic);
 
                         // LOCAL ECHO: Send the message back to the sender immediately
                         // Gossipsub doesn't deliver messages back to the sender, so we
@@ -141,8 +928,8 @@ async fn handle_client_message(msg: ClientMessage, state: &AppState) {
                         let echo_msg = WsMessage::ChatMessage {
                             id: message_id,
                             from: state.local_peer_id.to_string(),
-                            from_name: state.node_name.clone(),
-                            to: to.clone(),
+                            from_name: state.node_name(),
+                            to: recipient.as_ref().map(ToString::to_string),
                             room_id: room_id.clone(),
                             content: content.clone(),
                             timestamp,
@@ -157,441 +944,2691 @@ async fn handle_client_message(msg: ClientMessage, state: &AppState) {
                 }
                 Err(e) => {
                     error!("Failed to serialize chat message: {}", e);
+                    return Err(HandlerError::internal("Failed to serialize chat message"));
                 }
             }
         }
 
-        ClientMessage::GetPeers => {
-            // Peer list is sent on connect, but can be requested again
-            if let Ok(peers) = state.store.list_peers().await {
-                let entries: Vec<PeerListEntry> = peers.into_iter().map(Into::into).collect();
-                let msg = WsMessage::PeersList { peers: entries };
-                let _ = state.event_tx.send(msg);
+        ClientMessage::EditChat { message_id, content } => {
+            if let Err(e) = check_chat_content(state, &content) {
+                return Err(HandlerError::invalid(e));
             }
+            amend_chat(state, session, ChatAmendment::Edit { message_id, content }).await?;
         }
 
-        ClientMessage::GetStats => {
-            let stats = WsMessage::Stats {
-                peer_count: state.store.list_peers().await.map(|p| p.len()).unwrap_or(0),
-                message_count: state.message_count.load(std::sync::atomic::Ordering::Relaxed),
-                uptime_seconds: state.start_time.elapsed().as_secs(),
-            };
-            let _ = state.event_tx.send(stats);
+        ClientMessage::DeleteChat { message_id } => {
+            amend_chat(state, session, ChatAmendment::Delete { message_id }).await?;
         }
 
-        ClientMessage::Subscribe { topic } => {
-            if let Err(e) = state.network.subscribe(&topic).await {
-                error!("Failed to subscribe to topic {}: {}", topic, e);
-            }
+        ClientMessage::ReactChat { message_id, emoji } => {
+            // Reacting again with the same emoji takes the reaction back;
+            // peers are told which, so their copies can't drift
+            let local = state.local_peer_id.to_string();
+            let added = match state.store.has_reaction(&message_id, &local, &emoji).await {
+                Ok(present) => !present,
+                Err(e) => {
+                    error!("Failed to look up chat reaction: {}", e);
+                    return Err(HandlerError::internal("Failed to update chat message"));
+                }
+            };
+            amend_chat(state, session, ChatAmendment::React { message_id, emoji, added }).await?;
         }
 
-        // ============ Economics Protocol Handlers ============
-
-        ClientMessage::SendVouch { vouchee, weight, message } => {
-            info!("SendVouch: vouchee='{}', weight={}", vouchee, weight);
-
-            let timestamp = chrono::Utc::now().timestamp_millis();
+        ClientMessage::MarkRead { message_id } => {
+            amend_chat(state, session, ChatAmendment::Read { message_id }).await?;
+        }
 
-            // Create vouch request message (uses stake, not weight)
-            let mut vouch_req = VouchRequest::new(
-                state.local_peer_id.to_string(),
-                vouchee.clone(),
-                weight, // VouchRequest calls this 'stake'
-            );
-            if let Some(msg) = message {
-                vouch_req = vouch_req.with_message(msg);
-            }
-            let request_id = vouch_req.id.to_string();
-            let vouch_msg = VouchMessage::VouchRequest(vouch_req);
+        ClientMessage::Resume { token } => {
+            let now = chrono::Utc::now().timestamp_millis();
+            let point = match state.resume_tokens.verify(&token, now) {
+                Ok(point) => point,
+                Err(e) => {
+                    session.reply(WsMessage::Resync { reason: e.message().to_string() });
+                    return Ok(());
+                }
+            };
+            info!("Resuming connection {} after seq {}", point.connection_id, point.last_seq);
 
-            // Serialize and publish to network
-            match serde_json::to_vec(&vouch_msg) {
-                Ok(data) => {
-                    if let Err(e) = state.network.publish(topics::VOUCH, data).await {
-                        error!("Failed to publish vouch request: {}", e);
-                    } else {
-                        info!("Vouch request published successfully");
-
-                        // Local echo for the sender
-                        let echo_msg = WsMessage::VouchRequest {
-                            id: request_id,
-                            voucher: state.local_peer_id.to_string(),
-                            vouchee,
-                            weight,
-                            timestamp,
-                        };
-                        let _ = state.event_tx.send(echo_msg);
-                    }
+            state.event_log_fence.wait().await;
+            match state.store.list_events_after(point.last_seq, MAX_RESUME_EVENTS as i64 + 1).await {
+                Ok(logged) if logged.len() > MAX_RESUME_EVENTS => {
+                    session.reply(WsMessage::Resync { reason: "Too many events missed".to_string() });
+                }
+                Ok(logged) => {
+                    // Only what this connection would have been sent live
+                    let delivery = session.delivery();
+                    let events = to_replayed(logged)
+                        .into_iter()
+                        .filter(|replayed| delivery.read().wants_logged(&replayed.event))
+                        .collect();
+                    session.reply(WsMessage::Resumed {
+                        resumed_from: point.connection_id,
+                        events,
+                    });
                 }
                 Err(e) => {
-                    error!("Failed to serialize vouch request: {}", e);
+                    error!("Failed to load events to resume: {}", e);
+                    return Err(HandlerError::internal("Failed to load missed events"));
                 }
             }
         }
 
-        ClientMessage::RespondVouch { request_id, accept } => {
-            info!("RespondVouch: request_id='{}', accept={}", request_id, accept);
+        ClientMessage::Ping { nonce } => {
+            session.reply(WsMessage::Pong {
+                nonce,
+                server_time: chrono::Utc::now().timestamp_millis(),
+            });
+        }
 
-            let timestamp = chrono::Utc::now().timestamp_millis();
+        ClientMessage::GetUnreadCounts => send_unread_counts(state, session).await?,
+
+        ClientMessage::MarkSeen { category, up_to } => {
+            let identity = session.identity().unwrap_or_else(|| state.local_peer_id.to_string());
+            if let Err(e) = state.store.mark_seen(&identity, category.label(), up_to).await {
+                error!("Failed to mark {} seen: {}", category.label(), e);
+                return Err(HandlerError::internal("Failed to update seen marker"));
+            }
+            send_unread_counts(state, session).await?;
+        }
+
+        ClientMessage::GetPeers => {
+            // Peer list is sent on connect, but can be requested again
+            if let Ok(peers) = state.store.list_peers().await {
+                let entries: Vec<PeerListEntry> = peers.into_iter().map(Into::into).collect();
+                let msg = WsMessage::PeersList { peers: entries };
+                let _ = state.event_tx.send(msg);
+            }
+        }
+
+        ClientMessage::GetChatHistory { before, before_seq, limit } => {
+            let limit = limit.clamp(1, MAX_CHAT_HISTORY_PAGE);
+            // A bare timestamp excludes every message sent in that millisecond
+            let cursor = before.map(|timestamp| (timestamp, before_seq.unwrap_or(i64::MIN)));
+            let (messages, has_more) = chat_history(state, session.identity().as_deref(), cursor, limit).await?;
+            session.reply(WsMessage::ChatHistory { messages, has_more });
+        }
 
-            // Parse request_id as UUID
-            let vouch_id = match Uuid::parse_str(&request_id) {
-                Ok(id) => id,
+        ClientMessage::GetPeer { peer_id } => {
+            let (info, reputation) = match state.store.get_peer(&peer_id).await {
+                Ok(Some(peer)) => peer,
+                Ok(None) => {
+                    return Err(HandlerError::invalid(format!("Unknown peer {}", peer_id)));
+                }
                 Err(e) => {
-                    error!("Invalid vouch request ID: {}", e);
-                    return;
+                    error!("Failed to look up peer {}: {}", peer_id, e);
+                    return Err(HandlerError::internal("Failed to look up peer"));
                 }
             };
-
-            // Create vouch ack message with correct fields
-            let ack_msg = VouchMessage::VouchAck(ProtocolVouchAck {
-                vouch_id,
-                from: state.local_peer_id.to_string(),
-                accepted: accept,
-                reason: None,
-                timestamp: chrono::Utc::now(),
+            let vouches = match state.store.list_peer_vouches(&peer_id).await {
+                Ok(vouches) => vouches,
+                Err(e) => {
+                    error!("Failed to load vouches for {}: {}", peer_id, e);
+                    return Err(HandlerError::internal("Failed to look up peer"));
+                }
+            };
+            session.reply(WsMessage::PeerDetail {
+                display_name: naming::display_name(info.name.as_deref(), &peer_id, state.config.fallback_name),
+                id: peer_id,
+                name: info.name,
+                public_key: info.public_key,
+                addresses: info.addresses,
+                first_seen: info.first_seen.timestamp_millis(),
+                last_seen: info.last_seen.timestamp_millis(),
+                reputation: reputation.score,
+                successful_interactions: reputation.successful_interactions,
+                failed_interactions: reputation.failed_interactions,
+                vouches: vouches
+                    .into_iter()
+                    .map(|v| PeerVouchEntry {
+                        id: v.id,
+                        voucher: v.voucher,
+                        vouchee: v.vouchee,
+                        weight: v.weight,
+                        status: v.status,
+                        created_at: v.created_at,
+                    })
+                    .collect(),
             });
+        }
 
-            match serde_json::to_vec(&ack_msg) {
-                Ok(data) => {
-                    if let Err(e) = state.network.publish(topics::VOUCH, data).await {
-                        error!("Failed to publish vouch ack: {}", e);
-                    } else {
-                        let echo_msg = WsMessage::VouchAck {
-                            id: Uuid::new_v4().to_string(),
-                            request_id,
-                            accepted: accept,
-                            new_reputation: None,
-                            timestamp,
-                        };
-                        let _ = state.event_tx.send(echo_msg);
+        ClientMessage::GetPeersBulk { ids } => {
+            if ids.len() > MAX_PEERS_BULK {
+                return Err(HandlerError::invalid(
+                    format!("At most {} peers may be requested at once", MAX_PEERS_BULK),
+                ));
+            }
+            let mut seen = HashSet::new();
+            let mut entries = Vec::new();
+            let mut unknown = Vec::new();
+            for id in ids.into_iter().filter(|id| seen.insert(id.clone())) {
+                match state.store.get_peer(&id).await {
+                    Ok(Some(peer)) => entries.push(PeerListEntry::from(peer)),
+                    Ok(None) => unknown.push(id),
+                    Err(e) => {
+                        error!("Failed to look up peer {}: {}", id, e);
+                        return Err(HandlerError::internal("Failed to look up peers"));
                     }
                 }
-                Err(e) => {
-                    error!("Failed to serialize vouch ack: {}", e);
-                }
             }
+            session.reply(WsMessage::PeersBulk { entries, unknown });
         }
 
-        ClientMessage::CreateCreditLine { debtor, limit } => {
-            info!("CreateCreditLine: debtor='{}', limit={}", debtor, limit);
-
-            let timestamp = chrono::Utc::now().timestamp_millis();
-
-            let credit_msg = CreditMessage::CreateLine(ProtocolCreateCreditLine::new(
-                state.local_peer_id.to_string(),
-                debtor.clone(),
-                limit,
-            ));
-
-            match serde_json::to_vec(&credit_msg) {
-                Ok(data) => {
-                    if let Err(e) = state.network.publish(topics::CREDIT, data).await {
-                        error!("Failed to publish credit line: {}", e);
-                    } else {
-                        let echo_msg = WsMessage::CreditLine {
-                            id: Uuid::new_v4().to_string(),
-                            creditor: state.local_peer_id.to_string(),
-                            debtor,
-                            limit,
-                            balance: 0.0,
-                            timestamp,
-                        };
-                        let _ = state.event_tx.send(echo_msg);
-                    }
-                }
+        ClientMessage::SearchPeers { query, min_reputation, limit } => {
+            if min_reputation.is_some_and(|min| !min.is_finite()) {
+                return Err(HandlerError::invalid("min_reputation must be a finite number"));
+            }
+            let limit = limit.clamp(1, MAX_PEER_SEARCH_RESULTS);
+            let query = query.trim().to_lowercase();
+            let peers = match state.store.list_peers().await {
+                Ok(peers) => peers,
                 Err(e) => {
-                    error!("Failed to serialize credit line: {}", e);
+                    error!("Failed to list peers: {}", e);
+                    return Err(HandlerError::internal("Failed to search peers"));
                 }
-            }
+            };
+            let peers = peers
+                .into_iter()
+                .filter(|(info, reputation)| {
+                    let name_matches = query.is_empty()
+                        || info.name.as_deref().is_some_and(|name| name.to_lowercase().contains(&query));
+                    name_matches && min_reputation.map_or(true, |min| reputation.score >= min)
+                })
+                .take(limit)
+                .map(PeerListEntry::from)
+                .collect();
+            session.reply(WsMessage::PeersList { peers });
         }
 
-        ClientMessage::TransferCredit { to, amount, memo } => {
-            info!("TransferCredit: to='{}', amount={}", to, amount);
-
-            let timestamp = chrono::Utc::now().timestamp_millis();
+        ClientMessage::GetStats => {
+            session.reply(current_stats(state).await);
+        }
 
-            // For transfers, we use a placeholder line_id - in practice, the client should
-            // provide the actual credit line ID they want to use for the transfer
-            let line_id = Uuid::new_v4(); // Placeholder - real impl would look up active credit line
-            let mut transfer = ProtocolCreditTransfer::new(
-                line_id,
-                state.local_peer_id.to_string(),
-                to.clone(),
-                amount,
-            );
-            if let Some(ref m) = memo {
-                transfer = transfer.with_memo(m);
+        ClientMessage::GetStatsHistory { since, interval_secs } => {
+            if interval_secs == 0 {
+                return Err(HandlerError::invalid("interval_secs must be positive"));
             }
-            let transfer_msg = CreditMessage::Transfer(transfer);
-
-            match serde_json::to_vec(&transfer_msg) {
-                Ok(data) => {
-                    if let Err(e) = state.network.publish(topics::CREDIT, data).await {
-                        error!("Failed to publish credit transfer: {}", e);
-                    } else {
-                        let echo_msg = WsMessage::CreditTransfer {
-                            id: Uuid::new_v4().to_string(),
-                            from: state.local_peer_id.to_string(),
-                            to,
-                            amount,
-                            memo,
-                            timestamp,
-                        };
-                        let _ = state.event_tx.send(echo_msg);
-                    }
+            match state.store.list_stats_snapshots(since).await {
+                Ok(snapshots) => {
+                    let interval_ms = interval_secs.saturating_mul(1000).min(i64::MAX as u64) as i64;
+                    let points = to_points(&downsample(&snapshots, interval_ms));
+                    session.reply(WsMessage::StatsHistory { interval_secs, points });
                 }
                 Err(e) => {
-                    error!("Failed to serialize credit transfer: {}", e);
+                    error!("Failed to load stats history: {}", e);
+                    return Err(HandlerError::internal("Failed to load stats history"));
                 }
             }
         }
 
-        ClientMessage::CreateProposal { title, description, proposal_type } => {
-            info!("CreateProposal: title='{}'", title);
-
-            let timestamp = chrono::Utc::now().timestamp_millis();
+        ClientMessage::Subscribe { topic } => {
+            let error = match subscribe_topic(state, session, &topic).await {
+                Ok(()) => None,
+                Err(e @ SubscribeError::LimitReached(_)) => {
+                    return Err(HandlerError::rate_limited(e.message()));
+                }
+                Err(e) => Some(e.message()),
+            };
+            session.reply(WsMessage::SubscribeResult {
+                topic,
+                success: error.is_none(),
+                error,
+            });
+        }
 
-            let proposal_msg = GovernanceMessage::CreateProposal(ProtocolCreateProposal::new(
-                state.local_peer_id.to_string(),
-                title.clone(),
-                description.clone(),
-            ));
+        ClientMessage::Unsubscribe { topic } => {
+            let error = unsubscribe_topic(state, session, &topic).await.err();
+            session.reply(WsMessage::UnsubscribeResult {
+                topic,
+                success: error.is_none(),
+                error,
+            });
+        }
 
-            match serde_json::to_vec(&proposal_msg) {
-                Ok(data) => {
-                    if let Err(e) = state.network.publish(topics::GOVERNANCE, data).await {
-                        error!("Failed to publish proposal: {}", e);
-                    } else {
-                        let echo_msg = WsMessage::Proposal {
-                            id: Uuid::new_v4().to_string(),
-                            proposer: state.local_peer_id.to_string(),
-                            title,
-                            description,
-                            proposal_type,
-                            status: "active".to_string(),
-                            yes_votes: 0,
-                            no_votes: 0,
-                            quorum: 3,
-                            deadline: timestamp + 86400000, // 24 hours
-                            timestamp,
-                        };
-                        let _ = state.event_tx.send(echo_msg);
-                    }
-                }
-                Err(e) => {
-                    error!("Failed to serialize proposal: {}", e);
-                }
+        ClientMessage::SubscribeMany { topics } => {
+            if topics.len() > MAX_SUBSCRIBE_MANY {
+                return Err(HandlerError::invalid(
+                    format!("At most {} topics may be subscribed at once", MAX_SUBSCRIBE_MANY),
+                ));
             }
+            let mut results = Vec::with_capacity(topics.len());
+            for topic in topics {
+                let error = subscribe_topic(state, session, &topic).await.err().map(SubscribeError::message);
+                results.push(SubscribeResultEntry {
+                    topic,
+                    success: error.is_none(),
+                    error,
+                });
+            }
+            session.reply(WsMessage::SubscribeManyResult { results });
         }
 
-        ClientMessage::CastVote { proposal_id, vote } => {
-            info!("CastVote: proposal_id='{}', vote='{}'", proposal_id, vote);
+        // ============ Economics Protocol Handlers ============
 
-            let timestamp = chrono::Utc::now().timestamp_millis();
+        ClientMessage::SetCapability { capability, enabled } => {
+            if enabled && capability == Capability::RawProtocol && !state.config.allow_debug_capabilities {
+                return Err(HandlerError::forbidden(
+                    "Debug capabilities are disabled on this node",
+                ));
+            }
+            info!("{:?} capability {}", capability, if enabled { "enabled" } else { "disabled" });
+            session.set_capability(capability, enabled);
+        }
 
-            // Parse proposal_id as UUID
-            let prop_uuid = match Uuid::parse_str(&proposal_id) {
-                Ok(id) => id,
-                Err(e) => {
-                    error!("Invalid proposal ID: {}", e);
-                    return;
+        ClientMessage::SetLocale { locale } => {
+            let parsed = match locale.as_deref().map(Locale::parse) {
+                None => None,
+                Some(Some(parsed)) => Some(parsed),
+                Some(None) => {
+                    return Err(HandlerError::invalid(
+                        format!("Unsupported locale {}", locale.unwrap_or_default()),
+                    ));
                 }
             };
+            session.set_locale(parsed);
+        }
 
-            let vote_enum = match vote.as_str() {
-                "yes" => Vote::For,
-                "no" => Vote::Against,
-                _ => Vote::Abstain,
-            };
+        ClientMessage::SetEventFilter { include } => {
+            let include: HashSet<String> = include.iter().map(|tag| tag.trim().to_string()).collect();
+            if include.iter().any(String::is_empty) {
+                return Err(HandlerError::invalid("Event types must not be empty"));
+            }
+            if let Some(unknown) = include.iter().find(|tag| !schema::SERVER_MESSAGES.iter().any(|m| m.name == tag.as_str())) {
+                return Err(HandlerError::invalid(format!("Unknown event type {}", unknown)));
+            }
+            info!("Connection {} event filter: {:?}", session.id(), include);
+            session.set_event_filter(include);
+        }
 
-            // CastVote::new takes (proposal_id: Uuid, voter, vote, weight)
-            let vote_msg = GovernanceMessage::CastVote(ProtocolCastVote::new(
-                prop_uuid,
-                state.local_peer_id.to_string(),
-                vote_enum,
-                1.0, // Default weight, could be based on reputation
-            ));
+        ClientMessage::Typing { to } => {
+            if !session.try_typing(std::time::Instant::now()) {
+                return Ok(());
+            }
+            let from = session.identity().unwrap_or_else(|| state.local_peer_id.to_string());
+            let from_name = naming::resolve_display_name(state, &from).await;
+            let _ = state.event_tx.send(WsMessage::TypingIndicator {
+                from,
+                from_name,
+                to,
+                timestamp: chrono::Utc::now().timestamp_millis(),
+            });
+        }
 
-            match serde_json::to_vec(&vote_msg) {
-                Ok(data) => {
-                    if let Err(e) = state.network.publish(topics::GOVERNANCE, data).await {
-                        error!("Failed to publish vote: {}", e);
-                    } else {
-                        let echo_msg = WsMessage::VoteCast {
-                            id: Uuid::new_v4().to_string(),
-                            proposal_id,
-                            voter: state.local_peer_id.to_string(),
-                            vote,
-                            weight: 1.0,
-                            timestamp,
-                        };
-                        let _ = state.event_tx.send(echo_msg);
-                    }
+        ClientMessage::SetNickname { name } => {
+            let name = name.trim();
+            if name.is_empty() || name.chars().count() > MAX_NICKNAME_LEN {
+                return Err(HandlerError::invalid(
+                    format!("Name must be 1-{} characters", MAX_NICKNAME_LEN),
+                ));
+            }
+            info!("Node renamed to {}", name);
+            *state.node_name.write() = name.to_string();
+            let _ = state.event_tx.send(WsMessage::NodeRenamed { name: name.to_string() });
+        }
+
+        ClientMessage::Identify { peer_id, signature } => {
+            let peer = peer_id
+                .trim()
+                .parse::<Libp2pPeerId>()
+                .map_err(|_| HandlerError::invalid(format!("Peer ID is not valid: {}", peer_id)))?;
+            let peer_id = peer.to_base58();
+            // The upgrade token already authorizes acting for this node;
+            // any other peer must prove it holds the key
+            if peer_id != state.local_peer_id.to_string()
+                && !signature.is_some_and(|signature| verify_identify(&peer, session.id(), &signature))
+            {
+                return Err(HandlerError::unauthorized(
+                    "Identifying as another peer needs its signature over this connection's challenge",
+                ));
+            }
+            info!("Connection {} identified as {}", session.id(), peer_id);
+            session.identify(peer_id.as_str());
+            state.connections.identify(session.id(), &peer_id);
+        }
+
+        ClientMessage::AuthenticateAdmin { token } => {
+            match &state.config.admin_token {
+                Some(expected) if tokens_match(expected, token.expose()) => {
+                    info!("Connection {} authenticated as admin", session.id());
+                    session.grant_admin();
+                }
+                _ => return Err(HandlerError::forbidden("Invalid admin token")),
+            }
+        }
+
+        ClientMessage::Kick { connection_id, reason } => {
+            if !session.is_admin() {
+                return Err(HandlerError::forbidden("Kick requires admin"));
+            }
+            let existed = state.connections.kick(&connection_id, &reason);
+            info!("Admin kick of {} ({}): existed={}", connection_id, reason, existed);
+            session.reply(WsMessage::KickResult { connection_id, existed });
+        }
+
+        ClientMessage::GetConfig => {
+            if !session.is_admin() {
+                return Err(HandlerError::forbidden("GetConfig requires admin"));
+            }
+            session.reply(WsMessage::ConfigSnapshot {
+                config: state.config.snapshot(),
+            });
+        }
+
+        ClientMessage::Signed { payload, nonce, timestamp, signature } => {
+            let Some(signer) = state.config.action_signer.clone() else {
+                return Err(HandlerError::invalid("Signed actions are not enabled"));
+            };
+            let signature = ActionSignature { signer, payload, nonce, timestamp, signature };
+            if !signature.verify() {
+                return Err(HandlerError::unauthorized("Invalid action signature"));
+            }
+            // Nonces are remembered for the replay window, so older signatures are refused
+            let age = chrono::Utc::now().timestamp_millis().abs_diff(signature.timestamp);
+            if u128::from(age) > state.config.replay_window.as_millis() {
+                return Err(HandlerError::unauthorized("Action signature expired"));
+            }
+            let nonce = format!("{}:{}", signature.signer, signature.nonce);
+            if !state.replay_guard.check_and_record(MessageCategory::Action, &nonce) {
+                return Err(HandlerError::unauthorized("Action signature already used"));
+            }
+            let action = match serde_json::from_str::<ClientMessage>(&signature.payload) {
+                Ok(action) if action.is_economics_action() => action,
+                Ok(_) => {
+                    return Err(HandlerError::invalid("Only economics actions can be signed"));
                 }
                 Err(e) => {
-                    error!("Failed to serialize vote: {}", e);
+                    return Err(HandlerError::invalid(format!("Invalid signed payload: {}", e)));
                 }
+            };
+            // The action's handler attaches the signature to what it publishes
+            session.set_action_signature(signature);
+            Box::pin(handle_client_message(action, state, session)).await;
+            session.take_action_signature();
+        }
+
+        ClientMessage::GetConnectionStats { connection_id } => {
+            if !session.is_admin() {
+                return Err(HandlerError::forbidden("GetConnectionStats requires admin"));
+            }
+            match (state.connections.stats(&connection_id), state.connections.outbound(&connection_id)) {
+                (Some(stats), Some(outbound)) => session.reply(WsMessage::ConnectionStats {
+                    connection_id,
+                    messages_sent: stats.messages(),
+                    bytes_before: stats.bytes_before(),
+                    bytes_after: stats.bytes_after(),
+                    compression_ratio: stats.ratio(),
+                    queue_depth: outbound.depth(),
+                    frames_dropped: outbound.dropped(),
+                }),
+                _ => return Err(HandlerError::invalid(format!("Unknown connection {}", connection_id))),
             }
         }
 
-        ClientMessage::ReportResource { resource_type, amount, unit } => {
-            info!("ReportResource: type='{}', amount={}", resource_type, amount);
+        ClientMessage::GetConnections => {
+            if !session.is_admin() {
+                return Err(HandlerError::forbidden("GetConnections requires admin"));
+            }
+            session.reply(WsMessage::Connections {
+                clients: state.connections.list(),
+            });
+        }
 
-            let timestamp = chrono::Utc::now().timestamp_millis();
+        ClientMessage::DescribeProtocol => {
+            session.reply(WsMessage::ProtocolSchema {
+                messages: schema::catalog(),
+            });
+        }
 
-            let res_type = match resource_type.as_str() {
-                "bandwidth" => ResourceType::Bandwidth,
-                "storage" => ResourceType::Storage,
-                "compute" => ResourceType::Compute,
-                _ => ResourceType::Other(resource_type.clone()),
-            };
+        ClientMessage::Whoami => {
+            session.reply(WsMessage::Identity {
+                peer_id: state.local_peer_id.to_string(),
+                node_name: state.node_name(),
+                addresses: state.listen_addresses.read().clone(),
+            });
+        }
 
-            let resource_msg = ResourceMessage::Contribution(ProtocolResourceContribution::new(
-                state.local_peer_id.to_string(),
-                res_type,
-                amount,
-                unit.clone(),
-            ));
+        ClientMessage::RequestSync => {
+            if !session.is_admin() {
+                return Err(HandlerError::forbidden("RequestSync requires admin"));
+            }
+            if let Err(e) = anti_entropy::request_sync(state).await {
+                warn!("{}", e);
+                return Err(HandlerError::internal(e));
+            }
+        }
 
-            match serde_json::to_vec(&resource_msg) {
-                Ok(data) => {
-                    if let Err(e) = state.network.publish(topics::RESOURCE, data).await {
-                        error!("Failed to publish resource contribution: {}", e);
-                    } else {
-                        let echo_msg = WsMessage::ResourceContribution {
-                            id: Uuid::new_v4().to_string(),
-                            peer_id: state.local_peer_id.to_string(),
-                            resource_type,
-                            amount,
-                            unit,
-                            timestamp,
-                        };
-                        let _ = state.event_tx.send(echo_msg);
+        msg @ (ClientMessage::SendVouch { .. }
+        | ClientMessage::RespondVouch { .. }
+        | ClientMessage::RevokeVouch { .. }
+        | ClientMessage::GetVouchStats
+        | ClientMessage::GetPendingVouches
+        | ClientMessage::GetMyVouches) => vouches::handle_request(msg, state, session).await?,
+
+        msg @ (ClientMessage::CreateCreditLine { .. }
+        | ClientMessage::GetCreditLines
+        | ClientMessage::GetCreditHistory { .. }
+        | ClientMessage::TransferCredit { .. }
+        | ClientMessage::RequestPayment { .. }) => credit::handle_request(msg, state, session).await?,
+
+        msg @ (ClientMessage::CreateProposal { .. }
+        | ClientMessage::AmendProposal { .. }
+        | ClientMessage::CancelProposal { .. }
+        | ClientMessage::CastVote { .. }
+        | ClientMessage::FinalizeProposal { .. }
+        | ClientMessage::GetProposals
+        | ClientMessage::GetGovernanceStats
+        | ClientMessage::GetGovernanceSummary) => governance::handle_request(msg, state, session).await?,
+
+        ClientMessage::ReplayForPeer { peer_id, since, after_seq, limit } => {
+            info!("ReplayForPeer: peer_id='{}', since={}", peer_id, since);
+
+            let limit = limit.unwrap_or(DEFAULT_REPLAY_PAGE).clamp(1, MAX_REPLAY_PAGE);
+            // Without a sequence cursor, exclude everything at exactly `since`
+            let after_seq = after_seq.unwrap_or(i64::MAX);
+
+            // Make sure events from this connection's earlier writes are logged
+            state.event_log_fence.wait().await;
+
+            // Only events this connection would have been sent live are
+            // replayed, so keep reading until one more than a page is
+            // visible or the log runs out
+            let delivery = session.delivery();
+            let (mut since, mut after_seq) = (since, after_seq);
+            let mut events = Vec::new();
+            loop {
+                let logged = match state.store.list_events_for_peer(&peer_id, since, after_seq, limit as i64 + 1).await {
+                    Ok(logged) => logged,
+                    Err(e) => {
+                        error!("Failed to replay events for {}: {}", peer_id, e);
+                        return Err(HandlerError::internal("Failed to load logged events"));
                     }
+                };
+                let exhausted = logged.len() <= limit;
+                if let Some(last) = logged.last() {
+                    (since, after_seq) = (last.timestamp, last.seq);
                 }
-                Err(e) => {
-                    error!("Failed to serialize resource contribution: {}", e);
+                events.extend(
+                    to_replayed(logged)
+                        .into_iter()
+                        .filter(|replayed| delivery.read().wants_logged(&replayed.event)),
+                );
+                if exhausted || events.len() > limit {
+                    break;
                 }
             }
+            let has_more = events.len() > limit;
+            events.truncate(limit);
+            session.reply(WsMessage::PeerReplay { peer_id, events, has_more });
         }
 
+        msg @ (ClientMessage::ReportResource { .. }
+        | ClientMessage::GetResourceContributors { .. }
+        | ClientMessage::GetPeerResourceHistory { .. }) => resources::handle_request(msg, state, session).await?,
+
         // ============ Room/Seance Handlers ============
 
-        ClientMessage::CreateRoom { room_id, room_name, description, is_public } => {
-            info!("CreateRoom: name='{}', is_public={:?}", room_name, is_public);
+        msg @ (ClientMessage::CreateRoom { .. }
+        | ClientMessage::JoinRoom { .. }
+        | ClientMessage::LeaveRoom { .. }
+        | ClientMessage::GetRooms) => rooms::handle_request(msg, state, session).await?,
+    }
+    Ok(())
+}
 
-            let timestamp = chrono::Utc::now().timestamp_millis();
-            let id = room_id.unwrap_or_else(|| Uuid::new_v4().to_string());
-            let topic = format!("/mycelial/1.0.0/room/{}", id);
-            let is_public = is_public.unwrap_or(true);
-
-            // Subscribe to the room topic
-            if let Err(e) = state.network.subscribe(&topic).await {
-                error!("Failed to subscribe to room topic {}: {}", topic, e);
-                let error_msg = WsMessage::Error {
-                    message: format!("Failed to create room: {}", e),
-                };
-                let _ = state.event_tx.send(error_msg);
-                return;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ServerConfig;
+    use crate::event_log::record_event;
+    use crate::proposal_types::ProposalKind;
+    use crate::server::messages::{Secret, UnreadCategory};
+    use crate::server::session::EncodingStats;
+    use crate::test_support::{
+        remote_peer, store_credit_line_from, store_local_proposal, test_session, test_state, test_state_with_config,
+    };
+    use mycelial_core::peer::{PeerId, PeerInfo};
+    use mycelial_core::reputation::Reputation;
+    use mycelial_network::NetworkCommand;
+    use mycelial_protocol::{PassingRule, QuorumMode};
+    use mycelial_state::{CreditLineRecord, VouchRecord};
+    use uuid::Uuid;
+
+    /// `keypair`'s hex-encoded signature over a connection's identify challenge
+    fn sign_identify(keypair: &mycelial_network::Keypair, connection_id: &str) -> String {
+        let signature = keypair.sign(identify_challenge(connection_id).as_bytes()).unwrap();
+        SignatureBytes::from_bytes(signature.try_into().unwrap()).to_hex()
+    }
+
+    fn chat(from: &str, to: Option<&str>, content: &str) -> WsMessage {
+        WsMessage::ChatMessage {
+            id: Uuid::new_v4().to_string(),
+            from: from.to_string(),
+            from_name: from.to_string(),
+            to: to.map(str::to_string),
+            room_id: None,
+            content: content.to_string(),
+            timestamp: 0,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_connection_greeted_with_hello_first() {
+        let (state, _commands) = test_state().await;
+
+        let greeting = greeting(&state, &SnapshotSections::default()).await;
+        match &greeting[0] {
+            WsMessage::Hello { protocol_version, peer_id, features, .. } => {
+                assert_eq!(*protocol_version, PROTOCOL_VERSION);
+                assert_eq!(*peer_id, state.local_peer_id.to_string());
+                assert!(features.iter().any(|f| f == "get_chat_history"));
             }
+            other => panic!("expected hello, got {:?}", other),
+        }
+        assert!(matches!(greeting[1], WsMessage::PeersList { .. }));
+    }
 
-            info!("Room created and subscribed to topic: {}", topic);
+    #[tokio::test]
+    async fn test_connection_snapshot_has_requested_sections() {
+        let (state, _commands) = test_state().await;
+        let proposal_id = store_local_proposal(&state).await;
+        let record = CreditLineRecord {
+            id: "l1".to_string(),
+            creditor: state.local_peer_id.to_string(),
+            debtor: "bob".to_string(),
+            limit: 50.0,
+            balance: 0.0,
+            created_at: 1,
+        };
+        state.store.insert_credit_line(&record).await.unwrap();
+        // Direct messages stay out of the greeting, even those to this node
+        record_event(&state, &chat("alice", Some(&state.local_peer_id.to_string()), "psst")).await;
+        record_event(&state, &chat("alice", None, "hello")).await;
+
+        let tags = |messages: &[WsMessage]| -> Vec<&'static str> { messages.iter().map(WsMessage::type_tag).collect() };
+        let full = greeting(&state, &SnapshotSections::default()).await;
+        assert_eq!(
+            tags(&full),
+            vec![
+                "hello",
+                "peers_list",
+                "proposals",
+                "credit_lines_list",
+                "resource_pool_update",
+                "resource_pool_update",
+                "resource_pool_update",
+                "resource_pool_update",
+                "chat_history",
+            ]
+        );
+        for message in &full {
+            match message {
+                WsMessage::Proposals { proposals } => assert_eq!(proposals[0].id, proposal_id),
+                WsMessage::CreditLinesList { lines } => assert_eq!(lines[0].id, "l1"),
+                WsMessage::ChatHistory { messages, .. } => {
+                    let contents: Vec<_> = messages.iter().map(|m| m.content.as_str()).collect();
+                    assert_eq!(contents, ["hello"]);
+                }
+                _ => {}
+            }
+        }
 
-            // Send room joined confirmation
-            let room_msg = WsMessage::RoomJoined {
-                id: id.clone(),
-                name: room_name,
-                description,
-                topic,
-                members: vec![state.local_peer_id.to_string()],
-                created_by: state.local_peer_id.to_string(),
-                created_at: timestamp,
-                is_public,
+        // Only what was asked for follows the Hello
+        let partial = greeting(&state, &SnapshotSections::parse("chat,credit_lines")).await;
+        assert_eq!(tags(&partial), vec!["hello", "credit_lines_list", "chat_history"]);
+        let bare = greeting(&state, &SnapshotSections::none()).await;
+        assert_eq!(tags(&bare), vec!["hello"]);
+    }
+
+    #[tokio::test]
+    async fn test_unparseable_message_answered_with_error() {
+        let (state, _commands) = test_state().await;
+        let (mut session, mut replies) = test_session(8);
+
+        handle_text("{not json", &state, &mut session).await;
+        assert!(matches!(
+            replies.try_recv(),
+            Ok(WsMessage::Error { code: ErrorCode::InvalidRequest, .. })
+        ));
+
+        // The serde error names what was wrong
+        handle_text(r#"{"type":"subscribe"}"#, &state, &mut session).await;
+        match replies.try_recv() {
+            Ok(WsMessage::Error { code: ErrorCode::InvalidRequest, message }) => {
+                assert!(message.contains("topic"), "{}", message)
+            }
+            other => panic!("expected error, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_client_ref_acknowledged() {
+        let (state, commands) = test_state().await;
+        let (mut session, mut replies) = test_session(8);
+        let send = |client_ref: &str| {
+            format!(r#"{{"type":"send_chat","content":"hi","client_ref":"{}"}}"#, client_ref)
+        };
+
+        handle_text(&send("first"), &state, &mut session).await;
+        match replies.try_recv() {
+            Ok(WsMessage::Ack { client_ref, ok, error }) => {
+                assert_eq!(client_ref, "first");
+                assert!(ok);
+                assert!(error.is_none());
+            }
+            other => panic!("expected ack, got {:?}", other),
+        }
+
+        // With the network gone the same send fails, and the ack says so
+        drop(commands);
+        handle_text(&send("second"), &state, &mut session).await;
+        match replies.try_recv() {
+            Ok(WsMessage::Ack { client_ref, ok, error }) => {
+                assert_eq!(client_ref, "second");
+                assert!(!ok);
+                assert!(error.unwrap().contains("publish"));
+            }
+            other => panic!("expected ack, got {:?}", other),
+        }
+
+        // Messages without a reference aren't acknowledged
+        handle_text(r#"{"type":"send_chat","content":"hi"}"#, &state, &mut session).await;
+        assert!(replies.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_direct_messages_reach_only_their_identity() {
+        let (state, _commands) = test_state().await;
+        let (mut alice, _alice_replies) = test_session(8);
+        let (mut bob, _bob_replies) = test_session(8);
+        alice.identify("alice");
+        bob.identify("bob");
+
+        let to_bob = chat("carol", Some("bob"), "for bob");
+        assert!(bob.delivery().read().wants(&to_bob));
+        assert!(!alice.delivery().read().wants(&to_bob));
+
+        // Senders see their own direct messages; public chat reaches everyone
+        let from_alice = chat("alice", Some("carol"), "from alice");
+        assert!(alice.delivery().read().wants(&from_alice));
+        assert!(!bob.delivery().read().wants(&from_alice));
+        let public = chat("carol", None, "hello all");
+        assert!(alice.delivery().read().wants(&public) && bob.delivery().read().wants(&public));
+
+        // An unidentified connection gets no direct messages at all
+        let (anonymous, _replies) = test_session(8);
+        assert!(!anonymous.delivery().read().wants(&to_bob));
+
+        // Nor do others see direct messages being edited or deleted, live or replayed
+        let edited = WsMessage::ChatEdited {
+            message_id: "m1".to_string(),
+            from: "carol".to_string(),
+            to: Some("bob".to_string()),
+            content: "for bob only".to_string(),
+            edited_at: 1,
+        };
+        let deleted = WsMessage::ChatDeleted {
+            message_id: "m1".to_string(),
+            from: "carol".to_string(),
+            to: Some("bob".to_string()),
+            deleted_at: 2,
+        };
+        for event in [edited, deleted] {
+            let logged = serde_json::to_value(&event).unwrap();
+            assert!(bob.delivery().read().wants(&event) && bob.delivery().read().wants_logged(&logged));
+            assert!(!alice.delivery().read().wants(&event) && !alice.delivery().read().wants_logged(&logged));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_direct_chat_published_to_recipient_inbox() {
+        let (state, mut commands) = test_state().await;
+        let (mut session, mut replies) = test_session(8);
+        let mut events = state.event_tx.subscribe();
+        let bob = remote_peer();
+
+        let msg = ClientMessage::SendChat { content: "psst".to_string(), to: Some(bob.clone()), room_id: None };
+        handle_client_message(msg, &state, &mut session).await;
+        match commands.try_recv() {
+            Ok(NetworkCommand::Publish { topic, data }) => {
+                assert_eq!(topic, topics::direct(&bob));
+                let msg: mycelial_core::message::Message = serde_json::from_slice(&data).unwrap();
+                assert_eq!(msg.recipient, Some(PeerId(bob.clone())));
+            }
+            other => panic!("expected publish, got {:?}", other),
+        }
+        match events.try_recv() {
+            Ok(WsMessage::ChatMessage { to, .. }) => assert_eq!(to, Some(bob.clone())),
+            other => panic!("expected chat echo, got {:?}", other),
+        }
+
+        // Recipients must be peer IDs, and rooms can't be combined with them
+        for (to, room_id) in [(Some("bob".to_string()), None), (Some(bob), Some("general".to_string()))] {
+            let msg = ClientMessage::SendChat { content: "psst".to_string(), to, room_id };
+            handle_client_message(msg, &state, &mut session).await;
+            assert!(matches!(replies.try_recv(), Ok(WsMessage::Error { code: ErrorCode::InvalidRequest, .. })));
+        }
+        assert!(commands.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_identify_requires_peer_signature() {
+        let (state, _commands) = test_state().await;
+        let (mut session, mut replies) = test_session(8);
+        let keypair = mycelial_network::Keypair::generate_ed25519();
+        let peer = keypair.public().to_peer_id().to_base58();
+        let other = mycelial_network::Keypair::generate_ed25519();
+
+        // Unsigned, signed by another key, or signed for another connection
+        for signature in [None, Some(sign_identify(&other, session.id())), Some(sign_identify(&keypair, "other"))] {
+            let msg = ClientMessage::Identify { peer_id: peer.clone(), signature };
+            handle_client_message(msg, &state, &mut session).await;
+            assert!(matches!(replies.try_recv(), Ok(WsMessage::Error { code: ErrorCode::Unauthorized, .. })));
+            assert_eq!(session.identity(), None);
+        }
+
+        let signature = Some(sign_identify(&keypair, session.id()));
+        handle_client_message(ClientMessage::Identify { peer_id: peer.clone(), signature }, &state, &mut session).await;
+        assert!(replies.try_recv().is_err());
+        assert_eq!(session.identity(), Some(peer));
+
+        // Acting for the local node again needs no signature
+        let local = state.local_peer_id.to_string();
+        handle_client_message(ClientMessage::Identify { peer_id: local.clone(), signature: None }, &state, &mut session).await;
+        assert_eq!(session.identity(), Some(local));
+    }
+
+    #[tokio::test]
+    async fn test_publish_rate_limited_per_connection() {
+        let (state, mut commands) = test_state().await;
+        let (mut session, mut replies) = test_session(8);
+        // A burst of three, then effectively no refill during the test
+        session.set_publish_limit(TokenBucket::new(0.001, 3));
+
+        for i in 0..5 {
+            let msg = ClientMessage::SendChat {
+                content: format!("flood {}", i),
+                to: None,
+                room_id: None,
             };
-            let _ = state.event_tx.send(room_msg);
+            handle_client_message(msg, &state, &mut session).await;
         }
 
-        ClientMessage::JoinRoom { room_id, room_name: _ } => {
-            info!("JoinRoom: room_id='{}'", room_id);
+        let mut published = 0;
+        while let Ok(command) = commands.try_recv() {
+            if matches!(command, NetworkCommand::Publish { .. }) {
+                published += 1;
+            }
+        }
+        assert_eq!(published, 3);
 
-            let timestamp = chrono::Utc::now().timestamp_millis();
-            let topic = format!("/mycelial/1.0.0/room/{}", room_id);
+        let mut limited = 0;
+        while let Ok(reply) = replies.try_recv() {
+            if matches!(reply, WsMessage::Error { code: ErrorCode::RateLimited, .. }) {
+                limited += 1;
+            }
+        }
+        assert_eq!(limited, 2);
 
-            // Subscribe to the room topic
-            if let Err(e) = state.network.subscribe(&topic).await {
-                error!("Failed to subscribe to room topic {}: {}", topic, e);
-                let error_msg = WsMessage::Error {
-                    message: format!("Failed to join room: {}", e),
-                };
-                let _ = state.event_tx.send(error_msg);
-                return;
+        // Requests that don't publish aren't limited
+        handle_client_message(ClientMessage::GetPeers, &state, &mut session).await;
+        assert!(!matches!(replies.try_recv(), Ok(WsMessage::Error { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_chat_history_pages() {
+        let (state, _commands) = test_state().await;
+        let (mut session, mut replies) = test_session(8);
+
+        let history = |before: Option<(i64, i64)>| ClientMessage::GetChatHistory {
+            before: before.map(|(timestamp, _)| timestamp),
+            before_seq: before.map(|(_, seq)| seq),
+            limit: 2,
+        };
+        handle_client_message(history(None), &state, &mut session).await;
+        match replies.try_recv() {
+            Ok(WsMessage::ChatHistory { messages, has_more }) => {
+                assert!(messages.is_empty());
+                assert!(!has_more);
             }
+            other => panic!("expected chat history, got {:?}", other),
+        }
 
-            info!("Joined room and subscribed to topic: {}", topic);
+        // Two messages share a millisecond, and the first page splits them
+        for (timestamp, content) in [(1, "first at 1"), (1, "second at 1"), (2, "at 2")] {
+            let mut msg = chat("alice", None, content);
+            if let WsMessage::ChatMessage { timestamp: ref mut ts, .. } = msg {
+                *ts = timestamp;
+            }
+            record_event(&state, &msg).await;
+        }
 
-            // Send room joined confirmation
-            // Note: In a full implementation, we'd fetch room details from state/network
-            let room_msg = WsMessage::RoomJoined {
-                id: room_id.clone(),
-                name: format!("Room {}", &room_id[..8.min(room_id.len())]),
-                description: None,
-                topic: topic.clone(),
-                members: vec![state.local_peer_id.to_string()],
-                created_by: "unknown".to_string(),
-                created_at: timestamp,
-                is_public: true,
+        handle_client_message(history(None), &state, &mut session).await;
+        let oldest = match replies.try_recv() {
+            Ok(WsMessage::ChatHistory { messages, has_more }) => {
+                let contents: Vec<_> = messages.iter().map(|m| m.content.as_str()).collect();
+                assert_eq!(contents, vec!["at 2", "second at 1"]);
+                assert!(has_more);
+                (messages[1].timestamp, messages[1].seq)
+            }
+            other => panic!("expected chat history, got {:?}", other),
+        };
+
+        // The next page continues from the oldest message seen
+        handle_client_message(history(Some(oldest)), &state, &mut session).await;
+        match replies.try_recv() {
+            Ok(WsMessage::ChatHistory { messages, has_more }) => {
+                assert_eq!(messages.len(), 1);
+                assert_eq!(messages[0].content, "first at 1");
+                assert!(!has_more);
+            }
+            other => panic!("expected chat history, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_resume_skips_events_not_for_connection() {
+        let (state, _commands) = test_state().await;
+        let (mut session, mut reply_rx) = test_session(8);
+        session.identify("alice");
+        let now = chrono::Utc::now().timestamp_millis();
+        let (token, _) = state.resume_tokens.issue("earlier", state.store.latest_event_seq().await.unwrap(), now);
+        record_event(&state, &chat("bob", Some("carol"), "between others")).await;
+        record_event(&state, &chat("bob", Some("alice"), "for alice")).await;
+        record_event(&state, &chat("bob", None, "for everyone")).await;
+
+        handle_client_message(ClientMessage::Resume { token }, &state, &mut session).await;
+        match reply_rx.try_recv() {
+            Ok(WsMessage::Resumed { events, .. }) => {
+                let contents: Vec<_> = events.iter().map(|e| e.event["content"].as_str().unwrap()).collect();
+                assert_eq!(contents, ["for alice", "for everyone"]);
+            }
+            other => panic!("expected resumed, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_resume_with_reconnection_token() {
+        let (state, _commands) = test_state().await;
+        let (mut session, mut reply_rx) = test_session(8);
+        record_event(&state, &chat("alice", None, "seen")).await;
+        let last_seq = state.store.latest_event_seq().await.unwrap();
+        let now = chrono::Utc::now().timestamp_millis();
+        let (token, _) = state.resume_tokens.issue("earlier", last_seq, now);
+        record_event(&state, &chat("bob", None, "missed")).await;
+
+        handle_client_message(ClientMessage::Resume { token: token.clone() }, &state, &mut session).await;
+        match reply_rx.try_recv() {
+            Ok(WsMessage::Resumed { resumed_from, events }) => {
+                assert_eq!(resumed_from, "earlier");
+                assert_eq!(events.len(), 1);
+                assert_eq!(events[0].event["content"], "missed");
+            }
+            other => panic!("expected resumed, got {:?}", other),
+        }
+
+        // A client can't rewind its starting point by editing the token
+        let tampered = token.replacen(&format!(".{}.", last_seq), ".0.", 1);
+        handle_client_message(ClientMessage::Resume { token: tampered }, &state, &mut session).await;
+        assert!(matches!(reply_rx.try_recv(), Ok(WsMessage::Resync { .. })));
+
+        let (expired, _) = state.resume_tokens.issue("earlier", last_seq, now - 3_600_000);
+        handle_client_message(ClientMessage::Resume { token: expired }, &state, &mut session).await;
+        match reply_rx.try_recv() {
+            Ok(WsMessage::Resync { reason }) => assert_eq!(reason, "Reconnection token expired"),
+            other => panic!("expected resync, got {:?}", other),
+        }
+    }
+
+    /// Log a chat message at a fixed time
+    async fn log_chat(state: &AppState, from: &str, to: Option<&str>, timestamp: i64) {
+        let msg = chat(from, to, "hello");
+        let payload = serde_json::to_string(&msg).unwrap();
+        state
+            .store
+            .append_event("chat_message", timestamp, &payload, &msg.involved_peers())
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_unread_counts_cleared_by_mark_seen() {
+        let (state, _commands) = test_state().await;
+        let (mut session, mut reply_rx) = test_session(8);
+        let me = remote_peer();
+        session.identify(me.clone());
+        let local = state.local_peer_id.to_string();
+        let unread = |reply: Result<WsMessage, _>| match reply {
+            Ok(WsMessage::UnreadCounts { chat, proposals, vouches }) => (chat, proposals, vouches),
+            other => panic!("expected unread counts, got {:?}", other),
+        };
+
+        log_chat(&state, "alice", None, 1_000).await;
+        log_chat(&state, "alice", Some(&me), 1_100).await;
+        // Neither direct messages between other peers nor the identity's own count
+        log_chat(&state, "alice", Some("bob"), 1_200).await;
+        log_chat(&state, &me, None, 1_300).await;
+        for (id, vouchee) in [("v1", &me), ("v2", &local)] {
+            state
+                .store
+                .insert_vouch(&VouchRecord {
+                    id: id.to_string(),
+                    voucher: "alice".to_string(),
+                    vouchee: vouchee.clone(),
+                    weight: 0.5,
+                    message: None,
+                    status: "pending".to_string(),
+                    created_at: 1_000,
+                })
+                .await
+                .unwrap();
+        }
+
+        handle_client_message(ClientMessage::GetUnreadCounts, &state, &mut session).await;
+        assert_eq!(unread(reply_rx.try_recv()), (2, 0, 1));
+
+        let mark = ClientMessage::MarkSeen { category: UnreadCategory::Chat, up_to: 1_300 };
+        handle_client_message(mark, &state, &mut session).await;
+        assert_eq!(unread(reply_rx.try_recv()), (0, 0, 1));
+
+        // A new message after the marker is unread again
+        log_chat(&state, "bob", None, 1_400).await;
+        handle_client_message(ClientMessage::GetUnreadCounts, &state, &mut session).await;
+        assert_eq!(unread(reply_rx.try_recv()), (1, 0, 1));
+
+        // The marker belongs to the connection's identity, not the node's
+        let counts = state.store.unread_counts(&local).await.unwrap();
+        assert_eq!((counts.chat, counts.vouches), (3, 1));
+    }
+
+    #[tokio::test]
+    async fn test_chat_content_size_limit() {
+        let (state, mut commands) = test_state().await;
+        let (mut session, mut replies) = test_session(8);
+        let limit = state.config.max_chat_content;
+        let send = |content: String| ClientMessage::SendChat { content, to: None, room_id: None };
+
+        handle_client_message(send("x".repeat(limit + 1)), &state, &mut session).await;
+        match replies.try_recv() {
+            Ok(WsMessage::Error { code: ErrorCode::InvalidRequest, message }) => {
+                assert!(message.contains(&format!("limit of {} bytes", limit)));
+            }
+            other => panic!("expected error, got {:?}", other),
+        }
+        assert!(commands.try_recv().is_err());
+
+        // Content exactly at the limit is published
+        handle_client_message(send("x".repeat(limit)), &state, &mut session).await;
+        assert!(replies.try_recv().is_err());
+        assert!(matches!(commands.try_recv(), Ok(NetworkCommand::Publish { .. })));
+
+        // Whole messages over the frame limit are refused before parsing
+        let oversized = "y".repeat(state.config.max_frame_size + 1);
+        assert!(!within_frame_limit(&oversized, &state, &session));
+        assert!(matches!(
+            replies.try_recv(),
+            Ok(WsMessage::Error { code: ErrorCode::InvalidRequest, .. })
+        ));
+        assert!(within_frame_limit("{\"type\":\"get_peers\"}", &state, &session));
+    }
+
+    #[tokio::test]
+    async fn test_ping_answered_with_pong_to_sender_only() {
+        let (state, _commands) = test_state().await;
+        let (mut session, mut reply_rx) = test_session(8);
+        let mut events = state.event_tx.subscribe();
+
+        let before = chrono::Utc::now().timestamp_millis();
+        let ping = ClientMessage::Ping { nonce: "abc-123".to_string() };
+        handle_client_message(ping, &state, &mut session).await;
+
+        match reply_rx.try_recv() {
+            Ok(WsMessage::Pong { nonce, server_time }) => {
+                assert_eq!(nonce, "abc-123");
+                assert!(server_time >= before);
+            }
+            other => panic!("expected pong, got {:?}", other),
+        }
+        assert!(events.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_replay_for_peer_filters_and_orders() {
+        let (state, _commands) = test_state().await;
+
+        record_event(&state, &chat("alice", None, "first")).await;
+        record_event(&state, &chat("bob", None, "unrelated")).await;
+        record_event(&state, &chat("bob", Some("alice"), "second")).await;
+        record_event(&state, &WsMessage::PeerLeft { peer_id: "alice".to_string() }).await;
+
+        let mut broadcasts = state.event_tx.subscribe();
+        let replay = || ClientMessage::ReplayForPeer {
+            peer_id: "alice".to_string(),
+            since: 0,
+            after_seq: None,
+            limit: Some(2),
+        };
+        let (mut session, mut replies) = test_session(8);
+        session.identify("alice");
+        handle_client_message(replay(), &state, &mut session).await;
+
+        match replies.try_recv().unwrap() {
+            WsMessage::PeerReplay { peer_id, events, has_more } => {
+                assert_eq!(peer_id, "alice");
+                assert!(has_more);
+                let contents: Vec<_> = events.iter().map(|e| e.event["content"].clone()).collect();
+                assert_eq!(contents, vec!["first", "second"]);
+                assert!(events.windows(2).all(|w| w[0].timestamp <= w[1].timestamp));
+            }
+            other => panic!("unexpected message: {:?}", other),
+        }
+        // The replay goes only to the requester
+        assert!(broadcasts.try_recv().is_err());
+
+        // Another identity doesn't see alice's direct messages
+        let (mut session, mut replies) = test_session(8);
+        session.identify("carol");
+        handle_client_message(replay(), &state, &mut session).await;
+        match replies.try_recv().unwrap() {
+            WsMessage::PeerReplay { events, has_more, .. } => {
+                assert!(!has_more);
+                let types: Vec<_> = events.iter().map(|e| e.event["type"].clone()).collect();
+                assert_eq!(types, vec!["chat_message", "peer_left"]);
+                assert_eq!(events[0].event["content"], "first");
+            }
+            other => panic!("unexpected message: {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_replay_observes_own_prior_write() {
+        let (state, _commands) = test_state().await;
+        tokio::spawn(crate::event_log::run_event_recorder(state.clone()));
+        // Let the recorder subscribe before anything is broadcast
+        tokio::task::yield_now().await;
+
+        let (mut session, mut replies) = test_session(8);
+        let local = state.local_peer_id.to_string();
+
+        let write = ClientMessage::SendChat {
+            content: "hello".to_string(),
+            to: None,
+            room_id: None,
+        };
+        handle_client_message(write, &state, &mut session).await;
+        let read = ClientMessage::ReplayForPeer {
+            peer_id: local.clone(),
+            since: 0,
+            after_seq: None,
+            limit: None,
+        };
+        handle_client_message(read, &state, &mut session).await;
+
+        let replay = std::iter::from_fn(|| replies.try_recv().ok())
+            .find_map(|event| match event {
+                WsMessage::PeerReplay { events, .. } => Some(events),
+                _ => None,
+            })
+            .expect("replay should be sent");
+        assert_eq!(replay.len(), 1);
+        assert_eq!(replay[0].event["content"], "hello");
+    }
+
+    #[tokio::test]
+    async fn test_subscription_cap_rejects_extra_subscribe() {
+        let config = ServerConfig {
+            max_subscriptions_per_connection: 2,
+            ..ServerConfig::default()
+        };
+        let (state, _commands) = test_state_with_config(config).await;
+        let (mut session, mut replies) = test_session(state.config.max_subscriptions_per_connection);
+
+        for topic in ["a", "b", "c"] {
+            let msg = ClientMessage::Subscribe { topic: topic.to_string() };
+            handle_client_message(msg, &state, &mut session).await;
+        }
+
+        for topic in ["a", "b"] {
+            match replies.try_recv().unwrap() {
+                WsMessage::SubscribeResult { topic: t, success, .. } => {
+                    assert_eq!(t, topic);
+                    assert!(success);
+                }
+                other => panic!("unexpected message: {:?}", other),
+            }
+        }
+        match replies.try_recv().unwrap() {
+            WsMessage::Error { code, .. } => assert_eq!(code, ErrorCode::RateLimited),
+            other => panic!("unexpected message: {:?}", other),
+        }
+        assert!(replies.try_recv().is_err());
+
+        // Freeing a slot lets the next subscription through
+        session.remove_subscription("a");
+        let msg = ClientMessage::Subscribe { topic: "c".to_string() };
+        handle_client_message(msg, &state, &mut session).await;
+        assert!(matches!(
+            replies.try_recv(),
+            Ok(WsMessage::SubscribeResult { success: true, .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_failed_subscribe_reported_to_client() {
+        let (state, commands) = test_state().await;
+        // With the network task gone, subscribe commands can't be delivered
+        drop(commands);
+        let (mut session, mut replies) = test_session(8);
+
+        let msg = ClientMessage::Subscribe { topic: "news".to_string() };
+        handle_client_message(msg, &state, &mut session).await;
+
+        match replies.try_recv().unwrap() {
+            WsMessage::SubscribeResult { topic, success, error } => {
+                assert_eq!(topic, "news");
+                assert!(!success);
+                assert!(error.is_some());
+            }
+            other => panic!("unexpected message: {:?}", other),
+        }
+        // The failed topic doesn't hold a subscription slot
+        assert!(!session.remove_subscription("news"));
+    }
+
+    #[tokio::test]
+    async fn test_failed_publish_surfaces_network_error() {
+        let (state, commands) = test_state().await;
+        drop(commands);
+        let mut events = state.event_tx.subscribe();
+        let (mut session, _replies) = test_session(8);
+
+        // Repeated failures of the same operation are reported once
+        for content in ["hello", "again"] {
+            let msg = ClientMessage::SendChat {
+                content: content.to_string(),
+                to: None,
+                room_id: None,
             };
-            let _ = state.event_tx.send(room_msg);
+            handle_client_message(msg, &state, &mut session).await;
+        }
 
-            // Notify other room members (broadcast to room topic)
-            let peer_joined_msg = WsMessage::RoomPeerJoined {
-                room_id: room_id.clone(),
-                peer_id: state.local_peer_id.to_string(),
-                peer_name: Some(state.node_name.clone()),
+        let mut reported = Vec::new();
+        while let Ok(event) = events.try_recv() {
+            if let WsMessage::NetworkError { operation, recoverable, .. } = event {
+                reported.push((operation, recoverable));
+            }
+        }
+        // The network task is gone, so retrying won't help
+        assert_eq!(reported, vec![("publish".to_string(), false)]);
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_many_reports_per_topic() {
+        let (state, mut commands) = test_state().await;
+        let (mut session, mut replies) = test_session(8);
+
+        let topics = ["news", "", "bad topic", "alerts"];
+        let msg = ClientMessage::SubscribeMany {
+            topics: topics.iter().map(|t| t.to_string()).collect(),
+        };
+        handle_client_message(msg, &state, &mut session).await;
+
+        match replies.try_recv().unwrap() {
+            WsMessage::SubscribeManyResult { results } => {
+                let outcomes: Vec<_> = results.iter().map(|r| (r.topic.as_str(), r.success)).collect();
+                assert_eq!(
+                    outcomes,
+                    vec![("news", true), ("", false), ("bad topic", false), ("alerts", true)]
+                );
+                assert!(results.iter().all(|r| r.success == r.error.is_none()));
+            }
+            other => panic!("unexpected message: {:?}", other),
+        }
+
+        // Only the valid topics reached the network
+        let mut subscribed = Vec::new();
+        while let Ok(command) = commands.try_recv() {
+            if let NetworkCommand::Subscribe { topic } = command {
+                subscribed.push(topic);
+            }
+        }
+        assert_eq!(subscribed, vec!["news", "alerts"]);
+        assert!(session.remove_subscription("news"));
+        assert!(!session.remove_subscription("bad topic"));
+    }
+
+    #[tokio::test]
+    async fn test_unsubscribe_releases_topic_after_last_subscriber() {
+        let (state, mut commands) = test_state().await;
+        let (mut first, mut first_replies) = test_session(8);
+        let (mut second, _second_replies) = test_session(8);
+        let subscribe = || ClientMessage::Subscribe { topic: "news".to_string() };
+        let unsubscribe = || ClientMessage::Unsubscribe { topic: "news".to_string() };
+
+        handle_client_message(subscribe(), &state, &mut first).await;
+        handle_client_message(subscribe(), &state, &mut second).await;
+        handle_client_message(unsubscribe(), &state, &mut first).await;
+        // The second connection still needs the topic
+        assert!(matches!(commands.try_recv(), Ok(NetworkCommand::Subscribe { topic }) if topic == "news"));
+        assert!(commands.try_recv().is_err());
+
+        handle_client_message(unsubscribe(), &state, &mut second).await;
+        assert!(matches!(commands.try_recv(), Ok(NetworkCommand::Unsubscribe { topic }) if topic == "news"));
+
+        let _ = first_replies.try_recv();
+        match first_replies.try_recv() {
+            Ok(WsMessage::UnsubscribeResult { topic, success: true, error: None }) => assert_eq!(topic, "news"),
+            other => panic!("expected unsubscribe result, got {:?}", other),
+        }
+        // Unsubscribing again is an error
+        handle_client_message(unsubscribe(), &state, &mut first).await;
+        assert!(matches!(
+            first_replies.try_recv(),
+            Ok(WsMessage::UnsubscribeResult { success: false, .. })
+        ));
+        assert!(commands.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_raw_protocol_capability_gated_by_config() {
+        let (state, _commands) = test_state().await;
+        let (mut session, mut replies) = test_session(8);
+        let msg = ClientMessage::SetCapability {
+            capability: Capability::RawProtocol,
+            enabled: true,
+        };
+        handle_client_message(msg, &state, &mut session).await;
+
+        match replies.try_recv().unwrap() {
+            WsMessage::Error { code, .. } => assert_eq!(code, ErrorCode::Forbidden),
+            other => panic!("unexpected message: {:?}", other),
+        }
+        let raw = WsMessage::RawProtocol {
+            topic: "t".to_string(),
+            json: serde_json::Value::Null,
+        };
+        assert!(!session.delivery().read().wants(&raw));
+    }
+
+    #[tokio::test]
+    async fn test_event_filter_skips_unlisted_broadcasts() {
+        let (state, _commands) = test_state().await;
+        let (mut session, mut replies) = test_session(8);
+        let filter = |include: &[&str]| ClientMessage::SetEventFilter {
+            include: include.iter().map(|tag| tag.to_string()).collect(),
+        };
+
+        handle_client_message(filter(&["chat_message", " "]), &state, &mut session).await;
+        assert!(matches!(
+            replies.try_recv(),
+            Ok(WsMessage::Error { code: ErrorCode::InvalidRequest, .. })
+        ));
+        // A misspelt tag would silently filter out everything
+        handle_client_message(filter(&["chat_mesage"]), &state, &mut session).await;
+        match replies.try_recv() {
+            Ok(WsMessage::Error { code: ErrorCode::InvalidRequest, message }) => {
+                assert_eq!(message, "Unknown event type chat_mesage");
+            }
+            other => panic!("expected error, got {:?}", other),
+        }
+        assert!(session.delivery().read().wants(&current_stats(&state).await));
+
+        handle_client_message(filter(&["chat_message"]), &state, &mut session).await;
+        assert!(replies.try_recv().is_err());
+        let stats = current_stats(&state).await;
+        assert!(!session.delivery().read().wants(&stats));
+        assert!(session.delivery().read().wants(&chat("alice", None, "hi")));
+    }
+
+    #[tokio::test]
+    async fn test_lagged_receiver_resyncs_and_continues() {
+        let (session, _replies) = test_session(8);
+        let delivery = session.delivery();
+        let mut backlog = CollapseBuffer::default();
+        let (tx, mut rx) = tokio::sync::broadcast::channel(2);
+        for i in 0..5 {
+            tx.send(WsMessage::PeerLeft { peer_id: format!("peer-{}", i) }).unwrap();
+        }
+
+        match recv_broadcast(&mut rx, &mut backlog, &delivery).await {
+            Some(WsMessage::Resync { reason }) => assert_eq!(reason, "Missed 3 events while lagging"),
+            other => panic!("expected resync, got {:?}", other),
+        }
+
+        // Delivery carries on with what is still buffered, then new events
+        async fn next_left(
+            rx: &mut broadcast::Receiver<WsMessage>,
+            backlog: &mut CollapseBuffer,
+            delivery: &RwLock<DeliveryPrefs>,
+        ) -> String {
+            match recv_broadcast(rx, backlog, delivery).await {
+                Some(WsMessage::PeerLeft { peer_id }) => peer_id,
+                other => panic!("expected peer left, got {:?}", other),
+            }
+        }
+        assert_eq!(next_left(&mut rx, &mut backlog, &delivery).await, "peer-3");
+        assert_eq!(next_left(&mut rx, &mut backlog, &delivery).await, "peer-4");
+        tx.send(WsMessage::PeerLeft { peer_id: "peer-5".to_string() }).unwrap();
+        assert_eq!(next_left(&mut rx, &mut backlog, &delivery).await, "peer-5");
+
+        // A connection collapsing updates is told it lagged too
+        session.set_capability(Capability::CollapseUpdates, true);
+        for i in 6..11 {
+            tx.send(WsMessage::PeerLeft { peer_id: format!("peer-{}", i) }).unwrap();
+        }
+        assert!(matches!(
+            recv_broadcast(&mut rx, &mut backlog, &delivery).await,
+            Some(WsMessage::Resync { .. })
+        ));
+        assert_eq!(next_left(&mut rx, &mut backlog, &delivery).await, "peer-9");
+        // The rest was already waiting, so it is served from the backlog
+        assert!(matches!(backlog.pop(), Some(WsMessage::PeerLeft { peer_id }) if peer_id == "peer-10"));
+    }
+
+    #[tokio::test]
+    async fn test_set_nickname_renames_node() {
+        let (state, _commands) = test_state().await;
+        let (mut session, mut replies) = test_session(8);
+        let mut events = state.event_tx.subscribe();
+
+        handle_client_message(ClientMessage::SetNickname { name: " Hyphae ".to_string() }, &state, &mut session).await;
+        match events.try_recv().unwrap() {
+            WsMessage::NodeRenamed { name } => assert_eq!(name, "Hyphae"),
+            other => panic!("unexpected message: {:?}", other),
+        }
+        assert_eq!(state.node_name(), "Hyphae");
+
+        // Over-length names are refused and leave the name alone
+        handle_client_message(ClientMessage::SetNickname { name: "x".repeat(65) }, &state, &mut session).await;
+        assert!(matches!(
+            replies.try_recv(),
+            Ok(WsMessage::Error { code: ErrorCode::InvalidRequest, .. })
+        ));
+        assert!(events.try_recv().is_err());
+        assert_eq!(state.node_name(), "Hyphae");
+    }
+
+    #[tokio::test]
+    async fn test_typing_relayed_once_per_debounce() {
+        let (state, _commands) = test_state().await;
+        let (mut session, _replies) = test_session(8);
+        let mut events = state.event_tx.subscribe();
+
+        for _ in 0..5 {
+            handle_client_message(ClientMessage::Typing { to: Some("bob".to_string()) }, &state, &mut session).await;
+        }
+
+        match events.try_recv().unwrap() {
+            WsMessage::TypingIndicator { from, to, .. } => {
+                assert_eq!(from, state.local_peer_id.to_string());
+                assert_eq!(to.as_deref(), Some("bob"));
+            }
+            other => panic!("unexpected message: {:?}", other),
+        }
+        assert!(events.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_stats_replied_only_to_requester() {
+        let (state, _commands) = test_state().await;
+        let (mut session, mut replies) = test_session(8);
+        let mut events = state.event_tx.subscribe();
+        for id in ["a", "b"] {
+            let peer = PeerInfo {
+                id: mycelial_core::peer::PeerId(id.to_string()),
+                public_key: id.to_string(),
+                addresses: vec![],
+                first_seen: chrono::Utc::now(),
+                last_seen: chrono::Utc::now(),
+                name: None,
             };
-            if let Ok(data) = serde_json::to_vec(&peer_joined_msg) {
-                if let Err(e) = state.network.publish(&topic, data).await {
-                    warn!("Failed to announce room join: {}", e);
+            state.store.upsert_peer(&peer, None).await.unwrap();
+        }
+
+        handle_client_message(ClientMessage::GetStats, &state, &mut session).await;
+
+        match replies.try_recv().unwrap() {
+            WsMessage::Stats { peer_count, .. } => assert_eq!(peer_count, 2),
+            other => panic!("unexpected message: {:?}", other),
+        }
+        assert!(events.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_edit_own_chat_message() {
+        let (state, mut commands) = test_state().await;
+        let (mut session, mut replies) = test_session(8);
+        let mut events = state.event_tx.subscribe();
+        let local = state.local_peer_id.to_string();
+        let own = chat(&local, None, "helo");
+        let WsMessage::ChatMessage { id, .. } = &own else { unreachable!() };
+        let id = id.clone();
+        record_event(&state, &own).await;
+
+        let edit = ClientMessage::EditChat { message_id: id.clone(), content: "hello".to_string() };
+        handle_client_message(edit, &state, &mut session).await;
+        assert!(matches!(commands.try_recv(), Ok(NetworkCommand::Publish { .. })));
+        match events.try_recv() {
+            Ok(WsMessage::ChatEdited { message_id, content, .. }) => {
+                assert_eq!(message_id, id);
+                assert_eq!(content, "hello");
+            }
+            other => panic!("expected chat edited, got {:?}", other),
+        }
+
+        handle_client_message(ClientMessage::GetChatHistory { before: None, before_seq: None, limit: 10 }, &state, &mut session).await;
+        match replies.try_recv() {
+            Ok(WsMessage::ChatHistory { messages, .. }) => {
+                assert_eq!(messages[0].content, "hello");
+                assert!(messages[0].edited_at.is_some());
+                assert!(!messages[0].deleted);
+            }
+            other => panic!("expected chat history, got {:?}", other),
+        }
+
+        handle_client_message(ClientMessage::DeleteChat { message_id: id.clone() }, &state, &mut session).await;
+        assert!(matches!(events.try_recv(), Ok(WsMessage::ChatDeleted { to: None, .. })));
+        handle_client_message(ClientMessage::GetChatHistory { before: None, before_seq: None, limit: 10 }, &state, &mut session).await;
+        match replies.try_recv() {
+            Ok(WsMessage::ChatHistory { messages, .. }) => {
+                assert!(messages[0].deleted);
+                assert!(messages[0].content.is_empty());
+            }
+            other => panic!("expected chat history, got {:?}", other),
+        }
+        while commands.try_recv().is_ok() {}
+
+        // A deleted message can't be edited
+        let edit = ClientMessage::EditChat { message_id: id.clone(), content: "back again".to_string() };
+        handle_client_message(edit, &state, &mut session).await;
+        assert!(matches!(replies.try_recv(), Ok(WsMessage::Error { code: ErrorCode::InvalidRequest, .. })));
+        assert!(commands.try_recv().is_err());
+        assert!(events.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_edit_of_another_peers_chat_rejected() {
+        let (state, mut commands) = test_state().await;
+        let (mut session, mut replies) = test_session(8);
+        let theirs = chat("alice", None, "mine, not yours");
+        let WsMessage::ChatMessage { id, .. } = &theirs else { unreachable!() };
+        let id = id.clone();
+        record_event(&state, &theirs).await;
+
+        let edit = ClientMessage::EditChat { message_id: id, content: "rewritten".to_string() };
+        handle_client_message(edit, &state, &mut session).await;
+        assert!(matches!(replies.try_recv(), Ok(WsMessage::Error { code: ErrorCode::Forbidden, .. })));
+        assert!(commands.try_recv().is_err());
+
+        let unknown = ClientMessage::DeleteChat { message_id: "missing".to_string() };
+        handle_client_message(unknown, &state, &mut session).await;
+        assert!(matches!(replies.try_recv(), Ok(WsMessage::Error { code: ErrorCode::InvalidRequest, .. })));
+
+        handle_client_message(ClientMessage::GetChatHistory { before: None, before_seq: None, limit: 10 }, &state, &mut session).await;
+        match replies.try_recv() {
+            Ok(WsMessage::ChatHistory { messages, .. }) => assert_eq!(messages[0].content, "mine, not yours"),
+            other => panic!("expected chat history, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_mark_read_sends_receipt_for_direct_messages() {
+        let (state, mut commands) = test_state().await;
+        let (mut session, mut replies) = test_session(8);
+        let mut events = state.event_tx.subscribe();
+        let local = state.local_peer_id.to_string();
+        let bob = remote_peer();
+
+        // Reading a DM to us publishes a receipt for its sender and records it
+        let dm = chat(&bob, Some(&local), "psst");
+        let WsMessage::ChatMessage { id, .. } = &dm else { unreachable!() };
+        let id = id.clone();
+        record_event(&state, &dm).await;
+        handle_client_message(ClientMessage::MarkRead { message_id: id.clone() }, &state, &mut session).await;
+        match commands.try_recv() {
+            Ok(NetworkCommand::Publish { topic, data }) => {
+                assert_eq!(topic, topics::direct(&bob));
+                let msg: mycelial_core::message::Message = serde_json::from_slice(&data).unwrap();
+                let amendment: ChatAmendment = serde_json::from_slice(&msg.payload).unwrap();
+                assert!(matches!(amendment, ChatAmendment::Read { message_id } if message_id == id));
+            }
+            other => panic!("expected publish, got {:?}", other),
+        }
+        match events.try_recv() {
+            Ok(WsMessage::ReadReceipt { message_id, reader, to, .. }) => {
+                assert_eq!(message_id, id);
+                assert_eq!(reader, local);
+                assert_eq!(to, bob);
+            }
+            other => panic!("expected read receipt, got {:?}", other),
+        }
+        handle_client_message(ClientMessage::GetChatHistory { before: None, before_seq: None, limit: 10 }, &state, &mut session).await;
+        match replies.try_recv() {
+            Ok(WsMessage::ChatHistory { messages, .. }) => assert!(messages[0].read_at.is_some()),
+            other => panic!("expected chat history, got {:?}", other),
+        }
+
+        // A receipt from the recipient of our DM is relayed to our clients
+        let ours = chat(&local, Some(&bob), "did you see this?");
+        let WsMessage::ChatMessage { id: ours_id, .. } = &ours else { unreachable!() };
+        let ours_id = ours_id.clone();
+        record_event(&state, &ours).await;
+        let payload = serde_json::to_vec(&ChatAmendment::Read { message_id: ours_id.clone() }).unwrap();
+        let receipt = mycelial_core::message::Message::new(
+            mycelial_core::message::MessageType::System,
+            PeerId(bob.clone()),
+            payload,
+        );
+        chat_edits::handle_remote(&state, receipt, Some(bob.clone()), 42).await;
+        let receipt = match events.try_recv() {
+            Ok(receipt @ WsMessage::ReadReceipt { .. }) => receipt,
+            other => panic!("expected read receipt, got {:?}", other),
+        };
+        let WsMessage::ReadReceipt { message_id, reader, to, timestamp } = &receipt else { unreachable!() };
+        assert_eq!(*message_id, ours_id);
+        assert_eq!(*reader, bob);
+        assert_eq!(*to, local);
+        assert_eq!(*timestamp, 42);
+
+        // Only the sender's connections are told, live or replayed
+        let logged = serde_json::to_value(&receipt).unwrap();
+        let (sender, _sender_replies) = test_session(8);
+        sender.identify(&local);
+        let (other, _other_replies) = test_session(8);
+        other.identify("carol");
+        assert!(sender.delivery().read().wants(&receipt) && sender.delivery().read().wants_logged(&logged));
+        assert!(!other.delivery().read().wants(&receipt) && !other.delivery().read().wants_logged(&logged));
+    }
+
+    #[tokio::test]
+    async fn test_mark_read_ignores_room_messages() {
+        let (state, mut commands) = test_state().await;
+        let (mut session, mut replies) = test_session(8);
+        let mut events = state.event_tx.subscribe();
+        let mut room_msg = chat(&remote_peer(), None, "hello room");
+        if let WsMessage::ChatMessage { ref mut room_id, .. } = room_msg {
+            *room_id = Some("lobby".to_string());
+        }
+        let WsMessage::ChatMessage { id, .. } = &room_msg else { unreachable!() };
+        let id = id.clone();
+        record_event(&state, &room_msg).await;
+
+        handle_client_message(ClientMessage::MarkRead { message_id: id.clone() }, &state, &mut session).await;
+        assert!(commands.try_recv().is_err());
+        assert!(events.try_recv().is_err());
+        assert!(replies.try_recv().is_err());
+        assert_eq!(state.store.read_at(&id).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_chat_reaction_toggles() {
+        let (state, mut commands) = test_state().await;
+        let (mut session, mut replies) = test_session(8);
+        let mut events = state.event_tx.subscribe();
+        let theirs = chat("alice", None, "ship it");
+        let WsMessage::ChatMessage { id, .. } = &theirs else { unreachable!() };
+        let id = id.clone();
+        record_event(&state, &theirs).await;
+        let react = || ClientMessage::ReactChat { message_id: id.clone(), emoji: "👍".to_string() };
+        // Peers are told whether the reaction was added, not asked to toggle it
+        let published_added = |commands: &mut mpsc::Receiver<NetworkCommand>| match commands.try_recv() {
+            Ok(NetworkCommand::Publish { data, .. }) => {
+                let msg: mycelial_core::message::Message = serde_json::from_slice(&data).unwrap();
+                match serde_json::from_slice(&msg.payload).unwrap() {
+                    ChatAmendment::React { added, .. } => added,
+                    other => panic!("expected reaction, got {:?}", other),
                 }
             }
+            other => panic!("expected publish, got {:?}", other),
+        };
+
+        handle_client_message(react(), &state, &mut session).await;
+        assert!(published_added(&mut commands));
+        match events.try_recv() {
+            Ok(WsMessage::ChatReaction { reactor, emoji, added, .. }) => {
+                assert_eq!(reactor, state.local_peer_id.to_string());
+                assert_eq!(emoji, "👍");
+                assert!(added);
+            }
+            other => panic!("expected chat reaction, got {:?}", other),
+        }
+        handle_client_message(ClientMessage::GetChatHistory { before: None, before_seq: None, limit: 10 }, &state, &mut session).await;
+        match replies.try_recv() {
+            Ok(WsMessage::ChatHistory { messages, .. }) => assert_eq!(messages[0].reactions.get("👍"), Some(&1)),
+            other => panic!("expected chat history, got {:?}", other),
         }
 
-        ClientMessage::LeaveRoom { room_id } => {
-            info!("LeaveRoom: room_id='{}'", room_id);
+        // Reacting again takes it back
+        handle_client_message(react(), &state, &mut session).await;
+        assert!(!published_added(&mut commands));
+        assert!(matches!(events.try_recv(), Ok(WsMessage::ChatReaction { added: false, .. })));
+        handle_client_message(ClientMessage::GetChatHistory { before: None, before_seq: None, limit: 10 }, &state, &mut session).await;
+        match replies.try_recv() {
+            Ok(WsMessage::ChatHistory { messages, .. }) => assert!(messages[0].reactions.is_empty()),
+            other => panic!("expected chat history, got {:?}", other),
+        }
 
-            let topic = format!("/mycelial/1.0.0/room/{}", room_id);
+        // A reaction is exactly one emoji, however many code points it takes
+        for emoji in ["", "lol", "👍 👍", "👍👍", "👍👍👍👍👍👍👍👍👍"] {
+            let invalid = ClientMessage::ReactChat { message_id: id.clone(), emoji: emoji.to_string() };
+            handle_client_message(invalid, &state, &mut session).await;
+            assert!(matches!(replies.try_recv(), Ok(WsMessage::Error { code: ErrorCode::InvalidRequest, .. })));
+        }
+        for emoji in ["👍🏽", "👨\u{200d}👩\u{200d}👧\u{200d}👦", "🇳🇿", "❤\u{fe0f}"] {
+            assert!(chat_edits::valid_emoji(emoji), "{} should be accepted", emoji);
+        }
+    }
 
-            // Notify other room members before leaving
-            let peer_left_msg = WsMessage::RoomPeerLeft {
-                room_id: room_id.clone(),
-                peer_id: state.local_peer_id.to_string(),
+    #[tokio::test]
+    async fn test_direct_message_reactions_stay_between_its_parties() {
+        let (state, mut commands) = test_state().await;
+        let (mut session, mut replies) = test_session(8);
+        let mut events = state.event_tx.subscribe();
+        let local = state.local_peer_id.to_string();
+        let bob = remote_peer();
+
+        // A reaction to a DM goes to the other party and names them
+        let dm = chat(&bob, Some(&local), "psst");
+        let WsMessage::ChatMessage { id, .. } = &dm else { unreachable!() };
+        record_event(&state, &dm).await;
+        let react = ClientMessage::ReactChat { message_id: id.clone(), emoji: "👀".to_string() };
+        handle_client_message(react, &state, &mut session).await;
+        match commands.try_recv() {
+            Ok(NetworkCommand::Publish { topic, .. }) => assert_eq!(topic, topics::direct(&bob)),
+            other => panic!("expected publish, got {:?}", other),
+        }
+        match events.try_recv() {
+            Ok(WsMessage::ChatReaction { to, .. }) => assert_eq!(to, Some(bob.clone())),
+            other => panic!("expected chat reaction, got {:?}", other),
+        }
+
+        // Someone else's DM can't be reacted to
+        let others = chat(&bob, Some("carol"), "not for you");
+        let WsMessage::ChatMessage { id, .. } = &others else { unreachable!() };
+        record_event(&state, &others).await;
+        let react = ClientMessage::ReactChat { message_id: id.clone(), emoji: "👀".to_string() };
+        handle_client_message(react, &state, &mut session).await;
+        assert!(matches!(replies.try_recv(), Ok(WsMessage::Error { code: ErrorCode::InvalidRequest, .. })));
+        assert!(commands.try_recv().is_err());
+        assert!(events.try_recv().is_err());
+
+        // Nor does a reaction to a DM reach anyone outside it
+        let reaction = WsMessage::ChatReaction {
+            message_id: id.clone(),
+            reactor: bob.clone(),
+            to: Some("carol".to_string()),
+            emoji: "👀".to_string(),
+            added: true,
+        };
+        let logged = serde_json::to_value(&reaction).unwrap();
+        assert!(!session.delivery().read().wants(&reaction));
+        assert!(!session.delivery().read().wants_logged(&logged));
+        let (carol, _carol_replies) = test_session(8);
+        carol.identify("carol");
+        assert!(carol.delivery().read().wants(&reaction) && carol.delivery().read().wants_logged(&logged));
+    }
+
+    #[tokio::test]
+    async fn test_stats_count_messages_per_topic() {
+        let (state, _commands) = test_state().await;
+        let (mut session, mut replies) = test_session(8);
+        let bob = remote_peer();
+        for to in [None, None, Some(bob.clone())] {
+            let msg = ClientMessage::SendChat {
+                content: "hello".to_string(),
+                to,
+                room_id: None,
             };
-            if let Ok(data) = serde_json::to_vec(&peer_left_msg) {
-                if let Err(e) = state.network.publish(&topic, data).await {
-                    warn!("Failed to announce room leave: {}", e);
+            handle_client_message(msg, &state, &mut session).await;
+        }
+        while replies.try_recv().is_ok() {}
+
+        handle_client_message(ClientMessage::GetStats, &state, &mut session).await;
+        match replies.try_recv().unwrap() {
+            WsMessage::Stats { topic_counts, .. } => {
+                assert_eq!(topic_counts.len(), 2);
+                assert_eq!(topic_counts.get("/mycelial/1.0.0/chat"), Some(&2));
+                assert_eq!(topic_counts.get(&topics::direct(&bob)), Some(&1));
+            }
+            other => panic!("unexpected message: {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_stats_report_broadcast_queue_depth() {
+        let (state, _commands) = test_state().await;
+        let (mut session, mut replies) = test_session(8);
+        let mut slow = state.event_tx.subscribe();
+        for i in 0..3 {
+            state.event_tx.send(WsMessage::PeerLeft { peer_id: format!("peer-{}", i) }).unwrap();
+        }
+
+        handle_client_message(ClientMessage::GetStats, &state, &mut session).await;
+        match replies.try_recv().unwrap() {
+            WsMessage::Stats { broadcast_queue_depth, broadcast_high_water, broadcast_capacity, .. } => {
+                assert_eq!(broadcast_queue_depth, 3);
+                assert_eq!(broadcast_high_water, 3);
+                assert_eq!(broadcast_capacity, 256);
+            }
+            other => panic!("unexpected message: {:?}", other),
+        }
+
+        // Once the client catches up the depth drops but the mark remains
+        while slow.try_recv().is_ok() {}
+        handle_client_message(ClientMessage::GetStats, &state, &mut session).await;
+        match replies.try_recv().unwrap() {
+            WsMessage::Stats { broadcast_queue_depth, broadcast_high_water, .. } => {
+                assert_eq!(broadcast_queue_depth, 0);
+                assert_eq!(broadcast_high_water, 3);
+            }
+            other => panic!("unexpected message: {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_stats_history_downsampled() {
+        let (state, _commands) = test_state().await;
+        let (mut session, mut replies) = test_session(8);
+
+        // A sample every 20s, 10 messages apart
+        for i in 0..6 {
+            let snapshot = mycelial_state::StatsSnapshot {
+                timestamp: i * 20_000,
+                peer_count: 2,
+                message_count: i as u64 * 10,
+            };
+            state.store.record_stats_snapshot(&snapshot).await.unwrap();
+        }
+
+        let msg = ClientMessage::GetStatsHistory { since: 0, interval_secs: 60 };
+        handle_client_message(msg, &state, &mut session).await;
+
+        match replies.try_recv().unwrap() {
+            WsMessage::StatsHistory { interval_secs, points } => {
+                assert_eq!(interval_secs, 60);
+                assert_eq!(points.len(), 2);
+                assert_eq!(points[0].message_count, 20);
+                assert_eq!(points[1].message_count, 50);
+                // 30 messages over 60 seconds
+                assert!((points[1].message_rate - 0.5).abs() < 1e-9);
+            }
+            other => panic!("unexpected message: {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_overlong_correlation_id_rejected() {
+        let (state, mut commands) = test_state().await;
+        let (mut session, mut replies) = test_session(8);
+
+        let msg = ClientMessage::ReportResource {
+            resource_type: "storage".to_string(),
+            amount: 1.0,
+            unit: "GB".to_string(),
+            correlation_id: Some("c".repeat(MAX_CORRELATION_ID_LEN + 1)),
+        };
+        handle_client_message(msg, &state, &mut session).await;
+        assert!(matches!(
+            replies.try_recv(),
+            Ok(WsMessage::Error { code: ErrorCode::InvalidRequest, .. })
+        ));
+        assert!(commands.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_malformed_ids_reported_to_client() {
+        let (state, _commands) = test_state().await;
+        let (mut session, mut replies) = test_session(8);
+
+        let respond = ClientMessage::RespondVouch {
+            request_id: "not-a-uuid".to_string(),
+            accept: true,
+            correlation_id: None,
+        };
+        handle_client_message(respond, &state, &mut session).await;
+        match replies.try_recv() {
+            Ok(WsMessage::Error { code: ErrorCode::InvalidRequest, message }) => {
+                assert!(message.starts_with("Invalid vouch request ID"), "{}", message);
+            }
+            other => panic!("expected invalid request, got {:?}", other),
+        }
+        // The failure is also what an Ack reports
+        assert!(session.take_failure().unwrap().starts_with("Invalid vouch request ID"));
+
+        let vote = ClientMessage::CastVote {
+            proposal_id: "not-a-uuid".to_string(),
+            vote: "yes".to_string(),
+            correlation_id: None,
+        };
+        handle_client_message(vote, &state, &mut session).await;
+        match replies.try_recv() {
+            Ok(WsMessage::Error { code: ErrorCode::InvalidRequest, message }) => {
+                assert!(message.starts_with("Invalid proposal ID"), "{}", message);
+            }
+            other => panic!("expected invalid request, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_whoami_returns_local_identity() {
+        let (state, _commands) = test_state().await;
+        let (mut session, mut replies) = test_session(8);
+        state.listen_addresses.write().push("/ip4/127.0.0.1/tcp/9000".to_string());
+
+        handle_client_message(ClientMessage::Whoami, &state, &mut session).await;
+        match replies.try_recv() {
+            Ok(WsMessage::Identity { peer_id, node_name, addresses }) => {
+                assert_eq!(peer_id, state.local_peer_id.to_string());
+                assert_eq!(node_name, state.node_name());
+                assert_eq!(addresses, vec!["/ip4/127.0.0.1/tcp/9000".to_string()]);
+            }
+            other => panic!("expected identity, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_kick_requires_admin_and_closes_target() {
+        let config = ServerConfig {
+            admin_token: Some("secret".to_string()),
+            ..ServerConfig::default()
+        };
+        let (state, _commands) = test_state_with_config(config).await;
+        let (mut session, mut reply_rx) = test_session(8);
+        let (target_tx, mut target_rx) = mpsc::unbounded_channel();
+        let (bystander_tx, mut bystander_rx) = mpsc::unbounded_channel();
+        let target_close = state
+            .connections
+            .register("target", target_tx, Arc::default(), OutboundQueue::new(8), Activity::default());
+        let bystander_close = state
+            .connections
+            .register("bystander", bystander_tx, Arc::default(), OutboundQueue::new(8), Activity::default());
+        let kick = |id: &str| ClientMessage::Kick {
+            connection_id: id.to_string(),
+            reason: "abuse".to_string(),
+        };
+
+        // Without admin the kick is refused and nothing is closed
+        handle_client_message(kick("target"), &state, &mut session).await;
+        assert!(matches!(
+            reply_rx.try_recv(),
+            Ok(WsMessage::Error { code: ErrorCode::Forbidden, .. })
+        ));
+        assert!(target_rx.try_recv().is_err());
+
+        let auth = ClientMessage::AuthenticateAdmin { token: Secret::new("secret") };
+        // Logged without the token
+        assert!(!format!("{:?}", auth).contains("secret"));
+        handle_client_message(auth, &state, &mut session).await;
+        assert!(session.is_admin());
+
+        handle_client_message(kick("target"), &state, &mut session).await;
+        assert!(matches!(
+            reply_rx.try_recv(),
+            Ok(WsMessage::KickResult { existed: true, .. })
+        ));
+        assert!(matches!(
+            target_rx.try_recv(),
+            Ok(WsMessage::Error { code: ErrorCode::Unauthorized, .. })
+        ));
+        tokio::time::timeout(std::time::Duration::from_millis(100), target_close.notified())
+            .await
+            .expect("kicked connection should be closed");
+
+        // Other connections are untouched
+        assert!(bystander_rx.try_recv().is_err());
+        assert!(
+            tokio::time::timeout(std::time::Duration::from_millis(20), bystander_close.notified())
+                .await
+                .is_err()
+        );
+
+        handle_client_message(kick("target"), &state, &mut session).await;
+        assert!(matches!(
+            reply_rx.try_recv(),
+            Ok(WsMessage::KickResult { existed: false, .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_sends_notice_and_closes_socket() {
+        use tokio_tungstenite::tungstenite::Message as ClientFrame;
+
+        let config = ServerConfig {
+            open_websocket: true,
+            ..ServerConfig::default()
+        };
+        let (state, _commands) = test_state_with_config(config).await;
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let app = crate::server::create_router(state.clone());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let (mut socket, _) = tokio_tungstenite::connect_async(format!("ws://{}/ws", addr)).await.unwrap();
+        // Wait for the connection to be registered
+        loop {
+            match socket.next().await {
+                Some(Ok(ClientFrame::Text(text))) if text.contains("connection_ready") => break,
+                Some(Ok(_)) => {}
+                other => panic!("connection ended early: {:?}", other),
+            }
+        }
+
+        assert_eq!(state.connections.shutdown("Node is shutting down", 5_000), 1);
+        let mut notice = None;
+        let mut closed = false;
+        while let Ok(Some(frame)) = tokio::time::timeout(std::time::Duration::from_secs(1), socket.next()).await {
+            match frame {
+                Ok(ClientFrame::Text(text)) => {
+                    let message: serde_json::Value = serde_json::from_str(&text).unwrap();
+                    if message["type"] == "shutdown" {
+                        notice = Some(message);
+                    }
                 }
+                Ok(ClientFrame::Close(_)) => {
+                    closed = true;
+                    break;
+                }
+                Ok(_) => {}
+                Err(_) => break,
+            }
+        }
+        let notice = notice.expect("shutdown notice should arrive before the close");
+        assert_eq!(notice["reason"], "Node is shutting down");
+        assert_eq!(notice["reconnect_after_ms"], 5_000);
+        assert!(closed);
+
+        // The connection unregisters once its socket is done
+        tokio::time::timeout(std::time::Duration::from_secs(1), async {
+            while !state.connections.is_empty() {
+                tokio::time::sleep(std::time::Duration::from_millis(10)).await;
             }
+        })
+        .await
+        .expect("connection should unregister");
+    }
+
+    #[tokio::test]
+    async fn test_binary_frames_follow_negotiated_encoding() {
+        use tokio_tungstenite::tungstenite::{Error as ClientError, Message as ClientFrame};
+
+        /// The next message of type `tag`, from either text or MessagePack frames
+        async fn next_of_type<S>(socket: &mut S, tag: &str) -> serde_json::Value
+        where
+            S: futures::Stream<Item = Result<ClientFrame, ClientError>> + Unpin,
+        {
+            let wait = async {
+                loop {
+                    let message: serde_json::Value = match socket.next().await {
+                        Some(Ok(ClientFrame::Text(text))) => serde_json::from_str(&text).unwrap(),
+                        Some(Ok(ClientFrame::Binary(data))) => rmp_serde::from_slice(&data).unwrap(),
+                        Some(Ok(_)) => continue,
+                        other => panic!("connection ended early: {:?}", other),
+                    };
+                    if message["type"] == tag {
+                        return message;
+                    }
+                }
+            };
+            tokio::time::timeout(std::time::Duration::from_secs(1), wait)
+                .await
+                .unwrap_or_else(|_| panic!("no {} message", tag))
+        }
 
-            // Unsubscribe from the room topic
-            if let Err(e) = state.network.unsubscribe(&topic).await {
-                error!("Failed to unsubscribe from room topic {}: {}", topic, e);
+        let config = ServerConfig {
+            open_websocket: true,
+            ..ServerConfig::default()
+        };
+        let (state, _commands) = test_state_with_config(config).await;
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let app = crate::server::create_router(state.clone());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        let ping = |nonce: &str| serde_json::json!({ "type": "ping", "nonce": nonce });
+
+        // Without MessagePack a binary frame is rejected, but the connection stays open
+        let (mut socket, _) = tokio_tungstenite::connect_async(format!("ws://{}/ws", addr)).await.unwrap();
+        let packed = rmp_serde::to_vec_named(&ping("binary")).unwrap();
+        socket.send(ClientFrame::Binary(packed)).await.unwrap();
+        let error = next_of_type(&mut socket, "error").await;
+        assert_eq!(error["code"], "invalid_request");
+        socket.send(ClientFrame::Text(ping("text").to_string())).await.unwrap();
+        assert_eq!(next_of_type(&mut socket, "pong").await["nonce"], "text");
+
+        // With MessagePack negotiated, the same frame is handled
+        let url = format!("ws://{}/ws?encoding=msgpack", addr);
+        let (mut socket, _) = tokio_tungstenite::connect_async(url).await.unwrap();
+        let packed = rmp_serde::to_vec_named(&ping("binary")).unwrap();
+        socket.send(ClientFrame::Binary(packed)).await.unwrap();
+        assert_eq!(next_of_type(&mut socket, "pong").await["nonce"], "binary");
+    }
+
+    #[tokio::test]
+    async fn test_connections_listed_while_open() {
+        use tokio_tungstenite::tungstenite::Message as ClientFrame;
+
+        let config = ServerConfig {
+            open_websocket: true,
+            ..ServerConfig::default()
+        };
+        let (state, _commands) = test_state_with_config(config).await;
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let app = crate::server::create_router(state.clone());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let (mut socket, _) = tokio_tungstenite::connect_async(format!("ws://{}/ws", addr)).await.unwrap();
+        let connection_id = loop {
+            match socket.next().await {
+                Some(Ok(ClientFrame::Text(text))) if text.contains("connection_ready") => {
+                    let message: serde_json::Value = serde_json::from_str(&text).unwrap();
+                    break message["connection_id"].as_str().unwrap().to_string();
+                }
+                Some(Ok(_)) => {}
+                other => panic!("connection ended early: {:?}", other),
+            }
+        };
+        let (mut session, mut replies) = test_session(8);
+        session.grant_admin();
+
+        // A connection that hasn't identified is listed without a peer
+        handle_client_message(ClientMessage::GetConnections, &state, &mut session).await;
+        match replies.try_recv() {
+            Ok(WsMessage::Connections { clients }) => {
+                assert_eq!(clients.len(), 1);
+                assert_eq!(clients[0].connection_id, connection_id);
+                assert_eq!(clients[0].peer_id, None);
             }
+            other => panic!("expected connections, got {:?}", other),
+        }
 
-            info!("Left room and unsubscribed from topic: {}", topic);
+        let keypair = mycelial_network::Keypair::generate_ed25519();
+        let peer = keypair.public().to_peer_id().to_base58();
+        let signature = sign_identify(&keypair, &connection_id);
+        let identify = serde_json::json!({ "type": "identify", "peer_id": peer, "signature": signature }).to_string();
+        socket.send(ClientFrame::Text(identify.into())).await.unwrap();
+        let listed = tokio::time::timeout(std::time::Duration::from_secs(1), async {
+            loop {
+                handle_client_message(ClientMessage::GetConnections, &state, &mut session).await;
+                match replies.try_recv() {
+                    Ok(WsMessage::Connections { clients }) => {
+                        let found = clients.into_iter().find(|entry| entry.connection_id == connection_id);
+                        if let Some(entry) = found.filter(|entry| entry.peer_id.as_deref() == Some(peer.as_str())) {
+                            return entry;
+                        }
+                    }
+                    other => panic!("expected connections, got {:?}", other),
+                }
+                tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("identified connection should be listed");
+        assert!(listed.last_activity >= listed.connected_at);
+        match current_stats(&state).await {
+            WsMessage::Stats { connection_count, .. } => assert_eq!(connection_count, 1),
+            other => panic!("expected stats, got {:?}", other),
+        }
+
+        socket.close(None).await.unwrap();
+        tokio::time::timeout(std::time::Duration::from_secs(1), async {
+            while !state.connections.is_empty() {
+                tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("connection should unregister");
+        handle_client_message(ClientMessage::GetConnections, &state, &mut session).await;
+        assert!(matches!(
+            replies.try_recv(),
+            Ok(WsMessage::Connections { clients }) if clients.is_empty()
+        ));
+
+        // Listing connections is for operators only
+        let (mut session, mut replies) = test_session(8);
+        handle_client_message(ClientMessage::GetConnections, &state, &mut session).await;
+        assert!(matches!(
+            replies.try_recv(),
+            Ok(WsMessage::Error { code: ErrorCode::Forbidden, .. })
+        ));
+    }
 
-            // Send room left confirmation
-            let left_msg = WsMessage::RoomLeft { room_id };
-            let _ = state.event_tx.send(left_msg);
+    #[tokio::test]
+    async fn test_last_session_closing_announces_leave() {
+        use tokio_tungstenite::tungstenite::Message as ClientFrame;
+
+        let config = ServerConfig {
+            open_websocket: true,
+            announce_presence: true,
+            ..ServerConfig::default()
+        };
+        let (state, mut commands) = test_state_with_config(config).await;
+        let mut events = state.event_tx.subscribe();
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let app = crate::server::create_router(state.clone());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        // Two sessions acting for the local peer
+        let mut sockets = Vec::new();
+        for _ in 0..2 {
+            let (mut socket, _) = tokio_tungstenite::connect_async(format!("ws://{}/ws", addr)).await.unwrap();
+            loop {
+                match socket.next().await {
+                    Some(Ok(ClientFrame::Text(text))) if text.contains("connection_ready") => break,
+                    Some(Ok(_)) => {}
+                    other => panic!("connection ended early: {:?}", other),
+                }
+            }
+            sockets.push(socket);
+        }
+        let presence_publishes = |commands: &mut mpsc::Receiver<NetworkCommand>| {
+            let mut notices = Vec::new();
+            while let Ok(command) = commands.try_recv() {
+                if let NetworkCommand::Publish { topic, data } = command {
+                    if topic == topics::PRESENCE {
+                        notices.push(serde_json::from_slice::<serde_json::Value>(&data).unwrap());
+                    }
+                }
+            }
+            notices
+        };
+
+        // Only the last session to close announces the departure, which is
+        // published before it is broadcast locally
+        for mut socket in sockets {
+            socket.close(None).await.unwrap();
+        }
+        let local = state.local_peer_id.to_string();
+        let left = tokio::time::timeout(std::time::Duration::from_secs(1), async {
+            loop {
+                if let Ok(WsMessage::PeerLeft { peer_id }) = events.recv().await {
+                    return peer_id;
+                }
+            }
+        })
+        .await
+        .expect("departure should be broadcast locally");
+        assert_eq!(left, local);
+
+        let notices = presence_publishes(&mut commands);
+        assert_eq!(notices.len(), 1);
+        assert_eq!(notices[0]["type"], "leave");
+        assert_eq!(notices[0]["peer_id"], local);
+    }
+
+    #[tokio::test]
+    async fn test_connection_stats_for_admin() {
+        let (state, _commands) = test_state().await;
+        let (mut session, mut reply_rx) = test_session(8);
+        let (target_tx, _target_rx) = mpsc::unbounded_channel();
+        let stats = Arc::new(EncodingStats::default());
+        state
+            .connections
+            .register("target", target_tx, stats.clone(), OutboundQueue::new(8), Activity::default());
+        stats.record(2_000, 500);
+        stats.record(1_000, 1_000);
+        let query = |id: &str| ClientMessage::GetConnectionStats { connection_id: id.to_string() };
+
+        handle_client_message(query("target"), &state, &mut session).await;
+        assert!(matches!(
+            reply_rx.try_recv(),
+            Ok(WsMessage::Error { code: ErrorCode::Forbidden, .. })
+        ));
+
+        session.grant_admin();
+        handle_client_message(query("target"), &state, &mut session).await;
+        match reply_rx.try_recv() {
+            Ok(WsMessage::ConnectionStats { messages_sent, bytes_before, bytes_after, compression_ratio, .. }) => {
+                assert_eq!((messages_sent, bytes_before, bytes_after), (2, 3_000, 1_500));
+                // Bytes after per byte before across both frames
+                assert_eq!(compression_ratio, 0.5);
+            }
+            other => panic!("expected connection stats, got {:?}", other),
         }
 
-        ClientMessage::GetRooms => {
-            info!("GetRooms requested");
+        handle_client_message(query("missing"), &state, &mut session).await;
+        assert!(matches!(
+            reply_rx.try_recv(),
+            Ok(WsMessage::Error { code: ErrorCode::InvalidRequest, .. })
+        ));
+    }
 
-            // For now, send an empty list
-            // In a full implementation, we'd query a room registry or DHT
-            let rooms_msg = WsMessage::RoomList { rooms: vec![] };
-            let _ = state.event_tx.send(rooms_msg);
+    #[tokio::test]
+    async fn test_correlation_id_links_transfer_and_proposal() {
+        let (state, mut commands) = test_state().await;
+        let (mut session, _reply_rx) = test_session(8);
+        let mut events = state.event_tx.subscribe();
+        let correlation_id = Some("treasury-42".to_string());
+        let bob = remote_peer();
+        store_credit_line_from(&state, &bob, 100.0).await;
+
+        let propose = ClientMessage::CreateProposal {
+            title: "Fund relay".to_string(),
+            description: "Pay for relay hosting".to_string(),
+            proposal_type: ProposalKind::TreasurySpend {
+                recipient: remote_peer(),
+                amount: 25.0,
+            },
+            quorum: None,
+            quorum_mode: QuorumMode::Snapshot,
+            passing_rule: PassingRule::SimpleMajority,
+            deadline_secs: None,
+            correlation_id: correlation_id.clone(),
+        };
+        let transfer = ClientMessage::TransferCredit {
+            to: bob,
+            amount: 50.0,
+            memo: None,
+            request_ref: None,
+            correlation_id: correlation_id.clone(),
+        };
+        handle_client_message(propose, &state, &mut session).await;
+        handle_client_message(transfer, &state, &mut session).await;
+
+        match events.try_recv() {
+            Ok(WsMessage::Proposal { correlation_id: id, .. }) => assert_eq!(id, correlation_id),
+            other => panic!("expected proposal, got {:?}", other),
+        }
+        match events.try_recv() {
+            Ok(WsMessage::CreditTransfer { correlation_id: id, .. }) => assert_eq!(id, correlation_id),
+            other => panic!("expected transfer, got {:?}", other),
         }
+
+        // Peers receive the tag too
+        for topic in [topics::GOVERNANCE, topics::CREDIT] {
+            match commands.try_recv() {
+                Ok(NetworkCommand::Publish { topic: published, data }) => {
+                    assert_eq!(published, topic);
+                    let json: serde_json::Value = serde_json::from_slice(&data).unwrap();
+                    assert_eq!(json["correlation_id"], "treasury-42");
+                }
+                other => panic!("expected publish, got {:?}", other),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_signed_transfer_verified_against_signer() {
+        use mycelial_core::identity::{Keypair, KeypairExt};
+
+        let keypair = Keypair::generate();
+        let config = ServerConfig {
+            action_signer: Some(keypair.did().to_string()),
+            ..ServerConfig::default()
+        };
+        let (state, mut commands) = test_state_with_config(config).await;
+        let (mut session, mut reply_rx) = test_session(8);
+        let bob = remote_peer();
+        store_credit_line_from(&state, &bob, 100.0).await;
+        let payload = format!(r#"{{"type":"transfer_credit","to":"{}","amount":10.0}}"#, bob);
+        let now = chrono::Utc::now().timestamp_millis();
+        let sign = |payload: &str, nonce: &str, timestamp: i64| ClientMessage::Signed {
+            payload: payload.to_string(),
+            nonce: nonce.to_string(),
+            timestamp,
+            signature: keypair.sign_bytes(&ActionSignature::signed_bytes(nonce, timestamp, payload)).to_hex(),
+        };
+
+        // Unsigned actions are refused once a signer is configured
+        let unsigned = serde_json::from_str::<ClientMessage>(&payload).unwrap();
+        handle_client_message(unsigned, &state, &mut session).await;
+        match reply_rx.try_recv() {
+            Ok(WsMessage::Error { code, .. }) => assert_eq!(code, ErrorCode::Unauthorized),
+            other => panic!("expected unauthorized, got {:?}", other),
+        }
+
+        handle_client_message(sign(&payload, "n1", now), &state, &mut session).await;
+        match commands.try_recv() {
+            Ok(NetworkCommand::Publish { topic, data }) => {
+                assert_eq!(topic, topics::CREDIT);
+                let json: serde_json::Value = serde_json::from_slice(&data).unwrap();
+                assert_eq!(json["signature"]["signer"], keypair.did().to_string());
+                assert_eq!(json["signature"]["payload"], payload);
+                assert_eq!(json["signature"]["nonce"], "n1");
+            }
+            other => panic!("expected publish, got {:?}", other),
+        }
+        assert!(!session.has_action_signature());
+
+        let tampered = match sign(&payload, "n2", now) {
+            ClientMessage::Signed { payload, nonce, timestamp, signature } => ClientMessage::Signed {
+                payload: payload.replace("10.0", "1000.0"),
+                nonce,
+                timestamp,
+                signature,
+            },
+            _ => unreachable!(),
+        };
+        // Tampered, presented again, or signed too long ago
+        let stale = now - state.config.replay_window.as_millis() as i64 - 1_000;
+        for refused in [tampered, sign(&payload, "n1", now), sign(&payload, "n3", stale)] {
+            handle_client_message(refused, &state, &mut session).await;
+            match reply_rx.try_recv() {
+                Ok(WsMessage::Error { code, .. }) => assert_eq!(code, ErrorCode::Unauthorized),
+                other => panic!("expected unauthorized, got {:?}", other),
+            }
+        }
+        assert!(commands.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_get_peers_bulk_returns_requested_known_peers() {
+        let (state, _commands) = test_state().await;
+        let (mut session, mut reply_rx) = test_session(8);
+        for (id, name) in [("alice", Some("Alice")), ("bob", None), ("carol", None)] {
+            let peer = PeerInfo {
+                id: mycelial_core::peer::PeerId(id.to_string()),
+                public_key: id.to_string(),
+                addresses: vec![],
+                first_seen: chrono::Utc::now(),
+                last_seen: chrono::Utc::now(),
+                name: name.map(str::to_string),
+            };
+            state.store.upsert_peer(&peer, None).await.unwrap();
+        }
+
+        let ids = ["carol", "alice", "mallory", "alice"].map(str::to_string).to_vec();
+        handle_client_message(ClientMessage::GetPeersBulk { ids }, &state, &mut session).await;
+        match reply_rx.try_recv() {
+            Ok(WsMessage::PeersBulk { entries, unknown }) => {
+                let ids: Vec<_> = entries.iter().map(|e| e.id.as_str()).collect();
+                assert_eq!(ids, vec!["carol", "alice"]);
+                assert_eq!(entries[1].name.as_deref(), Some("Alice"));
+                assert_eq!(unknown, vec!["mallory".to_string()]);
+            }
+            other => panic!("expected peers bulk, got {:?}", other),
+        }
+
+        let too_many = vec!["alice".to_string(); MAX_PEERS_BULK + 1];
+        handle_client_message(ClientMessage::GetPeersBulk { ids: too_many }, &state, &mut session).await;
+        assert!(matches!(
+            reply_rx.try_recv(),
+            Ok(WsMessage::Error { code: ErrorCode::InvalidRequest, .. })
+        ));
+    }
+
+    /// Store peers with names and reputations, the first seen most recently
+    async fn store_named_peers(state: &AppState, peers: &[(&str, Option<&str>, f64)]) {
+        for (i, (id, name, score)) in peers.iter().enumerate() {
+            let last_seen = chrono::Utc::now() - chrono::Duration::seconds(i as i64);
+            let peer = PeerInfo {
+                id: mycelial_core::peer::PeerId(id.to_string()),
+                public_key: id.to_string(),
+                addresses: vec![],
+                first_seen: last_seen,
+                last_seen,
+                name: name.map(str::to_string),
+            };
+            let reputation = mycelial_core::reputation::Reputation::new(*score);
+            state.store.upsert_peer(&peer, Some(&reputation)).await.unwrap();
+        }
+    }
+
+    fn searched_ids(reply: Option<WsMessage>) -> Vec<String> {
+        match reply {
+            Some(WsMessage::PeersList { peers }) => peers.into_iter().map(|p| p.id).collect(),
+            other => panic!("expected peers list, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_search_peers_matches_name_ignoring_case() {
+        let (state, _commands) = test_state().await;
+        let (mut session, mut reply_rx) = test_session(8);
+        store_named_peers(
+            &state,
+            &[("p1", Some("Alice"), 0.5), ("p2", Some("Malice Hub"), 0.5), ("p3", Some("Bob"), 0.5), ("p4", None, 0.5)],
+        )
+        .await;
+        let search = |query: &str, limit| ClientMessage::SearchPeers {
+            query: query.to_string(),
+            min_reputation: None,
+            limit,
+        };
+
+        handle_client_message(search("ALIC", 10), &state, &mut session).await;
+        assert_eq!(searched_ids(reply_rx.try_recv().ok()), vec!["p1", "p2"]);
+
+        // The limit is clamped to at least one result
+        handle_client_message(search("alic", 0), &state, &mut session).await;
+        assert_eq!(searched_ids(reply_rx.try_recv().ok()), vec!["p1"]);
+
+        // An empty query matches every peer, named or not
+        handle_client_message(search(" ", 10), &state, &mut session).await;
+        assert_eq!(searched_ids(reply_rx.try_recv().ok()), vec!["p1", "p2", "p3", "p4"]);
+
+        handle_client_message(search("carol", 10), &state, &mut session).await;
+        assert!(searched_ids(reply_rx.try_recv().ok()).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_search_peers_filters_by_reputation() {
+        let (state, _commands) = test_state().await;
+        let (mut session, mut reply_rx) = test_session(8);
+        store_named_peers(
+            &state,
+            &[("p1", Some("Relay One"), 0.9), ("p2", Some("Relay Two"), 0.3), ("p3", Some("Storage"), 0.8)],
+        )
+        .await;
+        let search = |query: &str, min_reputation| ClientMessage::SearchPeers {
+            query: query.to_string(),
+            min_reputation,
+            limit: 10,
+        };
+
+        handle_client_message(search("relay", Some(0.5)), &state, &mut session).await;
+        assert_eq!(searched_ids(reply_rx.try_recv().ok()), vec!["p1"]);
+        handle_client_message(search("", Some(0.8)), &state, &mut session).await;
+        assert_eq!(searched_ids(reply_rx.try_recv().ok()), vec!["p1", "p3"]);
+
+        handle_client_message(search("relay", Some(f64::NAN)), &state, &mut session).await;
+        assert!(matches!(
+            reply_rx.try_recv(),
+            Ok(WsMessage::Error { code: ErrorCode::InvalidRequest, .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_get_peer_returns_detail() {
+        let (state, _commands) = test_state().await;
+        let (mut session, mut reply_rx) = test_session(8);
+        let peer = PeerInfo {
+            id: mycelial_core::peer::PeerId("alice".to_string()),
+            public_key: "alice-key".to_string(),
+            addresses: vec!["/ip4/10.0.0.1/tcp/9000".to_string(), "/ip4/10.0.0.2/tcp/9000".to_string()],
+            first_seen: chrono::Utc::now(),
+            last_seen: chrono::Utc::now(),
+            name: Some("Alice".to_string()),
+        };
+        let reputation = mycelial_core::reputation::Reputation {
+            score: 0.8,
+            successful_interactions: 4,
+            failed_interactions: 1,
+            ..Default::default()
+        };
+        state.store.upsert_peer(&peer, Some(&reputation)).await.unwrap();
+        state
+            .store
+            .insert_vouch(&VouchRecord {
+                id: "v1".to_string(),
+                voucher: "bob".to_string(),
+                vouchee: "alice".to_string(),
+                weight: 0.5,
+                message: None,
+                status: "accepted".to_string(),
+                created_at: 1_000,
+            })
+            .await
+            .unwrap();
+
+        handle_client_message(ClientMessage::GetPeer { peer_id: "alice".to_string() }, &state, &mut session).await;
+        match reply_rx.try_recv() {
+            Ok(WsMessage::PeerDetail { id, display_name, addresses, reputation, successful_interactions, vouches, .. }) => {
+                assert_eq!(id, "alice");
+                assert_eq!(display_name, "Alice");
+                assert_eq!(addresses.len(), 2);
+                assert!((reputation - 0.8).abs() < 1e-9);
+                assert_eq!(successful_interactions, 4);
+                assert_eq!(vouches.len(), 1);
+                assert_eq!(vouches[0].voucher, "bob");
+            }
+            other => panic!("expected peer detail, got {:?}", other),
+        }
+
+        handle_client_message(ClientMessage::GetPeer { peer_id: "mallory".to_string() }, &state, &mut session).await;
+        assert!(matches!(
+            reply_rx.try_recv(),
+            Ok(WsMessage::Error { code: ErrorCode::InvalidRequest, .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_economics_actions_require_valid_peer_ids() {
+        let (state, mut commands) = test_state().await;
+        let (mut session, mut replies) = test_session(8);
+        let mut events = state.event_tx.subscribe();
+        let actions = |peer: &str| {
+            [
+                ClientMessage::SendVouch {
+                    vouchee: peer.to_string(),
+                    weight: 0.5,
+                    message: None,
+                    correlation_id: None,
+                },
+                ClientMessage::CreateCreditLine {
+                    debtor: peer.to_string(),
+                    limit: 10.0,
+                    correlation_id: None,
+                },
+                ClientMessage::TransferCredit {
+                    to: peer.to_string(),
+                    amount: 1.0,
+                    memo: None,
+                    request_ref: None,
+                    correlation_id: None,
+                },
+            ]
+        };
+
+        for invalid in ["", "   ", "bob", "12D3KooWnot-a-peer-id"] {
+            for action in actions(invalid) {
+                handle_client_message(action, &state, &mut session).await;
+                match replies.try_recv() {
+                    Ok(WsMessage::Error { code, .. }) => assert_eq!(code, ErrorCode::InvalidRequest),
+                    other => panic!("expected invalid request for {:?}, got {:?}", invalid, other),
+                }
+            }
+        }
+        assert!(commands.try_recv().is_err());
+
+        // Valid IDs are published in canonical form, whitespace trimmed
+        let peer = remote_peer();
+        store_credit_line_from(&state, &peer, 10.0).await;
+        for action in actions(&format!(" {} ", peer)) {
+            handle_client_message(action, &state, &mut session).await;
+            assert!(matches!(commands.try_recv(), Ok(NetworkCommand::Publish { .. })));
+        }
+        match events.try_recv() {
+            Ok(WsMessage::VouchRequest { vouchee, .. }) => assert_eq!(vouchee, peer),
+            other => panic!("expected vouch request, got {:?}", other),
+        }
+        match events.try_recv() {
+            Ok(WsMessage::CreditLine { debtor, .. }) => assert_eq!(debtor, peer),
+            other => panic!("expected credit line, got {:?}", other),
+        }
+        match events.try_recv() {
+            Ok(WsMessage::CreditTransfer { to, .. }) => assert_eq!(to, peer),
+            other => panic!("expected credit transfer, got {:?}", other),
+        }
+        assert!(replies.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_chat_to_room_joins_room() {
+        let (state, mut commands) = test_state().await;
+        let (mut session, mut replies) = test_session(8);
+        let mut events = state.event_tx.subscribe();
+        let send = |room: &str| {
+            let json = format!(r#"{{"type":"send_chat","content":"hi","room":"{}"}}"#, room);
+            serde_json::from_str::<ClientMessage>(&json).unwrap()
+        };
+        let topic = "/mycelial/1.0.0/room/general";
+
+        handle_client_message(send("general"), &state, &mut session).await;
+        assert!(matches!(commands.try_recv(), Ok(NetworkCommand::Subscribe { topic: t }) if t == topic));
+        assert!(matches!(commands.try_recv(), Ok(NetworkCommand::Publish { topic: t, .. }) if t == topic));
+        assert!(session.is_subscribed(topic));
+        match events.try_recv() {
+            Ok(WsMessage::ChatMessage { room_id, content, .. }) => {
+                assert_eq!(room_id.as_deref(), Some("general"));
+                assert_eq!(content, "hi");
+            }
+            other => panic!("expected chat echo, got {:?}", other),
+        }
+
+        // Later messages to the room don't subscribe again
+        handle_client_message(send("general"), &state, &mut session).await;
+        assert!(matches!(commands.try_recv(), Ok(NetworkCommand::Publish { topic: t, .. }) if t == topic));
+        assert!(commands.try_recv().is_err());
+
+        // A room ID that would alter the topic is rejected before publishing
+        handle_client_message(send("general/../chat"), &state, &mut session).await;
+        assert!(matches!(
+            replies.try_recv(),
+            Ok(WsMessage::Error { code: ErrorCode::InvalidRequest, .. })
+        ));
+        assert!(commands.try_recv().is_err());
+        let join = ClientMessage::JoinRoom { room_id: "a b".to_string(), room_name: None };
+        handle_client_message(join, &state, &mut session).await;
+        assert!(matches!(
+            replies.try_recv(),
+            Ok(WsMessage::Error { code: ErrorCode::InvalidRequest, .. })
+        ));
+        assert!(commands.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_ephemeral_room_chat_relayed_not_persisted() {
+        let (state, _commands) = test_state().await;
+        let (mut session, _reply_rx) = test_session(8);
+        let mut events = state.event_tx.subscribe();
+
+        for (room_id, ephemeral) in [("live", true), ("durable", false)] {
+            let msg = ClientMessage::CreateRoom {
+                room_id: Some(room_id.to_string()),
+                room_name: room_id.to_string(),
+                description: None,
+                is_public: None,
+                ephemeral: Some(ephemeral),
+            };
+            handle_client_message(msg, &state, &mut session).await;
+        }
+        // Joining reports the flag the creator set
+        let join = ClientMessage::JoinRoom { room_id: "live".to_string(), room_name: None };
+        handle_client_message(join, &state, &mut session).await;
+        let mut joined = 0;
+        while let Ok(event) = events.try_recv() {
+            if let WsMessage::RoomJoined { id, ephemeral, .. } = event {
+                assert_eq!(ephemeral, id == "live");
+                joined += 1;
+            }
+        }
+        assert_eq!(joined, 3);
+
+        for room_id in ["live", "durable"] {
+            let mut msg = chat("alice", None, room_id);
+            if let WsMessage::ChatMessage { room_id: ref mut room, .. } = msg {
+                *room = Some(room_id.to_string());
+            }
+            state.event_tx.send(msg.clone()).unwrap();
+            record_event(&state, &msg).await;
+        }
+
+        // Both messages reach live subscribers
+        let relayed: Vec<_> = std::iter::from_fn(|| events.try_recv().ok()).collect();
+        assert_eq!(relayed.len(), 2);
+
+        // Only the durable room's message is in history
+        let history = state.store.list_events_for_peer("alice", 0, 0, 10).await.unwrap();
+        assert_eq!(history.len(), 1);
+        assert!(history[0].payload_json.contains("durable"));
     }
 }
@@ -0,0 +1,136 @@
+//! Periodic stats snapshots
+//!
+//! A background task samples the node's statistics on a fixed interval and
+//! persists them, so clients can request a downsampled history for charts.
+//! Optionally another task pushes the current stats to every client on an
+//! interval, so dashboards stay live without polling.
+
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use tracing::warn;
+
+use mycelial_state::StatsSnapshot;
+
+use crate::server::messages::{StatsPoint, WsMessage};
+use crate::AppState;
+
+/// Messages published or received on each topic since startup
+#[derive(Debug, Default)]
+pub struct TopicCounts {
+    counts: Mutex<HashMap<String, u64>>,
+}
+
+impl TopicCounts {
+    /// Count one message on `topic`
+    pub fn record(&self, topic: &str) {
+        let mut counts = self.counts.lock();
+        match counts.get_mut(topic) {
+            Some(count) => *count += 1,
+            None => {
+                counts.insert(topic.to_string(), 1);
+            }
+        }
+    }
+
+    /// Current count for every topic seen so far
+    pub fn snapshot(&self) -> HashMap<String, u64> {
+        self.counts.lock().clone()
+    }
+}
+
+/// Sample and persist stats until the process exits
+pub async fn run_stats_recorder(state: Arc<AppState>) {
+    let mut interval = tokio::time::interval(state.config.stats_snapshot_interval);
+    loop {
+        interval.tick().await;
+
+        let snapshot = take_snapshot(&state).await;
+        if let Err(e) = state.store.record_stats_snapshot(&snapshot).await {
+            warn!("Failed to record stats snapshot: {}", e);
+        }
+
+        let cutoff = snapshot.timestamp - state.config.stats_retention.as_millis() as i64;
+        if let Err(e) = state.store.prune_stats_snapshots(cutoff).await {
+            warn!("Failed to prune stats snapshots: {}", e);
+        }
+    }
+}
+
+/// Broadcast the current stats to all clients every `interval`
+pub async fn run_stats_push(state: Arc<AppState>, interval: std::time::Duration) {
+    let mut interval = tokio::time::interval(interval);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    loop {
+        interval.tick().await;
+        // Skip the count entirely while nobody is listening
+        if state.event_tx.receiver_count() == 0 {
+            continue;
+        }
+        let _ = state.event_tx.send(current_stats(&state).await);
+    }
+}
+
+/// The node's current statistics as a `Stats` message
+pub async fn current_stats(state: &AppState) -> WsMessage {
+    let broadcast_queue_depth = state.sample_broadcast_depth();
+    WsMessage::Stats {
+        peer_count: peer_count(state).await,
+        message_count: state.message_count.load(Ordering::Relaxed),
+        topic_counts: state.topic_counts.snapshot(),
+        uptime_seconds: state.start_time.elapsed().as_secs(),
+        broadcast_queue_depth,
+        broadcast_high_water: state.broadcast_high_water.load(Ordering::Relaxed),
+        broadcast_capacity: state.config.broadcast_capacity,
+        connection_count: state.connections.len(),
+    }
+}
+
+/// Sample the node's current statistics
+pub async fn take_snapshot(state: &AppState) -> StatsSnapshot {
+    StatsSnapshot {
+        timestamp: chrono::Utc::now().timestamp_millis(),
+        peer_count: peer_count(state).await,
+        message_count: state.message_count.load(Ordering::Relaxed),
+    }
+}
+
+/// Number of known peers, or zero if the store can't be read
+async fn peer_count(state: &AppState) -> usize {
+    match state.store.count_peers().await {
+        Ok(count) => count as usize,
+        Err(e) => {
+            warn!("Failed to count peers: {}", e);
+            0
+        }
+    }
+}
+
+/// Convert downsampled snapshots into chart points
+///
+/// The message rate of each point is measured against the previous point;
+/// the first point has no predecessor and reports a rate of zero.
+pub fn to_points(snapshots: &[StatsSnapshot]) -> Vec<StatsPoint> {
+    let mut previous: Option<&StatsSnapshot> = None;
+    snapshots
+        .iter()
+        .map(|snapshot| {
+            let message_rate = match previous {
+                Some(prev) if snapshot.timestamp > prev.timestamp => {
+                    let messages = snapshot.message_count.saturating_sub(prev.message_count);
+                    let secs = (snapshot.timestamp - prev.timestamp) as f64 / 1000.0;
+                    messages as f64 / secs
+                }
+                _ => 0.0,
+            };
+            previous = Some(snapshot);
+            StatsPoint {
+                timestamp: snapshot.timestamp,
+                peer_count: snapshot.peer_count,
+                message_count: snapshot.message_count,
+                message_rate,
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,98 @@
+//! Shared helpers for unit tests
+
+use std::sync::Arc;
+use tokio::sync::mpsc;
+use uuid::Uuid;
+
+use mycelial_core::peer::PeerId;
+use mycelial_network::{Keypair, NetworkCommand, NetworkHandle};
+use mycelial_protocol::{PassingRule, QuorumMode};
+use mycelial_state::{CreditLineRecord, ProposalRecord, SqliteStore};
+
+use crate::config::ServerConfig;
+use crate::server::messages::WsMessage;
+use crate::server::session::Session;
+use crate::AppState;
+
+/// Build an [`AppState`] backed by an in-memory store and a network handle
+/// whose commands are returned to the test instead of reaching libp2p.
+///
+/// The state starts with one connected peer so the node isn't isolated.
+pub async fn test_state_with_config(
+    config: ServerConfig,
+) -> (Arc<AppState>, mpsc::Receiver<NetworkCommand>) {
+    let libp2p_peer_id = Keypair::generate_ed25519().public().to_peer_id();
+    let (command_tx, command_rx) = mpsc::channel(256);
+    let network = NetworkHandle::new(command_tx, libp2p_peer_id);
+    let store = SqliteStore::new(":memory:").await.unwrap();
+
+    let state = AppState::new(
+        PeerId(libp2p_peer_id.to_base58()),
+        network,
+        store,
+        "test-node".to_string(),
+        config,
+    );
+    state.connected_peers.write().insert("test-peer".to_string());
+    (Arc::new(state), command_rx)
+}
+
+/// Build an [`AppState`] with the default configuration
+pub async fn test_state() -> (Arc<AppState>, mpsc::Receiver<NetworkCommand>) {
+    test_state_with_config(ServerConfig::default()).await
+}
+
+/// A dashboard session whose replies are returned to the test
+pub fn test_session(max_subscriptions: usize) -> (Session, mpsc::UnboundedReceiver<WsMessage>) {
+    let (reply_tx, reply_rx) = mpsc::unbounded_channel();
+    (Session::new(reply_tx, max_subscriptions), reply_rx)
+}
+
+/// A well-formed peer ID for a remote peer
+pub fn remote_peer() -> String {
+    Keypair::generate_ed25519().public().to_peer_id().to_base58()
+}
+
+/// Store a credit line `creditor` extended to the local node, returning its ID
+pub async fn store_credit_line_from(state: &AppState, creditor: &str, limit: f64) -> String {
+    let id = Uuid::new_v4().to_string();
+    state
+        .store
+        .insert_credit_line(&CreditLineRecord {
+            id: id.clone(),
+            creditor: creditor.to_string(),
+            debtor: state.local_peer_id.to_string(),
+            limit,
+            balance: 0.0,
+            created_at: 1_000,
+        })
+        .await
+        .unwrap();
+    id
+}
+
+/// Store an open text proposal by the local node, returning its ID
+pub async fn store_local_proposal(state: &AppState) -> String {
+    let id = Uuid::new_v4().to_string();
+    state
+        .store
+        .upsert_proposal(&ProposalRecord {
+            id: id.clone(),
+            proposer: state.local_peer_id.to_string(),
+            title: "Original".to_string(),
+            description: "Original text".to_string(),
+            proposal_type: "text".to_string(),
+            parameters: Default::default(),
+            status: "active".to_string(),
+            quorum: 3,
+            quorum_fraction: 0.5,
+            quorum_mode: QuorumMode::Snapshot,
+            passing_rule: PassingRule::SimpleMajority,
+            deadline: i64::MAX,
+            created_at: 0,
+            version: 1,
+        })
+        .await
+        .unwrap();
+    id
+}
@@ -0,0 +1,295 @@
+//! Reputation changes driven by vouch responses
+//!
+//! When a vouchee accepts a vouch, the vouchee's stored reputation rises by
+//! an amount that grows with the vouch's stake and the voucher's own
+//! reputation, and shrinks as the vouchee nears full trust. Every node
+//! applies the same rule to the acknowledgements it sees, so the change
+//! needs no message of its own on the network. Revoking an accepted vouch
+//! removes its contribution again. The local node has no stored peer
+//! record, so its reputation is persisted under a state key instead.
+
+use tracing::{debug, warn};
+
+use mycelial_core::reputation::Reputation;
+
+use crate::server::messages::WsMessage;
+use crate::AppState;
+
+/// Largest reputation gain from one vouch: a full stake from a fully trusted voucher
+pub const VOUCH_IMPACT: f64 = 0.2;
+
+/// Reputation gained by a vouchee at `current` from an accepted vouch
+///
+/// The gain is `VOUCH_IMPACT × stake × voucher score`, scaled by the room
+/// left below 1.0. Out-of-range inputs are clamped and NaN counts as zero.
+pub fn vouch_delta(current: f64, stake: f64, voucher_score: f64) -> f64 {
+    VOUCH_IMPACT * unit(stake) * unit(voucher_score) * (1.0 - unit(current))
+}
+
+/// Score of a vouchee at `current` once an accepted vouch is revoked
+///
+/// Inverts [`vouch_delta`], which took a score `c` to `c + k(1 - c)`. The
+/// voucher's present score stands in for the one it had at acceptance.
+pub fn revoked_score(current: f64, stake: f64, voucher_score: f64) -> f64 {
+    let k = VOUCH_IMPACT * unit(stake) * unit(voucher_score);
+    (1.0 - (1.0 - unit(current)) / (1.0 - k)).max(0.0)
+}
+
+/// Clamp to the unit interval, counting NaN as zero
+fn unit(value: f64) -> f64 {
+    if value.is_nan() {
+        0.0
+    } else {
+        value.clamp(0.0, 1.0)
+    }
+}
+
+/// State key holding the local node's reputation
+const LOCAL_REPUTATION_KEY: &str = "local_reputation";
+
+/// Current reputation of `peer_id`, or `None` for a peer without a stored record
+///
+/// The local node always has a reputation, starting from the default.
+pub async fn reputation_of(state: &AppState, peer_id: &str) -> Option<Reputation> {
+    if peer_id == state.local_peer_id.as_str() {
+        return match state.store.get_sync_value(LOCAL_REPUTATION_KEY).await {
+            Ok(Some((value, _))) => match serde_json::from_slice(&value) {
+                Ok(reputation) => Some(reputation),
+                Err(e) => {
+                    warn!("Ignoring unreadable local reputation: {}", e);
+                    Some(Reputation::default())
+                }
+            },
+            Ok(None) => Some(Reputation::default()),
+            Err(e) => {
+                warn!("Failed to load local reputation: {}", e);
+                None
+            }
+        };
+    }
+    match state.store.get_peer(peer_id).await {
+        Ok(Some((_, reputation))) => Some(reputation),
+        Ok(None) => {
+            debug!("No stored reputation for {}", peer_id);
+            None
+        }
+        Err(e) => {
+            warn!("Failed to load reputation for {}: {}", peer_id, e);
+            None
+        }
+    }
+}
+
+/// Keep `peer_id`'s new reputation and broadcast it, returning whether it was kept
+async fn save_reputation(state: &AppState, peer_id: &str, reputation: &Reputation) -> bool {
+    let stored = if peer_id == state.local_peer_id.as_str() {
+        match serde_json::to_vec(reputation) {
+            Ok(value) => state.store.set_sync_value(LOCAL_REPUTATION_KEY, &value).await,
+            Err(e) => Err(e.into()),
+        }
+    } else {
+        state.store.update_peer_reputation(peer_id, reputation).await
+    };
+    if let Err(e) = stored {
+        warn!("Failed to store reputation for {}: {}", peer_id, e);
+        return false;
+    }
+    let _ = state.event_tx.send(WsMessage::ReputationUpdate {
+        peer_id: peer_id.to_string(),
+        new_score: reputation.score,
+    });
+    true
+}
+
+/// Record a vouchee's response to a vouch, returning the vouchee's new score
+///
+/// Only the first response to a vouch counts. An accepted vouch raises the
+/// vouchee's reputation, persists it and broadcasts a `ReputationUpdate`.
+/// Rejections, repeated responses and vouchees without a stored peer record
+/// leave reputation unchanged.
+pub async fn record_vouch_response(
+    state: &AppState,
+    vouch_id: &str,
+    accepted: bool,
+    timestamp: i64,
+) -> Option<f64> {
+    match state.store.respond_vouch(vouch_id, accepted, timestamp).await {
+        Ok(true) => {}
+        Ok(false) => {
+            debug!("Vouch {} unknown or already answered", vouch_id);
+            return None;
+        }
+        Err(e) => {
+            warn!("Failed to record vouch response: {}", e);
+            return None;
+        }
+    }
+    if !accepted {
+        return None;
+    }
+
+    let vouch = match state.store.get_vouch(vouch_id).await {
+        Ok(Some(vouch)) => vouch,
+        Ok(None) => return None,
+        Err(e) => {
+            warn!("Failed to load vouch {}: {}", vouch_id, e);
+            return None;
+        }
+    };
+    let mut reputation = reputation_of(state, &vouch.vouchee).await?;
+    let voucher_score = reputation_of(state, &vouch.voucher)
+        .await
+        .unwrap_or_default()
+        .score;
+
+    reputation.adjust(vouch_delta(reputation.score, vouch.weight, voucher_score));
+    if !save_reputation(state, &vouch.vouchee, &reputation).await {
+        return None;
+    }
+    Some(reputation.score)
+}
+
+/// Revoke `voucher`'s vouches for `vouchee`, returning whether any were active
+///
+/// Accepted vouches lose their contribution to the vouchee's stored
+/// reputation, which is persisted and broadcast as a `ReputationUpdate`.
+/// Revoking only pending vouches leaves reputation unchanged.
+pub async fn record_vouch_revocation(state: &AppState, voucher: &str, vouchee: &str, timestamp: i64) -> bool {
+    let revoked = match state.store.revoke_vouches(voucher, vouchee, timestamp).await {
+        Ok(revoked) => revoked,
+        Err(e) => {
+            warn!("Failed to revoke vouches from {} for {}: {}", voucher, vouchee, e);
+            return false;
+        }
+    };
+    let accepted: Vec<_> = revoked.iter().filter(|vouch| vouch.status == "accepted").collect();
+    if accepted.is_empty() {
+        return !revoked.is_empty();
+    }
+
+    let Some(mut reputation) = reputation_of(state, vouchee).await else {
+        return true;
+    };
+    let voucher_score = reputation_of(state, voucher).await.unwrap_or_default().score;
+
+    for vouch in accepted {
+        let current = reputation.score;
+        reputation.adjust(revoked_score(current, vouch.weight, voucher_score) - current);
+    }
+    save_reputation(state, vouchee, &reputation).await;
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::test_state;
+    use mycelial_core::peer::{PeerId, PeerInfo};
+    use mycelial_state::VouchRecord;
+
+    #[test]
+    fn test_vouch_delta() {
+        // Full stake from a fully trusted voucher to a neutral peer
+        assert!((vouch_delta(0.5, 1.0, 1.0) - 0.1).abs() < 1e-9);
+        // Scales with stake and the voucher's reputation
+        assert!((vouch_delta(0.5, 0.5, 0.5) - 0.025).abs() < 1e-9);
+        // Diminishes toward full trust, and never pushes past it
+        assert!(vouch_delta(0.9, 1.0, 1.0) < vouch_delta(0.5, 1.0, 1.0));
+        assert_eq!(vouch_delta(1.0, 1.0, 1.0), 0.0);
+        // Untrusted vouchers and bad inputs add nothing
+        assert_eq!(vouch_delta(0.5, 1.0, 0.0), 0.0);
+        assert_eq!(vouch_delta(0.5, f64::NAN, 1.0), 0.0);
+        assert!((vouch_delta(0.5, 3.0, 1.0) - 0.1).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_revoked_score_inverts_delta() {
+        for (current, stake, voucher) in [(0.5, 1.0, 1.0), (0.2, 0.3, 0.7), (0.0, 0.5, 0.5), (0.95, 1.0, 0.1)] {
+            let raised = current + vouch_delta(current, stake, voucher);
+            assert!((revoked_score(raised, stake, voucher) - current).abs() < 1e-9);
+        }
+        // Never drops below zero
+        assert_eq!(revoked_score(0.0, 1.0, 1.0), 0.0);
+    }
+
+    async fn store_vouch(state: &AppState, id: &str, voucher: &str, vouchee: &str) {
+        let peer = PeerInfo {
+            id: PeerId(vouchee.to_string()),
+            public_key: vouchee.to_string(),
+            addresses: vec![],
+            first_seen: chrono::Utc::now(),
+            last_seen: chrono::Utc::now(),
+            name: None,
+        };
+        state.store.upsert_peer(&peer, None).await.unwrap();
+        let record = VouchRecord {
+            id: id.to_string(),
+            voucher: voucher.to_string(),
+            vouchee: vouchee.to_string(),
+            weight: 1.0,
+            message: None,
+            status: "pending".to_string(),
+            created_at: 0,
+        };
+        state.store.insert_vouch(&record).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_accepted_vouch_raises_reputation() {
+        let (state, _commands) = test_state().await;
+        let mut events = state.event_tx.subscribe();
+        store_vouch(&state, "v1", "alice", "bob").await;
+
+        // An unknown voucher counts as neutral: 0.2 × 1.0 × 0.5 × 0.5
+        let score = record_vouch_response(&state, "v1", true, 10).await.unwrap();
+        assert!((score - 0.55).abs() < 1e-9);
+        match events.try_recv() {
+            Ok(WsMessage::ReputationUpdate { peer_id, new_score }) => {
+                assert_eq!(peer_id, "bob");
+                assert_eq!(new_score, score);
+            }
+            other => panic!("expected reputation update, got {:?}", other),
+        }
+        let (_, stored) = state.store.get_peer("bob").await.unwrap().unwrap();
+        assert_eq!(stored.score, score);
+
+        // A repeated response changes nothing
+        assert!(record_vouch_response(&state, "v1", true, 20).await.is_none());
+        assert!(events.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_revoking_accepted_vouch_restores_reputation() {
+        let (state, _commands) = test_state().await;
+        store_vouch(&state, "v1", "alice", "bob").await;
+        record_vouch_response(&state, "v1", true, 10).await.unwrap();
+        let mut events = state.event_tx.subscribe();
+
+        assert!(record_vouch_revocation(&state, "alice", "bob", 20).await);
+        match events.try_recv() {
+            Ok(WsMessage::ReputationUpdate { peer_id, new_score }) => {
+                assert_eq!(peer_id, "bob");
+                assert!((new_score - 0.5).abs() < 1e-9);
+            }
+            other => panic!("expected reputation update, got {:?}", other),
+        }
+        let (_, stored) = state.store.get_peer("bob").await.unwrap().unwrap();
+        assert!((stored.score - 0.5).abs() < 1e-9);
+
+        // Nothing is left to revoke
+        assert!(!record_vouch_revocation(&state, "alice", "bob", 30).await);
+        assert!(events.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_rejected_vouch_leaves_reputation() {
+        let (state, _commands) = test_state().await;
+        let mut events = state.event_tx.subscribe();
+        store_vouch(&state, "v1", "alice", "bob").await;
+
+        assert!(record_vouch_response(&state, "v1", false, 10).await.is_none());
+        assert!(events.try_recv().is_err());
+        let (_, stored) = state.store.get_peer("bob").await.unwrap().unwrap();
+        assert_eq!(stored.score, 0.5);
+    }
+}
@@ -26,15 +26,23 @@ pub mod messages;
 pub use messages::{
     // Topics
     topics,
+    // Signed actions
+    ActionSignature, Signed,
+    // Correlation IDs
+    Correlated, MAX_CORRELATION_ID_LEN,
     // Vouch protocol
-    VouchMessage, VouchRequest, VouchAck, ReputationUpdate, ReputationChangeReason,
+    VouchMessage, VouchRequest, VouchAck, VouchRevoke, ReputationUpdate, ReputationChangeReason,
     // Credit protocol
     CreditMessage, CreateCreditLine, CreditLineAck, CreditTransfer, CreditTransferAck, CreditLineUpdate,
+    PaymentRequest,
     // Governance protocol
     GovernanceMessage, CreateProposal, ProposalType, CastVote, Vote, ProposalUpdate, ProposalStatus, ProposalExecuted,
+    ProposalAmendment, ProposalCancellation, QuorumMode, PassingRule, SyncRequest, SyncResponse,
     // Resource protocol
     ResourceMessage, ResourceContribution, ResourceType, ResourceMetrics,
     BandwidthMetrics, StorageMetrics, ComputeMetrics, ResourcePoolUpdate, ContributorSummary,
+    // Presence
+    PresenceMessage, PresenceLeave,
 };
 
 use mycelial_core::{Message, MycelialError, Result};
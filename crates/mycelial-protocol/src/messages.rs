@@ -4,6 +4,8 @@
 //! for the Mycelial Economics system: vouching, credits, governance, and resources.
 
 use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use mycelial_core::identity::{Did, PublicKeyExt, SignatureBytes};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
@@ -17,8 +19,219 @@ pub mod topics {
     pub const GOVERNANCE: &str = "/mycelial/1.0.0/governance";
     /// Topic for resource sharing metrics
     pub const RESOURCE: &str = "/mycelial/1.0.0/resource";
+    /// Default topic for presence notices
+    pub const PRESENCE: &str = "/mycelial/1.0.0/presence";
+    /// Prefix of direct message topics, followed by the recipient's peer ID
+    pub const DIRECT_PREFIX: &str = "/mycelial/1.0.0/direct/";
+    /// Prefix of chat room topics, followed by the room ID
+    pub const ROOM_PREFIX: &str = "/mycelial/1.0.0/room/";
+    /// Longest accepted room ID, in bytes
+    pub const MAX_ROOM_ID_LEN: usize = 64;
+
+    /// Whether `room_id` can be embedded in a topic
+    ///
+    /// Room IDs are 1 to [`MAX_ROOM_ID_LEN`] ASCII letters, digits, `-` or
+    /// `_`, which covers generated UUIDs and keeps separators such as `/`
+    /// out of topic strings.
+    pub fn is_valid_room_id(room_id: &str) -> bool {
+        !room_id.is_empty()
+            && room_id.len() <= MAX_ROOM_ID_LEN
+            && room_id.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_')
+    }
+
+    /// Topic for a chat room, if `room_id` is valid
+    pub fn room(room_id: &str) -> Option<String> {
+        is_valid_room_id(room_id).then(|| format!("{}{}", ROOM_PREFIX, room_id))
+    }
+
+    /// The room ID of a chat room topic
+    pub fn room_id(topic: &str) -> Option<&str> {
+        topic.strip_prefix(ROOM_PREFIX).filter(|id| is_valid_room_id(id))
+    }
+
+    /// Topic carrying direct messages to `peer_id`
+    ///
+    /// Each node subscribes only to its own, so direct messages aren't
+    /// relayed to every node on the network.
+    pub fn direct(peer_id: &str) -> String {
+        format!("{}{}", DIRECT_PREFIX, peer_id)
+    }
+
+    /// The recipient's peer ID of a direct message topic
+    pub fn direct_recipient(topic: &str) -> Option<&str> {
+        topic.strip_prefix(DIRECT_PREFIX).filter(|peer_id| !peer_id.is_empty())
+    }
+
+    /// The topic as used on the wire within `namespace`
+    ///
+    /// Namespaces let separate logical networks share a transport: a
+    /// namespace `testnet` turns `/mycelial/1.0.0/chat` into
+    /// `/testnet/mycelial/1.0.0/chat`. An empty namespace leaves topics as is.
+    pub fn namespaced(namespace: &str, topic: &str) -> String {
+        if namespace.is_empty() {
+            topic.to_string()
+        } else {
+            format!("/{}{}", namespace, topic)
+        }
+    }
+
+    /// The un-namespaced topic, if `wire_topic` belongs to `namespace`
+    pub fn strip_namespace<'a>(namespace: &str, wire_topic: &'a str) -> Option<&'a str> {
+        if namespace.is_empty() {
+            return Some(wire_topic);
+        }
+        wire_topic
+            .strip_prefix('/')
+            .and_then(|rest| rest.strip_prefix(namespace))
+            .filter(|topic| topic.starts_with('/'))
+    }
+}
+
+// ============================================================================
+// SIGNED ACTIONS
+// ============================================================================
+
+/// A client's signature over the action that produced a message
+///
+/// The exact signed payload travels with the signature so any peer can check
+/// that the action was authorized by the holder of `signer`'s key. The
+/// signature also covers a nonce and the time of signing (see
+/// [`signed_bytes`](Self::signed_bytes)), so a captured signature can't be
+/// presented again as a new action.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ActionSignature {
+    /// Signer's `did:key` identifier
+    pub signer: String,
+    /// The signed action payload, exactly as signed
+    pub payload: String,
+    /// Client-chosen value unique to this signature
+    pub nonce: String,
+    /// When the action was signed (epoch millis)
+    pub timestamp: i64,
+    /// Hex-encoded Ed25519 signature over [`signed_bytes`](Self::signed_bytes)
+    pub signature: String,
+}
+
+impl ActionSignature {
+    /// The bytes a signer signs for `payload`: `mycelial-action:<nonce>:<timestamp>:<payload>`
+    pub fn signed_bytes(nonce: &str, timestamp: i64, payload: &str) -> Vec<u8> {
+        format!("mycelial-action:{}:{}:{}", nonce, timestamp, payload).into_bytes()
+    }
+
+    /// Whether `signature` is a valid signature by `signer` over the payload, nonce and timestamp
+    pub fn verify(&self) -> bool {
+        let Ok(public_key) = Did::parse(&self.signer).and_then(|did| did.to_public_key()) else {
+            return false;
+        };
+        let Ok(signature) = SignatureBytes::from_hex(&self.signature) else {
+            return false;
+        };
+        let signed = Self::signed_bytes(&self.nonce, self.timestamp, &self.payload);
+        public_key.verify_bytes(&signed, &signature).is_ok()
+    }
+}
+
+/// A message that can carry the [`ActionSignature`] of the client action
+/// that produced it
+///
+/// Nodes attach the signature when a client signed the action, and peers drop
+/// messages whose signature doesn't verify. Unsigned messages carry none.
+pub trait Signed: Sized {
+    /// The attached signature, if any
+    fn signature(&self) -> Option<&ActionSignature>;
+
+    /// Attach the signature of the action behind this message
+    fn with_signature(self, signature: Option<ActionSignature>) -> Self;
+}
+
+macro_rules! impl_signed {
+    ($($message:ty),* $(,)?) => {
+        $(
+            impl Signed for $message {
+                fn signature(&self) -> Option<&ActionSignature> {
+                    self.signature.as_ref()
+                }
+
+                fn with_signature(mut self, signature: Option<ActionSignature>) -> Self {
+                    self.signature = signature;
+                    self
+                }
+            }
+        )*
+    };
+}
+
+impl_signed!(
+    VouchRequest,
+    VouchAck,
+    VouchRevoke,
+    CreateCreditLine,
+    CreditTransfer,
+    PaymentRequest,
+    CreateProposal,
+    CastVote,
+    ResourceContribution,
+);
+
+/// Longest accepted correlation ID, in bytes
+pub const MAX_CORRELATION_ID_LEN: usize = 128;
+
+/// A message that can carry a correlation ID grouping it with related
+/// economics activity
+///
+/// Clients tag an action with an ID of their choosing, and nodes echo it on
+/// every event the action produces. IDs longer than
+/// [`MAX_CORRELATION_ID_LEN`] are dropped, both when attached and when
+/// received.
+pub trait Correlated: Sized {
+    /// The attached correlation ID, if any
+    fn correlation_id(&self) -> Option<&str>;
+
+    /// Tag the message with a correlation ID linking related activity
+    fn with_correlation_id(self, correlation_id: Option<String>) -> Self;
+}
+
+/// Keep a correlation ID only if it is within [`MAX_CORRELATION_ID_LEN`]
+pub fn cap_correlation_id(correlation_id: Option<String>) -> Option<String> {
+    correlation_id.filter(|id| id.len() <= MAX_CORRELATION_ID_LEN)
+}
+
+fn capped_correlation_id<'de, D>(deserializer: D) -> Result<Option<String>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    Option::<String>::deserialize(deserializer).map(cap_correlation_id)
 }
 
+macro_rules! impl_correlated {
+    ($($message:ty),* $(,)?) => {
+        $(
+            impl Correlated for $message {
+                fn correlation_id(&self) -> Option<&str> {
+                    self.correlation_id.as_deref()
+                }
+
+                fn with_correlation_id(mut self, correlation_id: Option<String>) -> Self {
+                    self.correlation_id = cap_correlation_id(correlation_id);
+                    self
+                }
+            }
+        )*
+    };
+}
+
+impl_correlated!(
+    VouchRequest,
+    VouchAck,
+    VouchRevoke,
+    CreateCreditLine,
+    CreditTransfer,
+    PaymentRequest,
+    CreateProposal,
+    CastVote,
+    ResourceContribution,
+);
+
 // ============================================================================
 // VOUCH PROTOCOL MESSAGES
 // ============================================================================
@@ -31,10 +244,24 @@ pub enum VouchMessage {
     VouchRequest(VouchRequest),
     /// Vouch acknowledgement
     VouchAck(VouchAck),
+    /// Withdrawal of a voucher's vouches for a peer
+    VouchRevoke(VouchRevoke),
     /// Reputation update notification
     ReputationUpdate(ReputationUpdate),
 }
 
+impl VouchMessage {
+    /// Signature of the client action behind the message, if signed
+    pub fn signature(&self) -> Option<&ActionSignature> {
+        match self {
+            VouchMessage::VouchRequest(request) => request.signature(),
+            VouchMessage::VouchAck(ack) => ack.signature(),
+            VouchMessage::VouchRevoke(revoke) => revoke.signature(),
+            VouchMessage::ReputationUpdate(_) => None,
+        }
+    }
+}
+
 /// A vouch request from one peer to another
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct VouchRequest {
@@ -52,6 +279,12 @@ pub struct VouchRequest {
     pub timestamp: DateTime<Utc>,
     /// Expiration time for the vouch
     pub expires_at: Option<DateTime<Utc>>,
+    /// See [`Correlated`]
+    #[serde(default, deserialize_with = "capped_correlation_id", skip_serializing_if = "Option::is_none")]
+    pub correlation_id: Option<String>,
+    /// See [`Signed`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<ActionSignature>,
 }
 
 impl VouchRequest {
@@ -65,6 +298,8 @@ impl VouchRequest {
             message: None,
             timestamp: Utc::now(),
             expires_at: None,
+            correlation_id: None,
+            signature: None,
         }
     }
 
@@ -94,6 +329,44 @@ pub struct VouchAck {
     pub reason: Option<String>,
     /// Timestamp
     pub timestamp: DateTime<Utc>,
+    /// See [`Correlated`]
+    #[serde(default, deserialize_with = "capped_correlation_id", skip_serializing_if = "Option::is_none")]
+    pub correlation_id: Option<String>,
+    /// See [`Signed`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<ActionSignature>,
+}
+
+/// Withdrawal of a voucher's vouches for a peer
+///
+/// Revokes every pending or accepted vouch from `voucher` to `vouchee`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VouchRevoke {
+    /// Peer withdrawing its vouch
+    pub voucher: String,
+    /// Peer the vouch was for
+    pub vouchee: String,
+    /// When the vouch was revoked
+    pub timestamp: DateTime<Utc>,
+    /// See [`Correlated`]
+    #[serde(default, deserialize_with = "capped_correlation_id", skip_serializing_if = "Option::is_none")]
+    pub correlation_id: Option<String>,
+    /// See [`Signed`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<ActionSignature>,
+}
+
+impl VouchRevoke {
+    /// Create a revocation of `voucher`'s vouches for `vouchee`
+    pub fn new(voucher: String, vouchee: String) -> Self {
+        Self {
+            voucher,
+            vouchee,
+            timestamp: Utc::now(),
+            correlation_id: None,
+            signature: None,
+        }
+    }
 }
 
 /// Reputation update notification
@@ -149,6 +422,20 @@ pub enum CreditMessage {
     TransferAck(CreditTransferAck),
     /// Credit line update notification
     LineUpdate(CreditLineUpdate),
+    /// Request for payment
+    PaymentRequest(PaymentRequest),
+}
+
+impl CreditMessage {
+    /// Signature of the client action behind the message, if signed
+    pub fn signature(&self) -> Option<&ActionSignature> {
+        match self {
+            CreditMessage::CreateLine(line) => line.signature(),
+            CreditMessage::Transfer(transfer) => transfer.signature(),
+            CreditMessage::PaymentRequest(request) => request.signature(),
+            CreditMessage::LineAck(_) | CreditMessage::TransferAck(_) | CreditMessage::LineUpdate(_) => None,
+        }
+    }
 }
 
 /// Request to create a credit line
@@ -168,6 +455,12 @@ pub struct CreateCreditLine {
     pub collateral: Option<String>,
     /// When the request was created
     pub timestamp: DateTime<Utc>,
+    /// See [`Correlated`]
+    #[serde(default, deserialize_with = "capped_correlation_id", skip_serializing_if = "Option::is_none")]
+    pub correlation_id: Option<String>,
+    /// See [`Signed`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<ActionSignature>,
 }
 
 impl CreateCreditLine {
@@ -181,6 +474,8 @@ impl CreateCreditLine {
             interest_rate: 0.0,
             collateral: None,
             timestamp: Utc::now(),
+            correlation_id: None,
+            signature: None,
         }
     }
 }
@@ -215,8 +510,17 @@ pub struct CreditTransfer {
     pub amount: f64,
     /// Optional memo
     pub memo: Option<String>,
+    /// Payment request this transfer settles
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_ref: Option<String>,
     /// Timestamp
     pub timestamp: DateTime<Utc>,
+    /// See [`Correlated`]
+    #[serde(default, deserialize_with = "capped_correlation_id", skip_serializing_if = "Option::is_none")]
+    pub correlation_id: Option<String>,
+    /// See [`Signed`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<ActionSignature>,
 }
 
 impl CreditTransfer {
@@ -229,7 +533,10 @@ impl CreditTransfer {
             to,
             amount,
             memo: None,
+            request_ref: None,
             timestamp: Utc::now(),
+            correlation_id: None,
+            signature: None,
         }
     }
 
@@ -238,6 +545,57 @@ impl CreditTransfer {
         self.memo = Some(memo.into());
         self
     }
+
+    /// Link the transfer to the payment request it settles
+    pub fn with_request_ref(mut self, request_ref: impl Into<String>) -> Self {
+        self.request_ref = Some(request_ref.into());
+        self
+    }
+}
+
+/// Request for another peer to send credits
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PaymentRequest {
+    /// Unique request ID, referenced by the settling transfer
+    pub id: Uuid,
+    /// Peer asking to be paid
+    pub requester: String,
+    /// Peer asked to pay
+    pub payer: String,
+    /// Requested amount
+    pub amount: f64,
+    /// Optional memo
+    pub memo: Option<String>,
+    /// Timestamp
+    pub timestamp: DateTime<Utc>,
+    /// See [`Correlated`]
+    #[serde(default, deserialize_with = "capped_correlation_id", skip_serializing_if = "Option::is_none")]
+    pub correlation_id: Option<String>,
+    /// See [`Signed`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<ActionSignature>,
+}
+
+impl PaymentRequest {
+    /// Create a new payment request
+    pub fn new(requester: String, payer: String, amount: f64) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            requester,
+            payer,
+            amount,
+            memo: None,
+            timestamp: Utc::now(),
+            correlation_id: None,
+            signature: None,
+        }
+    }
+
+    /// Add a memo to the request
+    pub fn with_memo(mut self, memo: impl Into<String>) -> Self {
+        self.memo = Some(memo.into());
+        self
+    }
 }
 
 /// Acknowledgement of credit transfer
@@ -245,6 +603,9 @@ impl CreditTransfer {
 pub struct CreditTransferAck {
     /// Transfer ID
     pub transfer_id: Uuid,
+    /// Peer sending acknowledgement
+    #[serde(default)]
+    pub from: String,
     /// Whether successful
     pub success: bool,
     /// New balance after transfer
@@ -288,6 +649,25 @@ pub enum GovernanceMessage {
     ProposalUpdate(ProposalUpdate),
     /// Proposal executed notification
     ProposalExecuted(ProposalExecuted),
+    /// Proposal amended by its proposer before voting began
+    AmendProposal(ProposalAmendment),
+    /// Proposal withdrawn by its proposer
+    CancelProposal(ProposalCancellation),
+    /// Ask peers for governance state the requester may have missed
+    SyncRequest(SyncRequest),
+    /// Governance state shared in answer to a sync request
+    SyncResponse(SyncResponse),
+}
+
+impl GovernanceMessage {
+    /// Signature of the client action behind the message, if signed
+    pub fn signature(&self) -> Option<&ActionSignature> {
+        match self {
+            GovernanceMessage::CreateProposal(proposal) => proposal.signature(),
+            GovernanceMessage::CastVote(vote) => vote.signature(),
+            _ => None,
+        }
+    }
 }
 
 /// Create a new governance proposal
@@ -305,12 +685,28 @@ pub struct CreateProposal {
     pub proposal_type: ProposalType,
     /// Required quorum (0.0 to 1.0)
     pub quorum: f64,
+    /// Voters needed for quorum when the proposer fixed the number, taking
+    /// precedence over `quorum` when the proposal is created
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quorum_voters: Option<u32>,
+    /// Which set of eligible voters the quorum is measured against
+    #[serde(default)]
+    pub quorum_mode: QuorumMode,
     /// Required approval threshold (0.0 to 1.0)
     pub threshold: f64,
+    /// How the votes cast decide whether the proposal passes
+    #[serde(default)]
+    pub passing_rule: PassingRule,
     /// Voting deadline
     pub deadline: DateTime<Utc>,
     /// When created
     pub timestamp: DateTime<Utc>,
+    /// See [`Correlated`]
+    #[serde(default, deserialize_with = "capped_correlation_id", skip_serializing_if = "Option::is_none")]
+    pub correlation_id: Option<String>,
+    /// See [`Signed`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<ActionSignature>,
 }
 
 impl CreateProposal {
@@ -323,9 +719,14 @@ impl CreateProposal {
             description,
             proposal_type: ProposalType::General,
             quorum: 0.5,
+            quorum_voters: None,
+            quorum_mode: QuorumMode::default(),
             threshold: 0.5,
+            passing_rule: PassingRule::default(),
             deadline: Utc::now() + chrono::Duration::days(7),
             timestamp: Utc::now(),
+            correlation_id: None,
+            signature: None,
         }
     }
 
@@ -341,12 +742,30 @@ impl CreateProposal {
         self
     }
 
+    /// Fix the number of voters needed for quorum
+    pub fn with_quorum_voters(mut self, voters: u32) -> Self {
+        self.quorum_voters = Some(voters.max(1));
+        self
+    }
+
+    /// Set how the quorum denominator is determined
+    pub fn with_quorum_mode(mut self, quorum_mode: QuorumMode) -> Self {
+        self.quorum_mode = quorum_mode;
+        self
+    }
+
     /// Set approval threshold
     pub fn with_threshold(mut self, threshold: f64) -> Self {
         self.threshold = threshold.clamp(0.0, 1.0);
         self
     }
 
+    /// Set the rule deciding whether the proposal passes
+    pub fn with_passing_rule(mut self, passing_rule: PassingRule) -> Self {
+        self.passing_rule = passing_rule;
+        self
+    }
+
     /// Set voting deadline
     pub fn with_deadline(mut self, deadline: DateTime<Utc>) -> Self {
         self.deadline = deadline;
@@ -354,6 +773,180 @@ impl CreateProposal {
     }
 }
 
+/// How the votes on a proposal decide whether it passes
+///
+/// Only yes and no votes count; abstentions are ignored. Whether enough
+/// peers voted is a separate quorum check.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PassingRule {
+    /// More yes weight than no weight
+    #[default]
+    SimpleMajority,
+    /// Yes weight is at least this fraction of yes plus no weight
+    Supermajority(f64),
+    /// At least one yes vote and no votes against
+    Unanimous,
+}
+
+impl PassingRule {
+    /// Check that a supermajority fraction is above one half and at most one
+    pub fn validate(&self) -> Result<(), String> {
+        match self {
+            PassingRule::Supermajority(fraction) if !(*fraction > 0.5 && *fraction <= 1.0) => Err(format!(
+                "Supermajority fraction must be greater than 0.5 and at most 1.0, got {}",
+                fraction
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Whether `yes` and `no` vote weights satisfy the rule
+    pub fn passes(&self, yes: f64, no: f64) -> bool {
+        match self {
+            PassingRule::SimpleMajority => yes > no,
+            PassingRule::Supermajority(fraction) => yes > 0.0 && yes >= fraction * (yes + no),
+            PassingRule::Unanimous => yes > 0.0 && no == 0.0,
+        }
+    }
+
+    /// Stable string form used for storage
+    pub fn to_storage(&self) -> String {
+        match self {
+            PassingRule::SimpleMajority => "simple_majority".to_string(),
+            PassingRule::Supermajority(fraction) => format!("supermajority:{}", fraction),
+            PassingRule::Unanimous => "unanimous".to_string(),
+        }
+    }
+
+    /// Parse the storage form, defaulting unknown values to `SimpleMajority`
+    pub fn parse(value: &str) -> Self {
+        match value.split_once(':') {
+            Some(("supermajority", fraction)) => fraction
+                .parse()
+                .map(PassingRule::Supermajority)
+                .unwrap_or_default(),
+            _ if value == "unanimous" => PassingRule::Unanimous,
+            _ => PassingRule::SimpleMajority,
+        }
+    }
+}
+
+/// How a proposal's quorum adapts to changes in the eligible voter set
+///
+/// The quorum is a fraction of eligible voters. With `Snapshot`, the
+/// number of eligible voters is fixed when the proposal is created, so the
+/// required vote count never changes. With `Dynamic`, the required count is
+/// recomputed against the eligible set at the time it's evaluated (including
+/// finalization), so peers joining or leaving move the bar.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QuorumMode {
+    /// Required votes fixed against the eligible set at creation
+    #[default]
+    Snapshot,
+    /// Required votes recomputed against the current eligible set
+    Dynamic,
+}
+
+impl QuorumMode {
+    /// Stable string form used for storage
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            QuorumMode::Snapshot => "snapshot",
+            QuorumMode::Dynamic => "dynamic",
+        }
+    }
+
+    /// Parse the storage form, defaulting unknown values to `Snapshot`
+    pub fn parse(value: &str) -> Self {
+        match value {
+            "dynamic" => QuorumMode::Dynamic,
+            _ => QuorumMode::Snapshot,
+        }
+    }
+}
+
+/// Amendment to a proposal's text, made by its proposer
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProposalAmendment {
+    /// Proposal being amended
+    pub proposal_id: Uuid,
+    /// Proposer making the amendment
+    pub proposer: String,
+    /// Amended title
+    pub title: String,
+    /// Amended description
+    pub description: String,
+    /// New amendment version (the original proposal is version 1)
+    pub version: u32,
+    /// Timestamp
+    pub timestamp: DateTime<Utc>,
+}
+
+/// Withdrawal of an active proposal by its proposer
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProposalCancellation {
+    /// Proposal being cancelled
+    pub proposal_id: Uuid,
+    /// Proposer cancelling it
+    pub proposer: String,
+    /// Timestamp
+    pub timestamp: DateTime<Utc>,
+}
+
+/// Request for governance state created since a point in time
+///
+/// Sent by a node that may have been offline; peers answer with a
+/// [`SyncResponse`] carrying the proposals and votes they know of.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyncRequest {
+    /// Unique request ID, echoed in responses
+    pub id: Uuid,
+    /// Peer asking for state
+    pub requester: String,
+    /// Only proposals created at or after this time are wanted
+    pub since: DateTime<Utc>,
+    /// Timestamp
+    pub timestamp: DateTime<Utc>,
+}
+
+impl SyncRequest {
+    /// Create a request for state since `since`
+    pub fn new(requester: String, since: DateTime<Utc>) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            requester,
+            since,
+            timestamp: Utc::now(),
+        }
+    }
+}
+
+/// Governance state shared by a peer in answer to a [`SyncRequest`]
+///
+/// Large answers are split across several responses, numbered by `page`,
+/// so each fits in one gossip message.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyncResponse {
+    /// Request being answered
+    pub request_id: Uuid,
+    /// Peer sharing its state
+    pub responder: String,
+    /// Position of this response among the responder's answers, from 0
+    #[serde(default)]
+    pub page: u32,
+    /// Proposals known to the responder
+    pub proposals: Vec<CreateProposal>,
+    /// Status of those proposals at the responder; any missing are active
+    #[serde(default)]
+    pub statuses: HashMap<Uuid, ProposalStatus>,
+    /// Votes known to the responder on proposals it shares
+    pub votes: Vec<CastVote>,
+    /// Timestamp
+    pub timestamp: DateTime<Utc>,
+}
+
 /// Type of governance proposal
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
@@ -391,6 +984,12 @@ pub struct CastVote {
     pub reason: Option<String>,
     /// Timestamp
     pub timestamp: DateTime<Utc>,
+    /// See [`Correlated`]
+    #[serde(default, deserialize_with = "capped_correlation_id", skip_serializing_if = "Option::is_none")]
+    pub correlation_id: Option<String>,
+    /// See [`Signed`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<ActionSignature>,
 }
 
 impl CastVote {
@@ -403,6 +1002,8 @@ impl CastVote {
             weight: weight.max(0.0),
             reason: None,
             timestamp: Utc::now(),
+            correlation_id: None,
+            signature: None,
         }
     }
 
@@ -491,6 +1092,16 @@ pub enum ResourceMessage {
     PoolUpdate(ResourcePoolUpdate),
 }
 
+impl ResourceMessage {
+    /// Signature of the client action behind the message, if signed
+    pub fn signature(&self) -> Option<&ActionSignature> {
+        match self {
+            ResourceMessage::Contribution(contribution) => contribution.signature(),
+            ResourceMessage::Metrics(_) | ResourceMessage::PoolUpdate(_) => None,
+        }
+    }
+}
+
 /// Report of resource contribution
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ResourceContribution {
@@ -508,6 +1119,12 @@ pub struct ResourceContribution {
     pub duration_secs: u64,
     /// Timestamp
     pub timestamp: DateTime<Utc>,
+    /// See [`Correlated`]
+    #[serde(default, deserialize_with = "capped_correlation_id", skip_serializing_if = "Option::is_none")]
+    pub correlation_id: Option<String>,
+    /// See [`Signed`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<ActionSignature>,
 }
 
 impl ResourceContribution {
@@ -521,6 +1138,8 @@ impl ResourceContribution {
             unit,
             duration_secs: 0,
             timestamp: Utc::now(),
+            correlation_id: None,
+            signature: None,
         }
     }
 
@@ -629,6 +1248,37 @@ pub struct ContributorSummary {
     pub primary_resource: ResourceType,
 }
 
+// ============================================================================
+// PRESENCE MESSAGES
+// ============================================================================
+
+/// Messages announcing changes in a peer's presence
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum PresenceMessage {
+    /// The peer's last dashboard session closed
+    Leave(PresenceLeave),
+}
+
+/// Notice that a peer has left
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PresenceLeave {
+    /// Peer that left
+    pub peer_id: String,
+    /// Timestamp
+    pub timestamp: DateTime<Utc>,
+}
+
+impl PresenceLeave {
+    /// Create a notice that `peer_id` left now
+    pub fn new(peer_id: String) -> Self {
+        Self {
+            peer_id,
+            timestamp: Utc::now(),
+        }
+    }
+}
+
 // ============================================================================
 // TESTS
 // ============================================================================
@@ -637,6 +1287,45 @@ pub struct ContributorSummary {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_room_topics() {
+        assert_eq!(topics::room("general").as_deref(), Some("/mycelial/1.0.0/room/general"));
+        let uuid = "67e55044-10b1-426f-9247-bb680e5fe0c8";
+        assert_eq!(topics::room_id(&topics::room(uuid).unwrap()), Some(uuid));
+        assert_eq!(topics::room_id(topics::CREDIT), None);
+
+        // Names that would break out of the topic are rejected
+        let too_long = "x".repeat(65);
+        for bad in ["", "a/b", "../chat", "room id", "caf\u{e9}", too_long.as_str()] {
+            assert!(!topics::is_valid_room_id(bad), "{:?} accepted", bad);
+            assert_eq!(topics::room(bad), None);
+        }
+        assert!(topics::is_valid_room_id(&"x".repeat(64)));
+        assert_eq!(topics::room_id("/mycelial/1.0.0/room/a/b"), None);
+    }
+
+    #[test]
+    fn test_direct_topics() {
+        let topic = topics::direct("12D3KooWPeer");
+        assert_eq!(topic, "/mycelial/1.0.0/direct/12D3KooWPeer");
+        assert_eq!(topics::direct_recipient(&topic), Some("12D3KooWPeer"));
+        assert_eq!(topics::direct_recipient(topics::DIRECT_PREFIX), None);
+        assert_eq!(topics::direct_recipient("/mycelial/1.0.0/chat"), None);
+    }
+
+    #[test]
+    fn test_topic_namespaces() {
+        let wire = topics::namespaced("testnet", topics::CREDIT);
+        assert_eq!(wire, "/testnet/mycelial/1.0.0/credit");
+        assert_eq!(topics::strip_namespace("testnet", &wire), Some(topics::CREDIT));
+        assert_eq!(topics::strip_namespace("test", &wire), None);
+        assert_eq!(topics::strip_namespace("testnet", topics::CREDIT), None);
+
+        // No namespace leaves topics unchanged
+        assert_eq!(topics::namespaced("", topics::CREDIT), topics::CREDIT);
+        assert_eq!(topics::strip_namespace("", topics::CREDIT), Some(topics::CREDIT));
+    }
+
     #[test]
     fn test_vouch_request_creation() {
         let vouch = VouchRequest::new(
@@ -659,6 +1348,73 @@ mod tests {
         assert_eq!(vouch.message, Some("Great collaborator!".to_string()));
     }
 
+    #[test]
+    fn test_correlation_ids_capped() {
+        let longest = "c".repeat(MAX_CORRELATION_ID_LEN);
+        let vouch = VouchRevoke::new("alice".to_string(), "bob".to_string()).with_correlation_id(Some(longest.clone()));
+        assert_eq!(vouch.correlation_id(), Some(longest.as_str()));
+        let vouch = vouch.with_correlation_id(Some(format!("{}c", longest)));
+        assert_eq!(vouch.correlation_id(), None);
+
+        // Over-long IDs from the network are dropped, not rejected
+        let mut json = serde_json::to_value(VouchRevoke::new("alice".to_string(), "bob".to_string())).unwrap();
+        json["correlation_id"] = serde_json::Value::String(format!("{}c", longest));
+        let received: VouchRevoke = serde_json::from_value(json).unwrap();
+        assert_eq!(received.correlation_id(), None);
+        assert_eq!(received.voucher, "alice");
+    }
+
+    #[test]
+    fn test_action_signature_verification() {
+        use mycelial_core::identity::{Keypair, KeypairExt};
+
+        let keypair = Keypair::generate();
+        let payload = r#"{"type":"transfer_credit","to":"bob","amount":10.0}"#.to_string();
+        let signed = ActionSignature {
+            signer: keypair.did().to_string(),
+            signature: keypair.sign_bytes(&ActionSignature::signed_bytes("n1", 1_000, &payload)).to_hex(),
+            payload,
+            nonce: "n1".to_string(),
+            timestamp: 1_000,
+        };
+        assert!(signed.verify());
+
+        let mut tampered = signed.clone();
+        tampered.payload = tampered.payload.replace("10.0", "1000.0");
+        assert!(!tampered.verify());
+
+        // The nonce and time are covered too
+        let mut renonced = signed.clone();
+        renonced.nonce = "n2".to_string();
+        assert!(!renonced.verify());
+        let mut redated = signed.clone();
+        redated.timestamp = 2_000;
+        assert!(!redated.verify());
+
+        let mut wrong_signer = signed;
+        wrong_signer.signer = Keypair::generate().did().to_string();
+        assert!(!wrong_signer.verify());
+    }
+
+    #[test]
+    fn test_passing_rules() {
+        // 6 yes to 4 no: a majority, but short of two thirds
+        assert!(PassingRule::SimpleMajority.passes(6.0, 4.0));
+        assert!(!PassingRule::Supermajority(2.0 / 3.0).passes(6.0, 4.0));
+        assert!(PassingRule::Supermajority(2.0 / 3.0).passes(7.0, 3.0));
+        assert!(!PassingRule::Unanimous.passes(9.0, 1.0));
+        assert!(PassingRule::Unanimous.passes(1.0, 0.0));
+
+        assert!(PassingRule::Supermajority(0.75).validate().is_ok());
+        assert!(PassingRule::Supermajority(0.5).validate().is_err());
+        assert!(PassingRule::Supermajority(1.5).validate().is_err());
+        assert!(PassingRule::Supermajority(f64::NAN).validate().is_err());
+
+        for rule in [PassingRule::SimpleMajority, PassingRule::Supermajority(0.75), PassingRule::Unanimous] {
+            assert_eq!(PassingRule::parse(&rule.to_storage()), rule);
+        }
+    }
+
     #[test]
     fn test_stake_clamping() {
         let vouch = VouchRequest::new("alice".to_string(), "bob".to_string(), 1.5);
@@ -713,6 +1469,22 @@ mod tests {
         assert_eq!(proposal.threshold, 0.7);
     }
 
+    #[test]
+    fn test_proposal_quorum_voters_optional_on_wire() {
+        let proposal = CreateProposal::new("alice".to_string(), "Fund".to_string(), "Fund it".to_string());
+        let mut json = serde_json::to_value(&proposal).unwrap();
+        assert!(json.get("quorum_voters").is_none());
+
+        // Proposals from nodes without the field derive quorum from the fraction
+        json.as_object_mut().unwrap().remove("quorum_voters");
+        let parsed: CreateProposal = serde_json::from_value(json).unwrap();
+        assert_eq!(parsed.quorum_voters, None);
+
+        let fixed = proposal.with_quorum_voters(4);
+        let parsed: CreateProposal = serde_json::from_value(serde_json::to_value(&fixed).unwrap()).unwrap();
+        assert_eq!(parsed.quorum_voters, Some(4));
+    }
+
     #[test]
     fn test_cast_vote() {
         let proposal_id = Uuid::new_v4();
@@ -782,6 +1554,22 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_transfer_request_ref_is_optional() {
+        let transfer = CreditTransfer::new(Uuid::new_v4(), "alice".to_string(), "bob".to_string(), 5.0);
+        let json = serde_json::to_value(&transfer).expect("serialization failed");
+        assert!(json.get("request_ref").is_none());
+
+        // Transfers from peers that predate request_ref still parse
+        let parsed: CreditTransfer = serde_json::from_value(json).expect("deserialization failed");
+        assert_eq!(parsed.request_ref, None);
+
+        let linked = transfer.with_request_ref("req-1");
+        let json = serde_json::to_string(&linked).expect("serialization failed");
+        let parsed: CreditTransfer = serde_json::from_str(&json).expect("deserialization failed");
+        assert_eq!(parsed.request_ref.as_deref(), Some("req-1"));
+    }
+
     #[test]
     fn test_governance_message_serialization() {
         let msg = GovernanceMessage::CastVote(CastVote::new(
@@ -0,0 +1,34 @@
+-- Governance schema for mycelial-state SQLite database
+-- Version: 002
+--
+-- Timestamps in these tables are epoch milliseconds, matching the
+-- governance protocol and dashboard messages.
+
+-- Proposals table: governance proposals seen locally or via gossip
+CREATE TABLE IF NOT EXISTS proposals (
+    id TEXT PRIMARY KEY,
+    proposer_peer_id TEXT NOT NULL,
+    title TEXT NOT NULL,
+    description TEXT NOT NULL,
+    proposal_type TEXT NOT NULL,
+    status TEXT NOT NULL DEFAULT 'active',
+    quorum INTEGER NOT NULL,
+    deadline INTEGER NOT NULL,
+    created_at INTEGER NOT NULL,
+    updated_at INTEGER NOT NULL DEFAULT (strftime('%s', 'now'))
+);
+
+-- Votes table: one vote per voter per proposal
+CREATE TABLE IF NOT EXISTS votes (
+    proposal_id TEXT NOT NULL,
+    voter_peer_id TEXT NOT NULL,
+    vote TEXT NOT NULL,
+    weight REAL NOT NULL DEFAULT 1.0,
+    timestamp INTEGER NOT NULL,
+    PRIMARY KEY (proposal_id, voter_peer_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_proposals_status ON proposals(status);
+CREATE INDEX IF NOT EXISTS idx_proposals_created ON proposals(created_at);
+CREATE INDEX IF NOT EXISTS idx_votes_voter ON votes(voter_peer_id);
+CREATE INDEX IF NOT EXISTS idx_votes_timestamp ON votes(timestamp);
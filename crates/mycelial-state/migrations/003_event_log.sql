@@ -0,0 +1,24 @@
+-- Event log schema for mycelial-state SQLite database
+-- Version: 003
+--
+-- Dashboard events are appended here so clients can replay history.
+-- Timestamps are epoch milliseconds.
+
+-- Event log: one row per broadcast event, payload stored as JSON
+CREATE TABLE IF NOT EXISTS event_log (
+    seq INTEGER PRIMARY KEY AUTOINCREMENT,
+    event_type TEXT NOT NULL,
+    timestamp INTEGER NOT NULL,
+    payload_json TEXT NOT NULL
+);
+
+-- Peers involved in each logged event
+CREATE TABLE IF NOT EXISTS event_log_peers (
+    seq INTEGER NOT NULL,
+    peer_id TEXT NOT NULL,
+    PRIMARY KEY (seq, peer_id),
+    FOREIGN KEY (seq) REFERENCES event_log(seq) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_event_log_timestamp ON event_log(timestamp);
+CREATE INDEX IF NOT EXISTS idx_event_log_peers_peer ON event_log_peers(peer_id);
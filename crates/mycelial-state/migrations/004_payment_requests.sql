@@ -0,0 +1,18 @@
+-- Payment request schema for mycelial-state SQLite database
+-- Version: 004
+--
+-- Payment requests let a transfer reference the request it settles.
+-- Timestamps are epoch milliseconds.
+
+CREATE TABLE IF NOT EXISTS payment_requests (
+    id TEXT PRIMARY KEY NOT NULL,
+    requester_peer_id TEXT NOT NULL,
+    payer_peer_id TEXT NOT NULL,
+    amount REAL NOT NULL,
+    memo TEXT,
+    created_at INTEGER NOT NULL,
+    -- Transfer that settled the request, NULL while outstanding
+    settled_by TEXT
+);
+
+CREATE INDEX IF NOT EXISTS idx_payment_requests_payer ON payment_requests(payer_peer_id);
@@ -0,0 +1,11 @@
+-- Stats snapshot schema for mycelial-state SQLite database
+-- Version: 005
+--
+-- Periodic samples of node statistics for trend charts.
+-- Timestamps are epoch milliseconds.
+
+CREATE TABLE IF NOT EXISTS stats_snapshots (
+    timestamp INTEGER PRIMARY KEY NOT NULL,
+    peer_count INTEGER NOT NULL,
+    message_count INTEGER NOT NULL
+);
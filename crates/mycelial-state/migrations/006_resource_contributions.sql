@@ -0,0 +1,17 @@
+-- Resource contribution schema for mycelial-state SQLite database
+-- Version: 006
+--
+-- Individual resource contributions, aggregated per resource type into
+-- contributor rankings. Timestamps are epoch milliseconds.
+
+CREATE TABLE IF NOT EXISTS resource_contributions (
+    id TEXT PRIMARY KEY NOT NULL,
+    peer_id TEXT NOT NULL,
+    resource_type TEXT NOT NULL,
+    amount REAL NOT NULL,
+    unit TEXT NOT NULL,
+    timestamp INTEGER NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_resource_contributions_type ON resource_contributions(resource_type);
+CREATE INDEX IF NOT EXISTS idx_resource_contributions_peer ON resource_contributions(peer_id);
@@ -0,0 +1,21 @@
+-- Vouch schema for mycelial-state SQLite database
+-- Version: 007
+--
+-- Vouch requests seen on the network and the vouchee's response.
+-- Status is 'pending' until acknowledged, then 'accepted' or 'rejected'.
+-- Timestamps are epoch milliseconds.
+
+CREATE TABLE IF NOT EXISTS vouches (
+    id TEXT PRIMARY KEY NOT NULL,
+    voucher_peer_id TEXT NOT NULL,
+    vouchee_peer_id TEXT NOT NULL,
+    weight REAL NOT NULL,
+    message TEXT,
+    status TEXT NOT NULL DEFAULT 'pending',
+    created_at INTEGER NOT NULL,
+    responded_at INTEGER
+);
+
+CREATE INDEX IF NOT EXISTS idx_vouches_voucher ON vouches(voucher_peer_id);
+CREATE INDEX IF NOT EXISTS idx_vouches_vouchee ON vouches(vouchee_peer_id);
+CREATE INDEX IF NOT EXISTS idx_vouches_status ON vouches(status);
@@ -0,0 +1,12 @@
+-- Seen markers schema for mycelial-state SQLite database
+-- Version: 008
+--
+-- How far each identity has read in each category (chat, proposals,
+-- vouches), for unread counts. Timestamps are epoch milliseconds.
+
+CREATE TABLE IF NOT EXISTS seen_markers (
+    identity TEXT NOT NULL,
+    category TEXT NOT NULL,
+    up_to INTEGER NOT NULL,
+    PRIMARY KEY (identity, category)
+);
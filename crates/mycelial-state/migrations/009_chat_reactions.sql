@@ -0,0 +1,13 @@
+-- Chat reactions schema for mycelial-state SQLite database
+-- Version: 009
+--
+-- One row per (message, reactor, emoji); reacting again removes the row.
+-- Timestamps are epoch milliseconds.
+
+CREATE TABLE IF NOT EXISTS chat_reactions (
+    message_id TEXT NOT NULL,
+    reactor TEXT NOT NULL,
+    emoji TEXT NOT NULL,
+    created_at INTEGER NOT NULL,
+    PRIMARY KEY (message_id, reactor, emoji)
+);
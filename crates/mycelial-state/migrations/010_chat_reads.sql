@@ -0,0 +1,12 @@
+-- Chat read receipts schema for mycelial-state SQLite database
+-- Version: 010
+--
+-- One row per (message, reader), kept from the first time the reader marked
+-- the message read. Timestamps are epoch milliseconds.
+
+CREATE TABLE IF NOT EXISTS chat_reads (
+    message_id TEXT NOT NULL,
+    reader TEXT NOT NULL,
+    read_at INTEGER NOT NULL,
+    PRIMARY KEY (message_id, reader)
+);
@@ -0,0 +1,454 @@
+//! Credit line persistence
+//!
+//! A credit line lets its debtor draw credit from its creditor up to a
+//! limit. Lines are kept in the `credit_relationships` table, one per
+//! creditor and debtor, under the ID the first line between them was
+//! announced with so transfers and clients can refer to it; a later line
+//! between the same peers replaces the limit. Each transfer drawn on a line
+//! is kept as one of its `credit_transactions`, for the line's history.
+//!
+//! Both tables store times in epoch seconds, so the `created_at` of lines
+//! and transfers keeps second precision.
+
+use sqlx::Row;
+use tracing::debug;
+
+use crate::error::Result;
+use crate::storage::{insert_credit_transaction, SqliteStore};
+
+/// A stored credit line
+#[derive(Debug, Clone, PartialEq)]
+pub struct CreditLineRecord {
+    /// Credit line ID
+    pub id: String,
+    /// Peer extending credit
+    pub creditor: String,
+    /// Peer receiving credit
+    pub debtor: String,
+    /// Maximum credit the debtor may draw
+    pub limit: f64,
+    /// Credit drawn so far
+    pub balance: f64,
+    /// When the line was created (epoch millis)
+    pub created_at: i64,
+}
+
+/// A transfer drawn on a stored credit line
+#[derive(Debug, Clone, PartialEq)]
+pub struct CreditTransferRecord {
+    /// Transfer ID
+    pub id: String,
+    /// Credit line the transfer drew on
+    pub line_id: String,
+    /// Paying peer, the line's debtor
+    pub from: String,
+    /// Receiving peer, the line's creditor
+    pub to: String,
+    /// Amount transferred
+    pub amount: f64,
+    /// Optional memo
+    pub memo: Option<String>,
+    /// When the transfer was made (epoch millis, kept to the second)
+    pub created_at: i64,
+}
+
+/// A recorded transfer in a credit line's history
+#[derive(Debug, Clone, PartialEq)]
+pub struct CreditHistoryEntry {
+    /// The transfer
+    pub transfer: CreditTransferRecord,
+    /// The line's balance once the transfer was drawn
+    pub balance_after: f64,
+}
+
+impl SqliteStore {
+    // ========== Credit Line Operations ==========
+
+    /// Store a credit line, or update the limit of the one between its peers
+    ///
+    /// Relationships reference both peers, so either one not stored yet is
+    /// added as a known peer without a public key.
+    pub async fn insert_credit_line(&self, line: &CreditLineRecord) -> Result<()> {
+        let established = line.created_at / 1000;
+        let mut tx = self.pool().begin().await?;
+
+        for peer_id in [&line.creditor, &line.debtor] {
+            sqlx::query(
+                r#"
+                INSERT INTO peers (peer_id, public_key, first_seen, last_seen)
+                VALUES (?, '', ?, ?)
+                ON CONFLICT(peer_id) DO NOTHING
+                "#,
+            )
+            .bind(peer_id)
+            .bind(established)
+            .bind(established)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        let updated = sqlx::query(
+            r#"
+            UPDATE credit_relationships
+            SET credit_limit = ?, active = 1, updated_at = strftime('%s', 'now')
+            WHERE creditor_peer_id = ? AND debtor_peer_id = ?
+            "#,
+        )
+        .bind(line.limit)
+        .bind(&line.creditor)
+        .bind(&line.debtor)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected()
+            > 0;
+        if !updated {
+            sqlx::query(
+                r#"
+                INSERT INTO credit_relationships (
+                    id, creditor_peer_id, debtor_peer_id, credit_limit, balance,
+                    active, established, last_transaction
+                ) VALUES (?, ?, ?, ?, ?, 1, ?, ?)
+                "#,
+            )
+            .bind(&line.id)
+            .bind(&line.creditor)
+            .bind(&line.debtor)
+            .bind(line.limit)
+            .bind(line.balance)
+            .bind(established)
+            .bind(established)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        tx.commit().await?;
+
+        debug!("Stored credit line: {}", line.id);
+        Ok(())
+    }
+
+    /// List credit lines a peer extended or received, newest first
+    pub async fn list_credit_lines_for(&self, peer_id: &str) -> Result<Vec<CreditLineRecord>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT id, creditor_peer_id, debtor_peer_id, credit_limit, balance, established
+            FROM credit_relationships
+            WHERE creditor_peer_id = ? OR debtor_peer_id = ?
+            ORDER BY established DESC, id ASC
+            "#,
+        )
+        .bind(peer_id)
+        .bind(peer_id)
+        .fetch_all(self.pool())
+        .await?;
+
+        Ok(rows.iter().map(row_to_credit_line).collect())
+    }
+
+    /// Get a credit line by ID
+    pub async fn get_credit_line(&self, id: &str) -> Result<Option<CreditLineRecord>> {
+        let row = sqlx::query(
+            r#"
+            SELECT id, creditor_peer_id, debtor_peer_id, credit_limit, balance, established
+            FROM credit_relationships WHERE id = ?
+            "#,
+        )
+        .bind(id)
+        .fetch_optional(self.pool())
+        .await?;
+
+        Ok(row.as_ref().map(row_to_credit_line))
+    }
+
+    /// The credit line `creditor` extended to `debtor`
+    pub async fn find_credit_line(&self, creditor: &str, debtor: &str) -> Result<Option<CreditLineRecord>> {
+        let row = sqlx::query(
+            r#"
+            SELECT id, creditor_peer_id, debtor_peer_id, credit_limit, balance, established
+            FROM credit_relationships
+            WHERE creditor_peer_id = ? AND debtor_peer_id = ?
+            "#,
+        )
+        .bind(creditor)
+        .bind(debtor)
+        .fetch_optional(self.pool())
+        .await?;
+
+        Ok(row.as_ref().map(row_to_credit_line))
+    }
+
+    /// Record a transfer and draw it on its line's balance
+    ///
+    /// The transfer is kept as one of the line's credit transactions, with
+    /// the balance it left. The draw is checked against the line's limit in
+    /// the same statement, so concurrent transfers can't overdraw it. Returns
+    /// `false`, recording nothing, if the transfer was already recorded, the
+    /// line doesn't exist, the payer and recipient aren't its debtor and
+    /// creditor, or the amount isn't positive or exceeds the credit left.
+    pub async fn record_credit_transfer(&self, transfer: &CreditTransferRecord) -> Result<bool> {
+        let mut tx = self.pool().begin().await?;
+
+        let balance_after: Option<f64> = sqlx::query_scalar(
+            r#"
+            UPDATE credit_relationships
+            SET balance = balance + ?, last_transaction = ?, updated_at = strftime('%s', 'now')
+            WHERE id = ? AND debtor_peer_id = ? AND creditor_peer_id = ?
+              AND ? > 0 AND balance + ? <= credit_limit
+            RETURNING balance
+            "#,
+        )
+        .bind(transfer.amount)
+        .bind(transfer.created_at / 1000)
+        .bind(&transfer.line_id)
+        .bind(&transfer.from)
+        .bind(&transfer.to)
+        .bind(transfer.amount)
+        .bind(transfer.amount)
+        .fetch_optional(&mut *tx)
+        .await?;
+        let Some(balance_after) = balance_after else {
+            return Ok(false);
+        };
+
+        let inserted = insert_credit_transaction(
+            &mut *tx,
+            &transfer.id,
+            &transfer.line_id,
+            transfer.amount,
+            balance_after,
+            transfer.memo.as_deref(),
+            transfer.created_at / 1000,
+        )
+        .await?;
+        if !inserted {
+            // Dropping the transaction rolls back the draw
+            return Ok(false);
+        }
+
+        tx.commit().await?;
+
+        debug!("Recorded transfer {} of {} on credit line {}", transfer.id, transfer.amount, transfer.line_id);
+        Ok(true)
+    }
+
+    /// Undo a recorded transfer, returning its amount to the line
+    ///
+    /// For a transfer that was drawn but couldn't be sent. Returns `false` if
+    /// the transfer isn't recorded.
+    pub async fn revert_credit_transfer(&self, transfer_id: &str) -> Result<bool> {
+        let mut tx = self.pool().begin().await?;
+
+        let row = sqlx::query("DELETE FROM credit_transactions WHERE id = ? RETURNING relationship_id, amount")
+            .bind(transfer_id)
+            .fetch_optional(&mut *tx)
+            .await?;
+        let Some(row) = row else {
+            return Ok(false);
+        };
+        let relationship_id: String = row.get("relationship_id");
+        let amount: f64 = row.get("amount");
+
+        sqlx::query(
+            r#"
+            UPDATE credit_relationships
+            SET balance = balance - ?, updated_at = strftime('%s', 'now')
+            WHERE id = ?
+            "#,
+        )
+        .bind(amount)
+        .bind(&relationship_id)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        debug!("Reverted transfer {} of {} on credit line {}", transfer_id, amount, relationship_id);
+        Ok(true)
+    }
+
+    /// Transfers drawn on a credit line, oldest first, with the balance each left
+    pub async fn list_credit_transfers(&self, line_id: &str) -> Result<Vec<CreditHistoryEntry>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT t.id, t.relationship_id, t.amount, t.balance_after, t.description, t.timestamp,
+                   r.creditor_peer_id, r.debtor_peer_id
+            FROM credit_transactions t
+            JOIN credit_relationships r ON r.id = t.relationship_id
+            WHERE t.relationship_id = ?
+            ORDER BY t.timestamp ASC, t.rowid ASC
+            "#,
+        )
+        .bind(line_id)
+        .fetch_all(self.pool())
+        .await?;
+
+        Ok(rows
+            .iter()
+            .map(|row| CreditHistoryEntry {
+                transfer: CreditTransferRecord {
+                    id: row.get("id"),
+                    line_id: row.get("relationship_id"),
+                    from: row.get("debtor_peer_id"),
+                    to: row.get("creditor_peer_id"),
+                    amount: row.get("amount"),
+                    memo: row.get("description"),
+                    created_at: row.get::<i64, _>("timestamp") * 1000,
+                },
+                balance_after: row.get("balance_after"),
+            })
+            .collect())
+    }
+}
+
+fn row_to_credit_line(row: &sqlx::sqlite::SqliteRow) -> CreditLineRecord {
+    CreditLineRecord {
+        id: row.get("id"),
+        creditor: row.get("creditor_peer_id"),
+        debtor: row.get("debtor_peer_id"),
+        limit: row.get("credit_limit"),
+        balance: row.get("balance"),
+        created_at: row.get::<i64, _>("established") * 1000,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(id: &str, creditor: &str, debtor: &str, created_at: i64) -> CreditLineRecord {
+        CreditLineRecord {
+            id: id.to_string(),
+            creditor: creditor.to_string(),
+            debtor: debtor.to_string(),
+            limit: 100.0,
+            balance: 0.0,
+            created_at,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_list_credit_lines_for_peer() {
+        let store = SqliteStore::new(":memory:").await.unwrap();
+        store.insert_credit_line(&line("l1", "alice", "bob", 1_000)).await.unwrap();
+        store.insert_credit_line(&line("l2", "carol", "alice", 2_000)).await.unwrap();
+        store.insert_credit_line(&line("l3", "bob", "carol", 3_000)).await.unwrap();
+        // Seen again, the line keeps its ID and creation time
+        store.insert_credit_line(&line("l1", "alice", "bob", 9_000)).await.unwrap();
+
+        let lines = store.list_credit_lines_for("alice").await.unwrap();
+        let ids: Vec<_> = lines.iter().map(|l| l.id.as_str()).collect();
+        assert_eq!(ids, vec!["l2", "l1"]);
+        assert_eq!(lines[1].created_at, 1_000);
+    }
+
+    #[tokio::test]
+    async fn test_credit_lines_are_credit_relationships() {
+        let store = SqliteStore::new(":memory:").await.unwrap();
+        store.insert_credit_line(&line("l1", "alice", "bob", 1_000)).await.unwrap();
+        let transfer = CreditTransferRecord {
+            id: "t1".to_string(),
+            line_id: "l1".to_string(),
+            from: "bob".to_string(),
+            to: "alice".to_string(),
+            amount: 30.0,
+            memo: None,
+            created_at: 1_500,
+        };
+        assert!(store.record_credit_transfer(&transfer).await.unwrap());
+
+        // A later line between the same peers raises the limit of the first
+        let mut raised = line("l2", "alice", "bob", 2_000);
+        raised.limit = 250.0;
+        store.insert_credit_line(&raised).await.unwrap();
+        let found = store.find_credit_line("alice", "bob").await.unwrap().unwrap();
+        assert_eq!(found.id, "l1");
+        assert_eq!(found.limit, 250.0);
+        assert!((found.balance - 30.0).abs() < 1e-9);
+        assert!(store.get_credit_line("l2").await.unwrap().is_none());
+
+        let relationship = store.get_credit_relationship("l1").await.unwrap().unwrap();
+        assert_eq!(relationship.creditor.as_str(), "alice");
+        assert_eq!(relationship.credit_limit, 250.0);
+        assert!(relationship.active);
+        // Both parties are known peers now
+        assert!(store.get_peer("bob").await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_transfers_recorded_once_per_line() {
+        let store = SqliteStore::new(":memory:").await.unwrap();
+        store.insert_credit_line(&line("l1", "alice", "bob", 1_000)).await.unwrap();
+        let transfer = |id: &str, from: &str, amount: f64, created_at: i64| CreditTransferRecord {
+            id: id.to_string(),
+            line_id: "l1".to_string(),
+            from: from.to_string(),
+            to: "alice".to_string(),
+            amount,
+            memo: None,
+            created_at,
+        };
+
+        assert!(store.record_credit_transfer(&transfer("t2", "bob", 5.0, 20_000)).await.unwrap());
+        assert!(store.record_credit_transfer(&transfer("t1", "bob", 10.0, 10_000)).await.unwrap());
+        // Seen again, from a non-debtor, to a non-creditor, or on a missing line
+        assert!(!store.record_credit_transfer(&transfer("t1", "bob", 10.0, 10_000)).await.unwrap());
+        assert!(!store.record_credit_transfer(&transfer("t3", "carol", 7.0, 30_000)).await.unwrap());
+        let mut misdirected = transfer("t4", "bob", 7.0, 40_000);
+        misdirected.to = "carol".to_string();
+        assert!(!store.record_credit_transfer(&misdirected).await.unwrap());
+        let mut stray = transfer("t5", "bob", 7.0, 50_000);
+        stray.line_id = "missing".to_string();
+        assert!(!store.record_credit_transfer(&stray).await.unwrap());
+
+        // Listed by time, each with the balance it left when it was drawn
+        let history = store.list_credit_transfers("l1").await.unwrap();
+        let summary: Vec<_> = history
+            .iter()
+            .map(|entry| (entry.transfer.id.as_str(), entry.transfer.created_at, entry.balance_after))
+            .collect();
+        assert_eq!(summary, vec![("t1", 10_000, 15.0), ("t2", 20_000, 5.0)]);
+        assert_eq!(history[0].transfer.from, "bob");
+        assert_eq!(history[0].transfer.to, "alice");
+        assert!(store.list_credit_transfers("missing").await.unwrap().is_empty());
+        let line = store.get_credit_line("l1").await.unwrap().unwrap();
+        assert!((line.balance - 15.0).abs() < 1e-9);
+        assert!(store.get_credit_line("missing").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_transfers_limited_by_credit_line() {
+        let store = SqliteStore::new(":memory:").await.unwrap();
+        store.insert_credit_line(&line("l1", "alice", "bob", 1_000)).await.unwrap();
+        let transfer = |id: &str, amount: f64| CreditTransferRecord {
+            id: id.to_string(),
+            line_id: "l1".to_string(),
+            from: "bob".to_string(),
+            to: "alice".to_string(),
+            amount,
+            memo: None,
+            created_at: 2_000,
+        };
+
+        assert!(store.record_credit_transfer(&transfer("t1", 60.0)).await.unwrap());
+        // Over the 40 left, or not a positive amount
+        assert!(!store.record_credit_transfer(&transfer("t2", 40.5)).await.unwrap());
+        assert!(!store.record_credit_transfer(&transfer("t3", -10.0)).await.unwrap());
+        assert!(!store.record_credit_transfer(&transfer("t4", 0.0)).await.unwrap());
+        assert!(store.record_credit_transfer(&transfer("t5", 40.0)).await.unwrap());
+
+        // Reverting returns the credit for another transfer
+        assert!(store.revert_credit_transfer("t5").await.unwrap());
+        assert!(!store.revert_credit_transfer("t5").await.unwrap());
+        let line = store.get_credit_line("l1").await.unwrap().unwrap();
+        assert!((line.balance - 60.0).abs() < 1e-9);
+        let ids: Vec<_> = store
+            .list_credit_transfers("l1")
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.transfer.id)
+            .collect();
+        assert_eq!(ids, vec!["t1"]);
+        assert!(store.record_credit_transfer(&transfer("t6", 25.0)).await.unwrap());
+    }
+}
@@ -0,0 +1,308 @@
+//! Event log persistence
+//!
+//! Dashboard events are appended to a log along with the peers they involve,
+//! so clients can replay history (for example, a single peer's timeline)
+//! after the live broadcast has passed.
+
+use sqlx::Row;
+use tracing::debug;
+
+use crate::error::Result;
+use crate::storage::SqliteStore;
+
+/// An event read back from the log
+#[derive(Debug, Clone, PartialEq)]
+pub struct LoggedEvent {
+    /// Monotonic sequence number assigned on append
+    pub seq: i64,
+    /// Event type tag
+    pub event_type: String,
+    /// When the event was logged (epoch millis)
+    pub timestamp: i64,
+    /// Event payload as JSON
+    pub payload_json: String,
+}
+
+impl SqliteStore {
+    // ========== Event Log Operations ==========
+
+    /// Append an event to the log, returning its sequence number
+    pub async fn append_event(
+        &self,
+        event_type: &str,
+        timestamp: i64,
+        payload_json: &str,
+        peers: &[String],
+    ) -> Result<i64> {
+        let mut tx = self.pool().begin().await?;
+
+        let result = sqlx::query(
+            "INSERT INTO event_log (event_type, timestamp, payload_json) VALUES (?, ?, ?)",
+        )
+        .bind(event_type)
+        .bind(timestamp)
+        .bind(payload_json)
+        .execute(&mut *tx)
+        .await?;
+        let seq = result.last_insert_rowid();
+
+        for peer_id in peers {
+            sqlx::query(
+                "INSERT INTO event_log_peers (seq, peer_id) VALUES (?, ?) ON CONFLICT DO NOTHING",
+            )
+            .bind(seq)
+            .bind(peer_id)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        tx.commit().await?;
+
+        debug!("Logged {} event: {}", event_type, seq);
+        Ok(seq)
+    }
+
+    /// List logged events involving a peer, in timestamp order
+    ///
+    /// Returns events strictly after the `(since, after_seq)` cursor: events
+    /// with a later timestamp, or the same timestamp and a higher sequence.
+    pub async fn list_events_for_peer(
+        &self,
+        peer_id: &str,
+        since: i64,
+        after_seq: i64,
+        limit: i64,
+    ) -> Result<Vec<LoggedEvent>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT e.seq, e.event_type, e.timestamp, e.payload_json
+            FROM event_log e JOIN event_log_peers p ON p.seq = e.seq
+            WHERE p.peer_id = ?
+              AND (e.timestamp > ? OR (e.timestamp = ? AND e.seq > ?))
+            ORDER BY e.timestamp ASC, e.seq ASC
+            LIMIT ?
+            "#,
+        )
+        .bind(peer_id)
+        .bind(since)
+        .bind(since)
+        .bind(after_seq)
+        .bind(limit)
+        .fetch_all(self.pool())
+        .await?;
+
+        Ok(rows.iter().map(row_to_event).collect())
+    }
+
+    /// List logged events with a sequence above `after_seq`, in sequence order
+    pub async fn list_events_after(&self, after_seq: i64, limit: i64) -> Result<Vec<LoggedEvent>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT seq, event_type, timestamp, payload_json
+            FROM event_log WHERE seq > ?
+            ORDER BY seq ASC
+            LIMIT ?
+            "#,
+        )
+        .bind(after_seq)
+        .bind(limit)
+        .fetch_all(self.pool())
+        .await?;
+
+        Ok(rows.iter().map(row_to_event).collect())
+    }
+
+    /// List logged chat messages before the `(timestamp, seq)` cursor, newest first
+    ///
+    /// Messages are ordered by their own timestamps, then by sequence, so
+    /// paging from the last message returned neither skips nor repeats ones
+    /// sent in the same millisecond. Direct messages are only included when
+    /// `viewer` sent or received them.
+    pub async fn list_messages(
+        &self,
+        viewer: Option<&str>,
+        before: Option<(i64, i64)>,
+        limit: i64,
+    ) -> Result<Vec<LoggedEvent>> {
+        let (before_timestamp, before_seq) = before.unwrap_or((i64::MAX, i64::MAX));
+        let rows = sqlx::query(
+            r#"
+            SELECT seq, event_type, timestamp, payload_json
+            FROM event_log
+            WHERE event_type = 'chat_message'
+              AND (json_extract(payload_json, '$.timestamp') < ?
+                   OR (json_extract(payload_json, '$.timestamp') = ? AND seq < ?))
+              AND (json_extract(payload_json, '$.to') IS NULL
+                   OR json_extract(payload_json, '$.to') = ?
+                   OR json_extract(payload_json, '$.from') = ?)
+            ORDER BY json_extract(payload_json, '$.timestamp') DESC, seq DESC
+            LIMIT ?
+            "#,
+        )
+        .bind(before_timestamp)
+        .bind(before_timestamp)
+        .bind(before_seq)
+        .bind(viewer)
+        .bind(viewer)
+        .bind(limit)
+        .fetch_all(self.pool())
+        .await?;
+
+        Ok(rows.iter().map(row_to_event).collect())
+    }
+
+    /// Find a logged chat message by its message ID
+    pub async fn find_message(&self, message_id: &str) -> Result<Option<LoggedEvent>> {
+        let row = sqlx::query(
+            r#"
+            SELECT seq, event_type, timestamp, payload_json
+            FROM event_log
+            WHERE event_type = 'chat_message' AND json_extract(payload_json, '$.id') = ?
+            ORDER BY seq ASC
+            LIMIT 1
+            "#,
+        )
+        .bind(message_id)
+        .fetch_optional(self.pool())
+        .await?;
+
+        Ok(row.as_ref().map(row_to_event))
+    }
+
+    /// Replace a logged chat message's content, returning whether it was
+    /// found and not deleted
+    pub async fn edit_message(&self, message_id: &str, content: &str, edited_at: i64) -> Result<bool> {
+        let result = sqlx::query(
+            r#"
+            UPDATE event_log
+            SET payload_json = json_set(payload_json, '$.content', ?, '$.edited_at', ?)
+            WHERE event_type = 'chat_message' AND json_extract(payload_json, '$.id') = ?
+              AND json_extract(payload_json, '$.deleted') IS NULL
+            "#,
+        )
+        .bind(content)
+        .bind(edited_at)
+        .bind(message_id)
+        .execute(self.pool())
+        .await?;
+
+        debug!("Edited chat message {}", message_id);
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Replace a logged chat message with a tombstone, returning whether it was found
+    ///
+    /// The entry stays in history so clients can show where it was, but its
+    /// content is discarded.
+    pub async fn delete_message(&self, message_id: &str, deleted_at: i64) -> Result<bool> {
+        let result = sqlx::query(
+            r#"
+            UPDATE event_log
+            SET payload_json = json_set(payload_json, '$.content', '', '$.deleted', json('true'), '$.edited_at', ?)
+            WHERE event_type = 'chat_message' AND json_extract(payload_json, '$.id') = ?
+            "#,
+        )
+        .bind(deleted_at)
+        .bind(message_id)
+        .execute(self.pool())
+        .await?;
+
+        debug!("Deleted chat message {}", message_id);
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Sequence number of the most recently logged event, or 0 if the log is empty
+    pub async fn latest_event_seq(&self) -> Result<i64> {
+        let seq: i64 = sqlx::query("SELECT COALESCE(MAX(seq), 0) as seq FROM event_log")
+            .fetch_one(self.pool())
+            .await?
+            .get("seq");
+        Ok(seq)
+    }
+}
+
+fn row_to_event(row: &sqlx::sqlite::SqliteRow) -> LoggedEvent {
+    LoggedEvent {
+        seq: row.get("seq"),
+        event_type: row.get("event_type"),
+        timestamp: row.get("timestamp"),
+        payload_json: row.get("payload_json"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_events_for_peer() {
+        let store = SqliteStore::new(":memory:").await.unwrap();
+
+        let alice = vec!["alice".to_string()];
+        let both = vec!["alice".to_string(), "bob".to_string()];
+        store.append_event("a", 300, "{}", &alice).await.unwrap();
+        store.append_event("b", 100, "{}", &both).await.unwrap();
+        store.append_event("c", 200, "{}", &["bob".to_string()]).await.unwrap();
+
+        let events = store.list_events_for_peer("alice", 0, 0, 10).await.unwrap();
+        let types: Vec<_> = events.iter().map(|e| e.event_type.as_str()).collect();
+        assert_eq!(types, vec!["b", "a"]);
+
+        // Cursor excludes everything up to and including the first event
+        let first = &events[0];
+        let events = store
+            .list_events_for_peer("alice", first.timestamp, first.seq, 10)
+            .await
+            .unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].event_type, "a");
+    }
+
+    #[tokio::test]
+    async fn test_message_pages_split_within_a_millisecond() {
+        let store = SqliteStore::new(":memory:").await.unwrap();
+        for id in ["m1", "m2", "m3"] {
+            let payload = format!(r#"{{"type":"chat_message","id":"{}","from":"alice","content":"hi","timestamp":7}}"#, id);
+            store.append_event("chat_message", 7, &payload, &["alice".to_string()]).await.unwrap();
+        }
+
+        let first = store.list_messages(None, None, 2).await.unwrap();
+        assert_eq!(first.len(), 2);
+        let last = &first[1];
+        let rest = store.list_messages(None, Some((last.timestamp, last.seq)), 2).await.unwrap();
+        assert_eq!(rest.len(), 1);
+        let mut seqs: Vec<_> = first.iter().chain(&rest).map(|e| e.seq).collect();
+        seqs.dedup();
+        assert_eq!(seqs.len(), 3);
+
+        // A timestamp alone still excludes that millisecond entirely
+        assert!(store.list_messages(None, Some((7, i64::MIN)), 10).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_edit_and_delete_message() {
+        let store = SqliteStore::new(":memory:").await.unwrap();
+        let payload = r#"{"type":"chat_message","id":"m1","from":"alice","content":"helo","timestamp":1}"#;
+        store.append_event("chat_message", 1, payload, &["alice".to_string()]).await.unwrap();
+
+        assert!(store.edit_message("m1", "hello", 5).await.unwrap());
+        assert!(!store.edit_message("missing", "hello", 5).await.unwrap());
+        let edited: serde_json::Value =
+            serde_json::from_str(&store.find_message("m1").await.unwrap().unwrap().payload_json).unwrap();
+        assert_eq!(edited["content"], "hello");
+        assert_eq!(edited["edited_at"], 5);
+
+        assert!(store.delete_message("m1", 9).await.unwrap());
+        let messages = store.list_messages(None, None, 10).await.unwrap();
+        let deleted: serde_json::Value = serde_json::from_str(&messages[0].payload_json).unwrap();
+        assert_eq!(deleted["content"], "");
+        assert_eq!(deleted["deleted"], true);
+        assert!(store.find_message("missing").await.unwrap().is_none());
+
+        // A tombstone can't be edited back to life
+        assert!(!store.edit_message("m1", "resurrected", 12).await.unwrap());
+        let messages = store.list_messages(None, None, 10).await.unwrap();
+        let deleted: serde_json::Value = serde_json::from_str(&messages[0].payload_json).unwrap();
+        assert_eq!(deleted["content"], "");
+    }
+}
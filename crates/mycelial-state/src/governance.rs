@@ -0,0 +1,1060 @@
+//! Governance persistence
+//!
+//! This module stores governance proposals and votes so that tallies and
+//! participation metrics can be derived from what this node has observed,
+//! whether the proposal was created locally or received over gossip.
+
+use mycelial_protocol::{PassingRule, QuorumMode};
+use sqlx::Row;
+use std::collections::{BTreeMap, HashMap};
+use tracing::debug;
+
+use crate::error::Result;
+use crate::storage::SqliteStore;
+
+/// A stored governance proposal
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProposalRecord {
+    /// Proposal ID
+    pub id: String,
+    /// Peer that created the proposal
+    pub proposer: String,
+    /// Proposal title
+    pub title: String,
+    /// Proposal description
+    pub description: String,
+    /// Proposal type (text, parameter_change, treasury_spend, ...)
+    pub proposal_type: String,
+    /// Parameters of the proposal type, e.g. `recipient` and `amount` for a
+    /// treasury spend
+    pub parameters: BTreeMap<String, String>,
+    /// Current status (active, passed, rejected, ...)
+    pub status: String,
+    /// Number of voters required for the result to count, as computed
+    /// against the eligible set when the proposal was created
+    pub quorum: u32,
+    /// Quorum as a fraction of eligible voters (0.0-1.0)
+    pub quorum_fraction: f64,
+    /// Whether `quorum` is fixed or recomputed as eligibility changes
+    pub quorum_mode: QuorumMode,
+    /// How the votes decide whether the proposal passes
+    pub passing_rule: PassingRule,
+    /// Voting deadline (epoch millis)
+    pub deadline: i64,
+    /// When the proposal was created (epoch millis)
+    pub created_at: i64,
+    /// Amendment version, starting at 1 and incremented by each amendment
+    pub version: u32,
+}
+
+/// A stored vote on a proposal
+#[derive(Debug, Clone, PartialEq)]
+pub struct VoteRecord {
+    /// Proposal being voted on
+    pub proposal_id: String,
+    /// Voting peer
+    pub voter: String,
+    /// Vote value (yes, no, abstain)
+    pub vote: String,
+    /// Voting weight
+    pub weight: f64,
+    /// When the vote was cast (epoch millis)
+    pub timestamp: i64,
+}
+
+/// Vote totals with the combined weight of low-reputation voters capped
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CappedTally {
+    /// Totals after capping
+    pub tally: VoteTally,
+    /// Voters whose reputation is below the floor
+    pub low_reputation_voters: usize,
+    /// Largest number of low-reputation voters casting the same vote
+    pub largest_low_reputation_bloc: usize,
+}
+
+/// Tally votes, limiting what identities below a reputation floor can add
+///
+/// Each vote is paired with its voter's reputation. Votes at or above
+/// `floor` count in full; the combined weight of the rest is scaled down
+/// proportionally so it never exceeds `cap`, so spinning up many fresh
+/// identities can't outweigh established voters.
+pub fn cap_low_reputation_weight(votes: &[(VoteRecord, f64)], floor: f64, cap: f64) -> CappedTally {
+    let groups = votes
+        .iter()
+        .map(|(vote, reputation)| (vote.vote.as_str(), *reputation < floor, vote.weight, 1));
+    cap_grouped_votes(groups, cap)
+}
+
+/// Slot of a vote value in per-value arrays: yes, no, abstain
+fn vote_slot(vote: &str) -> usize {
+    match vote {
+        "yes" => 0,
+        "no" => 1,
+        _ => 2,
+    }
+}
+
+/// [`cap_low_reputation_weight`] over votes already grouped by value and
+/// whether the voters are below the floor, as `(vote, low, weight, voters)`
+fn cap_grouped_votes<'a>(groups: impl IntoIterator<Item = (&'a str, bool, f64, usize)>, cap: f64) -> CappedTally {
+    let mut result = CappedTally::default();
+    // yes, no, abstain
+    let mut low_weight = [0.0f64; 3];
+    let mut low_count = [0usize; 3];
+
+    for (vote, low, weight, voters) in groups {
+        let slot = vote_slot(vote);
+        if low {
+            low_weight[slot] += weight;
+            low_count[slot] += voters;
+            result.low_reputation_voters += voters;
+        } else {
+            match slot {
+                0 => result.tally.yes += weight,
+                1 => result.tally.no += weight,
+                _ => result.tally.abstain += weight,
+            }
+        }
+        result.tally.voters += voters;
+    }
+
+    let low_total: f64 = low_weight.iter().sum();
+    let scale = if low_total > cap.max(0.0) { cap.max(0.0) / low_total } else { 1.0 };
+    result.tally.yes += low_weight[0] * scale;
+    result.tally.no += low_weight[1] * scale;
+    result.tally.abstain += low_weight[2] * scale;
+    result.largest_low_reputation_bloc = low_count.into_iter().max().unwrap_or(0);
+    result
+}
+
+/// Aggregate governance participation metrics
+#[derive(Debug, Clone, PartialEq)]
+pub struct GovernanceStats {
+    /// Proposals still open for voting
+    pub active_proposals: usize,
+    /// Mean turnout (voters / eligible) across proposals in the window
+    pub avg_turnout: f64,
+    /// Distinct peers that voted within the window
+    pub participating_peers: usize,
+    /// Number of peers eligible to vote
+    pub total_peers: usize,
+}
+
+impl ProposalRecord {
+    /// Number of voters needed to reach quorum given the current eligible set
+    ///
+    /// `Snapshot` proposals keep the count fixed at creation; `Dynamic`
+    /// proposals recompute it from `quorum_fraction` and `eligible_now`.
+    pub fn required_voters(&self, eligible_now: usize) -> u32 {
+        match self.quorum_mode {
+            QuorumMode::Snapshot => self.quorum,
+            QuorumMode::Dynamic => required_voters(self.quorum_fraction, eligible_now),
+        }
+    }
+
+    /// Final status of the proposal once voting closes: `"passed"` if quorum
+    /// was reached and the votes satisfy its passing rule, else `"rejected"`
+    pub fn outcome(&self, tally: &VoteTally, eligible_now: usize) -> &'static str {
+        let quorum_met = tally.voters as u32 >= self.required_voters(eligible_now);
+        if quorum_met && self.passing_rule.passes(tally.yes, tally.no) {
+            "passed"
+        } else {
+            "rejected"
+        }
+    }
+
+    /// Whether votes may still be cast at `now`: the proposal is active and
+    /// its deadline hasn't passed
+    pub fn accepts_votes(&self, now: i64) -> bool {
+        self.status == "active" && now < self.deadline
+    }
+
+    /// Status as of `now`: the stored status once closed, the outcome once
+    /// the deadline has passed, and otherwise `"active"`
+    pub fn current_status(&self, tally: &VoteTally, eligible_now: usize, now: i64) -> &str {
+        if self.status != "active" {
+            &self.status
+        } else if now >= self.deadline {
+            self.outcome(tally, eligible_now)
+        } else {
+            "active"
+        }
+    }
+}
+
+/// Voters needed for `fraction` of `eligible` to have voted, at least one
+pub fn required_voters(fraction: f64, eligible: usize) -> u32 {
+    ((fraction.clamp(0.0, 1.0) * eligible as f64).ceil() as u32).max(1)
+}
+
+/// Quorum of a proposal received from a peer
+///
+/// The proposer's fixed voter count is kept when it is between one and
+/// `eligible`; otherwise quorum is `fraction` of the voters eligible here.
+pub fn received_quorum(voters: Option<u32>, fraction: f64, eligible: usize) -> u32 {
+    voters
+        .filter(|&voters| voters >= 1 && voters as usize <= eligible)
+        .unwrap_or_else(|| required_voters(fraction, eligible))
+}
+
+/// Weighted vote totals for a single proposal
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct VoteTally {
+    /// Total weight of yes votes
+    pub yes: f64,
+    /// Total weight of no votes
+    pub no: f64,
+    /// Total weight of abstentions
+    pub abstain: f64,
+    /// Number of distinct voters
+    pub voters: usize,
+}
+
+/// Turnout of a single proposal: voters / eligible, clamped to 1.0
+///
+/// Returns 0.0 when nobody is eligible.
+pub fn turnout(voters: usize, eligible: usize) -> f64 {
+    if eligible == 0 {
+        return 0.0;
+    }
+    (voters as f64 / eligible as f64).min(1.0)
+}
+
+impl SqliteStore {
+    // ========== Governance Operations ==========
+
+    /// Store or update a proposal
+    ///
+    /// An update carrying an older amendment version than the stored one is
+    /// ignored, so a re-delivered original never undoes an amendment. An
+    /// update never changes a stored proposal's status either: a proposal
+    /// re-delivered as active after it closed stays closed, and statuses only
+    /// move through [`close_proposal`](Self::close_proposal).
+    pub async fn upsert_proposal(&self, proposal: &ProposalRecord) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO proposals (
+                id, proposer_peer_id, title, description, proposal_type, parameters,
+                status, quorum, quorum_fraction, quorum_mode, passing_rule, deadline, created_at, version
+            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            ON CONFLICT(id) DO UPDATE SET
+                title = excluded.title,
+                description = excluded.description,
+                proposal_type = excluded.proposal_type,
+                parameters = excluded.parameters,
+                quorum = excluded.quorum,
+                quorum_fraction = excluded.quorum_fraction,
+                quorum_mode = excluded.quorum_mode,
+                passing_rule = excluded.passing_rule,
+                deadline = excluded.deadline,
+                version = excluded.version,
+                updated_at = strftime('%s', 'now')
+            WHERE excluded.version >= proposals.version
+            "#,
+        )
+        .bind(&proposal.id)
+        .bind(&proposal.proposer)
+        .bind(&proposal.title)
+        .bind(&proposal.description)
+        .bind(&proposal.proposal_type)
+        .bind(serde_json::to_string(&proposal.parameters)?)
+        .bind(&proposal.status)
+        .bind(proposal.quorum as i64)
+        .bind(proposal.quorum_fraction)
+        .bind(proposal.quorum_mode.as_str())
+        .bind(proposal.passing_rule.to_storage())
+        .bind(proposal.deadline)
+        .bind(proposal.created_at)
+        .bind(proposal.version as i64)
+        .execute(self.pool())
+        .await?;
+
+        debug!("Upserted proposal: {}", proposal.id);
+        Ok(())
+    }
+
+    /// Store a proposal unless one with the same ID is already stored
+    ///
+    /// Used for proposals received from other peers: a re-sent proposal never
+    /// replaces the stored one, whose terms only change through amendments.
+    /// Returns whether the proposal was new.
+    pub async fn insert_proposal(&self, proposal: &ProposalRecord) -> Result<bool> {
+        let result = sqlx::query(
+            r#"
+            INSERT INTO proposals (
+                id, proposer_peer_id, title, description, proposal_type, parameters,
+                status, quorum, quorum_fraction, quorum_mode, passing_rule, deadline, created_at, version
+            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            ON CONFLICT(id) DO NOTHING
+            "#,
+        )
+        .bind(&proposal.id)
+        .bind(&proposal.proposer)
+        .bind(&proposal.title)
+        .bind(&proposal.description)
+        .bind(&proposal.proposal_type)
+        .bind(serde_json::to_string(&proposal.parameters)?)
+        .bind(&proposal.status)
+        .bind(proposal.quorum as i64)
+        .bind(proposal.quorum_fraction)
+        .bind(proposal.quorum_mode.as_str())
+        .bind(proposal.passing_rule.to_storage())
+        .bind(proposal.deadline)
+        .bind(proposal.created_at)
+        .bind(proposal.version as i64)
+        .execute(self.pool())
+        .await?;
+
+        let inserted = result.rows_affected() > 0;
+        if inserted {
+            debug!("Inserted proposal: {}", proposal.id);
+        }
+        Ok(inserted)
+    }
+
+    /// Get a proposal by ID
+    pub async fn get_proposal(&self, id: &str) -> Result<Option<ProposalRecord>> {
+        let row = sqlx::query(
+            r#"
+            SELECT id, proposer_peer_id, title, description, proposal_type, parameters,
+                   status, quorum, quorum_fraction, quorum_mode, passing_rule, deadline, created_at, version
+            FROM proposals WHERE id = ?
+            "#,
+        )
+        .bind(id)
+        .fetch_optional(self.pool())
+        .await?;
+
+        Ok(row.as_ref().map(row_to_proposal))
+    }
+
+    /// List all proposals, newest first
+    pub async fn list_proposals(&self) -> Result<Vec<ProposalRecord>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT id, proposer_peer_id, title, description, proposal_type, parameters,
+                   status, quorum, quorum_fraction, quorum_mode, passing_rule, deadline, created_at, version
+            FROM proposals ORDER BY created_at DESC
+            "#,
+        )
+        .fetch_all(self.pool())
+        .await?;
+
+        Ok(rows.iter().map(row_to_proposal).collect())
+    }
+
+    /// Amend a proposal's title and description
+    ///
+    /// Amendments are only accepted before the first vote is cast, and only
+    /// if `version` is newer than the stored version. Returns whether the
+    /// amendment was applied.
+    pub async fn amend_proposal(
+        &self,
+        id: &str,
+        title: &str,
+        description: &str,
+        version: u32,
+    ) -> Result<bool> {
+        let result = sqlx::query(
+            r#"
+            UPDATE proposals SET
+                title = ?, description = ?, version = ?,
+                updated_at = strftime('%s', 'now')
+            WHERE id = ? AND version < ?
+              AND NOT EXISTS (SELECT 1 FROM votes WHERE proposal_id = ?)
+            "#,
+        )
+        .bind(title)
+        .bind(description)
+        .bind(version as i64)
+        .bind(id)
+        .bind(version as i64)
+        .bind(id)
+        .execute(self.pool())
+        .await?;
+
+        let applied = result.rows_affected() > 0;
+        if applied {
+            debug!("Amended proposal {} to version {}", id, version);
+        }
+        Ok(applied)
+    }
+
+    /// Record that this node created a proposal itself
+    ///
+    /// Proposals stored from gossip or sync never carry this mark, so only
+    /// proposals made through this node are ever executed by it.
+    pub async fn mark_proposal_local(&self, id: &str) -> Result<()> {
+        sqlx::query("UPDATE proposals SET local_origin = 1 WHERE id = ?")
+            .bind(id)
+            .execute(self.pool())
+            .await?;
+        Ok(())
+    }
+
+    /// Whether this node created a proposal itself
+    pub async fn is_local_proposal(&self, id: &str) -> Result<bool> {
+        let row = sqlx::query("SELECT local_origin FROM proposals WHERE id = ?")
+            .bind(id)
+            .fetch_optional(self.pool())
+            .await?;
+        Ok(row.is_some_and(|row| row.get::<i64, _>("local_origin") != 0))
+    }
+
+    /// Move an active proposal to a terminal status
+    ///
+    /// Returns `false` if the proposal is unknown or already closed, so each
+    /// proposal is closed at most once.
+    pub async fn close_proposal(&self, id: &str, status: &str) -> Result<bool> {
+        let result = sqlx::query(
+            r#"
+            UPDATE proposals SET status = ?, updated_at = strftime('%s', 'now')
+            WHERE id = ? AND status = 'active'
+            "#,
+        )
+        .bind(status)
+        .bind(id)
+        .execute(self.pool())
+        .await?;
+
+        let closed = result.rows_affected() > 0;
+        if closed {
+            debug!("Closed proposal {} as {}", id, status);
+        }
+        Ok(closed)
+    }
+
+    /// Record a vote, replacing any earlier vote by the same voter
+    pub async fn record_vote(&self, vote: &VoteRecord) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO votes (proposal_id, voter_peer_id, vote, weight, timestamp)
+            VALUES (?, ?, ?, ?, ?)
+            ON CONFLICT(proposal_id, voter_peer_id) DO UPDATE SET
+                vote = excluded.vote,
+                weight = excluded.weight,
+                timestamp = excluded.timestamp
+            "#,
+        )
+        .bind(&vote.proposal_id)
+        .bind(&vote.voter)
+        .bind(&vote.vote)
+        .bind(vote.weight)
+        .bind(vote.timestamp)
+        .execute(self.pool())
+        .await?;
+
+        debug!("Recorded vote by {} on {}", vote.voter, vote.proposal_id);
+        Ok(())
+    }
+
+    /// Get the vote a peer has cast on a proposal, if any
+    pub async fn get_vote(&self, proposal_id: &str, voter: &str) -> Result<Option<VoteRecord>> {
+        let row = sqlx::query(
+            r#"
+            SELECT proposal_id, voter_peer_id, vote, weight, timestamp
+            FROM votes WHERE proposal_id = ? AND voter_peer_id = ?
+            "#,
+        )
+        .bind(proposal_id)
+        .bind(voter)
+        .fetch_optional(self.pool())
+        .await?;
+
+        Ok(row.as_ref().map(row_to_vote))
+    }
+
+    /// List votes cast on a proposal
+    pub async fn list_votes(&self, proposal_id: &str) -> Result<Vec<VoteRecord>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT proposal_id, voter_peer_id, vote, weight, timestamp
+            FROM votes WHERE proposal_id = ?
+            ORDER BY timestamp ASC
+            "#,
+        )
+        .bind(proposal_id)
+        .fetch_all(self.pool())
+        .await?;
+
+        Ok(rows.iter().map(row_to_vote).collect())
+    }
+
+    /// Sum the votes cast on a proposal by weight
+    pub async fn tally_votes(&self, proposal_id: &str) -> Result<VoteTally> {
+        let rows = sqlx::query(
+            r#"
+            SELECT vote, COALESCE(SUM(weight), 0.0) as total, COUNT(*) as voters
+            FROM votes WHERE proposal_id = ?
+            GROUP BY vote
+            "#,
+        )
+        .bind(proposal_id)
+        .fetch_all(self.pool())
+        .await?;
+
+        let mut tally = VoteTally::default();
+        for row in &rows {
+            let total: f64 = row.get("total");
+            let voters: i64 = row.get("voters");
+            match row.get::<String, _>("vote").as_str() {
+                "yes" => tally.yes += total,
+                "no" => tally.no += total,
+                _ => tally.abstain += total,
+            }
+            tally.voters += voters as usize;
+        }
+        Ok(tally)
+    }
+
+    /// Sum the votes on every proposal by weight, in one grouped query
+    ///
+    /// Proposals nobody has voted on are absent from the result.
+    pub async fn tally_all_votes(&self) -> Result<HashMap<String, VoteTally>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT proposal_id, vote, COALESCE(SUM(weight), 0.0) as total, COUNT(*) as voters
+            FROM votes
+            GROUP BY proposal_id, vote
+            "#,
+        )
+        .fetch_all(self.pool())
+        .await?;
+
+        let mut tallies: HashMap<String, VoteTally> = HashMap::new();
+        for row in &rows {
+            let tally = tallies.entry(row.get("proposal_id")).or_default();
+            let total: f64 = row.get("total");
+            let voters: i64 = row.get("voters");
+            match vote_slot(row.get::<String, _>("vote").as_str()) {
+                0 => tally.yes += total,
+                1 => tally.no += total,
+                _ => tally.abstain += total,
+            }
+            tally.voters += voters as usize;
+        }
+        Ok(tallies)
+    }
+
+    /// [`tally_votes_capped`](Self::tally_votes_capped) for every proposal,
+    /// in one grouped query
+    ///
+    /// Proposals nobody has voted on are absent from the result.
+    pub async fn tally_all_votes_capped(&self, floor: f64, cap: f64) -> Result<HashMap<String, CappedTally>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT v.proposal_id, v.vote,
+                   COALESCE(p.reputation_score, 0.5) < ? as low,
+                   COALESCE(SUM(v.weight), 0.0) as total, COUNT(*) as voters
+            FROM votes v LEFT JOIN peers p ON p.peer_id = v.voter_peer_id
+            GROUP BY v.proposal_id, v.vote, low
+            "#,
+        )
+        .bind(floor)
+        .fetch_all(self.pool())
+        .await?;
+
+        let mut groups: HashMap<String, Vec<(String, bool, f64, usize)>> = HashMap::new();
+        for row in &rows {
+            let voters: i64 = row.get("voters");
+            groups.entry(row.get("proposal_id")).or_default().push((
+                row.get("vote"),
+                row.get("low"),
+                row.get("total"),
+                voters as usize,
+            ));
+        }
+        Ok(groups
+            .into_iter()
+            .map(|(proposal_id, groups)| {
+                let groups = groups.iter().map(|(vote, low, total, voters)| (vote.as_str(), *low, *total, *voters));
+                (proposal_id, cap_grouped_votes(groups, cap))
+            })
+            .collect())
+    }
+
+    /// Tally a proposal with low-reputation weight capped
+    ///
+    /// Voters unknown to this node are treated as having the neutral
+    /// reputation new peers start with (0.5), so votes relayed from peers
+    /// this node hasn't met aren't capped for that alone.
+    /// See [`cap_low_reputation_weight`].
+    pub async fn tally_votes_capped(&self, proposal_id: &str, floor: f64, cap: f64) -> Result<CappedTally> {
+        let rows = sqlx::query(
+            r#"
+            SELECT v.proposal_id, v.voter_peer_id, v.vote, v.weight, v.timestamp,
+                   COALESCE(p.reputation_score, 0.5) as reputation
+            FROM votes v LEFT JOIN peers p ON p.peer_id = v.voter_peer_id
+            WHERE v.proposal_id = ?
+            "#,
+        )
+        .bind(proposal_id)
+        .fetch_all(self.pool())
+        .await?;
+
+        let votes: Vec<(VoteRecord, f64)> = rows
+            .iter()
+            .map(|row| (row_to_vote(row), row.get("reputation")))
+            .collect();
+        Ok(cap_low_reputation_weight(&votes, floor, cap))
+    }
+
+    /// Compute governance participation metrics
+    ///
+    /// # Arguments
+    /// * `eligible_voters` - Number of peers allowed to vote (the turnout denominator)
+    /// * `since` - Start of the participation window (epoch millis)
+    /// * `now` - Current time (epoch millis), used to decide which proposals are still open
+    pub async fn governance_stats(
+        &self,
+        eligible_voters: usize,
+        since: i64,
+        now: i64,
+    ) -> Result<GovernanceStats> {
+        let active: i64 = sqlx::query(
+            "SELECT COUNT(*) as count FROM proposals WHERE status = 'active' AND deadline > ?",
+        )
+        .bind(now)
+        .fetch_one(self.pool())
+        .await?
+        .get("count");
+
+        let turnout_rows = sqlx::query(
+            r#"
+            SELECT p.id, COUNT(v.voter_peer_id) as voters
+            FROM proposals p LEFT JOIN votes v ON v.proposal_id = p.id
+            WHERE p.created_at >= ?
+            GROUP BY p.id
+            "#,
+        )
+        .bind(since)
+        .fetch_all(self.pool())
+        .await?;
+
+        let avg_turnout = if turnout_rows.is_empty() {
+            0.0
+        } else {
+            let sum: f64 = turnout_rows
+                .iter()
+                .map(|row| turnout(row.get::<i64, _>("voters") as usize, eligible_voters))
+                .sum();
+            sum / turnout_rows.len() as f64
+        };
+
+        let participating: i64 = sqlx::query(
+            "SELECT COUNT(DISTINCT voter_peer_id) as count FROM votes WHERE timestamp >= ?",
+        )
+        .bind(since)
+        .fetch_one(self.pool())
+        .await?
+        .get("count");
+
+        Ok(GovernanceStats {
+            active_proposals: active as usize,
+            avg_turnout,
+            participating_peers: participating as usize,
+            total_peers: eligible_voters,
+        })
+    }
+
+    /// Count votes on all proposals, returning the total and how many `voter` cast
+    pub async fn vote_counts(&self, voter: &str) -> Result<(usize, usize)> {
+        let row = sqlx::query(
+            "SELECT COUNT(*) as total, COALESCE(SUM(voter_peer_id = ?), 0) as mine FROM votes",
+        )
+        .bind(voter)
+        .fetch_one(self.pool())
+        .await?;
+
+        let total: i64 = row.get("total");
+        let mine: i64 = row.get("mine");
+        Ok((total as usize, mine as usize))
+    }
+}
+
+fn row_to_proposal(row: &sqlx::sqlite::SqliteRow) -> ProposalRecord {
+    let quorum: i64 = row.get("quorum");
+    let version: i64 = row.get("version");
+    ProposalRecord {
+        id: row.get("id"),
+        proposer: row.get("proposer_peer_id"),
+        title: row.get("title"),
+        description: row.get("description"),
+        proposal_type: row.get("proposal_type"),
+        parameters: serde_json::from_str(&row.get::<String, _>("parameters")).unwrap_or_default(),
+        status: row.get("status"),
+        quorum: quorum.max(0) as u32,
+        quorum_fraction: row.get("quorum_fraction"),
+        quorum_mode: QuorumMode::parse(&row.get::<String, _>("quorum_mode")),
+        passing_rule: PassingRule::parse(&row.get::<String, _>("passing_rule")),
+        deadline: row.get("deadline"),
+        created_at: row.get("created_at"),
+        version: version.max(1) as u32,
+    }
+}
+
+fn row_to_vote(row: &sqlx::sqlite::SqliteRow) -> VoteRecord {
+    VoteRecord {
+        proposal_id: row.get("proposal_id"),
+        voter: row.get("voter_peer_id"),
+        vote: row.get("vote"),
+        weight: row.get("weight"),
+        timestamp: row.get("timestamp"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn create_test_store() -> SqliteStore {
+        SqliteStore::new(":memory:").await.unwrap()
+    }
+
+    fn proposal(id: &str, created_at: i64, deadline: i64) -> ProposalRecord {
+        ProposalRecord {
+            id: id.to_string(),
+            proposer: "alice".to_string(),
+            title: format!("Proposal {}", id),
+            description: "Test proposal".to_string(),
+            proposal_type: "text".to_string(),
+            parameters: BTreeMap::new(),
+            status: "active".to_string(),
+            quorum: 3,
+            quorum_fraction: 0.5,
+            quorum_mode: QuorumMode::Snapshot,
+            passing_rule: PassingRule::SimpleMajority,
+            deadline,
+            created_at,
+            version: 1,
+        }
+    }
+
+    fn vote(proposal_id: &str, voter: &str, timestamp: i64) -> VoteRecord {
+        VoteRecord {
+            proposal_id: proposal_id.to_string(),
+            voter: voter.to_string(),
+            vote: "yes".to_string(),
+            weight: 1.0,
+            timestamp,
+        }
+    }
+
+    #[test]
+    fn test_required_voters() {
+        assert_eq!(required_voters(0.5, 5), 3);
+        assert_eq!(required_voters(0.5, 0), 1);
+        assert_eq!(required_voters(1.5, 4), 4);
+    }
+
+    #[test]
+    fn test_received_quorum() {
+        assert_eq!(received_quorum(Some(3), 0.5, 5), 3);
+        assert_eq!(received_quorum(Some(5), 0.5, 5), 5);
+        // Counts no local set could reach fall back to the fraction
+        assert_eq!(received_quorum(Some(6), 0.5, 5), 3);
+        assert_eq!(received_quorum(Some(u32::MAX), 0.5, 5), 3);
+        assert_eq!(received_quorum(Some(0), 0.5, 5), 3);
+        assert_eq!(received_quorum(None, 0.5, 5), 3);
+    }
+
+    #[test]
+    fn test_turnout() {
+        assert_eq!(turnout(2, 4), 0.5);
+        assert_eq!(turnout(5, 4), 1.0);
+        assert_eq!(turnout(3, 0), 0.0);
+    }
+
+    #[tokio::test]
+    async fn test_proposal_and_vote_crud() {
+        let store = create_test_store().await;
+
+        store.upsert_proposal(&proposal("p1", 1_000, 10_000)).await.unwrap();
+        let stored = store.get_proposal("p1").await.unwrap().unwrap();
+        assert_eq!(stored.title, "Proposal p1");
+        assert_eq!(stored.quorum, 3);
+        assert!(stored.parameters.is_empty());
+
+        let mut spend = proposal("p2", 1_500, 10_000);
+        spend.proposal_type = "treasury_spend".to_string();
+        spend.parameters.insert("recipient".to_string(), "bob".to_string());
+        spend.parameters.insert("amount".to_string(), "25".to_string());
+        store.upsert_proposal(&spend).await.unwrap();
+        assert_eq!(store.get_proposal("p2").await.unwrap().unwrap(), spend);
+
+        store.record_vote(&vote("p1", "bob", 2_000)).await.unwrap();
+        let mut changed = vote("p1", "bob", 3_000);
+        changed.vote = "no".to_string();
+        store.record_vote(&changed).await.unwrap();
+
+        let votes = store.list_votes("p1").await.unwrap();
+        assert_eq!(votes.len(), 1);
+        assert_eq!(votes[0].vote, "no");
+        assert_eq!(store.get_vote("p1", "bob").await.unwrap().unwrap().timestamp, 3_000);
+        assert!(store.get_vote("p1", "carol").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_redelivered_proposal_keeps_status() {
+        let store = create_test_store().await;
+        store.upsert_proposal(&proposal("p1", 1_000, 10_000)).await.unwrap();
+        assert!(store.close_proposal("p1", "passed").await.unwrap());
+
+        // The original arrives again, still marked active
+        let mut redelivered = proposal("p1", 1_000, 10_000);
+        redelivered.title = "Renamed".to_string();
+        store.upsert_proposal(&redelivered).await.unwrap();
+
+        let stored = store.get_proposal("p1").await.unwrap().unwrap();
+        assert_eq!(stored.status, "passed");
+        assert_eq!(stored.title, "Renamed");
+    }
+
+    #[tokio::test]
+    async fn test_inserted_proposal_never_replaced() {
+        let store = create_test_store().await;
+        assert!(store.insert_proposal(&proposal("p1", 1_000, 10_000)).await.unwrap());
+
+        let mut resent = proposal("p1", 1_000, 99_000);
+        resent.title = "Rewritten".to_string();
+        resent.version = 5;
+        assert!(!store.insert_proposal(&resent).await.unwrap());
+        let stored = store.get_proposal("p1").await.unwrap().unwrap();
+        assert_eq!(stored.title, "Proposal p1");
+        assert_eq!(stored.deadline, 10_000);
+        assert_eq!(stored.version, 1);
+    }
+
+    #[tokio::test]
+    async fn test_only_marked_proposals_are_local() {
+        let store = create_test_store().await;
+        store.upsert_proposal(&proposal("p1", 1_000, 10_000)).await.unwrap();
+        assert!(!store.is_local_proposal("p1").await.unwrap());
+
+        store.mark_proposal_local("p1").await.unwrap();
+        assert!(store.is_local_proposal("p1").await.unwrap());
+        // Storing it again, as a gossip echo would, keeps the mark
+        store.upsert_proposal(&proposal("p1", 1_000, 10_000)).await.unwrap();
+        assert!(store.is_local_proposal("p1").await.unwrap());
+        assert!(!store.is_local_proposal("p2").await.unwrap());
+    }
+
+    #[test]
+    fn test_current_status_follows_quorum_and_deadline() {
+        let record = proposal("p1", 0, 10_000);
+        let tally = |yes: f64, no: f64, voters: usize| VoteTally { yes, no, abstain: 0.0, voters };
+
+        // Open until the deadline regardless of votes
+        assert_eq!(record.current_status(&tally(3.0, 0.0, 3), 6, 5_000), "active");
+        // After it, quorum and the passing rule decide
+        assert_eq!(record.current_status(&tally(3.0, 0.0, 3), 6, 10_000), "passed");
+        assert_eq!(record.current_status(&tally(2.0, 0.0, 2), 6, 10_000), "rejected");
+        assert_eq!(record.current_status(&tally(1.0, 2.0, 3), 6, 10_000), "rejected");
+
+        // A closed proposal keeps its stored status
+        let mut closed = record.clone();
+        closed.status = "passed".to_string();
+        assert_eq!(closed.current_status(&tally(0.0, 0.0, 0), 6, 5_000), "passed");
+    }
+
+    #[test]
+    fn test_accepts_votes_only_while_open() {
+        let mut record = proposal("p1", 1_000, 10_000);
+        assert!(record.accepts_votes(9_999));
+        assert!(!record.accepts_votes(10_000));
+
+        record.status = "cancelled".to_string();
+        assert!(!record.accepts_votes(5_000));
+    }
+
+    #[tokio::test]
+    async fn test_quorum_modes_as_voter_set_changes() {
+        let store = create_test_store().await;
+
+        // Created with 6 eligible voters and a 50% quorum => 3 required
+        let snapshot = proposal("snap", 0, 10_000);
+        let mut dynamic = proposal("dyn", 0, 10_000);
+        dynamic.quorum_mode = QuorumMode::Dynamic;
+        store.upsert_proposal(&snapshot).await.unwrap();
+        store.upsert_proposal(&dynamic).await.unwrap();
+
+        let snapshot = store.get_proposal("snap").await.unwrap().unwrap();
+        let dynamic = store.get_proposal("dyn").await.unwrap().unwrap();
+        assert_eq!(dynamic.quorum_mode, QuorumMode::Dynamic);
+
+        // Unchanged voter set: both need 3
+        assert_eq!(snapshot.required_voters(6), 3);
+        assert_eq!(dynamic.required_voters(6), 3);
+
+        // Voter set grows to 10: snapshot still needs 3, dynamic needs 5
+        assert_eq!(snapshot.required_voters(10), 3);
+        assert_eq!(dynamic.required_voters(10), 5);
+
+        // Voter set shrinks to 2: dynamic needs only 1
+        assert_eq!(snapshot.required_voters(2), 3);
+        assert_eq!(dynamic.required_voters(2), 1);
+    }
+
+    #[tokio::test]
+    async fn test_passing_rule_decides_outcome() {
+        let store = create_test_store().await;
+        let majority = proposal("majority", 0, 10_000);
+        let mut supermajority = proposal("super", 0, 10_000);
+        supermajority.passing_rule = PassingRule::Supermajority(2.0 / 3.0);
+        store.upsert_proposal(&majority).await.unwrap();
+        store.upsert_proposal(&supermajority).await.unwrap();
+
+        // 3 yes, 2 no: quorum met for both, 60% approval
+        for id in ["majority", "super"] {
+            for (i, choice) in ["yes", "yes", "yes", "no", "no"].iter().enumerate() {
+                let mut v = vote(id, &format!("voter-{}", i), 1_000);
+                v.vote = choice.to_string();
+                store.record_vote(&v).await.unwrap();
+            }
+        }
+
+        let majority = store.get_proposal("majority").await.unwrap().unwrap();
+        let supermajority = store.get_proposal("super").await.unwrap().unwrap();
+        assert_eq!(supermajority.passing_rule, PassingRule::Supermajority(2.0 / 3.0));
+
+        let tally = store.tally_votes("majority").await.unwrap();
+        assert_eq!(majority.outcome(&tally, 6), "passed");
+        let tally = store.tally_votes("super").await.unwrap();
+        assert_eq!(supermajority.outcome(&tally, 6), "rejected");
+
+        // Short of quorum, even a unanimous vote is rejected
+        let too_few = VoteTally { yes: 2.0, no: 0.0, abstain: 0.0, voters: 2 };
+        assert_eq!(majority.outcome(&too_few, 6), "rejected");
+    }
+
+    #[tokio::test]
+    async fn test_amend_only_before_voting() {
+        let store = create_test_store().await;
+        store.upsert_proposal(&proposal("p1", 1_000, 10_000)).await.unwrap();
+
+        assert!(store.amend_proposal("p1", "New title", "New text", 2).await.unwrap());
+        // Stale or repeated versions are ignored
+        assert!(!store.amend_proposal("p1", "Older", "Older", 2).await.unwrap());
+
+        let stored = store.get_proposal("p1").await.unwrap().unwrap();
+        assert_eq!(stored.title, "New title");
+        assert_eq!(stored.version, 2);
+
+        // Re-storing the original proposal doesn't undo the amendment
+        store.upsert_proposal(&proposal("p1", 1_000, 10_000)).await.unwrap();
+        let stored = store.get_proposal("p1").await.unwrap().unwrap();
+        assert_eq!(stored.title, "New title");
+        assert_eq!(stored.version, 2);
+
+        store.record_vote(&vote("p1", "bob", 2_000)).await.unwrap();
+        assert!(!store.amend_proposal("p1", "Too late", "Too late", 3).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_tally_votes() {
+        let store = create_test_store().await;
+        store.upsert_proposal(&proposal("p1", 1_000, 10_000)).await.unwrap();
+
+        store.record_vote(&vote("p1", "alice", 2_000)).await.unwrap();
+        let mut heavy = vote("p1", "bob", 2_100);
+        heavy.weight = 2.5;
+        store.record_vote(&heavy).await.unwrap();
+        let mut against = vote("p1", "carol", 2_200);
+        against.vote = "no".to_string();
+        store.record_vote(&against).await.unwrap();
+
+        let tally = store.tally_votes("p1").await.unwrap();
+        assert!((tally.yes - 3.5).abs() < 1e-9);
+        assert!((tally.no - 1.0).abs() < 1e-9);
+        assert_eq!(tally.abstain, 0.0);
+        assert_eq!(tally.voters, 3);
+
+        assert_eq!(store.tally_votes("missing").await.unwrap(), VoteTally::default());
+    }
+
+    #[tokio::test]
+    async fn test_capped_tally_treats_unknown_voters_as_neutral() {
+        use mycelial_core::peer::{PeerId, PeerInfo};
+        use mycelial_core::reputation::Reputation;
+
+        let store = create_test_store().await;
+        store.upsert_proposal(&proposal("p1", 1_000, 10_000)).await.unwrap();
+        let distrusted = PeerInfo {
+            id: PeerId("mallory".to_string()),
+            public_key: "mallory".to_string(),
+            addresses: vec![],
+            first_seen: chrono::Utc::now(),
+            last_seen: chrono::Utc::now(),
+            name: None,
+        };
+        let reputation = Reputation { score: 0.1, ..Default::default() };
+        store.upsert_peer(&distrusted, Some(&reputation)).await.unwrap();
+
+        store.record_vote(&vote("p1", "stranger", 2_000)).await.unwrap();
+        store.record_vote(&vote("p1", "mallory", 2_100)).await.unwrap();
+
+        // The stranger counts in full; only the known low-reputation voter is capped
+        let capped = store.tally_votes_capped("p1", 0.3, 0.0).await.unwrap();
+        assert!((capped.tally.yes - 1.0).abs() < 1e-9);
+        assert_eq!(capped.low_reputation_voters, 1);
+        assert_eq!(capped.tally.voters, 2);
+    }
+
+    #[tokio::test]
+    async fn test_tally_all_votes_matches_per_proposal_tallies() {
+        let store = create_test_store().await;
+        for id in ["p1", "p2", "p3"] {
+            store.upsert_proposal(&proposal(id, 1_000, 10_000)).await.unwrap();
+        }
+        store.record_vote(&vote("p1", "alice", 2_000)).await.unwrap();
+        let mut heavy = vote("p1", "bob", 2_100);
+        heavy.weight = 2.5;
+        store.record_vote(&heavy).await.unwrap();
+        let mut against = vote("p2", "alice", 2_200);
+        against.vote = "no".to_string();
+        store.record_vote(&against).await.unwrap();
+        store.record_vote(&vote("p2", "stranger", 2_300)).await.unwrap();
+
+        let all = store.tally_all_votes().await.unwrap();
+        let capped = store.tally_all_votes_capped(0.3, 0.5).await.unwrap();
+        assert_eq!(all.len(), 2);
+        assert!(!all.contains_key("p3"));
+        for id in ["p1", "p2"] {
+            assert_eq!(all[id], store.tally_votes(id).await.unwrap());
+            assert_eq!(capped[id], store.tally_votes_capped(id, 0.3, 0.5).await.unwrap());
+        }
+        assert!((all["p1"].yes - 3.5).abs() < 1e-9);
+    }
+
+    #[tokio::test]
+    async fn test_governance_stats() {
+        let store = create_test_store().await;
+        let now = 100_000;
+
+        // Two open proposals, one past its deadline
+        store.upsert_proposal(&proposal("p1", 10_000, now + 1_000)).await.unwrap();
+        store.upsert_proposal(&proposal("p2", 20_000, now + 1_000)).await.unwrap();
+        store.upsert_proposal(&proposal("p3", 30_000, now - 1_000)).await.unwrap();
+
+        // p1: 2 voters, p2: 1 voter, p3: 0 voters
+        store.record_vote(&vote("p1", "alice", 40_000)).await.unwrap();
+        store.record_vote(&vote("p1", "bob", 41_000)).await.unwrap();
+        store.record_vote(&vote("p2", "alice", 42_000)).await.unwrap();
+
+        let stats = store.governance_stats(4, 0, now).await.unwrap();
+        assert_eq!(stats.active_proposals, 2);
+        assert_eq!(stats.participating_peers, 2);
+        assert_eq!(stats.total_peers, 4);
+        // Turnouts: 2/4, 1/4, 0/4 => mean 0.25
+        assert!((stats.avg_turnout - 0.25).abs() < 1e-9);
+
+        // Narrowing the window drops older proposals and votes
+        let stats = store.governance_stats(4, 25_000, now).await.unwrap();
+        assert_eq!(stats.participating_peers, 2);
+        assert!((stats.avg_turnout - 0.0).abs() < 1e-9);
+
+        assert_eq!(store.vote_counts("alice").await.unwrap(), (3, 2));
+        assert_eq!(store.vote_counts("carol").await.unwrap(), (3, 0));
+    }
+}
@@ -7,6 +7,13 @@
 //! - **storage**: SQLite-based persistence with sqlx
 //! - **cache**: LRU in-memory caching for peers, messages, and credit relationships
 //! - **sync**: State synchronization with vector clocks and CRDT-style merge strategies
+//! - **governance**: Proposal and vote persistence with participation metrics
+//! - **events**: Append-only dashboard event log for replay
+//! - **payments**: Payment requests that transfers can settle
+//! - **stats**: Periodic node statistics snapshots for trend charts
+//! - **resources**: Resource contributions and contributor rankings
+//! - **vouches**: Vouch requests, their responses, and trust-network statistics
+//! - **credit_lines**: Credit lines and the transfers drawn on them
 //! - **error**: State-specific error types
 //!
 //! ## Example
@@ -34,9 +41,27 @@ pub mod error;
 pub mod storage;
 pub mod cache;
 pub mod sync;
+pub mod governance;
+pub mod events;
+pub mod payments;
+pub mod stats;
+pub mod resources;
+pub mod vouches;
+pub mod unread;
+pub mod reactions;
+pub mod reads;
+pub mod credit_lines;
 
 // Re-exports for convenience
 pub use error::{Result, StateError};
 pub use storage::SqliteStore;
 pub use cache::{StateCache, PeerCache, MessageCache, CreditCache, MemoryCache, CacheStats};
 pub use sync::{StateSync, StateUpdate, VectorClock, PeerInfoUpdate};
+pub use governance::{ProposalRecord, VoteRecord, VoteTally, CappedTally, GovernanceStats};
+pub use events::LoggedEvent;
+pub use payments::PaymentRequestRecord;
+pub use stats::StatsSnapshot;
+pub use resources::{ContributionRecord, ContributorTotal};
+pub use vouches::{VouchRecord, VouchStats};
+pub use unread::UnreadCounts;
+pub use credit_lines::{CreditHistoryEntry, CreditLineRecord, CreditTransferRecord};
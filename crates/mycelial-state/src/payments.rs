@@ -0,0 +1,153 @@
+//! Payment request persistence
+//!
+//! A payment request asks a peer to send credit. Transfers may reference a
+//! request by ID, and the request records which transfer settled it so the
+//! two can be reconciled.
+
+use sqlx::Row;
+use tracing::debug;
+
+use crate::credit_lines::CreditTransferRecord;
+use crate::error::Result;
+use crate::storage::SqliteStore;
+
+/// A stored payment request
+#[derive(Debug, Clone, PartialEq)]
+pub struct PaymentRequestRecord {
+    /// Request ID
+    pub id: String,
+    /// Peer asking to be paid
+    pub requester: String,
+    /// Peer asked to pay
+    pub payer: String,
+    /// Requested amount
+    pub amount: f64,
+    /// Optional memo
+    pub memo: Option<String>,
+    /// When the request was made (epoch millis)
+    pub created_at: i64,
+    /// Transfer that settled the request, if any
+    pub settled_by: Option<String>,
+}
+
+impl SqliteStore {
+    // ========== Payment Request Operations ==========
+
+    /// Store a payment request, ignoring duplicates
+    pub async fn insert_payment_request(&self, request: &PaymentRequestRecord) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO payment_requests (
+                id, requester_peer_id, payer_peer_id, amount, memo, created_at, settled_by
+            ) VALUES (?, ?, ?, ?, ?, ?, ?)
+            ON CONFLICT(id) DO NOTHING
+            "#,
+        )
+        .bind(&request.id)
+        .bind(&request.requester)
+        .bind(&request.payer)
+        .bind(request.amount)
+        .bind(&request.memo)
+        .bind(request.created_at)
+        .bind(&request.settled_by)
+        .execute(self.pool())
+        .await?;
+
+        debug!("Stored payment request: {}", request.id);
+        Ok(())
+    }
+
+    /// Get a payment request by ID
+    pub async fn get_payment_request(&self, id: &str) -> Result<Option<PaymentRequestRecord>> {
+        let row = sqlx::query(
+            r#"
+            SELECT id, requester_peer_id, payer_peer_id, amount, memo, created_at, settled_by
+            FROM payment_requests WHERE id = ?
+            "#,
+        )
+        .bind(id)
+        .fetch_optional(self.pool())
+        .await?;
+
+        Ok(row.as_ref().map(row_to_payment_request))
+    }
+
+    /// Mark an outstanding request as settled by a transfer
+    ///
+    /// The transfer must come from the requested payer, go to the requester,
+    /// and cover the requested amount. Returns `false` if the request doesn't
+    /// exist, was already settled, or the transfer doesn't match it.
+    pub async fn settle_payment_request(&self, id: &str, transfer: &CreditTransferRecord) -> Result<bool> {
+        let result = sqlx::query(
+            r#"
+            UPDATE payment_requests SET settled_by = ?
+            WHERE id = ? AND settled_by IS NULL
+              AND payer_peer_id = ? AND requester_peer_id = ? AND amount <= ?
+            "#,
+        )
+        .bind(&transfer.id)
+        .bind(id)
+        .bind(&transfer.from)
+        .bind(&transfer.to)
+        .bind(transfer.amount)
+        .execute(self.pool())
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+}
+
+fn row_to_payment_request(row: &sqlx::sqlite::SqliteRow) -> PaymentRequestRecord {
+    PaymentRequestRecord {
+        id: row.get("id"),
+        requester: row.get("requester_peer_id"),
+        payer: row.get("payer_peer_id"),
+        amount: row.get("amount"),
+        memo: row.get("memo"),
+        created_at: row.get("created_at"),
+        settled_by: row.get("settled_by"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_settle_payment_request() {
+        let store = SqliteStore::new(":memory:").await.unwrap();
+        let request = PaymentRequestRecord {
+            id: "r1".to_string(),
+            requester: "alice".to_string(),
+            payer: "bob".to_string(),
+            amount: 12.5,
+            memo: Some("lunch".to_string()),
+            created_at: 1_000,
+            settled_by: None,
+        };
+        store.insert_payment_request(&request).await.unwrap();
+        assert_eq!(store.get_payment_request("r1").await.unwrap(), Some(request));
+
+        let transfer = |id: &str, from: &str, to: &str, amount: f64| CreditTransferRecord {
+            id: id.to_string(),
+            line_id: "l1".to_string(),
+            from: from.to_string(),
+            to: to.to_string(),
+            amount,
+            memo: None,
+            created_at: 2_000,
+        };
+
+        // Only the requested payer, paying the requester in full, settles it
+        assert!(!store.settle_payment_request("r1", &transfer("t0", "carol", "alice", 12.5)).await.unwrap());
+        assert!(!store.settle_payment_request("r1", &transfer("t0", "bob", "carol", 12.5)).await.unwrap());
+        assert!(!store.settle_payment_request("r1", &transfer("t0", "bob", "alice", 12.0)).await.unwrap());
+        assert!(store.settle_payment_request("r1", &transfer("t1", "bob", "alice", 12.5)).await.unwrap());
+        // A request can only be settled once
+        assert!(!store.settle_payment_request("r1", &transfer("t2", "bob", "alice", 12.5)).await.unwrap());
+        assert!(!store.settle_payment_request("missing", &transfer("t3", "bob", "alice", 12.5)).await.unwrap());
+
+        let stored = store.get_payment_request("r1").await.unwrap().unwrap();
+        assert_eq!(stored.settled_by.as_deref(), Some("t1"));
+    }
+}
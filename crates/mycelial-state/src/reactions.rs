@@ -0,0 +1,159 @@
+//! Chat reactions
+//!
+//! Each peer can react to a chat message with any number of emoji, once per
+//! emoji, and take a reaction back.
+
+use sqlx::Row;
+use std::collections::{BTreeMap, HashMap};
+use tracing::debug;
+
+use crate::error::Result;
+use crate::storage::SqliteStore;
+
+impl SqliteStore {
+    // ========== Reaction Operations ==========
+
+    /// Add `reactor`'s reaction if `added`, otherwise take it back
+    ///
+    /// Returns whether anything changed.
+    pub async fn set_reaction(
+        &self,
+        message_id: &str,
+        reactor: &str,
+        emoji: &str,
+        added: bool,
+        timestamp: i64,
+    ) -> Result<bool> {
+        let query = if added {
+            sqlx::query(
+                "INSERT OR IGNORE INTO chat_reactions (message_id, reactor, emoji, created_at) VALUES (?, ?, ?, ?)",
+            )
+            .bind(message_id)
+            .bind(reactor)
+            .bind(emoji)
+            .bind(timestamp)
+        } else {
+            sqlx::query("DELETE FROM chat_reactions WHERE message_id = ? AND reactor = ? AND emoji = ?")
+                .bind(message_id)
+                .bind(reactor)
+                .bind(emoji)
+        };
+        let changed = query.execute(self.pool()).await?.rows_affected() > 0;
+
+        if changed {
+            debug!(
+                "{} reaction {} by {} on {}",
+                if added { "Added" } else { "Removed" },
+                emoji,
+                reactor,
+                message_id
+            );
+        }
+        Ok(changed)
+    }
+
+    /// Whether `reactor` has reacted to a message with `emoji`
+    pub async fn has_reaction(&self, message_id: &str, reactor: &str, emoji: &str) -> Result<bool> {
+        let row = sqlx::query(
+            "SELECT 1 FROM chat_reactions WHERE message_id = ? AND reactor = ? AND emoji = ?",
+        )
+        .bind(message_id)
+        .bind(reactor)
+        .bind(emoji)
+        .fetch_optional(self.pool())
+        .await?;
+
+        Ok(row.is_some())
+    }
+
+    /// Number of reactions per emoji on a message
+    pub async fn reaction_counts(&self, message_id: &str) -> Result<BTreeMap<String, u64>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT emoji, COUNT(*) as count FROM chat_reactions
+            WHERE message_id = ?
+            GROUP BY emoji
+            "#,
+        )
+        .bind(message_id)
+        .fetch_all(self.pool())
+        .await?;
+
+        Ok(rows
+            .iter()
+            .map(|row| (row.get("emoji"), row.get::<i64, _>("count") as u64))
+            .collect())
+    }
+
+    /// Number of reactions per emoji on each of several messages, in one query
+    ///
+    /// Messages without reactions are left out.
+    pub async fn reaction_counts_for(
+        &self,
+        message_ids: &[String],
+    ) -> Result<HashMap<String, BTreeMap<String, u64>>> {
+        let ids = serde_json::to_string(message_ids)?;
+        let rows = sqlx::query(
+            r#"
+            SELECT message_id, emoji, COUNT(*) as count FROM chat_reactions
+            WHERE message_id IN (SELECT value FROM json_each(?))
+            GROUP BY message_id, emoji
+            "#,
+        )
+        .bind(ids)
+        .fetch_all(self.pool())
+        .await?;
+
+        let mut counts: HashMap<String, BTreeMap<String, u64>> = HashMap::new();
+        for row in &rows {
+            counts
+                .entry(row.get("message_id"))
+                .or_default()
+                .insert(row.get("emoji"), row.get::<i64, _>("count") as u64);
+        }
+        Ok(counts)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_set_reaction() {
+        let store = SqliteStore::new(":memory:").await.unwrap();
+
+        assert!(store.set_reaction("m1", "alice", "👍", true, 1).await.unwrap());
+        assert!(store.set_reaction("m1", "bob", "👍", true, 2).await.unwrap());
+        assert!(store.set_reaction("m1", "bob", "🎉", true, 3).await.unwrap());
+        // Adding the same reaction again changes nothing
+        assert!(!store.set_reaction("m1", "bob", "🎉", true, 4).await.unwrap());
+        assert!(store.has_reaction("m1", "bob", "🎉").await.unwrap());
+        let counts = store.reaction_counts("m1").await.unwrap();
+        assert_eq!(counts.get("👍"), Some(&2));
+        assert_eq!(counts.get("🎉"), Some(&1));
+
+        assert!(store.set_reaction("m1", "bob", "🎉", false, 5).await.unwrap());
+        assert!(!store.set_reaction("m1", "bob", "🎉", false, 6).await.unwrap());
+        assert!(!store.has_reaction("m1", "bob", "🎉").await.unwrap());
+        let counts = store.reaction_counts("m1").await.unwrap();
+        assert_eq!(counts.get("🎉"), None);
+        assert!(store.reaction_counts("m2").await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_reaction_counts_for_several_messages() {
+        let store = SqliteStore::new(":memory:").await.unwrap();
+        store.set_reaction("m1", "alice", "👍", true, 1).await.unwrap();
+        store.set_reaction("m1", "bob", "👍", true, 2).await.unwrap();
+        store.set_reaction("m2", "bob", "🎉", true, 3).await.unwrap();
+        store.set_reaction("m3", "bob", "🎉", true, 4).await.unwrap();
+
+        let ids = ["m1", "m2", "m4"].map(str::to_string);
+        let counts = store.reaction_counts_for(&ids).await.unwrap();
+        assert_eq!(counts.len(), 2);
+        assert_eq!(counts["m1"].get("👍"), Some(&2));
+        assert_eq!(counts["m2"].get("🎉"), Some(&1));
+        assert!(store.reaction_counts_for(&[]).await.unwrap().is_empty());
+    }
+}
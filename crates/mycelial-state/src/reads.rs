@@ -0,0 +1,62 @@
+//! Chat read receipts
+//!
+//! Records when a direct message's recipient first read it, so the sender
+//! can be told and chat history can show it.
+
+use sqlx::Row;
+use tracing::debug;
+
+use crate::error::Result;
+use crate::storage::SqliteStore;
+
+impl SqliteStore {
+    // ========== Read Receipt Operations ==========
+
+    /// Record that `reader` read a message, keeping the earliest time
+    ///
+    /// Returns when the message was first read.
+    pub async fn mark_read(&self, message_id: &str, reader: &str, timestamp: i64) -> Result<i64> {
+        sqlx::query("INSERT OR IGNORE INTO chat_reads (message_id, reader, read_at) VALUES (?, ?, ?)")
+            .bind(message_id)
+            .bind(reader)
+            .bind(timestamp)
+            .execute(self.pool())
+            .await?;
+
+        let row = sqlx::query("SELECT read_at FROM chat_reads WHERE message_id = ? AND reader = ?")
+            .bind(message_id)
+            .bind(reader)
+            .fetch_one(self.pool())
+            .await?;
+
+        debug!("{} read {}", reader, message_id);
+        Ok(row.get("read_at"))
+    }
+
+    /// When a message was first read by anyone, if it has been
+    pub async fn read_at(&self, message_id: &str) -> Result<Option<i64>> {
+        let row = sqlx::query("SELECT MIN(read_at) as read_at FROM chat_reads WHERE message_id = ?")
+            .bind(message_id)
+            .fetch_one(self.pool())
+            .await?;
+
+        Ok(row.get("read_at"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_mark_read_keeps_first_time() {
+        let store = SqliteStore::new(":memory:").await.unwrap();
+
+        assert_eq!(store.read_at("m1").await.unwrap(), None);
+        assert_eq!(store.mark_read("m1", "bob", 100).await.unwrap(), 100);
+        // Marking again doesn't move the time
+        assert_eq!(store.mark_read("m1", "bob", 200).await.unwrap(), 100);
+        assert_eq!(store.read_at("m1").await.unwrap(), Some(100));
+        assert_eq!(store.read_at("m2").await.unwrap(), None);
+    }
+}
@@ -0,0 +1,273 @@
+//! Resource contribution persistence
+//!
+//! Contributions reported by peers are stored individually and aggregated
+//! per resource type into a ranked list of contributors. Older
+//! contributions can optionally decay so rankings favour recent activity.
+//! A report states what a peer offers now, so the capacity available to a
+//! pool counts only each peer's latest report.
+
+use std::collections::HashMap;
+
+use sqlx::Row;
+use tracing::debug;
+
+use crate::error::Result;
+use crate::storage::SqliteStore;
+
+/// A stored resource contribution
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContributionRecord {
+    /// Contribution ID
+    pub id: String,
+    /// Contributing peer
+    pub peer_id: String,
+    /// Resource type label (bandwidth, storage, compute, ...)
+    pub resource_type: String,
+    /// Amount contributed
+    pub amount: f64,
+    /// Unit of measurement
+    pub unit: String,
+    /// When the contribution was reported (epoch millis)
+    pub timestamp: i64,
+}
+
+/// A peer's aggregate contribution to one resource type
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContributorTotal {
+    /// Contributing peer
+    pub peer_id: String,
+    /// Total (possibly decayed) contribution
+    pub contribution: f64,
+    /// Share of all contributions to the resource type, 0-100
+    pub percentage: f64,
+}
+
+/// Aggregate contributions per peer, ranked by contribution descending
+///
+/// With a `half_life_ms`, each contribution is weighted by
+/// `0.5 ^ (age / half_life)` relative to `now`. Ties are ordered by peer ID
+/// so pagination is stable.
+pub fn rank_contributors(
+    contributions: &[ContributionRecord],
+    now: i64,
+    half_life_ms: Option<i64>,
+) -> Vec<ContributorTotal> {
+    let mut totals: HashMap<&str, f64> = HashMap::new();
+    for contribution in contributions {
+        let weight = match half_life_ms {
+            Some(half_life) if half_life > 0 => {
+                let age = (now - contribution.timestamp).max(0) as f64;
+                0.5f64.powf(age / half_life as f64)
+            }
+            _ => 1.0,
+        };
+        *totals.entry(contribution.peer_id.as_str()).or_insert(0.0) += contribution.amount * weight;
+    }
+
+    let sum: f64 = totals.values().sum();
+    let mut ranked: Vec<ContributorTotal> = totals
+        .into_iter()
+        .map(|(peer_id, contribution)| ContributorTotal {
+            peer_id: peer_id.to_string(),
+            contribution,
+            percentage: if sum > 0.0 { contribution / sum * 100.0 } else { 0.0 },
+        })
+        .collect();
+    ranked.sort_by(|a, b| {
+        b.contribution
+            .total_cmp(&a.contribution)
+            .then_with(|| a.peer_id.cmp(&b.peer_id))
+    });
+    ranked
+}
+
+/// Capacity offered to the pool: the sum of each peer's latest report per
+/// resource type
+///
+/// Reports with the same timestamp are ordered by ID so the result doesn't
+/// depend on the order they were loaded in.
+pub fn available_capacity(contributions: &[ContributionRecord]) -> f64 {
+    let mut latest: HashMap<(&str, &str), &ContributionRecord> = HashMap::new();
+    for contribution in contributions {
+        let key = (contribution.resource_type.as_str(), contribution.peer_id.as_str());
+        let newer = latest
+            .get(&key)
+            .map_or(true, |current| (contribution.timestamp, &contribution.id) > (current.timestamp, &current.id));
+        if newer {
+            latest.insert(key, contribution);
+        }
+    }
+    latest.values().map(|contribution| contribution.amount).sum()
+}
+
+impl SqliteStore {
+    // ========== Resource Contribution Operations ==========
+
+    /// Store a resource contribution, ignoring duplicates
+    pub async fn insert_resource_contribution(&self, contribution: &ContributionRecord) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO resource_contributions (id, peer_id, resource_type, amount, unit, timestamp)
+            VALUES (?, ?, ?, ?, ?, ?)
+            ON CONFLICT(id) DO NOTHING
+            "#,
+        )
+        .bind(&contribution.id)
+        .bind(&contribution.peer_id)
+        .bind(&contribution.resource_type)
+        .bind(contribution.amount)
+        .bind(&contribution.unit)
+        .bind(contribution.timestamp)
+        .execute(self.pool())
+        .await?;
+
+        debug!("Stored {} contribution from {}", contribution.resource_type, contribution.peer_id);
+        Ok(())
+    }
+
+    /// List all contributions to a resource type, oldest first
+    pub async fn list_resource_contributions(&self, resource_type: &str) -> Result<Vec<ContributionRecord>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT id, peer_id, resource_type, amount, unit, timestamp
+            FROM resource_contributions WHERE resource_type = ?
+            ORDER BY timestamp ASC
+            "#,
+        )
+        .bind(resource_type)
+        .fetch_all(self.pool())
+        .await?;
+
+        Ok(rows.iter().map(row_to_contribution).collect())
+    }
+
+    /// List every stored contribution, oldest first
+    pub async fn list_all_resource_contributions(&self) -> Result<Vec<ContributionRecord>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT id, peer_id, resource_type, amount, unit, timestamp
+            FROM resource_contributions
+            ORDER BY timestamp ASC, id ASC
+            "#,
+        )
+        .fetch_all(self.pool())
+        .await?;
+
+        Ok(rows.iter().map(row_to_contribution).collect())
+    }
+
+    /// Rewrite a contribution's amount and unit, e.g. after converting units
+    pub async fn update_resource_contribution_unit(&self, id: &str, amount: f64, unit: &str) -> Result<()> {
+        sqlx::query("UPDATE resource_contributions SET amount = ?, unit = ? WHERE id = ?")
+            .bind(amount)
+            .bind(unit)
+            .bind(id)
+            .execute(self.pool())
+            .await?;
+        Ok(())
+    }
+
+    /// List a peer's contributions at or after `since`, oldest first
+    ///
+    /// With a `resource_type`, only contributions to that type are returned.
+    pub async fn list_peer_contributions(
+        &self,
+        peer_id: &str,
+        resource_type: Option<&str>,
+        since: i64,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<ContributionRecord>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT id, peer_id, resource_type, amount, unit, timestamp
+            FROM resource_contributions
+            WHERE peer_id = ? AND timestamp >= ? AND (? IS NULL OR resource_type = ?)
+            ORDER BY timestamp ASC, id ASC
+            LIMIT ? OFFSET ?
+            "#,
+        )
+        .bind(peer_id)
+        .bind(since)
+        .bind(resource_type)
+        .bind(resource_type)
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(self.pool())
+        .await?;
+
+        Ok(rows.iter().map(row_to_contribution).collect())
+    }
+}
+
+fn row_to_contribution(row: &sqlx::sqlite::SqliteRow) -> ContributionRecord {
+    ContributionRecord {
+        id: row.get("id"),
+        peer_id: row.get("peer_id"),
+        resource_type: row.get("resource_type"),
+        amount: row.get("amount"),
+        unit: row.get("unit"),
+        timestamp: row.get("timestamp"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn contribution(id: &str, peer_id: &str, resource_type: &str, amount: f64, timestamp: i64) -> ContributionRecord {
+        ContributionRecord {
+            id: id.to_string(),
+            peer_id: peer_id.to_string(),
+            resource_type: resource_type.to_string(),
+            amount,
+            unit: "GB".to_string(),
+            timestamp,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_contributors_ranked_descending() {
+        let store = SqliteStore::new(":memory:").await.unwrap();
+        store.insert_resource_contribution(&contribution("c1", "alice", "storage", 10.0, 0)).await.unwrap();
+        store.insert_resource_contribution(&contribution("c2", "bob", "storage", 50.0, 0)).await.unwrap();
+        store.insert_resource_contribution(&contribution("c3", "alice", "storage", 20.0, 0)).await.unwrap();
+        store.insert_resource_contribution(&contribution("c4", "carol", "storage", 20.0, 0)).await.unwrap();
+        store.insert_resource_contribution(&contribution("c5", "dave", "compute", 99.0, 0)).await.unwrap();
+
+        let contributions = store.list_resource_contributions("storage").await.unwrap();
+        let ranked = rank_contributors(&contributions, 0, None);
+
+        let peers: Vec<_> = ranked.iter().map(|c| c.peer_id.as_str()).collect();
+        assert_eq!(peers, vec!["bob", "alice", "carol"]);
+        assert!((ranked[0].percentage - 50.0).abs() < 1e-9);
+        assert!((ranked[1].percentage - 30.0).abs() < 1e-9);
+        assert!((ranked[2].percentage - 20.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_decay_favours_recent_contributions() {
+        let contributions = vec![
+            contribution("c1", "old", "storage", 100.0, 0),
+            contribution("c2", "new", "storage", 60.0, 2_000),
+        ];
+        // Two half-lives have passed for the old contribution: 100 -> 25
+        let ranked = rank_contributors(&contributions, 2_000, Some(1_000));
+        assert_eq!(ranked[0].peer_id, "new");
+        assert!((ranked[1].contribution - 25.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_available_capacity_counts_latest_reports() {
+        let contributions = vec![
+            contribution("c1", "alice", "storage", 10.0, 1_000),
+            contribution("c2", "alice", "storage", 40.0, 3_000),
+            contribution("c3", "alice", "storage", 25.0, 2_000),
+            contribution("c4", "bob", "storage", 5.0, 500),
+            contribution("c5", "bob", "compute", 2.0, 600),
+        ];
+        // alice's report at 3_000 replaces her earlier ones
+        assert!((available_capacity(&contributions) - 47.0).abs() < 1e-9);
+        assert_eq!(available_capacity(&[]), 0.0);
+    }
+}
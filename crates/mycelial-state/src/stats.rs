@@ -0,0 +1,135 @@
+//! Stats snapshot persistence
+//!
+//! The node periodically samples its statistics so dashboards can chart
+//! peer count and message rate over time. Samples are downsampled to the
+//! interval a client asks for.
+
+use sqlx::Row;
+
+use crate::error::Result;
+use crate::storage::SqliteStore;
+
+/// A single sample of node statistics
+#[derive(Debug, Clone, PartialEq)]
+pub struct StatsSnapshot {
+    /// When the sample was taken (epoch millis)
+    pub timestamp: i64,
+    /// Known peers at the time
+    pub peer_count: usize,
+    /// Total messages received since the node started
+    pub message_count: u64,
+}
+
+/// Reduce snapshots to at most one per `interval_ms` bucket
+///
+/// Input must be in timestamp order. Each bucket keeps its latest snapshot,
+/// since message counts are cumulative and the latest sample is the most
+/// current reading for that bucket.
+pub fn downsample(snapshots: &[StatsSnapshot], interval_ms: i64) -> Vec<StatsSnapshot> {
+    let interval_ms = interval_ms.max(1);
+    let mut points: Vec<StatsSnapshot> = Vec::new();
+    for snapshot in snapshots {
+        let bucket = snapshot.timestamp.div_euclid(interval_ms);
+        match points.last_mut() {
+            Some(last) if last.timestamp.div_euclid(interval_ms) == bucket => {
+                *last = snapshot.clone();
+            }
+            _ => points.push(snapshot.clone()),
+        }
+    }
+    points
+}
+
+impl SqliteStore {
+    // ========== Stats Snapshot Operations ==========
+
+    /// Store a stats sample
+    pub async fn record_stats_snapshot(&self, snapshot: &StatsSnapshot) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO stats_snapshots (timestamp, peer_count, message_count)
+            VALUES (?, ?, ?)
+            ON CONFLICT(timestamp) DO UPDATE SET
+                peer_count = excluded.peer_count,
+                message_count = excluded.message_count
+            "#,
+        )
+        .bind(snapshot.timestamp)
+        .bind(snapshot.peer_count as i64)
+        .bind(snapshot.message_count as i64)
+        .execute(self.pool())
+        .await?;
+        Ok(())
+    }
+
+    /// List samples taken at or after `since`, oldest first
+    pub async fn list_stats_snapshots(&self, since: i64) -> Result<Vec<StatsSnapshot>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT timestamp, peer_count, message_count
+            FROM stats_snapshots WHERE timestamp >= ?
+            ORDER BY timestamp ASC
+            "#,
+        )
+        .bind(since)
+        .fetch_all(self.pool())
+        .await?;
+
+        Ok(rows
+            .iter()
+            .map(|row| StatsSnapshot {
+                timestamp: row.get("timestamp"),
+                peer_count: row.get::<i64, _>("peer_count").max(0) as usize,
+                message_count: row.get::<i64, _>("message_count").max(0) as u64,
+            })
+            .collect())
+    }
+
+    /// Delete samples older than `before`, returning how many were removed
+    pub async fn prune_stats_snapshots(&self, before: i64) -> Result<u64> {
+        let result = sqlx::query("DELETE FROM stats_snapshots WHERE timestamp < ?")
+            .bind(before)
+            .execute(self.pool())
+            .await?;
+        Ok(result.rows_affected())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(timestamp: i64, message_count: u64) -> StatsSnapshot {
+        StatsSnapshot {
+            timestamp,
+            peer_count: 3,
+            message_count,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_snapshots_downsample_to_interval() {
+        let store = SqliteStore::new(":memory:").await.unwrap();
+
+        // One sample every 10s for 2 minutes
+        for i in 0..12 {
+            store.record_stats_snapshot(&snapshot(i * 10_000, i as u64)).await.unwrap();
+        }
+
+        let snapshots = store.list_stats_snapshots(0).await.unwrap();
+        assert_eq!(snapshots.len(), 12);
+
+        // 30s buckets: [0,30), [30,60), [60,90), [90,120)
+        let points = downsample(&snapshots, 30_000);
+        assert_eq!(points.len(), 4);
+        // Each bucket keeps its latest sample
+        let counts: Vec<_> = points.iter().map(|p| p.message_count).collect();
+        assert_eq!(counts, vec![2, 5, 8, 11]);
+
+        // An interval finer than the sampling rate keeps every sample
+        assert_eq!(downsample(&snapshots, 1_000).len(), 12);
+
+        assert_eq!(store.prune_stats_snapshots(60_000).await.unwrap(), 6);
+        assert_eq!(store.list_stats_snapshots(0).await.unwrap().len(), 6);
+    }
+}
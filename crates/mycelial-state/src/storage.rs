@@ -64,10 +64,106 @@ impl SqliteStore {
             .await
             .map_err(|e| StateError::Migration(e.to_string()))?;
 
+        // Governance proposals and votes
+        sqlx::query(include_str!("../migrations/002_governance.sql"))
+            .execute(&self.pool)
+            .await
+            .map_err(|e| StateError::Migration(e.to_string()))?;
+
+        // Dashboard event log
+        sqlx::query(include_str!("../migrations/003_event_log.sql"))
+            .execute(&self.pool)
+            .await
+            .map_err(|e| StateError::Migration(e.to_string()))?;
+
+        // Payment requests
+        sqlx::query(include_str!("../migrations/004_payment_requests.sql"))
+            .execute(&self.pool)
+            .await
+            .map_err(|e| StateError::Migration(e.to_string()))?;
+
+        // Stats snapshots
+        sqlx::query(include_str!("../migrations/005_stats_snapshots.sql"))
+            .execute(&self.pool)
+            .await
+            .map_err(|e| StateError::Migration(e.to_string()))?;
+
+        // Resource contributions
+        sqlx::query(include_str!("../migrations/006_resource_contributions.sql"))
+            .execute(&self.pool)
+            .await
+            .map_err(|e| StateError::Migration(e.to_string()))?;
+
+        // Vouches
+        sqlx::query(include_str!("../migrations/007_vouches.sql"))
+            .execute(&self.pool)
+            .await
+            .map_err(|e| StateError::Migration(e.to_string()))?;
+
+        // Seen markers for unread counts
+        sqlx::query(include_str!("../migrations/008_seen_markers.sql"))
+            .execute(&self.pool)
+            .await
+            .map_err(|e| StateError::Migration(e.to_string()))?;
+
+        // Chat reactions
+        sqlx::query(include_str!("../migrations/009_chat_reactions.sql"))
+            .execute(&self.pool)
+            .await
+            .map_err(|e| StateError::Migration(e.to_string()))?;
+
+        // Chat read receipts
+        sqlx::query(include_str!("../migrations/010_chat_reads.sql"))
+            .execute(&self.pool)
+            .await
+            .map_err(|e| StateError::Migration(e.to_string()))?;
+
+        // Proposal amendment version
+        self.ensure_column("proposals", "version", "INTEGER NOT NULL DEFAULT 1")
+            .await?;
+
+        // Proposal quorum semantics
+        self.ensure_column("proposals", "quorum_fraction", "REAL NOT NULL DEFAULT 0.5")
+            .await?;
+        self.ensure_column("proposals", "quorum_mode", "TEXT NOT NULL DEFAULT 'snapshot'")
+            .await?;
+
+        // Proposal passing rule
+        self.ensure_column("proposals", "passing_rule", "TEXT NOT NULL DEFAULT 'simple_majority'")
+            .await?;
+
+        // Typed proposal parameters, as a JSON object of strings
+        self.ensure_column("proposals", "parameters", "TEXT NOT NULL DEFAULT '{}'")
+            .await?;
+
+        // Whether this node created the proposal itself
+        self.ensure_column("proposals", "local_origin", "INTEGER NOT NULL DEFAULT 0")
+            .await?;
+
         debug!("Migrations completed successfully");
         Ok(())
     }
 
+    /// Add a column to an existing table unless it is already present
+    ///
+    /// SQLite has no `ADD COLUMN IF NOT EXISTS` and migrations run on every
+    /// startup, so column additions check the table schema first.
+    async fn ensure_column(&self, table: &str, column: &str, definition: &str) -> Result<()> {
+        let columns = sqlx::query(&format!("PRAGMA table_info({})", table))
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| StateError::Migration(e.to_string()))?;
+        if columns.iter().any(|row| row.get::<String, _>("name") == column) {
+            return Ok(());
+        }
+
+        sqlx::query(&format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition))
+            .execute(&self.pool)
+            .await
+            .map_err(|e| StateError::Migration(e.to_string()))?;
+        Ok(())
+    }
+
     /// Get a reference to the connection pool
     pub fn pool(&self) -> &SqlitePool {
         &self.pool
@@ -624,20 +720,8 @@ impl SqliteStore {
         let id = Uuid::new_v4().to_string();
         let timestamp = Utc::now().timestamp();
 
-        sqlx::query(
-            r#"
-            INSERT INTO credit_transactions (id, relationship_id, amount, balance_after, description, timestamp)
-            VALUES (?, ?, ?, ?, ?, ?)
-            "#,
-        )
-        .bind(&id)
-        .bind(relationship_id)
-        .bind(amount)
-        .bind(balance_after)
-        .bind(description)
-        .bind(timestamp)
-        .execute(&self.pool)
-        .await?;
+        insert_credit_transaction(&self.pool, &id, relationship_id, amount, balance_after, description, timestamp)
+            .await?;
 
         debug!("Recorded credit transaction: {}", id);
         Ok(())
@@ -745,6 +829,41 @@ impl StateStore for SqliteStore {
     }
 }
 
+/// Insert a credit transaction, unless one is already stored under `id`
+///
+/// Returns whether it was inserted. Takes any executor so callers can make it
+/// part of a transaction.
+pub(crate) async fn insert_credit_transaction<'e, E>(
+    executor: E,
+    id: &str,
+    relationship_id: &str,
+    amount: f64,
+    balance_after: f64,
+    description: Option<&str>,
+    timestamp: i64,
+) -> Result<bool>
+where
+    E: sqlx::Executor<'e, Database = sqlx::Sqlite>,
+{
+    let result = sqlx::query(
+        r#"
+        INSERT INTO credit_transactions (id, relationship_id, amount, balance_after, description, timestamp)
+        VALUES (?, ?, ?, ?, ?, ?)
+        ON CONFLICT(id) DO NOTHING
+        "#,
+    )
+    .bind(id)
+    .bind(relationship_id)
+    .bind(amount)
+    .bind(balance_after)
+    .bind(description)
+    .bind(timestamp)
+    .execute(executor)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -788,6 +907,33 @@ mod tests {
         assert!(store.get_peer("test_peer_123").await.unwrap().is_none());
     }
 
+    #[tokio::test]
+    async fn test_count_peers_tracks_mutations() {
+        let store = create_test_store().await;
+        let peer = |id: &str| PeerInfo {
+            id: PeerId(id.to_string()),
+            public_key: id.to_string(),
+            addresses: vec![],
+            first_seen: Utc::now(),
+            last_seen: Utc::now(),
+            name: None,
+        };
+
+        assert_eq!(store.count_peers().await.unwrap(), 0);
+        for id in ["a", "b", "c"] {
+            store.upsert_peer(&peer(id), None).await.unwrap();
+        }
+        // Upserting an existing peer doesn't add another
+        store.upsert_peer(&peer("b"), None).await.unwrap();
+        assert_eq!(store.count_peers().await.unwrap() as usize, store.list_peers().await.unwrap().len());
+        assert_eq!(store.count_peers().await.unwrap(), 3);
+
+        store.delete_peer("a").await.unwrap();
+        store.delete_peer("missing").await.unwrap();
+        assert_eq!(store.count_peers().await.unwrap() as usize, store.list_peers().await.unwrap().len());
+        assert_eq!(store.count_peers().await.unwrap(), 2);
+    }
+
     #[tokio::test]
     async fn test_message_crud() {
         let store = create_test_store().await;
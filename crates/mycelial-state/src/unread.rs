@@ -0,0 +1,105 @@
+//! Unread counts
+//!
+//! Each identity has a seen marker per category (chat, proposals, vouches).
+//! Items newer than the marker count as unread; marking a category seen
+//! moves its marker forward.
+
+use sqlx::Row;
+use tracing::debug;
+
+use crate::error::Result;
+use crate::storage::SqliteStore;
+
+/// Items newer than an identity's seen markers
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UnreadCounts {
+    /// Chat messages from other peers, sent to everyone or to the identity
+    pub chat: usize,
+    /// Proposals created by other peers
+    pub proposals: usize,
+    /// Vouch requests for the identity still awaiting a response
+    pub vouches: usize,
+}
+
+impl SqliteStore {
+    // ========== Unread Operations ==========
+
+    /// Mark a category read up to `up_to` (epoch millis) for an identity
+    ///
+    /// Markers only move forward, so a stale request can't resurrect items
+    /// already marked seen.
+    pub async fn mark_seen(&self, identity: &str, category: &str, up_to: i64) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO seen_markers (identity, category, up_to) VALUES (?, ?, ?)
+            ON CONFLICT(identity, category) DO UPDATE SET up_to = MAX(up_to, excluded.up_to)
+            "#,
+        )
+        .bind(identity)
+        .bind(category)
+        .bind(up_to)
+        .execute(self.pool())
+        .await?;
+
+        debug!("Marked {} seen up to {} for {}", category, up_to, identity);
+        Ok(())
+    }
+
+    /// Seen marker for a category, or 0 if the identity has never marked it
+    async fn seen_marker(&self, identity: &str, category: &str) -> Result<i64> {
+        let row = sqlx::query("SELECT up_to FROM seen_markers WHERE identity = ? AND category = ?")
+            .bind(identity)
+            .bind(category)
+            .fetch_optional(self.pool())
+            .await?;
+        Ok(row.map(|row| row.get("up_to")).unwrap_or(0))
+    }
+
+    /// Count items newer than the identity's seen markers
+    pub async fn unread_counts(&self, identity: &str) -> Result<UnreadCounts> {
+        let chat_since = self.seen_marker(identity, "chat").await?;
+        let chat: i64 = sqlx::query(
+            r#"
+            SELECT COUNT(*) as count FROM event_log
+            WHERE event_type = 'chat_message' AND timestamp > ?
+              AND json_extract(payload_json, '$.from') != ?
+              AND (json_extract(payload_json, '$.to') IS NULL OR json_extract(payload_json, '$.to') = ?)
+            "#,
+        )
+        .bind(chat_since)
+        .bind(identity)
+        .bind(identity)
+        .fetch_one(self.pool())
+        .await?
+        .get("count");
+
+        let proposals_since = self.seen_marker(identity, "proposals").await?;
+        let proposals: i64 = sqlx::query(
+            "SELECT COUNT(*) as count FROM proposals WHERE created_at > ? AND proposer_peer_id != ?",
+        )
+        .bind(proposals_since)
+        .bind(identity)
+        .fetch_one(self.pool())
+        .await?
+        .get("count");
+
+        let vouches_since = self.seen_marker(identity, "vouches").await?;
+        let vouches: i64 = sqlx::query(
+            r#"
+            SELECT COUNT(*) as count FROM vouches
+            WHERE vouchee_peer_id = ? AND status = 'pending' AND created_at > ?
+            "#,
+        )
+        .bind(identity)
+        .bind(vouches_since)
+        .fetch_one(self.pool())
+        .await?
+        .get("count");
+
+        Ok(UnreadCounts {
+            chat: chat as usize,
+            proposals: proposals as usize,
+            vouches: vouches as usize,
+        })
+    }
+}
@@ -0,0 +1,339 @@
+//! Vouch persistence
+//!
+//! Vouch requests are stored as they are seen and updated when the vouchee
+//! responds. Only accepted vouches count toward trust-network statistics;
+//! unanswered requests are reported separately as pending. A voucher may
+//! later revoke a pending or accepted vouch, after which it no longer counts.
+
+use sqlx::Row;
+use tracing::debug;
+
+use crate::error::Result;
+use crate::storage::SqliteStore;
+
+/// A stored vouch request
+#[derive(Debug, Clone, PartialEq)]
+pub struct VouchRecord {
+    /// Vouch request ID
+    pub id: String,
+    /// Peer vouching
+    pub voucher: String,
+    /// Peer being vouched for
+    pub vouchee: String,
+    /// Weight (stake) of the vouch
+    pub weight: f64,
+    /// Optional message from the voucher
+    pub message: Option<String>,
+    /// pending, accepted, rejected, or revoked
+    pub status: String,
+    /// When the vouch was requested (epoch millis)
+    pub created_at: i64,
+}
+
+/// Aggregate statistics over the vouch network
+#[derive(Debug, Clone, PartialEq)]
+pub struct VouchStats {
+    /// Number of accepted vouches
+    pub total_vouches: usize,
+    /// Mean weight of accepted vouches
+    pub avg_weight: f64,
+    /// Peers with the highest inbound accepted vouch weight, descending
+    pub most_vouched_peers: Vec<(String, f64)>,
+    /// Vouch requests still awaiting a response
+    pub pending_count: usize,
+}
+
+impl SqliteStore {
+    // ========== Vouch Operations ==========
+
+    /// Store a vouch request, ignoring duplicates
+    pub async fn insert_vouch(&self, vouch: &VouchRecord) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO vouches (id, voucher_peer_id, vouchee_peer_id, weight, message, status, created_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?)
+            ON CONFLICT(id) DO NOTHING
+            "#,
+        )
+        .bind(&vouch.id)
+        .bind(&vouch.voucher)
+        .bind(&vouch.vouchee)
+        .bind(vouch.weight)
+        .bind(&vouch.message)
+        .bind(&vouch.status)
+        .bind(vouch.created_at)
+        .execute(self.pool())
+        .await?;
+
+        debug!("Stored vouch {} from {} for {}", vouch.id, vouch.voucher, vouch.vouchee);
+        Ok(())
+    }
+
+    /// Record the vouchee's response to a pending vouch
+    ///
+    /// Returns `false` if the vouch is unknown or was already answered.
+    pub async fn respond_vouch(&self, id: &str, accepted: bool, responded_at: i64) -> Result<bool> {
+        let result = sqlx::query(
+            "UPDATE vouches SET status = ?, responded_at = ? WHERE id = ? AND status = 'pending'",
+        )
+        .bind(if accepted { "accepted" } else { "rejected" })
+        .bind(responded_at)
+        .bind(id)
+        .execute(self.pool())
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// List `voucher`'s pending and accepted vouches for `vouchee`, oldest first
+    pub async fn active_vouches(&self, voucher: &str, vouchee: &str) -> Result<Vec<VouchRecord>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT id, voucher_peer_id, vouchee_peer_id, weight, message, status, created_at
+            FROM vouches
+            WHERE voucher_peer_id = ? AND vouchee_peer_id = ? AND status IN ('pending', 'accepted')
+            ORDER BY created_at ASC, id ASC
+            "#,
+        )
+        .bind(voucher)
+        .bind(vouchee)
+        .fetch_all(self.pool())
+        .await?;
+
+        Ok(rows.iter().map(row_to_vouch).collect())
+    }
+
+    /// Revoke `voucher`'s pending and accepted vouches for `vouchee`
+    ///
+    /// Returns the revoked vouches with the status they had before, so
+    /// callers can undo what the accepted ones contributed.
+    pub async fn revoke_vouches(&self, voucher: &str, vouchee: &str, revoked_at: i64) -> Result<Vec<VouchRecord>> {
+        let mut tx = self.pool().begin().await?;
+
+        let rows = sqlx::query(
+            r#"
+            SELECT id, voucher_peer_id, vouchee_peer_id, weight, message, status, created_at
+            FROM vouches
+            WHERE voucher_peer_id = ? AND vouchee_peer_id = ? AND status IN ('pending', 'accepted')
+            ORDER BY created_at ASC, id ASC
+            "#,
+        )
+        .bind(voucher)
+        .bind(vouchee)
+        .fetch_all(&mut *tx)
+        .await?;
+
+        sqlx::query(
+            r#"
+            UPDATE vouches SET status = 'revoked', responded_at = ?
+            WHERE voucher_peer_id = ? AND vouchee_peer_id = ? AND status IN ('pending', 'accepted')
+            "#,
+        )
+        .bind(revoked_at)
+        .bind(voucher)
+        .bind(vouchee)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        debug!("Revoked {} vouches from {} for {}", rows.len(), voucher, vouchee);
+        Ok(rows.iter().map(row_to_vouch).collect())
+    }
+
+    /// Get a vouch request by ID
+    pub async fn get_vouch(&self, id: &str) -> Result<Option<VouchRecord>> {
+        let row = sqlx::query(
+            r#"
+            SELECT id, voucher_peer_id, vouchee_peer_id, weight, message, status, created_at
+            FROM vouches WHERE id = ?
+            "#,
+        )
+        .bind(id)
+        .fetch_optional(self.pool())
+        .await?;
+
+        Ok(row.as_ref().map(row_to_vouch))
+    }
+
+    /// List vouch requests awaiting `vouchee`'s response, newest first
+    pub async fn list_pending_vouches_for(&self, vouchee: &str) -> Result<Vec<VouchRecord>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT id, voucher_peer_id, vouchee_peer_id, weight, message, status, created_at
+            FROM vouches WHERE vouchee_peer_id = ? AND status = 'pending'
+            ORDER BY created_at DESC, id ASC
+            "#,
+        )
+        .bind(vouchee)
+        .fetch_all(self.pool())
+        .await?;
+
+        Ok(rows.iter().map(row_to_vouch).collect())
+    }
+
+    /// List vouches a peer gave or received, newest first
+    pub async fn list_peer_vouches(&self, peer_id: &str) -> Result<Vec<VouchRecord>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT id, voucher_peer_id, vouchee_peer_id, weight, message, status, created_at
+            FROM vouches WHERE voucher_peer_id = ? OR vouchee_peer_id = ?
+            ORDER BY created_at DESC, id ASC
+            "#,
+        )
+        .bind(peer_id)
+        .bind(peer_id)
+        .fetch_all(self.pool())
+        .await?;
+
+        Ok(rows.iter().map(row_to_vouch).collect())
+    }
+
+    /// Aggregate vouch statistics, listing at most `top_limit` most-vouched peers
+    pub async fn vouch_stats(&self, top_limit: usize) -> Result<VouchStats> {
+        let row = sqlx::query(
+            r#"
+            SELECT COUNT(*) as count, COALESCE(AVG(weight), 0.0) as avg_weight
+            FROM vouches WHERE status = 'accepted'
+            "#,
+        )
+        .fetch_one(self.pool())
+        .await?;
+        let total: i64 = row.get("count");
+        let avg_weight: f64 = row.get("avg_weight");
+
+        let top_rows = sqlx::query(
+            r#"
+            SELECT vouchee_peer_id, SUM(weight) as total_weight
+            FROM vouches WHERE status = 'accepted'
+            GROUP BY vouchee_peer_id
+            ORDER BY total_weight DESC, vouchee_peer_id ASC
+            LIMIT ?
+            "#,
+        )
+        .bind(top_limit as i64)
+        .fetch_all(self.pool())
+        .await?;
+
+        let pending: i64 = sqlx::query("SELECT COUNT(*) as count FROM vouches WHERE status = 'pending'")
+            .fetch_one(self.pool())
+            .await?
+            .get("count");
+
+        Ok(VouchStats {
+            total_vouches: total as usize,
+            avg_weight,
+            most_vouched_peers: top_rows
+                .iter()
+                .map(|row| (row.get("vouchee_peer_id"), row.get("total_weight")))
+                .collect(),
+            pending_count: pending as usize,
+        })
+    }
+}
+
+fn row_to_vouch(row: &sqlx::sqlite::SqliteRow) -> VouchRecord {
+    VouchRecord {
+        id: row.get("id"),
+        voucher: row.get("voucher_peer_id"),
+        vouchee: row.get("vouchee_peer_id"),
+        weight: row.get("weight"),
+        message: row.get("message"),
+        status: row.get("status"),
+        created_at: row.get("created_at"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vouch(id: &str, voucher: &str, vouchee: &str, weight: f64) -> VouchRecord {
+        VouchRecord {
+            id: id.to_string(),
+            voucher: voucher.to_string(),
+            vouchee: vouchee.to_string(),
+            weight,
+            message: None,
+            status: "pending".to_string(),
+            created_at: 0,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_vouch_stats() {
+        let store = SqliteStore::new(":memory:").await.unwrap();
+        for v in [
+            vouch("v1", "alice", "bob", 0.2),
+            vouch("v2", "carol", "bob", 0.4),
+            vouch("v3", "alice", "carol", 0.9),
+            vouch("v4", "bob", "dave", 0.3),
+            vouch("v5", "carol", "dave", 0.5),
+        ] {
+            store.insert_vouch(&v).await.unwrap();
+        }
+        for id in ["v1", "v2", "v3"] {
+            assert!(store.respond_vouch(id, true, 10).await.unwrap());
+        }
+        assert!(store.respond_vouch("v4", false, 10).await.unwrap());
+        // Already answered
+        assert!(!store.respond_vouch("v1", false, 20).await.unwrap());
+        assert_eq!(store.get_vouch("v1").await.unwrap().unwrap().status, "accepted");
+        assert_eq!(store.get_vouch("v4").await.unwrap().unwrap().status, "rejected");
+        assert!(store.get_vouch("missing").await.unwrap().is_none());
+
+        let stats = store.vouch_stats(10).await.unwrap();
+        assert_eq!(stats.total_vouches, 3);
+        assert!((stats.avg_weight - 0.5).abs() < 1e-9);
+        assert_eq!(stats.pending_count, 1);
+
+        let top: Vec<_> = stats.most_vouched_peers.iter().map(|(peer, _)| peer.as_str()).collect();
+        assert_eq!(top, vec!["carol", "bob"]);
+        assert!((stats.most_vouched_peers[1].1 - 0.6).abs() < 1e-9);
+
+        let capped = store.vouch_stats(1).await.unwrap();
+        assert_eq!(capped.most_vouched_peers.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_revoke_vouches() {
+        let store = SqliteStore::new(":memory:").await.unwrap();
+        for v in [
+            vouch("v1", "alice", "bob", 0.2),
+            vouch("v2", "alice", "bob", 0.4),
+            vouch("v3", "alice", "bob", 0.6),
+            vouch("v4", "carol", "bob", 0.5),
+        ] {
+            store.insert_vouch(&v).await.unwrap();
+        }
+        assert!(store.respond_vouch("v1", true, 10).await.unwrap());
+        assert!(store.respond_vouch("v3", false, 10).await.unwrap());
+        assert_eq!(store.active_vouches("alice", "bob").await.unwrap().len(), 2);
+
+        // Rejected vouches and other vouchers' vouches are untouched
+        let revoked = store.revoke_vouches("alice", "bob", 20).await.unwrap();
+        let statuses: Vec<_> = revoked.iter().map(|v| (v.id.as_str(), v.status.as_str())).collect();
+        assert_eq!(statuses, vec![("v1", "accepted"), ("v2", "pending")]);
+        assert_eq!(store.get_vouch("v1").await.unwrap().unwrap().status, "revoked");
+        assert_eq!(store.get_vouch("v3").await.unwrap().unwrap().status, "rejected");
+        assert_eq!(store.get_vouch("v4").await.unwrap().unwrap().status, "pending");
+
+        // Nothing is left to revoke, and revoked vouches can't be accepted
+        assert!(store.active_vouches("alice", "bob").await.unwrap().is_empty());
+        assert!(store.revoke_vouches("alice", "bob", 30).await.unwrap().is_empty());
+        assert!(!store.respond_vouch("v2", true, 40).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_list_peer_vouches_both_directions() {
+        let store = SqliteStore::new(":memory:").await.unwrap();
+        let mut given = vouch("v1", "bob", "carol", 0.3);
+        given.created_at = 5;
+        store.insert_vouch(&vouch("v0", "alice", "bob", 0.2)).await.unwrap();
+        store.insert_vouch(&given).await.unwrap();
+        store.insert_vouch(&vouch("v2", "alice", "carol", 0.9)).await.unwrap();
+
+        let ids: Vec<_> = store.list_peer_vouches("bob").await.unwrap().into_iter().map(|v| v.id).collect();
+        assert_eq!(ids, vec!["v1", "v0"]);
+        assert!(store.list_peer_vouches("nobody").await.unwrap().is_empty());
+    }
+}
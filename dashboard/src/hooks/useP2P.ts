@@ -18,6 +18,8 @@ import type {
 
 interface UseP2POptions {
   url?: string;
+  /** Token the node requires to open a WebSocket (its --ws-token) */
+  token?: string;
   reconnectInterval?: number;
   maxReconnectAttempts?: number;
   apiUrl?: string;
@@ -105,6 +107,7 @@ function updateConversation(
 // Note: Orchestrator is separate at port 9090, handled by useOrchestrator hook
 const ENV_WS_URL = import.meta.env.VITE_P2P_WS_URL || 'ws://localhost:8080/ws';
 const ENV_API_URL = import.meta.env.VITE_P2P_API_URL || 'http://localhost:8080';
+const ENV_WS_TOKEN = import.meta.env.VITE_P2P_WS_TOKEN;
 
 // Normalize peer data from different backend formats
 function normalizePeer(peer: unknown): NormalizedPeer {
@@ -128,6 +131,7 @@ function normalizePeer(peer: unknown): NormalizedPeer {
 export function useP2P(options: UseP2POptions = {}) {
   // Extract options with defaults
   const wsUrl = options.url ?? ENV_WS_URL;
+  const wsToken = options.token ?? ENV_WS_TOKEN;
   const apiUrl = options.apiUrl ?? ENV_API_URL;
   const reconnectInterval = options.reconnectInterval ?? 3000;
   const maxReconnectAttempts = options.maxReconnectAttempts ?? 5;
@@ -141,12 +145,14 @@ export function useP2P(options: UseP2POptions = {}) {
 
   // Store options in refs to avoid dependency cycles
   const wsUrlRef = useRef(wsUrl);
+  const wsTokenRef = useRef(wsToken);
   const apiUrlRef = useRef(apiUrl);
   const reconnectIntervalRef = useRef(reconnectInterval);
   const maxReconnectAttemptsRef = useRef(maxReconnectAttempts);
 
   // Update refs when options change
   wsUrlRef.current = wsUrl;
+  wsTokenRef.current = wsToken;
   apiUrlRef.current = apiUrl;
   reconnectIntervalRef.current = reconnectInterval;
   maxReconnectAttemptsRef.current = maxReconnectAttempts;
@@ -456,7 +462,9 @@ export function useP2P(options: UseP2POptions = {}) {
     console.log(`Connecting to P2P WebSocket: ${currentUrl} (attempt ${reconnectAttemptsRef.current + 1}/${maxReconnectAttemptsRef.current})`);
 
     try {
-      const ws = new WebSocket(currentUrl);
+      // The token rides in the subprotocol list so it stays out of URLs and logs
+      const token = wsTokenRef.current;
+      const ws = token ? new WebSocket(currentUrl, [`bearer.${token}`]) : new WebSocket(currentUrl);
 
       ws.onopen = () => {
         if (!isMountedRef.current) {
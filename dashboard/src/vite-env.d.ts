@@ -3,6 +3,7 @@
 interface ImportMetaEnv {
   readonly VITE_WS_URL?: string;
   readonly VITE_API_URL?: string;
+  readonly VITE_P2P_WS_TOKEN?: string;
 }
 
 interface ImportMeta {
@@ -1,19 +1,23 @@
 # Delete all databases
 rm -f *.db bootstrap.db alice.db bob.db mycelial.db
 
+# Token the dashboard presents to open a WebSocket (export it in each terminal)
+export MYCELIAL_WS_TOKEN="${MYCELIAL_WS_TOKEN:-$(openssl rand -hex 16)}"
+
 # Terminal 1: Bootstrap
 cargo run --release --bin mycelial-node -- \
-  --bootstrap --name "Bootstrap" --port 9000 --http-port 8080 --db bootstrap.db
+  --bootstrap --name "Bootstrap" --port 9000 --http-port 8080 --db bootstrap.db \
+  --ws-token "$MYCELIAL_WS_TOKEN"
 
 # Terminal 2: Alice  
 cargo run --release --bin mycelial-node -- \
   --name "Alice" --port 9001 --http-port 8081 --db alice.db \
-  --connect "/ip4/127.0.0.1/tcp/9000"
+  --connect "/ip4/127.0.0.1/tcp/9000" --ws-token "$MYCELIAL_WS_TOKEN"
 
 # Terminal 3: Bob
 cargo run --release --bin mycelial-node -- \
   --name "Bob" --port 9002 --http-port 8082 --db bob.db \
-  --connect "/ip4/127.0.0.1/tcp/9000"
+  --connect "/ip4/127.0.0.1/tcp/9000" --ws-token "$MYCELIAL_WS_TOKEN"
 
 # START 
-npx pnpm dev -- --host
+VITE_P2P_WS_TOKEN="$MYCELIAL_WS_TOKEN" npx pnpm dev -- --host
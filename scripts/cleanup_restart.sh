@@ -25,6 +25,9 @@ BOOTSTRAP_PORT=9000
 HTTP_PORT=8080
 BOOTSTRAP_ADDR="/ip4/127.0.0.1/tcp/${BOOTSTRAP_PORT}"
 
+# Token dashboard clients present to open a WebSocket
+WS_TOKEN="${MYCELIAL_WS_TOKEN:-$(openssl rand -hex 16)}"
+
 # Node names for the network
 PEER_NAMES=("Alice" "Bob" "Carol" "Dave")
 
@@ -148,6 +151,7 @@ start_bootstrap() {
         --name "Bootstrap" \
         --port "$BOOTSTRAP_PORT" \
         --http-port "$HTTP_PORT" \
+        --ws-token "$WS_TOKEN" \
         > /tmp/mycelial-bootstrap.log 2>&1 &
 
     local bootstrap_pid=$!
@@ -181,6 +185,7 @@ start_peers() {
         cargo run --release --bin mycelial-node -- \
             --name "$name" \
             --connect "$BOOTSTRAP_ADDR" \
+            --ws-token "$WS_TOKEN" \
             > "/tmp/mycelial-${name,,}.log" 2>&1 &
 
         local peer_pid=$!
@@ -213,6 +218,7 @@ show_status() {
     echo ""
     log_info "Dashboard connection:"
     echo "  WebSocket: ws://localhost:${HTTP_PORT}/ws"
+    echo "  Token: ${WS_TOKEN}"
     echo "  API: http://localhost:${HTTP_PORT}/api"
 
     echo ""
@@ -224,7 +230,7 @@ show_status() {
 
     echo ""
     log_info "To start dashboard:"
-    echo "  cd dashboard && npm run dev"
+    echo "  cd dashboard && VITE_P2P_WS_TOKEN=${WS_TOKEN} npm run dev"
 
     echo ""
     log_info "To stop all nodes:"